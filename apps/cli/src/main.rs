@@ -1,9 +1,14 @@
 use std::fs;
+use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use anyhow::{anyhow, bail, Context, Result};
 use clap::{Args, Parser, Subcommand, ValueEnum};
-use rustnotepad_core::{Document, Encoding, LegacyEncoding, LineEnding};
+use rustnotepad_core::{
+    Document, Encoding, FileFilter, FindEvent, FindInFiles, LargeFileOptions, LineEnding,
+    LineEndingState, ReplaceJournal, WorkspaceIndex,
+};
 use rustnotepad_plugin_admin as plugin_admin;
 use rustnotepad_plugin_admin::{
     InstallOptions as PluginInstallOptions, InstallOutcome as PluginInstallOutcome,
@@ -11,11 +16,10 @@ use rustnotepad_plugin_admin::{
 use rustnotepad_plugin_wasm::MANIFEST_FILE as WASM_MANIFEST_FILE;
 #[cfg(target_os = "windows")]
 use rustnotepad_plugin_winabi::LoadedPlugin;
-use rustnotepad_search::{
-    FileSearchResult, ReplaceAllOutcome, SearchEngine, SearchMode, SearchOptions, SearchReport,
-};
+use rustnotepad_project::{SavedSearch, SavedSearchMode, SearchHistoryStore};
+use rustnotepad_search::{FileSearchInput, ReplacePlan, SearchMatch, SearchOptions, SearchReport};
 use rustnotepad_settings::{PreferencesStore, ThemeDefinition, ThemeManager};
-use serde_json::Value as SerdeValue;
+use serde_json::{json, Value as SerdeValue};
 use walkdir::WalkDir;
 
 #[derive(Parser)]
@@ -38,7 +42,13 @@ enum Commands {
     /// 在不同編碼與行尾間轉換文字檔。 / Convert text files between encodings and line endings.
     Convert(ConvertArgs),
     /// 搜尋與選用的取代指令。 / Search (and optional replace) across files.
-    Search(SearchArgs),
+    Search(PatternSearchArgs),
+    /// 管理與執行已儲存的搜尋預設。 / List, run or remove saved search presets.
+    #[command(subcommand)]
+    SearchPresets(SearchPresetsCommand),
+    /// 檢視或還原多檔取代日誌。 / List or undo journaled multi-file replacements.
+    #[command(subcommand)]
+    ReplaceHistory(ReplaceHistoryCommand),
     /// 管理 RustNotePad 外掛（安裝/移除）。 / Manage RustNotePad plugins (install/remove).
     #[command(subcommand)]
     Plugin(PluginCommand),
@@ -59,13 +69,13 @@ struct ConvertArgs {
     #[arg(required = true)]
     inputs: Vec<PathBuf>,
 
-    /// 預期的輸入編碼；若略過則採自動偵測。 / Expected encoding of the input files; detection is used when omitted.
+    /// 預期的輸入編碼；傳統編碼會直接以此解碼，略過時採自動偵測。 / Expected encoding of the input files; legacy encodings are decoded as given, detection is used when omitted.
     #[arg(long)]
     from: Option<EncodingChoice>,
 
     /// 輸出的目標編碼。 / Target encoding for the output.
-    #[arg(long, value_name = "ENCODING")]
-    to: EncodingChoice,
+    #[arg(long, value_name = "ENCODING", required_unless_present = "check_eol")]
+    to: Option<EncodingChoice>,
 
     /// 輸出的目標行尾類型。 / Target line ending for the output.
     #[arg(long, value_name = "LINE_ENDING")]
@@ -86,24 +96,39 @@ struct ConvertArgs {
     /// 批次轉換時的輸出資料夾。 / Output directory for batch conversions.
     #[arg(long, conflicts_with = "in_place")]
     output_dir: Option<PathBuf>,
+
+    /// 僅回報混用行尾的檔案而不改寫；資料夾會遞迴掃描，發現混用時以非零碼結束。 / Only report files that mix line endings without rewriting them; directories are scanned recursively and the exit code is non-zero when any are found.
+    #[arg(
+        long,
+        conflicts_with_all = ["to", "from", "line_ending", "bom", "in_place", "output", "output_dir"]
+    )]
+    check_eol: bool,
 }
 
-#[derive(Clone, Copy, Debug, ValueEnum)]
-enum EncodingChoice {
-    #[value(alias = "utf-8")]
-    Utf8,
-    #[value(name = "utf16-le", aliases = ["utf16le", "utf16_le"])]
-    Utf16Le,
-    #[value(name = "utf16-be", aliases = ["utf16be", "utf16_be"])]
-    Utf16Be,
-    #[value(name = "windows-1252", aliases = ["cp1252", "windows1252", "latin1"])]
-    Windows1252,
-    #[value(name = "shift-jis", aliases = ["shiftjis", "sjis"])]
-    ShiftJis,
-    #[value(name = "gbk", aliases = ["gb2312"])]
-    Gbk,
-    #[value(name = "big5")]
-    Big5,
+/// 接受任何 WHATWG 編碼標籤（如 `iso-8859-2`、`koi8-r`、`euc-kr`），並保留舊有別名。 / Accepts any WHATWG encoding label (e.g. `iso-8859-2`, `koi8-r`, `euc-kr`) plus the historical CLI aliases.
+#[derive(Clone, Copy, Debug)]
+struct EncodingChoice(Encoding);
+
+impl FromStr for EncodingChoice {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let normalized = value.trim().to_ascii_lowercase();
+        let label = match normalized.as_str() {
+            "utf16-le" | "utf16le" | "utf16_le" => "utf-16le",
+            "utf16-be" | "utf16be" | "utf16_be" => "utf-16be",
+            "windows1252" => "windows-1252",
+            "shiftjis" => "shift_jis",
+            other => other,
+        };
+        Encoding::from_label(label)
+            .map(EncodingChoice)
+            .ok_or_else(|| {
+                format!(
+                    "unknown encoding `{value}` (expected a label such as utf-8, utf16-le, iso-8859-2, koi8-r, euc-kr, windows-1251 or gb18030)"
+                )
+            })
+    }
 }
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
@@ -118,15 +143,7 @@ enum LineEndingChoice {
 
 impl From<EncodingChoice> for Encoding {
     fn from(choice: EncodingChoice) -> Self {
-        match choice {
-            EncodingChoice::Utf8 => Encoding::Utf8,
-            EncodingChoice::Utf16Le => Encoding::Utf16Le,
-            EncodingChoice::Utf16Be => Encoding::Utf16Be,
-            EncodingChoice::Windows1252 => Encoding::Legacy(LegacyEncoding::Windows1252),
-            EncodingChoice::ShiftJis => Encoding::Legacy(LegacyEncoding::ShiftJis),
-            EncodingChoice::Gbk => Encoding::Legacy(LegacyEncoding::Gbk),
-            EncodingChoice::Big5 => Encoding::Legacy(LegacyEncoding::Big5),
-        }
+        choice.0
     }
 }
 
@@ -141,10 +158,17 @@ impl From<LineEndingChoice> for LineEnding {
 }
 
 #[derive(Args)]
-struct SearchArgs {
-    /// 搜尋樣式（文字或 regex） / Pattern to search for (literal or regex).
+struct PatternSearchArgs {
+    /// 搜尋樣式（文字或 regex）。 / Pattern to search for (literal or regex).
     pattern: String,
 
+    #[command(flatten)]
+    args: SearchArgs,
+}
+
+/// `search` 與 `search-presets run` 共用的路徑與旗標。 / Paths and flags shared by `search` and `search-presets run`.
+#[derive(Args)]
+struct SearchArgs {
     /// 指定搜尋路徑（檔案或資料夾）；預設為目前目錄。 / Files or directories to search; defaults to current directory.
     #[arg(value_name = "PATH")]
     paths: Vec<PathBuf>,
@@ -153,25 +177,162 @@ struct SearchArgs {
     #[arg(long)]
     regex: bool,
 
+    /// 解析 \n、\r、\t、\0、\xNN 跳脫字元。 / Interpret \n, \r, \t, \0 and \xNN escapes in pattern and replacement.
+    #[arg(long, conflicts_with = "regex")]
+    extended: bool,
+
+    /// 模糊比對：同一行內依序出現樣式的每個字元。 / Fuzzy match: every pattern character in order within a line.
+    #[arg(long, conflicts_with_all = ["regex", "extended"])]
+    fuzzy: bool,
+
     /// 區分大小寫。 / Case sensitive search.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_case_sensitive")]
     case_sensitive: bool,
 
+    /// 不區分大小寫（覆寫預設）。 / Case insensitive search, overriding a preset.
+    #[arg(long, overrides_with = "case_sensitive")]
+    no_case_sensitive: bool,
+
     /// 限制完整字詞。 / Match whole words only.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_whole_word")]
     whole_word: bool,
 
+    /// 不限制完整字詞（覆寫預設）。 / Match inside words too, overriding a preset.
+    #[arg(long, overrides_with = "whole_word")]
+    no_whole_word: bool,
+
     /// 讓 '.' 匹配換行字元。 / Treat '.' as matching newlines (regex only).
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_dot_matches_newline")]
     dot_matches_newline: bool,
 
+    /// 讓 '.' 不匹配換行字元（覆寫預設）。 / Keep '.' from matching newlines, overriding a preset.
+    #[arg(long, overrides_with = "dot_matches_newline")]
+    no_dot_matches_newline: bool,
+
     /// 以指定文字取代。 / Replacement text to apply.
     #[arg(long, value_name = "TEXT")]
     replace: Option<String>,
 
-    /// 實際覆寫檔案（需搭配 --replace）。 / Persist replacements to disk (requires --replace).
-    #[arg(long, requires = "replace")]
+    /// 將這次搜尋存成具名預設。 / Save this search (pattern, replacement, flags and masks) as a named preset.
+    #[arg(long, value_name = "NAME")]
+    save_preset: Option<String>,
+
+    /// 實際覆寫檔案（需搭配 --replace 或含取代文字的預設）。 / Persist replacements to disk (requires --replace or a preset with a replacement).
+    #[arg(long)]
     apply: bool,
+
+    /// 以 unified diff 顯示取代預覽。 / Preview the replacements as a unified diff.
+    #[arg(long)]
+    diff: bool,
+
+    /// 逐筆確認每個取代。 / Confirm each replacement interactively (answers are read from stdin).
+    #[arg(long)]
+    interactive: bool,
+
+    /// 還原日誌資料夾；預設為 <workspace>/.rustnotepad/replace_journal。 / Rollback journal directory; defaults to <workspace>/.rustnotepad/replace_journal.
+    #[arg(long, value_name = "DIR", requires = "apply")]
+    journal: Option<PathBuf>,
+
+    /// 僅搜尋符合遮罩的檔案（可重複）。 / Only search files matching this glob (repeatable, e.g. '*.rs' or 'src/**/*.toml').
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// 排除符合遮罩的檔案或資料夾（可重複）。 / Skip files or directories matching this glob (repeatable).
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// 包含隱藏檔與資料夾。 / Search hidden files and directories.
+    #[arg(long)]
+    hidden: bool,
+
+    /// 不套用 .gitignore 與 .ignore 規則。 / Do not honour .gitignore and .ignore files.
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// 一併搜尋二進位檔。 / Search binary files instead of skipping them.
+    #[arg(long)]
+    binary: bool,
+
+    /// 略過大於此大小的檔案（可用 K/M/G）。 / Skip files larger than this size (accepts K, M and G suffixes).
+    #[arg(long, value_name = "SIZE", value_parser = parse_file_size)]
+    max_filesize: Option<u64>,
+
+    /// 搜尋執行緒數量；0 代表自動。 / Number of search threads; 0 picks automatically.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    threads: usize,
+
+    /// 以工作區的三字元組索引略過不可能符合的檔案，並重新索引過期的檔案。 / Use the workspace trigram index to skip files that cannot match, re-indexing the stale files it reads.
+    #[arg(long, conflicts_with = "replace")]
+    index: bool,
+
+    /// 輸出格式。 / Output format for the results.
+    #[arg(long, value_enum, default_value_t = SearchOutputFormat::Text)]
+    format: SearchOutputFormat,
+
+    /// 依 grep 慣例結束：有符合為 0、無符合為 1、錯誤為 2。 / Exit like grep: 0 when something matched, 1 when nothing did, 2 on errors.
+    #[arg(long)]
+    exit_code: bool,
+}
+
+#[derive(Subcommand)]
+enum SearchPresetsCommand {
+    /// 列出預設。 / List saved presets.
+    List,
+    /// 執行預設；命令列旗標疊加在預設之上。 / Run a preset; flags on the command line layer on top of it.
+    Run {
+        /// 預設名稱。 / Preset name.
+        name: String,
+        #[command(flatten)]
+        args: SearchArgs,
+    },
+    /// 刪除預設。 / Remove a preset.
+    Remove {
+        /// 預設名稱。 / Preset name.
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReplaceHistoryCommand {
+    /// 列出日誌，最新的在前。 / List journaled replacements, newest first.
+    List(ReplaceHistoryListArgs),
+    /// 還原一次取代（預設為最新一次）。 / Undo a journaled replacement (the newest by default).
+    Undo(ReplaceHistoryUndoArgs),
+}
+
+#[derive(Args)]
+struct ReplaceHistoryListArgs {
+    /// 日誌資料夾。 / Journal directory.
+    #[arg(long, value_name = "DIR")]
+    journal: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ReplaceHistoryUndoArgs {
+    /// 要還原的日誌 ID。 / Journal id to undo.
+    id: Option<String>,
+
+    /// 日誌資料夾。 / Journal directory.
+    #[arg(long, value_name = "DIR")]
+    journal: Option<PathBuf>,
+
+    /// 即使檔案在取代後被修改也還原。 / Restore even if the files changed after the replace.
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum SearchOutputFormat {
+    /// 人類可讀的摘要。 / Human-readable summary.
+    Text,
+    /// 單一 JSON 文件。 / One JSON document for the whole report.
+    Json,
+    /// 每個符合項目一行 JSON。 / One JSON object per match.
+    Jsonl,
+    /// `path:line:column:text`，供 Vim quickfix 使用。 / `path:line:column:text` for Vim's quickfix list.
+    Vimgrep,
+    /// SARIF 2.1.0 記錄。 / SARIF 2.1.0 log.
+    Sarif,
 }
 
 #[derive(Subcommand)]
@@ -292,17 +453,50 @@ struct PreferencesImportArgs {
 }
 
 fn main() {
-    if let Err(err) = run() {
+    let cli = Cli::parse();
+    let error_status = match &cli.command {
+        Commands::Search(PatternSearchArgs { args, .. })
+        | Commands::SearchPresets(SearchPresetsCommand::Run { args, .. })
+            if args.exit_code =>
+        {
+            2
+        }
+        _ => 1,
+    };
+    if let Err(err) = run(cli) {
+        if err.is::<NoMatches>() {
+            std::process::exit(1);
+        }
         eprintln!("Error: {err}");
-        std::process::exit(1);
+        std::process::exit(error_status);
+    }
+}
+
+/// `search --exit-code` 沒有找到任何符合項目；以狀態 1 結束且不輸出訊息。 / `search --exit-code` found nothing; exits with status 1 and no message.
+#[derive(Debug)]
+struct NoMatches;
+
+impl std::fmt::Display for NoMatches {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no matches found")
     }
 }
 
-fn run() -> Result<()> {
-    let Cli { workspace, command } = Cli::parse();
+impl std::error::Error for NoMatches {}
+
+fn run(cli: Cli) -> Result<()> {
+    let Cli { workspace, command } = cli;
     match command {
         Commands::Convert(args) => execute_convert(args),
-        Commands::Search(args) => execute_search(args),
+        Commands::Search(PatternSearchArgs { pattern, args }) => {
+            execute_search(SearchQuery::Pattern(pattern), args, workspace)
+        }
+        Commands::SearchPresets(subcommand) => {
+            execute_search_presets_command(subcommand, workspace)
+        }
+        Commands::ReplaceHistory(subcommand) => {
+            execute_replace_history_command(subcommand, workspace)
+        }
         Commands::Plugin(subcommand) => {
             let workspace_root = resolve_workspace(workspace.clone())?;
             execute_plugin_command(subcommand, &workspace_root)
@@ -323,6 +517,13 @@ fn run() -> Result<()> {
 }
 
 fn execute_convert(args: ConvertArgs) -> Result<()> {
+    if args.check_eol {
+        return check_line_endings(&args.inputs);
+    }
+    let Some(to) = args.to else {
+        bail!("--to is required unless --check-eol is given");
+    };
+
     if args.inputs.len() > 1 {
         if args.output.is_some() {
             bail!("--output can only be used when converting a single file");
@@ -344,7 +545,7 @@ fn execute_convert(args: ConvertArgs) -> Result<()> {
         convert_single(
             input,
             args.from,
-            args.to,
+            to,
             args.line_ending,
             args.bom,
             args.in_place,
@@ -630,6 +831,16 @@ fn import_preferences(args: PreferencesImportArgs, workspace_root: &Path) -> Res
     Ok(())
 }
 
+/// 依工作區偏好設定的巨檔門檻建立選項。 / Builds large-file options from the workspace's `large_file_threshold_mb` preference.
+fn large_file_options(workspace: Option<PathBuf>) -> Result<LargeFileOptions> {
+    let prefs_path = preferences_path(&resolve_workspace(workspace)?);
+    let store = PreferencesStore::load(&prefs_path)
+        .with_context(|| format!("failed to load preferences from {}", prefs_path.display()))?;
+    Ok(LargeFileOptions::with_threshold_mib(
+        store.preferences().editor.large_file_threshold_mb,
+    ))
+}
+
 fn localization_dir(workspace_root: &Path) -> PathBuf {
     workspace_root.join(".rustnotepad").join("langs")
 }
@@ -776,20 +987,28 @@ fn convert_single(
     single_output: Option<&PathBuf>,
     output_dir: Option<&PathBuf>,
 ) -> Result<()> {
-    let mut document =
-        Document::open(input).with_context(|| format!("failed to open {}", input.display()))?;
-
-    if let Some(expected) = from {
-        let expected_encoding: Encoding = expected.into();
-        if document.encoding() != expected_encoding {
-            bail!(
-                "input {} is detected as {} but --from {} was supplied",
-                input.display(),
-                document.encoding().name(),
-                expected_encoding.name()
-            );
+    let mut document = match from.map(Encoding::from) {
+        // 傳統編碼的偵測只是推測（如 iso-8859-2 與 windows-1250），直接以指定編碼解碼。 / Legacy detection is only a guess (e.g. iso-8859-2 vs windows-1250), so decode with the requested encoding.
+        Some(expected @ Encoding::Legacy(_)) => Document::open_with_encoding(input, expected)
+            .with_context(|| {
+                format!("failed to open {} as {}", input.display(), expected.name())
+            })?,
+        expected => {
+            let document = Document::open(input)
+                .with_context(|| format!("failed to open {}", input.display()))?;
+            if let Some(expected_encoding) = expected {
+                if document.encoding() != expected_encoding {
+                    bail!(
+                        "input {} is detected as {} but --from {} was supplied",
+                        input.display(),
+                        document.encoding().name(),
+                        expected_encoding.name()
+                    );
+                }
+            }
+            document
         }
-    }
+    };
 
     let target_encoding: Encoding = to.into();
     document.set_encoding(target_encoding);
@@ -844,14 +1063,79 @@ fn resolve_output_path(
     bail!("missing --output or --output-dir for conversion");
 }
 
-fn execute_search(mut args: SearchArgs) -> Result<()> {
-    let mut options = SearchOptions::new(args.pattern);
+/// 搜尋的來源：命令列樣式或已儲存的預設。 / What to search for: a pattern from the command line or a saved preset.
+enum SearchQuery {
+    Pattern(String),
+    Preset(String),
+}
+
+/// 明確給出的 `--flag` / `--no-flag` 覆寫預設值；都沒給時保留預設。 / An explicit `--flag` / `--no-flag` overrides the preset value; neither keeps it.
+fn override_flag(value: &mut bool, on: bool, off: bool) {
+    if on {
+        *value = true;
+    } else if off {
+        *value = false;
+    }
+}
+
+fn execute_search(
+    query: SearchQuery,
+    mut args: SearchArgs,
+    workspace: Option<PathBuf>,
+) -> Result<()> {
+    let history_store = SearchHistoryStore::new(search_history_path(workspace.clone())?);
+    let mut search = match query {
+        SearchQuery::Preset(name) => history_store
+            .load()?
+            .preset(&name)
+            .cloned()
+            .ok_or_else(|| anyhow!("no search preset named \"{name}\""))?,
+        SearchQuery::Pattern(pattern) => SavedSearch {
+            pattern,
+            ..SavedSearch::default()
+        },
+    };
+    // 命令列旗標疊加在預設之上。 / Command-line flags layer on top of the preset.
     if args.regex {
-        options.mode = SearchMode::Regex;
+        search.mode = SavedSearchMode::Regex;
+    } else if args.extended {
+        search.mode = SavedSearchMode::Extended;
+    } else if args.fuzzy {
+        search.mode = SavedSearchMode::Fuzzy;
     }
-    options.case_sensitive = args.case_sensitive;
-    options.whole_word = args.whole_word;
-    options.dot_matches_newline = args.dot_matches_newline;
+    override_flag(
+        &mut search.case_sensitive,
+        args.case_sensitive,
+        args.no_case_sensitive,
+    );
+    override_flag(&mut search.whole_word, args.whole_word, args.no_whole_word);
+    override_flag(
+        &mut search.dot_matches_newline,
+        args.dot_matches_newline,
+        args.no_dot_matches_newline,
+    );
+    if args.replace.is_some() {
+        search.replacement = args.replace.clone();
+    }
+    search.include.append(&mut args.include);
+    search.exclude.append(&mut args.exclude);
+    if let Some(name) = &args.save_preset {
+        let mut history = history_store.load()?;
+        history.save_preset(name.clone(), search.clone());
+        history_store.save(&history)?;
+        eprintln!("Saved search preset \"{name}\".");
+    }
+
+    args.replace = search.replacement.clone();
+    args.include = search.include.clone();
+    args.exclude = search.exclude.clone();
+    if args.replace.is_none() && (args.apply || args.diff || args.interactive) {
+        bail!("--apply, --diff and --interactive need a replacement; pass --replace");
+    }
+    if args.replace.is_some() && args.index {
+        bail!("--index cannot be combined with a replacement");
+    }
+    let mut options = search.to_options();
     options.wrap_around = false;
 
     if args.paths.is_empty() {
@@ -859,48 +1143,372 @@ fn execute_search(mut args: SearchArgs) -> Result<()> {
         args.paths.push(cwd);
     }
 
-    let targets = collect_target_files(&args.paths)?;
-    if targets.is_empty() {
-        println!("No files to search.");
-        return Ok(());
+    if args.index {
+        // 索引以絕對路徑為鍵。 / The index is keyed by absolute paths.
+        args.paths = args
+            .paths
+            .iter()
+            .map(|path| resolve_input_path(path))
+            .collect::<Result<Vec<_>>>()?;
     }
+    let large_files = large_file_options(workspace.clone())?;
+    let finder = FindInFiles::new(args.paths.iter().cloned())
+        .with_filter(FileFilter {
+            include: args.include.clone(),
+            exclude: args.exclude.clone(),
+            include_hidden: args.hidden,
+            respect_ignore_files: !args.no_ignore,
+            skip_binary: !args.binary,
+            max_file_size: args.max_filesize,
+        })
+        .with_threads(args.threads)
+        .with_large_files(large_files);
+    let mut index = if args.index {
+        let root = resolve_workspace(workspace.clone())?;
+        Some(WorkspaceIndex::open(
+            root.join(".rustnotepad").join("search.index"),
+            vec![root],
+            FileFilter::default(),
+        )?)
+    } else {
+        None
+    };
 
-    let mut entries = Vec::new();
-    let mut applied = Vec::new();
+    let mut plan = None;
+    let report;
+    let searched_any;
+    // 無法讀取的檔案數；`--exit-code` 依此回傳狀態 2。 / Files that could not be read; `--exit-code` turns them into status 2.
+    let mut failures = 0;
+    let mut on_event = |event: FindEvent| {
+        if matches!(event, FindEvent::Failed { .. }) {
+            failures += 1;
+        }
+        report_find_event(event);
+    };
 
-    for path in targets {
-        match handle_file(&path, &options, args.replace.as_deref(), args.apply) {
-            Ok(Some((result, applied_count))) => {
-                if let Some(count) = applied_count {
-                    applied.push((path.clone(), count));
-                }
-                entries.push(result);
+    if let Some(replacement) = args.replace.as_deref() {
+        let targets = finder.collect_files(&mut on_event)?;
+        searched_any = !targets.is_empty();
+        let inputs = targets.into_iter().filter_map(|path| {
+            // 巨檔模式唯讀：取代需要整份內容，因此略過超過門檻的檔案。 / Large-file mode is read-only: replacing needs the whole text, so files above the threshold are skipped.
+            let len = fs::metadata(&path)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            if large_files.is_large(len) {
+                eprintln!(
+                    "warning: {}: skipped; files above the large-file threshold are read-only",
+                    path.display()
+                );
+                failures += 1;
+                return None;
             }
-            Ok(None) => {}
-            Err(err) => {
-                eprintln!("warning: {}: {}", path.display(), err);
+            match Document::open(&path) {
+                Ok(document) => Some(FileSearchInput::new(path, document.contents().to_string())),
+                Err(err) => {
+                    eprintln!("warning: {}: {}", path.display(), err);
+                    failures += 1;
+                    None
+                }
             }
+        });
+        let mut built = ReplacePlan::build(inputs, &options, replacement)?;
+        if args.interactive {
+            review_replacements(&mut built)?;
         }
+        report = built.report().clone();
+        plan = Some(built);
+    } else {
+        let mut entries = Vec::new();
+        let mut collect = |event| match event {
+            FindEvent::Matched(result) => entries.push(result),
+            other => on_event(other),
+        };
+        let stats = match index.as_mut() {
+            Some(index) => {
+                let stats = index.search(finder, &options, &mut collect)?;
+                index.save()?;
+                stats
+            }
+            None => finder.run(&options, &mut collect)?,
+        };
+        searched_any = stats.files_searched > 0;
+        // 平行搜尋完成順序不定，輸出前依路徑排序。 / Parallel results arrive in any order; sort by path for stable output.
+        entries.sort_by(|left, right| left.path.cmp(&right.path));
+        report = SearchReport::new(entries);
     }
 
-    let report = SearchReport::new(entries);
-    if report.is_empty() {
-        println!("No matches found.");
-        return Ok(());
+    if args.format == SearchOutputFormat::Text {
+        if !searched_any {
+            println!("No files to search.");
+        } else if report.is_empty() {
+            println!("No matches found.");
+        } else {
+            match &plan {
+                Some(plan) if args.diff => print!("{}", plan.unified_diff(3)),
+                _ => print_search_report(&report, &options),
+            }
+        }
+    } else {
+        print_machine_report(&report, &options, args.format)?;
     }
 
-    print_search_report(&report, &options);
-
-    if args.replace.is_some() {
+    if let Some(plan) = plan.as_ref().filter(|plan| !plan.is_empty()) {
+        // 機器格式的 stdout 只保留結果。 / Machine formats keep stdout for the results only.
+        let note = |line: String| {
+            if args.format == SearchOutputFormat::Text {
+                println!("{line}");
+            } else {
+                eprintln!("{line}");
+            }
+        };
         if args.apply {
-            for (path, count) in applied {
-                println!("Applied {} replacements to {}", count, path.display());
+            let applied: Vec<(PathBuf, usize)> = plan
+                .rewrites()
+                .iter()
+                .map(|rewrite| (rewrite.path.to_path_buf(), rewrite.replacements))
+                .collect();
+            if applied.is_empty() {
+                note("No replacements selected; nothing was written.".to_string());
+            } else {
+                let journal =
+                    ReplaceJournal::new(replace_journal_root(workspace, args.journal.clone())?);
+                let outcome = journal.apply(plan)?;
+                for skipped in &outcome.skipped {
+                    eprintln!(
+                        "warning: {}: skipped {} replacements; the text cannot be represented in {}",
+                        skipped.path.display(),
+                        skipped.replacements,
+                        skipped.encoding
+                    );
+                }
+                for (path, count) in applied {
+                    if outcome.skipped.iter().any(|skipped| skipped.path == path) {
+                        continue;
+                    }
+                    note(format!(
+                        "Applied {} replacements to {}",
+                        count,
+                        path.display()
+                    ));
+                }
+                match outcome.entry {
+                    Some(entry) => note(format!(
+                        "Journal {}; undo with `rustnotepad-cli replace-history undo {}`.",
+                        entry.id, entry.id
+                    )),
+                    None => note("Every file was skipped; nothing was written.".to_string()),
+                }
             }
         } else {
-            println!("Dry run only; re-run with --apply to write changes.");
+            note("Dry run only; re-run with --apply to write changes.".to_string());
         }
     }
 
+    if args.exit_code {
+        if failures > 0 {
+            bail!("{failures} files could not be searched");
+        }
+        if report.is_empty() {
+            return Err(NoMatches.into());
+        }
+    }
+    Ok(())
+}
+
+/// 逐筆詢問是否取代，從 stdin 讀取答案，提示輸出到 stderr。 / Asks about each candidate on stderr and reads the answers from stdin.
+fn review_replacements(plan: &mut ReplacePlan) -> Result<()> {
+    let positions: Vec<(usize, usize)> = plan
+        .report()
+        .results
+        .iter()
+        .enumerate()
+        .flat_map(|(file, entry)| (0..entry.matches.len()).map(move |index| (file, index)))
+        .collect();
+    let previews: Vec<String> = plan
+        .matches()
+        .map(|(path, hit, replacement)| {
+            format!(
+                "{}:{}:{}\n  - {}\n  + {}",
+                path.display(),
+                hit.line,
+                hit.column,
+                hit.line_text,
+                preview_replacement(hit, replacement)
+            )
+        })
+        .collect();
+
+    let stdin = io::stdin();
+    let mut answers = stdin.lock().lines();
+    let mut remaining = None;
+    for ((file, index), preview) in positions.into_iter().zip(previews) {
+        let accept = match remaining {
+            Some(accept) => accept,
+            None => {
+                eprintln!("{preview}");
+                eprint!("Replace? [y]es, [n]o, [a]ll remaining, [q]uit: ");
+                io::stderr().flush()?;
+                let answer = answers.next().transpose()?.unwrap_or_else(|| "q".into());
+                match answer.trim().to_ascii_lowercase().as_str() {
+                    "y" | "yes" => true,
+                    "a" | "all" => {
+                        remaining = Some(true);
+                        true
+                    }
+                    "q" | "quit" => {
+                        remaining = Some(false);
+                        false
+                    }
+                    _ => false,
+                }
+            }
+        };
+        plan.set_marked(file, index, accept);
+    }
+    Ok(())
+}
+
+/// 以取代後的內容顯示該行；換行以 `\n` 呈現。 / Shows the line with this one match replaced; newlines render as `\n`.
+fn preview_replacement(hit: &SearchMatch, replacement: &str) -> String {
+    let prefix: String = hit
+        .line_text
+        .chars()
+        .take(hit.column.saturating_sub(1))
+        .collect();
+    let suffix: String = if hit.matched.contains('\n') {
+        String::new()
+    } else {
+        hit.line_text
+            .chars()
+            .skip(hit.column.saturating_sub(1) + hit.matched.chars().count())
+            .collect()
+    };
+    format!("{prefix}{}{suffix}", replacement.replace('\n', "\\n"))
+}
+
+fn search_history_path(workspace: Option<PathBuf>) -> Result<PathBuf> {
+    Ok(resolve_workspace(workspace)?
+        .join(".rustnotepad")
+        .join("search_history.json"))
+}
+
+fn execute_search_presets_command(
+    command: SearchPresetsCommand,
+    workspace: Option<PathBuf>,
+) -> Result<()> {
+    let store = SearchHistoryStore::new(search_history_path(workspace.clone())?);
+    match command {
+        SearchPresetsCommand::List => {
+            let history = store.load()?;
+            if history.presets.is_empty() {
+                println!("No saved search presets.");
+            }
+            for preset in &history.presets {
+                let search = &preset.search;
+                let mut line = format!("{}: {}", preset.name, search.pattern);
+                if let Some(replacement) = &search.replacement {
+                    line.push_str(&format!(" -> {replacement}"));
+                }
+                println!("{line}");
+            }
+        }
+        SearchPresetsCommand::Run { name, args } => {
+            return execute_search(SearchQuery::Preset(name), args, workspace);
+        }
+        SearchPresetsCommand::Remove { name } => {
+            let mut history = store.load()?;
+            if !history.remove_preset(&name) {
+                bail!("no search preset named \"{name}\"");
+            }
+            store.save(&history)?;
+            println!("Removed search preset \"{name}\".");
+        }
+    }
+    Ok(())
+}
+
+fn replace_journal_root(workspace: Option<PathBuf>, journal: Option<PathBuf>) -> Result<PathBuf> {
+    match journal {
+        Some(path) => resolve_input_path(&path),
+        None => Ok(resolve_workspace(workspace)?
+            .join(".rustnotepad")
+            .join("replace_journal")),
+    }
+}
+
+fn execute_replace_history_command(
+    command: ReplaceHistoryCommand,
+    workspace: Option<PathBuf>,
+) -> Result<()> {
+    match command {
+        ReplaceHistoryCommand::List(args) => {
+            let journal = ReplaceJournal::new(replace_journal_root(workspace, args.journal)?);
+            let entries = journal.list()?;
+            if entries.is_empty() {
+                println!("No journaled replacements.");
+            }
+            for entry in entries {
+                println!(
+                    "{} ({} replacements in {} files)",
+                    entry.id,
+                    entry.replacements,
+                    entry.files.len()
+                );
+                for path in &entry.files {
+                    println!("  {}", path.display());
+                }
+            }
+        }
+        ReplaceHistoryCommand::Undo(args) => {
+            let journal = ReplaceJournal::new(replace_journal_root(workspace, args.journal)?);
+            let entry = match &args.id {
+                Some(id) => journal
+                    .find(id)?
+                    .ok_or_else(|| anyhow!("no journaled replacement with id {id}"))?,
+                None => journal
+                    .list()?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("no journaled replacements to undo"))?,
+            };
+            let restored = journal.rollback(&entry, args.force)?;
+            println!("Restored {restored} files from journal {}.", entry.id);
+        }
+    }
+    Ok(())
+}
+
+fn check_line_endings(inputs: &[PathBuf]) -> Result<()> {
+    let files = collect_target_files(inputs)?;
+    let mut mixed = 0usize;
+    let mut skipped = 0usize;
+    for path in &files {
+        let document = match Document::open(path) {
+            Ok(document) => document,
+            Err(_) => {
+                // 二進位或無法解碼的檔案不納入檢查。 / Binary or undecodable files are not checked.
+                skipped += 1;
+                continue;
+            }
+        };
+        if document.line_ending_state() == LineEndingState::Mixed {
+            let stats = document.line_ending_stats();
+            println!(
+                "{}: mixed line endings (LF {}, CRLF {}, CR {})",
+                path.display(),
+                stats.lf,
+                stats.crlf,
+                stats.cr
+            );
+            mixed += 1;
+        }
+    }
+
+    let checked = files.len() - skipped;
+    println!("Checked {checked} files ({skipped} skipped); {mixed} with mixed line endings.");
+    if mixed > 0 {
+        bail!("{mixed} file(s) have mixed line endings");
+    }
     Ok(())
 }
 
@@ -929,57 +1537,192 @@ fn collect_target_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
     Ok(files)
 }
 
-fn handle_file(
-    path: &Path,
+fn print_machine_report(
+    report: &SearchReport,
     options: &SearchOptions,
-    replacement: Option<&str>,
-    apply: bool,
-) -> Result<Option<(FileSearchResult, Option<usize>)>> {
-    let mut document =
-        Document::open(path).with_context(|| format!("failed to open {}", path.display()))?;
-
-    if let Some(replacement_text) = replacement {
-        let outcome = {
-            let engine = SearchEngine::new(document.contents());
-            engine.replace_all(replacement_text, options)?
-        };
-
-        if outcome.matches.is_empty() {
-            return Ok(None);
+    format: SearchOutputFormat,
+) -> Result<()> {
+    let hits = report.results.iter().flat_map(|entry| {
+        let path = entry
+            .path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<unsaved>".to_string());
+        entry.matches.iter().map(move |hit| (path.clone(), hit))
+    });
+    match format {
+        SearchOutputFormat::Text => print_search_report(report, options),
+        SearchOutputFormat::Json => {
+            let files: Vec<SerdeValue> = report
+                .results
+                .iter()
+                .map(|entry| {
+                    json!({
+                        "path": entry.path.as_ref().map(|p| p.display().to_string()),
+                        "matches": entry.matches.iter().map(match_json).collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            let document = json!({
+                "pattern": options.pattern,
+                "total_matches": report.total_matches,
+                "files_with_matches": report.results.len(),
+                "files": files,
+            });
+            println!("{}", serde_json::to_string_pretty(&document)?);
+        }
+        SearchOutputFormat::Jsonl => {
+            for (path, hit) in hits {
+                let mut line = match_json(hit);
+                line["path"] = json!(path);
+                println!("{}", serde_json::to_string(&line)?);
+            }
+        }
+        SearchOutputFormat::Vimgrep => {
+            for (path, hit) in hits {
+                println!(
+                    "{}:{}:{}:{}",
+                    path,
+                    hit.line,
+                    byte_column(hit),
+                    hit.line_text
+                );
+            }
         }
+        SearchOutputFormat::Sarif => {
+            let results: Vec<SerdeValue> = hits
+                .map(|(path, hit)| {
+                    let (end_line, end_column) = match_end(hit);
+                    json!({
+                        "ruleId": "search-match",
+                        "level": "warning",
+                        "message": { "text": format!("Found \"{}\"", hit.matched) },
+                        "locations": [{
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": path_to_uri(&path) },
+                                "region": {
+                                    "startLine": hit.line,
+                                    "startColumn": hit.column,
+                                    "endLine": end_line,
+                                    "endColumn": end_column,
+                                    "snippet": { "text": hit.line_text },
+                                },
+                            },
+                        }],
+                    })
+                })
+                .collect();
+            let log = json!({
+                "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+                "version": "2.1.0",
+                "runs": [{
+                    "tool": {
+                        "driver": {
+                            "name": "rustnotepad-cli",
+                            "version": env!("CARGO_PKG_VERSION"),
+                            "rules": [{
+                                "id": "search-match",
+                                "shortDescription": { "text": format!("Matches of \"{}\"", options.pattern) },
+                            }],
+                        },
+                    },
+                    "columnKind": "unicodeCodePoints",
+                    "results": results,
+                }],
+            });
+            println!("{}", serde_json::to_string_pretty(&log)?);
+        }
+    }
+    Ok(())
+}
 
-        let ReplaceAllOutcome {
-            replaced_text,
-            replacements,
-            matches,
-        } = outcome;
+/// 位元組範圍以解碼後、行尾正規化為 `\n` 的文字為準。 / Byte ranges refer to the decoded text with newlines normalised to `\n`.
+fn match_json(hit: &SearchMatch) -> SerdeValue {
+    json!({
+        "line": hit.line,
+        "column": hit.column,
+        "byte_start": hit.start,
+        "byte_end": hit.end,
+        "matched": hit.matched,
+        "line_text": hit.line_text,
+    })
+}
 
-        if apply {
-            document.set_contents(replaced_text);
-            document
-                .save()
-                .with_context(|| format!("failed to write {}", path.display()))?;
-        }
+/// Vim 的欄位以位元組計算。 / Vim counts columns in bytes.
+fn byte_column(hit: &SearchMatch) -> usize {
+    let prefix: usize = hit
+        .line_text
+        .chars()
+        .take(hit.column.saturating_sub(1))
+        .map(char::len_utf8)
+        .sum();
+    prefix + 1
+}
 
-        return Ok(Some((
-            FileSearchResult::new(Some(path.to_path_buf()), matches),
-            if apply { Some(replacements) } else { None },
-        )));
+/// 回傳符合項目結束處（不含）的行與欄。 / Returns the exclusive end line and column of a match.
+fn match_end(hit: &SearchMatch) -> (usize, usize) {
+    match hit.matched.rsplit_once('\n') {
+        Some((head, tail)) => (
+            hit.line + head.matches('\n').count() + 1,
+            tail.chars().count() + 1,
+        ),
+        None => (hit.line, hit.column + hit.matched.chars().count()),
     }
+}
 
-    let matches = {
-        let engine = SearchEngine::new(document.contents());
-        engine.find_all(options)?
-    };
+fn path_to_uri(path: &str) -> String {
+    let normalized = path.replace('\\', "/");
+    let mut encoded = String::with_capacity(normalized.len());
+    for byte in normalized.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    if Path::new(path).is_absolute() {
+        let separator = if encoded.starts_with('/') { "" } else { "/" };
+        format!("file://{separator}{encoded}")
+    } else {
+        encoded
+    }
+}
 
-    if matches.is_empty() {
-        return Ok(None);
+/// 將走訪錯誤輸出到 stderr；略過的檔案不另行提示。 / Prints walk failures to stderr; skipped files stay silent.
+fn report_find_event(event: FindEvent) {
+    match event {
+        FindEvent::Failed {
+            path: Some(path),
+            message,
+        } => eprintln!("warning: {}: {}", path.display(), message),
+        FindEvent::Failed {
+            path: None,
+            message,
+        } => eprintln!("warning: {message}"),
+        FindEvent::Matched(_) | FindEvent::Skipped { .. } => {}
     }
+}
 
-    Ok(Some((
-        FileSearchResult::new(Some(path.to_path_buf()), matches),
-        None,
-    )))
+fn parse_file_size(value: &str) -> std::result::Result<u64, String> {
+    let trimmed = value.trim();
+    let (digits, multiplier) = match trimmed.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => {
+            let multiplier = match unit.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => return Err(format!("unknown size suffix `{unit}`; use K, M or G")),
+            };
+            (&trimmed[..index], multiplier)
+        }
+        _ => (trimmed, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size `{value}`"))
 }
 
 fn print_search_report(report: &SearchReport, options: &SearchOptions) {
@@ -2,7 +2,7 @@ use std::error::Error;
 use std::fs;
 
 use assert_cmd::Command;
-use encoding_rs::GBK;
+use encoding_rs::{GBK, KOI8_R, WINDOWS_1251};
 use predicates::prelude::*;
 use tempfile::tempdir;
 
@@ -91,3 +91,83 @@ fn convert_gbk_to_utf8_in_place() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn convert_koi8r_to_windows1251() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let input = dir.path().join("ru.txt");
+    let (encoded, _, _) = KOI8_R.encode("Привет, мир");
+    fs::write(&input, encoded.as_ref())?;
+    let output = dir.path().join("ru-1251.txt");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            "--from",
+            "koi8-r",
+            "--to",
+            "cp1251",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let (expected, _, _) = WINDOWS_1251.encode("Привет, мир");
+    assert_eq!(fs::read(&output)?, expected.as_ref());
+
+    Ok(())
+}
+
+#[test]
+fn convert_rejects_unknown_encoding_label() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let input = dir.path().join("notes.txt");
+    fs::write(&input, "Plain UTF-8")?;
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["convert", input.to_str().unwrap(), "--to", "klingon"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown encoding `klingon`"));
+
+    Ok(())
+}
+
+#[test]
+fn check_eol_reports_mixed_files_without_rewriting() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let nested = dir.path().join("src");
+    fs::create_dir_all(&nested)?;
+    let mixed = nested.join("mixed.txt");
+    fs::write(&mixed, "one\r\ntwo\nthree\r\n")?;
+    fs::write(dir.path().join("clean.txt"), "one\ntwo\n")?;
+    fs::write(dir.path().join("blob.bin"), [0xFF, 0xFE, 0x00, 0xD8])?;
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["convert", "--check-eol", dir.path().to_str().unwrap()])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "mixed.txt: mixed line endings (LF 1, CRLF 2, CR 0)",
+        ))
+        .stdout(predicate::str::contains("clean.txt").not())
+        .stderr(predicate::str::contains(
+            "1 file(s) have mixed line endings",
+        ));
+
+    assert_eq!(fs::read(&mixed)?, b"one\r\ntwo\nthree\r\n");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args([
+            "convert",
+            "--check-eol",
+            dir.path().join("clean.txt").to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 with mixed line endings"));
+
+    Ok(())
+}
@@ -47,6 +47,7 @@ fn search_replace_apply_overwrites_files() -> Result<(), Box<dyn Error>> {
     fs::write(&file, "hello world\nhello world\n")?;
 
     Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
         .args([
             "search",
             "world",
@@ -64,3 +65,457 @@ fn search_replace_apply_overwrites_files() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn search_replace_supports_case_escapes_and_extended_mode() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let file = dir.path().join("names.txt");
+    fs::write(&file, "first_name;last_name\n")?;
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args([
+            "search",
+            r"(\w+)_name",
+            file.to_str().unwrap(),
+            "--regex",
+            "--replace",
+            r"\u$1Name",
+            "--apply",
+        ])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file)?, "FirstName;LastName\n");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args([
+            "search",
+            ";",
+            file.to_str().unwrap(),
+            "--extended",
+            "--replace",
+            r"\n",
+            "--apply",
+        ])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file)?, "FirstName\nLastName\n");
+
+    Ok(())
+}
+
+#[test]
+fn search_filters_files_by_mask_ignore_rules_and_content() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let root = dir.path();
+    fs::create_dir_all(root.join("src"))?;
+    fs::create_dir_all(root.join("vendor"))?;
+    fs::write(root.join(".gitignore"), "generated.rs\n")?;
+    fs::write(root.join("src/lib.rs"), "// needle\n")?;
+    fs::write(root.join("src/notes.md"), "needle\n")?;
+    fs::write(root.join("vendor/dep.rs"), "needle\n")?;
+    fs::write(root.join("generated.rs"), "needle\n")?;
+    fs::write(root.join("image.bin"), b"needle\0\x01")?;
+    let root_arg = root.to_str().unwrap();
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args([
+            "search",
+            "needle",
+            root_arg,
+            "--include",
+            "*.rs",
+            "--exclude",
+            "vendor",
+            "--threads",
+            "2",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("(1 hits in 1 files)")
+                .and(predicate::str::contains("lib.rs"))
+                .and(predicate::str::contains("generated.rs").not()),
+        );
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "needle", root_arg, "--no-ignore", "--binary"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(5 hits in 5 files)"));
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "needle", root_arg, "--max-filesize", "1K"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(3 hits in 3 files)"));
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "needle", root_arg, "--max-filesize", "1Q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown size suffix"));
+
+    Ok(())
+}
+
+#[test]
+fn search_emits_machine_readable_formats() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let file = dir.path().join("lint.txt");
+    fs::write(&file, "ok\nTODO é TODO\n")?;
+    let path = file.to_str().unwrap();
+
+    let output = Command::cargo_bin("rustnotepad-cli")?
+        .args([
+            "search",
+            "TODO",
+            path,
+            "--case-sensitive",
+            "--format",
+            "json",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(report["total_matches"], 2);
+    let second = &report["files"][0]["matches"][1];
+    assert_eq!(second["line"], 2);
+    assert_eq!(second["column"], 8);
+    assert_eq!(second["byte_start"], 11);
+    assert_eq!(second["byte_end"], 15);
+    assert_eq!(second["line_text"], "TODO é TODO");
+
+    let output = Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "TODO", path, "--format", "jsonl"])
+        .output()?;
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["path"], path);
+    assert_eq!(lines[0]["matched"], "TODO");
+
+    // Vim 欄位以位元組計算。 / Vim columns count bytes, so `é` takes two.
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "TODO", path, "--format", "vimgrep"])
+        .assert()
+        .success()
+        .stdout(format!("{path}:2:1:TODO é TODO\n{path}:2:9:TODO é TODO\n"));
+
+    let output = Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "TODO", path, "--format", "sarif", "--exit-code"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stderr.is_empty());
+    let log: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(log["version"], "2.1.0");
+    let region = &log["runs"][0]["results"][1]["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["startLine"], 2);
+    assert_eq!(region["startColumn"], 8);
+    assert_eq!(region["endColumn"], 12);
+
+    // grep 慣例：無符合為 1、錯誤為 2，皆不附帶訊息以外的輸出。 / grep convention: 1 when nothing matched, 2 on errors.
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "FIXME", path, "--format", "jsonl", "--exit-code"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "TODO", "missing-dir/none.txt", "--exit-code"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Error:"));
+
+    Ok(())
+}
+
+#[test]
+fn search_replace_previews_selects_and_undoes() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let file = dir.path().join("plan.txt");
+    fs::write(&file, "foo one\nfoo two\nfoo three\n")?;
+    let journal = dir.path().join("journal");
+    let file_arg = file.to_str().unwrap();
+    let journal_arg = journal.to_str().unwrap();
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["search", "foo", file_arg, "--replace", "bar", "--diff"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("@@ -1,3 +1,3 @@")
+                .and(predicate::str::contains("-foo two\n"))
+                .and(predicate::str::contains("+bar two\n"))
+                .and(predicate::str::contains("Dry run only")),
+        );
+    assert_eq!(fs::read_to_string(&file)?, "foo one\nfoo two\nfoo three\n");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args([
+            "search",
+            "foo",
+            file_arg,
+            "--replace",
+            "bar",
+            "--interactive",
+            "--apply",
+            "--journal",
+            journal_arg,
+        ])
+        .write_stdin("y\nn\n")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Applied 1 replacements")
+                .and(predicate::str::contains("replace-history undo")),
+        )
+        .stderr(predicate::str::contains("+ bar two"));
+    assert_eq!(fs::read_to_string(&file)?, "bar one\nfoo two\nfoo three\n");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["replace-history", "list", "--journal", journal_arg])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(1 replacements in 1 files)"));
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["replace-history", "undo", "--journal", journal_arg])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored 1 files"));
+    assert_eq!(fs::read_to_string(&file)?, "foo one\nfoo two\nfoo three\n");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .args(["replace-history", "undo", "--journal", journal_arg])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no journaled replacements"));
+
+    Ok(())
+}
+
+#[test]
+fn search_replace_journals_absolute_paths_for_relative_arguments() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    let work = dir.path().join("work");
+    fs::create_dir(&work)?;
+    fs::write(work.join("notes.txt"), "foo\n")?;
+    let journal = dir.path().join("journal");
+    let journal_arg = journal.to_str().unwrap();
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(&work)
+        .args([
+            "search",
+            "foo",
+            "notes.txt",
+            "--replace",
+            "bar",
+            "--apply",
+            "--journal",
+            journal_arg,
+        ])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(work.join("notes.txt"))?, "bar\n");
+
+    // 從其他目錄還原仍指向同一個檔案。 / Undo from another directory still finds the file.
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["replace-history", "undo", "--journal", journal_arg])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored 1 files"));
+    assert_eq!(fs::read_to_string(work.join("notes.txt"))?, "foo\n");
+    assert!(!dir.path().join("notes.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn search_replace_preserves_encodings_and_skips_unrepresentable_files() -> Result<(), Box<dyn Error>>
+{
+    let dir = tempdir()?;
+    let wide = dir.path().join("wide.txt");
+    let legacy = dir.path().join("legacy.txt");
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend("テスト one\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+    fs::write(&wide, &utf16)?;
+    // Shift-JIS for "テスト\r\nテスト\r\n".
+    let shift_jis = b"\x83\x65\x83\x58\x83\x67\r\n\x83\x65\x83\x58\x83\x67\r\n";
+    fs::write(&legacy, shift_jis)?;
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args([
+            "search",
+            "テスト",
+            dir.path().to_str().unwrap(),
+            "--replace",
+            "🦀",
+            "--apply",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(format!("Applied 1 replacements to {}", wide.display()))
+                .and(predicate::str::contains("Applied 2 replacements").not()),
+        )
+        .stderr(predicate::str::contains(format!(
+            "warning: {}: skipped 2 replacements; the text cannot be represented in shift-jis",
+            legacy.display()
+        )));
+
+    let mut expected = vec![0xFF, 0xFE];
+    expected.extend("🦀 one\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+    assert_eq!(fs::read(&wide)?, expected);
+    assert_eq!(fs::read(&legacy)?, shift_jis);
+
+    Ok(())
+}
+
+#[test]
+fn search_index_prunes_files_and_picks_up_changes() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    for idx in 0..10 {
+        let contents = if idx == 4 { "needle\n" } else { "hay\n" };
+        fs::write(dir.path().join(format!("file_{idx}.txt")), contents)?;
+    }
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search", "needle", "--index"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(1 hits in 1 files)"));
+    assert!(dir.path().join(".rustnotepad/search.index").is_file());
+
+    fs::write(dir.path().join("file_7.txt"), "needle again\n")?;
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search", "needle", "--index"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(2 hits in 2 files)"));
+
+    Ok(())
+}
+
+#[test]
+fn search_reads_files_above_the_large_file_threshold_in_chunks() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    fs::create_dir_all(dir.path().join(".rustnotepad"))?;
+    fs::write(
+        dir.path().join(".rustnotepad/preferences.json"),
+        r#"{"editor": {"large_file_threshold_mb": 1}}"#,
+    )?;
+    let mut contents = "hay\r\n".repeat(300_000);
+    contents.push_str("needle at the end\r\n");
+    fs::write(dir.path().join("big.log"), contents)?;
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search", "needle"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("(1 hits in 1 files)").and(predicate::str::contains(
+                "Line 300001 (Col 1): needle at the end",
+            )),
+        );
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search", "needle", "--replace", "pin", "--apply"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "skipped; files above the large-file threshold are read-only",
+        ))
+        .stdout(predicate::str::contains("No matches found."));
+    assert!(fs::read_to_string(dir.path().join("big.log"))?.contains("needle at the end"));
+
+    Ok(())
+}
+
+#[test]
+fn search_presets_are_saved_and_run_by_name() -> Result<(), Box<dyn Error>> {
+    let dir = tempdir()?;
+    fs::create_dir_all(dir.path().join("src"))?;
+    fs::write(
+        dir.path().join("src/lib.rs"),
+        "// TODO: one\n// todo: two\n",
+    )?;
+    fs::write(dir.path().join("notes.md"), "TODO: three\n")?;
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args([
+            "search",
+            "TODO|FIXME",
+            "--regex",
+            "--case-sensitive",
+            "--include",
+            "*.rs",
+            "--save-preset",
+            "todos",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Saved search preset \"todos\"."))
+        .stdout(predicate::str::contains("(1 hits in 1 files)"));
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search-presets", "run", "todos", "src"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Search \"TODO|FIXME\" (1 hits in 1 files)")
+                .and(predicate::str::contains("notes.md").not()),
+        );
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args([
+            "search-presets",
+            "run",
+            "todos",
+            "src",
+            "--no-case-sensitive",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Search \"TODO|FIXME\" (2 hits in 1 files)",
+        ));
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search-presets", "list"])
+        .assert()
+        .success()
+        .stdout("todos: TODO|FIXME\n");
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search-presets", "run", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no search preset named \"missing\"",
+        ));
+
+    Command::cargo_bin("rustnotepad-cli")?
+        .current_dir(dir.path())
+        .args(["search-presets", "run", "todos", "--apply"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass --replace"));
+
+    Ok(())
+}
@@ -31,6 +31,9 @@ pub struct CompletionRequest {
     pub trigger: CompletionTrigger,
     pub context: CompletionContext,
     pub language: Option<String>,
+    /// Zero-based (line, character column) of the caret, when known.
+    /// （游標的 0 起算（行, 字元欄），若已知。）
+    pub position: Option<(usize, usize)>,
 }
 
 impl CompletionRequest {
@@ -43,6 +46,7 @@ impl CompletionRequest {
             trigger: CompletionTrigger::Automatic,
             context: CompletionContext::default(),
             language: None,
+            position: None,
         }
     }
 
@@ -70,6 +74,11 @@ impl CompletionRequest {
         self.language = language;
         self
     }
+
+    pub fn with_position(mut self, line: usize, column: usize) -> Self {
+        self.position = Some((line, column));
+        self
+    }
 }
 
 /// Type of item returned by a completion provider.
@@ -904,6 +913,7 @@ pub struct LspRequestParams<'a> {
     pub trigger: CompletionTrigger,
     pub context: &'a CompletionContext,
     pub max_items: usize,
+    pub position: Option<(usize, usize)>,
 }
 
 /// Trait implemented by backends capable of serving LSP completions.
@@ -947,6 +957,7 @@ impl CompletionProvider for LspProvider {
             trigger: request.trigger,
             context: &request.context,
             max_items: self.max_items.min(request.max_items),
+            position: request.position,
         };
 
         match self.bridge.complete(params) {
@@ -1,4 +1,5 @@
 use crate::editor::{Caret, EditOperation, EditorBuffer, EditorError};
+use crate::text_buffer::TextBuffer;
 
 /// 描述矩形選取範圍。 / Describes a rectangular selection across multiple lines.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -40,6 +41,14 @@ pub fn replace_columnar(
     buffer: &mut EditorBuffer,
     selection: ColumnSelection,
     payload: &[String],
+) -> Result<(), EditorError> {
+    buffer.transaction(|buffer| replace_columnar_inner(buffer, selection, payload))
+}
+
+fn replace_columnar_inner(
+    buffer: &mut EditorBuffer,
+    selection: ColumnSelection,
+    payload: &[String],
 ) -> Result<(), EditorError> {
     let normalized = ColumnSelection::new(
         selection.start_line,
@@ -47,20 +56,16 @@ pub fn replace_columnar(
         selection.start_column,
         selection.end_column,
     );
-    let text = buffer.contents().to_owned();
-    let mut line_bounds = compute_line_bounds(&text);
-    if line_bounds.is_empty() {
-        line_bounds.push((0, 0, 0));
-    }
-
-    let max_index = line_bounds.len().saturating_sub(1);
+    let text = buffer.text();
+    let max_index = last_line(text);
     let start_line = normalized.start_line.min(max_index);
     let end_line = normalized.end_line.min(max_index);
     let mut ops = Vec::new();
 
     for (idx, line_idx) in (start_line..=end_line).enumerate() {
-        let (line_start, content_len, _newline) = line_bounds[line_idx];
-        let mut line = text[line_start..line_start + content_len].to_string();
+        let line_start = text.line_start(line_idx);
+        let line_end = text.line_end(line_idx);
+        let mut line = text.line_text(line_idx);
         let insertion = resolve_payload(payload, idx);
 
         let max_col = normalized.start_column.max(normalized.end_column);
@@ -75,7 +80,7 @@ pub fn replace_columnar(
 
         ops.push(EditOperation {
             start: line_start,
-            end: line_start + content_len,
+            end: line_end,
             text: line,
         });
     }
@@ -126,24 +131,14 @@ fn column_to_byte(line: &str, column: usize) -> usize {
     line.len()
 }
 
-fn compute_line_bounds(text: &str) -> Vec<(usize, usize, usize)> {
-    let mut results = Vec::new();
-    let mut start = 0usize;
-    let mut iter = text.char_indices().peekable();
-    while let Some((idx, ch)) = iter.next() {
-        if ch == '\n' {
-            results.push((start, idx - start, 1));
-            start = idx + 1;
-        } else if iter.peek().is_none() {
-            results.push((start, idx + ch.len_utf8() - start, 0));
-        }
-    }
-    if start < text.len() {
-        results.push((start, text.len() - start, 0));
-    } else if text.is_empty() {
-        results.push((0, 0, 0));
+/// 最後一行的索引；結尾換行後的空行不算。 / Index of the last line, not counting the empty line after a trailing newline.
+fn last_line(text: &TextBuffer) -> usize {
+    let count = text.line_count();
+    if count > 1 && text.ends_with_newline() {
+        count - 2
+    } else {
+        count - 1
     }
-    results
 }
 
 fn update_carets(
@@ -153,16 +148,16 @@ fn update_carets(
     start_column: usize,
     payload: &[String],
 ) -> Result<(), EditorError> {
-    let final_text = buffer.contents().to_owned();
-    let line_bounds = compute_line_bounds(&final_text);
+    let text = buffer.text();
+    let max_index = last_line(text);
     let mut carets = Vec::new();
 
     for (idx, line_idx) in (start_line..=end_line).enumerate() {
-        if line_idx >= line_bounds.len() {
+        if line_idx > max_index {
             continue;
         }
-        let (line_start, content_len, _) = line_bounds[line_idx];
-        let line_slice = &final_text[line_start..line_start + content_len];
+        let line_start = text.line_start(line_idx);
+        let line_slice = text.line_text(line_idx);
         let payload_cols = if payload.is_empty() {
             0
         } else if payload.len() == 1 {
@@ -171,7 +166,7 @@ fn update_carets(
             payload[idx.min(payload.len() - 1)].chars().count()
         };
         let caret_col = start_column + payload_cols;
-        let caret_byte = column_to_byte(line_slice, caret_col);
+        let caret_byte = column_to_byte(&line_slice, caret_col);
         carets.push(Caret::new(line_start + caret_byte));
     }
 
@@ -201,4 +196,17 @@ mod tests {
         assert_eq!(buffer.contents(), "1a\n22b\n333c");
         assert_eq!(buffer.carets().len(), 3);
     }
+
+    #[test]
+    fn replace_columnar_undoes_in_one_step() {
+        let mut buffer = EditorBuffer::new("a\nb\nc");
+        let payload = vec!["1".into(), "22".into(), "333".into()];
+        replace_columnar(&mut buffer, ColumnSelection::new(0, 2, 0, 0), &payload).unwrap();
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "a\nb\nc");
+        assert_eq!(buffer.carets(), &[Caret::new(0)]);
+        assert!(buffer.redo());
+        assert_eq!(buffer.contents(), "1a\n22b\n333c");
+        assert_eq!(buffer.carets().len(), 3);
+    }
 }
@@ -1,13 +1,23 @@
 use std::borrow::Cow;
 use std::fs::{self, File, Metadata};
 use std::io::{self, ErrorKind, Read, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use chardetng::EncodingDetector;
-use encoding_rs::{Encoding as RsEncoding, BIG5, GBK, SHIFT_JIS, WINDOWS_1252};
+use encoding_rs::{
+    Encoding as RsEncoding, BIG5, EUC_JP, EUC_KR, GB18030, GBK, IBM866, ISO_2022_JP, ISO_8859_10,
+    ISO_8859_13, ISO_8859_14, ISO_8859_15, ISO_8859_16, ISO_8859_2, ISO_8859_3, ISO_8859_4,
+    ISO_8859_5, ISO_8859_6, ISO_8859_7, ISO_8859_8, ISO_8859_8_I, KOI8_R, KOI8_U, MACINTOSH,
+    SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252, WINDOWS_1253,
+    WINDOWS_1254, WINDOWS_1255, WINDOWS_1256, WINDOWS_1257, WINDOWS_1258, WINDOWS_874,
+    X_MAC_CYRILLIC, X_USER_DEFINED,
+};
 use thiserror::Error;
 
+use crate::text_buffer::TextBuffer;
+
 /// 表示文件目前使用的行尾樣式。 / Represents the current line ending style for a document.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineEnding {
@@ -27,6 +37,118 @@ impl LineEnding {
     }
 }
 
+/// 載入時統計的各類行尾數量。 / Per-kind line ending counts gathered when a file is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineEndingStats {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl LineEndingStats {
+    /// 統計尚未正規化文字中的行尾。 / Counts line endings in text that has not been normalised yet.
+    pub fn from_text(text: &str) -> Self {
+        let bytes = text.as_bytes();
+        let mut stats = Self::default();
+        let mut idx = 0;
+        while idx < bytes.len() {
+            match bytes[idx] {
+                b'\r' if bytes.get(idx + 1) == Some(&b'\n') => {
+                    stats.crlf += 1;
+                    idx += 1;
+                }
+                b'\r' => stats.cr += 1,
+                b'\n' => stats.lf += 1,
+                _ => {}
+            }
+            idx += 1;
+        }
+        stats
+    }
+
+    /// 指定種類的數量。 / Count for one line ending kind.
+    pub fn count(&self, ending: LineEnding) -> usize {
+        match ending {
+            LineEnding::Lf => self.lf,
+            LineEnding::CrLf => self.crlf,
+            LineEnding::Cr => self.cr,
+        }
+    }
+
+    /// 行尾總數。 / Total number of line endings.
+    pub fn total(&self) -> usize {
+        self.lf + self.crlf + self.cr
+    }
+
+    /// 是否混用兩種以上的行尾。 / Indicates whether more than one kind of line ending occurs.
+    pub fn is_mixed(&self) -> bool {
+        [self.lf, self.crlf, self.cr]
+            .iter()
+            .filter(|&&count| count > 0)
+            .count()
+            > 1
+    }
+
+    /// 出現最多的行尾；同數時依 LF、CRLF、CR 的順序。 / Most frequent line ending, ties resolved in LF, CRLF, CR order.
+    pub fn dominant(&self) -> Option<LineEnding> {
+        [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr]
+            .into_iter()
+            .filter(|&ending| self.count(ending) > 0)
+            .rev()
+            .max_by_key(|&ending| self.count(ending))
+    }
+
+    fn uniform(ending: LineEnding, total: usize) -> Self {
+        let mut stats = Self::default();
+        *stats.count_mut(ending) = total;
+        stats
+    }
+
+    /// 統計新文字；只含 `\n` 時視為已正規化，全數算作 `ending`。 / Counts new text; text with only `\n` is treated as normalised and counted as `ending`.
+    fn for_new_text(text: &str, ending: LineEnding) -> Self {
+        let stats = Self::from_text(text);
+        if stats.crlf == 0 && stats.cr == 0 {
+            Self::uniform(ending, stats.total())
+        } else {
+            stats
+        }
+    }
+
+    /// 記錄一次編輯：扣除移除的換行並加上插入文字的行尾。 / Records an edit: drops the removed line breaks and adds those of the inserted text.
+    ///
+    /// 緩衝中的換行已正規化，無從得知原本種類，因此先從文件本身的行尾扣除，再依 LF、CRLF、CR 的順序。 /
+    /// The buffer only holds normalised breaks, so their original kind is unknown; removals come out of the
+    /// document's own line ending first, then LF, CRLF and CR.
+    fn apply_edit(&mut self, removed_breaks: usize, inserted: &str, ending: LineEnding) {
+        let mut remaining = removed_breaks;
+        for kind in [ending, LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr] {
+            let count = self.count_mut(kind);
+            let taken = remaining.min(*count);
+            *count -= taken;
+            remaining -= taken;
+        }
+        let added = Self::for_new_text(inserted, ending);
+        self.lf += added.lf;
+        self.crlf += added.crlf;
+        self.cr += added.cr;
+    }
+
+    fn count_mut(&mut self, ending: LineEnding) -> &mut usize {
+        match ending {
+            LineEnding::Lf => &mut self.lf,
+            LineEnding::CrLf => &mut self.crlf,
+            LineEnding::Cr => &mut self.cr,
+        }
+    }
+}
+
+/// 行尾一致性狀態，混用時應提示使用者。 / Line ending consistency; `Mixed` should be surfaced as a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEndingState {
+    Consistent,
+    Mixed,
+}
+
 /// 列舉文件支援的文字編碼。 / Supported encodings for text documents.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Encoding {
@@ -44,31 +166,178 @@ pub enum DiskState {
     Removed,
 }
 
-/// 指定支援的傳統多位元編碼。 / Enumerates supported legacy multi-byte encodings.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// 指定支援的傳統（非 Unicode）編碼，涵蓋 `encoding_rs` 的全部標籤。 / Enumerates supported legacy (non-Unicode) encodings, covering every `encoding_rs` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LegacyEncoding {
     Windows1252,
     ShiftJis,
     Gbk,
     Big5,
+    Gb18030,
+    EucJp,
+    Iso2022Jp,
+    EucKr,
+    Ibm866,
+    Iso8859_2,
+    Iso8859_3,
+    Iso8859_4,
+    Iso8859_5,
+    Iso8859_6,
+    Iso8859_7,
+    Iso8859_8,
+    Iso8859_8I,
+    Iso8859_10,
+    Iso8859_13,
+    Iso8859_14,
+    Iso8859_15,
+    Iso8859_16,
+    Koi8R,
+    Koi8U,
+    Macintosh,
+    MacCyrillic,
+    Windows874,
+    Windows1250,
+    Windows1251,
+    Windows1253,
+    Windows1254,
+    Windows1255,
+    Windows1256,
+    Windows1257,
+    Windows1258,
+    UserDefined,
 }
 
 impl LegacyEncoding {
+    /// 所有支援的傳統編碼，依選單顯示順序排列。 / Every supported legacy encoding, in menu order.
+    pub const ALL: [LegacyEncoding; 36] = [
+        LegacyEncoding::Windows1252,
+        LegacyEncoding::ShiftJis,
+        LegacyEncoding::Gbk,
+        LegacyEncoding::Big5,
+        LegacyEncoding::Gb18030,
+        LegacyEncoding::EucJp,
+        LegacyEncoding::Iso2022Jp,
+        LegacyEncoding::EucKr,
+        LegacyEncoding::Ibm866,
+        LegacyEncoding::Iso8859_2,
+        LegacyEncoding::Iso8859_3,
+        LegacyEncoding::Iso8859_4,
+        LegacyEncoding::Iso8859_5,
+        LegacyEncoding::Iso8859_6,
+        LegacyEncoding::Iso8859_7,
+        LegacyEncoding::Iso8859_8,
+        LegacyEncoding::Iso8859_8I,
+        LegacyEncoding::Iso8859_10,
+        LegacyEncoding::Iso8859_13,
+        LegacyEncoding::Iso8859_14,
+        LegacyEncoding::Iso8859_15,
+        LegacyEncoding::Iso8859_16,
+        LegacyEncoding::Koi8R,
+        LegacyEncoding::Koi8U,
+        LegacyEncoding::Macintosh,
+        LegacyEncoding::MacCyrillic,
+        LegacyEncoding::Windows874,
+        LegacyEncoding::Windows1250,
+        LegacyEncoding::Windows1251,
+        LegacyEncoding::Windows1253,
+        LegacyEncoding::Windows1254,
+        LegacyEncoding::Windows1255,
+        LegacyEncoding::Windows1256,
+        LegacyEncoding::Windows1257,
+        LegacyEncoding::Windows1258,
+        LegacyEncoding::UserDefined,
+    ];
+
+    /// 持久化用的名稱；最早支援的四種沿用舊名以相容既有中繼資料。 / Persisted name; the original four keep their historical spelling for metadata compatibility.
     pub fn name(self) -> &'static str {
         match self {
             LegacyEncoding::Windows1252 => "windows-1252",
             LegacyEncoding::ShiftJis => "shift-jis",
             LegacyEncoding::Gbk => "gbk",
             LegacyEncoding::Big5 => "big5",
+            LegacyEncoding::Gb18030 => "gb18030",
+            LegacyEncoding::EucJp => "euc-jp",
+            LegacyEncoding::Iso2022Jp => "iso-2022-jp",
+            LegacyEncoding::EucKr => "euc-kr",
+            LegacyEncoding::Ibm866 => "ibm866",
+            LegacyEncoding::Iso8859_2 => "iso-8859-2",
+            LegacyEncoding::Iso8859_3 => "iso-8859-3",
+            LegacyEncoding::Iso8859_4 => "iso-8859-4",
+            LegacyEncoding::Iso8859_5 => "iso-8859-5",
+            LegacyEncoding::Iso8859_6 => "iso-8859-6",
+            LegacyEncoding::Iso8859_7 => "iso-8859-7",
+            LegacyEncoding::Iso8859_8 => "iso-8859-8",
+            LegacyEncoding::Iso8859_8I => "iso-8859-8-i",
+            LegacyEncoding::Iso8859_10 => "iso-8859-10",
+            LegacyEncoding::Iso8859_13 => "iso-8859-13",
+            LegacyEncoding::Iso8859_14 => "iso-8859-14",
+            LegacyEncoding::Iso8859_15 => "iso-8859-15",
+            LegacyEncoding::Iso8859_16 => "iso-8859-16",
+            LegacyEncoding::Koi8R => "koi8-r",
+            LegacyEncoding::Koi8U => "koi8-u",
+            LegacyEncoding::Macintosh => "macintosh",
+            LegacyEncoding::MacCyrillic => "x-mac-cyrillic",
+            LegacyEncoding::Windows874 => "windows-874",
+            LegacyEncoding::Windows1250 => "windows-1250",
+            LegacyEncoding::Windows1251 => "windows-1251",
+            LegacyEncoding::Windows1253 => "windows-1253",
+            LegacyEncoding::Windows1254 => "windows-1254",
+            LegacyEncoding::Windows1255 => "windows-1255",
+            LegacyEncoding::Windows1256 => "windows-1256",
+            LegacyEncoding::Windows1257 => "windows-1257",
+            LegacyEncoding::Windows1258 => "windows-1258",
+            LegacyEncoding::UserDefined => "x-user-defined",
         }
     }
 
-    fn to_rs(self) -> &'static RsEncoding {
+    /// 以 WHATWG 標籤（如 `latin1`、`cp1251`、`sjis`）查找編碼。 / Looks up an encoding by any WHATWG label such as `latin1`, `cp1251`, or `sjis`.
+    pub fn from_label(label: &str) -> Option<Self> {
+        RsEncoding::for_label(label.trim().as_bytes()).and_then(map_rs_encoding)
+    }
+
+    /// 是否與 ASCII 相容（`\n` 必定是單一位元組）。 / Indicates whether ASCII bytes, including `\n`, always map to themselves.
+    pub fn is_ascii_compatible(self) -> bool {
+        self.to_rs().is_ascii_compatible()
+    }
+
+    pub(crate) fn to_rs(self) -> &'static RsEncoding {
         match self {
             LegacyEncoding::Windows1252 => WINDOWS_1252,
             LegacyEncoding::ShiftJis => SHIFT_JIS,
             LegacyEncoding::Gbk => GBK,
             LegacyEncoding::Big5 => BIG5,
+            LegacyEncoding::Gb18030 => GB18030,
+            LegacyEncoding::EucJp => EUC_JP,
+            LegacyEncoding::Iso2022Jp => ISO_2022_JP,
+            LegacyEncoding::EucKr => EUC_KR,
+            LegacyEncoding::Ibm866 => IBM866,
+            LegacyEncoding::Iso8859_2 => ISO_8859_2,
+            LegacyEncoding::Iso8859_3 => ISO_8859_3,
+            LegacyEncoding::Iso8859_4 => ISO_8859_4,
+            LegacyEncoding::Iso8859_5 => ISO_8859_5,
+            LegacyEncoding::Iso8859_6 => ISO_8859_6,
+            LegacyEncoding::Iso8859_7 => ISO_8859_7,
+            LegacyEncoding::Iso8859_8 => ISO_8859_8,
+            LegacyEncoding::Iso8859_8I => ISO_8859_8_I,
+            LegacyEncoding::Iso8859_10 => ISO_8859_10,
+            LegacyEncoding::Iso8859_13 => ISO_8859_13,
+            LegacyEncoding::Iso8859_14 => ISO_8859_14,
+            LegacyEncoding::Iso8859_15 => ISO_8859_15,
+            LegacyEncoding::Iso8859_16 => ISO_8859_16,
+            LegacyEncoding::Koi8R => KOI8_R,
+            LegacyEncoding::Koi8U => KOI8_U,
+            LegacyEncoding::Macintosh => MACINTOSH,
+            LegacyEncoding::MacCyrillic => X_MAC_CYRILLIC,
+            LegacyEncoding::Windows874 => WINDOWS_874,
+            LegacyEncoding::Windows1250 => WINDOWS_1250,
+            LegacyEncoding::Windows1251 => WINDOWS_1251,
+            LegacyEncoding::Windows1253 => WINDOWS_1253,
+            LegacyEncoding::Windows1254 => WINDOWS_1254,
+            LegacyEncoding::Windows1255 => WINDOWS_1255,
+            LegacyEncoding::Windows1256 => WINDOWS_1256,
+            LegacyEncoding::Windows1257 => WINDOWS_1257,
+            LegacyEncoding::Windows1258 => WINDOWS_1258,
+            LegacyEncoding::UserDefined => X_USER_DEFINED,
         }
     }
 }
@@ -82,6 +351,27 @@ impl Encoding {
             Encoding::Legacy(legacy) => legacy.name(),
         }
     }
+
+    /// 解析 `name` 的輸出或任何 WHATWG 標籤，供工作階段與 CLI 使用。 / Parses a `name` value or any WHATWG label, for sessions and the CLI.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let encoding = RsEncoding::for_label(label.trim().as_bytes())?;
+        if encoding == UTF_8 {
+            Some(Encoding::Utf8)
+        } else if encoding == UTF_16LE {
+            Some(Encoding::Utf16Le)
+        } else if encoding == UTF_16BE {
+            Some(Encoding::Utf16Be)
+        } else {
+            map_rs_encoding(encoding).map(Encoding::Legacy)
+        }
+    }
+
+    /// 列出所有可選的編碼。 / Lists every selectable encoding.
+    pub fn all() -> impl Iterator<Item = Encoding> {
+        [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be]
+            .into_iter()
+            .chain(LegacyEncoding::ALL.into_iter().map(Encoding::Legacy))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -109,14 +399,19 @@ pub enum DocumentError {
     InvalidEncoding,
     #[error("text cannot be represented in target encoding {0}")]
     Unrepresentable(&'static str),
+    #[error("offset {offset} is beyond the end of the data ({len} bytes)")]
+    OffsetOutOfRange { offset: usize, len: usize },
+    #[error("search failed: {0}")]
+    Search(#[from] rustnotepad_search::SearchError),
 }
 
 /// 代表以 Unicode 或特定舊式文字編碼為後盾的文件記憶體模型。 / In-memory representation of a text document backed by a Unicode or selected legacy-encoded text file.
 #[derive(Debug, Clone)]
 pub struct Document {
     path: Option<PathBuf>,
-    contents: String,
+    contents: TextBuffer,
     line_ending: LineEnding,
+    line_ending_stats: LineEndingStats,
     encoding: Encoding,
     has_bom: bool,
     is_dirty: bool,
@@ -128,8 +423,9 @@ impl Document {
     pub fn new() -> Self {
         Self {
             path: None,
-            contents: String::new(),
+            contents: TextBuffer::new(),
             line_ending: LineEnding::Lf,
+            line_ending_stats: LineEndingStats::default(),
             encoding: Encoding::Utf8,
             has_bom: false,
             is_dirty: false,
@@ -140,25 +436,39 @@ impl Document {
     /// 從磁碟載入文件並將行尾內部正規化為 `\n`。 / Loads a document from disk, normalising newlines to `\n` internally.
     pub fn open(path: impl AsRef<Path>) -> Result<Self, DocumentError> {
         let path_ref = path.as_ref();
-        let mut file = File::open(path_ref)?;
-        let metadata = file.metadata()?;
-        let mut bytes = Vec::new();
-        file.read_to_end(&mut bytes)?;
-
+        let (bytes, metadata) = read_file(path_ref)?;
         let decoded = decode_bytes(bytes)?;
-        let line_ending = detect_line_ending(&decoded.text);
-        let contents = normalize_newlines(&decoded.text);
-        let signature = FileSignature::from_metadata(&metadata);
+        Ok(Self::from_decoded(path_ref, decoded, &metadata))
+    }
+
+    /// 以指定編碼開啟檔案，略過自動偵測；位元組無效時回傳 `InvalidEncoding`。 / Opens a file with an explicit encoding, bypassing detection; malformed bytes yield `InvalidEncoding`.
+    pub fn open_with_encoding(
+        path: impl AsRef<Path>,
+        encoding: Encoding,
+    ) -> Result<Self, DocumentError> {
+        let path_ref = path.as_ref();
+        let (bytes, metadata) = read_file(path_ref)?;
+        let decoded = decode_bytes_as(&bytes, encoding)?;
+        Ok(Self::from_decoded(path_ref, decoded, &metadata))
+    }
 
-        Ok(Self {
-            path: Some(path_ref.to_path_buf()),
-            contents,
+    fn from_decoded(path: &Path, decoded: DecodedText, metadata: &Metadata) -> Self {
+        let line_ending_stats = LineEndingStats::from_text(&decoded.text);
+        // 混用時以最常見的行尾為準，而非第一個出現者。 / With mixed endings, prefer the most frequent kind over the first one seen.
+        let line_ending = line_ending_stats
+            .dominant()
+            .unwrap_or_else(|| detect_line_ending(&decoded.text));
+        let contents = normalize_newlines(&decoded.text);
+        Self {
+            path: Some(path.to_path_buf()),
+            contents: TextBuffer::from(contents),
             line_ending,
+            line_ending_stats,
             encoding: decoded.encoding,
             has_bom: decoded.has_bom,
             is_dirty: false,
-            on_disk_signature: Some(signature),
-        })
+            on_disk_signature: Some(FileSignature::from_metadata(metadata)),
+        }
     }
 
     /// 將文件儲存至現有路徑；若尚未指定路徑則失敗。 / Saves the document to its current path; fails if no path is set.
@@ -175,35 +485,84 @@ impl Document {
     pub fn save_as(&mut self, path: impl AsRef<Path>) -> Result<(), DocumentError> {
         let path_ref = path.as_ref();
         let encoded = self.serialise_contents()?;
-
-        // 先寫入暫存檔再重新命名，避免出現部分寫入的情況。 / Use a temporary file plus rename to guard against partial writes.
-        let tmp_path = path_ref.with_extension("tmp_rustnotepad");
-        {
-            let mut tmp_file = File::create(&tmp_path)?;
-            tmp_file.write_all(&encoded)?;
-            tmp_file.sync_all()?; // 確保資料在重新命名前已寫入磁碟。 / Ensure bytes hit the disk before rename.
-        }
-        fs::rename(&tmp_path, path_ref)?;
+        write_atomically(path_ref, &encoded)?;
 
         let metadata = fs::metadata(path_ref)?;
         self.path = Some(path_ref.to_path_buf());
         self.is_dirty = false;
+        self.line_ending_stats =
+            LineEndingStats::uniform(self.line_ending, self.contents.line_count() - 1);
         self.on_disk_signature = Some(FileSignature::from_metadata(&metadata));
         Ok(())
     }
 
-    /// 取得目前文件內容（行尾已正規化為 `\n`）。 / Returns the current document contents, normalised to `\n` line endings.
+    /// 取得目前文件內容（行尾已正規化為 `\n`）；會展開全文，僅供一次性匯出。 / Returns the contents normalised to `\n`; flattens the text, so use it for one-off export only.
     pub fn contents(&self) -> &str {
+        self.contents.as_str()
+    }
+
+    /// 取得底層 rope 緩衝，可做不展開全文的行查詢。 / Returns the rope buffer for line queries that avoid materialising the text.
+    pub fn text(&self) -> &TextBuffer {
         &self.contents
     }
 
-    /// 以新文字取代記憶體內容並標記文件為已修改。 / Replaces the in-memory contents, marking the document as dirty.
+    /// 文件的編輯版本號，見 [`TextBuffer::revision`]。 / Edit revision of the text, see [`TextBuffer::revision`].
+    pub fn revision(&self) -> u64 {
+        self.contents.revision()
+    }
+
+    /// 以新文字取代記憶體內容、重新統計行尾並標記文件為已修改。 / Replaces the in-memory contents, recounting line endings and marking the document as dirty.
+    ///
+    /// 只含 `\n` 的文字視為已正規化，儲存時會套用文件的行尾設定。 / Text with only `\n` is treated as already normalised and is saved with the document's line ending.
     pub fn set_contents(&mut self, text: impl Into<String>) {
-        let text = normalize_newlines(&text.into());
-        self.contents = text;
+        let text = text.into();
+        self.line_ending_stats = LineEndingStats::for_new_text(&text, self.line_ending);
+        self.contents = TextBuffer::from(normalize_newlines(&text));
+        self.is_dirty = true;
+    }
+
+    /// 於位元組位置插入文字（換行會正規化為 `\n`）。 / Inserts text at a byte offset, normalising newlines to `\n`.
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        self.replace_range(offset..offset, text);
+    }
+
+    /// 移除位元組範圍內的文字。 / Removes the text within a byte range.
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.replace_range(range, "");
+    }
+
+    /// 以新文字取代位元組範圍，只更動受影響的 rope 節點；行尾統計隨之更新。 / Replaces a byte range, touching only the affected rope nodes and keeping the line ending counts in step.
+    pub fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        if range.is_empty() && text.is_empty() {
+            return;
+        }
+        let removed_breaks =
+            self.contents.line_of_offset(range.end) - self.contents.line_of_offset(range.start);
+        self.line_ending_stats
+            .apply_edit(removed_breaks, text, self.line_ending);
+        if text.contains('\r') {
+            self.contents.replace(range, &normalize_newlines(text));
+        } else {
+            self.contents.replace(range, text);
+        }
         self.is_dirty = true;
     }
 
+    /// 行數（結尾換行後的空行也計入）。 / Number of lines, including the empty line after a trailing newline.
+    pub fn line_count(&self) -> usize {
+        self.contents.line_count()
+    }
+
+    /// 取得指定行內容（0 起算，不含換行）。 / Returns a zero-based line without its newline.
+    pub fn line(&self, line: usize) -> String {
+        self.contents.line_text(line)
+    }
+
+    /// 將位元組位置轉為 0 起算的（行, 字元欄）。 / Converts a byte offset into zero-based (line, character column).
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        self.contents.line_column(offset)
+    }
+
     /// 取得目前行尾設定。 / Returns the current line ending preference.
     pub fn line_ending(&self) -> LineEnding {
         self.line_ending
@@ -217,6 +576,30 @@ impl Document {
         }
     }
 
+    /// 行尾數量：載入時統計，之後隨編輯更新。 / Line ending counts gathered on load and kept up to date by edits.
+    pub fn line_ending_stats(&self) -> LineEndingStats {
+        self.line_ending_stats
+    }
+
+    /// 檢查文件是否混用行尾。 / Reports whether the document mixes line endings.
+    pub fn line_ending_state(&self) -> LineEndingState {
+        if self.line_ending_stats.is_mixed() {
+            LineEndingState::Mixed
+        } else {
+            LineEndingState::Consistent
+        }
+    }
+
+    /// 將全部行尾統一為指定樣式，於下次儲存時寫出。 / Normalises every line ending to the given style, written out on the next save.
+    pub fn normalize_line_endings(&mut self, ending: LineEnding) {
+        let stats = LineEndingStats::uniform(ending, self.line_ending_stats.total());
+        if stats != self.line_ending_stats || self.line_ending != ending {
+            self.line_ending_stats = stats;
+            self.line_ending = ending;
+            self.is_dirty = true;
+        }
+    }
+
     /// 取得目前文件編碼。 / Returns the current document encoding.
     pub fn encoding(&self) -> Encoding {
         self.encoding
@@ -304,7 +687,14 @@ impl Document {
     }
 
     pub(crate) fn serialise_contents(&self) -> Result<Vec<u8>, DocumentError> {
-        let text = self.contents.replace('\n', self.line_ending.as_str());
+        let mut text = String::with_capacity(self.contents.len());
+        for chunk in self.contents.chunks() {
+            if self.line_ending == LineEnding::Lf {
+                text.push_str(chunk);
+            } else {
+                text.push_str(&chunk.replace('\n', self.line_ending.as_str()));
+            }
+        }
         let bytes = match self.encoding {
             Encoding::Utf8 => {
                 if self.has_bom {
@@ -331,6 +721,69 @@ struct DecodedText {
     has_bom: bool,
 }
 
+/// 先寫入暫存檔再重新命名，避免出現部分寫入的情況。 / Writes through a temporary file plus rename to guard against partial writes.
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp_rustnotepad");
+    {
+        let mut tmp_file = File::create(&tmp_path)?;
+        tmp_file.write_all(bytes)?;
+        tmp_file.sync_all()?; // 確保資料在重新命名前已寫入磁碟。 / Ensure bytes hit the disk before rename.
+    }
+    fs::rename(&tmp_path, path)
+}
+
+fn read_file(path: &Path) -> Result<(Vec<u8>, Metadata), DocumentError> {
+    let mut file = File::open(path)?;
+    let metadata = file.metadata()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok((bytes, metadata))
+}
+
+fn decode_bytes_as(bytes: &[u8], encoding: Encoding) -> Result<DecodedText, DocumentError> {
+    let (text, has_bom) = match encoding {
+        Encoding::Utf8 => {
+            let (body, has_bom) = match bytes.strip_prefix(b"\xEF\xBB\xBF") {
+                Some(body) => (body, true),
+                None => (bytes, false),
+            };
+            let text = std::str::from_utf8(body).map_err(|_| DocumentError::InvalidEncoding)?;
+            (text.to_owned(), has_bom)
+        }
+        Encoding::Utf16Le => match bytes.strip_prefix(b"\xFF\xFE") {
+            Some(body) => (decode_utf16(body, false)?, true),
+            None => (decode_utf16(bytes, false)?, false),
+        },
+        Encoding::Utf16Be => match bytes.strip_prefix(b"\xFE\xFF") {
+            Some(body) => (decode_utf16(body, true)?, true),
+            None => (decode_utf16(bytes, true)?, false),
+        },
+        Encoding::Legacy(legacy) => (decode_legacy(bytes, legacy)?, false),
+    };
+    Ok(DecodedText {
+        text,
+        encoding,
+        has_bom,
+    })
+}
+
+/// 以與 `Document::open` 相同的偵測規則解碼，並將行尾正規化為 `\n`。 / Decodes with the same detection as `Document::open` and normalises newlines to `\n`.
+pub(crate) fn decode_text(bytes: Vec<u8>) -> Result<String, DocumentError> {
+    decode_bytes(bytes).map(|decoded| normalize_newlines(&decoded.text))
+}
+
+/// 含 NUL 位元組且不像 UTF-16 的樣本視為二進位資料。 / Treats a sample containing NUL bytes as binary unless it looks like UTF-16.
+pub(crate) fn looks_binary(sample: &[u8]) -> bool {
+    if !sample.contains(&0) {
+        return false;
+    }
+    if sample.starts_with(b"\xFF\xFE") || sample.starts_with(b"\xFE\xFF") {
+        return false;
+    }
+    let even = &sample[..sample.len() & !1];
+    !(looks_like_utf16_le(even) || looks_like_utf16_be(even))
+}
+
 fn decode_bytes(bytes: Vec<u8>) -> Result<DecodedText, DocumentError> {
     if bytes.starts_with(b"\xEF\xBB\xBF") {
         let text =
@@ -388,11 +841,13 @@ fn decode_bytes(bytes: Vec<u8>) -> Result<DecodedText, DocumentError> {
 
     if let Some(legacy) = detect_legacy_encoding(&bytes) {
         let text = decode_legacy(&bytes, legacy)?;
-        return Ok(DecodedText {
-            text,
-            encoding: Encoding::Legacy(legacy),
-            has_bom: false,
-        });
+        if looks_like_text(&text) {
+            return Ok(DecodedText {
+                text,
+                encoding: Encoding::Legacy(legacy),
+                has_bom: false,
+            });
+        }
     }
 
     Err(DocumentError::InvalidEncoding)
@@ -457,27 +912,78 @@ fn decode_legacy(bytes: &[u8], legacy: LegacyEncoding) -> Result<String, Documen
 }
 
 fn detect_legacy_encoding(bytes: &[u8]) -> Option<LegacyEncoding> {
-    if bytes.is_empty() {
+    if bytes.is_empty() || looks_binary(bytes) {
         return None;
     }
+    guess_legacy_encoding(bytes, true)
+}
+
+/// 短樣本的非 ASCII 位元組少於此數時，拉丁字母編碼間的差異不足以判斷。 / Below this many non-ASCII bytes a sample cannot tell the Latin-script code pages apart.
+const SHORT_SAMPLE_NON_ASCII: usize = 8;
+
+/// 以 chardetng 猜測舊式編碼；短樣本若猜成其他拉丁字母編碼且 windows-1252 解碼也像文字，則改用 windows-1252。 /
+/// Guesses a legacy encoding with chardetng; for short samples a guess among the other Latin-script code pages
+/// gives way to windows-1252 when that also decodes to plausible text.
+pub(crate) fn guess_legacy_encoding(bytes: &[u8], last: bool) -> Option<LegacyEncoding> {
     let mut detector = EncodingDetector::new();
-    detector.feed(bytes, true);
-    let guess = detector.guess(None, true);
+    detector.feed(bytes, last);
+    let (guess, confident) = detector.guess_assess(None, true);
+    if !confident {
+        return None;
+    }
+    let non_ascii = bytes.iter().filter(|byte| !byte.is_ascii()).count();
+    if non_ascii < SHORT_SAMPLE_NON_ASCII && is_latin_sibling(guess) {
+        let (text, had_errors) = WINDOWS_1252.decode_without_bom_handling(bytes);
+        if !had_errors && looks_like_text(&text) {
+            return Some(LegacyEncoding::Windows1252);
+        }
+    }
     map_rs_encoding(guess)
 }
 
-fn map_rs_encoding(encoding: &'static RsEncoding) -> Option<LegacyEncoding> {
-    if encoding == WINDOWS_1252 {
-        Some(LegacyEncoding::Windows1252)
-    } else if encoding == SHIFT_JIS {
-        Some(LegacyEncoding::ShiftJis)
-    } else if encoding == GBK {
-        Some(LegacyEncoding::Gbk)
-    } else if encoding == BIG5 {
-        Some(LegacyEncoding::Big5)
-    } else {
-        None
+fn is_latin_sibling(encoding: &'static RsEncoding) -> bool {
+    [
+        ISO_8859_2,
+        ISO_8859_3,
+        ISO_8859_4,
+        ISO_8859_10,
+        ISO_8859_13,
+        ISO_8859_14,
+        ISO_8859_15,
+        ISO_8859_16,
+        WINDOWS_1250,
+        WINDOWS_1254,
+        WINDOWS_1257,
+        WINDOWS_1258,
+    ]
+    .contains(&encoding)
+}
+
+/// 單位元組編碼幾乎能解碼任何位元組，因此要求結果不含控制字元，且非 ASCII 字元多為可見字元而非 NBSP 等空白。 /
+/// Single-byte code pages decode almost any input, so require no control characters and mostly visible non-ASCII characters rather than NBSP-like spaces.
+fn looks_like_text(text: &str) -> bool {
+    let mut visible = 0usize;
+    let mut blank = 0usize;
+    for ch in text.chars() {
+        if ch.is_control() && !matches!(ch, '\t' | '\n' | '\r' | '\x0c') {
+            return false;
+        }
+        if ch.is_ascii() {
+            continue;
+        }
+        if ch.is_whitespace() {
+            blank += 1;
+        } else {
+            visible += 1;
+        }
     }
+    visible > blank
+}
+
+pub(crate) fn map_rs_encoding(encoding: &'static RsEncoding) -> Option<LegacyEncoding> {
+    LegacyEncoding::ALL
+        .into_iter()
+        .find(|legacy| legacy.to_rs() == encoding)
 }
 
 fn looks_like_utf16_le(bytes: &[u8]) -> bool {
@@ -663,6 +1169,158 @@ mod tests {
 
         let err = Document::open(&file_path).unwrap_err();
         assert!(matches!(err, DocumentError::InvalidEncoding));
+
+        let err =
+            Document::open_with_encoding(&file_path, Encoding::Legacy(LegacyEncoding::ShiftJis))
+                .unwrap_err();
+        assert!(matches!(err, DocumentError::InvalidEncoding));
+    }
+
+    #[test]
+    fn open_rejects_binary_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("blob.bin");
+        write_bytes(&file_path, &[0x00, 0x01, 0x02, 0xFF, 0x80]);
+
+        let err = Document::open(&file_path).unwrap_err();
+        assert!(matches!(err, DocumentError::InvalidEncoding));
+    }
+
+    #[test]
+    fn open_detects_extended_code_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases = [
+            (
+                WINDOWS_1251,
+                "Съешь же ещё этих мягких французских булок",
+                LegacyEncoding::Windows1251,
+            ),
+            (
+                EUC_KR,
+                "다람쥐 헌 쳇바퀴에 타고파 다람쥐 헌 쳇바퀴에 타고파",
+                LegacyEncoding::EucKr,
+            ),
+            (
+                EUC_JP,
+                "いろはにほへと ちりぬるを わかよたれそ つねならむ",
+                LegacyEncoding::EucJp,
+            ),
+        ];
+        for (encoding, text, expected) in cases {
+            let file_path = dir.path().join(format!("{}.txt", expected.name()));
+            let (encoded, _, _) = encoding.encode(text);
+            write_bytes(&file_path, encoded.as_ref());
+
+            let doc = Document::open(&file_path).unwrap();
+            assert_eq!(doc.contents(), text);
+            assert_eq!(doc.encoding(), Encoding::Legacy(expected));
+        }
+    }
+
+    #[test]
+    fn open_detects_short_single_byte_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases: [(&str, &[u8], &str, LegacyEncoding); 5] = [
+            (
+                "latin1",
+                b"caf\xE9\n",
+                "café\n",
+                LegacyEncoding::Windows1252,
+            ),
+            (
+                "latin1-word",
+                b"na\xEFve",
+                "naïve",
+                LegacyEncoding::Windows1252,
+            ),
+            (
+                "cp1252",
+                b"\x93hi\x94 \x96 ok",
+                "“hi” – ok",
+                LegacyEncoding::Windows1252,
+            ),
+            // chardetng 以 KOI8-U（KOI8-R 的超集）回報俄文。 / chardetng reports Russian as KOI8-U, a superset of KOI8-R.
+            (
+                "koi8",
+                b"\xF0\xD2\xC9\xD7\xC5\xD4",
+                "Привет",
+                LegacyEncoding::Koi8U,
+            ),
+            (
+                "koi8-line",
+                b"\xE4\xC1 \xCE\xC5\xD4\n",
+                "Да нет\n",
+                LegacyEncoding::Koi8U,
+            ),
+        ];
+        for (name, bytes, text, expected) in cases {
+            let file_path = dir.path().join(format!("{name}.txt"));
+            write_bytes(&file_path, bytes);
+
+            let doc = Document::open(&file_path).unwrap_or_else(|err| panic!("{name}: {err}"));
+            assert_eq!(doc.contents(), text, "{name}");
+            assert_eq!(doc.encoding(), Encoding::Legacy(expected), "{name}");
+        }
+    }
+
+    #[test]
+    fn open_with_encoding_overrides_detection() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("latin2.txt");
+        let (encoded, _, _) = ISO_8859_2.encode("Zażółć gęślą jaźń");
+        write_bytes(&file_path, encoded.as_ref());
+
+        let encoding = Encoding::Legacy(LegacyEncoding::Iso8859_2);
+        let mut doc = Document::open_with_encoding(&file_path, encoding).unwrap();
+        assert_eq!(doc.contents(), "Zażółć gęślą jaźń");
+        doc.save().unwrap();
+        assert_eq!(fs::read(&file_path).unwrap(), encoded.as_ref());
+    }
+
+    #[test]
+    fn encoding_labels_round_trip() {
+        for encoding in Encoding::all() {
+            assert_eq!(Encoding::from_label(encoding.name()), Some(encoding));
+        }
+        assert_eq!(
+            Encoding::from_label("cp1251"),
+            Some(Encoding::Legacy(LegacyEncoding::Windows1251))
+        );
+        assert_eq!(
+            Encoding::from_label("latin1"),
+            Some(Encoding::Legacy(LegacyEncoding::Windows1252))
+        );
+        assert_eq!(Encoding::from_label("utf-16"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::from_label("klingon"), None);
+    }
+
+    #[test]
+    fn open_reports_mixed_line_endings() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("mixed.txt");
+        write_bytes(&file_path, b"a\nb\r\nc\r\nd\re\r\n");
+
+        let mut doc = Document::open(&file_path).unwrap();
+        let stats = doc.line_ending_stats();
+        assert_eq!((stats.lf, stats.crlf, stats.cr), (1, 3, 1));
+        assert_eq!(stats.total(), 5);
+        assert_eq!(doc.line_ending(), LineEnding::CrLf);
+        assert_eq!(doc.line_ending_state(), LineEndingState::Mixed);
+        assert!(!doc.is_dirty());
+
+        doc.normalize_line_endings(LineEnding::Lf);
+        assert!(doc.is_dirty());
+        assert_eq!(doc.line_ending_state(), LineEndingState::Consistent);
+        assert_eq!(doc.line_ending_stats().lf, 5);
+        doc.save().unwrap();
+        assert_eq!(fs::read(&file_path).unwrap(), b"a\nb\nc\nd\ne\n");
+
+        doc.set_contents("x\r\ny\nz\n");
+        assert_eq!(doc.line_ending_state(), LineEndingState::Mixed);
+        assert_eq!(doc.line_ending_stats().total(), 3);
+        doc.set_contents("x\ny\n");
+        assert_eq!(doc.line_ending_state(), LineEndingState::Consistent);
+        assert_eq!(doc.line_ending_stats().lf, 2);
     }
 
     #[test]
@@ -797,6 +1455,51 @@ mod tests {
         assert_eq!(doc.check_disk_state().unwrap(), DiskState::Removed);
     }
 
+    #[test]
+    fn range_edits_update_line_index() {
+        let mut doc = Document::new();
+        doc.set_contents("alpha\nbeta\n");
+        doc.insert(0, "zero\r\n");
+        assert_eq!(doc.contents(), "zero\nalpha\nbeta\n");
+        assert_eq!(doc.line_count(), 4);
+        assert_eq!(doc.line(2), "beta");
+        assert_eq!(doc.line_column("zero\nal".len()), (1, 2));
+
+        doc.remove(0.."zero\n".len());
+        doc.replace_range(0..5, "ALPHA");
+        assert_eq!(doc.contents(), "ALPHA\nbeta\n");
+        assert!(doc.is_dirty());
+    }
+
+    #[test]
+    fn range_edits_keep_line_ending_stats_current() {
+        let mut doc = Document::new();
+        doc.set_line_ending(LineEnding::Lf);
+        doc.set_contents("a\nb\nc\n");
+        doc.insert(0, "x\r\n");
+        let stats = doc.line_ending_stats();
+        assert_eq!((stats.lf, stats.crlf, stats.cr), (3, 1, 0));
+        assert_eq!(doc.line_ending_state(), LineEndingState::Mixed);
+
+        doc.remove(0.."x\na\n".len());
+        assert_eq!(doc.line_ending_stats().total(), 2);
+        assert_eq!(doc.line_ending_stats().total(), doc.line_count() - 1);
+
+        let end = doc.contents().len();
+        doc.replace_range(0..end, "joined");
+        assert_eq!(doc.line_ending_stats().total(), 0);
+        assert_eq!(doc.line_ending_state(), LineEndingState::Consistent);
+        doc.insert(0, "top\n");
+        assert_eq!(
+            doc.line_ending_stats(),
+            LineEndingStats {
+                lf: 1,
+                crlf: 0,
+                cr: 0
+            }
+        );
+    }
+
     fn bytes_to_u16_be(bytes: &[u8]) -> Vec<u16> {
         bytes
             .chunks_exact(2)
@@ -1,3 +1,5 @@
+use crate::text_buffer::TextBuffer;
+
 /// 單一地圖項目。 / Represents one entry in the document minimap.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DocumentMapEntry {
@@ -68,6 +70,55 @@ pub fn collect_metrics(text: &str) -> DocumentMetrics {
     }
 }
 
+/// 以行索引建構迷你地圖，只讀取取樣到的行。 / Builds the document map from a line-indexed buffer, reading only the sampled lines.
+pub fn build_buffer_map(buffer: &TextBuffer, target_segments: usize) -> Vec<DocumentMapEntry> {
+    let mut total_lines = buffer.line_count();
+    if buffer.ends_with_newline() {
+        total_lines -= 1;
+    }
+    if total_lines == 0 {
+        return vec![DocumentMapEntry {
+            line: 0,
+            preview: String::new(),
+        }];
+    }
+
+    let segments = target_segments.max(1);
+    let step = total_lines.div_ceil(segments);
+    (0..total_lines)
+        .step_by(step)
+        .map(|line| DocumentMapEntry {
+            line,
+            preview: summarise_line(&buffer.line_text(line)),
+        })
+        .collect()
+}
+
+/// 逐區塊統計文件資訊，不需展開整份文字。 / Computes metrics chunk by chunk without materialising the text.
+pub fn collect_buffer_metrics(buffer: &TextBuffer) -> DocumentMetrics {
+    let mut word_count = 0usize;
+    let mut in_word = false;
+    for chunk in buffer.chunks() {
+        for ch in chunk.chars() {
+            if ch.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                word_count += 1;
+            }
+        }
+    }
+    let mut line_count = buffer.line_count();
+    if buffer.ends_with_newline() {
+        line_count -= 1;
+    }
+    DocumentMetrics {
+        line_count,
+        word_count,
+        char_count: buffer.len_chars(),
+    }
+}
+
 fn summarise_line(line: &str) -> String {
     const MAX_PREVIEW: usize = 32;
     let trimmed = line.trim();
@@ -99,4 +150,16 @@ mod tests {
         assert_eq!(metrics.word_count, 4);
         assert_eq!(metrics.char_count, 23);
     }
+
+    #[test]
+    fn buffer_variants_match_string_versions() {
+        for text in ["", "hello world\nsecond line", "a\nb\n", "\n\n  x y\n"] {
+            let buffer = TextBuffer::from(text);
+            assert_eq!(collect_buffer_metrics(&buffer), collect_metrics(text));
+            assert_eq!(build_buffer_map(&buffer, 3), build_document_map(text, 3));
+        }
+        let text = (0..100).map(|i| format!("Line {i}\n")).collect::<String>();
+        let buffer = TextBuffer::from(text.as_str());
+        assert_eq!(build_buffer_map(&buffer, 10), build_document_map(&text, 10));
+    }
 }
@@ -2,11 +2,15 @@ use std::cmp::Ordering;
 
 use thiserror::Error;
 
+use crate::history::{EditKind, EditRecord, EditTransaction, UndoHistory};
+use crate::text_buffer::TextBuffer;
+
 /// 描述多重游標環境中的插入點。 / Represents a caret within the editor buffer (optional selection).
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Caret {
     position: usize,
     selection: Option<Selection>,
+    goal_column: Option<usize>,
 }
 
 impl Caret {
@@ -15,6 +19,7 @@ impl Caret {
         Self {
             position,
             selection: None,
+            goal_column: None,
         }
     }
 
@@ -23,9 +28,16 @@ impl Caret {
         Self {
             position,
             selection: Some(selection),
+            goal_column: None,
         }
     }
 
+    /// 設定垂直移動時要回到的欄位。 / Sets the column vertical movement tries to return to.
+    pub fn with_goal_column(mut self, column: usize) -> Self {
+        self.goal_column = Some(column);
+        self
+    }
+
     /// 取得游標所在位置。 / Returns the caret position.
     pub fn position(&self) -> usize {
         self.position
@@ -36,7 +48,21 @@ impl Caret {
         self.selection.as_ref()
     }
 
-    fn edit_range(&self) -> (usize, usize) {
+    /// 垂直移動保留的目標欄位（若有）。 / Column kept across vertical moves, if any.
+    pub fn goal_column(&self) -> Option<usize> {
+        self.goal_column
+    }
+
+    /// 選取的固定端；無選取時為游標本身。 / Fixed end of the selection, or the caret itself without one.
+    pub fn anchor(&self) -> usize {
+        match &self.selection {
+            Some(selection) if self.position == selection.start => selection.end,
+            Some(selection) => selection.start,
+            None => self.position,
+        }
+    }
+
+    pub(crate) fn edit_range(&self) -> (usize, usize) {
         if let Some(selection) = &self.selection {
             (selection.start, selection.end)
         } else {
@@ -47,6 +73,7 @@ impl Caret {
     fn set_position(&mut self, position: usize) {
         self.position = position;
         self.selection = None;
+        self.goal_column = None;
     }
 
     fn clamp(&mut self, len: usize) {
@@ -124,8 +151,9 @@ pub enum EditorError {
 /// 具備多重游標與基本編輯操作的文字緩衝。 / Text buffer supporting multi-caret editing primitives.
 #[derive(Debug, Clone)]
 pub struct EditorBuffer {
-    contents: String,
+    contents: TextBuffer,
     carets: Vec<Caret>,
+    history: UndoHistory,
 }
 
 /// 描述一段待取代的編輯操作。 / Represents a replacement operation within the buffer.
@@ -140,26 +168,56 @@ impl EditorBuffer {
     /// 從給定文字建立緩衝區，預設建立單一游標在開頭。 / Creates a buffer with a single caret at the start.
     pub fn new(text: impl Into<String>) -> Self {
         Self {
-            contents: text.into(),
+            contents: TextBuffer::from(text.into()),
             carets: vec![Caret::new(0)],
+            history: UndoHistory::default(),
         }
     }
 
     /// 以指定的游標集合建立緩衝區。 / Creates a buffer with explicit caret positions.
     pub fn with_carets(text: impl Into<String>, carets: Vec<Caret>) -> Result<Self, EditorError> {
         let buffer = Self {
-            contents: text.into(),
+            contents: TextBuffer::from(text.into()),
             carets,
+            history: UndoHistory::default(),
         };
         buffer.validate_carets()?;
         Ok(buffer)
     }
 
-    /// 取得目前的內容。 / Returns the current buffer contents.
+    /// 取得目前的內容；會展開全文，僅供一次性匯出。 / Returns the current contents; flattens the text, so use it for one-off export only.
     pub fn contents(&self) -> &str {
+        self.contents.as_str()
+    }
+
+    /// 取得底層 rope 緩衝，可做對數時間的行查詢。 / Returns the underlying rope buffer for logarithmic line queries.
+    pub fn text(&self) -> &TextBuffer {
         &self.contents
     }
 
+    /// 緩衝的編輯版本號，見 [`TextBuffer::revision`]。 / Edit revision of the text, see [`TextBuffer::revision`].
+    pub fn revision(&self) -> u64 {
+        self.contents.revision()
+    }
+
+    /// 行數（結尾換行後的空行也計入）。 / Number of lines, including the empty line after a trailing newline.
+    pub fn line_count(&self) -> usize {
+        self.contents.line_count()
+    }
+
+    /// 將位元組位置轉為 0 起算的（行, 字元欄）。 / Converts a byte offset into zero-based (line, character column).
+    pub fn line_column(&self, position: usize) -> (usize, usize) {
+        self.contents.line_column(position)
+    }
+
+    /// 取得每個游標所在的（行, 欄）。 / Returns the zero-based (line, column) of every caret.
+    pub fn caret_line_columns(&self) -> Vec<(usize, usize)> {
+        self.carets
+            .iter()
+            .map(|caret| self.contents.line_column(caret.position()))
+            .collect()
+    }
+
     /// 取得所有游標。 / Returns all carets.
     pub fn carets(&self) -> &[Caret] {
         &self.carets
@@ -172,7 +230,12 @@ impl EditorBuffer {
 
     /// 取代每個游標範圍為給定文字。 / Replaces each caret selection with the provided text.
     pub fn insert_text(&mut self, text: &str) -> Result<(), EditorError> {
-        self.apply_replacements(|_, caret| {
+        let kind = if text.is_empty() || text.contains('\n') {
+            EditKind::Edit
+        } else {
+            EditKind::Typing
+        };
+        self.apply_replacements(kind, |_, caret| {
             let (start, end) = caret.edit_range();
             Ok(Replacement {
                 start,
@@ -185,7 +248,7 @@ impl EditorBuffer {
 
     /// 模擬 Backspace：若有選取則刪除選取，否則刪除游標前一個字元。 / Simulates a backspace operation.
     pub fn delete_backward(&mut self) -> Result<(), EditorError> {
-        self.apply_replacements(|text, caret| {
+        self.apply_replacements(EditKind::Deletion, |text, caret| {
             let (start, end) = caret.edit_range();
             if start != end {
                 return Ok(Replacement {
@@ -203,7 +266,7 @@ impl EditorBuffer {
                     owner: None,
                 });
             }
-            let prev = prev_grapheme_boundary(text, start).unwrap_or(0);
+            let prev = text.prev_char_boundary(start).unwrap_or(0);
             Ok(Replacement {
                 start: prev,
                 end: start,
@@ -215,7 +278,7 @@ impl EditorBuffer {
 
     /// 模擬 Delete 行為：刪除選取或游標後一個字元。 / Simulates a forward-delete operation.
     pub fn delete_forward(&mut self) -> Result<(), EditorError> {
-        self.apply_replacements(|text, caret| {
+        self.apply_replacements(EditKind::Deletion, |text, caret| {
             let (start, end) = caret.edit_range();
             if start != end {
                 return Ok(Replacement {
@@ -233,7 +296,7 @@ impl EditorBuffer {
                     owner: None,
                 });
             }
-            let next = next_grapheme_boundary(text, start).unwrap_or(text.len());
+            let next = text.next_char_boundary(start).unwrap_or(text.len());
             Ok(Replacement {
                 start,
                 end: next,
@@ -292,16 +355,121 @@ impl EditorBuffer {
             })
             .collect();
 
-        self.commit_replacements(replacements, None)?;
+        let carets_before = self.carets.clone();
+        let (_, edits) = self.commit_replacements(replacements, None)?;
         self.clamp_carets();
+        self.history
+            .record(EditKind::Edit, carets_before, edits, self.carets.clone());
         Ok(())
     }
 
-    fn apply_replacements<F>(&mut self, mut plan: F) -> Result<(), EditorError>
+    /// 開始一個交易，直到對應的 [`end_transaction`](Self::end_transaction) 前的編輯都合併為單一復原步驟。 / Opens a transaction; edits until the matching `end_transaction` undo as one step.
+    pub fn begin_transaction(&mut self) {
+        self.history.begin(self.carets.clone());
+    }
+
+    /// 結束交易；最外層結束時提交群組並回傳是否產生新步驟。 / Closes a transaction, committing the group when the outermost level ends.
+    pub fn end_transaction(&mut self) -> bool {
+        let carets = self.carets.clone();
+        self.history.end(carets)
+    }
+
+    /// 於交易中執行閉包；失敗時只回滾此層交易內已套用的編輯。 / Runs the closure inside a transaction, rolling back only the edits made at this level on failure.
+    pub fn transaction<T, F>(&mut self, apply: F) -> Result<T, EditorError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, EditorError>,
+    {
+        self.begin_transaction();
+        match apply(self) {
+            Ok(value) => {
+                self.end_transaction();
+                Ok(value)
+            }
+            Err(err) => {
+                if let Some(partial) = self.history.abort() {
+                    self.revert(&partial);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// 復原最近一個步驟並還原當時的游標。 / Undoes the most recent step, restoring the carets captured before it.
+    ///
+    /// 交易進行中時不做任何事並回傳 `false`。 / Returns `false` without changes while a transaction is open.
+    pub fn undo(&mut self) -> bool {
+        if self.history.in_transaction() {
+            return false;
+        }
+        let Some(step) = self.history.pop_undo() else {
+            return false;
+        };
+        self.revert(&step);
+        self.history.push_redo(step);
+        true
+    }
+
+    /// 重做最近一次復原的步驟。 / Re-applies the most recently undone step.
+    pub fn redo(&mut self) -> bool {
+        if self.history.in_transaction() {
+            return false;
+        }
+        let Some(step) = self.history.pop_redo() else {
+            return false;
+        };
+        for edit in step.edits() {
+            let end = edit.start + edit.removed.len();
+            self.contents.replace(edit.start..end, &edit.inserted);
+        }
+        self.carets = step.carets_after().to_vec();
+        self.history.push_undo(step);
+        true
+    }
+
+    /// 是否有可復原的步驟。 / Indicates whether an undo step is available.
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// 是否有可重做的步驟。 / Indicates whether a redo step is available.
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// 由舊到新列出可復原的步驟。 / Lists undoable steps from oldest to newest.
+    pub fn undo_history(&self) -> &[EditTransaction] {
+        self.history.undo_steps()
+    }
+
+    /// 中斷連續輸入的合併，下一次輸入會成為新步驟。 / Stops coalescing so the next keystroke starts a new undo step.
+    pub fn break_undo_coalescing(&mut self) {
+        self.history.break_coalescing();
+    }
+
+    /// 設定保留的復原步驟上限（至少 1）。 / Sets the maximum number of retained undo steps (minimum 1).
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// 清除所有復原/重做紀錄。 / Discards all undo and redo history.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    fn revert(&mut self, step: &EditTransaction) {
+        for edit in step.edits().iter().rev() {
+            let end = edit.start + edit.inserted.len();
+            self.contents.replace(edit.start..end, &edit.removed);
+        }
+        self.carets = step.carets_before().to_vec();
+    }
+
+    fn apply_replacements<F>(&mut self, kind: EditKind, mut plan: F) -> Result<(), EditorError>
     where
-        F: FnMut(&str, &Caret) -> Result<Replacement, EditorError>,
+        F: FnMut(&TextBuffer, &Caret) -> Result<Replacement, EditorError>,
     {
         self.validate_carets()?;
+        let carets_before = self.carets.clone();
 
         let mut replacements = Vec::with_capacity(self.carets.len());
         for (idx, caret) in self.carets.iter().enumerate() {
@@ -310,10 +478,13 @@ impl EditorBuffer {
             replacements.push(replacement);
         }
 
-        let new_positions = self.commit_replacements(replacements, Some(self.carets.len()))?;
+        let (new_positions, edits) =
+            self.commit_replacements(replacements, Some(self.carets.len()))?;
         for (idx, pos) in new_positions.into_iter().enumerate() {
             self.carets[idx].set_position(pos);
         }
+        self.history
+            .record(kind, carets_before, edits, self.carets.clone());
         Ok(())
     }
 
@@ -321,7 +492,7 @@ impl EditorBuffer {
         &mut self,
         mut replacements: Vec<Replacement>,
         owners: Option<usize>,
-    ) -> Result<Vec<usize>, EditorError> {
+    ) -> Result<(Vec<usize>, Vec<EditRecord>), EditorError> {
         // 確保取代區段不重疊。 / Ensure edits do not overlap.
         let mut order: Vec<usize> = (0..replacements.len()).collect();
         order.sort_by(|&a, &b| {
@@ -339,6 +510,7 @@ impl EditorBuffer {
         }
 
         let mut offset: isize = 0;
+        let mut edits = Vec::with_capacity(order.len());
         let mut new_positions = vec![];
         if let Some(count) = owners {
             new_positions.resize(count, 0);
@@ -348,8 +520,14 @@ impl EditorBuffer {
             let replacement = &mut replacements[index];
             let adjusted_start = (replacement.start as isize + offset).max(0) as usize;
             let adjusted_end = (replacement.end as isize + offset).max(0) as usize;
+            let removed = self.contents.slice(adjusted_start..adjusted_end);
             self.contents
-                .replace_range(adjusted_start..adjusted_end, &replacement.text);
+                .replace(adjusted_start..adjusted_end, &replacement.text);
+            edits.push(EditRecord {
+                start: adjusted_start,
+                removed,
+                inserted: replacement.text.clone(),
+            });
 
             let delta =
                 replacement.text.len() as isize - (replacement.end - replacement.start) as isize;
@@ -358,7 +536,7 @@ impl EditorBuffer {
                 new_positions[owner_idx] = adjusted_start + replacement.text.len();
             }
         }
-        Ok(new_positions)
+        Ok((new_positions, edits))
     }
 
     fn validate_carets(&self) -> Result<(), EditorError> {
@@ -404,28 +582,6 @@ struct Replacement {
     owner: Option<usize>,
 }
 
-fn prev_grapheme_boundary(text: &str, index: usize) -> Option<usize> {
-    if index == 0 || index > text.len() {
-        return None;
-    }
-    text[..index].char_indices().last().map(|(idx, _)| idx)
-}
-
-fn next_grapheme_boundary(text: &str, index: usize) -> Option<usize> {
-    if index >= text.len() {
-        return None;
-    }
-    for (idx, ch) in text[index..].char_indices() {
-        if idx != 0 {
-            return Some(index + idx);
-        }
-        if ch.len_utf8() > 0 {
-            return Some(index + ch.len_utf8());
-        }
-    }
-    Some(text.len())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -500,4 +656,140 @@ mod tests {
             .unwrap();
         assert_eq!(buffer.contents(), "LINE1\nLINE2\nline3");
     }
+
+    #[test]
+    fn consecutive_typing_undoes_as_one_step() {
+        let mut buffer = EditorBuffer::new("");
+        for ch in ["a", "b", "c"] {
+            buffer.insert_text(ch).unwrap();
+        }
+        buffer.insert_newline().unwrap();
+        buffer.insert_text("d").unwrap();
+        assert_eq!(buffer.undo_history().len(), 3);
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "abc\n");
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "abc");
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "");
+        assert_eq!(buffer.carets()[0].position(), 0);
+        assert!(!buffer.undo());
+
+        assert!(buffer.redo());
+        assert_eq!(buffer.contents(), "abc");
+        assert_eq!(buffer.carets()[0].position(), 3);
+    }
+
+    #[test]
+    fn moving_carets_breaks_coalescing() {
+        let mut buffer = EditorBuffer::new("xy");
+        buffer.insert_text("a").unwrap();
+        buffer.set_carets(vec![Caret::new(3)]).unwrap();
+        buffer.insert_text("b").unwrap();
+        assert_eq!(buffer.contents(), "axyb");
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "axy");
+        assert_eq!(buffer.carets(), &[Caret::new(3)]);
+    }
+
+    #[test]
+    fn undo_restores_multi_caret_selection() {
+        let carets = vec![
+            Caret::with_selection(5, Selection::new(0, 5)),
+            Caret::new("alpha\nbeta".len()),
+        ];
+        let mut buffer = EditorBuffer::with_carets("alpha\nbeta", carets.clone()).unwrap();
+        buffer.insert_text("X").unwrap();
+        buffer.delete_backward().unwrap();
+        assert_eq!(buffer.contents(), "\nbeta");
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "X\nbetaX");
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "alpha\nbeta");
+        assert_eq!(buffer.carets(), carets.as_slice());
+        assert!(buffer.can_redo());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut buffer = EditorBuffer::new("");
+        buffer.insert_text("a").unwrap();
+        buffer.undo();
+        buffer.insert_text("b").unwrap();
+        assert!(!buffer.can_redo());
+        assert!(!buffer.redo());
+        assert_eq!(buffer.contents(), "b");
+    }
+
+    #[test]
+    fn transaction_groups_and_rolls_back() {
+        let mut buffer = EditorBuffer::new("one two");
+        buffer
+            .transaction(|buffer| {
+                buffer.apply_edit_plan(vec![EditOperation {
+                    start: 0,
+                    end: 3,
+                    text: "1".into(),
+                }])?;
+                buffer.apply_edit_plan(vec![EditOperation {
+                    start: 2,
+                    end: 5,
+                    text: "2".into(),
+                }])
+            })
+            .unwrap();
+        assert_eq!(buffer.contents(), "1 2");
+        assert_eq!(buffer.undo_history().len(), 1);
+        assert_eq!(buffer.undo_history()[0].kind(), EditKind::Group);
+
+        let result = buffer.transaction(|buffer| {
+            buffer.insert_text("zz")?;
+            buffer.set_carets(vec![Caret::new(99)])
+        });
+        assert!(result.is_err());
+        assert_eq!(buffer.contents(), "1 2");
+        assert_eq!(buffer.undo_history().len(), 1);
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "one two");
+    }
+
+    #[test]
+    fn nested_rollback_restores_its_savepoint_only() {
+        let mut buffer = EditorBuffer::new("abc");
+        buffer.set_carets(vec![Caret::new(3)]).unwrap();
+        buffer
+            .transaction(|buffer| {
+                buffer.insert_text("1")?;
+                let inner = buffer.transaction(|buffer| {
+                    buffer.insert_text("2")?;
+                    buffer.set_carets(vec![Caret::new(99)])
+                });
+                assert!(inner.is_err());
+                assert_eq!(buffer.contents(), "abc1");
+                assert_eq!(buffer.carets()[0].position(), 4);
+                buffer.insert_text("3")
+            })
+            .unwrap();
+        assert_eq!(buffer.contents(), "abc13");
+        assert_eq!(buffer.undo_history().len(), 1);
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "abc");
+        assert!(buffer.redo());
+        assert_eq!(buffer.contents(), "abc13");
+    }
+
+    #[test]
+    fn history_limit_discards_oldest_steps() {
+        let mut buffer = EditorBuffer::new("");
+        buffer.set_history_limit(2);
+        for text in ["a\n", "b\n", "c\n"] {
+            buffer.insert_text(text).unwrap();
+        }
+        assert_eq!(buffer.undo_history().len(), 2);
+        while buffer.undo() {}
+        assert_eq!(buffer.contents(), "a\n");
+    }
 }
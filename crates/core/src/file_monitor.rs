@@ -63,6 +63,13 @@ impl FileMonitor {
             .map_err(FileMonitorError::from)
     }
 
+    /// 遞迴監看整個資料夾，供工作區索引使用。 / Watches a directory tree recursively, e.g. to keep a workspace index current.
+    pub fn watch_recursive(&mut self, path: impl AsRef<Path>) -> Result<(), FileMonitorError> {
+        self.watcher
+            .watch(path.as_ref(), RecursiveMode::Recursive)
+            .map_err(FileMonitorError::from)
+    }
+
     /// 停止監看指定路徑。 / Stops watching the provided path.
     pub fn unwatch(&mut self, path: impl AsRef<Path>) -> Result<(), FileMonitorError> {
         self.watcher
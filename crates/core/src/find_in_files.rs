@@ -0,0 +1,741 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{WalkBuilder, WalkState};
+use rustnotepad_search::{
+    FileSearchResult, IndexQuery, SearchEngine, SearchError, SearchOptions, SearchScope,
+    TrigramIndex,
+};
+use thiserror::Error;
+
+use crate::document::{decode_text, looks_binary, DocumentError};
+use crate::large_file::{LargeFileDocument, LargeFileOptions};
+use crate::search_index::file_stamp;
+
+/// 判斷二進位檔時檢查的前置位元組數。 / Number of leading bytes sniffed for binary detection.
+const BINARY_SNIFF_LEN: usize = 8 * 1024;
+
+/// 「在檔案中尋找」可能回傳的錯誤。 / Error type for Find in Files runs.
+#[derive(Debug, Error)]
+pub enum FindInFilesError {
+    #[error("invalid file mask `{mask}`: {message}")]
+    InvalidMask { mask: String, message: String },
+    #[error("search failed: {0}")]
+    Search(#[from] SearchError),
+    #[error("search worker panicked")]
+    WorkerPanicked,
+}
+
+/// 決定哪些檔案會被搜尋。 / Decides which files a Find in Files run visits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFilter {
+    /// 僅搜尋符合的檔案；空白代表全部。 / Only files matching one of these masks; empty means all files.
+    pub include: Vec<String>,
+    /// 排除符合的檔案與資料夾。 / Files and directories matching these masks are skipped.
+    pub exclude: Vec<String>,
+    /// 是否包含隱藏檔。 / Whether hidden (dot) files and directories are searched.
+    pub include_hidden: bool,
+    /// 是否遵循 `.gitignore` 與 `.ignore`。 / Whether `.gitignore` and `.ignore` rules apply.
+    pub respect_ignore_files: bool,
+    /// 是否略過二進位檔。 / Whether binary files are skipped.
+    pub skip_binary: bool,
+    /// 大於此位元組數的檔案會被略過。 / Files larger than this many bytes are skipped.
+    pub max_file_size: Option<u64>,
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_hidden: false,
+            respect_ignore_files: true,
+            skip_binary: true,
+            max_file_size: None,
+        }
+    }
+}
+
+impl FileFilter {
+    /// 解析 Notepad++ 風格的遮罩字串，如 `*.rs *.toml !target`。 / Adds Notepad++-style masks such as `*.rs *.toml !target`; `!` marks exclusions.
+    pub fn with_masks(mut self, masks: &str) -> Self {
+        let tokens = masks
+            .split(|c: char| c == ';' || c.is_whitespace())
+            .filter(|token| !token.is_empty());
+        for token in tokens {
+            match token.strip_prefix('!') {
+                Some("") => {}
+                Some(excluded) => self.exclude.push(excluded.to_string()),
+                None => self.include.push(token.to_string()),
+            }
+        }
+        self
+    }
+}
+
+/// 略過檔案的原因。 / Why a candidate file was not searched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    Binary,
+    TooLarge(u64),
+    Undecodable,
+}
+
+/// 搜尋期間逐步回報的事件。 / Incremental event streamed while a search runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FindEvent {
+    Matched(FileSearchResult),
+    Skipped {
+        path: PathBuf,
+        reason: SkipReason,
+    },
+    Failed {
+        path: Option<PathBuf>,
+        message: String,
+    },
+}
+
+/// 一次搜尋的統計資料。 / Totals gathered over one run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindStats {
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub total_matches: usize,
+    pub files_skipped: usize,
+    /// 由索引判定不可能符合而未讀取的檔案。 / Files the index ruled out without reading them.
+    pub files_pruned: usize,
+    pub errors: usize,
+}
+
+impl FindStats {
+    fn record(&mut self, event: &FindEvent) {
+        match event {
+            FindEvent::Matched(result) => {
+                self.files_matched += 1;
+                self.total_matches += result.matches.len();
+            }
+            FindEvent::Skipped { .. } => self.files_skipped += 1,
+            FindEvent::Failed { .. } => self.errors += 1,
+        }
+    }
+}
+
+/// 多執行緒的「在檔案中尋找」驅動程式。 / Multi-threaded Find in Files driver.
+#[derive(Debug, Clone)]
+pub struct FindInFiles {
+    roots: Vec<PathBuf>,
+    filter: FileFilter,
+    threads: usize,
+    index: Option<Arc<TrigramIndex>>,
+    large_files: Option<LargeFileOptions>,
+}
+
+impl FindInFiles {
+    /// 以檔案或資料夾作為搜尋根目錄。 / Creates a driver over the given files or directories.
+    pub fn new<I, P>(roots: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        Self {
+            roots: roots.into_iter().map(Into::into).collect(),
+            filter: FileFilter::default(),
+            threads: 0,
+            index: None,
+            large_files: None,
+        }
+    }
+
+    /// 設定檔案過濾條件。 / Replaces the file filter.
+    pub fn with_filter(mut self, filter: FileFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 設定工作執行緒數量；0 代表自動。 / Sets the worker thread count; 0 picks one automatically.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// 搜尋前以三字元組索引略過不可能符合的檔案；索引過期的檔案仍會被讀取。 / Consults a trigram index to skip files that cannot match; files changed since they were indexed are still read.
+    pub fn with_index(mut self, index: Arc<TrigramIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// 超過門檻的檔案改以巨檔模式分塊搜尋，不整個載入記憶體。 / Searches files above the threshold chunk by chunk in large-file mode instead of loading them whole.
+    pub fn with_large_files(mut self, options: LargeFileOptions) -> Self {
+        self.large_files = Some(options);
+        self
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn filter(&self) -> &FileFilter {
+        &self.filter
+    }
+
+    /// 列出通過過濾的檔案（已排序）；略過與錯誤透過回呼回報。 / Lists the files that pass the filter, sorted; skips and errors go to `on_event`.
+    pub fn collect_files(
+        &self,
+        mut on_event: impl FnMut(FindEvent),
+    ) -> Result<Vec<PathBuf>, FindInFilesError> {
+        let mut files = Vec::new();
+        self.walk(
+            &AtomicBool::new(false),
+            |entry| {
+                Some(match entry {
+                    Ok(path) => screen(path, &self.filter, true).map(|_| path.to_path_buf()),
+                    Err(err) => Err(walk_error(err)),
+                })
+            },
+            |outcome| match outcome {
+                Ok(path) => files.push(path),
+                Err(event) => on_event(event),
+            },
+        )?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// 平行搜尋並在結果產生時立即回呼。 / Searches in parallel, streaming events as files complete.
+    ///
+    /// 事件順序取決於執行緒排程。 / Event order depends on thread scheduling.
+    pub fn run(
+        &self,
+        options: &SearchOptions,
+        on_event: impl FnMut(FindEvent),
+    ) -> Result<FindStats, FindInFilesError> {
+        self.run_until(options, &AtomicBool::new(false), None, on_event)
+    }
+
+    /// 於背景執行緒搜尋，供 GUI 輪詢。 / Runs the search on a background thread for the GUI to poll.
+    pub fn spawn(self, options: SearchOptions) -> FindInFilesTask {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let handle = thread::spawn(move || {
+            self.run_until(&options, &flag, None, |event| {
+                let _ = sender.send(event);
+            })
+        });
+        FindInFilesTask {
+            receiver,
+            cancel,
+            handle: Some(handle),
+        }
+    }
+
+    /// `stale` 收集因索引項目缺少或過期而必須讀取的檔案。 / `stale` collects the files that had to be read because their index entry was missing or out of date.
+    pub(crate) fn run_until(
+        &self,
+        options: &SearchOptions,
+        cancel: &AtomicBool,
+        stale: Option<&Mutex<Vec<PathBuf>>>,
+        mut on_event: impl FnMut(FindEvent),
+    ) -> Result<FindStats, FindInFilesError> {
+        options.validate()?;
+        let mut scoped = options.clone();
+        scoped.scope = SearchScope::EntireDocument;
+        let prune = match &self.index {
+            Some(index) => Some((index.as_ref(), IndexQuery::new(&scoped)?)),
+            None => None,
+        };
+        let searched = AtomicUsize::new(0);
+        let pruned = AtomicUsize::new(0);
+        let mut stats = FindStats::default();
+        self.walk(
+            cancel,
+            |entry| match entry {
+                Ok(path) => {
+                    if let Some((index, query)) = &prune {
+                        if is_ruled_out(path, index, query) {
+                            pruned.fetch_add(1, Ordering::Relaxed);
+                            return None;
+                        }
+                        if let Some(stale) = stale.filter(|_| !is_current(path, index)) {
+                            stale
+                                .lock()
+                                .expect("stale list poisoned")
+                                .push(path.to_path_buf());
+                        }
+                    }
+                    search_file(
+                        path,
+                        &self.filter,
+                        self.large_files.as_ref(),
+                        &scoped,
+                        &searched,
+                    )
+                }
+                Err(err) => Some(walk_error(err)),
+            },
+            |event| {
+                stats.record(&event);
+                on_event(event);
+            },
+        )?;
+        stats.files_searched = searched.into_inner();
+        stats.files_pruned = pruned.into_inner();
+        Ok(stats)
+    }
+
+    /// 以平行走訪器產生檔案，`visit` 在工作執行緒上執行，`sink` 在呼叫端執行緒上執行。 / Walks files in parallel; `visit` runs on workers, `sink` on the calling thread.
+    fn walk<T, V, S>(
+        &self,
+        cancel: &AtomicBool,
+        visit: V,
+        mut sink: S,
+    ) -> Result<(), FindInFilesError>
+    where
+        T: Send,
+        V: Fn(Result<&Path, ignore::Error>) -> Option<T> + Sync,
+        S: FnMut(T),
+    {
+        let Some((first, rest)) = self.roots.split_first() else {
+            return Ok(());
+        };
+        let include = Arc::new(MaskSet::build(&self.filter.include)?);
+        let exclude = Arc::new(MaskSet::build(&self.filter.exclude)?);
+        let roots: Arc<[PathBuf]> = self.roots.clone().into();
+
+        let respect = self.filter.respect_ignore_files;
+        let mut builder = WalkBuilder::new(first);
+        for root in rest {
+            builder.add(root);
+        }
+        builder
+            .hidden(!self.filter.include_hidden)
+            .ignore(respect)
+            .git_ignore(respect)
+            .git_global(respect)
+            .git_exclude(respect)
+            .parents(respect)
+            .require_git(false)
+            .threads(self.threads)
+            .filter_entry(move |entry| {
+                // 明確指定的路徑一律保留。 / Explicitly named paths are always kept.
+                if entry.depth() == 0 {
+                    return true;
+                }
+                let relative = relative_to_root(&roots, entry.path());
+                if exclude.is_match(relative) {
+                    return false;
+                }
+                let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+                is_dir || include.is_empty() || include.is_match(relative)
+            });
+        let walker = builder.build_parallel();
+
+        let (sender, receiver) = mpsc::channel();
+        let visit = &visit;
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                walker.run(|| {
+                    let sender = sender.clone();
+                    Box::new(move |entry| {
+                        if cancel.load(Ordering::Relaxed) {
+                            return WalkState::Quit;
+                        }
+                        let outcome = match entry {
+                            Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_file()) => {
+                                visit(Ok(entry.path()))
+                            }
+                            Ok(_) => None,
+                            Err(err) => visit(Err(err)),
+                        };
+                        match outcome.map(|value| sender.send(value)) {
+                            Some(Err(_)) => WalkState::Quit,
+                            _ => WalkState::Continue,
+                        }
+                    })
+                });
+            });
+            for value in receiver {
+                sink(value);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// 背景搜尋工作。 / Handle to a background search started by [`FindInFiles::spawn`].
+#[derive(Debug)]
+pub struct FindInFilesTask {
+    receiver: Receiver<FindEvent>,
+    cancel: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Result<FindStats, FindInFilesError>>>,
+}
+
+impl FindInFilesTask {
+    /// 取出目前已產生的事件。 / Drains the events produced so far.
+    pub fn drain(&self) -> Vec<FindEvent> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// 要求工作提早停止。 / Asks the workers to stop early.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// 工作結束後回傳統計資料；之後應再呼叫一次 `drain`。 / Returns the totals once the worker has exited; call `drain` once more afterwards.
+    pub fn try_finish(&mut self) -> Option<Result<FindStats, FindInFilesError>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        self.handle.take().map(|handle| {
+            handle
+                .join()
+                .unwrap_or(Err(FindInFilesError::WorkerPanicked))
+        })
+    }
+
+    /// 阻塞直到工作結束。 / Blocks until the worker exits.
+    pub fn wait(mut self) -> Result<FindStats, FindInFilesError> {
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .unwrap_or(Err(FindInFilesError::WorkerPanicked)),
+            None => Ok(FindStats::default()),
+        }
+    }
+}
+
+/// 檔名遮罩比對檔名，含 `/` 的遮罩比對相對路徑。 / Masks without `/` match the file name; masks with `/` match the path relative to its root.
+struct MaskSet {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl MaskSet {
+    fn build(masks: &[String]) -> Result<Self, FindInFilesError> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for mask in masks {
+            let pattern = mask.replace('\\', "/");
+            let anchored = pattern.trim_start_matches('/');
+            let glob = GlobBuilder::new(anchored)
+                .literal_separator(true)
+                .case_insensitive(cfg!(windows))
+                .build()
+                .map_err(|err| FindInFilesError::InvalidMask {
+                    mask: mask.clone(),
+                    message: err.kind().to_string(),
+                })?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+        let build = |builder: GlobSetBuilder| {
+            builder
+                .build()
+                .map_err(|err| FindInFilesError::InvalidMask {
+                    mask: masks.join(" "),
+                    message: err.to_string(),
+                })
+        };
+        Ok(Self {
+            names: build(names)?,
+            paths: build(paths)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.paths.is_empty()
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        relative
+            .file_name()
+            .is_some_and(|name| self.names.is_match(name))
+            || self.paths.is_match(relative)
+    }
+}
+
+fn relative_to_root<'a>(roots: &[PathBuf], path: &'a Path) -> &'a Path {
+    roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path)
+}
+
+fn walk_error(err: ignore::Error) -> FindEvent {
+    FindEvent::Failed {
+        path: None,
+        message: err.to_string(),
+    }
+}
+
+/// 套用大小與二進位檢查並讀入內容；`sniff_only` 時只讀取前段。 / Applies the size and binary checks, reading only the leading bytes when `sniff_only` is set.
+fn screen(path: &Path, filter: &FileFilter, sniff_only: bool) -> Result<Vec<u8>, FindEvent> {
+    let failed = |err: io::Error| FindEvent::Failed {
+        path: Some(path.to_path_buf()),
+        message: err.to_string(),
+    };
+    let skipped = |reason| FindEvent::Skipped {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let mut file = File::open(path).map_err(failed)?;
+    let len = file.metadata().map_err(failed)?.len();
+    if filter.max_file_size.is_some_and(|limit| len > limit) {
+        return Err(skipped(SkipReason::TooLarge(len)));
+    }
+    let mut bytes = Vec::new();
+    if sniff_only {
+        file.take(BINARY_SNIFF_LEN as u64).read_to_end(&mut bytes)
+    } else {
+        file.read_to_end(&mut bytes)
+    }
+    .map_err(failed)?;
+    if filter.skip_binary && looks_binary(&bytes[..bytes.len().min(BINARY_SNIFF_LEN)]) {
+        return Err(skipped(SkipReason::Binary));
+    }
+    Ok(bytes)
+}
+
+/// 讀入並解碼通過過濾的檔案內容。 / Reads and decodes a file that passes the size and binary checks.
+pub(crate) fn load_text(path: &Path, filter: &FileFilter) -> Result<String, FindEvent> {
+    let bytes = screen(path, filter, false)?;
+    decode_text(bytes).map_err(|_| FindEvent::Skipped {
+        path: path.to_path_buf(),
+        reason: SkipReason::Undecodable,
+    })
+}
+
+/// 僅在索引內容仍是最新時才信任其判斷。 / Trusts the index only while its entry is still current.
+fn is_ruled_out(path: &Path, index: &TrigramIndex, query: &IndexQuery) -> bool {
+    index.may_match(path, query) == Some(false) && is_current(path, index)
+}
+
+/// 索引中的項目是否與檔案目前的版本一致。 / Whether the index entry matches the file as it is now.
+fn is_current(path: &Path, index: &TrigramIndex) -> bool {
+    std::fs::metadata(path).is_ok_and(|metadata| index.stamp(path) == Some(file_stamp(&metadata)))
+}
+
+fn search_file(
+    path: &Path,
+    filter: &FileFilter,
+    large_files: Option<&LargeFileOptions>,
+    options: &SearchOptions,
+    searched: &AtomicUsize,
+) -> Option<FindEvent> {
+    if let Some(large_files) = large_files {
+        let len = std::fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        if large_files.is_large(len) {
+            return match screen(path, filter, true) {
+                Ok(_) => search_large_file(path, large_files, options, searched),
+                Err(event) => Some(event),
+            };
+        }
+    }
+    let text = match load_text(path, filter) {
+        Ok(text) => text,
+        Err(event) => return Some(event),
+    };
+    searched.fetch_add(1, Ordering::Relaxed);
+    match SearchEngine::new(&text).find_all(options) {
+        Ok(matches) if matches.is_empty() => None,
+        Ok(matches) => Some(FindEvent::Matched(FileSearchResult::new(
+            Some(path.to_path_buf()),
+            matches,
+        ))),
+        Err(err) => Some(FindEvent::Failed {
+            path: Some(path.to_path_buf()),
+            message: err.to_string(),
+        }),
+    }
+}
+
+fn search_large_file(
+    path: &Path,
+    large_files: &LargeFileOptions,
+    options: &SearchOptions,
+    searched: &AtomicUsize,
+) -> Option<FindEvent> {
+    let report = LargeFileDocument::open(path, large_files).and_then(|document| {
+        searched.fetch_add(1, Ordering::Relaxed);
+        document.search(options)
+    });
+    match report {
+        Ok(report) => report.results.into_iter().next().map(FindEvent::Matched),
+        // 巨檔模式只接受與 ASCII 相容的文字編碼。 / Large-file mode only accepts ASCII-compatible text encodings.
+        Err(DocumentError::InvalidEncoding) => Some(FindEvent::Skipped {
+            path: path.to_path_buf(),
+            reason: SkipReason::Undecodable,
+        }),
+        Err(err) => Some(FindEvent::Failed {
+            path: Some(path.to_path_buf()),
+            message: err.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::write(root.join(".gitignore"), "ignored.txt\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() { needle(); }\n").unwrap();
+        fs::write(root.join("src/nested/util.rs"), "// needle\n// needle\n").unwrap();
+        fs::write(root.join("notes.txt"), "needle in notes\r\n").unwrap();
+        fs::write(root.join("ignored.txt"), "needle\n").unwrap();
+        fs::write(root.join("target/out.rs"), "needle\n").unwrap();
+        fs::write(root.join(".hidden/secret.rs"), "needle\n").unwrap();
+        fs::write(root.join("blob.bin"), b"needle\0\x01\x02").unwrap();
+        dir
+    }
+
+    fn matched_files(driver: &FindInFiles, root: &Path) -> (Vec<String>, FindStats) {
+        let mut files = Vec::new();
+        let stats = driver
+            .run(&SearchOptions::new("needle"), |event| {
+                if let FindEvent::Matched(result) = event {
+                    let path = result.path.unwrap();
+                    let relative = path.strip_prefix(root).unwrap().to_string_lossy();
+                    files.push(relative.replace('\\', "/"));
+                }
+            })
+            .unwrap();
+        files.sort();
+        (files, stats)
+    }
+
+    #[test]
+    fn default_filter_honours_gitignore_hidden_and_binary() {
+        let dir = tree();
+        let driver = FindInFiles::new([dir.path()]).with_threads(2);
+        let (files, stats) = matched_files(&driver, dir.path());
+        assert_eq!(
+            files,
+            [
+                "notes.txt",
+                "src/main.rs",
+                "src/nested/util.rs",
+                "target/out.rs"
+            ]
+        );
+        assert_eq!(stats.files_matched, 4);
+        assert_eq!(stats.total_matches, 5);
+        assert_eq!(stats.files_skipped, 1);
+
+        let everything = driver.clone().with_filter(FileFilter {
+            include_hidden: true,
+            respect_ignore_files: false,
+            skip_binary: false,
+            ..FileFilter::default()
+        });
+        let (files, _) = matched_files(&everything, dir.path());
+        assert_eq!(files.len(), 7);
+    }
+
+    #[test]
+    fn masks_limit_and_prune_the_walk() {
+        let dir = tree();
+        let driver = FindInFiles::new([dir.path()])
+            .with_filter(FileFilter::default().with_masks("*.rs; !target"));
+        let (files, _) = matched_files(&driver, dir.path());
+        assert_eq!(files, ["src/main.rs", "src/nested/util.rs"]);
+
+        let nested = FindInFiles::new([dir.path()])
+            .with_filter(FileFilter::default().with_masks("src/nested/*.rs"));
+        let (files, _) = matched_files(&nested, dir.path());
+        assert_eq!(files, ["src/nested/util.rs"]);
+
+        let listed = driver.collect_files(|_| {}).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed[0].ends_with("main.rs"));
+
+        let err = FindInFiles::new([dir.path()])
+            .with_filter(FileFilter::default().with_masks("src/[.rs"))
+            .collect_files(|_| {})
+            .unwrap_err();
+        assert!(matches!(err, FindInFilesError::InvalidMask { .. }));
+    }
+
+    #[test]
+    fn oversized_files_are_skipped_and_reported() {
+        let dir = tree();
+        let mut skipped = Vec::new();
+        let driver = FindInFiles::new([dir.path().join("src")]).with_filter(FileFilter {
+            max_file_size: Some(20),
+            ..FileFilter::default()
+        });
+        driver
+            .run(&SearchOptions::new("needle"), |event| {
+                if let FindEvent::Skipped { path, reason } = event {
+                    skipped.push((path.file_name().unwrap().to_owned(), reason));
+                }
+            })
+            .unwrap();
+        assert_eq!(skipped, [("main.rs".into(), SkipReason::TooLarge(24))]);
+    }
+
+    #[test]
+    fn files_above_the_large_file_threshold_are_searched_in_chunks() {
+        let dir = tree();
+        let collect = |driver: FindInFiles| {
+            let mut results = Vec::new();
+            driver
+                .run(&SearchOptions::new("needle"), |event| {
+                    if let FindEvent::Matched(result) = event {
+                        results.push(result);
+                    }
+                })
+                .unwrap();
+            results.sort_by(|left, right| left.path.cmp(&right.path));
+            results
+        };
+        let lazy = FindInFiles::new([dir.path()]).with_large_files(LargeFileOptions {
+            threshold_bytes: 0,
+            ..LargeFileOptions::default()
+        });
+        assert_eq!(collect(lazy), collect(FindInFiles::new([dir.path()])));
+    }
+
+    #[test]
+    fn spawned_task_streams_results() {
+        let dir = tree();
+        let mut task = FindInFiles::new([dir.path()]).spawn(SearchOptions::new("needle"));
+        let mut events = Vec::new();
+        let stats = loop {
+            events.extend(task.drain());
+            if let Some(result) = task.try_finish() {
+                events.extend(task.drain());
+                break result.unwrap();
+            }
+            thread::yield_now();
+        };
+        let matched = events
+            .iter()
+            .filter(|event| matches!(event, FindEvent::Matched(_)))
+            .count();
+        assert_eq!(matched, stats.files_matched);
+        assert_eq!(stats.files_searched, 4);
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::BTreeMap;
+
+/// 偵測文件中的可折疊區段。 / Detects foldable regions in a document.
+pub trait FoldProvider: Send + Sync {
+    /// 回傳 `(起始行, 結束行)` 範圍，結束行包含在內。 / Returns `(start_line, end_line)` ranges with an inclusive end.
+    fn fold_ranges(&self, text: &str) -> Vec<(usize, usize)>;
+}
+
+/// 語言的註解語法。 / Comment syntax of a language.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommentSyntax {
+    pub line: Vec<String>,
+    pub block: Option<(String, String)>,
+}
+
+impl CommentSyntax {
+    /// 建立註解語法。 / Builds comment syntax from line markers and an optional block pair.
+    pub fn new(line: &[&str], block: Option<(&str, &str)>) -> Self {
+        Self {
+            line: line.iter().map(|marker| marker.to_string()).collect(),
+            block: block.map(|(start, end)| (start.to_string(), end.to_string())),
+        }
+    }
+
+    fn line_marker<'a>(&self, body: &'a str) -> Option<&'a str> {
+        self.line
+            .iter()
+            .filter(|marker| !marker.is_empty())
+            .find_map(|marker| body.strip_prefix(marker.as_str()))
+    }
+}
+
+/// 以跨行的成對括號折疊，略過註解與字串內的括號。 / Folds between matching brackets on different lines, ignoring brackets in comments and strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketFolds {
+    pub pairs: Vec<(char, char)>,
+    pub comments: CommentSyntax,
+    pub quotes: Vec<char>,
+}
+
+impl FoldProvider for BracketFolds {
+    fn fold_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        let mut open: Vec<(char, usize)> = Vec::new();
+        scan(text, &self.comments, &self.quotes, |line, lexeme| {
+            let Lexeme::Code(ch) = lexeme else {
+                return;
+            };
+            if let Some(&(_, close)) = self.pairs.iter().find(|(opener, _)| *opener == ch) {
+                open.push((close, line));
+            } else if open.last().is_some_and(|(close, _)| *close == ch) {
+                if let Some((_, start)) = open.pop().filter(|(_, start)| line > *start) {
+                    regions.push((start, line));
+                }
+            }
+        });
+        regions
+    }
+}
+
+/// 依縮排折疊（Python、YAML），空白行與純註解行不影響區段。 / Folds by indentation (Python, YAML); blank and comment-only lines do not end a region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndentFolds {
+    pub tab_width: usize,
+    pub comments: CommentSyntax,
+}
+
+impl FoldProvider for IndentFolds {
+    fn fold_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        let mut last_content: Option<usize> = None;
+        for (line_no, line) in text.split('\n').enumerate() {
+            let body = line.trim();
+            if body.is_empty() || self.comments.line_marker(body).is_some() {
+                continue;
+            }
+            let indent = indent_width(line, self.tab_width);
+            while let Some(&(top_indent, start)) = open.last() {
+                if indent > top_indent {
+                    break;
+                }
+                open.pop();
+                if let Some(end) = last_content.filter(|end| *end > start) {
+                    regions.push((start, end));
+                }
+            }
+            open.push((indent, line_no));
+            last_content = Some(line_no);
+        }
+        for (_, start) in open {
+            if let Some(end) = last_content.filter(|end| *end > start) {
+                regions.push((start, end));
+            }
+        }
+        regions
+    }
+}
+
+/// 折疊跨行區塊註解與連續的整行註解。 / Folds multi-line block comments and runs of whole-line comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentFolds {
+    pub comments: CommentSyntax,
+    pub quotes: Vec<char>,
+}
+
+impl FoldProvider for CommentFolds {
+    fn fold_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        let mut block_start = None;
+        let mut code_line = None;
+        let mut run: Option<(usize, usize)> = None;
+        scan(
+            text,
+            &self.comments,
+            &self.quotes,
+            |line, lexeme| match lexeme {
+                Lexeme::Code(ch) => {
+                    if !ch.is_whitespace() {
+                        code_line = Some(line);
+                        close_run(&mut run, &mut regions);
+                    }
+                }
+                Lexeme::LineComment => {
+                    if code_line == Some(line) {
+                        return;
+                    }
+                    match run {
+                        Some((start, end)) if end + 1 == line => run = Some((start, line)),
+                        _ => {
+                            close_run(&mut run, &mut regions);
+                            run = Some((line, line));
+                        }
+                    }
+                }
+                Lexeme::BlockOpen => block_start = Some(line),
+                Lexeme::BlockClose => {
+                    if let Some(start) = block_start.take().filter(|start| line > *start) {
+                        regions.push((start, line));
+                    }
+                }
+            },
+        );
+        close_run(&mut run, &mut regions);
+        regions
+    }
+}
+
+fn close_run(run: &mut Option<(usize, usize)>, regions: &mut Vec<(usize, usize)>) {
+    if let Some((start, end)) = run.take().filter(|(start, end)| end > start) {
+        regions.push((start, end));
+    }
+}
+
+/// 以 `#region` / `// region` 與 `#endregion` / `// endregion` 標記折疊。 / Folds between `#region` / `// region` and `#endregion` / `// endregion` markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionMarkerFolds {
+    pub comments: CommentSyntax,
+}
+
+impl FoldProvider for RegionMarkerFolds {
+    fn fold_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        let mut open = Vec::new();
+        for (line_no, line) in text.split('\n').enumerate() {
+            match self.marker(line) {
+                Some(RegionMarker::Start) => open.push(line_no),
+                Some(RegionMarker::End) => {
+                    if let Some(start) = open.pop().filter(|start| line_no > *start) {
+                        regions.push((start, line_no));
+                    }
+                }
+                None => {}
+            }
+        }
+        regions
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegionMarker {
+    Start,
+    End,
+}
+
+impl RegionMarkerFolds {
+    fn marker(&self, line: &str) -> Option<RegionMarker> {
+        let body = line.trim();
+        let block_start = self
+            .comments
+            .block
+            .as_ref()
+            .map(|(start, _)| start.as_str());
+        let body = self
+            .comments
+            .line_marker(body)
+            .or_else(|| block_start.and_then(|start| body.strip_prefix(start)))
+            .or_else(|| body.starts_with('#').then_some(body))?
+            .trim_start();
+        let body = body.strip_prefix('#').unwrap_or(body);
+        let body = body.strip_prefix("pragma ").unwrap_or(body).trim_start();
+        let body = body.to_ascii_lowercase();
+        let words: Vec<&str> = body.split_whitespace().take(2).collect();
+        match words.as_slice() {
+            ["region", ..] => Some(RegionMarker::Start),
+            ["endregion", ..] | ["end", "region", ..] => Some(RegionMarker::End),
+            _ => None,
+        }
+    }
+}
+
+/// 語言的結構折疊方式。 / How a language's structural folds are detected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FoldStrategy {
+    /// 成對括號。 / Matching brackets.
+    #[default]
+    Brackets,
+    /// 縮排層級。 / Indentation levels.
+    Indentation,
+    /// 不偵測結構折疊。 / No structural folds.
+    None,
+}
+
+/// 單一語言的折疊偵測設定。 / Fold detection settings for one language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoldConfig {
+    pub strategy: FoldStrategy,
+    pub brackets: Vec<(char, char)>,
+    pub comments: CommentSyntax,
+    pub quotes: Vec<char>,
+    pub tab_width: usize,
+    /// 是否折疊註解區塊。 / Whether comment blocks fold.
+    pub fold_comments: bool,
+    /// 是否辨識 `#region` 標記。 / Whether `#region` markers fold.
+    pub region_markers: bool,
+}
+
+impl Default for FoldConfig {
+    fn default() -> Self {
+        Self {
+            strategy: FoldStrategy::None,
+            brackets: vec![('{', '}'), ('[', ']'), ('(', ')')],
+            comments: CommentSyntax::default(),
+            quotes: vec!['"'],
+            tab_width: 4,
+            fold_comments: false,
+            region_markers: false,
+        }
+    }
+}
+
+impl FoldConfig {
+    /// 依語言識別碼選擇折疊策略；未知語言不折疊。 / Picks the fold strategy for a language id; unknown languages do not fold.
+    pub fn for_language(language_id: &str) -> Self {
+        let c_like = CommentSyntax::new(&["//"], Some(("/*", "*/")));
+        let code = |strategy, comments, quotes: &[char]| Self {
+            strategy,
+            comments,
+            quotes: quotes.to_vec(),
+            fold_comments: true,
+            region_markers: true,
+            ..Self::default()
+        };
+        match language_id.to_ascii_lowercase().as_str() {
+            "rust" => code(FoldStrategy::Brackets, c_like, &['"']),
+            "c" | "cpp" | "csharp" | "cs" | "java" | "javascript" | "typescript" | "go"
+            | "kotlin" | "swift" | "php" | "css" => {
+                code(FoldStrategy::Brackets, c_like, &['"', '\''])
+            }
+            "json" => Self {
+                strategy: FoldStrategy::Brackets,
+                ..Self::default()
+            },
+            "python" | "yaml" => code(
+                FoldStrategy::Indentation,
+                CommentSyntax::new(&["#"], None),
+                &['"', '\''],
+            ),
+            _ => Self::default(),
+        }
+    }
+
+    /// 依設定建立折疊偵測器。 / Builds the providers this configuration enables.
+    pub fn providers(&self) -> Vec<Box<dyn FoldProvider>> {
+        let mut providers: Vec<Box<dyn FoldProvider>> = Vec::new();
+        match self.strategy {
+            FoldStrategy::Brackets => providers.push(Box::new(BracketFolds {
+                pairs: self.brackets.clone(),
+                comments: self.comments.clone(),
+                quotes: self.quotes.clone(),
+            })),
+            FoldStrategy::Indentation => providers.push(Box::new(IndentFolds {
+                tab_width: self.tab_width,
+                comments: self.comments.clone(),
+            })),
+            FoldStrategy::None => {}
+        }
+        if self.fold_comments {
+            providers.push(Box::new(CommentFolds {
+                comments: self.comments.clone(),
+                quotes: self.quotes.clone(),
+            }));
+        }
+        if self.region_markers {
+            providers.push(Box::new(RegionMarkerFolds {
+                comments: self.comments.clone(),
+            }));
+        }
+        providers
+    }
+
+    /// 合併所有偵測器的結果，同一起始行保留最長的區段。 / Merges every provider's ranges, keeping the longest region per start line.
+    pub fn detect(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut regions: BTreeMap<usize, usize> = BTreeMap::new();
+        for provider in self.providers() {
+            for (start, end) in provider.fold_ranges(text) {
+                let entry = regions.entry(start).or_insert(end);
+                *entry = (*entry).max(end);
+            }
+        }
+        regions.into_iter().collect()
+    }
+}
+
+fn indent_width(line: &str, tab_width: usize) -> usize {
+    line.chars()
+        .take_while(|ch| *ch == ' ' || *ch == '\t')
+        .map(|ch| if ch == '\t' { tab_width.max(1) } else { 1 })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lexeme {
+    Code(char),
+    LineComment,
+    BlockOpen,
+    BlockClose,
+}
+
+/// 逐字掃描程式碼並回報所在行，略過註解與字串內容。 / Walks the code characters with their line numbers, skipping comment and string contents.
+fn scan(
+    text: &str,
+    comments: &CommentSyntax,
+    quotes: &[char],
+    mut visit: impl FnMut(usize, Lexeme),
+) {
+    let block = comments
+        .block
+        .as_ref()
+        .filter(|(start, end)| !start.is_empty() && !end.is_empty());
+    let mut line = 0;
+    let mut idx = 0;
+    let mut quote: Option<char> = None;
+    let mut in_block = false;
+    while let Some(ch) = text[idx..].chars().next() {
+        let tail = &text[idx..];
+        if in_block {
+            if let Some((_, end)) = block.filter(|(_, end)| tail.starts_with(end.as_str())) {
+                in_block = false;
+                visit(line, Lexeme::BlockClose);
+                idx += end.len();
+                continue;
+            }
+        } else if let Some(open) = quote {
+            if ch == '\\' {
+                idx += 1;
+                if let Some(escaped) = text[idx..].chars().next() {
+                    line += usize::from(escaped == '\n');
+                    idx += escaped.len_utf8();
+                }
+                continue;
+            }
+            if ch == open {
+                quote = None;
+            }
+        } else if comments.line_marker(tail).is_some() {
+            visit(line, Lexeme::LineComment);
+            idx += tail.find('\n').unwrap_or(tail.len());
+            continue;
+        } else if let Some((start, _)) = block.filter(|(start, _)| tail.starts_with(start.as_str()))
+        {
+            in_block = true;
+            visit(line, Lexeme::BlockOpen);
+            idx += start.len();
+            continue;
+        } else if quotes.contains(&ch) {
+            quote = Some(ch);
+        } else {
+            visit(line, Lexeme::Code(ch));
+        }
+        line += usize::from(ch == '\n');
+        idx += ch.len_utf8();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_config_folds_brackets_comments_and_regions() {
+        let source = "\
+// region helpers
+
+/// Adds numbers.
+/// Returns the sum.
+fn add(
+    a: i32,
+    b: i32,
+) -> i32 {
+    let text = \"}\"; // {
+    /* a
+       b */
+    a + b
+}
+// endregion
+";
+        let config = FoldConfig::for_language("rust");
+        assert_eq!(config.strategy, FoldStrategy::Brackets);
+        assert_eq!(
+            config.detect(source),
+            [(0, 13), (2, 3), (4, 7), (7, 12), (9, 10)]
+        );
+    }
+
+    #[test]
+    fn python_config_folds_by_indentation() {
+        let source = "\
+class Shape:
+    def area(self):
+        # comment
+# dedented comment
+
+        return 0
+
+    def name(self):
+        return 'x'
+print(1)
+";
+        let config = FoldConfig::for_language("python");
+        assert_eq!(config.strategy, FoldStrategy::Indentation);
+        assert_eq!(config.detect(source), [(0, 8), (1, 5), (2, 3), (7, 8)]);
+        assert!(FoldConfig::for_language("plaintext")
+            .detect(source)
+            .is_empty());
+    }
+}
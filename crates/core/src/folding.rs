@@ -1,3 +1,8 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::fold_provider::{FoldConfig, FoldProvider};
+
 /// 描述可折疊區塊。 / Represents a foldable region of lines.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FoldRegion {
@@ -36,6 +41,41 @@ impl FoldTree {
         Some(id)
     }
 
+    /// 批次定義區段（如語法樹產生的範圍），略過無效或衝突者並回傳新增數量。 / Defines many regions (e.g. from a syntax tree), skipping invalid or conflicting ones; returns how many were added.
+    pub fn define_regions(&mut self, regions: impl IntoIterator<Item = (usize, usize)>) -> usize {
+        regions
+            .into_iter()
+            .filter_map(|(start_line, end_line)| self.define_region(start_line, end_line))
+            .count()
+    }
+
+    /// 以偵測結果取代所有區段，起始行相同者保留識別碼與折疊狀態。 / Replaces all regions with detected ranges, keeping the id and collapsed state of regions that start on the same line.
+    pub fn update_regions(&mut self, regions: impl IntoIterator<Item = (usize, usize)>) -> usize {
+        let mut previous: HashMap<usize, (usize, bool)> = self
+            .regions
+            .drain(..)
+            .map(|region| (region.start_line, (region.id, region.is_collapsed)))
+            .collect();
+        let added = self.define_regions(regions);
+        for region in &mut self.regions {
+            if let Some((id, collapsed)) = previous.remove(&region.start_line) {
+                region.id = id;
+                region.is_collapsed = collapsed;
+            }
+        }
+        added
+    }
+
+    /// 依語言設定重新偵測文件的折疊區段。 / Re-detects the document's regions with a language fold configuration.
+    pub fn refresh(&mut self, config: &FoldConfig, text: &str) -> usize {
+        self.update_regions(config.detect(text))
+    }
+
+    /// 以指定的偵測器（如語法樹後端）重新偵測折疊區段。 / Re-detects the document's regions with a given provider, such as a syntax-tree backend.
+    pub fn refresh_with(&mut self, provider: &dyn FoldProvider, text: &str) -> usize {
+        self.update_regions(provider.fold_ranges(text))
+    }
+
     /// 設定折疊狀態。 / Sets collapsed state for the given region id.
     pub fn set_collapsed(&mut self, id: usize, collapsed: bool) -> bool {
         if let Some(region) = self.regions.iter_mut().find(|region| region.id == id) {
@@ -53,6 +93,56 @@ impl FoldTree {
         Some(region.is_collapsed)
     }
 
+    /// 折疊所有區段並回傳變更數量。 / Collapses every region, returning how many changed.
+    pub fn fold_all(&mut self) -> usize {
+        self.set_all(|_| true, true)
+    }
+
+    /// 展開所有區段並回傳變更數量。 / Expands every region, returning how many changed.
+    pub fn unfold_all(&mut self) -> usize {
+        self.set_all(|_| true, false)
+    }
+
+    /// 折疊指定巢狀層級（1 為最外層）的區段。 / Collapses the regions at the given nesting level (1 is outermost).
+    pub fn fold_level(&mut self, level: usize) -> usize {
+        let levels = self.levels();
+        self.set_all(|idx| levels[idx] == level, true)
+    }
+
+    /// 展開指定巢狀層級（1 為最外層）的區段。 / Expands the regions at the given nesting level (1 is outermost).
+    pub fn unfold_level(&mut self, level: usize) -> usize {
+        let levels = self.levels();
+        self.set_all(|idx| levels[idx] == level, false)
+    }
+
+    /// 回傳區段的巢狀層級（1 為最外層）。 / Returns a region's nesting level (1 is outermost).
+    pub fn level(&self, id: usize) -> Option<usize> {
+        let idx = self.regions.iter().position(|region| region.id == id)?;
+        Some(self.levels()[idx])
+    }
+
+    /// 依行編輯重新對應區段：`line` 之後刪除 `removed_lines` 個換行並插入 `inserted_lines` 個；起點被刪除的區段會移除。 / Remaps regions after an edit on `line` that removed `removed_lines` line breaks and inserted `inserted_lines`; regions whose start line was deleted are dropped.
+    pub fn apply_line_edit(&mut self, line: usize, removed_lines: usize, inserted_lines: usize) {
+        let removed_end = line + removed_lines;
+        let remap = |target: usize| {
+            if target <= line {
+                target
+            } else if target > removed_end {
+                target - removed_lines + inserted_lines
+            } else {
+                line + inserted_lines
+            }
+        };
+        self.regions.retain_mut(|region| {
+            if region.start_line > line && region.start_line <= removed_end {
+                return false;
+            }
+            region.start_line = remap(region.start_line);
+            region.end_line = remap(region.end_line);
+            region.end_line > region.start_line
+        });
+    }
+
     /// 檢查行是否可見。 / Checks whether the given line should be visible.
     pub fn is_line_visible(&self, line: usize) -> bool {
         for region in &self.regions {
@@ -86,12 +176,54 @@ impl FoldTree {
         &self.regions
     }
 
+    fn set_all(&mut self, mut selected: impl FnMut(usize) -> bool, collapsed: bool) -> usize {
+        let mut changed = 0;
+        for (idx, region) in self.regions.iter_mut().enumerate() {
+            if selected(idx) && region.is_collapsed != collapsed {
+                region.is_collapsed = collapsed;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// 依起始行排序後以堆疊一次掃描求出每個區段的層級，相同範圍的區段同層。 / Computes every region's level in one sweep over the regions ordered by start line, using a stack of enclosing regions; identical ranges share a level.
+    fn levels(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.regions.len()).collect();
+        order.sort_by_key(|&idx| {
+            let region = &self.regions[idx];
+            (region.start_line, Reverse(region.end_line))
+        });
+        let mut levels = vec![0; self.regions.len()];
+        let mut enclosing: Vec<usize> = Vec::new();
+        for idx in order {
+            let region = &self.regions[idx];
+            while enclosing
+                .last()
+                .is_some_and(|&outer| self.regions[outer].end_line < region.end_line)
+            {
+                enclosing.pop();
+            }
+            match enclosing.last() {
+                Some(&outer)
+                    if (self.regions[outer].start_line, self.regions[outer].end_line)
+                        == (region.start_line, region.end_line) =>
+                {
+                    levels[idx] = levels[outer];
+                }
+                _ => {
+                    levels[idx] = enclosing.len() + 1;
+                    enclosing.push(idx);
+                }
+            }
+        }
+        levels
+    }
+
     fn conflicts(&self, start: usize, end: usize) -> bool {
         self.regions.iter().any(|region| {
             let overlaps = start < region.end_line && end > region.start_line;
-            let partial_overlap =
-                overlaps && !(start >= region.start_line && end <= region.end_line);
-            partial_overlap
+            overlaps && !(start >= region.start_line && end <= region.end_line)
         })
     }
 }
@@ -120,4 +252,86 @@ mod tests {
         // 若未形成巢狀卻部分重疊，應視為無效定義。
         assert!(tree.define_region(2, 5).is_none());
     }
+
+    #[test]
+    fn define_regions_skips_conflicts() {
+        let mut tree = FoldTree::default();
+        assert_eq!(
+            tree.define_regions([(0, 9), (2, 4), (3, 6), (5, 5), (6, 8)]),
+            3
+        );
+        let spans: Vec<(usize, usize)> = tree
+            .regions()
+            .iter()
+            .map(|region| (region.start_line, region.end_line))
+            .collect();
+        assert_eq!(spans, vec![(0, 9), (2, 4), (6, 8)]);
+    }
+
+    fn spans(tree: &FoldTree) -> Vec<(usize, usize, bool)> {
+        tree.regions()
+            .iter()
+            .map(|region| (region.start_line, region.end_line, region.is_collapsed))
+            .collect()
+    }
+
+    #[test]
+    fn fold_commands_follow_nesting_levels() {
+        let mut tree = FoldTree::default();
+        tree.define_regions([(0, 10), (1, 4), (2, 3), (6, 9)]);
+        assert_eq!(tree.fold_level(2), 2);
+        assert_eq!(
+            spans(&tree),
+            [(0, 10, false), (1, 4, true), (2, 3, false), (6, 9, true)]
+        );
+        assert_eq!(tree.level(tree.regions()[2].id), Some(3));
+        assert_eq!(tree.fold_all(), 2);
+        assert_eq!(tree.unfold_level(1), 1);
+        assert_eq!(tree.visible_lines(11), [0, 1, 5, 6, 10]);
+        assert_eq!(tree.unfold_all(), 3);
+
+        let mut tree = FoldTree::default();
+        tree.define_regions([(0, 9), (0, 5), (0, 5), (1, 2), (5, 9), (6, 7)]);
+        let levels: Vec<Option<usize>> = tree
+            .regions()
+            .iter()
+            .map(|region| tree.level(region.id))
+            .collect();
+        assert_eq!(
+            levels,
+            [Some(2), Some(2), Some(1), Some(3), Some(2), Some(3)]
+        );
+        assert_eq!(tree.fold_level(2), 3);
+    }
+
+    #[test]
+    fn edits_remap_regions_and_refresh_keeps_state() {
+        let mut tree = FoldTree::default();
+        tree.define_regions([(1, 4), (6, 9), (10, 12)]);
+        let id = tree.regions()[1].id;
+        tree.set_collapsed(id, true);
+
+        // 在第 0 行插入兩行，其後區段下移。 / Two lines inserted on line 0 shift later regions down.
+        tree.apply_line_edit(0, 0, 2);
+        assert_eq!(
+            spans(&tree),
+            [(3, 6, false), (8, 11, true), (12, 14, false)]
+        );
+        // 將第 5–12 行併入第 4 行：起點被併入的區段移除，結尾落在其中者縮到編輯行。 / Joining lines 5–12 into line 4 drops regions starting there and clamps ends inside it.
+        tree.apply_line_edit(4, 8, 0);
+        assert_eq!(spans(&tree), [(3, 4, false)]);
+
+        let mut tree = FoldTree::default();
+        tree.define_regions([(0, 3), (5, 8)]);
+        let id = tree.regions()[1].id;
+        tree.set_collapsed(id, true);
+        tree.apply_line_edit(2, 0, 1);
+        assert_eq!(tree.update_regions([(0, 4), (6, 9)]), 2);
+        assert_eq!(spans(&tree), [(0, 4, false), (6, 9, true)]);
+        assert_eq!(tree.regions()[1].id, id);
+
+        let text = "fn a() {\n}\n\n\n\n\nfn b() {\n    1\n}\n";
+        assert_eq!(tree.refresh(&FoldConfig::for_language("rust"), text), 2);
+        assert_eq!(spans(&tree), [(0, 1, false), (6, 8, true)]);
+    }
 }
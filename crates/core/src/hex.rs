@@ -0,0 +1,321 @@
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use rustnotepad_search::BytePattern;
+
+use crate::document::{write_atomically, DocumentError};
+
+/// 每列預設顯示的位元組數。 / Default number of bytes shown per row.
+pub const DEFAULT_BYTES_PER_ROW: usize = 16;
+
+/// 十六進位編輯的輸入模式。 / Input mode for hex editing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HexEditMode {
+    /// 覆寫游標處的位元組，必要時延伸檔尾。 / Overwrite bytes at the cursor, extending the data at the end.
+    #[default]
+    Overwrite,
+    /// 在游標處插入位元組。 / Insert bytes at the cursor.
+    Insert,
+}
+
+/// 十六進位檢視中的一列（位移、十六進位與 ASCII 欄）。 / One row of the hex view: offset, hex, and ASCII columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexRow<'a> {
+    pub offset: usize,
+    pub bytes: &'a [u8],
+    pub width: usize,
+}
+
+impl HexRow<'_> {
+    /// 以兩位大寫十六進位、空白分隔的位元組欄，未滿一列時補空白。 / Hex column as space-separated uppercase pairs, padded for short rows.
+    pub fn hex(&self) -> String {
+        let mut column = String::with_capacity(self.width * 3);
+        for idx in 0..self.width {
+            if idx > 0 {
+                column.push(' ');
+            }
+            match self.bytes.get(idx) {
+                Some(byte) => column.push_str(&format!("{byte:02X}")),
+                None => column.push_str("  "),
+            }
+        }
+        column
+    }
+
+    /// ASCII 欄，不可列印字元以 `.` 顯示。 / ASCII column, rendering non-printable bytes as `.`.
+    pub fn ascii(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for HexRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08X}  {}  |{}|", self.offset, self.hex(), self.ascii())
+    }
+}
+
+/// 以位元組為單位的文件，供無法解碼為文字的檔案使用。 / Byte-oriented document for files that cannot be decoded as text.
+#[derive(Debug, Clone, Default)]
+pub struct HexDocument {
+    path: Option<PathBuf>,
+    bytes: Vec<u8>,
+    is_dirty: bool,
+}
+
+impl HexDocument {
+    /// 建立空白文件。 / Creates an empty document.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由記憶體中的位元組建立文件。 / Wraps in-memory bytes.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            path: None,
+            bytes,
+            is_dirty: false,
+        }
+    }
+
+    /// 原樣讀入檔案，不做任何解碼。 / Reads a file verbatim without decoding.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DocumentError> {
+        let path_ref = path.as_ref();
+        Ok(Self {
+            path: Some(path_ref.to_path_buf()),
+            bytes: fs::read(path_ref)?,
+            is_dirty: false,
+        })
+    }
+
+    /// 儲存至現有路徑；若尚未指定路徑則失敗。 / Saves to the current path; fails if no path is set.
+    pub fn save(&mut self) -> Result<(), DocumentError> {
+        let path = self.path.clone().ok_or_else(|| {
+            DocumentError::Io(std::io::Error::other("document has no associated path"))
+        })?;
+        self.save_as(path)
+    }
+
+    /// 以原子換名方式另存。 / Saves to a new path using an atomic rename.
+    pub fn save_as(&mut self, path: impl AsRef<Path>) -> Result<(), DocumentError> {
+        let path_ref = path.as_ref();
+        write_atomically(path_ref, &self.bytes)?;
+        self.path = Some(path_ref.to_path_buf());
+        self.is_dirty = false;
+        Ok(())
+    }
+
+    /// 檔案路徑。 / Path of the underlying file, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// 全部位元組。 / All bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// 位元組數。 / Number of bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// 是否為空。 / Indicates whether the document holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// 是否有未儲存的修改。 / Indicates whether there are unsaved edits.
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    /// 依列寬計算列數（空文件仍有一列）。 / Number of rows for the given width; an empty document still has one row.
+    pub fn row_count(&self, width: usize) -> usize {
+        self.bytes.len().div_ceil(width.max(1)).max(1)
+    }
+
+    /// 取得單一列。 / Returns one row of the view.
+    pub fn row(&self, index: usize, width: usize) -> Option<HexRow<'_>> {
+        let width = width.max(1);
+        let start = index.checked_mul(width)?;
+        if start > self.bytes.len() || (start == self.bytes.len() && start > 0) {
+            return None;
+        }
+        let end = (start + width).min(self.bytes.len());
+        Some(HexRow {
+            offset: start,
+            bytes: &self.bytes[start..end],
+            width,
+        })
+    }
+
+    /// 取得一段列範圍，供捲動檢視使用。 / Returns a range of rows, e.g. the visible viewport.
+    pub fn rows(&self, range: Range<usize>, width: usize) -> Vec<HexRow<'_>> {
+        range.filter_map(|index| self.row(index, width)).collect()
+    }
+
+    /// 位移所在的列。 / Row containing the offset.
+    pub fn row_of_offset(&self, offset: usize, width: usize) -> usize {
+        offset.min(self.bytes.len()) / width.max(1)
+    }
+
+    /// 依模式於位移處寫入位元組。 / Writes bytes at the offset according to the edit mode.
+    pub fn write(
+        &mut self,
+        offset: usize,
+        data: &[u8],
+        mode: HexEditMode,
+    ) -> Result<(), DocumentError> {
+        match mode {
+            HexEditMode::Overwrite => self.overwrite(offset, data),
+            HexEditMode::Insert => self.insert(offset, data),
+        }
+    }
+
+    /// 覆寫位元組；超出檔尾的部分會附加。 / Overwrites bytes, appending whatever runs past the end.
+    pub fn overwrite(&mut self, offset: usize, data: &[u8]) -> Result<(), DocumentError> {
+        self.check_offset(offset)?;
+        let overlap = data.len().min(self.bytes.len() - offset);
+        self.bytes[offset..offset + overlap].copy_from_slice(&data[..overlap]);
+        self.bytes.extend_from_slice(&data[overlap..]);
+        self.is_dirty |= !data.is_empty();
+        Ok(())
+    }
+
+    /// 插入位元組。 / Inserts bytes at the offset.
+    pub fn insert(&mut self, offset: usize, data: &[u8]) -> Result<(), DocumentError> {
+        self.check_offset(offset)?;
+        self.bytes.splice(offset..offset, data.iter().copied());
+        self.is_dirty |= !data.is_empty();
+        Ok(())
+    }
+
+    /// 刪除位元組範圍。 / Removes a byte range.
+    pub fn remove(&mut self, range: Range<usize>) -> Result<(), DocumentError> {
+        self.check_offset(range.end)?;
+        if range.start < range.end {
+            self.bytes.drain(range);
+            self.is_dirty = true;
+        }
+        Ok(())
+    }
+
+    /// 尋找所有符合位元組樣式的範圍。 / Finds every range matching the byte pattern.
+    pub fn find_all(&self, pattern: &BytePattern) -> Vec<Range<usize>> {
+        pattern.find_all(&self.bytes)
+    }
+
+    /// 從位移往後尋找下一筆（會循環）。 / Finds the next match at or after the offset, wrapping around.
+    pub fn find_next(&self, pattern: &BytePattern, from: usize) -> Option<Range<usize>> {
+        pattern.find_next(&self.bytes, from, true)
+    }
+
+    fn check_offset(&self, offset: usize) -> Result<(), DocumentError> {
+        if offset > self.bytes.len() {
+            return Err(DocumentError::OffsetOutOfRange {
+                offset,
+                len: self.bytes.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 解析「跳至位移」輸入：`0x1F`、`1Fh` 為十六進位，其餘為十進位。 / Parses goto-offset input: `0x1F` or `1Fh` is hex, anything else decimal.
+pub fn parse_offset(input: &str) -> Option<usize> {
+    let trimmed = input.trim();
+    if let Some(hex) = trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .or_else(|| trimmed.strip_suffix(['h', 'H']))
+    {
+        return usize::from_str_radix(hex, 16).ok();
+    }
+    trimmed.parse().ok()
+}
+
+/// 將 `DE AD be ef` 或 `DEADBEEF` 形式的輸入轉為位元組。 / Parses `DE AD be ef` or `DEADBEEF` style input into bytes.
+pub fn parse_hex_bytes(input: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = input
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_render_offset_hex_and_ascii_columns() {
+        let doc = HexDocument::from_bytes(b"\x7FELF\x02\x01\x01\x00ABCDEFGHIJ".to_vec());
+        assert_eq!(doc.row_count(16), 2);
+        assert_eq!(
+            doc.row(0, 16).unwrap().to_string(),
+            "00000000  7F 45 4C 46 02 01 01 00 41 42 43 44 45 46 47 48  |.ELF....ABCDEFGH|"
+        );
+        let tail = doc.row(1, 16).unwrap();
+        assert_eq!(tail.offset, 16);
+        assert_eq!(tail.ascii(), "IJ");
+        assert_eq!(tail.hex().len(), 16 * 3 - 1);
+        assert!(doc.row(2, 16).is_none());
+        assert_eq!(doc.row_of_offset(17, 16), 1);
+    }
+
+    #[test]
+    fn overwrite_insert_and_remove_edit_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blob.bin");
+        fs::write(&path, [0x00, 0xFF, 0x10]).unwrap();
+
+        let mut doc = HexDocument::open(&path).unwrap();
+        doc.write(1, &[0xAA], HexEditMode::Overwrite).unwrap();
+        doc.write(0, &[0x01, 0x02], HexEditMode::Insert).unwrap();
+        doc.overwrite(4, &[0x20, 0x30]).unwrap();
+        assert_eq!(doc.bytes(), &[0x01, 0x02, 0x00, 0xAA, 0x20, 0x30]);
+        doc.remove(0..2).unwrap();
+        assert!(matches!(
+            doc.insert(9, &[0]),
+            Err(DocumentError::OffsetOutOfRange { offset: 9, len: 4 })
+        ));
+
+        assert!(doc.is_dirty());
+        doc.save().unwrap();
+        assert!(!doc.is_dirty());
+        assert_eq!(fs::read(&path).unwrap(), [0x00, 0xAA, 0x20, 0x30]);
+    }
+
+    #[test]
+    fn byte_search_and_offset_parsing() {
+        let doc = HexDocument::from_bytes(vec![0xDE, 0xAD, 0x00, 0xDE, 0xAD]);
+        let pattern = BytePattern::parse("DE AD").unwrap();
+        assert_eq!(doc.find_all(&pattern), vec![0..2, 3..5]);
+        assert_eq!(doc.find_next(&pattern, 1), Some(3..5));
+        assert_eq!(doc.find_next(&pattern, 4), Some(0..2));
+
+        assert_eq!(parse_offset("0x1F"), Some(31));
+        assert_eq!(parse_offset("1fh"), Some(31));
+        assert_eq!(parse_offset(" 42 "), Some(42));
+        assert_eq!(parse_offset("zz"), None);
+        assert_eq!(parse_hex_bytes("de AD0f"), Some(vec![0xDE, 0xAD, 0x0F]));
+        assert_eq!(parse_hex_bytes("abc"), None);
+    }
+}
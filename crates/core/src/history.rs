@@ -0,0 +1,246 @@
+use crate::editor::Caret;
+
+/// 預設保留的復原步驟上限。 / Default number of undo steps retained by a buffer.
+pub const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+/// 描述單一步驟的編輯種類，用於判斷是否可合併。 / Classifies a history step so consecutive edits can be coalesced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    /// 連續輸入的字元。 / Plain character input without newlines.
+    Typing,
+    /// Backspace 或 Delete 造成的刪除。 / Deletions caused by backspace or forward delete.
+    Deletion,
+    /// 其他一般編輯（換行、貼上、批次取代）。 / Any other edit (newline, paste, batch replacement).
+    Edit,
+    /// 以交易包裝的多步編輯。 / Several edits grouped by an explicit transaction.
+    Group,
+}
+
+impl EditKind {
+    fn coalesces(self) -> bool {
+        matches!(self, EditKind::Typing | EditKind::Deletion)
+    }
+}
+
+/// 單一取代紀錄，位置以套用當下的文字為準。 / A single replacement, positioned against the text at the moment it was applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditRecord {
+    pub start: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl EditRecord {
+    fn is_noop(&self) -> bool {
+        self.removed.is_empty() && self.inserted.is_empty()
+    }
+}
+
+/// 一個可復原的步驟，包含前後游標集合。 / One undoable step together with the caret sets around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditTransaction {
+    kind: EditKind,
+    edits: Vec<EditRecord>,
+    carets_before: Vec<Caret>,
+    carets_after: Vec<Caret>,
+}
+
+impl EditTransaction {
+    /// 步驟種類。 / Returns the kind of this step.
+    pub fn kind(&self) -> EditKind {
+        self.kind
+    }
+
+    /// 依套用順序列出取代紀錄。 / Returns the recorded replacements in application order.
+    pub fn edits(&self) -> &[EditRecord] {
+        &self.edits
+    }
+
+    /// 編輯前的游標集合。 / Caret set captured before the step.
+    pub fn carets_before(&self) -> &[Caret] {
+        &self.carets_before
+    }
+
+    /// 編輯後的游標集合。 / Caret set captured after the step.
+    pub fn carets_after(&self) -> &[Caret] {
+        &self.carets_after
+    }
+}
+
+/// 交易層級開始時的位置，供該層回滾。 / Where a transaction level started, so that level alone can roll back.
+#[derive(Debug, Clone)]
+struct Savepoint {
+    edits: usize,
+    carets: Vec<Caret>,
+}
+
+/// 管理復原/重做堆疊與交易群組。 / Maintains undo/redo stacks and transaction grouping.
+#[derive(Debug, Clone)]
+pub(crate) struct UndoHistory {
+    undo: Vec<EditTransaction>,
+    redo: Vec<EditTransaction>,
+    pending: Option<EditTransaction>,
+    savepoints: Vec<Savepoint>,
+    limit: usize,
+    coalesce_open: bool,
+}
+
+impl Default for UndoHistory {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: None,
+            savepoints: Vec::new(),
+            limit: DEFAULT_HISTORY_LIMIT,
+            coalesce_open: false,
+        }
+    }
+}
+
+impl UndoHistory {
+    pub(crate) fn record(
+        &mut self,
+        kind: EditKind,
+        carets_before: Vec<Caret>,
+        edits: Vec<EditRecord>,
+        carets_after: Vec<Caret>,
+    ) {
+        let edits: Vec<EditRecord> = edits.into_iter().filter(|edit| !edit.is_noop()).collect();
+        if edits.is_empty() {
+            return;
+        }
+
+        if self.in_transaction() {
+            let pending = self.pending.get_or_insert_with(|| EditTransaction {
+                kind: EditKind::Group,
+                edits: Vec::new(),
+                carets_before,
+                carets_after: Vec::new(),
+            });
+            pending.edits.extend(edits);
+            pending.carets_after = carets_after;
+            return;
+        }
+
+        self.redo.clear();
+        if self.coalesce_open && kind.coalesces() {
+            if let Some(last) = self.undo.last_mut() {
+                // 僅在游標延續上一步時合併，移動游標即會切斷。 / Merge only when carets continue from the previous step; moving them breaks the run.
+                if last.kind == kind && last.carets_after == carets_before {
+                    last.edits.extend(edits);
+                    last.carets_after = carets_after;
+                    return;
+                }
+            }
+        }
+
+        self.push_undo(EditTransaction {
+            kind,
+            edits,
+            carets_before,
+            carets_after,
+        });
+        self.coalesce_open = kind.coalesces();
+    }
+
+    /// 開啟一層交易並記下目前的游標作為回滾點。 / Opens a transaction level, remembering the carets as its savepoint.
+    pub(crate) fn begin(&mut self, carets: Vec<Caret>) {
+        let edits = self
+            .pending
+            .as_ref()
+            .map_or(0, |pending| pending.edits.len());
+        self.savepoints.push(Savepoint { edits, carets });
+    }
+
+    /// 結束一層交易；最外層結束時回傳是否產生新步驟。 / Closes one transaction level, committing the group when the outermost level ends.
+    pub(crate) fn end(&mut self, carets_after: Vec<Caret>) -> bool {
+        if self.savepoints.pop().is_none() || self.in_transaction() {
+            return false;
+        }
+        let Some(mut group) = self.pending.take() else {
+            return false;
+        };
+        group.carets_after = carets_after;
+        self.redo.clear();
+        self.push_undo(group);
+        self.coalesce_open = false;
+        true
+    }
+
+    /// 放棄最內層交易並回傳其後的編輯與回滾點游標，外層已套用的編輯保留。 / Drops the innermost transaction level and returns the edits made since its savepoint, with the carets to restore, for rollback; edits of outer levels stay pending.
+    pub(crate) fn abort(&mut self) -> Option<EditTransaction> {
+        let savepoint = self.savepoints.pop()?;
+        let pending = self.pending.as_mut()?;
+        let edits = pending
+            .edits
+            .split_off(savepoint.edits.min(pending.edits.len()));
+        if !self.in_transaction() {
+            self.pending = None;
+        }
+        Some(EditTransaction {
+            kind: EditKind::Group,
+            edits,
+            carets_before: savepoint.carets,
+            carets_after: Vec::new(),
+        })
+    }
+
+    pub(crate) fn in_transaction(&self) -> bool {
+        !self.savepoints.is_empty()
+    }
+
+    pub(crate) fn break_coalescing(&mut self) {
+        self.coalesce_open = false;
+    }
+
+    pub(crate) fn pop_undo(&mut self) -> Option<EditTransaction> {
+        self.coalesce_open = false;
+        self.undo.pop()
+    }
+
+    pub(crate) fn pop_redo(&mut self) -> Option<EditTransaction> {
+        self.coalesce_open = false;
+        self.redo.pop()
+    }
+
+    pub(crate) fn push_redo(&mut self, transaction: EditTransaction) {
+        self.redo.push(transaction);
+    }
+
+    pub(crate) fn push_undo(&mut self, transaction: EditTransaction) {
+        self.undo.push(transaction);
+        if self.undo.len() > self.limit {
+            let overflow = self.undo.len() - self.limit;
+            self.undo.drain(..overflow);
+        }
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub(crate) fn undo_steps(&self) -> &[EditTransaction] {
+        &self.undo
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        if self.undo.len() > self.limit {
+            let overflow = self.undo.len() - self.limit;
+            self.undo.drain(..overflow);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+        self.savepoints.clear();
+        self.coalesce_open = false;
+    }
+}
@@ -0,0 +1,511 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use encoding_rs::{Encoding as RsEncoding, UTF_8};
+use rustnotepad_search::{
+    BlockSearch, FileSearchResult, SearchEngine, SearchOptions, SearchReport, SearchScope,
+};
+
+use crate::document::{
+    guess_legacy_encoding, looks_binary, Document, DocumentError, Encoding, LineEnding,
+};
+
+/// 預設的巨檔門檻（64 MiB）。 / Default size above which files open in large-file mode (64 MiB).
+pub const DEFAULT_LARGE_FILE_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// 預設的讀取區塊大小（4 MiB）。 / Default chunk size used for scanning and searching (4 MiB).
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// 每隔多少行記錄一次檢查點。 / Number of lines between two line-index checkpoints.
+const LINE_CHECKPOINT_INTERVAL: usize = 256;
+
+/// 控制何時改用巨檔模式的選項。 / Options controlling when a file is opened in large-file mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LargeFileOptions {
+    pub threshold_bytes: u64,
+    pub chunk_size: usize,
+}
+
+impl Default for LargeFileOptions {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: DEFAULT_LARGE_FILE_THRESHOLD,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl LargeFileOptions {
+    /// 以 MiB 指定門檻。 / Builds options with a threshold expressed in MiB.
+    pub fn with_threshold_mib(mib: u32) -> Self {
+        Self {
+            threshold_bytes: u64::from(mib.max(1)) * 1024 * 1024,
+            ..Self::default()
+        }
+    }
+
+    /// 判斷檔案大小是否應使用巨檔模式。 / Indicates whether a file of this size should open lazily.
+    pub fn is_large(&self, len: u64) -> bool {
+        len > self.threshold_bytes
+    }
+
+    /// 依檔案大小決定可啟用的昂貴功能。 / Resolves which expensive features stay enabled for a file size.
+    pub fn features_for(&self, len: u64) -> DocumentFeatures {
+        if self.is_large(len) {
+            DocumentFeatures::reduced()
+        } else {
+            DocumentFeatures::full()
+        }
+    }
+}
+
+/// 依文件大小可開關的昂貴功能。 / Expensive editor features that are toggled by document size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocumentFeatures {
+    pub syntax_highlighting: bool,
+    pub word_index: bool,
+    pub document_map: bool,
+}
+
+impl DocumentFeatures {
+    /// 全部啟用。 / Every feature enabled.
+    pub fn full() -> Self {
+        Self {
+            syntax_highlighting: true,
+            word_index: true,
+            document_map: true,
+        }
+    }
+
+    /// 巨檔模式：全部停用。 / Large-file mode: every expensive feature disabled.
+    pub fn reduced() -> Self {
+        Self {
+            syntax_highlighting: false,
+            word_index: false,
+            document_map: false,
+        }
+    }
+}
+
+/// `Document::open_with_options` 的結果。 / Result of `Document::open_with_options`.
+#[derive(Debug)]
+pub enum OpenedDocument {
+    /// 完整載入記憶體的文件。 / Fully loaded, editable document.
+    Full(Document),
+    /// 按需讀取的唯讀巨檔。 / Read-only large file whose lines are read on demand.
+    Large(LargeFileDocument),
+}
+
+impl OpenedDocument {
+    /// 目前文件可啟用的功能。 / Features available for the opened document.
+    pub fn features(&self) -> DocumentFeatures {
+        match self {
+            OpenedDocument::Full(_) => DocumentFeatures::full(),
+            OpenedDocument::Large(_) => DocumentFeatures::reduced(),
+        }
+    }
+
+    /// 是否為巨檔模式。 / Indicates whether the document was opened lazily.
+    pub fn is_large(&self) -> bool {
+        matches!(self, OpenedDocument::Large(_))
+    }
+}
+
+impl Document {
+    /// 依大小選擇完整載入或巨檔模式開啟。 / Opens a file fully or lazily depending on its size.
+    pub fn open_with_options(
+        path: impl AsRef<Path>,
+        options: &LargeFileOptions,
+    ) -> Result<OpenedDocument, DocumentError> {
+        let path_ref = path.as_ref();
+        let len = std::fs::metadata(path_ref)?.len();
+        if options.is_large(len) {
+            LargeFileDocument::open(path_ref, options).map(OpenedDocument::Large)
+        } else {
+            Document::open(path_ref).map(OpenedDocument::Full)
+        }
+    }
+}
+
+/// 以區塊讀取、按需提供行內容的唯讀巨檔文件。 / Read-only document that chunk-reads a file and serves lines on demand.
+///
+/// 僅支援與 ASCII 相容的編碼（UTF-8 與大多數傳統編碼），因為行界以 `\n` 位元組切分。 /
+/// Only ASCII-compatible encodings (UTF-8 and most legacy code pages) are supported because lines are split on the `\n` byte.
+#[derive(Debug, Clone)]
+pub struct LargeFileDocument {
+    path: PathBuf,
+    len: u64,
+    data_start: u64,
+    encoding: Encoding,
+    has_bom: bool,
+    line_ending: LineEnding,
+    line_count: usize,
+    checkpoints: Vec<u64>,
+    chunk_size: usize,
+}
+
+impl LargeFileDocument {
+    /// 掃描檔案一次建立稀疏行索引，不保留內容。 / Scans the file once to build a sparse line index without keeping its contents.
+    pub fn open(path: impl AsRef<Path>, options: &LargeFileOptions) -> Result<Self, DocumentError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        let chunk_size = options.chunk_size.max(4096);
+
+        let mut head = vec![0u8; chunk_size.min(len as usize)];
+        file.read_exact(&mut head)?;
+        let (encoding, has_bom) = detect_chunk_encoding(&head)?;
+        let data_start: u64 = if has_bom { 3 } else { 0 };
+
+        file.seek(SeekFrom::Start(data_start))?;
+        let mut reader = BufReader::with_capacity(chunk_size, file);
+        let mut checkpoints = vec![data_start];
+        let mut line_ending = None;
+        let mut line_count = 1usize;
+        let mut offset = data_start;
+        let mut previous: Option<u8> = None;
+        loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            for (idx, &byte) in buf.iter().enumerate() {
+                if byte == b'\n' {
+                    if line_ending.is_none() {
+                        let prev = if idx > 0 {
+                            Some(buf[idx - 1])
+                        } else {
+                            previous
+                        };
+                        line_ending = Some(if prev == Some(b'\r') {
+                            LineEnding::CrLf
+                        } else {
+                            LineEnding::Lf
+                        });
+                    }
+                    if line_count.is_multiple_of(LINE_CHECKPOINT_INTERVAL) {
+                        checkpoints.push(offset + idx as u64 + 1);
+                    }
+                    line_count += 1;
+                }
+            }
+            previous = buf.last().copied();
+            let consumed = buf.len();
+            offset += consumed as u64;
+            reader.consume(consumed);
+        }
+
+        Ok(Self {
+            path,
+            len,
+            data_start,
+            encoding,
+            has_bom,
+            line_ending: line_ending.unwrap_or(LineEnding::Lf),
+            line_count,
+            checkpoints,
+            chunk_size,
+        })
+    }
+
+    /// 檔案路徑。 / Path of the underlying file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 檔案大小（位元組）。 / File size in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// 是否為空檔。 / Indicates whether the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len <= self.data_start
+    }
+
+    /// 偵測到的編碼。 / Detected encoding.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// 是否帶有 UTF-8 BOM。 / Indicates whether the file starts with a UTF-8 BOM.
+    pub fn has_bom(&self) -> bool {
+        self.has_bom
+    }
+
+    /// 依第一個換行推斷的行尾。 / Line ending inferred from the first newline.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// 行數（與 `TextBuffer::line_count` 定義一致）。 / Number of lines, matching `TextBuffer::line_count`.
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    /// 讀取單一行（0 起算，不含行尾）。 / Reads one zero-based line without its line terminator.
+    pub fn line(&self, line: usize) -> Result<String, DocumentError> {
+        Ok(self.lines(line..line + 1)?.pop().unwrap_or_default())
+    }
+
+    /// 讀取一段行範圍，供視窗捲動時使用。 / Reads a range of lines, e.g. the visible viewport.
+    pub fn lines(&self, range: Range<usize>) -> Result<Vec<String>, DocumentError> {
+        let end = range.end.min(self.line_count);
+        if range.start >= end {
+            return Ok(Vec::new());
+        }
+        let mut reader = self.reader_at_line(range.start)?;
+        let mut lines = Vec::with_capacity(end - range.start);
+        let mut raw = Vec::new();
+        for _ in range.start..end {
+            raw.clear();
+            reader.read_until(b'\n', &mut raw)?;
+            lines.push(self.decode_line(&raw)?);
+        }
+        Ok(lines)
+    }
+
+    /// 以行對齊的區塊搜尋整個檔案；結果位移為相對於內容起點的位元組。 / Searches the whole file chunk by chunk; offsets are bytes from the start of the content.
+    ///
+    /// 可能跨行或錨定全文的樣式無法逐塊比對，改為讀入整份內容後搜尋。 /
+    /// Patterns that can cross a line break or anchor to the whole text cannot be matched chunk by
+    /// chunk, so they read the whole content first.
+    pub fn search(&self, options: &SearchOptions) -> Result<SearchReport, DocumentError> {
+        let search = BlockSearch::new(options)?;
+        let mut matches = Vec::new();
+        if search.needs_whole_text() {
+            let mut scoped = options.clone();
+            scoped.scope = SearchScope::EntireDocument;
+            let mut text = String::new();
+            self.for_each_chunk(|chunk| {
+                text.push_str(&chunk.text);
+                Ok(())
+            })?;
+            matches = SearchEngine::new(&text).find_all(&scoped)?;
+        } else {
+            self.for_each_chunk(|chunk| {
+                matches.extend(search.find_in(
+                    &chunk.text,
+                    chunk.byte_offset,
+                    chunk.first_line,
+                    chunk.last,
+                ));
+                Ok(())
+            })?;
+        }
+
+        if matches.is_empty() {
+            return Ok(SearchReport::default());
+        }
+        Ok(SearchReport::new(vec![FileSearchResult::new(
+            Some(self.path.clone()),
+            matches,
+        )]))
+    }
+
+    /// 將巨檔完整載入成一般文件（例如使用者確認要編輯時）。 / Loads the file fully into a regular `Document`, e.g. once the user opts into editing.
+    pub fn into_document(self) -> Result<Document, DocumentError> {
+        Document::open(&self.path)
+    }
+
+    fn for_each_chunk<F>(&self, mut visit: F) -> Result<(), DocumentError>
+    where
+        F: FnMut(&TextChunk) -> Result<(), DocumentError>,
+    {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.data_start))?;
+        let mut reader = BufReader::with_capacity(self.chunk_size, file);
+        let mut chunk = TextChunk {
+            byte_offset: 0,
+            first_line: 0,
+            last: false,
+            text: String::new(),
+        };
+        let mut raw = Vec::with_capacity(self.chunk_size);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read > 0 {
+                raw.extend_from_slice(&line);
+            }
+            if (read == 0 || raw.len() >= self.chunk_size) && !raw.is_empty() {
+                chunk.last = read == 0 || reader.fill_buf()?.is_empty();
+                chunk.text = self.decode_chunk(&raw)?;
+                visit(&chunk)?;
+                chunk.byte_offset += chunk.text.len();
+                chunk.first_line += raw.iter().filter(|&&byte| byte == b'\n').count();
+                raw.clear();
+            }
+            if read == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn reader_at_line(&self, line: usize) -> Result<BufReader<File>, DocumentError> {
+        let checkpoint = (line / LINE_CHECKPOINT_INTERVAL).min(self.checkpoints.len() - 1);
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.checkpoints[checkpoint]))?;
+        let mut reader = BufReader::new(file);
+        let mut skipped = Vec::new();
+        for _ in checkpoint * LINE_CHECKPOINT_INTERVAL..line {
+            skipped.clear();
+            reader.read_until(b'\n', &mut skipped)?;
+        }
+        Ok(reader)
+    }
+
+    fn decode_line(&self, raw: &[u8]) -> Result<String, DocumentError> {
+        let mut trimmed = raw;
+        if let Some(rest) = trimmed.strip_suffix(b"\n") {
+            trimmed = rest;
+        }
+        if let Some(rest) = trimmed.strip_suffix(b"\r") {
+            trimmed = rest;
+        }
+        decode_with(self.rs_encoding(), trimmed)
+    }
+
+    fn decode_chunk(&self, raw: &[u8]) -> Result<String, DocumentError> {
+        // 統一成 `\n`，使位移與 `Document` 的正規化內容一致。 / Normalise to `\n` so offsets match `Document`'s normalised contents.
+        let text = decode_with(self.rs_encoding(), raw)?;
+        if text.contains('\r') {
+            Ok(text.replace("\r\n", "\n").replace('\r', "\n"))
+        } else {
+            Ok(text)
+        }
+    }
+
+    fn rs_encoding(&self) -> &'static RsEncoding {
+        match self.encoding {
+            Encoding::Legacy(legacy) => legacy.to_rs(),
+            _ => UTF_8,
+        }
+    }
+}
+
+struct TextChunk {
+    byte_offset: usize,
+    first_line: usize,
+    last: bool,
+    text: String,
+}
+
+fn detect_chunk_encoding(head: &[u8]) -> Result<(Encoding, bool), DocumentError> {
+    if head.starts_with(b"\xEF\xBB\xBF") {
+        return Ok((Encoding::Utf8, true));
+    }
+    if head.starts_with(b"\xFF\xFE") || head.starts_with(b"\xFE\xFF") {
+        // UTF-16 不以 `\n` 位元組分行，巨檔模式無法處理。 / UTF-16 lines are not `\n`-byte delimited, so lazy mode cannot index them.
+        return Err(DocumentError::InvalidEncoding);
+    }
+    if valid_utf8_prefix(head) {
+        return Ok((Encoding::Utf8, false));
+    }
+    if looks_binary(head) {
+        return Err(DocumentError::InvalidEncoding);
+    }
+    guess_legacy_encoding(head, false)
+        .filter(|legacy| legacy.is_ascii_compatible())
+        .map(|legacy| (Encoding::Legacy(legacy), false))
+        .ok_or(DocumentError::InvalidEncoding)
+}
+
+/// 區塊可能在多位元字元中間截斷，因此只要求錯誤出現在最後 3 個位元組內。 / A chunk may end mid-character, so only errors before the last three bytes count.
+fn valid_utf8_prefix(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none() && bytes.len() - err.valid_up_to() < 4,
+    }
+}
+
+fn decode_with(encoding: &'static RsEncoding, raw: &[u8]) -> Result<String, DocumentError> {
+    let (cow, had_errors) = encoding.decode_without_bom_handling(raw);
+    if had_errors {
+        return Err(DocumentError::InvalidEncoding);
+    }
+    Ok(match cow {
+        Cow::Borrowed(slice) => slice.to_owned(),
+        Cow::Owned(string) => string,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustnotepad_search::SearchMode;
+    use std::fmt::Write as _;
+    use std::fs;
+
+    fn sample_log(lines: usize) -> String {
+        let mut text = String::new();
+        for idx in 0..lines {
+            let _ = write!(text, "{idx:05} INFO request served\r\n");
+        }
+        text.push_str("tail ERROR final");
+        text
+    }
+
+    #[test]
+    fn open_with_options_switches_to_large_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.log");
+        fs::write(&path, sample_log(2000)).unwrap();
+
+        let options = LargeFileOptions {
+            threshold_bytes: 1024,
+            chunk_size: 4096,
+        };
+        let opened = Document::open_with_options(&path, &options).unwrap();
+        assert!(opened.is_large());
+        assert!(!opened.features().syntax_highlighting);
+        let OpenedDocument::Large(large) = opened else {
+            unreachable!()
+        };
+        assert_eq!(large.line_count(), 2001);
+        assert_eq!(large.line_ending(), LineEnding::CrLf);
+        assert_eq!(large.line(0).unwrap(), "00000 INFO request served");
+        assert_eq!(large.line(1337).unwrap(), "01337 INFO request served");
+        assert_eq!(large.line(2000).unwrap(), "tail ERROR final");
+        assert_eq!(large.lines(511..513).unwrap().len(), 2);
+
+        let small = Document::open_with_options(&path, &LargeFileOptions::default()).unwrap();
+        assert!(!small.is_large());
+    }
+
+    #[test]
+    fn search_spans_chunks_with_document_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.log");
+        let text = sample_log(3000);
+        fs::write(&path, &text).unwrap();
+
+        let options = LargeFileOptions {
+            threshold_bytes: 0,
+            chunk_size: 4096,
+        };
+        let large = LargeFileDocument::open(&path, &options).unwrap();
+        let mut query = SearchOptions::new("01999 INFO");
+        query.case_sensitive = true;
+        let report = large.search(&query).unwrap();
+        assert_eq!(report.total_matches, 1);
+
+        let full = Document::open(&path).unwrap();
+        let expected = SearchEngine::new(full.contents()).find_all(&query).unwrap();
+        assert_eq!(report.results[0].matches, expected);
+
+        let report = large.search(&SearchOptions::new("error")).unwrap();
+        assert_eq!(report.results[0].matches[0].line, 3001);
+        // 每行 27 位元組，第一個 4096 位元組區塊在第 152 行後結束。 / Lines are 27 bytes, so the first 4096-byte chunk ends after line 152.
+        let mut across = SearchOptions::new(r"served\n00152");
+        across.mode = SearchMode::Regex;
+        let report = large.search(&across).unwrap();
+        let expected = SearchEngine::new(full.contents())
+            .find_all(&across)
+            .unwrap();
+        assert_eq!(expected.len(), 1);
+        assert_eq!(report.results[0].matches, expected);
+    }
+}
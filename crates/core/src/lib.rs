@@ -4,23 +4,57 @@ pub mod document;
 pub mod document_map;
 pub mod editor;
 pub mod file_monitor;
+pub mod find_in_files;
+pub mod fold_provider;
 pub mod folding;
+pub mod hex;
+pub mod history;
+pub mod large_file;
 pub mod line_ops;
+pub mod marks;
+pub mod motion;
+pub mod multi_cursor;
 pub mod recovery;
+pub mod replace_journal;
+pub mod search_index;
 pub mod search_session;
 pub mod split_view;
+pub mod text_buffer;
 
 pub use bookmarks::BookmarkManager;
 pub use column_ops::ColumnSelection;
-pub use document::{Document, DocumentError, Encoding, LegacyEncoding, LineEnding};
-pub use document_map::{DocumentMapEntry, DocumentMetrics};
+pub use document::{
+    Document, DocumentError, Encoding, LegacyEncoding, LineEnding, LineEndingState, LineEndingStats,
+};
+pub use document_map::{
+    build_buffer_map, collect_buffer_metrics, DocumentMapEntry, DocumentMetrics,
+};
 pub use editor::{Caret, EditorBuffer, EditorError, Selection};
 pub use file_monitor::{FileEvent, FileMonitor, FileMonitorError, FileMonitorEventKind};
+pub use find_in_files::{
+    FileFilter, FindEvent, FindInFiles, FindInFilesError, FindInFilesTask, FindStats, SkipReason,
+};
+pub use fold_provider::{
+    BracketFolds, CommentFolds, CommentSyntax, FoldConfig, FoldProvider, FoldStrategy, IndentFolds,
+    RegionMarkerFolds,
+};
 pub use folding::{FoldRegion, FoldTree};
+pub use hex::{HexDocument, HexEditMode, HexRow, DEFAULT_BYTES_PER_ROW};
+pub use history::{EditKind, EditRecord, EditTransaction};
+pub use large_file::{DocumentFeatures, LargeFileDocument, LargeFileOptions, OpenedDocument};
 pub use line_ops::{CaseTransform, SortOrder};
+pub use marks::{MarkStyle, MarkStyles, MARK_STYLE_COUNT};
+pub use motion::{Motion, MotionTarget};
+pub use multi_cursor::OccurrenceOptions;
 pub use recovery::{RecoveryEntry, RecoveryManager};
+pub use replace_journal::{
+    JournalEntry, ReplaceJournal, ReplaceJournalError, ReplaceOutcome, SkippedRewrite,
+};
 pub use rustnotepad_search::{
-    SearchDirection, SearchError, SearchMatch, SearchMode, SearchOptions, SearchReport, SearchScope,
+    BytePattern, ReplacePlan, SearchDirection, SearchError, SearchMatch, SearchMode, SearchOptions,
+    SearchReport, SearchScope, TrigramIndex,
 };
+pub use search_index::{IndexUpdate, SearchIndexError, WatchedIndex, WorkspaceIndex};
 pub use search_session::SearchSession;
 pub use split_view::{MultiInstancePolicy, Pane, SplitViewState, TabId, TabRecord};
+pub use text_buffer::TextBuffer;
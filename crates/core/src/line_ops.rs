@@ -1,6 +1,9 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
 
 use crate::editor::{EditOperation, EditorBuffer, EditorError, Selection};
+use crate::text_buffer::TextBuffer;
 
 /// 指定要進行大小寫轉換的模式。 / Enumerates supported case conversion transforms.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,9 +27,11 @@ pub fn indent_lines(buffer: &mut EditorBuffer, indent: &str) -> Result<(), Edito
     if indent.is_empty() {
         return Ok(());
     }
+    buffer.transaction(|buffer| indent_lines_inner(buffer, indent))
+}
 
-    let text = buffer.contents().to_owned();
-    let (range_start, range_end, line_indices) = target_line_span(buffer, &text);
+fn indent_lines_inner(buffer: &mut EditorBuffer, indent: &str) -> Result<(), EditorError> {
+    let (range_start, range_end, line_indices) = target_line_span(buffer);
 
     if line_indices.is_empty() {
         return Ok(());
@@ -52,9 +57,11 @@ pub fn outdent_lines(buffer: &mut EditorBuffer, indent: &str) -> Result<(), Edit
     if indent.is_empty() {
         return Ok(());
     }
+    buffer.transaction(|buffer| outdent_lines_inner(buffer, indent))
+}
 
-    let text = buffer.contents().to_owned();
-    let (range_start, range_end, line_indices) = target_line_span(buffer, &text);
+fn outdent_lines_inner(buffer: &mut EditorBuffer, indent: &str) -> Result<(), EditorError> {
+    let (range_start, range_end, line_indices) = target_line_span(buffer);
     if line_indices.is_empty() {
         return Ok(());
     }
@@ -62,7 +69,7 @@ pub fn outdent_lines(buffer: &mut EditorBuffer, indent: &str) -> Result<(), Edit
     let mut ops = Vec::new();
     for &(line_start, content_len, _) in &line_indices {
         let line_end = line_start + content_len;
-        let line_slice = &text[line_start..line_end];
+        let line_slice = buffer.text().span(line_start..line_end);
         if line_slice.starts_with(indent) {
             ops.push(EditOperation {
                 start: line_start,
@@ -94,8 +101,7 @@ pub fn outdent_lines(buffer: &mut EditorBuffer, indent: &str) -> Result<(), Edit
 
 /// 修剪所有行末尾的空白。 / Removes trailing whitespace across targeted lines.
 pub fn trim_trailing_whitespace(buffer: &mut EditorBuffer) -> Result<usize, EditorError> {
-    let text = buffer.contents().to_owned();
-    let (_, _, line_indices) = target_line_span(buffer, &text);
+    let (_, _, line_indices) = target_line_span(buffer);
     if line_indices.is_empty() {
         return Ok(0);
     }
@@ -103,7 +109,7 @@ pub fn trim_trailing_whitespace(buffer: &mut EditorBuffer) -> Result<usize, Edit
     let mut ops = Vec::new();
     for &(line_start, content_len, _) in &line_indices {
         let line_end = line_start + content_len;
-        let line_slice = &text[line_start..line_end];
+        let line_slice = buffer.text().span(line_start..line_end);
         let mut trim_end = line_slice.len();
         while trim_end > 0 {
             let ch = line_slice[..trim_end].chars().last().unwrap();
@@ -132,18 +138,17 @@ pub fn convert_case(
     buffer: &mut EditorBuffer,
     transform: CaseTransform,
 ) -> Result<(), EditorError> {
-    let text = buffer.contents();
-    let (start, end) = selection_span(buffer, text);
+    let (start, end) = selection_span(buffer);
     if start == end {
         return Ok(());
     }
 
-    let target = &text[start..end];
+    let target = buffer.text().span(start..end);
     let converted = match transform {
         CaseTransform::Upper => target.to_uppercase(),
         CaseTransform::Lower => target.to_lowercase(),
-        CaseTransform::Toggle => toggle_case(target),
-        CaseTransform::Title => title_case(target),
+        CaseTransform::Toggle => toggle_case(&target),
+        CaseTransform::Title => title_case(&target),
     };
 
     buffer.apply_edit_plan(vec![EditOperation {
@@ -156,15 +161,15 @@ pub fn convert_case(
 
 /// 針對選取或整個範圍的行進行排序。 / Sorts lines within the active selection (or whole buffer).
 pub fn sort_lines(buffer: &mut EditorBuffer, order: SortOrder) -> Result<(), EditorError> {
-    let text = buffer.contents().to_owned();
-    let (range_start, range_end, line_indices) = target_line_span(buffer, &text);
+    let (range_start, range_end, line_indices) = target_line_span(buffer);
     if line_indices.len() <= 1 {
         return Ok(());
     }
 
-    let mut lines: Vec<&str> = line_indices
+    let text = buffer.text();
+    let mut lines: Vec<Cow<'_, str>> = line_indices
         .iter()
-        .map(|&(start, len, _)| &text[start..start + len])
+        .map(|&(start, len, _)| text.span(start..start + len))
         .collect();
 
     lines.sort_by(|a, b| match order {
@@ -173,7 +178,7 @@ pub fn sort_lines(buffer: &mut EditorBuffer, order: SortOrder) -> Result<(), Edi
         SortOrder::CaseInsensitiveAscending => a.to_lowercase().cmp(&b.to_lowercase()),
     });
 
-    let joined = join_lines(&lines, &line_indices, &text);
+    let joined = join_lines(&lines, &line_indices);
     buffer.apply_edit_plan(vec![EditOperation {
         start: range_start,
         end: range_end,
@@ -184,18 +189,18 @@ pub fn sort_lines(buffer: &mut EditorBuffer, order: SortOrder) -> Result<(), Edi
 
 /// 移除重複行，僅保留首次出現。 / Deduplicates lines while preserving first occurrence order.
 pub fn dedup_lines(buffer: &mut EditorBuffer, case_sensitive: bool) -> Result<(), EditorError> {
-    let text = buffer.contents().to_owned();
-    let (range_start, range_end, line_indices) = target_line_span(buffer, &text);
+    let (range_start, range_end, line_indices) = target_line_span(buffer);
     if line_indices.len() <= 1 {
         return Ok(());
     }
 
+    let text = buffer.text();
     let mut seen = HashSet::new();
     let mut ordered = Vec::new();
     for &(start, len, _) in &line_indices {
-        let line = &text[start..start + len];
+        let line = text.span(start..start + len);
         let key = if case_sensitive {
-            line.to_owned()
+            line.clone().into_owned()
         } else {
             line.to_lowercase()
         };
@@ -204,7 +209,7 @@ pub fn dedup_lines(buffer: &mut EditorBuffer, case_sensitive: bool) -> Result<()
         }
     }
 
-    let joined = join_lines(&ordered, &line_indices, &text);
+    let joined = join_lines(&ordered, &line_indices);
     buffer.apply_edit_plan(vec![EditOperation {
         start: range_start,
         end: range_end,
@@ -213,7 +218,81 @@ pub fn dedup_lines(buffer: &mut EditorBuffer, case_sensitive: bool) -> Result<()
     Ok(())
 }
 
-fn selection_span(buffer: &EditorBuffer, text: &str) -> (usize, usize) {
+/// 將標記的文字以換行串接，供「複製標記文字」使用。 / Joins the marked text, one mark per line, for "Copy Marked Text".
+pub fn copy_marked_text(buffer: &EditorBuffer, marks: &[Range<usize>]) -> String {
+    let text = buffer.text();
+    marks
+        .iter()
+        .filter(|range| {
+            range.start <= range.end
+                && range.end <= text.len()
+                && text.is_char_boundary(range.start)
+                && text.is_char_boundary(range.end)
+        })
+        .map(|range| text.span(range.clone()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 刪除含有標記的整行並回傳刪除的行數。 / Deletes every line touched by a mark, returning how many lines were removed.
+pub fn delete_marked_lines(
+    buffer: &mut EditorBuffer,
+    marks: &[Range<usize>],
+) -> Result<usize, EditorError> {
+    let (spans, lines) = marked_lines(buffer.text(), marks);
+    let ops = spans
+        .into_iter()
+        .map(|span| EditOperation {
+            start: span.start,
+            end: span.end,
+            text: String::new(),
+        })
+        .collect();
+    buffer.apply_edit_plan(ops)?;
+    Ok(lines)
+}
+
+/// 列出 [`delete_marked_lines`] 會刪除的位元組範圍（已排序）。 / Byte spans [`delete_marked_lines`] removes, in order.
+pub fn marked_line_spans(text: &TextBuffer, marks: &[Range<usize>]) -> Vec<Range<usize>> {
+    marked_lines(text, marks).0
+}
+
+fn marked_lines(text: &TextBuffer, marks: &[Range<usize>]) -> (Vec<Range<usize>>, usize) {
+    let mut touched = BTreeSet::new();
+    for range in marks {
+        let first = text.line_of_offset(range.start);
+        let last = text.line_of_offset(range.end.saturating_sub(1).max(range.start));
+        for line in first..=last {
+            let (start, content_len, newline_len) = line_meta(text, line);
+            let end = start + content_len + newline_len;
+            if range.start < end && range.end > start {
+                touched.insert(line);
+            }
+        }
+    }
+    let touched: Vec<usize> = touched.into_iter().collect();
+
+    let mut spans = Vec::new();
+    let mut run_start = 0;
+    for (pos, &line) in touched.iter().enumerate() {
+        if pos == 0 || touched[pos - 1] + 1 != line {
+            run_start = line;
+        }
+        if touched.get(pos + 1) == Some(&(line + 1)) {
+            continue;
+        }
+        let (last_start, content_len, newline_len) = line_meta(text, line);
+        let mut start = text.line_start(run_start);
+        // 刪到檔尾時一併移除前一行的換行。 / Removing the final lines also drops the newline before them.
+        if newline_len == 0 && run_start > 0 {
+            start -= line_meta(text, run_start - 1).2;
+        }
+        spans.push(start..last_start + content_len + newline_len);
+    }
+    (spans, touched.len())
+}
+
+fn selection_span(buffer: &EditorBuffer) -> (usize, usize) {
     let mut start = usize::MAX;
     let mut end = 0usize;
     for caret in buffer.carets() {
@@ -223,7 +302,7 @@ fn selection_span(buffer: &EditorBuffer, text: &str) -> (usize, usize) {
         }
     }
     if start == usize::MAX {
-        (0, text.len())
+        (0, buffer.text().len())
     } else {
         (start, end)
     }
@@ -263,43 +342,36 @@ fn title_case(input: &str) -> String {
     result
 }
 
-fn join_lines(lines: &[&str], indices: &[(usize, usize, usize)], original: &str) -> String {
+fn join_lines<S: AsRef<str>>(lines: &[S], indices: &[(usize, usize, usize)]) -> String {
     let mut result = String::new();
     for (idx, line) in lines.iter().enumerate() {
-        result.push_str(line);
-        if let Some(&(start, content_len, newline_len)) = indices.get(idx) {
-            if newline_len > 0 {
-                let newline_slice =
-                    &original[start + content_len..start + content_len + newline_len];
-                result.push_str(newline_slice);
-            }
+        result.push_str(line.as_ref());
+        if indices
+            .get(idx)
+            .is_some_and(|&(_, _, newline_len)| newline_len > 0)
+        {
+            result.push('\n');
         }
     }
     result
 }
 
-fn target_line_span(
-    buffer: &EditorBuffer,
-    text: &str,
-) -> (usize, usize, Vec<(usize, usize, usize)>) {
-    let line_meta = compute_line_boundaries(text);
-    if line_meta.is_empty() {
-        return (0, 0, Vec::new());
-    }
+fn target_line_span(buffer: &EditorBuffer) -> (usize, usize, Vec<(usize, usize, usize)>) {
+    let text = buffer.text();
+    let (sel_start, sel_end) = selection_span(buffer);
+    let start_line = text.line_of_offset(sel_start);
+    let end_line = text.line_of_offset(sel_end.saturating_sub(1));
 
-    let (sel_start, sel_end) = selection_span(buffer, text);
-    let start_line = find_line_for_index(sel_start, &line_meta);
-    let end_line = find_line_for_index(sel_end.saturating_sub(1), &line_meta);
-
-    let range_start = line_meta[start_line].0;
-    let range_end = line_meta[end_line].0 + line_meta[end_line].1 + line_meta[end_line].2;
-
-    let slice = line_meta[start_line..=end_line].to_vec();
-    (range_start, range_end, slice)
+    let slice: Vec<_> = (start_line..=end_line)
+        .map(|line| line_meta(text, line))
+        .collect();
+    let range_start = slice[0].0;
+    let (last_start, content_len, newline_len) = slice[slice.len() - 1];
+    (range_start, last_start + content_len + newline_len, slice)
 }
 
 fn clamp_selection(buffer: &mut EditorBuffer, start: usize, end: usize) {
-    let len = buffer.contents().len();
+    let len = buffer.text().len();
     let clamped_start = start.min(len);
     let clamped_end = end.min(len);
     if buffer.carets().is_empty() {
@@ -313,38 +385,16 @@ fn clamp_selection(buffer: &mut EditorBuffer, start: usize, end: usize) {
     }
 }
 
-fn compute_line_boundaries(text: &str) -> Vec<(usize, usize, usize)> {
-    if text.is_empty() {
-        return vec![(0, 0, 0)];
-    }
-
-    let mut results = Vec::new();
-    let mut cursor = 0usize;
-    for segment in text.split_inclusive('\n') {
-        let content_len = segment.trim_end_matches('\n').len();
-        let newline_len = segment.len() - content_len;
-        results.push((cursor, content_len, newline_len));
-        cursor += segment.len();
-    }
-
-    if text.ends_with('\n') {
-        results.push((text.len(), 0, 0));
-    }
-
-    results
-}
-
-fn find_line_for_index(index: usize, lines: &[(usize, usize, usize)]) -> usize {
-    if lines.is_empty() {
-        return 0;
-    }
-    for (i, &(start, content_len, newline_len)) in lines.iter().enumerate() {
-        let line_end = start + content_len + newline_len;
-        if index < line_end || i == lines.len() - 1 {
-            return i;
-        }
-    }
-    lines.len() - 1
+/// 行的（起點, 內容長度, 換行長度）。 / A line's (start, content length, newline length).
+fn line_meta(text: &TextBuffer, line: usize) -> (usize, usize, usize) {
+    let start = text.line_start(line);
+    let content_end = text.line_end(line);
+    let newline_len = if line + 1 < text.line_count() {
+        text.line_start(line + 1) - content_end
+    } else {
+        0
+    };
+    (start, content_end - start, newline_len)
 }
 
 #[cfg(test)]
@@ -384,6 +434,17 @@ mod tests {
         assert_eq!(buffer.contents(), "Hello World");
     }
 
+    #[test]
+    fn marked_text_is_copied_and_marked_lines_deleted() {
+        let mut buffer = EditorBuffer::new("keep\nfoo one\nfoo two\nkeep too\nlast foo");
+        let marks = vec![5..8, 13..16, 35..38];
+        assert_eq!(copy_marked_text(&buffer, &marks), "foo\nfoo\nfoo");
+        assert_eq!(marked_line_spans(buffer.text(), &marks), [5..21, 29..38]);
+        assert_eq!(delete_marked_lines(&mut buffer, &marks).unwrap(), 3);
+        assert_eq!(buffer.contents(), "keep\nkeep too");
+        assert_eq!(delete_marked_lines(&mut buffer, &[]).unwrap(), 0);
+    }
+
     #[test]
     fn sort_and_dedup_lines() {
         let mut buffer = buffer_with_selection("c\nb\na\nb\n", 0, 8);
@@ -392,4 +453,23 @@ mod tests {
         dedup_lines(&mut buffer, true).unwrap();
         assert_eq!(buffer.contents(), "a\nb\nc\n");
     }
+
+    #[test]
+    fn line_operations_undo_as_single_steps() {
+        let text = "c\nb\na";
+        let mut buffer = buffer_with_selection(text, 0, text.len());
+        indent_lines(&mut buffer, "  ").unwrap();
+        sort_lines(&mut buffer, SortOrder::Ascending).unwrap();
+        assert_eq!(buffer.contents(), "  a\n  b\n  c");
+        assert_eq!(buffer.undo_history().len(), 2);
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), "  c\n  b\n  a");
+        assert!(buffer.undo());
+        assert_eq!(buffer.contents(), text);
+        assert_eq!(
+            buffer.carets()[0].selection(),
+            Some(&Selection::new(0, text.len()))
+        );
+    }
 }
@@ -0,0 +1,242 @@
+use std::ops::Range;
+
+use rustnotepad_search::SearchMatch;
+
+use crate::editor::EditorBuffer;
+
+/// 可同時使用的標記樣式數量。 / Number of independent mark styles.
+pub const MARK_STYLE_COUNT: usize = 5;
+
+/// 五種標記樣式，對應 Notepad++ 的「使用第 N 種樣式」。 / The five mark styles, matching Notepad++'s "Using Nth Style".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MarkStyle {
+    First,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+}
+
+impl MarkStyle {
+    /// 依序列出所有樣式。 / Every style in order.
+    pub const ALL: [MarkStyle; MARK_STYLE_COUNT] = [
+        MarkStyle::First,
+        MarkStyle::Second,
+        MarkStyle::Third,
+        MarkStyle::Fourth,
+        MarkStyle::Fifth,
+    ];
+
+    /// 以 0 起算的索引。 / Zero-based index of the style.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// 由 0 起算的索引取得樣式。 / Looks up a style by zero-based index.
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+}
+
+/// 每種樣式各自的已標記範圍（位元組位移，已排序且不重疊）。 / Marked byte ranges per style, kept sorted and non-overlapping.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkStyles {
+    styles: [Vec<Range<usize>>; MARK_STYLE_COUNT],
+}
+
+impl MarkStyles {
+    /// 標記範圍，與重疊或相鄰的範圍合併；空範圍會被忽略。 / Marks a range, merging it with overlapping or adjacent ones; empty ranges are ignored.
+    pub fn mark(&mut self, style: MarkStyle, range: Range<usize>) -> bool {
+        if range.start >= range.end {
+            return false;
+        }
+        let ranges = &mut self.styles[style.index()];
+        let first = ranges.partition_point(|existing| existing.end < range.start);
+        let last = ranges.partition_point(|existing| existing.start <= range.end);
+        if last == first + 1 && ranges[first].start <= range.start && range.end <= ranges[first].end
+        {
+            return false;
+        }
+        let mut merged = range;
+        if first < last {
+            merged.start = merged.start.min(ranges[first].start);
+            merged.end = merged.end.max(ranges[last - 1].end);
+        }
+        ranges.splice(first..last, [merged]);
+        true
+    }
+
+    /// 以樣式標記搜尋結果，回傳新增的標記數。 / Marks search matches with a style, returning how many added new marks.
+    pub fn mark_matches<'a>(
+        &mut self,
+        style: MarkStyle,
+        matches: impl IntoIterator<Item = &'a SearchMatch>,
+    ) -> usize {
+        matches
+            .into_iter()
+            .filter(|hit| self.mark(style, hit.start..hit.end))
+            .count()
+    }
+
+    /// 以樣式標記每個游標的選取範圍。 / Marks every caret selection in the buffer with a style.
+    pub fn mark_selections(&mut self, style: MarkStyle, buffer: &EditorBuffer) -> usize {
+        buffer
+            .carets()
+            .iter()
+            .filter_map(|caret| caret.selection())
+            .filter(|selection| self.mark(style, selection.start()..selection.end()))
+            .count()
+    }
+
+    /// 取得樣式的所有範圍。 / Returns the ranges marked with a style.
+    pub fn ranges(&self, style: MarkStyle) -> &[Range<usize>] {
+        &self.styles[style.index()]
+    }
+
+    /// 依樣式順序列出所有標記。 / Iterates over every mark, style by style.
+    pub fn iter(&self) -> impl Iterator<Item = (MarkStyle, Range<usize>)> + '_ {
+        MarkStyle::ALL.into_iter().flat_map(move |style| {
+            self.ranges(style)
+                .iter()
+                .map(move |range| (style, range.clone()))
+        })
+    }
+
+    /// 列出涵蓋指定位移的樣式。 / Styles whose marks cover the given offset.
+    pub fn styles_at(&self, offset: usize) -> impl Iterator<Item = MarkStyle> + '_ {
+        MarkStyle::ALL.into_iter().filter(move |style| {
+            let ranges = self.ranges(*style);
+            let idx = ranges.partition_point(|range| range.end <= offset);
+            ranges.get(idx).is_some_and(|range| range.start <= offset)
+        })
+    }
+
+    /// 取得位移之後的下一個標記。 / Finds the next mark of a style starting after the offset.
+    pub fn next_after(&self, style: MarkStyle, offset: usize) -> Option<Range<usize>> {
+        let ranges = self.ranges(style);
+        let idx = ranges.partition_point(|range| range.start <= offset);
+        ranges.get(idx).cloned()
+    }
+
+    /// 取得位移之前的上一個標記。 / Finds the previous mark of a style starting before the offset.
+    pub fn previous_before(&self, style: MarkStyle, offset: usize) -> Option<Range<usize>> {
+        let ranges = self.ranges(style);
+        let idx = ranges.partition_point(|range| range.start < offset);
+        idx.checked_sub(1).map(|idx| ranges[idx].clone())
+    }
+
+    /// 清除單一樣式，回傳移除的標記數。 / Clears one style, returning how many marks were removed.
+    pub fn clear(&mut self, style: MarkStyle) -> usize {
+        let ranges = &mut self.styles[style.index()];
+        let removed = ranges.len();
+        ranges.clear();
+        removed
+    }
+
+    /// 清除所有樣式。 / Clears every style.
+    pub fn clear_all(&mut self) {
+        for ranges in &mut self.styles {
+            ranges.clear();
+        }
+    }
+
+    /// 樣式的標記數。 / Number of marks for a style.
+    pub fn count(&self, style: MarkStyle) -> usize {
+        self.ranges(style).len()
+    }
+
+    /// 是否沒有任何標記。 / Indicates whether no style holds a mark.
+    pub fn is_empty(&self) -> bool {
+        self.styles.iter().all(Vec::is_empty)
+    }
+
+    /// 依文字編輯調整標記：`start` 處刪除 `removed` 位元組並插入 `inserted` 位元組。 / Shifts marks for an edit that replaced `removed` bytes at `start` with `inserted` bytes.
+    ///
+    /// 被取代的文字不再標記，標記邊界上插入的文字也不會納入標記。 / Replaced text loses its mark, and text inserted at a mark's edge stays unmarked.
+    pub fn apply_edit(&mut self, start: usize, removed: usize, inserted: usize) {
+        let removed_end = start + removed;
+        let shift = |offset: usize| offset - removed + inserted;
+        for ranges in &mut self.styles {
+            for range in ranges.iter_mut() {
+                let new_start = if range.start < start {
+                    range.start
+                } else if range.start >= removed_end {
+                    shift(range.start)
+                } else {
+                    start + inserted
+                };
+                let new_end = if range.end <= start {
+                    range.end
+                } else if range.end >= removed_end {
+                    shift(range.end)
+                } else {
+                    start
+                };
+                *range = new_start..new_end;
+            }
+            ranges.retain(|range| range.start < range.end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{Caret, Selection};
+    use rustnotepad_search::{SearchEngine, SearchOptions};
+
+    #[test]
+    fn styles_hold_independent_merged_ranges() {
+        let mut marks = MarkStyles::default();
+        assert!(marks.mark(MarkStyle::First, 10..14));
+        assert!(marks.mark(MarkStyle::First, 2..4));
+        assert!(marks.mark(MarkStyle::First, 4..6));
+        assert!(!marks.mark(MarkStyle::First, 11..13));
+        assert!(!marks.mark(MarkStyle::First, 5..5));
+        assert!(marks.mark(MarkStyle::Third, 3..12));
+        assert_eq!(marks.ranges(MarkStyle::First), [2..6, 10..14]);
+        assert_eq!(marks.count(MarkStyle::First), 2);
+        assert_eq!(marks.count(MarkStyle::Second), 0);
+        assert_eq!(
+            marks.styles_at(5).collect::<Vec<_>>(),
+            [MarkStyle::First, MarkStyle::Third]
+        );
+        assert_eq!(marks.next_after(MarkStyle::First, 2), Some(10..14));
+        assert_eq!(marks.previous_before(MarkStyle::First, 10), Some(2..6));
+        assert_eq!(marks.previous_before(MarkStyle::First, 2), None);
+
+        marks.apply_edit(4, 7, 1);
+        assert_eq!(marks.ranges(MarkStyle::First), [2..4, 5..8]);
+        assert_eq!(marks.ranges(MarkStyle::Third).first(), Some(&(3..6)));
+        marks.apply_edit(5, 0, 2);
+        marks.apply_edit(10, 0, 1);
+        assert_eq!(marks.ranges(MarkStyle::First), [2..4, 7..10]);
+        marks.apply_edit(7, 3, 0);
+        assert_eq!(marks.clear(MarkStyle::First), 1);
+        assert_eq!(marks.iter().collect::<Vec<_>>(), [(MarkStyle::Third, 3..7)]);
+    }
+
+    #[test]
+    fn matches_and_selections_can_be_marked() {
+        let text = "foo bar foo\nbaz foo";
+        let hits = SearchEngine::new(text)
+            .find_all(&SearchOptions::new("foo"))
+            .unwrap();
+        let mut marks = MarkStyles::default();
+        assert_eq!(marks.mark_matches(MarkStyle::Second, &hits), 3);
+        assert_eq!(marks.mark_matches(MarkStyle::Second, &hits), 0);
+
+        let buffer = EditorBuffer::with_carets(
+            text,
+            vec![
+                Caret::with_selection(7, Selection::new(4, 7)),
+                Caret::new(12),
+            ],
+        )
+        .unwrap();
+        assert_eq!(marks.mark_selections(MarkStyle::Fifth, &buffer), 1);
+        assert_eq!(marks.ranges(MarkStyle::Fifth).first(), Some(&(4..7)));
+        assert_eq!(MarkStyle::from_index(4), Some(MarkStyle::Fifth));
+        assert_eq!(MarkStyle::from_index(5), None);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::editor::{Caret, EditorBuffer, EditorError, Selection};
+use crate::multi_cursor::merge_carets;
+use crate::text_buffer::TextBuffer;
+
+/// 游標移動方式。 / Ways a caret can move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    CharLeft,
+    CharRight,
+    /// 移到上一個單字的開頭。 / To the start of the previous word.
+    WordLeft,
+    /// 移到下一個單字的開頭。 / To the start of the next word.
+    WordRight,
+    /// 智慧 Home：先到第一個非空白字元，再按一次到行首。 / Smart home: first non-blank character, then column 0 on a second press.
+    LineStart,
+    LineEnd,
+    LineUp,
+    LineDown,
+    /// 移到目前或上一個段落的開頭（段落以空白行分隔）。 / To the start of the current or previous paragraph (paragraphs are separated by blank lines).
+    ParagraphUp,
+    /// 移到下一個段落的開頭。 / To the start of the next paragraph.
+    ParagraphDown,
+    PageUp {
+        lines: usize,
+    },
+    PageDown {
+        lines: usize,
+    },
+    DocumentStart,
+    DocumentEnd,
+    /// 移到游標旁括號的對應括號，停在同一側。 / To the bracket matching the one next to the caret, on the same side.
+    MatchingBracket,
+}
+
+/// 移動後的位置與垂直移動要保留的欄位。 / Position after a motion and the column vertical moves keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MotionTarget {
+    pub position: usize,
+    pub goal_column: Option<usize>,
+}
+
+/// 計算單一位置依移動方式到達的位置。 / Computes where a position ends up after a motion.
+///
+/// 只有上下與換頁移動會保留 `goal_column`，其餘移動會清除。 / Only line and page motions keep `goal_column`; every other motion clears it.
+pub fn motion_target(
+    text: &TextBuffer,
+    position: usize,
+    goal_column: Option<usize>,
+    motion: Motion,
+) -> MotionTarget {
+    let position = position.min(text.len());
+    let vertical = |lines: isize| {
+        let (line, column) = text.line_column(position);
+        let goal = goal_column.unwrap_or(column);
+        let target = line
+            .saturating_add_signed(lines)
+            .min(text.line_count().saturating_sub(1));
+        MotionTarget {
+            position: text.offset_of(target, goal),
+            goal_column: Some(goal),
+        }
+    };
+    let position = match motion {
+        Motion::LineUp => return vertical(-1),
+        Motion::LineDown => return vertical(1),
+        Motion::PageUp { lines } => return vertical(-(lines as isize)),
+        Motion::PageDown { lines } => return vertical(lines as isize),
+        Motion::CharLeft => text.prev_char_boundary(position).unwrap_or(0),
+        Motion::CharRight => text.next_char_boundary(position).unwrap_or(position),
+        Motion::WordLeft => word_left(text, position),
+        Motion::WordRight => word_right(text, position),
+        Motion::LineStart => smart_home(text, position),
+        Motion::LineEnd => line_content_end(text, text.line_of_offset(position)),
+        Motion::ParagraphUp => paragraph_up(text, position),
+        Motion::ParagraphDown => paragraph_down(text, position),
+        Motion::DocumentStart => 0,
+        Motion::DocumentEnd => text.len(),
+        Motion::MatchingBracket => matching_bracket(text, position).unwrap_or(position),
+    };
+    MotionTarget {
+        position,
+        goal_column: None,
+    }
+}
+
+/// 移動單一游標；`extend` 時保留固定端並延伸選取。 / Moves one caret; `extend` keeps the anchor and grows the selection.
+///
+/// 不延伸時，左右移動會先收合既有選取到對應的一端。 / Without `extend`, left/right motions first collapse a selection to that side.
+pub fn move_caret(text: &TextBuffer, caret: &Caret, motion: Motion, extend: bool) -> Caret {
+    if !extend {
+        if let Some(selection) = caret.selection() {
+            match motion {
+                Motion::CharLeft => return Caret::new(selection.start()),
+                Motion::CharRight => return Caret::new(selection.end()),
+                _ => {}
+            }
+        }
+    }
+    let target = motion_target(text, caret.position(), caret.goal_column(), motion);
+    let anchor = if extend {
+        caret.anchor()
+    } else {
+        target.position
+    };
+    let moved = if anchor == target.position {
+        Caret::new(target.position)
+    } else {
+        Caret::with_selection(target.position, Selection::new(anchor, target.position))
+    };
+    match target.goal_column {
+        Some(goal) => moved.with_goal_column(goal),
+        None => moved,
+    }
+}
+
+/// 以相同移動方式移動所有游標，重疊的游標會合併。 / Moves every caret with the same motion, merging carets that collide.
+pub fn move_carets(
+    buffer: &mut EditorBuffer,
+    motion: Motion,
+    extend: bool,
+) -> Result<(), EditorError> {
+    let carets: Vec<Caret> = buffer
+        .carets()
+        .iter()
+        .map(|caret| move_caret(buffer.text(), caret, motion, extend))
+        .collect();
+    buffer.set_carets(merge_carets(carets))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Punctuation,
+    Space,
+    Newline,
+}
+
+fn char_class(ch: char) -> CharClass {
+    if ch == '\n' || ch == '\r' {
+        CharClass::Newline
+    } else if ch.is_whitespace() {
+        CharClass::Space
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// `position` 之前的字元與其位元組位置，由近而遠。 / Characters before `position` with their byte offsets, nearest first.
+fn chars_before(text: &TextBuffer, position: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+    text.chars_before(position).scan(position, |offset, ch| {
+        *offset -= ch.len_utf8();
+        Some((*offset, ch))
+    })
+}
+
+/// 從 `position` 起的字元與其位元組位置。 / Characters from `position` on with their byte offsets.
+fn chars_after(text: &TextBuffer, position: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+    text.chars_after(position).scan(position, |offset, ch| {
+        let at = *offset;
+        *offset += ch.len_utf8();
+        Some((at, ch))
+    })
+}
+
+fn word_left(text: &TextBuffer, position: usize) -> usize {
+    let mut chars = chars_before(text, position).peekable();
+    let mut position = position;
+    let moved_from = position;
+    while let Some((idx, _)) = chars.next_if(|(_, ch)| char_class(*ch) == CharClass::Space) {
+        position = idx;
+    }
+    let Some(&(_, first)) = chars.peek() else {
+        return position;
+    };
+    let class = char_class(first);
+    if class == CharClass::Newline {
+        if position == moved_from {
+            // \r\n 視為一個換行。 / `\r\n` counts as one line break.
+            if let Some((idx, ch)) = chars.next() {
+                position = idx;
+                if ch == '\n' {
+                    if let Some((idx, _)) = chars.next_if(|(_, ch)| *ch == '\r') {
+                        position = idx;
+                    }
+                }
+            }
+        }
+        return position;
+    }
+    while let Some((idx, _)) = chars.next_if(|(_, ch)| char_class(*ch) == class) {
+        position = idx;
+    }
+    position
+}
+
+fn word_right(text: &TextBuffer, position: usize) -> usize {
+    let mut chars = chars_after(text, position).peekable();
+    let Some(&(_, first)) = chars.peek() else {
+        return position;
+    };
+    let class = char_class(first);
+    if class == CharClass::Newline {
+        chars.next();
+        if first == '\r' {
+            chars.next_if(|(_, ch)| *ch == '\n');
+        }
+        return chars.peek().map_or(text.len(), |(idx, _)| *idx);
+    }
+    if class != CharClass::Space {
+        while chars.next_if(|(_, ch)| char_class(*ch) == class).is_some() {}
+    }
+    while chars
+        .next_if(|(_, ch)| char_class(*ch) == CharClass::Space)
+        .is_some()
+    {}
+    chars.peek().map_or(text.len(), |(idx, _)| *idx)
+}
+
+fn smart_home(text: &TextBuffer, position: usize) -> usize {
+    let line = text.line_of_offset(position);
+    let start = text.line_start(line);
+    let indent = text
+        .line_text(line)
+        .chars()
+        .take_while(|ch| *ch == ' ' || *ch == '\t')
+        .count();
+    let first_non_blank = start + indent;
+    if position == first_non_blank {
+        start
+    } else {
+        first_non_blank
+    }
+}
+
+/// 行尾位置，`\r\n` 的 `\r` 不算在內。 / End of the line content, excluding the `\r` of `\r\n`.
+fn line_content_end(text: &TextBuffer, line: usize) -> usize {
+    let end = text.line_end(line);
+    if end > text.line_start(line) && text.slice(end - 1..end) == "\r" {
+        end - 1
+    } else {
+        end
+    }
+}
+
+fn is_blank_line(text: &TextBuffer, line: usize) -> bool {
+    text.line_text(line).trim().is_empty()
+}
+
+fn paragraph_up(text: &TextBuffer, position: usize) -> usize {
+    let current = text.line_of_offset(position);
+    let mut line = current;
+    if line > 0 && (position == text.line_start(line) || is_blank_line(text, line)) {
+        line -= 1;
+    }
+    while line > 0 && is_blank_line(text, line) {
+        line -= 1;
+    }
+    while line > 0 && !is_blank_line(text, line - 1) {
+        line -= 1;
+    }
+    text.line_start(line)
+}
+
+fn paragraph_down(text: &TextBuffer, position: usize) -> usize {
+    let last = text.line_count().saturating_sub(1);
+    let mut line = text.line_of_offset(position);
+    while line <= last && !is_blank_line(text, line) {
+        line += 1;
+    }
+    while line <= last && is_blank_line(text, line) {
+        line += 1;
+    }
+    if line > last {
+        text.len()
+    } else {
+        text.line_start(line)
+    }
+}
+
+const BRACKET_PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// 找出游標右側（優先）或左側括號的對應括號，回傳停在同一側的位置。 / Finds the partner of the bracket after (preferred) or before the caret, landing on the same side of it.
+fn matching_bracket(text: &TextBuffer, position: usize) -> Option<usize> {
+    let after = text.chars_after(position).next();
+    let before = text.chars_before(position).next();
+    if let Some(found) = after.and_then(|ch| partner(text, position, ch)) {
+        return Some(found);
+    }
+    let ch = before?;
+    partner(text, position - ch.len_utf8(), ch).map(|found| found + ch.len_utf8())
+}
+
+fn partner(text: &TextBuffer, at: usize, bracket: char) -> Option<usize> {
+    for (open, close) in BRACKET_PAIRS {
+        let mut depth = 0usize;
+        if bracket == open {
+            for (idx, ch) in chars_after(text, at) {
+                if ch == open {
+                    depth += 1;
+                } else if ch == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(idx);
+                    }
+                }
+            }
+            return None;
+        }
+        if bracket == close {
+            for (idx, ch) in chars_before(text, at + bracket.len_utf8()) {
+                if ch == close {
+                    depth += 1;
+                } else if ch == open {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(idx);
+                    }
+                }
+            }
+            return None;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(text: &str, position: usize, motion: Motion) -> usize {
+        motion_target(&TextBuffer::from(text), position, None, motion).position
+    }
+
+    #[test]
+    fn word_line_and_bracket_motions() {
+        let text = "let foo_bar = (a[1] + b);\r\n    next();";
+        assert_eq!(target(text, 0, Motion::WordRight), 4);
+        assert_eq!(target(text, 4, Motion::WordRight), 12);
+        assert_eq!(target(text, 12, Motion::WordRight), 14);
+        assert_eq!(target(text, 25, Motion::WordRight), 27);
+        assert_eq!(target(text, 27, Motion::WordRight), 31);
+        assert_eq!(target(text, 31, Motion::WordLeft), 27);
+        assert_eq!(target(text, 27, Motion::WordLeft), 25);
+        assert_eq!(target(text, 12, Motion::WordLeft), 4);
+        assert_eq!(target(text, 8, Motion::WordLeft), 4);
+        assert_eq!(target(text, text.len(), Motion::WordRight), text.len());
+
+        assert_eq!(target(text, 35, Motion::LineStart), 31);
+        assert_eq!(target(text, 31, Motion::LineStart), 27);
+        assert_eq!(target(text, 5, Motion::LineEnd), 25);
+        assert_eq!(target(text, 5, Motion::DocumentEnd), text.len());
+
+        assert_eq!(target(text, 14, Motion::MatchingBracket), 23);
+        assert_eq!(target(text, 24, Motion::MatchingBracket), 15);
+        assert_eq!(target(text, 17, Motion::MatchingBracket), 19);
+        assert_eq!(target(text, 2, Motion::MatchingBracket), 2);
+    }
+
+    #[test]
+    fn paragraph_page_and_extending_motions() {
+        let text = "one\ntwo\n\n\nthree\nfour\n\nfive";
+        assert_eq!(target(text, 1, Motion::ParagraphDown), 10);
+        assert_eq!(target(text, 10, Motion::ParagraphDown), 22);
+        assert_eq!(target(text, 22, Motion::ParagraphDown), text.len());
+        assert_eq!(target(text, 17, Motion::ParagraphUp), 10);
+        assert_eq!(target(text, 10, Motion::ParagraphUp), 0);
+        assert_eq!(target(text, 0, Motion::ParagraphUp), 0);
+
+        let buffer = TextBuffer::from(text);
+        let page = motion_target(&buffer, 2, None, Motion::PageDown { lines: 4 });
+        assert_eq!((page.position, page.goal_column), (12, Some(2)));
+        let back = motion_target(
+            &buffer,
+            page.position,
+            page.goal_column,
+            Motion::PageUp { lines: 10 },
+        );
+        assert_eq!(back.position, 2);
+
+        let mut editor =
+            EditorBuffer::with_carets(text, vec![Caret::new(1), Caret::new(5)]).unwrap();
+        move_carets(&mut editor, Motion::LineEnd, true).unwrap();
+        let selections: Vec<_> = editor
+            .carets()
+            .iter()
+            .map(|caret| caret.selection().map(|s| (s.start(), s.end())))
+            .collect();
+        assert_eq!(selections, [Some((1, 3)), Some((5, 7))]);
+        move_carets(&mut editor, Motion::CharLeft, false).unwrap();
+        assert_eq!(editor.carets(), [Caret::new(1), Caret::new(5)]);
+        move_carets(&mut editor, Motion::DocumentStart, true).unwrap();
+        assert_eq!(editor.carets().len(), 1);
+        assert_eq!(
+            editor.carets()[0].selection().map(|s| (s.start(), s.end())),
+            Some((0, 5))
+        );
+    }
+}
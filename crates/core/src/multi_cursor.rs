@@ -0,0 +1,341 @@
+use std::ops::Range;
+
+use rustnotepad_search::{SearchMatch, SearchOptions};
+
+use crate::editor::{Caret, EditorBuffer, EditorError, Selection};
+use crate::motion::{move_carets, Motion};
+use crate::text_buffer::TextBuffer;
+
+/// 尋找與選取相同內容時的比對規則。 / Matching rules used when looking for occurrences of the selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccurrenceOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+impl Default for OccurrenceOptions {
+    fn default() -> Self {
+        Self {
+            case_sensitive: true,
+            whole_word: false,
+        }
+    }
+}
+
+/// 取得位移所在（或緊鄰左側）的單字範圍。 / Returns the word containing the offset, or ending right before it.
+pub fn word_range_at(text: &TextBuffer, offset: usize) -> Option<Range<usize>> {
+    let offset = offset.min(text.len());
+    let start = offset
+        - text
+            .chars_before(offset)
+            .take_while(|ch| is_word_char(*ch))
+            .map(char::len_utf8)
+            .sum::<usize>();
+    let end = offset
+        + text
+            .chars_after(offset)
+            .take_while(|ch| is_word_char(*ch))
+            .map(char::len_utf8)
+            .sum::<usize>();
+    (start < end).then_some(start..end)
+}
+
+/// Ctrl+D：主游標無選取時先選取所在單字，否則選取下一個相同內容並加入游標；回傳是否有變更。 / Ctrl+D: selects the word at the primary caret, or adds a caret on the next occurrence of its selection; returns whether anything changed.
+///
+/// 主游標為最後加入的游標；搜尋到文件結尾後會從開頭繼續，已選取的位置會略過。 / The primary caret is the last one added; the search wraps around and skips occurrences that are already selected.
+pub fn add_next_occurrence(
+    buffer: &mut EditorBuffer,
+    options: OccurrenceOptions,
+) -> Result<bool, EditorError> {
+    let Some(needle) = primary_selection(buffer) else {
+        return select_word_at_primary(buffer);
+    };
+    let taken: Vec<(usize, usize)> = buffer.carets().iter().map(Caret::edit_range).collect();
+    let is_free = |hit: &&SearchMatch| {
+        !taken
+            .iter()
+            .any(|&(start, end)| start < hit.end && hit.start < end)
+    };
+    let hits = find_occurrences(buffer.text(), &needle, options);
+    let next = hits
+        .iter()
+        .filter(|hit| hit.start >= needle.end)
+        .find(is_free)
+        .or_else(|| hits.iter().find(is_free));
+    match next {
+        Some(hit) => {
+            buffer.push_caret(selection_caret(hit.start, hit.end))?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// 將游標換成主游標選取內容（無選取時為所在單字）的所有出現位置；回傳游標數。 / Replaces the carets with every occurrence of the primary selection (or the word at the caret); returns the caret count.
+pub fn select_all_occurrences(
+    buffer: &mut EditorBuffer,
+    options: OccurrenceOptions,
+) -> Result<usize, EditorError> {
+    let needle = match primary_selection(buffer) {
+        Some(needle) => needle,
+        None => {
+            if !select_word_at_primary(buffer)? {
+                return Ok(0);
+            }
+            primary_selection(buffer).unwrap_or_default()
+        }
+    };
+    let carets: Vec<Caret> = find_occurrences(buffer.text(), &needle, options)
+        .iter()
+        .map(|hit| selection_caret(hit.start, hit.end))
+        .collect();
+    let count = carets.len();
+    if count > 0 {
+        buffer.set_carets(carets)?;
+    }
+    Ok(count)
+}
+
+/// 在最上方游標的上一行同一欄加入游標；回傳是否加入。 / Adds a caret on the line above the topmost caret, at the same column; returns whether one was added.
+pub fn add_caret_above(buffer: &mut EditorBuffer) -> Result<bool, EditorError> {
+    add_caret_vertically(buffer, -1)
+}
+
+/// 在最下方游標的下一行同一欄加入游標；回傳是否加入。 / Adds a caret on the line below the bottommost caret, at the same column; returns whether one was added.
+pub fn add_caret_below(buffer: &mut EditorBuffer) -> Result<bool, EditorError> {
+    add_caret_vertically(buffer, 1)
+}
+
+/// 將跨行的選取拆成每行一個游標；回傳游標數。 / Splits every multi-line selection into one caret per line; returns the caret count.
+///
+/// 選取結束於行首時不包含該行；空白行得到不帶選取的游標。 / A selection ending at a line start does not include that line; empty lines get a bare caret.
+pub fn split_selection_into_lines(buffer: &mut EditorBuffer) -> Result<usize, EditorError> {
+    let text = buffer.text();
+    let mut carets = Vec::new();
+    for caret in buffer.carets() {
+        let Some(selection) = caret.selection() else {
+            carets.push(caret.clone());
+            continue;
+        };
+        let first = text.line_of_offset(selection.start());
+        let mut last = text.line_of_offset(selection.end());
+        if last > first && text.line_start(last) == selection.end() {
+            last -= 1;
+        }
+        for line in first..=last {
+            let start = text.line_start(line).max(selection.start());
+            let end = text.line_end(line).min(selection.end());
+            carets.push(if start < end {
+                selection_caret(start, end)
+            } else {
+                Caret::new(start)
+            });
+        }
+    }
+    let carets = merge_carets(carets);
+    let count = carets.len();
+    buffer.set_carets(carets)?;
+    Ok(count)
+}
+
+/// 將每個游標上下移動 `lines` 行並保留目標欄位；`extend` 時延伸選取。 / Moves every caret `lines` up (negative) or down, keeping its goal column; `extend` grows the selection instead.
+///
+/// 經過較短的行時游標停在行尾，之後回到原本的欄位；重疊的游標會合併。 / Carets stop at the end of shorter lines and return to their column afterwards; carets that collide are merged.
+pub fn move_carets_vertically(
+    buffer: &mut EditorBuffer,
+    lines: isize,
+    extend: bool,
+) -> Result<(), EditorError> {
+    let motion = if lines < 0 {
+        Motion::PageUp {
+            lines: lines.unsigned_abs(),
+        }
+    } else {
+        Motion::PageDown {
+            lines: lines as usize,
+        }
+    };
+    move_carets(buffer, motion, extend)
+}
+
+fn add_caret_vertically(buffer: &mut EditorBuffer, lines: isize) -> Result<bool, EditorError> {
+    let text = buffer.text();
+    let origin = if lines < 0 {
+        buffer.carets().iter().min_by_key(|caret| caret.position())
+    } else {
+        buffer.carets().iter().max_by_key(|caret| caret.position())
+    };
+    let Some(origin) = origin else {
+        return Ok(false);
+    };
+    let (line, column) = text.line_column(origin.position());
+    let Some(target) = line
+        .checked_add_signed(lines)
+        .filter(|target| *target < text.line_count())
+    else {
+        return Ok(false);
+    };
+    let goal = origin.goal_column().unwrap_or(column);
+    let position = text.offset_of(target, goal);
+    if buffer.carets().iter().any(|caret| {
+        let (start, end) = caret.edit_range();
+        start <= position && position <= end
+    }) {
+        return Ok(false);
+    }
+    buffer.push_caret(Caret::new(position).with_goal_column(goal))?;
+    Ok(true)
+}
+
+fn select_word_at_primary(buffer: &mut EditorBuffer) -> Result<bool, EditorError> {
+    let Some(position) = buffer.carets().last().map(Caret::position) else {
+        return Ok(false);
+    };
+    let Some(word) = word_range_at(buffer.text(), position) else {
+        return Ok(false);
+    };
+    let mut carets = buffer.carets().to_vec();
+    if let Some(primary) = carets.last_mut() {
+        *primary = selection_caret(word.start, word.end);
+    }
+    buffer.set_carets(carets)?;
+    Ok(true)
+}
+
+fn primary_selection(buffer: &EditorBuffer) -> Option<Range<usize>> {
+    let selection = buffer.carets().last()?.selection()?;
+    Some(selection.start()..selection.end())
+}
+
+fn find_occurrences(
+    text: &TextBuffer,
+    needle: &Range<usize>,
+    options: OccurrenceOptions,
+) -> Vec<SearchMatch> {
+    let mut search = SearchOptions::new(text.span(needle.clone()));
+    search.case_sensitive = options.case_sensitive;
+    search.whole_word = options.whole_word;
+    text.find_all(&search).unwrap_or_default()
+}
+
+fn selection_caret(start: usize, end: usize) -> Caret {
+    Caret::with_selection(end, Selection::new(start, end))
+}
+
+fn caret_between(anchor: usize, position: usize) -> Caret {
+    if anchor == position {
+        Caret::new(position)
+    } else {
+        Caret::with_selection(position, Selection::new(anchor, position))
+    }
+}
+
+/// 依位置排序並合併重疊或重複的游標。 / Sorts carets by position and merges overlapping or duplicate ones.
+pub(crate) fn merge_carets(mut carets: Vec<Caret>) -> Vec<Caret> {
+    carets.sort_by_key(Caret::edit_range);
+    let mut merged: Vec<Caret> = Vec::with_capacity(carets.len());
+    for caret in carets {
+        let (start, end) = caret.edit_range();
+        if let Some(previous) = merged.last_mut() {
+            let (prev_start, prev_end) = previous.edit_range();
+            if (prev_start, prev_end) == (start, end) || start < prev_end {
+                let union_end = end.max(prev_end);
+                let head = if caret.position() == start {
+                    prev_start
+                } else {
+                    union_end
+                };
+                let anchor = if head == prev_start {
+                    union_end
+                } else {
+                    prev_start
+                };
+                let goal = caret.goal_column();
+                *previous = caret_between(anchor, head);
+                if let Some(goal) = goal {
+                    *previous = previous.clone().with_goal_column(goal);
+                }
+                continue;
+            }
+        }
+        merged.push(caret);
+    }
+    merged
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selections(buffer: &EditorBuffer) -> Vec<(usize, usize)> {
+        buffer.carets().iter().map(Caret::edit_range).collect()
+    }
+
+    #[test]
+    fn occurrences_are_added_one_by_one_or_all_at_once() {
+        let text = "let foo = foo_bar + foo;\nFoo(foo)";
+        let mut buffer = EditorBuffer::with_carets(text, vec![Caret::new(5)]).unwrap();
+        let options = OccurrenceOptions::default();
+
+        assert!(add_next_occurrence(&mut buffer, options).unwrap());
+        assert_eq!(selections(&buffer), [(4, 7)]);
+        assert!(add_next_occurrence(&mut buffer, options).unwrap());
+        assert!(add_next_occurrence(&mut buffer, options).unwrap());
+        assert_eq!(selections(&buffer), [(4, 7), (10, 13), (20, 23)]);
+        assert!(add_next_occurrence(&mut buffer, options).unwrap());
+        assert!(!add_next_occurrence(&mut buffer, options).unwrap());
+        assert_eq!(buffer.carets().len(), 4);
+
+        buffer.insert_text("bar").unwrap();
+        assert_eq!(buffer.contents(), "let bar = bar_bar + bar;\nFoo(bar)");
+
+        let mut buffer = EditorBuffer::with_carets(text, vec![Caret::new(22)]).unwrap();
+        let whole_word = OccurrenceOptions {
+            case_sensitive: false,
+            whole_word: true,
+        };
+        assert_eq!(select_all_occurrences(&mut buffer, whole_word).unwrap(), 4);
+        assert_eq!(selections(&buffer), [(4, 7), (20, 23), (25, 28), (29, 32)]);
+
+        let mut buffer = EditorBuffer::with_carets(" + ", vec![Caret::new(1)]).unwrap();
+        assert!(!add_next_occurrence(&mut buffer, options).unwrap());
+        assert_eq!(select_all_occurrences(&mut buffer, options).unwrap(), 0);
+    }
+
+    #[test]
+    fn vertical_carets_keep_their_column_and_selections_split_by_line() {
+        let mut buffer =
+            EditorBuffer::with_carets("abcdef\nab\nabcdef\n", vec![Caret::new(4)]).unwrap();
+        assert!(!add_caret_above(&mut buffer).unwrap());
+        assert!(add_caret_below(&mut buffer).unwrap());
+        assert!(add_caret_below(&mut buffer).unwrap());
+        assert_eq!(buffer.caret_line_columns(), [(0, 4), (1, 2), (2, 4)]);
+
+        buffer.insert_text("|").unwrap();
+        assert_eq!(buffer.contents(), "abcd|ef\nab|\nabcd|ef\n");
+
+        let mut buffer =
+            EditorBuffer::with_carets("abcdef\nab\nabcdef\n", vec![Caret::new(4)]).unwrap();
+        move_carets_vertically(&mut buffer, 1, false).unwrap();
+        assert_eq!(buffer.caret_line_columns(), [(1, 2)]);
+        move_carets_vertically(&mut buffer, 1, true).unwrap();
+        assert_eq!(selections(&buffer), [(9, 14)]);
+        assert_eq!(buffer.caret_line_columns(), [(2, 4)]);
+        move_carets_vertically(&mut buffer, -5, true).unwrap();
+        assert_eq!(selections(&buffer), [(4, 9)]);
+
+        let mut buffer = EditorBuffer::with_carets(
+            "one\n\nthree\nfour",
+            vec![Caret::with_selection(11, Selection::new(1, 11))],
+        )
+        .unwrap();
+        assert_eq!(split_selection_into_lines(&mut buffer).unwrap(), 3);
+        assert_eq!(selections(&buffer), [(1, 3), (4, 4), (5, 10)]);
+        move_carets_vertically(&mut buffer, -2, false).unwrap();
+        assert_eq!(buffer.caret_line_columns(), [(0, 0), (0, 3)]);
+    }
+}
@@ -9,7 +9,7 @@ use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use thiserror::Error;
 
-use crate::{Document, DocumentError, Encoding, LegacyEncoding, LineEnding};
+use crate::{Document, DocumentError, Encoding, LineEnding};
 
 /// 自動儲存與還原流程的錯誤型別。 / Error type for autosave and recovery routines.
 #[derive(Debug, Error)]
@@ -234,16 +234,7 @@ fn parse_metadata(contents: &str) -> Result<ParsedMetadata, RecoveryError> {
 }
 
 fn parse_encoding(value: &str) -> Option<Encoding> {
-    match value {
-        "utf-8" => Some(Encoding::Utf8),
-        "utf-16le" => Some(Encoding::Utf16Le),
-        "utf-16be" => Some(Encoding::Utf16Be),
-        "windows-1252" => Some(Encoding::Legacy(LegacyEncoding::Windows1252)),
-        "shift-jis" => Some(Encoding::Legacy(LegacyEncoding::ShiftJis)),
-        "gbk" => Some(Encoding::Legacy(LegacyEncoding::Gbk)),
-        "big5" => Some(Encoding::Legacy(LegacyEncoding::Big5)),
-        _ => None,
-    }
+    Encoding::from_label(value)
 }
 
 fn parse_line_ending(value: &str) -> Option<LineEnding> {
@@ -0,0 +1,461 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rustnotepad_search::ReplacePlan;
+use thiserror::Error;
+
+use crate::document::write_atomically;
+use crate::{Document, DocumentError};
+
+const MANIFEST_FILE: &str = "journal.meta";
+
+/// 多檔取代與還原流程的錯誤型別。 / Error type for journaled multi-file replacements.
+#[derive(Debug, Error)]
+pub enum ReplaceJournalError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{path}: {source}")]
+    Document {
+        path: PathBuf,
+        #[source]
+        source: DocumentError,
+    },
+    #[error("{0} changed on disk after the replace plan was built")]
+    Stale(PathBuf),
+    #[error("failed to write {path}: {source}; files written earlier were restored")]
+    WriteFailed {
+        path: PathBuf,
+        #[source]
+        source: DocumentError,
+    },
+    #[error("{0} was modified after the replace; force the rollback to overwrite it")]
+    ModifiedSince(PathBuf),
+    #[error("invalid replace journal: {0}")]
+    InvalidJournal(String),
+}
+
+/// 一次已套用的多檔取代紀錄。 / Record of one applied multi-file replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub id: String,
+    pub timestamp: SystemTime,
+    pub replacements: usize,
+    pub files: Vec<PathBuf>,
+    dir: PathBuf,
+    hashes: Vec<u64>,
+}
+
+/// 因取代文字無法以原編碼表示而略過的檔案。 / A file left untouched because the replacement text cannot be encoded in its encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRewrite {
+    pub path: PathBuf,
+    pub encoding: &'static str,
+    pub replacements: usize,
+}
+
+/// 套用取代計畫的結果。 / Result of applying a replace plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceOutcome {
+    /// 實際寫入的日誌；全部檔案都被略過時為 `None`。 / Journal of the written files; `None` when every file was skipped.
+    pub entry: Option<JournalEntry>,
+    pub skipped: Vec<SkippedRewrite>,
+}
+
+/// 以日誌保存原始內容，讓多檔取代可整批套用與還原。 / Applies replace plans atomically across files and journals the originals for rollback.
+#[derive(Debug, Clone)]
+pub struct ReplaceJournal {
+    root: PathBuf,
+}
+
+/// 寫入前已編碼完成的單一檔案。 / One file with its original and re-encoded bytes, ready to write.
+struct PreparedWrite {
+    path: PathBuf,
+    document: Document,
+    original: Vec<u8>,
+    replaced: Vec<u8>,
+}
+
+impl ReplaceJournal {
+    /// 建立以指定資料夾保存日誌的實例。 / Creates a journal stored under the provided directory.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 套用計畫中選取的取代；任何檔案失敗時整批還原。 / Applies the selected replacements, restoring every file if any write fails.
+    ///
+    /// 檔案經由 `Document::open`/`Document::save` 寫回，保留編碼、BOM 與行尾；取代文字無法以原編碼表示的檔案會被略過並回報。 / Files are written back through `Document::open`/`Document::save`, keeping encoding, BOM and line endings; files whose encoding cannot represent the replacement are skipped and reported.
+    pub fn apply(&self, plan: &ReplacePlan) -> Result<ReplaceOutcome, ReplaceJournalError> {
+        let rewrites = plan.rewrites();
+        let mut prepared = Vec::with_capacity(rewrites.len());
+        let mut skipped = Vec::new();
+        let mut replacements = 0;
+        // 先驗證並編碼全部檔案，任何錯誤都不會留下部分寫入。 / Validate and encode everything first so errors leave no partial writes.
+        for rewrite in &rewrites {
+            let path = rewrite.path.to_path_buf();
+            let original = fs::read(&path)?;
+            let mut document =
+                Document::open(&path).map_err(|source| ReplaceJournalError::Document {
+                    path: path.clone(),
+                    source,
+                })?;
+            if document.contents() != rewrite.original {
+                return Err(ReplaceJournalError::Stale(path));
+            }
+            document.set_contents(rewrite.replaced.clone());
+            let replaced = match document.serialise_contents() {
+                Ok(bytes) => bytes,
+                Err(DocumentError::Unrepresentable(encoding)) => {
+                    skipped.push(SkippedRewrite {
+                        path,
+                        encoding,
+                        replacements: rewrite.replacements,
+                    });
+                    continue;
+                }
+                Err(source) => return Err(ReplaceJournalError::Document { path, source }),
+            };
+            replacements += rewrite.replacements;
+            prepared.push(PreparedWrite {
+                path,
+                document,
+                original,
+                replaced,
+            });
+        }
+        if prepared.is_empty() {
+            return Ok(ReplaceOutcome {
+                entry: None,
+                skipped,
+            });
+        }
+
+        let entry = self.record(&prepared, replacements)?;
+        for index in 0..prepared.len() {
+            if let Err(source) = prepared[index].document.save() {
+                for done in &prepared[..index] {
+                    let _ = write_atomically(&done.path, &done.original);
+                }
+                let _ = fs::remove_dir_all(&entry.dir);
+                return Err(ReplaceJournalError::WriteFailed {
+                    path: prepared[index].path.clone(),
+                    source,
+                });
+            }
+        }
+        Ok(ReplaceOutcome {
+            entry: Some(entry),
+            skipped,
+        })
+    }
+
+    /// 列出所有日誌，依時間新到舊排序。 / Lists journal entries, newest first.
+    pub fn list(&self) -> Result<Vec<JournalEntry>, ReplaceJournalError> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for item in fs::read_dir(&self.root)? {
+            let dir = item?.path();
+            let manifest = dir.join(MANIFEST_FILE);
+            if !manifest.is_file() {
+                continue;
+            }
+            let id = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            entries.push(parse_manifest(id, &dir, &fs::read_to_string(&manifest)?)?);
+        }
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(b.id.cmp(&a.id)));
+        Ok(entries)
+    }
+
+    /// 依識別碼尋找日誌。 / Finds a journal entry by id.
+    pub fn find(&self, id: &str) -> Result<Option<JournalEntry>, ReplaceJournalError> {
+        Ok(self.list()?.into_iter().find(|entry| entry.id == id))
+    }
+
+    /// 還原日誌中的原始內容並移除該日誌，回傳還原的檔案數。 / Restores the journaled originals, removes the entry and returns the number of files restored.
+    ///
+    /// 未指定 `force` 時，若檔案在取代後又被修改則拒絕還原。 / Without `force`, files edited after the replace abort the rollback.
+    pub fn rollback(
+        &self,
+        entry: &JournalEntry,
+        force: bool,
+    ) -> Result<usize, ReplaceJournalError> {
+        let mut originals = Vec::with_capacity(entry.files.len());
+        for (index, (path, hash)) in entry.files.iter().zip(&entry.hashes).enumerate() {
+            if !force {
+                let unchanged = fs::read(path)
+                    .map(|current| content_hash(&current) == *hash)
+                    .unwrap_or(false);
+                if !unchanged {
+                    return Err(ReplaceJournalError::ModifiedSince(path.clone()));
+                }
+            }
+            originals.push(fs::read(entry.dir.join(format!("{index}.orig")))?);
+        }
+        for (path, original) in entry.files.iter().zip(&originals) {
+            write_atomically(path, original)?;
+        }
+        self.discard(entry)?;
+        Ok(entry.files.len())
+    }
+
+    /// 刪除日誌而不還原檔案。 / Deletes a journal entry without touching the files.
+    pub fn discard(&self, entry: &JournalEntry) -> Result<(), ReplaceJournalError> {
+        if entry.dir.exists() {
+            fs::remove_dir_all(&entry.dir)?;
+        }
+        Ok(())
+    }
+
+    fn record(
+        &self,
+        prepared: &[PreparedWrite],
+        replacements: usize,
+    ) -> Result<JournalEntry, ReplaceJournalError> {
+        let timestamp_ms = duration_since_epoch_ms(SystemTime::now());
+        // 以毫秒精度保存，與清單讀回的值一致。 / Keep millisecond precision so listed entries compare equal.
+        let timestamp = UNIX_EPOCH + Duration::from_millis(timestamp_ms);
+        fs::create_dir_all(&self.root)?;
+        let mut suffix = 0;
+        let (id, dir) = loop {
+            let id = format!("{timestamp_ms}-{suffix}");
+            let dir = self.root.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => break (id, dir),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        // 以絕對路徑記錄，從其他目錄也能還原。 / Record absolute paths so undo works from any directory.
+        let files = prepared
+            .iter()
+            .map(|write| std::path::absolute(&write.path))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut manifest = format!("timestamp={timestamp_ms}\nreplacements={replacements}\n");
+        for (index, (write, path)) in prepared.iter().zip(&files).enumerate() {
+            write_atomically(&dir.join(format!("{index}.orig")), &write.original)?;
+            manifest.push_str(&format!(
+                "file={};{:016x}\n",
+                BASE64.encode(path.to_string_lossy().as_bytes()),
+                content_hash(&write.replaced)
+            ));
+        }
+        // 清單最後寫入，存在即代表日誌完整。 / The manifest is written last, so its presence marks a complete entry.
+        write_atomically(&dir.join(MANIFEST_FILE), manifest.as_bytes())?;
+
+        Ok(JournalEntry {
+            id,
+            timestamp,
+            replacements,
+            files,
+            dir,
+            hashes: prepared
+                .iter()
+                .map(|write| content_hash(&write.replaced))
+                .collect(),
+        })
+    }
+}
+
+fn parse_manifest(
+    id: String,
+    dir: &Path,
+    contents: &str,
+) -> Result<JournalEntry, ReplaceJournalError> {
+    let invalid = |message: String| ReplaceJournalError::InvalidJournal(format!("{id}: {message}"));
+    let mut timestamp_ms = None;
+    let mut replacements = 0;
+    let mut files = Vec::new();
+    let mut hashes = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (key, value) = trimmed
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("malformed line: {trimmed}")))?;
+        match key {
+            "timestamp" => {
+                timestamp_ms = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| invalid(format!("invalid timestamp: {value}")))?,
+                );
+            }
+            "replacements" => {
+                replacements = value
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid replacement count: {value}")))?;
+            }
+            "file" => {
+                let (encoded, hash) = value
+                    .split_once(';')
+                    .ok_or_else(|| invalid(format!("malformed file entry: {value}")))?;
+                let decoded = BASE64
+                    .decode(encoded.as_bytes())
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .ok_or_else(|| invalid("failed to decode file path".into()))?;
+                files.push(PathBuf::from(decoded));
+                hashes.push(
+                    u64::from_str_radix(hash, 16)
+                        .map_err(|_| invalid(format!("invalid content hash: {hash}")))?,
+                );
+            }
+            _ => {}
+        }
+    }
+    let timestamp_ms = timestamp_ms.ok_or_else(|| invalid("missing timestamp".into()))?;
+    Ok(JournalEntry {
+        id,
+        timestamp: UNIX_EPOCH
+            .checked_add(Duration::from_millis(timestamp_ms))
+            .unwrap_or(UNIX_EPOCH),
+        replacements,
+        files,
+        dir: dir.to_path_buf(),
+        hashes,
+    })
+}
+
+/// 跨版本穩定的 FNV-1a 雜湊，用來偵測取代後的修改。 / FNV-1a hash, stable across builds, used to detect edits made after a replace.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn duration_since_epoch_ms(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0))
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustnotepad_search::{FileSearchInput, SearchOptions};
+    use tempfile::tempdir;
+
+    fn plan_for(paths: &[&Path], pattern: &str, replacement: &str) -> ReplacePlan {
+        let documents: Vec<_> = paths
+            .iter()
+            .map(|path| (path.to_path_buf(), Document::open(path).unwrap()))
+            .collect();
+        let inputs = documents
+            .iter()
+            .map(|(path, document)| FileSearchInput::new(path.clone(), document.contents()));
+        ReplacePlan::build(inputs, &SearchOptions::new(pattern), replacement).unwrap()
+    }
+
+    #[test]
+    fn apply_preserves_encoding_and_rolls_back() {
+        let dir = tempdir().unwrap();
+        let crlf = dir.path().join("crlf.txt");
+        let utf16 = dir.path().join("utf16.txt");
+        fs::write(&crlf, "alpha beta\r\nbeta\r\n").unwrap();
+        let mut wide = vec![0xFF, 0xFE];
+        wide.extend("beta\n".encode_utf16().flat_map(u16::to_le_bytes));
+        fs::write(&utf16, &wide).unwrap();
+
+        let mut plan = plan_for(&[&crlf, &utf16], "beta", "gamma");
+        plan.unmark_where(|hit| hit.line == 2);
+        let journal = ReplaceJournal::new(dir.path().join("journal"));
+        let entry = journal.apply(&plan).unwrap().entry.unwrap();
+        assert_eq!(entry.replacements, 2);
+        assert_eq!(
+            fs::read_to_string(&crlf).unwrap(),
+            "alpha gamma\r\nbeta\r\n"
+        );
+        let reopened = Document::open(&utf16).unwrap();
+        assert_eq!(reopened.contents(), "gamma\n");
+        assert!(reopened.has_bom());
+
+        let listed = journal.list().unwrap();
+        assert_eq!(listed, vec![entry.clone()]);
+        assert_eq!(journal.rollback(&entry, false).unwrap(), 2);
+        assert_eq!(fs::read_to_string(&crlf).unwrap(), "alpha beta\r\nbeta\r\n");
+        assert_eq!(fs::read(&utf16).unwrap(), wide);
+        assert!(journal.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stale_files_abort_before_writing_and_edits_block_rollback() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("first.txt");
+        let second = dir.path().join("second.txt");
+        fs::write(&first, "one\n").unwrap();
+        fs::write(&second, "one two\n").unwrap();
+        let journal = ReplaceJournal::new(dir.path().join("journal"));
+
+        let plan = plan_for(&[&first, &second], "one", "1");
+        fs::write(&second, "one three\n").unwrap();
+        assert!(matches!(
+            journal.apply(&plan),
+            Err(ReplaceJournalError::Stale(path)) if path == second
+        ));
+        assert_eq!(fs::read_to_string(&first).unwrap(), "one\n");
+        assert!(journal.list().unwrap().is_empty());
+
+        let plan = plan_for(&[&first, &second], "one", "1");
+        let entry = journal.apply(&plan).unwrap().entry.unwrap();
+        fs::write(&first, "edited\n").unwrap();
+        assert!(matches!(
+            journal.rollback(&entry, false),
+            Err(ReplaceJournalError::ModifiedSince(path)) if path == first
+        ));
+        assert_eq!(fs::read_to_string(&second).unwrap(), "1 three\n");
+        assert_eq!(journal.rollback(&entry, true).unwrap(), 2);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "one three\n");
+    }
+
+    #[test]
+    fn unrepresentable_replacements_skip_only_that_file() {
+        let dir = tempdir().unwrap();
+        let sjis = dir.path().join("legacy.txt");
+        let utf8 = dir.path().join("modern.txt");
+        let (encoded, _, _) = encoding_rs::SHIFT_JIS.encode("テスト\r\nテスト\r\n");
+        fs::write(&sjis, &encoded).unwrap();
+        fs::write(&utf8, "テスト\n").unwrap();
+        let journal = ReplaceJournal::new(dir.path().join("journal"));
+
+        let plan = plan_for(&[&sjis, &utf8], "テスト", "🦀");
+        let outcome = journal.apply(&plan).unwrap();
+        assert_eq!(
+            outcome.skipped,
+            vec![SkippedRewrite {
+                path: sjis.clone(),
+                encoding: "shift-jis",
+                replacements: 2,
+            }]
+        );
+        assert_eq!(fs::read(&sjis).unwrap(), encoded.to_vec());
+        assert_eq!(fs::read_to_string(&utf8).unwrap(), "🦀\n");
+        let entry = outcome.entry.unwrap();
+        assert_eq!(entry.files, vec![utf8.clone()]);
+
+        let plan = plan_for(&[&sjis], "テスト", "日本");
+        let outcome = journal.apply(&plan).unwrap();
+        assert!(outcome.skipped.is_empty());
+        let (expected, _, _) = encoding_rs::SHIFT_JIS.encode("日本\r\n日本\r\n");
+        assert_eq!(fs::read(&sjis).unwrap(), expected.to_vec());
+        journal.rollback(&outcome.entry.unwrap(), false).unwrap();
+        assert_eq!(fs::read(&sjis).unwrap(), encoded.to_vec());
+    }
+}
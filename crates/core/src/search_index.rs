@@ -0,0 +1,516 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, UNIX_EPOCH};
+
+use rustnotepad_search::{SearchOptions, TrigramIndex};
+use thiserror::Error;
+
+use crate::document::write_atomically;
+use crate::file_monitor::{FileEvent, FileMonitor, FileMonitorError, FileMonitorEventKind};
+use crate::find_in_files::{
+    load_text, FileFilter, FindEvent, FindInFiles, FindInFilesError, FindStats,
+};
+
+/// 背景監看執行緒等待事件的間隔；閒置一個間隔後才發布並保存索引。 / How long the watch thread waits for events; the index is published and saved after one idle interval.
+const WATCH_POLL: Duration = Duration::from_millis(250);
+
+/// 工作區搜尋索引的錯誤型別。 / Error type for the persistent workspace search index.
+#[derive(Debug, Error)]
+pub enum SearchIndexError {
+    #[error("search index I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to walk the workspace: {0}")]
+    Walk(#[from] FindInFilesError),
+    #[error("failed to watch the workspace: {0}")]
+    Monitor(#[from] FileMonitorError),
+}
+
+/// 一次索引更新的統計。 / Totals from one index update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexUpdate {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// 以三字元組索引工作區檔案並保存至磁碟，供「在檔案中尋找」略過不可能符合的檔案。 / Persistent trigram index over a workspace, consulted by Find in Files to skip files that cannot match.
+///
+/// 長時間執行的程式以 [`watch`](Self::watch) 由 `FileMonitor` 事件維持最新；單次執行的程式以 [`search`](Self::search) 順便補上過期的檔案。 / Long-running callers keep it current from `FileMonitor` events with [`watch`](Self::watch); one-shot callers catch up stale files as part of [`search`](Self::search).
+#[derive(Debug)]
+pub struct WorkspaceIndex {
+    storage: PathBuf,
+    roots: Vec<PathBuf>,
+    filter: FileFilter,
+    index: Arc<TrigramIndex>,
+    dirty: bool,
+}
+
+impl WorkspaceIndex {
+    /// 載入既有索引；檔案不存在或格式錯誤時從空索引開始。 / Loads the stored index, starting empty when it is missing or unreadable.
+    pub fn open(
+        storage: impl Into<PathBuf>,
+        roots: Vec<PathBuf>,
+        filter: FileFilter,
+    ) -> Result<Self, SearchIndexError> {
+        let storage = storage.into();
+        let (index, dirty) = match File::open(&storage) {
+            Ok(file) => match TrigramIndex::read_from(BufReader::new(file)) {
+                Ok(index) => (index, false),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+                    ) =>
+                {
+                    (TrigramIndex::new(), true)
+                }
+                Err(err) => return Err(err.into()),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (TrigramIndex::new(), false),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            storage,
+            roots,
+            filter,
+            index: Arc::new(index),
+            dirty,
+        })
+    }
+
+    pub fn storage_path(&self) -> &Path {
+        &self.storage
+    }
+
+    /// 取得索引快照，可交給 [`FindInFiles::with_index`]。 / Returns a snapshot of the index for [`FindInFiles::with_index`].
+    pub fn index(&self) -> Arc<TrigramIndex> {
+        Arc::clone(&self.index)
+    }
+
+    /// 走訪工作區，只重新索引大小或修改時間改變的檔案並移除已消失的檔案。 / Walks the workspace, re-indexing files whose size or mtime changed and dropping files that disappeared.
+    pub fn refresh(&mut self) -> Result<IndexUpdate, SearchIndexError> {
+        let files = FindInFiles::new(self.roots.clone())
+            .with_filter(self.filter.clone())
+            .collect_files(|_| {})?;
+        let mut update = IndexUpdate::default();
+        for path in &files {
+            match fs::metadata(path) {
+                Ok(metadata) if self.index.stamp(path) == Some(file_stamp(&metadata)) => {
+                    update.unchanged += 1;
+                }
+                _ => {
+                    if self.reindex(path) {
+                        update.indexed += 1;
+                    }
+                }
+            }
+        }
+        let mut listed: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+        listed.sort();
+        let stale: Vec<PathBuf> = self
+            .index
+            .paths()
+            .filter(|path| listed.binary_search(path).is_err())
+            .map(Path::to_path_buf)
+            .collect();
+        if !stale.is_empty() {
+            let index = Arc::make_mut(&mut self.index);
+            for path in &stale {
+                index.remove(path);
+            }
+            update.removed = stale.len();
+            self.dirty = true;
+        }
+        Ok(update)
+    }
+
+    /// 以目前的索引搜尋，之後重新索引此次因項目缺少或過期而必須讀取的檔案。 / Searches with the current index, then re-indexes the files the run had to read because their entry was missing or out of date.
+    ///
+    /// 已刪除檔案的項目留待 [`refresh`](Self::refresh) 或監看事件移除。 / Entries of deleted files are left for [`refresh`](Self::refresh) or watch events to drop.
+    pub fn search(
+        &mut self,
+        finder: FindInFiles,
+        options: &SearchOptions,
+        on_event: impl FnMut(FindEvent),
+    ) -> Result<FindStats, SearchIndexError> {
+        let stale = Mutex::new(Vec::new());
+        let stats = finder.with_index(self.index()).run_until(
+            options,
+            &AtomicBool::new(false),
+            Some(&stale),
+            on_event,
+        )?;
+        for path in stale.into_inner().expect("stale list poisoned") {
+            self.reindex(&path);
+        }
+        Ok(stats)
+    }
+
+    /// 在背景執行緒補齊索引後持續套用工作區的檔案事件。 / Catches the index up on a background thread, then keeps applying workspace file events to it.
+    pub fn watch(mut self) -> Result<WatchedIndex, SearchIndexError> {
+        let mut monitor = FileMonitor::new()?;
+        for root in &self.roots {
+            monitor.watch_recursive(root)?;
+        }
+        let roots = self.roots.clone();
+        let state = Arc::new(Mutex::new(WatchState::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let (state, stop) = (Arc::clone(&state), Arc::clone(&stop));
+            thread::spawn(move || self.keep_current(&monitor, &state, &stop))
+        };
+        Ok(WatchedIndex {
+            roots,
+            state,
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    fn keep_current(
+        &mut self,
+        monitor: &FileMonitor,
+        state: &Mutex<WatchState>,
+        stop: &AtomicBool,
+    ) {
+        let caught_up = self.refresh().and_then(|_| self.save());
+        publish(state, self.index(), caught_up.err());
+        let mut changed = false;
+        while !stop.load(Ordering::Relaxed) {
+            match monitor.recv_timeout(WATCH_POLL) {
+                Ok(Some(event)) => {
+                    changed |= self.apply_event(&event);
+                    changed |= self.apply_events(monitor) > 0;
+                }
+                Ok(None) if changed => {
+                    changed = false;
+                    publish(state, self.index(), self.save().err());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    publish(state, self.index(), Some(err.into()));
+                    break;
+                }
+            }
+        }
+        if let Err(err) = self.save() {
+            publish(state, self.index(), Some(err));
+        }
+    }
+
+    /// 依檔案事件更新索引；回傳索引是否改變。 / Updates the index from a file event; returns whether anything changed.
+    pub fn apply_event(&mut self, event: &FileEvent) -> bool {
+        match &event.kind {
+            FileMonitorEventKind::Removed => self.forget(&event.path),
+            FileMonitorEventKind::Renamed { from, to } => {
+                let moved = self.forget(from);
+                self.reindex(to) || moved
+            }
+            FileMonitorEventKind::Modified
+            | FileMonitorEventKind::Created
+            | FileMonitorEventKind::Other => {
+                if event.path.is_file() {
+                    self.reindex(&event.path)
+                } else {
+                    self.forget(&event.path)
+                }
+            }
+        }
+    }
+
+    /// 套用監視器目前累積的所有事件，回傳改變索引的事件數。 / Applies every pending monitor event, returning how many changed the index.
+    pub fn apply_events(&mut self, monitor: &FileMonitor) -> usize {
+        let mut changed = 0;
+        while let Some(event) = monitor.try_next() {
+            if self.apply_event(&event) {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// 有變更時寫回磁碟。 / Writes the index back to disk if it changed.
+    pub fn save(&mut self) -> Result<(), SearchIndexError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = self.storage.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut bytes = Vec::new();
+        self.index.write_to(&mut bytes)?;
+        write_atomically(&self.storage, &bytes)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// 索引檔本身與（未包含隱藏檔時）隱藏路徑不列入索引。 / The index file itself and, unless hidden files are included, hidden paths stay out of the index.
+    fn is_tracked(&self, path: &Path) -> bool {
+        if path == self.storage {
+            return false;
+        }
+        self.roots.iter().any(|root| {
+            path.strip_prefix(root).is_ok_and(|relative| {
+                self.filter.include_hidden
+                    || !relative.components().any(|component| {
+                        matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+                    })
+            })
+        })
+    }
+
+    fn reindex(&mut self, path: &Path) -> bool {
+        if !self.is_tracked(path) {
+            return self.forget(path);
+        }
+        let loaded = fs::metadata(path).ok().and_then(|metadata| {
+            Some((file_stamp(&metadata), load_text(path, &self.filter).ok()?))
+        });
+        match loaded {
+            Some((stamp, text)) => {
+                Arc::make_mut(&mut self.index).insert(path, stamp, &text);
+                self.dirty = true;
+                true
+            }
+            // 二進位、過大或無法解碼的檔案不留在索引中。 / Binary, oversized or undecodable files stay out of the index.
+            None => self.forget(path),
+        }
+    }
+
+    fn forget(&mut self, path: &Path) -> bool {
+        if !self.index.contains(path) {
+            return false;
+        }
+        Arc::make_mut(&mut self.index).remove(path);
+        self.dirty = true;
+        true
+    }
+}
+
+/// 背景監看執行緒發布的狀態。 / State published by the watch thread.
+#[derive(Debug, Default)]
+struct WatchState {
+    index: Option<Arc<TrigramIndex>>,
+    error: Option<SearchIndexError>,
+}
+
+fn publish(state: &Mutex<WatchState>, index: Arc<TrigramIndex>, error: Option<SearchIndexError>) {
+    let mut state = state.lock().expect("watch state poisoned");
+    state.index = Some(index);
+    if error.is_some() {
+        state.error = error;
+    }
+}
+
+/// 由 [`WorkspaceIndex::watch`] 在背景維持的索引；釋放時停止監看，背景執行緒保存後結束。 / Index kept current in the background by [`WorkspaceIndex::watch`]; dropping it stops watching and lets the thread save and exit.
+#[derive(Debug)]
+pub struct WatchedIndex {
+    roots: Vec<PathBuf>,
+    state: Arc<Mutex<WatchState>>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl WatchedIndex {
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// 最近發布的索引快照；初次補齊完成前為 `None`。 / The latest published snapshot, or `None` until the initial catch-up finishes.
+    pub fn index(&self) -> Option<Arc<TrigramIndex>> {
+        self.state
+            .lock()
+            .expect("watch state poisoned")
+            .index
+            .clone()
+    }
+
+    /// 取出背景執行緒最近一次的錯誤。 / Takes the last error hit by the watch thread.
+    pub fn take_error(&self) -> Option<SearchIndexError> {
+        self.state
+            .lock()
+            .expect("watch state poisoned")
+            .error
+            .take()
+    }
+
+    /// 停止監看並等待最後一次保存完成。 / Stops watching and waits for the final save.
+    pub fn stop(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for WatchedIndex {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 由檔案大小與修改時間組成的版本戳記。 / Version stamp built from a file's size and modification time.
+pub(crate) fn file_stamp(metadata: &Metadata) -> u64 {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default();
+    modified.rotate_left(17) ^ metadata.len().wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FindEvent;
+    use rustnotepad_search::SearchOptions;
+    use tempfile::tempdir;
+
+    fn matched(driver: &FindInFiles, pattern: &str) -> (usize, usize) {
+        let mut matched = 0;
+        let stats = driver
+            .run(&SearchOptions::new(pattern), |event| {
+                if let FindEvent::Matched(_) = event {
+                    matched += 1;
+                }
+            })
+            .unwrap();
+        (matched, stats.files_pruned)
+    }
+
+    #[test]
+    fn index_prunes_find_in_files_and_tracks_changes() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("ws");
+        fs::create_dir_all(&root).unwrap();
+        for idx in 0..20 {
+            let contents = if idx == 7 { "the needle\n" } else { "hay\n" };
+            fs::write(root.join(format!("file_{idx:02}.txt")), contents).unwrap();
+        }
+        let storage = dir.path().join("index.bin");
+
+        let mut workspace =
+            WorkspaceIndex::open(&storage, vec![root.clone()], FileFilter::default()).unwrap();
+        let update = workspace.refresh().unwrap();
+        assert_eq!(update.indexed, 20);
+        workspace.save().unwrap();
+
+        let driver = FindInFiles::new([&root]).with_index(workspace.index());
+        assert_eq!(matched(&driver, "needle"), (1, 19));
+
+        let moved = root.join("moved.txt");
+        fs::rename(root.join("file_07.txt"), &moved).unwrap();
+        fs::write(root.join("file_03.txt"), "another needle\n").unwrap();
+        workspace.apply_event(&FileEvent {
+            path: moved.clone(),
+            kind: FileMonitorEventKind::Renamed {
+                from: root.join("file_07.txt"),
+                to: moved.clone(),
+            },
+        });
+        workspace.save().unwrap();
+
+        // 未套用的修改不會被錯誤地略過。 / A change the index has not seen yet is still searched.
+        let mut reopened =
+            WorkspaceIndex::open(&storage, vec![root.clone()], FileFilter::default()).unwrap();
+        let driver = FindInFiles::new([&root]).with_index(reopened.index());
+        assert_eq!(matched(&driver, "needle"), (2, 18));
+
+        let update = reopened.refresh().unwrap();
+        assert_eq!((update.indexed, update.removed), (1, 0));
+        assert_eq!(update.unchanged, 19);
+        fs::remove_file(&moved).unwrap();
+        assert_eq!(reopened.refresh().unwrap().removed, 1);
+    }
+
+    #[test]
+    fn search_reindexes_only_the_files_it_had_to_read() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("ws");
+        fs::create_dir_all(&root).unwrap();
+        for idx in 0..10 {
+            let contents = if idx == 4 { "needle\n" } else { "hay\n" };
+            fs::write(root.join(format!("file_{idx}.txt")), contents).unwrap();
+        }
+        let mut workspace = WorkspaceIndex::open(
+            dir.path().join("index.bin"),
+            vec![root.clone()],
+            FileFilter::default(),
+        )
+        .unwrap();
+
+        let stats = workspace
+            .search(
+                FindInFiles::new([&root]),
+                &SearchOptions::new("needle"),
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!((stats.files_matched, stats.files_pruned), (1, 0));
+        assert_eq!(workspace.index().len(), 10);
+
+        fs::write(root.join("file_7.txt"), "needle again\n").unwrap();
+        let stats = workspace
+            .search(
+                FindInFiles::new([&root]),
+                &SearchOptions::new("needle"),
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!((stats.files_matched, stats.files_pruned), (2, 8));
+
+        let stats = workspace
+            .search(
+                FindInFiles::new([&root]),
+                &SearchOptions::new("needle"),
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!((stats.files_searched, stats.files_pruned), (2, 8));
+    }
+
+    #[test]
+    fn watched_index_follows_file_events() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("ws");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.txt"), "hay\n").unwrap();
+        let storage = root.join(".rustnotepad").join("search.index");
+        let watched = WorkspaceIndex::open(&storage, vec![root.clone()], FileFilter::default())
+            .unwrap()
+            .watch()
+            .unwrap();
+        let query = rustnotepad_search::IndexQuery::new(&SearchOptions::new("needle")).unwrap();
+        let wait_for = |check: &dyn Fn(&TrigramIndex) -> bool| {
+            let deadline = std::time::Instant::now() + Duration::from_secs(10);
+            while std::time::Instant::now() < deadline {
+                if watched.index().is_some_and(|index| check(&index)) {
+                    return true;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            false
+        };
+        assert!(wait_for(&|index| index.contains(&root.join("a.txt"))));
+
+        let added = root.join("b.txt");
+        fs::write(&added, "needle\n").unwrap();
+        assert!(wait_for(
+            &|index| index.may_match(&added, &query) == Some(true)
+        ));
+        fs::remove_file(root.join("a.txt")).unwrap();
+        assert!(wait_for(&|index| !index.contains(&root.join("a.txt"))));
+        assert!(watched.take_error().is_none());
+        watched.stop();
+
+        let reopened =
+            WorkspaceIndex::open(&storage, vec![root.clone()], FileFilter::default()).unwrap();
+        assert!(reopened.index().contains(&added));
+        assert!(!reopened.index().contains(&storage));
+    }
+}
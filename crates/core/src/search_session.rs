@@ -2,11 +2,12 @@ use std::collections::BTreeSet;
 use std::path::PathBuf;
 
 use rustnotepad_search::{
-    FileSearchResult, ReplaceAllOutcome, SearchDirection, SearchEngine, SearchError, SearchMatch,
-    SearchOptions, SearchReport, SearchScope,
+    rank_matches, FileSearchResult, FuzzyScore, IncrementalSearch, ReplaceAllOutcome,
+    SearchDirection, SearchEngine, SearchError, SearchMatch, SearchOptions, SearchReport,
+    SearchScope,
 };
 
-use crate::{BookmarkManager, Document};
+use crate::{BookmarkManager, Document, MarkStyle, MarkStyles};
 
 /// Tracks the active search context for a document, including cached matches and bookmark marks.
 /// （追蹤文件目前的搜尋狀態，包含快取結果與書籤標記。）
@@ -16,6 +17,7 @@ pub struct SearchSession {
     matches: Vec<SearchMatch>,
     current: Option<usize>,
     marked_lines: BTreeSet<usize>,
+    incremental: IncrementalSearch,
 }
 
 impl SearchSession {
@@ -28,6 +30,7 @@ impl SearchSession {
             matches: Vec::new(),
             current: None,
             marked_lines: BTreeSet::new(),
+            incremental: IncrementalSearch::new(),
         })
     }
 
@@ -43,19 +46,66 @@ impl SearchSession {
         self.matches.clear();
         self.current = None;
         self.marked_lines.clear();
+        self.incremental.reset();
         &mut self.options
     }
 
     /// Recomputes matches against the given document contents using the current options.
     /// （依目前選項重新計算文件內的符合結果。）
     pub fn refresh(&mut self, document: &Document) -> Result<(), SearchError> {
-        let engine = SearchEngine::new(document.contents());
-        self.matches = engine.find_all(&self.options)?;
+        self.incremental.reset();
+        self.matches = document.text().find_all(&self.options)?;
         self.current = None;
         self.marked_lines.clear();
         Ok(())
     }
 
+    /// Find-as-you-type: updates the matches for the pattern typed so far without rescanning the whole document.
+    /// （輸入即搜尋：依目前輸入的樣式更新結果，不必重新掃描整份文件。）
+    ///
+    /// Extending the pattern only rescans lines that matched before and backspacing reuses earlier results.
+    /// The current match becomes the first one at or after the previous current match, wrapping to the first.
+    /// An empty pattern clears the matches. Results are cached per [`Document::revision`], so edits start over.
+    /// （延長樣式時只重新掃描先前有結果的行，退格時重用先前結果。目前結果會移到原本目前結果之後的第一筆，
+    /// 必要時回到第一筆；空白樣式會清空結果。結果依 [`Document::revision`] 快取，編輯後會重新搜尋。）
+    pub fn search_as_you_type(
+        &mut self,
+        document: &Document,
+        pattern: &str,
+    ) -> Result<&[SearchMatch], SearchError> {
+        let anchor = self.current().map_or(0, |hit| hit.start);
+        self.options.pattern = pattern.to_string();
+        self.matches = self
+            .incremental
+            .update(document.contents(), document.revision(), &self.options)?
+            .to_vec();
+        self.marked_lines.clear();
+        self.current = if self.matches.is_empty() {
+            None
+        } else {
+            Some(
+                self.matches
+                    .iter()
+                    .position(|hit| hit.start >= anchor)
+                    .unwrap_or(0),
+            )
+        };
+        Ok(&self.matches)
+    }
+
+    /// Cached matches ordered by fuzzy score, best first, for quick-jump lists.
+    /// （依模糊分數由高到低排列的快取結果，供快速跳轉清單使用。）
+    pub fn ranked_matches(&self) -> Vec<(&SearchMatch, FuzzyScore)> {
+        rank_matches(
+            &self.options.pattern,
+            self.options.case_sensitive,
+            &self.matches,
+        )
+        .into_iter()
+        .map(|(idx, score)| (&self.matches[idx], score))
+        .collect()
+    }
+
     /// Returns all cached matches (refresh must be called beforehand).
     /// （回傳快取的所有符合結果；需先呼叫 `refresh`。）
     pub fn matches(&self) -> &[SearchMatch] {
@@ -146,9 +196,7 @@ impl SearchSession {
             None => return Ok(None),
         };
 
-        let mut updated = document.contents().to_string();
-        updated.replace_range(target.start..target.end, replacement);
-        document.set_contents(updated);
+        document.replace_range(target.start..target.end, replacement);
 
         self.refresh(document)?;
         self.current = self
@@ -206,6 +254,12 @@ impl SearchSession {
         count
     }
 
+    /// Marks every cached match with one of the highlight styles, returning how many new marks were added.
+    /// （以指定的標記樣式標示所有快取結果，回傳新增的標記數。）
+    pub fn mark_style(&self, style: MarkStyle, marks: &mut MarkStyles) -> usize {
+        marks.mark_matches(style, &self.matches)
+    }
+
     /// Clears all marks previously applied via this session, restoring the bookmark manager.
     /// （清除此會話加上的所有標記，還原書籤管理器。）
     pub fn clear_marks(&mut self, bookmarks: &mut BookmarkManager) {
@@ -247,6 +301,7 @@ impl SearchSession {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rustnotepad_search::SearchMode;
 
     fn new_document(contents: &str) -> Document {
         let mut doc = Document::new();
@@ -327,6 +382,41 @@ mod tests {
         session.clear_marks(&mut bookmarks);
         assert!(!bookmarks.is_bookmarked(1));
         assert!(session.matches().iter().all(|m| !m.is_marked));
+
+        let mut styles = MarkStyles::default();
+        assert_eq!(session.mark_style(MarkStyle::Second, &mut styles), 3);
+        assert_eq!(styles.ranges(MarkStyle::Second), [0..4, 5..9, 10..14]);
+        assert!(styles.ranges(MarkStyle::First).is_empty());
+    }
+
+    #[test]
+    fn search_as_you_type_follows_the_current_match() {
+        let mut doc = new_document("fn main() {}\nfn parse_main() {}\nlet manifest = 1;");
+        let mut options = SearchOptions::new("m");
+        options.mode = SearchMode::Fuzzy;
+        let mut session = SearchSession::new(options).unwrap();
+
+        assert_eq!(session.search_as_you_type(&doc, "m").unwrap().len(), 3);
+        assert_eq!(session.current().unwrap().line, 1);
+        session.find_next();
+        assert_eq!(session.current().unwrap().line, 2);
+
+        let hits = session.search_as_you_type(&doc, "mn").unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(session.current().unwrap().matched, "main");
+        assert_eq!(session.current().unwrap().line, 2);
+
+        let ranked = session.ranked_matches();
+        assert_eq!((ranked[0].0.line, ranked[0].0.matched.as_str()), (3, "man"));
+        assert_eq!(ranked[1].0.line, 1);
+
+        assert!(session.search_as_you_type(&doc, "").unwrap().is_empty());
+        assert!(session.current().is_none());
+        assert_eq!(session.search_as_you_type(&doc, "let").unwrap().len(), 1);
+        assert_eq!(session.current().unwrap().line, 3);
+
+        doc.insert(0, "let x = 0;\n");
+        assert_eq!(session.search_as_you_type(&doc, "let").unwrap().len(), 2);
     }
 
     #[test]
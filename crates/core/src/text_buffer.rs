@@ -0,0 +1,370 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use ropey::Rope;
+use rustnotepad_search::{
+    BlockSearch, SearchEngine, SearchError, SearchMatch, SearchOptions, SearchScope,
+};
+
+/// 逐塊搜尋時每個區塊的目標大小（位元組，實際會延伸到行尾）。 / Target block size for block-wise search, extended to the end of a line.
+const SEARCH_BLOCK_BYTES: usize = 64 * 1024;
+
+/// 下一個編輯版本號；全域遞增，因此不同緩衝的版本號也不會重複。 / Next edit revision; global, so revisions never repeat across buffers either.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 以 rope 為後盾、具行索引的文字儲存。 / Rope-backed text storage with a line index.
+///
+/// 位置一律以 UTF-8 位元組計算，與 `&str` 切片一致；插入、刪除與行查詢皆為對數時間。
+/// 編輯路徑應透過行查詢、[`span`](Self::span)、[`chars_after`](Self::chars_after) 與
+/// [`find_all`](Self::find_all) 讀取文字；[`as_str`](Self::as_str) 會展開整份文字，只供一次性匯出。 /
+/// Offsets are UTF-8 byte indices, matching `&str` slicing; inserts, removals and line
+/// lookups are logarithmic. Editing paths read through the line lookups, [`span`](Self::span),
+/// [`chars_after`](Self::chars_after) and [`find_all`](Self::find_all); [`as_str`](Self::as_str)
+/// materialises the whole text and is meant for one-off export only.
+#[derive(Clone, Default)]
+pub struct TextBuffer {
+    rope: Rope,
+    flat: OnceLock<String>,
+    revision: u64,
+}
+
+impl TextBuffer {
+    /// 建立空白緩衝。 / Creates an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 文字長度（位元組）。 / Length in bytes.
+    pub fn len(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// 是否為空。 / Indicates whether the buffer holds no text.
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_bytes() == 0
+    }
+
+    /// 字元數量。 / Number of Unicode scalar values.
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    /// 行數；結尾換行後的空行也計入。 / Number of lines, counting the empty line after a trailing `\n`.
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// 是否以換行結尾。 / Indicates whether the text ends with `\n`.
+    pub fn ends_with_newline(&self) -> bool {
+        let len = self.len();
+        len > 0 && self.rope.byte(len - 1) == b'\n'
+    }
+
+    /// 取得位元組位置所在的行（0 起算）。 / Returns the zero-based line containing the byte offset.
+    pub fn line_of_offset(&self, offset: usize) -> usize {
+        self.rope.byte_to_line(offset.min(self.len()))
+    }
+
+    /// 取得行首的位元組位置。 / Returns the byte offset where the line starts.
+    pub fn line_start(&self, line: usize) -> usize {
+        let line = line.min(self.line_count().saturating_sub(1));
+        self.rope.line_to_byte(line)
+    }
+
+    /// 取得行尾（不含換行）的位元組位置。 / Returns the byte offset where the line content ends, excluding `\n`.
+    pub fn line_end(&self, line: usize) -> usize {
+        let line = line.min(self.line_count().saturating_sub(1));
+        let next = if line + 1 < self.line_count() {
+            self.rope.line_to_byte(line + 1)
+        } else {
+            self.len()
+        };
+        if next > 0 && self.rope.byte(next - 1) == b'\n' {
+            next - 1
+        } else {
+            next
+        }
+    }
+
+    /// 取得行內容（不含換行）。 / Returns the line contents without the trailing newline.
+    pub fn line_text(&self, line: usize) -> String {
+        self.slice(self.line_start(line)..self.line_end(line))
+    }
+
+    /// 將位元組位置轉為（行, 欄）；欄以字元計算，皆為 0 起算。 / Converts a byte offset into zero-based (line, character column).
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len());
+        let line = self.rope.byte_to_line(offset);
+        let line_char = self.rope.line_to_char(line);
+        (line, self.rope.byte_to_char(offset) - line_char)
+    }
+
+    /// 將（行, 欄）轉回位元組位置，欄超出行尾時停在行尾。 / Converts zero-based (line, column) back to a byte offset, clamping to the line end.
+    pub fn offset_of(&self, line: usize, column: usize) -> usize {
+        let start = self.line_start(line);
+        let end = self.line_end(line);
+        let start_char = self.rope.byte_to_char(start);
+        let end_char = self.rope.byte_to_char(end);
+        self.rope.char_to_byte((start_char + column).min(end_char))
+    }
+
+    /// 將字元索引轉為位元組位置。 / Converts a character index into a byte offset.
+    pub fn offset_of_char(&self, char_index: usize) -> usize {
+        self.rope.char_to_byte(char_index.min(self.len_chars()))
+    }
+
+    /// 將位元組位置轉為字元索引。 / Converts a byte offset into a character index.
+    pub fn char_of_offset(&self, offset: usize) -> usize {
+        self.rope.byte_to_char(offset.min(self.len()))
+    }
+
+    /// 判斷位置是否落在字元邊界。 / Checks whether the byte offset lies on a character boundary.
+    pub fn is_char_boundary(&self, offset: usize) -> bool {
+        if offset > self.len() {
+            return false;
+        }
+        self.rope.char_to_byte(self.rope.byte_to_char(offset)) == offset
+    }
+
+    /// 前一個字元的起點。 / Start of the character preceding the offset.
+    pub fn prev_char_boundary(&self, offset: usize) -> Option<usize> {
+        if offset == 0 || offset > self.len() {
+            return None;
+        }
+        let char_idx = self.rope.byte_to_char(offset);
+        let char_idx = if self.rope.char_to_byte(char_idx) == offset {
+            char_idx - 1
+        } else {
+            char_idx
+        };
+        Some(self.rope.char_to_byte(char_idx))
+    }
+
+    /// 下一個字元的起點。 / Start of the character following the offset.
+    pub fn next_char_boundary(&self, offset: usize) -> Option<usize> {
+        if offset >= self.len() {
+            return None;
+        }
+        let char_idx = self.rope.byte_to_char(offset);
+        Some(self.rope.char_to_byte(char_idx + 1))
+    }
+
+    /// 複製指定範圍的文字。 / Copies the text within the byte range.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let start = self.rope.byte_to_char(range.start);
+        let end = self.rope.byte_to_char(range.end);
+        self.rope.slice(start..end).to_string()
+    }
+
+    /// 借用指定範圍的文字；跨越內部區塊時才複製。 / Borrows the text within the byte range, copying only when it spans chunks.
+    pub fn span(&self, range: Range<usize>) -> Cow<'_, str> {
+        self.rope.byte_slice(range).into()
+    }
+
+    /// 自位元組位置往後走訪字元。 / Iterates the characters from the byte offset forwards.
+    pub fn chars_after(&self, offset: usize) -> impl Iterator<Item = char> + '_ {
+        self.rope
+            .chars_at(self.rope.byte_to_char(offset.min(self.len())))
+    }
+
+    /// 自位元組位置往前走訪字元（由近而遠）。 / Iterates the characters before the byte offset, nearest first.
+    pub fn chars_before(&self, offset: usize) -> impl Iterator<Item = char> + '_ {
+        self.rope
+            .chars_at(self.rope.byte_to_char(offset.min(self.len())))
+            .reversed()
+    }
+
+    /// 搜尋整份文字；不跨行的樣式逐一掃描行對齊區塊，不會展開整份文字。 /
+    /// Searches the text; patterns that cannot cross a line break scan line-aligned blocks without
+    /// materialising the whole text.
+    ///
+    /// 可能跨行、錨定全文或限定選取範圍的搜尋改用展開後的文字。 /
+    /// Searches that can cross lines, anchor to the whole text or are limited to a selection fall
+    /// back to the flattened text.
+    pub fn find_all(&self, options: &SearchOptions) -> Result<Vec<SearchMatch>, SearchError> {
+        let search = BlockSearch::new(options)?;
+        if search.needs_whole_text() || options.scope != SearchScope::EntireDocument {
+            return SearchEngine::new(self.as_str()).find_all(options);
+        }
+
+        let len = self.len();
+        let line_count = self.line_count();
+        let mut matches = Vec::new();
+        let mut line = 0;
+        while line < line_count {
+            let start = self.rope.line_to_byte(line);
+            let target = (start + SEARCH_BLOCK_BYTES).min(len);
+            let next_line = (self.rope.byte_to_line(target) + 1).min(line_count);
+            let end = if next_line < line_count {
+                self.rope.line_to_byte(next_line)
+            } else {
+                len
+            };
+            matches.extend(search.find_in(&self.span(start..end), start, line, end == len));
+            line = next_line;
+        }
+        Ok(matches)
+    }
+
+    /// 在位元組位置插入文字。 / Inserts text at the byte offset.
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let char_idx = self.rope.byte_to_char(offset);
+        self.rope.insert(char_idx, text);
+        self.flat = OnceLock::new();
+        self.revision = next_revision();
+    }
+
+    /// 移除位元組範圍內的文字。 / Removes the text within the byte range.
+    pub fn remove(&mut self, range: Range<usize>) {
+        if range.start >= range.end {
+            return;
+        }
+        let start = self.rope.byte_to_char(range.start);
+        let end = self.rope.byte_to_char(range.end);
+        self.rope.remove(start..end);
+        self.flat = OnceLock::new();
+        self.revision = next_revision();
+    }
+
+    /// 以新文字取代位元組範圍。 / Replaces the byte range with new text.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.remove(range);
+        self.insert(start, text);
+    }
+
+    /// 編輯版本號，每次修改都會換新；相同版本號代表內容相同，可作為快取鍵。 /
+    /// Edit revision, renewed by every change; equal revisions mean equal text, so it can key caches.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// 依序走訪內部區塊，不會展開整份文字。 / Iterates the internal chunks without materialising the whole text.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> + '_ {
+        self.rope.chunks()
+    }
+
+    /// 展開成連續字串，供存檔、複製等一次性匯出；結果快取至下一次修改。 /
+    /// Flattens the text for one-off export such as saving or copying; cached until the next edit.
+    pub fn as_str(&self) -> &str {
+        self.flat.get_or_init(|| self.rope.to_string())
+    }
+}
+
+impl From<&str> for TextBuffer {
+    fn from(text: &str) -> Self {
+        Self {
+            rope: Rope::from_str(text),
+            flat: OnceLock::new(),
+            revision: next_revision(),
+        }
+    }
+}
+
+impl From<String> for TextBuffer {
+    fn from(text: String) -> Self {
+        Self {
+            rope: Rope::from_str(&text),
+            flat: OnceLock::new(),
+            revision: next_revision(),
+        }
+    }
+}
+
+impl fmt::Debug for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextBuffer")
+            .field("len", &self.len())
+            .field("lines", &self.line_count())
+            .finish()
+    }
+}
+
+impl fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for TextBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.rope == other.rope
+    }
+}
+
+impl Eq for TextBuffer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_index_tracks_edits() {
+        let mut buffer = TextBuffer::from("alpha\nbeta\ngamma");
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.line_of_offset(7), 1);
+        assert_eq!(buffer.line_text(2), "gamma");
+
+        buffer.insert(0, "zero\n");
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!(buffer.line_start(2), "zero\nalpha\n".len());
+        buffer.remove(0.."zero\n".len());
+        assert_eq!(buffer.as_str(), "alpha\nbeta\ngamma");
+
+        let before = buffer.revision();
+        buffer.replace(6..10, "BETA");
+        assert_eq!(buffer.line_text(1), "BETA");
+        assert_eq!(buffer.to_string(), "alpha\nBETA\ngamma");
+        assert_ne!(buffer.revision(), before);
+        assert_eq!(buffer.clone().revision(), buffer.revision());
+        assert_ne!(TextBuffer::from("alpha").revision(), buffer.revision());
+    }
+
+    #[test]
+    fn line_column_counts_characters() {
+        let buffer = TextBuffer::from("你好\nab");
+        assert_eq!(buffer.line_column("你".len()), (0, 1));
+        assert_eq!(buffer.line_column("你好\na".len()), (1, 1));
+        assert_eq!(buffer.offset_of(0, 5), "你好".len());
+        assert_eq!(buffer.offset_of_char(4), "你好\na".len());
+        assert_eq!(buffer.char_of_offset("你好".len()), 2);
+        assert_eq!(buffer.line_end(0), "你好".len());
+        assert!(!buffer.is_char_boundary(1));
+        assert_eq!(buffer.prev_char_boundary("你好".len()), Some("你".len()));
+        assert_eq!(buffer.next_char_boundary(0), Some("你".len()));
+        assert_eq!(buffer.chars_after("你".len()).collect::<String>(), "好\nab");
+        assert_eq!(
+            buffer.chars_before("你好".len()).collect::<String>(),
+            "好你"
+        );
+    }
+
+    #[test]
+    fn find_all_matches_the_flat_search_across_blocks() {
+        let line = "alpha beta gamma delta\n".repeat(8);
+        let text = line.repeat(SEARCH_BLOCK_BYTES / line.len() + 2) + "alpha";
+        let buffer = TextBuffer::from(text.as_str());
+        for pattern in ["alpha", "^", "a\nb", "a$"] {
+            let mut options = SearchOptions::new(pattern);
+            options.mode = rustnotepad_search::SearchMode::Regex;
+            assert_eq!(
+                buffer.find_all(&options).unwrap(),
+                SearchEngine::new(&text).find_all(&options).unwrap(),
+                "{pattern}"
+            );
+        }
+    }
+}
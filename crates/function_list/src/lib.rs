@@ -131,6 +131,12 @@ impl ParserRegistry {
         self.parsers.insert(language_id.into(), parser);
     }
 
+    /// Removes and returns the parser for a language, e.g. to wrap it as a fallback.
+    /// （移除並回傳某語言的解析器，例如將其包裝為備援。）
+    pub fn remove_parser(&mut self, language_id: &str) -> Option<Box<dyn FunctionParser>> {
+        self.parsers.remove(language_id)
+    }
+
     pub fn parse(&self, language_id: &str, source: &str) -> Option<Vec<FunctionEntry>> {
         self.parsers
             .get(language_id)
@@ -0,0 +1,825 @@
+//! TextMate (`.tmLanguage`, `.tmLanguage.json`) and Sublime Text (`.sublime-syntax`) grammars.
+//! （TextMate 與 Sublime Text 語法檔。）
+//!
+//! Both formats are compiled into one model of named contexts holding ordered
+//! match rules that may push, set or pop contexts; a TextMate `begin`/`end`
+//! rule becomes a rule that pushes an anonymous context whose first rule pops
+//! on `end`. The line state is the stack of pushed contexts, so grammars plug
+//! into the incremental highlighter unchanged. Tokens carry the most specific
+//! scope name as [`HighlightKind::Custom`].
+//! 兩種格式都會編譯成同一個模型：具名 context 內含依序比對的規則，規則可推入、
+//! 取代或彈出 context；TextMate 的 `begin`/`end` 規則會轉成推入匿名 context 的
+//! 規則，該 context 的第一條規則在 `end` 時彈出。行狀態即為 context 堆疊，
+//! 因此語法可直接用於增量高亮。token 以最精確的 scope 名稱作為
+//! [`HighlightKind::Custom`]。
+//!
+//! Property lists are read with the `plist` crate and patterns run on
+//! `fancy-regex`, which covers the Oniguruma look-around, back-references and
+//! atomic groups grammars rely on. Patterns it still cannot compile (an `end`
+//! referring back to the `begin` captures, for one) are skipped and listed by
+//! [`Grammar::skipped_patterns`].
+//! 屬性列表以 `plist` crate 讀取，樣式以 `fancy-regex` 執行，涵蓋語法常用的
+//! Oniguruma 環視、反向參照與原子群組。仍無法編譯的樣式（例如參照 `begin`
+//! 擷取的 `end`）會被略過，並列於 [`Grammar::skipped_patterns`]。
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use fancy_regex::{Captures, Regex};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::language::{HighlightKind, HighlightToken};
+
+const MAIN_CONTEXT: &str = "main";
+const PROTOTYPE_CONTEXT: &str = "prototype";
+
+#[derive(Debug, Error)]
+pub enum GrammarError {
+    #[error("failed to read grammar: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid grammar JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid property list: {0}")]
+    Plist(#[from] plist::Error),
+    #[error("invalid YAML: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("unsupported grammar file '{0}'")]
+    UnsupportedFormat(String),
+    #[error("grammar is missing '{0}'")]
+    Missing(&'static str),
+}
+
+/// A compiled TextMate or Sublime Text grammar.
+/// （編譯完成的 TextMate 或 Sublime Text 語法。）
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    pub name: String,
+    pub scope_name: String,
+    pub file_extensions: Vec<String>,
+    contexts: Vec<Context>,
+    rules: Vec<Rule>,
+    main: usize,
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Context {
+    meta_scope: Option<String>,
+    meta_content_scope: Option<String>,
+    patterns: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    regex: Regex,
+    scope: Option<String>,
+    captures: Vec<(usize, String)>,
+    action: Action,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    None,
+    Push(Vec<usize>),
+    Set(Vec<usize>),
+    Pop(usize),
+}
+
+impl Grammar {
+    /// Loads a grammar file, picking the format from its name.
+    /// （讀取語法檔，依檔名判斷格式。）
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, GrammarError> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let contents = fs::read_to_string(path)?;
+        if file_name.ends_with(".sublime-syntax") {
+            Self::from_sublime_syntax(&contents)
+        } else if file_name.ends_with(".tmlanguage.json") {
+            Self::from_textmate_json(&contents)
+        } else if file_name.ends_with(".tmlanguage") || file_name.ends_with(".plist") {
+            Self::from_textmate_plist(&contents)
+        } else {
+            Err(GrammarError::UnsupportedFormat(file_name))
+        }
+    }
+
+    /// Parses a `.tmLanguage.json` grammar.
+    /// （解析 `.tmLanguage.json` 語法。）
+    pub fn from_textmate_json(input: &str) -> Result<Self, GrammarError> {
+        let value: Value = serde_json::from_str(input)?;
+        Self::from_textmate_value(&value)
+    }
+
+    /// Parses a property-list `.tmLanguage` grammar.
+    /// （解析屬性列表格式的 `.tmLanguage` 語法。）
+    pub fn from_textmate_plist(input: &str) -> Result<Self, GrammarError> {
+        let value = plist::Value::from_reader_xml(input.as_bytes())?;
+        Self::from_textmate_value(&plist_to_json(value))
+    }
+
+    /// Parses a `.sublime-syntax` grammar.
+    /// （解析 `.sublime-syntax` 語法。）
+    pub fn from_sublime_syntax(input: &str) -> Result<Self, GrammarError> {
+        crate::sublime_syntax::parse(input)
+    }
+
+    fn from_textmate_value(value: &Value) -> Result<Self, GrammarError> {
+        let scope_name = value
+            .get("scopeName")
+            .and_then(Value::as_str)
+            .ok_or(GrammarError::Missing("scopeName"))?;
+        let name = value
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or(scope_name);
+        let file_extensions = string_list(value.get("fileTypes"));
+
+        let mut builder = GrammarBuilder::default();
+        let items = builder.textmate_patterns(value.get("patterns"));
+        builder.add_context(MAIN_CONTEXT.to_string(), ContextDraft::new(items));
+        if let Some(repository) = value.get("repository").and_then(Value::as_object) {
+            for (entry, rule) in repository {
+                let items = builder.textmate_rule(rule);
+                builder.add_context(entry.clone(), ContextDraft::new(items));
+            }
+        }
+        builder.finish(name, scope_name, file_extensions)
+    }
+
+    /// Language identifier derived from the scope name (`source.python` → `python`).
+    /// （由 scope 名稱推導的語言識別子，例如 `source.python` → `python`。）
+    pub fn language_id(&self) -> String {
+        match self.scope_name.split_once('.') {
+            Some((_, rest)) if !rest.is_empty() => rest.to_string(),
+            _ => self.scope_name.clone(),
+        }
+    }
+
+    /// Patterns and includes that could not be compiled or resolved.
+    /// （無法編譯或解析的樣式與引用。）
+    pub fn skipped_patterns(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Lexes one line from the given context stack; returns line-relative tokens and the stack at its end.
+    /// （以指定的 context 堆疊掃描一行，回傳相對於行首的 token 與行尾的堆疊。）
+    pub(crate) fn highlight_line(
+        &self,
+        line: &str,
+        stack: &[usize],
+    ) -> (Vec<HighlightToken>, Vec<usize>) {
+        let mut stack = stack.to_vec();
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        let mut empty_at = None;
+        loop {
+            let context = &self.contexts[stack.last().copied().unwrap_or(self.main)];
+            let content_scope = self.content_scope(&stack);
+            let best = context
+                .patterns
+                .iter()
+                .filter_map(|&id| {
+                    let captures = self.rules[id].regex.captures_from_pos(line, pos);
+                    // 回溯超出上限視同不符合。 / Running out of backtracking counts as no match.
+                    Some((id, captures.ok()??))
+                })
+                .min_by_key(|(_, captures)| captures.get(0).map_or(usize::MAX, |hit| hit.start()));
+            let Some((id, captures)) = best else {
+                push_scoped(&mut tokens, pos..line.len(), content_scope);
+                break;
+            };
+            let Some(whole) = captures.get(0) else {
+                break;
+            };
+            push_scoped(&mut tokens, pos..whole.start(), content_scope);
+            let rule = &self.rules[id];
+
+            if whole.range().is_empty() {
+                let stuck = matches!(rule.action, Action::Pop(_)) && stack.is_empty();
+                if stuck || empty_at == Some(whole.start()) {
+                    // 空比對無法前進：跳過一個字元。 / An empty match cannot make progress: step over one character.
+                    let Some(ch) = line[whole.start()..].chars().next() else {
+                        break;
+                    };
+                    pos = whole.start() + ch.len_utf8();
+                    push_scoped(&mut tokens, whole.start()..pos, content_scope);
+                    empty_at = None;
+                    continue;
+                }
+                empty_at = Some(whole.start());
+            } else {
+                empty_at = None;
+            }
+
+            let scope = rule.scope.as_deref().or_else(|| match &rule.action {
+                Action::None => content_scope,
+                Action::Push(targets) | Action::Set(targets) => targets
+                    .last()
+                    .and_then(|target| self.contexts[*target].meta_scope.as_deref())
+                    .or(content_scope),
+                Action::Pop(count) => context
+                    .meta_scope
+                    .as_deref()
+                    .or_else(|| self.content_scope(&stack[..stack.len().saturating_sub(*count)])),
+            });
+            push_captures(&mut tokens, &captures, scope, &rule.captures);
+            match &rule.action {
+                Action::None => {}
+                Action::Push(targets) => stack.extend(targets),
+                Action::Set(targets) => {
+                    stack.pop();
+                    stack.extend(targets);
+                }
+                Action::Pop(count) => stack.truncate(stack.len().saturating_sub(*count)),
+            }
+            pos = whole.end();
+        }
+        (tokens, stack)
+    }
+
+    fn content_scope(&self, stack: &[usize]) -> Option<&str> {
+        stack.iter().rev().find_map(|id| {
+            let context = &self.contexts[*id];
+            context
+                .meta_content_scope
+                .as_deref()
+                .or(context.meta_scope.as_deref())
+        })
+    }
+}
+
+fn push_scoped(
+    tokens: &mut Vec<HighlightToken>,
+    range: std::ops::Range<usize>,
+    scope: Option<&str>,
+) {
+    let Some(scope) = scope else {
+        return;
+    };
+    if range.start >= range.end {
+        return;
+    }
+    // 多個 scope 以空白分隔時取最內層者。 / Space-separated scopes: keep the innermost one.
+    let scope = scope.rsplit(' ').next().unwrap_or(scope);
+    if let Some(last) = tokens.last_mut() {
+        if last.range.end == range.start
+            && matches!(&last.kind, HighlightKind::Custom(name) if name == scope)
+        {
+            last.range.end = range.end;
+            return;
+        }
+    }
+    tokens.push(HighlightToken {
+        range,
+        kind: HighlightKind::Custom(scope.to_string()),
+    });
+}
+
+fn push_captures(
+    tokens: &mut Vec<HighlightToken>,
+    captures: &Captures<'_>,
+    scope: Option<&str>,
+    scoped_groups: &[(usize, String)],
+) {
+    let Some(whole) = captures.get(0) else {
+        return;
+    };
+    let scope = scoped_groups
+        .iter()
+        .find(|(group, _)| *group == 0)
+        .map(|(_, name)| name.as_str())
+        .or(scope);
+    let mut groups: Vec<(std::ops::Range<usize>, &str)> = scoped_groups
+        .iter()
+        .filter(|(group, _)| *group > 0)
+        .filter_map(|(group, name)| Some((captures.get(*group)?.range(), name.as_str())))
+        .filter(|(range, _)| !range.is_empty())
+        .collect();
+    groups.sort_by_key(|(range, _)| range.start);
+    let mut cursor = whole.start();
+    for (range, name) in groups {
+        if range.start < cursor {
+            continue;
+        }
+        push_scoped(tokens, cursor..range.start, scope);
+        push_scoped(tokens, range.clone(), Some(name));
+        cursor = range.end;
+    }
+    push_scoped(tokens, cursor..whole.end(), scope);
+}
+
+/// Context being assembled; includes and push targets are resolved by name in [`GrammarBuilder::finish`].
+/// （組裝中的 context；引用與推入目標在 [`GrammarBuilder::finish`] 依名稱解析。）
+#[derive(Debug, Default)]
+pub(crate) struct ContextDraft {
+    pub(crate) meta_scope: Option<String>,
+    pub(crate) meta_content_scope: Option<String>,
+    pub(crate) include_prototype: bool,
+    pub(crate) items: Vec<Item>,
+}
+
+impl ContextDraft {
+    pub(crate) fn new(items: Vec<Item>) -> Self {
+        Self {
+            items,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Item {
+    Rule(usize),
+    Include(String),
+}
+
+#[derive(Debug)]
+pub(crate) enum ActionDraft {
+    None,
+    Push(Vec<String>),
+    Set(Vec<String>),
+    Pop(usize),
+}
+
+#[derive(Debug)]
+struct RuleDraft {
+    regex: Regex,
+    scope: Option<String>,
+    captures: Vec<(usize, String)>,
+    action: ActionDraft,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct GrammarBuilder {
+    names: HashMap<String, usize>,
+    contexts: Vec<ContextDraft>,
+    rules: Vec<RuleDraft>,
+    anonymous: usize,
+    skipped: Vec<String>,
+}
+
+impl GrammarBuilder {
+    pub(crate) fn add_context(&mut self, name: String, draft: ContextDraft) {
+        self.names.insert(name, self.contexts.len());
+        self.contexts.push(draft);
+    }
+
+    pub(crate) fn anonymous_name(&mut self) -> String {
+        self.anonymous += 1;
+        format!("#anonymous-{}", self.anonymous)
+    }
+
+    pub(crate) fn skip(&mut self, what: impl Into<String>) {
+        self.skipped.push(what.into());
+    }
+
+    /// Compiles a rule, or records its pattern as skipped when the regex is unsupported.
+    /// （編譯規則；正則不受支援時記錄為略過。）
+    pub(crate) fn add_rule(
+        &mut self,
+        pattern: &str,
+        scope: Option<String>,
+        captures: Vec<(usize, String)>,
+        action: ActionDraft,
+    ) -> Option<Item> {
+        let Ok(regex) = Regex::new(&format!("(?m){}", translate_pattern(pattern))) else {
+            self.skip(pattern);
+            return None;
+        };
+        self.rules.push(RuleDraft {
+            regex,
+            scope,
+            captures,
+            action,
+        });
+        Some(Item::Rule(self.rules.len() - 1))
+    }
+
+    fn textmate_patterns(&mut self, patterns: Option<&Value>) -> Vec<Item> {
+        patterns
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .flat_map(|rule| self.textmate_rule(rule))
+            .collect()
+    }
+
+    fn textmate_rule(&mut self, rule: &Value) -> Vec<Item> {
+        let text = |key: &str| rule.get(key).and_then(Value::as_str);
+        let scope = text("name").map(str::to_string);
+        if let Some(include) = text("include") {
+            return match include {
+                "$self" | "$base" => vec![Item::Include(MAIN_CONTEXT.to_string())],
+                local if local.starts_with('#') => vec![Item::Include(local[1..].to_string())],
+                external => {
+                    self.skip(format!("include {external}"));
+                    Vec::new()
+                }
+            };
+        }
+        if let Some(pattern) = text("match") {
+            let captures = textmate_captures(rule.get("captures"));
+            return self
+                .add_rule(pattern, scope, captures, ActionDraft::None)
+                .into_iter()
+                .collect();
+        }
+        if let Some(begin) = text("begin") {
+            let Some(end) = text("end").filter(|_| rule.get("while").is_none()) else {
+                self.skip(format!("begin/while rule {begin}"));
+                return Vec::new();
+            };
+            let shared = rule.get("captures");
+            let end_captures = textmate_captures(rule.get("endCaptures").or(shared));
+            let Some(end_rule) = self.add_rule(end, None, end_captures, ActionDraft::Pop(1)) else {
+                // 結尾樣式不受支援時整條規則都被略過（常見於參照 begin 擷取的 `\1`）。 / An unsupported end drops the whole rule (typically a `\1` back to the begin captures).
+                self.skip(format!("begin/end rule {begin}"));
+                return Vec::new();
+            };
+            let mut items = self.textmate_patterns(rule.get("patterns"));
+            if rule.get("applyEndPatternLast").and_then(Value::as_bool) == Some(true)
+                || rule.get("applyEndPatternLast").and_then(Value::as_i64) == Some(1)
+            {
+                items.push(end_rule);
+            } else {
+                items.insert(0, end_rule);
+            }
+            let context = self.anonymous_name();
+            self.add_context(
+                context.clone(),
+                ContextDraft {
+                    meta_scope: scope,
+                    meta_content_scope: text("contentName").map(str::to_string),
+                    include_prototype: false,
+                    items,
+                },
+            );
+            let begin_captures = textmate_captures(rule.get("beginCaptures").or(shared));
+            return self
+                .add_rule(
+                    begin,
+                    None,
+                    begin_captures,
+                    ActionDraft::Push(vec![context]),
+                )
+                .into_iter()
+                .collect();
+        }
+        self.textmate_patterns(rule.get("patterns"))
+    }
+
+    pub(crate) fn finish(
+        mut self,
+        name: &str,
+        scope_name: &str,
+        file_extensions: Vec<String>,
+    ) -> Result<Grammar, GrammarError> {
+        let main = *self
+            .names
+            .get(MAIN_CONTEXT)
+            .ok_or(GrammarError::Missing("main context"))?;
+        let prototype = self.names.get(PROTOTYPE_CONTEXT).copied();
+
+        let mut contexts = Vec::with_capacity(self.contexts.len());
+        for id in 0..self.contexts.len() {
+            let mut patterns = Vec::new();
+            if let Some(prototype) = prototype
+                .filter(|prototype| *prototype != id && self.contexts[id].include_prototype)
+            {
+                self.flatten(prototype, &mut patterns, &mut Vec::new());
+            }
+            self.flatten(id, &mut patterns, &mut Vec::new());
+            let draft = &mut self.contexts[id];
+            contexts.push(Context {
+                meta_scope: draft.meta_scope.take(),
+                meta_content_scope: draft.meta_content_scope.take(),
+                patterns,
+            });
+        }
+
+        let rules = std::mem::take(&mut self.rules)
+            .into_iter()
+            .map(|draft| Rule {
+                action: match draft.action {
+                    ActionDraft::None => Action::None,
+                    ActionDraft::Push(targets) => Action::Push(self.resolve(targets)),
+                    ActionDraft::Set(targets) => Action::Set(self.resolve(targets)),
+                    ActionDraft::Pop(count) => Action::Pop(count),
+                },
+                regex: draft.regex,
+                scope: draft.scope,
+                captures: draft.captures,
+            })
+            .collect();
+
+        Ok(Grammar {
+            name: name.to_string(),
+            scope_name: scope_name.to_string(),
+            file_extensions,
+            contexts,
+            rules,
+            main,
+            skipped: self.skipped,
+        })
+    }
+
+    fn flatten(&mut self, id: usize, patterns: &mut Vec<usize>, visiting: &mut Vec<usize>) {
+        if visiting.contains(&id) {
+            return;
+        }
+        visiting.push(id);
+        let items: Vec<(Option<usize>, Option<String>)> = self.contexts[id]
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Rule(rule) => (Some(*rule), None),
+                Item::Include(name) => (None, Some(name.clone())),
+            })
+            .collect();
+        for item in items {
+            match item {
+                (Some(rule), _) => patterns.push(rule),
+                (_, Some(name)) => match self.names.get(&name).copied() {
+                    Some(target) => self.flatten(target, patterns, visiting),
+                    None => self.skip(format!("include {name}")),
+                },
+                _ => {}
+            }
+        }
+        visiting.pop();
+    }
+
+    fn resolve(&mut self, targets: Vec<String>) -> Vec<usize> {
+        let mut resolved = Vec::with_capacity(targets.len());
+        for target in targets {
+            match self.names.get(&target) {
+                Some(id) => resolved.push(*id),
+                None => self.skip(format!("context {target}")),
+            }
+        }
+        resolved
+    }
+}
+
+fn textmate_captures(value: Option<&Value>) -> Vec<(usize, String)> {
+    let mut captures: Vec<(usize, String)> = value
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(group, capture)| {
+            let group = group.parse().ok()?;
+            let name = capture.get("name").and_then(Value::as_str)?;
+            Some((group, name.to_string()))
+        })
+        .collect();
+    captures.sort();
+    captures
+}
+
+pub(crate) fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Rewrites the Oniguruma escapes `fancy-regex` reads differently (`\h` is a hex digit there).
+/// （改寫 `fancy-regex` 解讀不同的 Oniguruma 跳脫字元，例如 `\h` 在 Oniguruma 中代表十六進位數字。）
+fn translate_pattern(pattern: &str) -> String {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    let mut in_class = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('h') if in_class => translated.push_str("0-9A-Fa-f"),
+                Some('h') => translated.push_str("[0-9A-Fa-f]"),
+                Some('H') if !in_class => translated.push_str("[^0-9A-Fa-f]"),
+                Some('Z') if !in_class => translated.push_str(r"\z"),
+                Some(escaped) => {
+                    translated.push('\\');
+                    translated.push(escaped);
+                }
+                None => translated.push('\\'),
+            },
+            '[' if in_class && chars.peek() == Some(&':') => {
+                translated.push('[');
+                for class_char in chars.by_ref() {
+                    translated.push(class_char);
+                    if class_char == ']' {
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                in_class = true;
+                translated.push('[');
+                if chars.peek() == Some(&'^') {
+                    translated.extend(chars.next());
+                }
+                if chars.peek() == Some(&']') {
+                    translated.push_str(r"\]");
+                    chars.next();
+                }
+            }
+            ']' if in_class => {
+                in_class = false;
+                translated.push(']');
+            }
+            _ => translated.push(ch),
+        }
+    }
+    translated
+}
+
+fn plist_to_json(value: plist::Value) -> Value {
+    match value {
+        plist::Value::Dictionary(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, plist_to_json(value)))
+                .collect(),
+        ),
+        plist::Value::Array(items) => Value::Array(items.into_iter().map(plist_to_json).collect()),
+        plist::Value::String(text) => Value::String(text),
+        plist::Value::Boolean(flag) => Value::Bool(flag),
+        plist::Value::Integer(number) => number
+            .as_signed()
+            .map(Value::from)
+            .or_else(|| number.as_unsigned().map(Value::from))
+            .unwrap_or(Value::Null),
+        plist::Value::Real(number) => Value::from(number),
+        plist::Value::Date(date) => Value::String(date.to_xml_format()),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::LanguageRegistry;
+    use crate::theme::{parse_highlight_palette, Color};
+
+    const TM_JSON: &str = r##"{
+        "name": "Mini",
+        "scopeName": "source.mini",
+        "fileTypes": ["mini"],
+        "patterns": [
+            { "include": "#comments" },
+            { "match": "\\b(if|else)\\b", "name": "keyword.control.mini" },
+            { "match": "\\b(let)\\s+(\\w+)",
+              "captures": { "1": { "name": "storage.type.mini" },
+                            "2": { "name": "variable.other.mini" } } },
+            { "begin": "\"", "end": "\"", "name": "string.quoted.double.mini",
+              "patterns": [ { "match": "\\\\.", "name": "constant.character.escape.mini" } ] },
+            { "match": "(?<=\\.)\\w+", "name": "variable.other.member.mini" },
+            { "begin": "<<(\\w+)", "end": "^\\1$", "name": "string.unquoted.heredoc.mini" },
+            { "match": "\\h++", "name": "constant.numeric.hex.mini" }
+        ],
+        "repository": {
+            "comments": { "patterns": [
+                { "begin": "/\\*", "end": "\\*/", "name": "comment.block.mini" },
+                { "match": "//.*$", "name": "comment.line.mini" }
+            ] }
+        }
+    }"##;
+
+    fn scopes(tokens: &[HighlightToken], text: &str) -> Vec<(String, String)> {
+        tokens
+            .iter()
+            .map(|token| {
+                let HighlightKind::Custom(scope) = &token.kind else {
+                    panic!("grammar tokens carry scopes");
+                };
+                (text[token.range.clone()].to_string(), scope.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn textmate_json_grammar_maps_scopes_and_spans_lines() {
+        let grammar = Grammar::from_textmate_json(TM_JSON).unwrap();
+        assert_eq!(grammar.language_id(), "mini");
+        assert_eq!(grammar.file_extensions, ["mini"]);
+        assert_eq!(
+            grammar.skipped_patterns(),
+            [r"^\1$", r"begin/end rule <<(\w+)"]
+        );
+
+        let mut registry = LanguageRegistry::new();
+        registry.register_grammar(grammar);
+        assert_eq!(
+            registry
+                .find_by_extension("MINI")
+                .map(|language| language.id.as_ref()),
+            Some("mini")
+        );
+        let text = "let x = \"a\\\"b\" /* one\ntwo */ if beef";
+        let tokens = registry.highlight("mini", text).unwrap();
+        assert_eq!(
+            scopes(&tokens, text),
+            [
+                ("let", "storage.type.mini"),
+                ("x", "variable.other.mini"),
+                ("\"a", "string.quoted.double.mini"),
+                ("\\\"", "constant.character.escape.mini"),
+                ("b\"", "string.quoted.double.mini"),
+                ("/* one\n", "comment.block.mini"),
+                ("two */", "comment.block.mini"),
+                ("if", "keyword.control.mini"),
+                ("beef", "constant.numeric.hex.mini"),
+            ]
+            .map(|(text, scope)| (text.to_string(), scope.to_string()))
+        );
+
+        let member = "x.y";
+        assert_eq!(
+            scopes(&registry.highlight("mini", member).unwrap(), member),
+            [("y".to_string(), "variable.other.member.mini".to_string())]
+        );
+
+        let mut highlighter = registry.incremental("mini", text).unwrap();
+        assert_eq!(highlighter.highlight_all(text), tokens);
+        assert!(matches!(
+            highlighter.line_state(1),
+            Some(crate::LineState::Grammar(stack)) if stack.len() == 1
+        ));
+
+        let palette = parse_highlight_palette(&serde_json::json!({
+            "keyword": { "foreground": "#0000FF" },
+            "keyword.operator": { "foreground": "#FF0000" },
+            "comment.block": { "foreground": "#00FF00" },
+        }))
+        .unwrap();
+        let color = |scope: &str| {
+            palette
+                .style_for(&HighlightKind::Custom(scope.into()))
+                .map(|style| style.foreground)
+        };
+        let rgb = |r, g, b| Some(Color { r, g, b, a: 255 });
+        assert_eq!(color("keyword.control.mini"), rgb(0, 0, 255));
+        assert_eq!(color("storage.type.mini"), rgb(0, 0, 255));
+        assert_eq!(color("keyword.operator.mini"), rgb(255, 0, 0));
+        assert_eq!(color("comment.block.mini"), rgb(0, 255, 0));
+        assert_eq!(color("string.quoted.double.mini"), None);
+    }
+
+    #[test]
+    fn plist_grammar_matches_json_grammar() {
+        let plist = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key><string>Mini</string>
+    <key>scopeName</key><string>source.mini</string>
+    <key>fileTypes</key><array><string>mini</string></array>
+    <key>patterns</key>
+    <array>
+        <dict><key>include</key><string>#numbers</string></dict>
+        <dict>
+            <key>begin</key><string>&lt;&lt;</string>
+            <key>end</key><string>&gt;&gt;</string>
+            <key>contentName</key><string>markup.raw.mini</string>
+            <key>applyEndPatternLast</key><true/>
+        </dict>
+    </array>
+    <key>repository</key>
+    <dict>
+        <key>numbers</key>
+        <dict>
+            <key>match</key><string>\b\d+\b</string>
+            <key>name</key><string>constant.numeric.mini</string>
+        </dict>
+    </dict>
+</dict>
+</plist>"#;
+        let grammar = Grammar::from_textmate_plist(plist).unwrap();
+        assert_eq!(grammar.name, "Mini");
+        assert!(grammar.skipped_patterns().is_empty());
+        let text = "1 <<raw 2>> 3";
+        let (tokens, stack) = grammar.highlight_line(text, &[]);
+        assert!(stack.is_empty());
+        assert_eq!(
+            scopes(&tokens, text),
+            [
+                ("1", "constant.numeric.mini"),
+                ("raw 2", "markup.raw.mini"),
+                ("3", "constant.numeric.mini"),
+            ]
+            .map(|(text, scope)| (text.to_string(), scope.to_string()))
+        );
+        assert!(matches!(
+            Grammar::from_textmate_json(r#"{ "name": "x" }"#),
+            Err(GrammarError::Missing("scopeName"))
+        ));
+    }
+}
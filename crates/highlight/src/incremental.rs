@@ -0,0 +1,427 @@
+//! Incremental, line-state based highlighting.
+//! （以行狀態為基礎的增量高亮。）
+//!
+//! Every line is lexed from the state left at the end of the previous line
+//! (inside a block comment, inside a string, or normal code). The highlighter
+//! caches each line's start state and tokens; after an edit only the touched
+//! lines are invalidated, and lexing resumes there until a line's new start
+//! state equals the cached one, after which the cached results are reused.
+//! Lines are lexed lazily up to the last line a viewport asks for.
+//! With a tree-sitter backend the document is reparsed instead, reusing the
+//! previous syntax tree, and only the requested lines are highlighted.
+//! 每一行都從上一行結尾的狀態（區塊註解中、字串中或一般程式碼）開始掃描。
+//! 高亮器快取每行的起始狀態與 token；編輯後只讓受影響的行失效，並從該處重新
+//! 掃描，直到某行的新起始狀態與快取相同為止，之後直接重用快取結果。
+//! 各行只在視窗查詢需要時才延遲掃描。若使用 tree-sitter 後端，則改為重用前一棵
+//! 語法樹重新解析文件，並只高亮查詢的行。
+
+use std::iter;
+use std::ops::Range;
+
+#[cfg(feature = "tree-sitter")]
+use tree_sitter::{InputEdit, Point};
+
+use crate::language::{HighlightToken, LanguageDefinition};
+#[cfg(feature = "tree-sitter")]
+use crate::syntax_tree::SyntaxTree;
+
+/// Lexer state carried from the end of one line to the start of the next.
+/// （由一行結尾延續到下一行開頭的詞法狀態。）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// Inside a line comment continued from the previous line.
+    /// （位於由上一行延續而來的行註解中。）
+    LineComment,
+    /// Inside an unterminated block comment.
+    /// （位於尚未結束的區塊註解中。）
+    BlockComment,
+    /// Inside an unterminated string opened by the delimiter at this index.
+    /// （位於此索引分隔符開啟、尚未結束的字串中。）
+    String(usize),
+    /// Inside these grammar contexts, innermost last.
+    /// （位於這些語法 context 中，最內層在最後。）
+    Grammar(Vec<usize>),
+}
+
+#[derive(Debug, Clone, Default)]
+struct CachedLine {
+    state: LineState,
+    lexed: Option<(Vec<HighlightToken>, LineState)>,
+}
+
+/// Highlighter that caches per-line states and re-lexes only what an edit can affect.
+/// （快取每行狀態、只重新掃描編輯可能影響範圍的高亮器。）
+///
+/// The caller keeps the text; every method receives the current text, which must
+/// match the edits reported through [`IncrementalHighlighter::apply_edit`].
+/// （文字由呼叫端保存；每個方法都會收到目前的文字，且須與透過
+/// [`IncrementalHighlighter::apply_edit`] 回報的編輯一致。）
+///
+/// With a tree-sitter backend no line states are tracked, so [`line_state`](Self::line_state) returns `None`.
+/// （使用 tree-sitter 後端時不追蹤行狀態，[`line_state`](Self::line_state) 一律回傳 `None`。）
+#[derive(Debug, Clone)]
+pub struct IncrementalHighlighter {
+    language: LanguageDefinition,
+    line_starts: Vec<usize>,
+    text_len: usize,
+    lines: Vec<CachedLine>,
+    valid: usize,
+    last_relexed: usize,
+    #[cfg(feature = "tree-sitter")]
+    syntax: Option<SyntaxTree>,
+    #[cfg(feature = "tree-sitter")]
+    syntax_stale: bool,
+}
+
+impl IncrementalHighlighter {
+    pub fn new(language: LanguageDefinition, text: &str) -> Self {
+        let mut highlighter = Self {
+            language,
+            line_starts: Vec::new(),
+            text_len: 0,
+            lines: Vec::new(),
+            valid: 0,
+            last_relexed: 0,
+            #[cfg(feature = "tree-sitter")]
+            syntax: None,
+            #[cfg(feature = "tree-sitter")]
+            syntax_stale: false,
+        };
+        highlighter.set_text(text);
+        highlighter
+    }
+
+    pub fn language(&self) -> &LanguageDefinition {
+        &self.language
+    }
+
+    /// Number of lines in the text.
+    /// （文字的行數。）
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Number of lines lexed by the latest query.
+    /// （最近一次查詢實際掃描的行數。）
+    pub fn last_relexed(&self) -> usize {
+        self.last_relexed
+    }
+
+    /// Start state of a line, if it is already known to be up to date.
+    /// （若已確認為最新，回傳該行的起始狀態。）
+    pub fn line_state(&self, line: usize) -> Option<LineState> {
+        (line < self.valid).then(|| self.lines[line].state.clone())
+    }
+
+    /// Replaces the whole text and drops every cached line.
+    /// （取代整份文字並清除所有快取行。）
+    pub fn set_text(&mut self, text: &str) {
+        self.line_starts = iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        self.text_len = text.len();
+        self.lines = vec![CachedLine::default(); self.line_starts.len()];
+        self.valid = 0;
+        #[cfg(feature = "tree-sitter")]
+        {
+            self.syntax = None;
+        }
+    }
+
+    /// Records an edit that replaced `removed` bytes at `start` with `inserted` bytes; `text` is the edited text.
+    /// （記錄在 `start` 處以 `inserted` 位元組取代 `removed` 位元組的編輯；`text` 為編輯後的文字。）
+    ///
+    /// Only the touched lines are invalidated; the lines after them keep their cache until re-verified.
+    /// （只有受影響的行會失效；之後的行保留快取，待重新驗證。）
+    pub fn apply_edit(&mut self, text: &str, start: usize, removed: usize, inserted: usize) {
+        let removed_end = start + removed;
+        if removed_end > self.text_len
+            || self.text_len - removed + inserted != text.len()
+            || !text.is_char_boundary(start)
+            || !text.is_char_boundary(start + inserted)
+        {
+            self.set_text(text);
+            return;
+        }
+        #[cfg(feature = "tree-sitter")]
+        let (start_position, old_end_position) = (self.point(start), self.point(removed_end));
+        let first = self.line_of_offset(start);
+        let last = self.line_of_offset(removed_end);
+        for line_start in &mut self.line_starts[last + 1..] {
+            *line_start = *line_start - removed + inserted;
+        }
+        let inserted_starts: Vec<usize> = text[start..start + inserted]
+            .match_indices('\n')
+            .map(|(idx, _)| start + idx + 1)
+            .collect();
+        let new_lines = inserted_starts.len() + 1;
+        self.line_starts.splice(first + 1..=last, inserted_starts);
+        self.lines.splice(
+            first..=last,
+            iter::repeat_with(CachedLine::default).take(new_lines),
+        );
+        self.text_len = text.len();
+        self.valid = self.valid.min(first);
+        #[cfg(feature = "tree-sitter")]
+        {
+            let new_end_position = self.point(start + inserted);
+            if let Some(tree) = &mut self.syntax {
+                tree.edit(&InputEdit {
+                    start_byte: start,
+                    old_end_byte: removed_end,
+                    new_end_byte: start + inserted,
+                    start_position,
+                    old_end_position,
+                    new_end_position,
+                });
+                self.syntax_stale = true;
+            }
+        }
+    }
+
+    /// Tokens for a range of lines (e.g. the viewport), with offsets into the whole text.
+    /// （取得指定行範圍（例如可視區域）的 token，位移以整份文字為準。）
+    ///
+    /// Lines before the range are lexed first when their state is not known yet.
+    /// （範圍之前狀態未知的行會先被掃描。）
+    pub fn highlight_lines(&mut self, text: &str, lines: Range<usize>) -> Vec<HighlightToken> {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(tokens) = self.syntax_tokens(text, lines.clone()) {
+            return tokens;
+        }
+        let end = lines.end.min(self.line_count());
+        self.last_relexed = 0;
+        self.ensure_lexed(text, end);
+        let mut tokens = Vec::new();
+        for line in lines.start.min(end)..end {
+            let offset = self.line_starts[line];
+            if let Some((line_tokens, _)) = &self.lines[line].lexed {
+                tokens.extend(line_tokens.iter().map(|token| HighlightToken {
+                    range: token.range.start + offset..token.range.end + offset,
+                    kind: token.kind.clone(),
+                }));
+            }
+        }
+        tokens
+    }
+
+    /// Tokens for the whole text.
+    /// （取得整份文字的 token。）
+    pub fn highlight_all(&mut self, text: &str) -> Vec<HighlightToken> {
+        self.highlight_lines(text, 0..self.line_count())
+    }
+
+    fn ensure_lexed(&mut self, text: &str, end: usize) {
+        while self.valid < end {
+            let line = self.valid;
+            let state = match line.checked_sub(1) {
+                Some(previous) => self.lines[previous]
+                    .lexed
+                    .as_ref()
+                    .map_or(LineState::Normal, |(_, end_state)| end_state.clone()),
+                None => LineState::Normal,
+            };
+            let cached = &self.lines[line];
+            if cached.lexed.is_some() && cached.state == state {
+                // 狀態已收斂：後續連續的快取行都仍有效。 / States converged: the following run of cached lines is still valid.
+                self.valid += 1;
+                while self
+                    .lines
+                    .get(self.valid)
+                    .is_some_and(|cached| cached.lexed.is_some())
+                {
+                    self.valid += 1;
+                }
+                continue;
+            }
+            let range = self.line_range(line);
+            let lexed = self.language.highlight_line(&text[range], state.clone());
+            self.lines[line] = CachedLine {
+                state,
+                lexed: Some(lexed),
+            };
+            self.last_relexed += 1;
+            self.valid += 1;
+        }
+    }
+
+    /// Tokens from the tree-sitter backend, reparsing first when the text was edited.
+    /// （由 tree-sitter 後端取得 token，文字編輯過時先重新解析。）
+    #[cfg(feature = "tree-sitter")]
+    fn syntax_tokens(&mut self, text: &str, lines: Range<usize>) -> Option<Vec<HighlightToken>> {
+        let backend = self.language.tree_sitter()?;
+        if self.syntax.is_none() || self.syntax_stale {
+            let parsed = match &self.syntax {
+                Some(old) => backend.reparse(text, old),
+                None => backend.parse(text),
+            };
+            self.syntax = Some(parsed.ok()?);
+            self.syntax_stale = false;
+        }
+        let end = lines.end.min(self.line_count());
+        let start = lines.start.min(end);
+        self.last_relexed = end - start;
+        let bytes = self.line_offset(start)..self.line_offset(end);
+        if bytes.is_empty() {
+            return Some(Vec::new());
+        }
+        let tree = self.syntax.as_ref()?;
+        Some(backend.highlight_range(tree, text, bytes))
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    fn line_offset(&self, line: usize) -> usize {
+        self.line_starts.get(line).copied().unwrap_or(self.text_len)
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    fn point(&self, offset: usize) -> Point {
+        let row = self.line_of_offset(offset);
+        Point::new(row, offset - self.line_starts[row])
+    }
+
+    fn line_of_offset(&self, offset: usize) -> usize {
+        self.line_starts
+            .partition_point(|start| *start <= offset)
+            .saturating_sub(1)
+    }
+
+    fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line];
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text_len);
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::{HighlightKind, LanguageRegistry};
+
+    fn replace(text: &mut String, start: usize, removed: usize, inserted: &str) {
+        text.replace_range(start..start + removed, inserted);
+    }
+
+    #[test]
+    fn edits_relex_until_line_states_converge() {
+        let registry = LanguageRegistry::with_defaults();
+        let rust = registry.get("rust").unwrap();
+        let mut text: String = (0..50)
+            .map(|idx| format!("let x{idx} = {idx};\n"))
+            .collect();
+        let mut highlighter = registry.incremental("rust", &text).unwrap();
+        assert_eq!(highlighter.line_count(), 51);
+        assert_eq!(highlighter.highlight_all(&text), rust.highlight(&text));
+        assert_eq!(highlighter.last_relexed(), 51);
+
+        let start = text.find("x10").unwrap();
+        replace(&mut text, start, 3, "renamed");
+        highlighter.apply_edit(&text, start, 3, 7);
+        assert_eq!(highlighter.highlight_all(&text), rust.highlight(&text));
+        assert_eq!(highlighter.last_relexed(), 1);
+
+        let start = text.find("let x20").unwrap();
+        replace(&mut text, start, 0, "/* open\n");
+        highlighter.apply_edit(&text, start, 0, 8);
+        let tokens = highlighter.highlight_all(&text);
+        assert_eq!(tokens, rust.highlight(&text));
+        assert_eq!(highlighter.last_relexed(), 32);
+        assert_eq!(highlighter.line_state(21), Some(LineState::BlockComment));
+        assert!(tokens
+            .iter()
+            .filter(|token| token.range.start > start)
+            .all(|token| token.kind == HighlightKind::Comment));
+
+        let start = text.find("let x30").unwrap();
+        replace(&mut text, start, 0, "*/");
+        highlighter.apply_edit(&text, start, 0, 2);
+        assert_eq!(highlighter.highlight_all(&text), rust.highlight(&text));
+        assert_eq!(highlighter.last_relexed(), 21);
+        assert_eq!(highlighter.line_state(32), Some(LineState::Normal));
+
+        let start = text.find("let x40").unwrap();
+        replace(&mut text, start, 14, "");
+        highlighter.apply_edit(&text, start, 14, 0);
+        assert_eq!(highlighter.line_count(), 51);
+        assert_eq!(highlighter.highlight_all(&text), rust.highlight(&text));
+        assert_eq!(highlighter.last_relexed(), 1);
+    }
+
+    #[test]
+    fn viewport_queries_lex_lazily_and_strings_span_lines() {
+        let registry = LanguageRegistry::with_defaults();
+        let mut text = "fn a() {}\n".repeat(1000);
+        let mut highlighter = registry.incremental("rust", &text).unwrap();
+        let tokens = highlighter.highlight_lines(&text, 10..12);
+        assert_eq!(highlighter.last_relexed(), 12);
+        assert_eq!(highlighter.line_state(12), None);
+        assert_eq!(tokens.first().map(|token| token.range.start), Some(100));
+        assert!(tokens
+            .iter()
+            .all(|token| (100..120).contains(&token.range.start)));
+        highlighter.highlight_lines(&text, 0..12);
+        assert_eq!(highlighter.last_relexed(), 0);
+
+        replace(&mut text, 0, 0, "\"x\\\"\ny\" ");
+        highlighter.apply_edit(&text, 0, 0, 8);
+        let tokens = highlighter.highlight_lines(&text, 0..3);
+        assert_eq!(highlighter.line_state(1), Some(LineState::String(0)));
+        assert_eq!(tokens[0].range, 0..5);
+        assert_eq!(tokens[0].kind, HighlightKind::String);
+        assert_eq!(tokens[1].range, 5..7);
+        assert_eq!(tokens[1].kind, HighlightKind::String);
+        assert_eq!(tokens[2].kind, HighlightKind::Keyword);
+
+        let rust = registry.get("rust").unwrap();
+        let (tokens, state) = rust.highlight_line("\"http://x\" // note\n", LineState::Normal);
+        assert_eq!(state, LineState::Normal);
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.range.clone(), token.kind.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (0..10, HighlightKind::String),
+                (11..19, HighlightKind::Comment)
+            ]
+        );
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn tree_sitter_languages_reparse_after_edits() {
+        let registry = LanguageRegistry::with_tree_sitter_defaults();
+        let rust = registry.get("rust").unwrap();
+        let mut text = String::from("fn main() {\n    let a = 1;\n    let b = 2;\n}\n");
+        let mut highlighter = registry.incremental("rust", &text).unwrap();
+        assert_eq!(highlighter.highlight_all(&text), rust.highlight(&text));
+        assert_eq!(highlighter.line_state(1), None);
+
+        // 跨行原始字串只有解析整份文件才能正確高亮。 / A raw string spanning lines is only right when the whole document is parsed.
+        let start = text.find("1;").unwrap();
+        replace(&mut text, start, 1, "r#\"x\n    let c = \"#");
+        highlighter.apply_edit(&text, start, 1, 18);
+        let viewport = highlighter.highlight_lines(&text, 2..3);
+        assert_eq!(highlighter.last_relexed(), 1);
+        let line = text.find("    let c").unwrap()..text.find("    let b").unwrap();
+        assert_eq!(viewport[0].range, line.start..line.end - 2);
+        assert_eq!(viewport[0].kind, HighlightKind::String);
+        assert_eq!(highlighter.highlight_all(&text), rust.highlight(&text));
+
+        // 單行高亮不會單獨解析該行，而是沿用行狀態。 / Line highlighting never parses the line alone; it follows the line state.
+        let line = "still a comment */ let s = r#\"a\"#;";
+        let regex_rust = LanguageRegistry::with_defaults();
+        let expected = regex_rust
+            .get("rust")
+            .unwrap()
+            .highlight_line(line, LineState::BlockComment);
+        assert_eq!(rust.highlight_line(line, LineState::BlockComment), expected);
+        assert_eq!(expected.0[0].kind, HighlightKind::Comment);
+    }
+}
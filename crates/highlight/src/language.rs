@@ -1,11 +1,21 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
 
 use regex::{Regex, RegexBuilder};
 use thiserror::Error;
 
-use crate::udl::{Delimiter, UdlDefinition};
+use crate::grammar::{Grammar, GrammarError};
+use crate::incremental::{IncrementalHighlighter, LineState};
+#[cfg(feature = "tree-sitter")]
+use crate::syntax_tree::{TreeSitterBackend, TreeSitterLanguage};
+use crate::theme::HighlightPalette;
+use crate::udl::{
+    DecimalSeparator, Delimiter, FoldMarkers, LineCommentPosition, NumberRules, UdlDefinition,
+    KEYWORD_GROUPS,
+};
 
 const NUMBER_PATTERN: &str = r"(?x)
     (?P<number>
@@ -61,36 +71,102 @@ pub struct LanguageDefinition {
     pub case_sensitive: bool,
     pub keywords: Vec<String>,
 
-    keyword_regex: Option<Regex>,
+    keyword_rules: Vec<PatternRule>,
     operator_regex: Option<Regex>,
+    separated_operators: Vec<String>,
     number_regex: Regex,
-    line_comment: Option<String>,
-    block_comment: Option<BlockComment>,
+    line_comments: Vec<String>,
+    line_comment_continue: Option<String>,
+    line_comment_close: Option<String>,
+    line_comment_position: LineCommentPosition,
+    line_comment_kind: HighlightKind,
+    block_comments: Vec<BlockComment>,
     string_delimiters: Vec<StringDelimiter>,
     additional_rules: Vec<PatternRule>,
+    fold_rules: Vec<FoldRule>,
+    comment_fold: Option<FoldRule>,
+    allow_fold_of_comments: bool,
+    fold_compact: bool,
+    palette: Option<HighlightPalette>,
+    grammar: Option<Arc<Grammar>>,
+    #[cfg(feature = "tree-sitter")]
+    tree_sitter: Option<Arc<TreeSitterBackend>>,
 }
 
 impl LanguageDefinition {
     pub fn from_udl(udl: UdlDefinition) -> Result<Self, HighlightError> {
         let id = LanguageId::from(udl.identifier.clone().unwrap_or_else(|| udl.name.clone()));
-        let keywords = udl.keywords.clone();
-        let keyword_regex = build_keyword_regex(&keywords, udl.case_sensitive)?;
+        let keywords = (1..=KEYWORD_GROUPS)
+            .flat_map(|group| udl.keyword_group(group).iter().cloned())
+            .collect();
+        let keyword_rules = build_keyword_rules(&udl)?;
         let operator_regex = build_operator_regex(&udl.operators)?;
-        let number_regex = build_number_regex(udl.number_pattern.as_deref())?;
-        let line_comment = udl.line_comment.clone();
-        let block_comment = udl
+        let number_regex = match &udl.number_pattern {
+            None if !udl.numbers.is_empty() || udl.decimal_separator != DecimalSeparator::Dot => {
+                build_udl_number_regex(&udl.numbers, udl.decimal_separator, udl.case_sensitive)?
+            }
+            custom => build_number_regex(custom.as_deref())?,
+        };
+        let line_comments = udl
+            .line_comment
+            .as_deref()
+            .map(split_alternatives)
+            .unwrap_or_default();
+        let line_comment_kind = if udl.styles.iter().any(|style| style.name == "LINE COMMENTS") {
+            HighlightKind::Custom("comment.line".into())
+        } else {
+            HighlightKind::Comment
+        };
+        let block_comments = udl
             .block_comment
-            .clone()
-            .map(|(start, end)| BlockComment { start, end });
+            .as_ref()
+            .map(|(start, end)| {
+                pair_alternatives(start, Some(end))
+                    .map(|(start, end)| BlockComment { start, end })
+                    .collect()
+            })
+            .unwrap_or_default();
         let string_delimiters = udl
             .delimiters
             .iter()
-            .map(|Delimiter { start, end, escape }| StringDelimiter {
-                start: start.clone(),
-                end: end.clone().unwrap_or_else(|| start.clone()),
-                escape: *escape,
+            .enumerate()
+            .flat_map(|(group, Delimiter { start, end, escape })| {
+                let kind = match group {
+                    0 => HighlightKind::String,
+                    _ => HighlightKind::Custom(format!("string.delimiter{}", group + 1)),
+                };
+                pair_alternatives(start, end.as_deref()).map(move |(start, end)| StringDelimiter {
+                    start,
+                    end,
+                    escape: *escape,
+                    kind: kind.clone(),
+                })
             })
             .collect();
+        let fold_rules = [
+            build_fold_rule(
+                &udl.folds_in_code1,
+                false,
+                udl.case_sensitive,
+                "keyword.fold.code1",
+            )?,
+            build_fold_rule(
+                &udl.folds_in_code2,
+                true,
+                udl.case_sensitive,
+                "keyword.fold.code2",
+            )?,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let comment_fold = build_fold_rule(
+            &udl.folds_in_comment,
+            false,
+            udl.case_sensitive,
+            "comment.fold",
+        )?;
+        let palette = (!udl.styles.is_empty()).then(|| udl.palette());
 
         Ok(Self {
             id,
@@ -98,44 +174,269 @@ impl LanguageDefinition {
             extensions: udl.extensions,
             case_sensitive: udl.case_sensitive,
             keywords,
-            keyword_regex,
+            keyword_rules,
             operator_regex,
+            separated_operators: udl.operators2,
             number_regex,
-            line_comment,
-            block_comment,
+            line_comments,
+            line_comment_continue: udl.line_comment_continue,
+            line_comment_close: udl.line_comment_close,
+            line_comment_position: udl.line_comment_position,
+            line_comment_kind,
+            block_comments,
             string_delimiters,
             additional_rules: Vec::new(),
+            fold_rules,
+            comment_fold,
+            allow_fold_of_comments: udl.allow_fold_of_comments,
+            fold_compact: udl.fold_compact,
+            palette,
+            grammar: None,
+            #[cfg(feature = "tree-sitter")]
+            tree_sitter: None,
         })
     }
 
+    /// Wraps a TextMate or Sublime grammar; tokens carry its scope names as [`HighlightKind::Custom`].
+    /// （包裝 TextMate 或 Sublime 語法；token 以其 scope 名稱作為 [`HighlightKind::Custom`]。）
+    pub fn from_grammar(grammar: Grammar) -> Self {
+        Self {
+            id: LanguageId::from(grammar.language_id()),
+            display_name: grammar.name.clone(),
+            extensions: grammar.file_extensions.clone(),
+            case_sensitive: true,
+            keywords: Vec::new(),
+            keyword_rules: Vec::new(),
+            operator_regex: None,
+            separated_operators: Vec::new(),
+            number_regex: build_number_regex(None).expect("built-in number regex must compile"),
+            line_comments: Vec::new(),
+            line_comment_continue: None,
+            line_comment_close: None,
+            line_comment_position: LineCommentPosition::Anywhere,
+            line_comment_kind: HighlightKind::Comment,
+            block_comments: Vec::new(),
+            string_delimiters: Vec::new(),
+            additional_rules: Vec::new(),
+            fold_rules: Vec::new(),
+            comment_fold: None,
+            allow_fold_of_comments: false,
+            fold_compact: false,
+            palette: None,
+            grammar: Some(Arc::new(grammar)),
+            #[cfg(feature = "tree-sitter")]
+            tree_sitter: None,
+        }
+    }
+
+    /// Wraps a bundled tree-sitter grammar without any regex rules.
+    /// （包裝內建的 tree-sitter 語法，不含任何正則規則。）
+    #[cfg(feature = "tree-sitter")]
+    pub fn from_tree_sitter(backend: TreeSitterBackend) -> Self {
+        let language = backend.language();
+        Self {
+            id: LanguageId::from(language.id()),
+            display_name: language.display_name().to_string(),
+            extensions: language
+                .extensions()
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            case_sensitive: true,
+            keywords: Vec::new(),
+            keyword_rules: Vec::new(),
+            operator_regex: None,
+            separated_operators: Vec::new(),
+            number_regex: build_number_regex(None).expect("built-in number regex must compile"),
+            line_comments: Vec::new(),
+            line_comment_continue: None,
+            line_comment_close: None,
+            line_comment_position: LineCommentPosition::Anywhere,
+            line_comment_kind: HighlightKind::Comment,
+            block_comments: Vec::new(),
+            string_delimiters: Vec::new(),
+            additional_rules: Vec::new(),
+            fold_rules: Vec::new(),
+            comment_fold: None,
+            allow_fold_of_comments: false,
+            fold_compact: false,
+            palette: None,
+            grammar: None,
+            tree_sitter: Some(Arc::new(backend)),
+        }
+    }
+
+    /// Makes highlighting, line lexing, the incremental highlighter and folding parse with tree-sitter.
+    /// （讓高亮、逐行掃描、增量高亮器與摺疊改用 tree-sitter 解析。）
+    #[cfg(feature = "tree-sitter")]
+    pub fn with_tree_sitter(mut self, backend: TreeSitterBackend) -> Self {
+        self.tree_sitter = Some(Arc::new(backend));
+        self
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    pub fn tree_sitter(&self) -> Option<&TreeSitterBackend> {
+        self.tree_sitter.as_deref()
+    }
+
+    pub fn grammar(&self) -> Option<&Grammar> {
+        self.grammar.as_deref()
+    }
+
     pub fn keywords(&self) -> &[String] {
         &self.keywords
     }
 
+    /// Styles the language brings along (a UDL's `<Styles>`), to be preferred over the theme.
+    /// （語言自帶的樣式（UDL 的 `<Styles>`），優先於主題使用。）
+    pub fn palette(&self) -> Option<&HighlightPalette> {
+        self.palette.as_ref()
+    }
+
+    /// Highlights a whole document, one line at a time.
+    /// （逐行高亮整份文件。）
     pub fn highlight(&self, input: &str) -> Vec<HighlightToken> {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(backend) = &self.tree_sitter {
+            if let Ok(tree) = backend.parse(input) {
+                return backend.highlight(&tree, input);
+            }
+        }
+
         let mut tokens = Vec::new();
-        if input.is_empty() {
-            return tokens;
+        let mut state = LineState::Normal;
+        let mut offset = 0;
+        for line in input.split_inclusive('\n') {
+            let (line_tokens, end_state) = self.highlight_line(line, state);
+            tokens.extend(line_tokens.into_iter().map(|token| HighlightToken {
+                range: token.range.start + offset..token.range.end + offset,
+                kind: token.kind,
+            }));
+            state = end_state;
+            offset += line.len();
         }
-        let mut occupied = vec![false; input.len()];
+        tokens
+    }
 
-        if let Some(block) = &self.block_comment {
-            highlight_block_comments(block, input, &mut tokens, &mut occupied);
+    /// Highlights one line that starts in `state`; returns line-relative tokens and the state at its end.
+    /// （以 `state` 為起始狀態高亮單一行，回傳相對於行首的 token 與行尾狀態。）
+    ///
+    /// Comments and strings are lexed left to right so that a marker inside one never opens another.
+    /// （註解與字串由左至右掃描，其中出現的符號不會開啟另一段註解或字串。）
+    ///
+    /// A tree-sitter backend is never run on a single line, since a line parsed on its own loses
+    /// every construct that spans lines; [`highlight`](Self::highlight) and [`IncrementalHighlighter`]
+    /// highlight from the whole-document tree instead.
+    /// （tree-sitter 後端不會只解析單一行，因為單獨解析的行會失去所有跨行結構；
+    /// [`highlight`](Self::highlight) 與 [`IncrementalHighlighter`] 改由整份文件的語法樹高亮。）
+    pub fn highlight_line(&self, line: &str, state: LineState) -> (Vec<HighlightToken>, LineState) {
+        if let Some(grammar) = &self.grammar {
+            let stack = match &state {
+                LineState::Grammar(stack) => stack.as_slice(),
+                _ => &[],
+            };
+            let (tokens, stack) = grammar.highlight_line(line, stack);
+            let state = if stack.is_empty() {
+                LineState::Normal
+            } else {
+                LineState::Grammar(stack)
+            };
+            return (tokens, state);
         }
 
-        if let Some(line_comment) = &self.line_comment {
-            highlight_line_comments(line_comment, input, &mut tokens, &mut occupied);
+        let mut tokens = Vec::new();
+        let mut occupied = vec![false; line.len()];
+        let mut state = match state {
+            LineState::Grammar(_) => LineState::Normal,
+            other => other,
+        };
+        let mut cursor = 0;
+
+        while cursor < line.len() {
+            let (start, body) = match state {
+                LineState::Normal => match self.next_opener(line, cursor) {
+                    Some((start, opener)) => {
+                        let len = match opener {
+                            Opener::LineComment(len) => {
+                                state = LineState::LineComment;
+                                len
+                            }
+                            Opener::BlockComment(len) => {
+                                state = LineState::BlockComment;
+                                len
+                            }
+                            Opener::String(idx, len) => {
+                                state = LineState::String(idx);
+                                len
+                            }
+                        };
+                        (start, start + len)
+                    }
+                    None => break,
+                },
+                _ => (cursor, cursor),
+            };
+            let (close, kind) = match state {
+                LineState::LineComment => (
+                    self.line_comment_close.as_ref().and_then(|close| {
+                        line[body..]
+                            .find(close.as_str())
+                            .map(|rel| body + rel + close.len())
+                    }),
+                    self.line_comment_kind.clone(),
+                ),
+                LineState::BlockComment => (
+                    self.block_comments
+                        .iter()
+                        .filter_map(|block| {
+                            line[body..]
+                                .find(&block.end)
+                                .map(|rel| body + rel + block.end.len())
+                        })
+                        .min(),
+                    HighlightKind::Comment,
+                ),
+                LineState::String(idx) => match self.string_delimiters.get(idx) {
+                    Some(delimiter) => (
+                        find_string_end(delimiter, line, body),
+                        delimiter.kind.clone(),
+                    ),
+                    None => (None, HighlightKind::String),
+                },
+                LineState::Normal | LineState::Grammar(_) => {
+                    unreachable!("openers always leave the normal state")
+                }
+            };
+            let end = close.unwrap_or(line.len());
+            if matches!(state, LineState::String(_)) {
+                push_span(&mut tokens, &mut occupied, start..end, kind);
+            } else {
+                self.push_comment(line, &mut tokens, &mut occupied, start..end, kind);
+            }
+            cursor = end;
+            if close.is_some() {
+                state = LineState::Normal;
+            }
+        }
+        if state == LineState::LineComment && !self.continues_line_comment(line) {
+            state = LineState::Normal;
         }
 
-        for delimiter in &self.string_delimiters {
-            highlight_strings(delimiter, input, &mut tokens, &mut occupied);
+        for rule in &self.fold_rules {
+            highlight_with_regex(
+                &rule.regex,
+                rule.kind.clone(),
+                line,
+                &mut tokens,
+                &mut occupied,
+            );
         }
 
-        if let Some(regex) = &self.keyword_regex {
+        for rule in &self.keyword_rules {
             highlight_with_regex(
-                regex,
-                HighlightKind::Keyword,
-                input,
+                &rule.regex,
+                rule.kind.clone(),
+                line,
                 &mut tokens,
                 &mut occupied,
             );
@@ -144,7 +445,7 @@ impl LanguageDefinition {
         highlight_with_regex(
             &self.number_regex,
             HighlightKind::Number,
-            input,
+            line,
             &mut tokens,
             &mut occupied,
         );
@@ -153,27 +454,215 @@ impl LanguageDefinition {
             highlight_with_regex(
                 regex,
                 HighlightKind::Operator,
-                input,
+                line,
                 &mut tokens,
                 &mut occupied,
             );
         }
 
+        highlight_separated(
+            &self.separated_operators,
+            self.case_sensitive,
+            line,
+            &mut tokens,
+            &mut occupied,
+        );
+
         for rule in &self.additional_rules {
             highlight_with_regex(
                 &rule.regex,
                 rule.kind.clone(),
-                input,
+                line,
                 &mut tokens,
                 &mut occupied,
             );
         }
 
         tokens.sort_by_key(|token| token.range.start);
-        tokens
+        (tokens, state)
+    }
+
+    /// Earliest comment or string opener at or after `from`; ties go to block comments, then line comments.
+    /// （`from` 之後最早出現的註解或字串開頭；位置相同時區塊註解優先，其次為行註解。）
+    fn next_opener(&self, line: &str, from: usize) -> Option<(usize, Opener)> {
+        let mut best: Option<(usize, Opener)> = None;
+        let mut consider = |marker: &str, opener: Opener| {
+            if marker.is_empty() {
+                return;
+            }
+            if let Some(rel) = line[from..].find(marker) {
+                let start = from + rel;
+                if best.is_none_or(|(current, _)| current > start) {
+                    best = Some((start, opener));
+                }
+            }
+        };
+        for block in &self.block_comments {
+            if !block.end.is_empty() {
+                consider(&block.start, Opener::BlockComment(block.start.len()));
+            }
+        }
+        let line_comment_allowed = |start: usize| match self.line_comment_position {
+            LineCommentPosition::Anywhere => true,
+            LineCommentPosition::LineStart => start == 0,
+            LineCommentPosition::AfterWhitespace => line[..start].trim().is_empty(),
+        };
+        for marker in &self.line_comments {
+            let allowed = line[from..]
+                .find(marker.as_str())
+                .is_some_and(|rel| line_comment_allowed(from + rel));
+            if allowed {
+                consider(marker, Opener::LineComment(marker.len()));
+            }
+        }
+        for (idx, delimiter) in self.string_delimiters.iter().enumerate() {
+            if !delimiter.end.is_empty() {
+                consider(&delimiter.start, Opener::String(idx, delimiter.start.len()));
+            }
+        }
+        best
+    }
+
+    fn continues_line_comment(&self, line: &str) -> bool {
+        self.line_comment_continue.as_ref().is_some_and(|marker| {
+            line.trim_end_matches(['\r', '\n'])
+                .ends_with(marker.as_str())
+        })
+    }
+
+    /// Pushes a comment span, splitting out the fold markers it contains.
+    /// （加入註解範圍，並切出其中的摺疊符號。）
+    fn push_comment(
+        &self,
+        line: &str,
+        tokens: &mut Vec<HighlightToken>,
+        occupied: &mut [bool],
+        range: Range<usize>,
+        kind: HighlightKind,
+    ) {
+        let mut from = range.start;
+        if let Some(rule) = &self.comment_fold {
+            for found in rule.regex.find_iter(&line[range.clone()]) {
+                let marker = range.start + found.start()..range.start + found.end();
+                push_span(tokens, occupied, from..marker.start, kind.clone());
+                push_span(tokens, occupied, marker.clone(), rule.kind.clone());
+                from = marker.end;
+            }
+        }
+        push_span(tokens, occupied, from..range.end, kind);
+    }
+
+    /// Foldable line ranges `(start_line, end_line)` from the UDL fold markers and, when allowed, comment blocks.
+    /// （依 UDL 摺疊符號（允許時亦含註解區塊）產生的可摺疊行範圍 `(起始行, 結束行)`。）
+    ///
+    /// A middle marker (such as `else`) ends the current region on the previous line and starts a new one.
+    /// （中間符號（例如 `else`）會在前一行結束目前區段並開始新區段。）
+    pub fn fold_regions(&self, text: &str) -> Vec<(usize, usize)> {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(backend) = &self.tree_sitter {
+            if let Ok(tree) = backend.parse(text) {
+                return backend.fold_regions(&tree);
+            }
+        }
+
+        let mut regions = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        let mut block_start = None;
+        let mut comment_run: Option<(usize, usize)> = None;
+        let mut blank = Vec::new();
+        let mut state = LineState::Normal;
+        for (line_no, line) in text.split_inclusive('\n').enumerate() {
+            blank.push(line.trim().is_empty());
+            let start_state = state.clone();
+            let (tokens, end_state) = self.highlight_line(line, state);
+            for token in &tokens {
+                let Some(rule) = self
+                    .fold_rules
+                    .iter()
+                    .chain(&self.comment_fold)
+                    .find(|rule| rule.kind == token.kind)
+                else {
+                    continue;
+                };
+                let word = &line[token.range.clone()];
+                let listed = |markers: &[String]| {
+                    markers.iter().any(|marker| {
+                        if self.case_sensitive {
+                            marker == word
+                        } else {
+                            marker.eq_ignore_ascii_case(word)
+                        }
+                    })
+                };
+                if listed(&rule.markers.open) {
+                    open.push(line_no);
+                } else if listed(&rule.markers.middle) {
+                    if let Some(start) = open.last_mut() {
+                        if line_no > *start {
+                            regions.push((*start, line_no - 1));
+                        }
+                        *start = line_no;
+                    }
+                } else if listed(&rule.markers.close) {
+                    if let Some(start) = open.pop().filter(|start| line_no > *start) {
+                        regions.push((start, line_no));
+                    }
+                }
+            }
+
+            if self.allow_fold_of_comments {
+                let in_block = |state: &LineState| *state == LineState::BlockComment;
+                if !in_block(&start_state) && in_block(&end_state) {
+                    block_start = Some(line_no);
+                } else if in_block(&start_state) && !in_block(&end_state) {
+                    if let Some(start) = block_start.take() {
+                        regions.push((start, line_no));
+                    }
+                }
+                let body = line.trim_start();
+                let comment_line = !in_block(&start_state)
+                    && self
+                        .line_comments
+                        .iter()
+                        .any(|marker| body.starts_with(marker.as_str()))
+                    && tokens
+                        .iter()
+                        .any(|token| token.range.start == line.len() - body.len());
+                if comment_line {
+                    let start = comment_run.map_or(line_no, |(start, _)| start);
+                    comment_run = Some((start, line_no));
+                } else if let Some((start, end)) = comment_run.take() {
+                    if end > start {
+                        regions.push((start, end));
+                    }
+                }
+            }
+            state = end_state;
+        }
+        if let Some((start, end)) = comment_run.filter(|(start, end)| end > start) {
+            regions.push((start, end));
+        }
+
+        if self.fold_compact {
+            for (_, end) in &mut regions {
+                while blank.get(*end + 1) == Some(&true) {
+                    *end += 1;
+                }
+            }
+        }
+        regions.sort_unstable();
+        regions.dedup();
+        regions
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Opener {
+    LineComment(usize),
+    BlockComment(usize),
+    String(usize, usize),
+}
+
 #[derive(Debug, Clone)]
 struct BlockComment {
     start: String,
@@ -185,6 +674,7 @@ struct StringDelimiter {
     start: String,
     end: String,
     escape: Option<char>,
+    kind: HighlightKind,
 }
 
 #[derive(Debug, Clone)]
@@ -193,6 +683,13 @@ struct PatternRule {
     kind: HighlightKind,
 }
 
+#[derive(Debug, Clone)]
+struct FoldRule {
+    regex: Regex,
+    kind: HighlightKind,
+    markers: FoldMarkers,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HighlightKind {
     Keyword,
@@ -218,6 +715,11 @@ pub enum HighlightError {
     RegexCompilation(String),
     #[error(transparent)]
     Udl(#[from] crate::udl::UdlError),
+    #[error(transparent)]
+    Grammar(#[from] GrammarError),
+    #[cfg(feature = "tree-sitter")]
+    #[error("tree-sitter: {0}")]
+    TreeSitter(String),
 }
 
 #[derive(Default)]
@@ -242,6 +744,25 @@ impl LanguageRegistry {
         registry
     }
 
+    /// Built-in languages, with the bundled tree-sitter grammars attached or added.
+    /// （內建語言，並附加或加入內建的 tree-sitter 語法。）
+    #[cfg(feature = "tree-sitter")]
+    pub fn with_tree_sitter_defaults() -> Self {
+        let mut registry = Self::with_defaults();
+        for language in TreeSitterLanguage::ALL {
+            let backend =
+                TreeSitterBackend::new(language).expect("bundled tree-sitter queries must compile");
+            let definition = match registry.languages.remove(language.id()) {
+                Some(existing) => existing.with_tree_sitter(backend),
+                None => LanguageDefinition::from_tree_sitter(backend),
+            };
+            registry
+                .register(definition)
+                .expect("tree-sitter language registration must succeed");
+        }
+        registry
+    }
+
     pub fn register(&mut self, language: LanguageDefinition) -> Result<(), HighlightError> {
         self.languages
             .insert(language.id.as_ref().to_string(), language);
@@ -253,10 +774,44 @@ impl LanguageRegistry {
         self.register(definition)
     }
 
+    /// Registers a TextMate or Sublime grammar under the id derived from its scope name.
+    /// （以 scope 名稱推導的識別子註冊 TextMate 或 Sublime 語法。）
+    pub fn register_grammar(&mut self, grammar: Grammar) -> LanguageId {
+        let language = LanguageDefinition::from_grammar(grammar);
+        let id = language.id.clone();
+        self.languages.insert(id.as_ref().to_string(), language);
+        id
+    }
+
+    /// Loads and registers a `.tmLanguage`, `.tmLanguage.json` or `.sublime-syntax` file.
+    /// （讀取並註冊 `.tmLanguage`、`.tmLanguage.json` 或 `.sublime-syntax` 檔案。）
+    pub fn register_grammar_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<LanguageId, HighlightError> {
+        let grammar = Grammar::from_path(path)?;
+        Ok(self.register_grammar(grammar))
+    }
+
     pub fn get(&self, id: impl AsRef<str>) -> Option<&LanguageDefinition> {
         self.languages.get(id.as_ref())
     }
 
+    /// Finds a language claiming the file extension (without the dot, case-insensitive).
+    /// When several do, the smallest id wins.
+    /// （尋找宣告該副檔名（不含點，不分大小寫）的語言；多個語言宣告時取識別子最小者。）
+    pub fn find_by_extension(&self, extension: &str) -> Option<&LanguageDefinition> {
+        self.languages
+            .values()
+            .filter(|language| {
+                language
+                    .extensions
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(extension))
+            })
+            .min_by(|left, right| left.id.as_ref().cmp(right.id.as_ref()))
+    }
+
     pub fn highlight(
         &self,
         id: impl AsRef<str>,
@@ -267,6 +822,19 @@ impl LanguageRegistry {
             .ok_or_else(|| HighlightError::LanguageNotRegistered(id.as_ref().to_string()))?;
         Ok(language.highlight(input))
     }
+
+    /// Creates an incremental highlighter for a registered language.
+    /// （為已註冊的語言建立增量高亮器。）
+    pub fn incremental(
+        &self,
+        id: impl AsRef<str>,
+        text: &str,
+    ) -> Result<IncrementalHighlighter, HighlightError> {
+        let language = self
+            .get(id.as_ref())
+            .ok_or_else(|| HighlightError::LanguageNotRegistered(id.as_ref().to_string()))?;
+        Ok(IncrementalHighlighter::new(language.clone(), text))
+    }
 }
 
 pub struct SyntaxHighlighter {
@@ -289,127 +857,41 @@ impl SyntaxHighlighter {
     ) -> Result<Vec<HighlightToken>, HighlightError> {
         self.registry.highlight(language_id, input)
     }
-}
 
-fn highlight_block_comments(
-    block: &BlockComment,
-    input: &str,
-    tokens: &mut Vec<HighlightToken>,
-    occupied: &mut [bool],
-) {
-    let mut index = 0;
-    while index < input.len() {
-        match input[index..].find(&block.start) {
-            Some(found) => {
-                let start = index + found;
-                let content_start = start + block.start.len();
-                let mut end = input.len();
-                if let Some(end_rel) = input[content_start..].find(&block.end) {
-                    end = content_start + end_rel + block.end.len();
-                }
-                mark_range(occupied, start..end);
-                tokens.push(HighlightToken {
-                    range: start..end,
-                    kind: HighlightKind::Comment,
-                });
-                index = end;
-            }
-            None => break,
-        }
+    pub fn incremental(
+        &self,
+        language_id: impl AsRef<str>,
+        text: &str,
+    ) -> Result<IncrementalHighlighter, HighlightError> {
+        self.registry.incremental(language_id, text)
     }
 }
 
-fn highlight_line_comments(
-    marker: &str,
-    input: &str,
-    tokens: &mut Vec<HighlightToken>,
-    occupied: &mut [bool],
-) {
-    if marker.is_empty() {
-        return;
-    }
-    let mut cursor = 0;
-    for line in input.split_inclusive('\n') {
-        if let Some(position) = line.find(marker) {
-            let start = cursor + position;
-            if !occupied.get(start).copied().unwrap_or(false) {
-                let end = cursor + line.len();
-                mark_range(occupied, start..end);
-                tokens.push(HighlightToken {
-                    range: start..end,
-                    kind: HighlightKind::Comment,
-                });
-            }
+fn find_string_end(delimiter: &StringDelimiter, line: &str, from: usize) -> Option<usize> {
+    let mut chars = line[from..].char_indices();
+    while let Some((rel, ch)) = chars.next() {
+        let at = from + rel;
+        if line[at..].starts_with(&delimiter.end) {
+            return Some(at + delimiter.end.len());
+        }
+        if Some(ch) == delimiter.escape {
+            chars.next();
         }
-        cursor += line.len();
     }
+    None
 }
 
-fn highlight_strings(
-    delimiter: &StringDelimiter,
-    input: &str,
+fn push_span(
     tokens: &mut Vec<HighlightToken>,
     occupied: &mut [bool],
+    range: Range<usize>,
+    kind: HighlightKind,
 ) {
-    if delimiter.start.is_empty() || delimiter.end.is_empty() {
+    if range.start >= range.end {
         return;
     }
-
-    let mut index = 0;
-    while index < input.len() {
-        if !input.is_char_boundary(index) {
-            index += 1;
-            continue;
-        }
-        match input[index..].find(&delimiter.start) {
-            Some(rel_start) => {
-                let start = index + rel_start;
-                if occupied.get(start).copied().unwrap_or(false) {
-                    index = start + delimiter.start.len();
-                    continue;
-                }
-
-                let mut cursor = start + delimiter.start.len();
-                let mut end = input.len();
-                while cursor < input.len() {
-                    if !input.is_char_boundary(cursor) {
-                        cursor += 1;
-                        continue;
-                    }
-                    if input[cursor..].starts_with(&delimiter.end) {
-                        if let Some(escape) = delimiter.escape {
-                            if cursor > start + delimiter.start.len() {
-                                if let Some(previous) = input[..cursor].chars().next_back() {
-                                    if previous == escape {
-                                        cursor += 1;
-                                        continue;
-                                    }
-                                }
-                            }
-                        }
-                        end = cursor + delimiter.end.len();
-                        break;
-                    }
-                    let step = input[cursor..]
-                        .chars()
-                        .next()
-                        .map(|ch| ch.len_utf8())
-                        .unwrap_or(1);
-                    cursor += step;
-                }
-                if !input.is_char_boundary(end) {
-                    end = input.len();
-                }
-                mark_range(occupied, start..end);
-                tokens.push(HighlightToken {
-                    range: start..end,
-                    kind: HighlightKind::String,
-                });
-                index = end;
-            }
-            None => break,
-        }
-    }
+    mark_range(occupied, range.clone());
+    tokens.push(HighlightToken { range, kind });
 }
 
 fn highlight_with_regex(
@@ -435,17 +917,71 @@ fn highlight_with_regex(
     }
 }
 
+/// Marks whitespace-separated words that equal one of `words` (UDL `Operators2`).
+/// （標示與 `words` 相符、以空白分隔的字詞。）
+fn highlight_separated(
+    words: &[String],
+    case_sensitive: bool,
+    input: &str,
+    tokens: &mut Vec<HighlightToken>,
+    occupied: &mut [bool],
+) {
+    if words.is_empty() {
+        return;
+    }
+    let mut word_start = None;
+    for (idx, ch) in input.char_indices().chain([(input.len(), ' ')]) {
+        if !ch.is_whitespace() {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+        let Some(start) = word_start.take() else {
+            continue;
+        };
+        let word = &input[start..idx];
+        let listed = words.iter().any(|candidate| {
+            if case_sensitive {
+                candidate == word
+            } else {
+                candidate.eq_ignore_ascii_case(word)
+            }
+        });
+        if listed && !occupied[start..idx].contains(&true) {
+            push_span(tokens, occupied, start..idx, HighlightKind::Operator);
+        }
+    }
+}
+
 fn mark_range(occupied: &mut [bool], range: Range<usize>) {
     let start = range.start.min(occupied.len());
     let end = range.end.min(occupied.len());
-    for index in start..end {
-        occupied[index] = true;
+    if start < end {
+        occupied[start..end].fill(true);
     }
 }
 
+fn build_keyword_rules(udl: &UdlDefinition) -> Result<Vec<PatternRule>, HighlightError> {
+    let mut rules = Vec::new();
+    for group in 1..=KEYWORD_GROUPS {
+        let prefix = udl.keyword_prefix[group - 1];
+        let Some(regex) =
+            build_keyword_regex(udl.keyword_group(group), udl.case_sensitive, prefix)?
+        else {
+            continue;
+        };
+        let kind = match group {
+            1 => HighlightKind::Keyword,
+            _ => HighlightKind::Custom(format!("keyword.group{group}")),
+        };
+        rules.push(PatternRule { regex, kind });
+    }
+    Ok(rules)
+}
+
 fn build_keyword_regex(
     keywords: &[String],
     case_sensitive: bool,
+    prefix: bool,
 ) -> Result<Option<Regex>, HighlightError> {
     if keywords.is_empty() {
         return Ok(None);
@@ -455,7 +991,8 @@ fn build_keyword_regex(
         .map(|keyword| regex::escape(keyword))
         .collect::<Vec<_>>()
         .join("|");
-    let pattern = format!(r"\b({pattern})\b");
+    let tail = if prefix { r"\w*" } else { r"\b" };
+    let pattern = format!(r"\b({pattern}){tail}");
     let mut builder = RegexBuilder::new(&pattern);
     builder.multi_line(true);
     if !case_sensitive {
@@ -466,6 +1003,38 @@ fn build_keyword_regex(
     })
 }
 
+fn build_fold_rule(
+    markers: &FoldMarkers,
+    whole_words: bool,
+    case_sensitive: bool,
+    scope: &str,
+) -> Result<Option<FoldRule>, HighlightError> {
+    if markers.is_empty() {
+        return Ok(None);
+    }
+    let mut alternatives: Vec<&String> = markers.all().collect();
+    alternatives.sort_by_key(|marker| std::cmp::Reverse(marker.len()));
+    let pattern = alternatives
+        .iter()
+        .map(|marker| regex::escape(marker))
+        .collect::<Vec<_>>()
+        .join("|");
+    let pattern = if whole_words {
+        format!(r"\b(?:{pattern})\b")
+    } else {
+        format!("(?:{pattern})")
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|err| HighlightError::RegexCompilation(format!("fold regex: {err}")))?;
+    Ok(Some(FoldRule {
+        regex,
+        kind: HighlightKind::Custom(scope.to_string()),
+        markers: markers.clone(),
+    }))
+}
+
 fn build_operator_regex(operators: &[String]) -> Result<Option<Regex>, HighlightError> {
     if operators.is_empty() {
         return Ok(None);
@@ -481,6 +1050,68 @@ fn build_operator_regex(operators: &[String]) -> Result<Option<Regex>, Highlight
         .map_err(|err| HighlightError::RegexCompilation(format!("operator regex: {err}")))
 }
 
+/// Builds the number regex from UDL prefixes, extras and suffixes.
+/// （依 UDL 的前綴、額外字元與後綴建立數字正則。）
+fn build_udl_number_regex(
+    numbers: &NumberRules,
+    separator: DecimalSeparator,
+    case_sensitive: bool,
+) -> Result<Regex, HighlightError> {
+    let alternation = |items: &mut dyn Iterator<Item = &String>| {
+        items
+            .map(|item| regex::escape(item))
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    let separator = match separator {
+        DecimalSeparator::Dot => r"\.",
+        DecimalSeparator::Comma => ",",
+        DecimalSeparator::Both => "[.,]",
+    };
+    let mut branches = Vec::new();
+    for (prefixes, extras) in [
+        (&numbers.prefix1, &numbers.extras1),
+        (&numbers.prefix2, &numbers.extras2),
+    ] {
+        if prefixes.is_empty() {
+            continue;
+        }
+        let prefix = alternation(&mut prefixes.iter());
+        let digits = match alternation(&mut extras.iter()) {
+            extras if extras.is_empty() => "[0-9]".to_string(),
+            extras => format!("[0-9]|{extras}"),
+        };
+        branches.push(format!("(?:{prefix})(?:{digits})+"));
+    }
+    branches.push(format!("[0-9]+(?:{separator}[0-9]+)?"));
+    let suffix = match alternation(&mut numbers.suffix1.iter().chain(&numbers.suffix2)) {
+        suffix if suffix.is_empty() => suffix,
+        suffix => format!("(?:{suffix})?"),
+    };
+    let pattern = format!("(?:{}){suffix}", branches.join("|"));
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|err| HighlightError::RegexCompilation(format!("number regex: {err}")))
+}
+
+fn split_alternatives(value: &str) -> Vec<String> {
+    value.split_whitespace().map(String::from).collect()
+}
+
+/// Pairs space-separated openers with closers in order; missing closers reuse the last one (or the opener).
+/// （依序配對以空白分隔的開頭與結尾；缺少的結尾沿用最後一個（或開頭本身）。）
+fn pair_alternatives(starts: &str, ends: Option<&str>) -> impl Iterator<Item = (String, String)> {
+    let ends = ends.map(split_alternatives).unwrap_or_default();
+    split_alternatives(starts)
+        .into_iter()
+        .enumerate()
+        .map(move |(idx, start)| {
+            let end = ends.get(idx).or(ends.last()).unwrap_or(&start).clone();
+            (start, end)
+        })
+}
+
 fn build_number_regex(custom: Option<&str>) -> Result<Regex, HighlightError> {
     let pattern = custom.unwrap_or(NUMBER_PATTERN);
     Regex::new(pattern)
@@ -524,6 +1155,7 @@ pub mod builtin {
             .map(String::from)
             .collect(),
             case_sensitive: true,
+            ..UdlDefinition::default()
         };
         LanguageDefinition::from_udl(udl).expect("built-in rust UDL should parse")
     }
@@ -551,6 +1183,7 @@ pub mod builtin {
                 "]".into(),
             ],
             case_sensitive: true,
+            ..UdlDefinition::default()
         };
         LanguageDefinition::from_udl(udl).expect("built-in json UDL should parse")
     }
@@ -567,6 +1200,7 @@ pub mod builtin {
             number_pattern: None,
             operators: Vec::new(),
             case_sensitive: true,
+            ..UdlDefinition::default()
         };
         LanguageDefinition::from_udl(udl).expect("plain text UDL should parse")
     }
@@ -621,6 +1255,7 @@ mod tests {
             number_pattern: None,
             operators: vec!["+".into()],
             case_sensitive: false,
+            ..UdlDefinition::default()
         };
         registry.register_udl(udl).unwrap();
         let tokens = registry
@@ -635,4 +1270,124 @@ mod tests {
             .iter()
             .any(|token| token.kind == HighlightKind::Comment));
     }
+
+    #[test]
+    fn udl_groups_numbers_and_folds_are_honored() {
+        let words = |list: &[&str]| list.iter().map(|word| word.to_string()).collect();
+        let mut keyword_prefix = [false; KEYWORD_GROUPS];
+        keyword_prefix[2] = true;
+        let udl = UdlDefinition {
+            name: "TeamScript".into(),
+            keywords: words(&["let"]),
+            keyword_groups: vec![
+                words(&["print"]),
+                words(&["get"]),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                words(&["TODO"]),
+            ],
+            keyword_prefix,
+            line_comment: Some("; #".into()),
+            line_comment_continue: Some("\\".into()),
+            line_comment_position: LineCommentPosition::AfterWhitespace,
+            block_comment: Some(("(*".into(), "*)".into())),
+            delimiters: vec![
+                Delimiter {
+                    start: "\"".into(),
+                    end: Some("\"".into()),
+                    escape: Some('\\'),
+                },
+                Delimiter {
+                    start: "'".into(),
+                    end: None,
+                    escape: None,
+                },
+                Delimiter {
+                    start: "[[".into(),
+                    end: Some("]]".into()),
+                    escape: None,
+                },
+            ],
+            numbers: NumberRules {
+                prefix1: words(&["0x"]),
+                extras1: words(&["A", "B", "C", "D", "E", "F"]),
+                suffix1: words(&["L"]),
+                suffix2: words(&["u"]),
+                ..NumberRules::default()
+            },
+            decimal_separator: DecimalSeparator::Both,
+            operators: words(&["+", "="]),
+            operators2: words(&["and"]),
+            folds_in_code1: FoldMarkers {
+                open: words(&["{"]),
+                middle: vec![],
+                close: words(&["}"]),
+            },
+            folds_in_code2: FoldMarkers {
+                open: words(&["if"]),
+                middle: words(&["else"]),
+                close: words(&["endif"]),
+            },
+            folds_in_comment: FoldMarkers {
+                open: words(&["region"]),
+                middle: vec![],
+                close: words(&["endregion"]),
+            },
+            allow_fold_of_comments: true,
+            case_sensitive: false,
+            ..UdlDefinition::default()
+        };
+        let language = LanguageDefinition::from_udl(udl).unwrap();
+        let source = "let total = 0x1FL + 3,5 and 7u
+    ; region setup
+if ready
+    print \"a\\\"b\" 'c' [[raw]]
+else
+    getValue TODO {
+    }
+endif
+    ; endregion
+(* block
+   comment *)
+x ; not a comment
+; one
+; two \\
+continued
+";
+        let tokens = language.highlight(source);
+        let kind_of = |needle: &str| {
+            tokens
+                .iter()
+                .find(|token| &source[token.range.clone()] == needle)
+                .map(|token| token.kind.clone())
+        };
+        let custom = |scope: &str| Some(HighlightKind::Custom(scope.to_string()));
+        assert_eq!(kind_of("let"), Some(HighlightKind::Keyword));
+        assert_eq!(kind_of("print"), custom("keyword.group2"));
+        assert_eq!(kind_of("getValue"), custom("keyword.group3"));
+        assert_eq!(kind_of("TODO"), custom("keyword.group8"));
+        assert_eq!(kind_of("0x1FL"), Some(HighlightKind::Number));
+        assert_eq!(kind_of("3,5"), Some(HighlightKind::Number));
+        assert_eq!(kind_of("7u"), Some(HighlightKind::Number));
+        assert_eq!(kind_of("and"), Some(HighlightKind::Operator));
+        assert_eq!(kind_of("\"a\\\"b\""), Some(HighlightKind::String));
+        assert_eq!(kind_of("'c'"), custom("string.delimiter2"));
+        assert_eq!(kind_of("[[raw]]"), custom("string.delimiter3"));
+        assert_eq!(kind_of("if"), custom("keyword.fold.code2"));
+        assert_eq!(kind_of("{"), custom("keyword.fold.code1"));
+        assert_eq!(kind_of("region"), custom("comment.fold"));
+        assert_eq!(kind_of("; "), Some(HighlightKind::Comment));
+        assert_eq!(kind_of("continued\n"), Some(HighlightKind::Comment));
+        let not_comment = source.find("x ;").unwrap();
+        assert!(tokens
+            .iter()
+            .all(|token| token.range.start != not_comment + 2));
+
+        assert_eq!(
+            language.fold_regions(source),
+            [(1, 8), (2, 3), (4, 7), (5, 6), (9, 10), (12, 13)]
+        );
+    }
 }
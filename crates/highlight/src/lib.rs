@@ -1,12 +1,24 @@
+mod grammar;
+mod incremental;
 mod language;
+mod sublime_syntax;
+#[cfg(feature = "tree-sitter")]
+mod syntax_tree;
 mod theme;
 mod udl;
 
+pub use grammar::{Grammar, GrammarError};
+pub use incremental::{IncrementalHighlighter, LineState};
 pub use language::{
     builtin, HighlightError, HighlightKind, HighlightToken, LanguageDefinition, LanguageId,
     LanguageRegistry, SyntaxHighlighter,
 };
+#[cfg(feature = "tree-sitter")]
+pub use syntax_tree::{SyntaxTree, TreeSitterBackend, TreeSitterLanguage};
 pub use theme::{
     parse_highlight_palette, Color, HighlightPalette, HighlightStyle, ThemeParseError,
 };
-pub use udl::{Delimiter, UdlDefinition, UdlError};
+pub use udl::{
+    DecimalSeparator, Delimiter, FoldMarkers, LineCommentPosition, NumberRules, UdlDefinition,
+    UdlError, UdlStyle, DELIMITER_GROUPS, KEYWORD_GROUPS,
+};
@@ -0,0 +1,276 @@
+//! `.sublime-syntax` loading.
+//! （`.sublime-syntax` 載入。）
+//!
+//! Sublime syntaxes are YAML; `serde_yaml` reads them into a
+//! `serde_json::Value` before the contexts are compiled. `{{variables}}` are
+//! expanded, `prototype` is prepended to contexts that allow it, and `embed`
+//! or cross-syntax includes are recorded as skipped.
+//! Sublime 語法為 YAML；以 `serde_yaml` 讀成 `serde_json::Value` 後再編譯
+//! context。`{{variables}}` 會展開，`prototype` 會加在允許的 context 前面，
+//! `embed` 與跨語法引用則記錄為略過。
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::grammar::{
+    string_list, ActionDraft, ContextDraft, Grammar, GrammarBuilder, GrammarError, Item,
+};
+
+pub(crate) fn parse(input: &str) -> Result<Grammar, GrammarError> {
+    let document: Value = serde_yaml::from_str(input)?;
+    let scope_name = document
+        .get("scope")
+        .and_then(Value::as_str)
+        .ok_or(GrammarError::Missing("scope"))?;
+    let name = document
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or(scope_name);
+    let contexts = document
+        .get("contexts")
+        .and_then(Value::as_object)
+        .ok_or(GrammarError::Missing("contexts"))?;
+
+    let mut variables = HashMap::new();
+    for (key, value) in document
+        .get("variables")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(value) = value.as_str() {
+            variables.insert(key.clone(), value.to_string());
+        }
+    }
+
+    let mut compiler = Compiler {
+        builder: GrammarBuilder::default(),
+        variables,
+    };
+    for (context, patterns) in contexts {
+        let draft = compiler.context(patterns);
+        compiler.builder.add_context(context.clone(), draft);
+    }
+    compiler.builder.finish(
+        name,
+        scope_name,
+        string_list(document.get("file_extensions")),
+    )
+}
+
+struct Compiler {
+    builder: GrammarBuilder,
+    variables: HashMap<String, String>,
+}
+
+impl Compiler {
+    fn context(&mut self, patterns: &Value) -> ContextDraft {
+        let mut draft = ContextDraft {
+            include_prototype: true,
+            ..ContextDraft::default()
+        };
+        for entry in patterns.as_array().into_iter().flatten() {
+            let text = |key: &str| entry.get(key).and_then(Value::as_str).map(str::to_string);
+            if let Some(scope) = text("meta_scope") {
+                draft.meta_scope = Some(scope);
+            } else if let Some(scope) = text("meta_content_scope") {
+                draft.meta_content_scope = Some(scope);
+            } else if let Some(include) = entry.get("meta_include_prototype") {
+                draft.include_prototype = include.as_bool().unwrap_or(true);
+            } else if let Some(include) = text("include") {
+                if include.starts_with("scope:") || include.starts_with("Packages/") {
+                    self.builder.skip(format!("include {include}"));
+                } else {
+                    draft.items.push(Item::Include(include));
+                }
+            } else if let Some(pattern) = text("match") {
+                if let Some(item) = self.rule(entry, &pattern) {
+                    draft.items.push(item);
+                }
+            }
+        }
+        draft
+    }
+
+    fn rule(&mut self, entry: &Value, pattern: &str) -> Option<Item> {
+        if entry.get("embed").is_some() || entry.get("branch").is_some() {
+            self.builder.skip(pattern);
+            return None;
+        }
+        let action = if let Some(targets) = entry.get("push") {
+            ActionDraft::Push(self.targets(targets))
+        } else if let Some(targets) = entry.get("set") {
+            ActionDraft::Set(self.targets(targets))
+        } else {
+            match entry.get("pop") {
+                Some(Value::Bool(true)) => ActionDraft::Pop(1),
+                Some(Value::Number(count)) => {
+                    ActionDraft::Pop(count.as_u64().unwrap_or(1) as usize)
+                }
+                _ => ActionDraft::None,
+            }
+        };
+        let captures = entry
+            .get("captures")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(group, scope)| Some((group.parse().ok()?, scope.as_str()?.to_string())))
+            .collect();
+        let scope = entry
+            .get("scope")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let pattern = self.expand(pattern);
+        self.builder.add_rule(&pattern, scope, captures, action)
+    }
+
+    /// Context names for `push`/`set`; anonymous contexts are compiled under generated names.
+    /// （`push`/`set` 的 context 名稱；匿名 context 會以產生的名稱編譯。）
+    fn targets(&mut self, value: &Value) -> Vec<String> {
+        match value {
+            Value::String(name) => vec![name.clone()],
+            Value::Array(items) if items.iter().all(Value::is_object) => {
+                vec![self.anonymous(value)]
+            }
+            Value::Array(items) => items
+                .iter()
+                .flat_map(|item| match item {
+                    Value::String(name) => vec![name.clone()],
+                    other => vec![self.anonymous(other)],
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn anonymous(&mut self, patterns: &Value) -> String {
+        let name = self.builder.anonymous_name();
+        let draft = self.context(patterns);
+        self.builder.add_context(name.clone(), draft);
+        name
+    }
+
+    fn expand(&self, pattern: &str) -> String {
+        let mut expanded = pattern.to_string();
+        // 變數可引用其他變數；限制展開次數以避免循環。 / Variables may reference each other; bound the passes to stop cycles.
+        for _ in 0..64 {
+            let Some(start) = expanded.find("{{") else {
+                break;
+            };
+            let Some(length) = expanded[start..].find("}}") else {
+                break;
+            };
+            let name = expanded[start + 2..start + length].trim().to_string();
+            let value = self.variables.get(&name).cloned().unwrap_or_default();
+            expanded.replace_range(start..start + length + 2, &value);
+        }
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::{HighlightKind, LanguageRegistry};
+
+    const SYNTAX: &str = r#"%YAML 1.2
+---
+# A tiny language.
+name: Mini Sublime
+file_extensions: [msub, 'ms']
+scope: source.msub
+variables:
+  ident: '[A-Za-z_]\w*'
+  call: '{{ident}}(?:\s*)\('
+
+contexts:
+  prototype:
+    - match: '#.*$'
+      scope: comment.line.msub
+  main:
+    - match: \b(fn)\s+({{ident}})
+      captures:
+        1: storage.type.function.msub
+        2: entity.name.function.msub
+    - match: '"'
+      scope: punctuation.definition.string.begin.msub
+      push: string
+    - match: '{{call}}'
+      scope: variable.function.msub
+    - match: (?=x)
+      embed: scope:source.other
+      escape: y
+    - match: '\['
+      push:
+        - meta_scope: meta.list.msub
+        - match: '\]'
+          pop: true
+        - include: main
+  string:
+    - meta_include_prototype: false
+    - meta_scope: string.quoted.double.msub
+    - match: |-
+        (?x) \\ .   # an escape
+      scope: constant.character.escape.msub
+    - match: '"'
+      pop: true
+"#;
+
+    #[test]
+    fn sublime_syntax_compiles_contexts_and_variables() {
+        let grammar = Grammar::from_sublime_syntax(SYNTAX).unwrap();
+        assert_eq!(grammar.name, "Mini Sublime");
+        assert_eq!(grammar.language_id(), "msub");
+        assert_eq!(grammar.file_extensions, ["msub", "ms"]);
+        assert_eq!(grammar.skipped_patterns(), ["(?=x)"]);
+
+        let mut registry = LanguageRegistry::new();
+        registry.register_grammar(grammar);
+        let text = "fn go # note\n\"a\\n# b\" [call(\nx] \"";
+        let tokens = registry.highlight("msub", text).unwrap();
+        let scopes: Vec<(&str, &str)> = tokens
+            .iter()
+            .map(|token| match &token.kind {
+                HighlightKind::Custom(scope) => (&text[token.range.clone()], scope.as_str()),
+                other => panic!("unexpected kind {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            scopes,
+            [
+                ("fn", "storage.type.function.msub"),
+                ("go", "entity.name.function.msub"),
+                ("# note", "comment.line.msub"),
+                ("\"", "punctuation.definition.string.begin.msub"),
+                ("a", "string.quoted.double.msub"),
+                ("\\n", "constant.character.escape.msub"),
+                ("# b\"", "string.quoted.double.msub"),
+                ("[", "meta.list.msub"),
+                ("call(", "variable.function.msub"),
+                ("\n", "meta.list.msub"),
+                ("x]", "meta.list.msub"),
+                ("\"", "punctuation.definition.string.begin.msub"),
+            ]
+        );
+        let mut highlighter = registry.incremental("msub", text).unwrap();
+        assert_eq!(highlighter.highlight_all(text), tokens);
+        assert!(matches!(
+            highlighter.line_state(2),
+            Some(crate::LineState::Grammar(stack)) if stack.len() == 1
+        ));
+    }
+
+    #[test]
+    fn malformed_or_incomplete_syntaxes_are_rejected() {
+        assert!(matches!(
+            Grammar::from_sublime_syntax("scope: a\n   b: 2\n"),
+            Err(GrammarError::Yaml(_))
+        ));
+        assert!(matches!(
+            Grammar::from_sublime_syntax("name: x\ncontexts:\n  main: []\n"),
+            Err(GrammarError::Missing("scope"))
+        ));
+    }
+}
@@ -0,0 +1,507 @@
+//! Tree-sitter backend (cargo feature `tree-sitter`).
+//! （tree-sitter 後端，需啟用 cargo 功能 `tree-sitter`。）
+//!
+//! Parsing the whole document into a syntax tree gets constructs right that
+//! the line-based regex rules cannot: nested generics, raw strings and
+//! signatures spread over several lines. From one tree the backend derives
+//! highlight tokens (the grammar's `highlights.scm`), fold regions (every
+//! multi-line node) and function-list entries (the grammar's `tags.scm`).
+//! 將整份文件解析成語法樹，可正確處理逐行正則規則無法處理的結構：巢狀泛型、
+//! 原始字串與跨多行的函式簽章。後端由同一棵樹產生高亮 token（語法的
+//! `highlights.scm`）、摺疊區段（所有跨行節點）與函式清單條目（語法的
+//! `tags.scm`）。
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use rustnotepad_core::FoldProvider;
+use rustnotepad_function_list::{FunctionEntry, FunctionKind, FunctionParser, TextRange};
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Language, Node, Parser, Query, QueryCursor, Tree};
+
+use crate::language::{HighlightError, HighlightKind, HighlightToken};
+
+/// Grammars bundled with the tree-sitter backend.
+/// （tree-sitter 後端內建的語法。）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TreeSitterLanguage {
+    Rust,
+    Python,
+    C,
+    Json,
+}
+
+impl TreeSitterLanguage {
+    pub const ALL: [TreeSitterLanguage; 4] = [
+        TreeSitterLanguage::Rust,
+        TreeSitterLanguage::Python,
+        TreeSitterLanguage::C,
+        TreeSitterLanguage::Json,
+    ];
+
+    /// Language identifier used by the registries (`rust`, `python`, `c`, `json`).
+    /// （註冊中心使用的語言識別子。）
+    pub fn id(self) -> &'static str {
+        match self {
+            TreeSitterLanguage::Rust => "rust",
+            TreeSitterLanguage::Python => "python",
+            TreeSitterLanguage::C => "c",
+            TreeSitterLanguage::Json => "json",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|language| language.id() == id)
+    }
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            TreeSitterLanguage::Rust => "Rust",
+            TreeSitterLanguage::Python => "Python",
+            TreeSitterLanguage::C => "C",
+            TreeSitterLanguage::Json => "JSON",
+        }
+    }
+
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            TreeSitterLanguage::Rust => &["rs"],
+            TreeSitterLanguage::Python => &["py", "pyw"],
+            TreeSitterLanguage::C => &["c", "h"],
+            TreeSitterLanguage::Json => &["json"],
+        }
+    }
+
+    fn grammar(self) -> (Language, &'static str, Option<&'static str>) {
+        match self {
+            TreeSitterLanguage::Rust => (
+                tree_sitter_rust::LANGUAGE.into(),
+                tree_sitter_rust::HIGHLIGHTS_QUERY,
+                Some(tree_sitter_rust::TAGS_QUERY),
+            ),
+            TreeSitterLanguage::Python => (
+                tree_sitter_python::LANGUAGE.into(),
+                tree_sitter_python::HIGHLIGHTS_QUERY,
+                Some(tree_sitter_python::TAGS_QUERY),
+            ),
+            TreeSitterLanguage::C => (
+                tree_sitter_c::LANGUAGE.into(),
+                tree_sitter_c::HIGHLIGHT_QUERY,
+                Some(tree_sitter_c::TAGS_QUERY),
+            ),
+            TreeSitterLanguage::Json => (
+                tree_sitter_json::LANGUAGE.into(),
+                tree_sitter_json::HIGHLIGHTS_QUERY,
+                None,
+            ),
+        }
+    }
+}
+
+/// A parsed document.
+/// （解析完成的文件。）
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    tree: Tree,
+}
+
+impl SyntaxTree {
+    /// Whether the parser had to recover from syntax errors.
+    /// （解析器是否曾從語法錯誤中復原。）
+    pub fn has_errors(&self) -> bool {
+        self.tree.root_node().has_error()
+    }
+
+    /// Records an edit so the next [`TreeSitterBackend::reparse`] reuses the untouched subtrees.
+    /// （記錄編輯，讓下一次 [`TreeSitterBackend::reparse`] 重用未受影響的子樹。）
+    pub(crate) fn edit(&mut self, edit: &InputEdit) {
+        self.tree.edit(edit);
+    }
+}
+
+/// Compiled grammar and queries for one bundled language.
+/// （單一內建語言的已編譯語法與查詢。）
+pub struct TreeSitterBackend {
+    language: TreeSitterLanguage,
+    grammar: Language,
+    highlights: Query,
+    tags: Option<Query>,
+}
+
+impl std::fmt::Debug for TreeSitterBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TreeSitterBackend")
+            .field("language", &self.language)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TreeSitterBackend {
+    pub fn new(language: TreeSitterLanguage) -> Result<Self, HighlightError> {
+        let (grammar, highlights, tags) = language.grammar();
+        let query = |source: &str| {
+            Query::new(&grammar, source)
+                .map_err(|err| HighlightError::TreeSitter(format!("{}: {err}", language.id())))
+        };
+        let highlights = query(highlights)?;
+        let tags = tags.map(query).transpose()?;
+        Ok(Self {
+            language,
+            grammar,
+            highlights,
+            tags,
+        })
+    }
+
+    pub fn language(&self) -> TreeSitterLanguage {
+        self.language
+    }
+
+    pub fn parse(&self, source: &str) -> Result<SyntaxTree, HighlightError> {
+        self.parse_with(source, None)
+    }
+
+    /// Parses `source` again after the edits recorded on `old`, reusing its unchanged nodes.
+    /// （於 `old` 記錄的編輯之後重新解析 `source`，重用未變動的節點。）
+    pub fn reparse(&self, source: &str, old: &SyntaxTree) -> Result<SyntaxTree, HighlightError> {
+        self.parse_with(source, Some(&old.tree))
+    }
+
+    fn parse_with(&self, source: &str, old: Option<&Tree>) -> Result<SyntaxTree, HighlightError> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&self.grammar)
+            .map_err(|err| HighlightError::TreeSitter(err.to_string()))?;
+        let tree = parser
+            .parse(source, old)
+            .ok_or_else(|| HighlightError::TreeSitter("parsing was cancelled".into()))?;
+        Ok(SyntaxTree { tree })
+    }
+
+    /// Highlight tokens from the grammar's highlight query.
+    /// （依語法的高亮查詢產生 token。）
+    ///
+    /// Where captures nest, the innermost one wins (an escape inside a string stays an escape);
+    /// among captures of the same node, the first pattern in the query wins.
+    /// （擷取巢狀時以最內層為準（字串中的跳脫字元仍為跳脫字元）；同一節點的多個擷取以查詢中較前的樣式為準。）
+    pub fn highlight(&self, tree: &SyntaxTree, source: &str) -> Vec<HighlightToken> {
+        self.highlight_range(tree, source, 0..source.len())
+    }
+
+    /// Highlight tokens inside a byte range (e.g. the viewport), clipped to it.
+    /// （取得位元組範圍（例如可視區域）內的高亮 token，並裁切至該範圍。）
+    pub fn highlight_range(
+        &self,
+        tree: &SyntaxTree,
+        source: &str,
+        range: Range<usize>,
+    ) -> Vec<HighlightToken> {
+        let mut found: Vec<(Range<usize>, u32)> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(range.clone());
+        let mut captures =
+            cursor.captures(&self.highlights, tree.tree.root_node(), source.as_bytes());
+        while let Some((found_match, index)) = captures.next() {
+            let capture = found_match.captures[*index];
+            let node = capture.node.byte_range();
+            if node.start < range.end && range.start < node.end {
+                found.push((node, capture.index));
+            }
+        }
+        // 外層在前；同範圍保留先出現者。 / Outer captures first; for equal ranges the earliest one is kept.
+        found.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+        found.dedup_by(|later, earlier| later.0 == earlier.0);
+
+        let names = self.highlights.capture_names();
+        let mut tokens = Vec::new();
+        let mut emit = |span: Range<usize>, index: u32| {
+            let span = span.start.max(range.start)..span.end.min(range.end);
+            if !span.is_empty() {
+                tokens.push(HighlightToken {
+                    range: span,
+                    kind: capture_kind(names[index as usize]),
+                });
+            }
+        };
+        // 節點範圍不會部分重疊，因此以堆疊掃描：每段文字歸給包含它的最內層擷取。 /
+        // Node ranges never partially overlap, so a stack sweep gives every span to the innermost capture holding it.
+        let mut stack: Vec<(Range<usize>, u32)> = Vec::new();
+        let mut position = range.start;
+        for (span, index) in found {
+            while let Some((outer, outer_index)) = stack.last().cloned() {
+                if outer.end > span.start {
+                    break;
+                }
+                emit(position.max(outer.start)..outer.end, outer_index);
+                position = position.max(outer.end);
+                stack.pop();
+            }
+            if let Some((outer, outer_index)) = stack.last() {
+                emit(position.max(outer.start)..span.start, *outer_index);
+            }
+            position = span.start;
+            stack.push((span, index));
+        }
+        while let Some((outer, outer_index)) = stack.pop() {
+            emit(position.max(outer.start)..outer.end, outer_index);
+            position = position.max(outer.end);
+        }
+        tokens
+    }
+
+    /// Foldable line ranges `(start_line, end_line)`, one per start line, from every multi-line node.
+    /// （由所有跨行節點產生的可摺疊行範圍 `(起始行, 結束行)`，每個起始行一筆。）
+    pub fn fold_regions(&self, tree: &SyntaxTree) -> Vec<(usize, usize)> {
+        let mut regions: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut cursor = tree.tree.walk();
+        loop {
+            let node = cursor.node();
+            if let Some((start, end)) = node_lines(node) {
+                let entry = regions.entry(start).or_insert(end);
+                *entry = (*entry).max(end);
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return regions.into_iter().collect();
+                }
+            }
+        }
+    }
+
+    /// Function-list entries from the grammar's tags query.
+    /// （依語法的 tags 查詢產生函式清單條目。）
+    pub fn functions(&self, tree: &SyntaxTree, source: &str) -> Vec<FunctionEntry> {
+        let Some(tags) = &self.tags else {
+            return Vec::new();
+        };
+        let names = tags.capture_names();
+        let mut entries: Vec<FunctionEntry> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(tags, tree.tree.root_node(), source.as_bytes());
+        while let Some(found) = matches.next() {
+            let mut name = None;
+            let mut definition = None;
+            for capture in found.captures {
+                let capture_name = names[capture.index as usize];
+                if capture_name == "name" {
+                    name = capture.node.utf8_text(source.as_bytes()).ok();
+                } else if let Some(kind) = tag_kind(capture_name, capture.node.kind()) {
+                    definition = Some((kind, capture.node.byte_range()));
+                }
+            }
+            if let (Some(name), Some((kind, range))) = (name, definition) {
+                entries.push(FunctionEntry::new(
+                    name,
+                    kind,
+                    TextRange::new(range.start, range.end),
+                ));
+            }
+        }
+        entries.sort_by_key(|entry| entry.range.start);
+        entries.dedup_by(|a, b| a.range.start == b.range.start && a.name == b.name);
+        entries
+    }
+}
+
+impl FunctionParser for TreeSitterBackend {
+    fn parse(&self, source: &str) -> Vec<FunctionEntry> {
+        TreeSitterBackend::parse(self, source)
+            .map(|tree| self.functions(&tree, source))
+            .unwrap_or_default()
+    }
+}
+
+impl FoldProvider for TreeSitterBackend {
+    fn fold_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        self.parse(text)
+            .map(|tree| self.fold_regions(&tree))
+            .unwrap_or_default()
+    }
+}
+
+fn node_lines(node: Node<'_>) -> Option<(usize, usize)> {
+    if !node.is_named() || node.parent().is_none() {
+        return None;
+    }
+    let start = node.start_position().row;
+    let end_position = node.end_position();
+    // 以換行結束的節點不包含下一行。 / A node ending right after a newline does not own the next line.
+    let end = if end_position.column == 0 {
+        end_position.row.saturating_sub(1)
+    } else {
+        end_position.row
+    };
+    (end > start).then_some((start, end))
+}
+
+fn capture_kind(name: &str) -> HighlightKind {
+    let category = name.split('.').next().unwrap_or(name);
+    match category {
+        "keyword" => HighlightKind::Keyword,
+        "comment" => HighlightKind::Comment,
+        "string" | "escape" => HighlightKind::String,
+        "number" => HighlightKind::Number,
+        "operator" => HighlightKind::Operator,
+        "variable" => HighlightKind::Identifier,
+        _ => HighlightKind::Custom(name.to_string()),
+    }
+}
+
+fn tag_kind(capture: &str, node_kind: &str) -> Option<FunctionKind> {
+    let kind = match capture.strip_prefix("definition.")? {
+        "function" => FunctionKind::Function,
+        "method" => FunctionKind::Method,
+        "class" | "type" if node_kind.starts_with("struct") => FunctionKind::Struct,
+        "class" | "type" if node_kind.starts_with("enum") => FunctionKind::Enum,
+        "class" => FunctionKind::Class,
+        other => FunctionKind::Custom(other.to_string()),
+    };
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_tree_handles_generics_raw_strings_and_multiline_signatures() {
+        let backend = TreeSitterBackend::new(TreeSitterLanguage::Rust).unwrap();
+        let source = r###"struct Cache<K, V: Into<Vec<Option<u8>>>> {
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V: Into<Vec<Option<u8>>>> Cache<K, V> {
+    fn lookup(
+        &self,
+        key: &K,
+    ) -> Option<&V> {
+        let _doc = r#"fn fake() { "quoted" }"#;
+        None
+    }
+}
+
+enum Mode { A, B }
+"###;
+        let tree = backend.parse(source).unwrap();
+        assert!(!tree.has_errors());
+
+        let entries = backend.functions(&tree, source);
+        let listed: Vec<(&str, &FunctionKind)> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), &entry.kind))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("Cache", &FunctionKind::Struct),
+                ("lookup", &FunctionKind::Method),
+                ("Mode", &FunctionKind::Enum),
+            ]
+        );
+        assert!(source[entries[1].range.start..entries[1].range.end].ends_with('}'));
+
+        let tokens = backend.highlight(&tree, source);
+        let raw_start = source.find("r#\"").unwrap();
+        let raw = tokens
+            .iter()
+            .find(|token| token.range.start == raw_start)
+            .unwrap();
+        assert_eq!(raw.kind, HighlightKind::String);
+        assert_eq!(
+            &source[raw.range.clone()],
+            r##"r#"fn fake() { "quoted" }"#"##
+        );
+        assert!(tokens
+            .iter()
+            .any(|token| token.kind == HighlightKind::Keyword
+                && &source[token.range.clone()] == "impl"));
+
+        assert_eq!(
+            backend.fold_regions(&tree),
+            [(0, 2), (4, 12), (5, 11), (8, 11)]
+        );
+        let mut folds = rustnotepad_core::FoldTree::default();
+        assert_eq!(folds.refresh_with(&backend, source), 4);
+        let spans: Vec<(usize, usize)> = folds
+            .regions()
+            .iter()
+            .map(|region| (region.start_line, region.end_line))
+            .collect();
+        assert_eq!(spans, backend.fold_regions(&tree));
+    }
+
+    #[test]
+    fn nested_captures_resolve_to_the_innermost_one() {
+        let python = TreeSitterBackend::new(TreeSitterLanguage::Python).unwrap();
+        let source = "s = f\"a{x}b\"\n";
+        let tree = python.parse(source).unwrap();
+        let tokens: Vec<(&str, HighlightKind)> = python
+            .highlight(&tree, source)
+            .into_iter()
+            .map(|token| (&source[token.range], token.kind))
+            .collect();
+        let punctuation = HighlightKind::Custom("punctuation.special".into());
+        assert_eq!(
+            tokens[2..],
+            [
+                ("f\"a", HighlightKind::String),
+                ("{", punctuation.clone()),
+                ("x", HighlightKind::Identifier),
+                ("}", punctuation),
+                ("b\"", HighlightKind::String),
+            ]
+        );
+
+        let start = source.find('x').unwrap();
+        let viewport = python.highlight_range(&tree, source, start..source.len());
+        assert_eq!(viewport[0].range, start..start + 1);
+        assert_eq!(viewport[0].kind, HighlightKind::Identifier);
+    }
+
+    #[test]
+    fn bundled_grammars_parse_and_feed_the_registry() {
+        for language in TreeSitterLanguage::ALL {
+            let backend = TreeSitterBackend::new(language).unwrap();
+            assert_eq!(TreeSitterLanguage::from_id(language.id()), Some(language));
+            assert!(backend.parse("").is_ok());
+        }
+
+        let python = TreeSitterBackend::new(TreeSitterLanguage::Python).unwrap();
+        let source = "class Shape:\n    def area(self,\n             scale):\n        return 0\n";
+        let entries = FunctionParser::parse(&python, source);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.kind.clone()))
+                .collect::<Vec<_>>(),
+            [
+                ("Shape", FunctionKind::Class),
+                ("area", FunctionKind::Function)
+            ]
+        );
+
+        let c = TreeSitterBackend::new(TreeSitterLanguage::C).unwrap();
+        let source = "struct point { int x; };\nint\nmain(void)\n{\n    return 0;\n}\n";
+        let tree = c.parse(source).unwrap();
+        let names: Vec<String> = c
+            .functions(&tree, source)
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["point", "main"]);
+        assert_eq!(c.fold_regions(&tree), [(1, 5), (3, 5)]);
+
+        let registry = crate::LanguageRegistry::with_tree_sitter_defaults();
+        let source = "{\"key\": [1, true]}";
+        let tokens = registry.highlight("json", source).unwrap();
+        assert_eq!(tokens[0].kind, HighlightKind::String);
+        assert!(tokens
+            .iter()
+            .any(|token| token.kind == HighlightKind::Number));
+        assert!(registry.get("python").is_some());
+        assert!(registry.get("c").unwrap().tree_sitter().is_some());
+    }
+}
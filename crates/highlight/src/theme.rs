@@ -39,7 +39,31 @@ impl HighlightPalette {
             HighlightKind::Number => self.number.as_ref(),
             HighlightKind::Operator => self.operator.as_ref(),
             HighlightKind::Identifier => self.identifier.as_ref(),
-            HighlightKind::Custom(name) => self.custom.get(name),
+            HighlightKind::Custom(name) => self.style_for_scope(name),
+        }
+    }
+
+    /// Looks up a TextMate scope, trying ever shorter prefixes (`keyword.control.rust` → `keyword.control` → `keyword`).
+    /// （查詢 TextMate scope，逐步縮短前綴，例如 `keyword.control.rust` → `keyword.control` → `keyword`。）
+    ///
+    /// Each prefix checks custom entries first, then the standard category it corresponds to.
+    /// （每個前綴先查自訂項目，再查對應的標準分類。）
+    pub fn style_for_scope(&self, scope: &str) -> Option<&HighlightStyle> {
+        let mut prefix = scope;
+        loop {
+            let standard = match prefix {
+                "comment" => self.comment.as_ref(),
+                "string" => self.string.as_ref(),
+                "constant.numeric" => self.number.as_ref(),
+                "keyword.operator" => self.operator.as_ref(),
+                "keyword" | "storage" => self.keyword.as_ref(),
+                "variable" | "entity.name" => self.identifier.as_ref(),
+                _ => None,
+            };
+            if let Some(style) = self.custom.get(prefix).or(standard) {
+                return Some(style);
+            }
+            prefix = prefix.rsplit_once('.')?.0;
         }
     }
 
@@ -1,6 +1,20 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::theme::{Color, HighlightPalette, HighlightStyle};
+
+/// Number of keyword groups (`Keywords1`–`Keywords8`) in a Notepad++ UDL.
+/// （Notepad++ UDL 的關鍵字群組數量。）
+pub const KEYWORD_GROUPS: usize = 8;
+
+/// Number of delimiter styles (`DELIMITERS1`–`DELIMITERS8`) in a Notepad++ UDL.
+/// （Notepad++ UDL 的分隔符樣式數量。）
+pub const DELIMITER_GROUPS: usize = 8;
+
+const UDL_VERSION: &str = "2.1";
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UdlDefinition {
     pub name: String,
@@ -8,18 +22,74 @@ pub struct UdlDefinition {
     pub identifier: Option<String>,
     #[serde(default)]
     pub extensions: Vec<String>,
+    /// Keyword group 1 (`Keywords1`).
+    /// （關鍵字群組 1。）
     #[serde(default)]
     pub keywords: Vec<String>,
+    /// Keyword groups 2–8 (`Keywords2`…`Keywords8`), in order.
+    /// （關鍵字群組 2–8，依序排列。）
+    #[serde(default)]
+    pub keyword_groups: Vec<Vec<String>>,
+    /// Groups whose words also match as prefixes (`Prefix` settings), indexed from group 1.
+    /// （其字詞亦以前綴方式比對的群組，索引自群組 1 起算。）
+    #[serde(default)]
+    pub keyword_prefix: [bool; KEYWORD_GROUPS],
+    /// Line comment openers; several alternatives are separated by spaces.
+    /// （行註解開頭；多個候選以空白分隔。）
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub line_comment: Option<String>,
+    /// Marker that continues a line comment onto the next line when it ends the line.
+    /// （位於行尾時讓行註解延續到下一行的符號。）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_comment_continue: Option<String>,
+    /// Marker that ends a line comment before the end of the line.
+    /// （在行尾之前結束行註解的符號。）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_comment_close: Option<String>,
+    #[serde(default)]
+    pub line_comment_position: LineCommentPosition,
+    /// Block comment openers and closers; alternatives are separated by spaces and paired in order.
+    /// （區塊註解的開頭與結尾；候選以空白分隔並依序配對。）
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub block_comment: Option<(String, String)>,
+    /// Delimiters by group; the index selects the `DELIMITERSn` style.
+    /// （依群組排列的分隔符；索引決定使用的 `DELIMITERSn` 樣式。）
     #[serde(default)]
     pub delimiters: Vec<Delimiter>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub number_pattern: Option<String>,
     #[serde(default)]
+    pub numbers: NumberRules,
+    #[serde(default)]
+    pub decimal_separator: DecimalSeparator,
+    /// Operators matched anywhere (`Operators1`).
+    /// （任意位置皆比對的運算子。）
+    #[serde(default)]
     pub operators: Vec<String>,
+    /// Operators that must be surrounded by whitespace (`Operators2`).
+    /// （前後須為空白的運算子。）
+    #[serde(default)]
+    pub operators2: Vec<String>,
+    /// Fold markers that need no separators, such as `{` and `}`.
+    /// （不需分隔的摺疊符號，例如 `{` 與 `}`。）
+    #[serde(default)]
+    pub folds_in_code1: FoldMarkers,
+    /// Fold keywords matched as whole words, such as `begin` and `end`.
+    /// （以完整字詞比對的摺疊關鍵字，例如 `begin` 與 `end`。）
+    #[serde(default)]
+    pub folds_in_code2: FoldMarkers,
+    /// Fold markers recognised inside comments.
+    /// （於註解內辨識的摺疊符號。）
+    #[serde(default)]
+    pub folds_in_comment: FoldMarkers,
+    #[serde(default)]
+    pub allow_fold_of_comments: bool,
+    /// Extends folds over the blank lines that follow them.
+    /// （讓摺疊區段涵蓋其後的空白行。）
+    #[serde(default)]
+    pub fold_compact: bool,
+    #[serde(default)]
+    pub styles: Vec<UdlStyle>,
     #[serde(default = "default_case_sensitive")]
     pub case_sensitive: bool,
 }
@@ -28,15 +98,203 @@ fn default_case_sensitive() -> bool {
     true
 }
 
+impl Default for UdlDefinition {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            identifier: None,
+            extensions: Vec::new(),
+            keywords: Vec::new(),
+            keyword_groups: Vec::new(),
+            keyword_prefix: [false; KEYWORD_GROUPS],
+            line_comment: None,
+            line_comment_continue: None,
+            line_comment_close: None,
+            line_comment_position: LineCommentPosition::default(),
+            block_comment: None,
+            delimiters: Vec::new(),
+            number_pattern: None,
+            numbers: NumberRules::default(),
+            decimal_separator: DecimalSeparator::default(),
+            operators: Vec::new(),
+            operators2: Vec::new(),
+            folds_in_code1: FoldMarkers::default(),
+            folds_in_code2: FoldMarkers::default(),
+            folds_in_comment: FoldMarkers::default(),
+            allow_fold_of_comments: false,
+            fold_compact: false,
+            styles: Vec::new(),
+            case_sensitive: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Delimiter {
+    /// Openers; alternatives are separated by spaces.
+    /// （開頭符號；候選以空白分隔。）
     pub start: String,
+    /// Closers paired with the openers in order; `None` closes with the opener.
+    /// （依序與開頭配對的結尾符號；`None` 表示以開頭符號結束。）
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub end: Option<String>,
     #[serde(with = "serde_char_option")]
     pub escape: Option<char>,
 }
 
+/// Where a line comment may start (`forcePureLC`).
+/// （行註解可開始的位置。）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineCommentPosition {
+    #[default]
+    Anywhere,
+    /// Only in the first column.
+    /// （僅限第一欄。）
+    LineStart,
+    /// At the start of the line or after leading whitespace.
+    /// （行首或前導空白之後。）
+    AfterWhitespace,
+}
+
+/// Decimal separator accepted inside numbers (`decimalSeparator`).
+/// （數字中接受的小數點符號。）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecimalSeparator {
+    #[default]
+    Dot,
+    Comma,
+    Both,
+}
+
+/// UDL number rules (`Numbers, prefix1` … `Numbers, range`).
+/// （UDL 數字規則。）
+///
+/// Digits after a `prefix1` may also use `extras1`, digits after a `prefix2` may use `extras2`;
+/// any number may end with a suffix.
+/// （`prefix1` 之後的數字可含 `extras1`，`prefix2` 之後可含 `extras2`；任何數字都可接後綴。）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NumberRules {
+    #[serde(default)]
+    pub prefix1: Vec<String>,
+    #[serde(default)]
+    pub prefix2: Vec<String>,
+    #[serde(default)]
+    pub extras1: Vec<String>,
+    #[serde(default)]
+    pub extras2: Vec<String>,
+    #[serde(default)]
+    pub suffix1: Vec<String>,
+    #[serde(default)]
+    pub suffix2: Vec<String>,
+    #[serde(default)]
+    pub range: Vec<String>,
+}
+
+impl NumberRules {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Open, middle and close markers of one fold category.
+/// （單一摺疊類別的開啟、中間與關閉符號。）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FoldMarkers {
+    #[serde(default)]
+    pub open: Vec<String>,
+    #[serde(default)]
+    pub middle: Vec<String>,
+    #[serde(default)]
+    pub close: Vec<String>,
+}
+
+impl FoldMarkers {
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty() && self.middle.is_empty() && self.close.is_empty()
+    }
+
+    pub(crate) fn all(&self) -> impl Iterator<Item = &String> {
+        self.open.iter().chain(&self.middle).chain(&self.close)
+    }
+}
+
+/// One `<WordsStyle>` entry.
+/// （單一 `<WordsStyle>` 項目。）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UdlStyle {
+    /// Notepad++ style name, e.g. `KEYWORDS2` or `DELIMITERS1`.
+    /// （Notepad++ 樣式名稱，例如 `KEYWORDS2` 或 `DELIMITERS1`。）
+    pub name: String,
+    /// Foreground colour as `RRGGBB`.
+    /// （以 `RRGGBB` 表示的前景色。）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_name: Option<String>,
+    /// Bit flags: 1 bold, 2 italic, 4 underline.
+    /// （位元旗標：1 粗體、2 斜體、4 底線。）
+    #[serde(default)]
+    pub font_style: u8,
+    #[serde(default)]
+    pub nesting: u32,
+}
+
+impl UdlStyle {
+    /// Palette key used for tokens of this style, e.g. `keyword.group2`.
+    /// （此樣式 token 使用的調色盤鍵，例如 `keyword.group2`。）
+    pub fn scope(&self) -> Option<String> {
+        let scope = match self.name.as_str() {
+            "COMMENTS" => "comment".to_string(),
+            "LINE COMMENTS" => "comment.line".to_string(),
+            "NUMBERS" => "number".to_string(),
+            "OPERATORS" => "operator".to_string(),
+            "KEYWORDS1" => "keyword".to_string(),
+            "DELIMITERS1" => "string".to_string(),
+            "FOLDER IN CODE1" => "keyword.fold.code1".to_string(),
+            "FOLDER IN CODE2" => "keyword.fold.code2".to_string(),
+            "FOLDER IN COMMENT" => "comment.fold".to_string(),
+            name => {
+                if let Some(group) = name.strip_prefix("KEYWORDS") {
+                    format!("keyword.group{}", group.parse::<usize>().ok()?)
+                } else if let Some(group) = name.strip_prefix("DELIMITERS") {
+                    format!("string.delimiter{}", group.parse::<usize>().ok()?)
+                } else {
+                    return None;
+                }
+            }
+        };
+        Some(scope)
+    }
+
+    pub fn highlight_style(&self) -> Option<HighlightStyle> {
+        Some(HighlightStyle {
+            foreground: parse_udl_color(self.foreground.as_deref()?)?,
+            background: self.background.as_deref().and_then(parse_udl_color),
+            bold: self.font_style & 1 != 0,
+            italic: self.font_style & 2 != 0,
+            underline: self.font_style & 4 != 0,
+        })
+    }
+}
+
+fn parse_udl_color(value: &str) -> Option<Color> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+    Some(Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+        a: 255,
+    })
+}
+
 mod serde_char_option {
     use serde::{Deserialize, Deserializer, Serializer};
 
@@ -65,26 +323,55 @@ pub enum UdlError {
     XmlDecode(#[from] quick_xml::DeError),
     #[error("failed to encode Notepad++ UDL: {0}")]
     XmlEncode(#[from] quick_xml::Error),
+    #[error("Notepad++ UDL contains no <UserLang> element")]
+    MissingUserLang,
 }
 
 impl UdlDefinition {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
-            identifier: None,
-            extensions: Vec::new(),
-            keywords: Vec::new(),
-            line_comment: None,
-            block_comment: None,
-            delimiters: Vec::new(),
-            number_pattern: None,
-            operators: Vec::new(),
-            case_sensitive: true,
+            ..Self::default()
         }
     }
 
+    /// Words of a keyword group, counted from 1.
+    /// （取得關鍵字群組的字詞，群組自 1 起算。）
+    pub fn keyword_group(&self, group: usize) -> &[String] {
+        match group {
+            1 => &self.keywords,
+            _ => group
+                .checked_sub(2)
+                .and_then(|idx| self.keyword_groups.get(idx))
+                .map_or(&[], Vec::as_slice),
+        }
+    }
+
+    /// Palette built from the UDL's own styles.
+    /// （由 UDL 自身樣式建立的調色盤。）
+    pub fn palette(&self) -> HighlightPalette {
+        let mut palette = HighlightPalette::default();
+        for style in &self.styles {
+            if let (Some(scope), Some(highlight)) = (style.scope(), style.highlight_style()) {
+                palette.insert_standard(&scope, highlight);
+            }
+        }
+        palette
+    }
+
+    /// Parses a Notepad++ UDL file, either UDL 2.1 (`<NotepadPlus><UserLang udlVersion="2.1">`) or the older flat layout.
+    /// （解析 Notepad++ UDL 檔案，支援 UDL 2.1 與較舊的扁平格式。）
     pub fn from_notepad_xml(xml: &str) -> Result<Self, UdlError> {
-        let user_lang: UserLang = quick_xml::de::from_str(xml)?;
+        let user_lang = if xml.contains("<NotepadPlus") {
+            let document: NotepadPlus = quick_xml::de::from_str(xml)?;
+            document
+                .user_langs
+                .into_iter()
+                .next()
+                .ok_or(UdlError::MissingUserLang)?
+        } else {
+            quick_xml::de::from_str::<UserLang>(xml)?
+        };
 
         let mut definition = UdlDefinition::new(user_lang.name);
         definition.identifier = user_lang.lexer;
@@ -97,69 +384,166 @@ impl UdlDefinition {
                     .collect()
             })
             .unwrap_or_default();
-        definition.case_sensitive = !matches!(
-            user_lang.case_ignored.as_deref(),
-            Some(value) if value.eq_ignore_ascii_case("yes")
-        );
+
+        let global = user_lang
+            .settings
+            .as_ref()
+            .and_then(|settings| settings.global.as_ref());
+        let case_ignored = global
+            .and_then(|global| global.case_ignored.as_deref())
+            .or(user_lang.case_ignored.as_deref());
+        definition.case_sensitive = !case_ignored.is_some_and(is_yes);
+        if let Some(global) = global {
+            definition.allow_fold_of_comments =
+                global.allow_fold_of_comments.as_deref().is_some_and(is_yes);
+            definition.fold_compact = global.fold_compact.as_deref().is_some_and(is_yes);
+            definition.line_comment_position = match global.force_pure_lc.as_deref() {
+                Some("1") => LineCommentPosition::LineStart,
+                Some("2") => LineCommentPosition::AfterWhitespace,
+                _ => LineCommentPosition::Anywhere,
+            };
+            definition.decimal_separator = match global.decimal_separator.as_deref() {
+                Some("1") => DecimalSeparator::Comma,
+                Some("2") => DecimalSeparator::Both,
+                _ => DecimalSeparator::Dot,
+            };
+        }
+        if let Some(prefix) = user_lang
+            .settings
+            .as_ref()
+            .and_then(|settings| settings.prefix.as_ref())
+        {
+            for (flag, value) in definition.keyword_prefix.iter_mut().zip(prefix.values()) {
+                *flag = value.is_some_and(is_yes);
+            }
+        }
 
         if let Some(list) = user_lang.keyword_lists {
-            let mut keywords = Vec::new();
+            let mut groups = vec![Vec::new(); KEYWORD_GROUPS - 1];
             for entry in list.keywords {
-                keywords.extend(split_keywords(&entry.text));
+                let words = split_keywords(&entry.text);
+                let numbers = &mut definition.numbers;
+                match entry.name.as_str() {
+                    "Comments" => apply_comments(&mut definition, &split_prefixed(&entry.text)),
+                    "Delimiters" => {
+                        definition.delimiters = delimiters_from_slots(&split_prefixed(&entry.text))
+                    }
+                    "Operators1" => definition.operators = words,
+                    "Operators2" => definition.operators2 = words,
+                    "Numbers, prefix1" => numbers.prefix1 = words,
+                    "Numbers, prefix2" => numbers.prefix2 = words,
+                    "Numbers, extras1" => numbers.extras1 = words,
+                    "Numbers, extras2" => numbers.extras2 = words,
+                    "Numbers, suffix1" => numbers.suffix1 = words,
+                    "Numbers, suffix2" => numbers.suffix2 = words,
+                    "Numbers, range" => numbers.range = words,
+                    name => {
+                        if let Some(markers) = fold_slot(&mut definition, name) {
+                            *markers = words;
+                            continue;
+                        }
+                        match name
+                            .strip_prefix("Keywords")
+                            .and_then(|group| group.parse::<usize>().ok())
+                        {
+                            Some(group @ 2..=KEYWORD_GROUPS) => groups[group - 2] = words,
+                            // 舊格式把其他清單都併入群組 1。 / Older files fold every other list into group 1.
+                            _ => definition.keywords.extend(words),
+                        }
+                    }
+                }
             }
-            definition.keywords = keywords;
+            while groups.last().is_some_and(Vec::is_empty) {
+                groups.pop();
+            }
+            definition.keyword_groups = groups;
         }
 
         if let Some(comments) = user_lang.comments {
-            let mut line_comment = None;
             let mut block_start = None;
             let mut block_end = None;
             for entry in comments.comments {
                 match entry.name.as_str() {
-                    "Line" => line_comment = entry.value,
+                    "Line" => definition.line_comment = entry.value,
                     "Start" => block_start = entry.value,
                     "End" => block_end = entry.value,
                     _ => {}
                 }
             }
-            definition.line_comment = line_comment;
             if let (Some(start), Some(end)) = (block_start, block_end) {
                 definition.block_comment = Some((start, end));
             }
         }
 
         if let Some(delimiters) = user_lang.delimiters {
-            let mut items = Vec::new();
-            for entry in delimiters.delimiters {
-                if let Some(start) = entry.open {
-                    items.push(Delimiter {
-                        start,
+            definition.delimiters = delimiters
+                .delimiters
+                .into_iter()
+                .filter_map(|entry| {
+                    Some(Delimiter {
+                        start: entry.open?,
                         end: entry.close,
                         escape: entry.escape.and_then(|value| value.chars().next()),
-                    });
-                }
-            }
-            definition.delimiters = items;
+                    })
+                })
+                .collect();
+        }
+
+        if let Some(styles) = user_lang.styles {
+            definition.styles = styles
+                .styles
+                .into_iter()
+                .map(|entry| UdlStyle {
+                    name: entry.name,
+                    foreground: entry.fg_color,
+                    background: entry.bg_color,
+                    font_name: entry.font_name,
+                    font_style: entry
+                        .font_style
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or(0),
+                    nesting: entry
+                        .nesting
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or(0),
+                })
+                .collect();
         }
 
         Ok(definition)
     }
 
+    /// Writes a UDL 2.1 file that Notepad++ can import.
+    /// （輸出 Notepad++ 可匯入的 UDL 2.1 檔案。）
     pub fn to_notepad_xml(&self) -> Result<String, UdlError> {
         let user_lang = UserLang {
             name: self.name.clone(),
             lexer: self.identifier.clone(),
             ext: (!self.extensions.is_empty()).then(|| self.extensions.join(" ")),
-            case_ignored: (!self.case_sensitive).then(|| "yes".to_string()),
-            comments: comments_to_xml(self),
-            keyword_lists: keywords_to_xml(self),
-            delimiters: delimiters_to_xml(self),
+            udl_version: Some(UDL_VERSION.to_string()),
+            case_ignored: None,
+            settings: Some(settings_to_xml(self)),
+            keyword_lists: Some(keywords_to_xml(self)),
+            styles: (!self.styles.is_empty()).then(|| styles_to_xml(self)),
+            comments: None,
+            delimiters: None,
+        };
+        let document = NotepadPlus {
+            user_langs: vec![user_lang],
         };
-        let xml = quick_xml::se::to_string(&user_lang)?;
+        let xml = quick_xml::se::to_string(&document)?;
         Ok(xml)
     }
 }
 
+fn is_yes(value: &str) -> bool {
+    value.eq_ignore_ascii_case("yes")
+}
+
+fn yes_no(flag: bool) -> String {
+    if flag { "yes" } else { "no" }.to_string()
+}
+
 fn split_keywords(value: &str) -> Vec<String> {
     value
         .split_whitespace()
@@ -168,58 +552,233 @@ fn split_keywords(value: &str) -> Vec<String> {
         .collect()
 }
 
-fn comments_to_xml(definition: &UdlDefinition) -> Option<Comments> {
-    if definition.line_comment.is_none() && definition.block_comment.is_none() {
-        return None;
+/// Splits `00// 01 03/*` style lists into values keyed by their two-digit slot.
+/// （將 `00// 01 03/*` 形式的清單依兩位數欄位拆開。）
+fn split_prefixed(value: &str) -> BTreeMap<usize, Vec<String>> {
+    let mut slots: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for token in value.split_whitespace() {
+        let Some(slot) = token.get(..2).and_then(|digits| digits.parse().ok()) else {
+            continue;
+        };
+        let value = &token[2..];
+        let entry = slots.entry(slot).or_default();
+        if !value.is_empty() {
+            entry.push(value.to_string());
+        }
+    }
+    slots
+}
+
+fn join_prefixed(slots: &[(usize, Option<&str>)]) -> String {
+    let mut items = Vec::new();
+    for (slot, value) in slots {
+        let mut values = value
+            .map(str::split_whitespace)
+            .into_iter()
+            .flatten()
+            .peekable();
+        if values.peek().is_none() {
+            items.push(format!("{slot:02}"));
+        }
+        items.extend(values.map(|value| format!("{slot:02}{value}")));
     }
-    let mut comments = Vec::new();
-    if let Some(line) = &definition.line_comment {
-        comments.push(CommentEntry {
-            name: "Line".into(),
-            value: Some(line.clone()),
-        });
+    items.join(" ")
+}
+
+fn joined(slots: &BTreeMap<usize, Vec<String>>, slot: usize) -> Option<String> {
+    slots
+        .get(&slot)
+        .filter(|values| !values.is_empty())
+        .map(|values| values.join(" "))
+}
+
+fn apply_comments(definition: &mut UdlDefinition, slots: &BTreeMap<usize, Vec<String>>) {
+    definition.line_comment = joined(slots, 0);
+    definition.line_comment_continue = joined(slots, 1);
+    definition.line_comment_close = joined(slots, 2);
+    definition.block_comment = joined(slots, 3).zip(joined(slots, 4));
+}
+
+fn delimiters_from_slots(slots: &BTreeMap<usize, Vec<String>>) -> Vec<Delimiter> {
+    let mut delimiters: Vec<Delimiter> = (0..DELIMITER_GROUPS)
+        .map(|group| Delimiter {
+            start: joined(slots, group * 3).unwrap_or_default(),
+            end: joined(slots, group * 3 + 2),
+            escape: joined(slots, group * 3 + 1).and_then(|value| value.chars().next()),
+        })
+        .collect();
+    while delimiters
+        .last()
+        .is_some_and(|delimiter| delimiter.start.is_empty())
+    {
+        delimiters.pop();
     }
-    if let Some((start, end)) = &definition.block_comment {
-        comments.push(CommentEntry {
-            name: "Start".into(),
-            value: Some(start.clone()),
-        });
-        comments.push(CommentEntry {
-            name: "End".into(),
-            value: Some(end.clone()),
-        });
+    delimiters
+}
+
+fn fold_slot<'a>(definition: &'a mut UdlDefinition, name: &str) -> Option<&'a mut Vec<String>> {
+    let (category, position) = name.strip_prefix("Folders in ")?.split_once(", ")?;
+    let markers = match category {
+        "code1" => &mut definition.folds_in_code1,
+        "code2" => &mut definition.folds_in_code2,
+        "comment" => &mut definition.folds_in_comment,
+        _ => return None,
+    };
+    match position {
+        "open" => Some(&mut markers.open),
+        "middle" => Some(&mut markers.middle),
+        "close" => Some(&mut markers.close),
+        _ => None,
     }
-    Some(Comments { comments })
 }
 
-fn keywords_to_xml(definition: &UdlDefinition) -> Option<KeywordLists> {
-    if definition.keywords.is_empty() {
-        return None;
+fn settings_to_xml(definition: &UdlDefinition) -> Settings {
+    let global = GlobalSettings {
+        case_ignored: Some(yes_no(!definition.case_sensitive)),
+        allow_fold_of_comments: Some(yes_no(definition.allow_fold_of_comments)),
+        fold_compact: Some(yes_no(definition.fold_compact)),
+        force_pure_lc: Some(
+            match definition.line_comment_position {
+                LineCommentPosition::Anywhere => "0",
+                LineCommentPosition::LineStart => "1",
+                LineCommentPosition::AfterWhitespace => "2",
+            }
+            .to_string(),
+        ),
+        decimal_separator: Some(
+            match definition.decimal_separator {
+                DecimalSeparator::Dot => "0",
+                DecimalSeparator::Comma => "1",
+                DecimalSeparator::Both => "2",
+            }
+            .to_string(),
+        ),
+    };
+    let flag = |group: usize| Some(yes_no(definition.keyword_prefix[group]));
+    let prefix = PrefixSettings {
+        keywords1: flag(0),
+        keywords2: flag(1),
+        keywords3: flag(2),
+        keywords4: flag(3),
+        keywords5: flag(4),
+        keywords6: flag(5),
+        keywords7: flag(6),
+        keywords8: flag(7),
+    };
+    Settings {
+        global: Some(global),
+        prefix: Some(prefix),
     }
-    let text = definition.keywords.join(" ");
-    Some(KeywordLists {
-        keywords: vec![KeywordEntry {
-            name: "Keywords1".into(),
-            text,
-        }],
-    })
 }
 
-fn delimiters_to_xml(definition: &UdlDefinition) -> Option<Delimiters> {
-    if definition.delimiters.is_empty() {
-        return None;
+fn keywords_to_xml(definition: &UdlDefinition) -> KeywordLists {
+    let mut keywords = Vec::new();
+    let mut push = |name: &str, text: String| {
+        keywords.push(KeywordEntry {
+            name: name.to_string(),
+            text,
+        })
+    };
+    let (block_start, block_end) = definition
+        .block_comment
+        .as_ref()
+        .map(|(start, end)| (start.as_str(), end.as_str()))
+        .unzip();
+    push(
+        "Comments",
+        join_prefixed(&[
+            (0, definition.line_comment.as_deref()),
+            (1, definition.line_comment_continue.as_deref()),
+            (2, definition.line_comment_close.as_deref()),
+            (3, block_start),
+            (4, block_end),
+        ]),
+    );
+    let numbers = &definition.numbers;
+    for (name, words) in [
+        ("Numbers, prefix1", &numbers.prefix1),
+        ("Numbers, prefix2", &numbers.prefix2),
+        ("Numbers, extras1", &numbers.extras1),
+        ("Numbers, extras2", &numbers.extras2),
+        ("Numbers, suffix1", &numbers.suffix1),
+        ("Numbers, suffix2", &numbers.suffix2),
+        ("Numbers, range", &numbers.range),
+        ("Operators1", &definition.operators),
+        ("Operators2", &definition.operators2),
+    ] {
+        push(name, words.join(" "));
     }
-    let delimiters = definition
+    for (category, markers) in [
+        ("code1", &definition.folds_in_code1),
+        ("code2", &definition.folds_in_code2),
+        ("comment", &definition.folds_in_comment),
+    ] {
+        push(
+            &format!("Folders in {category}, open"),
+            markers.open.join(" "),
+        );
+        push(
+            &format!("Folders in {category}, middle"),
+            markers.middle.join(" "),
+        );
+        push(
+            &format!("Folders in {category}, close"),
+            markers.close.join(" "),
+        );
+    }
+    for group in 1..=KEYWORD_GROUPS {
+        push(
+            &format!("Keywords{group}"),
+            definition.keyword_group(group).join(" "),
+        );
+    }
+    let escapes: Vec<Option<String>> = definition
         .delimiters
         .iter()
-        .map(|delimiter| DelimiterEntry {
-            name: None,
-            open: Some(delimiter.start.clone()),
-            close: delimiter.end.clone(),
-            escape: delimiter.escape.map(|ch| ch.to_string()),
+        .map(|delimiter| delimiter.escape.map(String::from))
+        .collect();
+    let delimiter_slots: Vec<(usize, Option<&str>)> = (0..DELIMITER_GROUPS)
+        .flat_map(|group| {
+            let delimiter = definition.delimiters.get(group);
+            [
+                (
+                    group * 3,
+                    delimiter.map(|delimiter| delimiter.start.as_str()),
+                ),
+                (group * 3 + 1, escapes.get(group).and_then(Option::as_deref)),
+                (
+                    group * 3 + 2,
+                    delimiter.and_then(|delimiter| delimiter.end.as_deref()),
+                ),
+            ]
+        })
+        .collect();
+    let delimiters = join_prefixed(&delimiter_slots);
+    push("Delimiters", delimiters);
+    KeywordLists { keywords }
+}
+
+fn styles_to_xml(definition: &UdlDefinition) -> Styles {
+    let styles = definition
+        .styles
+        .iter()
+        .map(|style| WordsStyle {
+            name: style.name.clone(),
+            fg_color: style.foreground.clone(),
+            bg_color: style.background.clone(),
+            font_name: style.font_name.clone(),
+            font_style: Some(style.font_style.to_string()),
+            nesting: Some(style.nesting.to_string()),
         })
         .collect();
-    Some(Delimiters { delimiters })
+    Styles { styles }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename = "NotepadPlus")]
+struct NotepadPlus {
+    #[serde(rename = "UserLang", default)]
+    user_langs: Vec<UserLang>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -227,20 +786,100 @@ fn delimiters_to_xml(definition: &UdlDefinition) -> Option<Delimiters> {
 struct UserLang {
     #[serde(rename = "@name")]
     name: String,
-    #[serde(rename = "@lexer", default)]
+    #[serde(rename = "@lexer", default, skip_serializing_if = "Option::is_none")]
     lexer: Option<String>,
-    #[serde(rename = "@ext", default)]
+    #[serde(rename = "@ext", default, skip_serializing_if = "Option::is_none")]
     ext: Option<String>,
-    #[serde(rename = "@caseIgnored", default)]
+    #[serde(
+        rename = "@udlVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    udl_version: Option<String>,
+    #[serde(
+        rename = "@caseIgnored",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     case_ignored: Option<String>,
-    #[serde(rename = "Comments", default)]
-    comments: Option<Comments>,
-    #[serde(rename = "KeywordLists", default)]
+    #[serde(rename = "Settings", default, skip_serializing_if = "Option::is_none")]
+    settings: Option<Settings>,
+    #[serde(
+        rename = "KeywordLists",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     keyword_lists: Option<KeywordLists>,
-    #[serde(rename = "Delimiters", default)]
+    #[serde(rename = "Styles", default, skip_serializing_if = "Option::is_none")]
+    styles: Option<Styles>,
+    #[serde(rename = "Comments", default, skip_serializing_if = "Option::is_none")]
+    comments: Option<Comments>,
+    #[serde(
+        rename = "Delimiters",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     delimiters: Option<Delimiters>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    #[serde(rename = "Global", default, skip_serializing_if = "Option::is_none")]
+    global: Option<GlobalSettings>,
+    #[serde(rename = "Prefix", default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<PrefixSettings>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GlobalSettings {
+    #[serde(rename = "@caseIgnored", default)]
+    case_ignored: Option<String>,
+    #[serde(rename = "@allowFoldOfComments", default)]
+    allow_fold_of_comments: Option<String>,
+    #[serde(rename = "@foldCompact", default)]
+    fold_compact: Option<String>,
+    #[serde(rename = "@forcePureLC", default)]
+    force_pure_lc: Option<String>,
+    #[serde(rename = "@decimalSeparator", default)]
+    decimal_separator: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PrefixSettings {
+    #[serde(rename = "@Keywords1", default)]
+    keywords1: Option<String>,
+    #[serde(rename = "@Keywords2", default)]
+    keywords2: Option<String>,
+    #[serde(rename = "@Keywords3", default)]
+    keywords3: Option<String>,
+    #[serde(rename = "@Keywords4", default)]
+    keywords4: Option<String>,
+    #[serde(rename = "@Keywords5", default)]
+    keywords5: Option<String>,
+    #[serde(rename = "@Keywords6", default)]
+    keywords6: Option<String>,
+    #[serde(rename = "@Keywords7", default)]
+    keywords7: Option<String>,
+    #[serde(rename = "@Keywords8", default)]
+    keywords8: Option<String>,
+}
+
+impl PrefixSettings {
+    fn values(&self) -> [Option<&str>; KEYWORD_GROUPS] {
+        [
+            &self.keywords1,
+            &self.keywords2,
+            &self.keywords3,
+            &self.keywords4,
+            &self.keywords5,
+            &self.keywords6,
+            &self.keywords7,
+            &self.keywords8,
+        ]
+        .map(Option::as_deref)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Comments {
     #[serde(rename = "Comment", default)]
@@ -287,6 +926,32 @@ struct DelimiterEntry {
     escape: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct Styles {
+    #[serde(rename = "WordsStyle", default)]
+    styles: Vec<WordsStyle>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WordsStyle {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@fgColor", default, skip_serializing_if = "Option::is_none")]
+    fg_color: Option<String>,
+    #[serde(rename = "@bgColor", default, skip_serializing_if = "Option::is_none")]
+    bg_color: Option<String>,
+    #[serde(rename = "@fontName", default, skip_serializing_if = "Option::is_none")]
+    font_name: Option<String>,
+    #[serde(
+        rename = "@fontStyle",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    font_style: Option<String>,
+    #[serde(rename = "@nesting", default, skip_serializing_if = "Option::is_none")]
+    nesting: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,6 +973,7 @@ mod tests {
             number_pattern: None,
             operators: vec!["+".into()],
             case_sensitive: false,
+            ..UdlDefinition::default()
         };
 
         let xml = original.to_notepad_xml().unwrap();
@@ -320,4 +986,119 @@ mod tests {
         assert_eq!(parsed.delimiters, original.delimiters);
         assert!(!parsed.case_sensitive);
     }
+
+    const TEAM_UDL: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<NotepadPlus>
+    <UserLang name="TeamScript" ext="ts1 tsx1" udlVersion="2.1">
+        <Settings>
+            <Global caseIgnored="yes" allowFoldOfComments="yes" foldCompact="no" forcePureLC="2" decimalSeparator="2" />
+            <Prefix Keywords1="no" Keywords2="no" Keywords3="yes" Keywords4="no" Keywords5="no" Keywords6="no" Keywords7="no" Keywords8="no" />
+        </Settings>
+        <KeywordLists>
+            <Keywords name="Comments">00; 00# 01\ 02 03(* 04*)</Keywords>
+            <Keywords name="Numbers, prefix1">0x</Keywords>
+            <Keywords name="Numbers, prefix2">0b</Keywords>
+            <Keywords name="Numbers, extras1">A B C D E F</Keywords>
+            <Keywords name="Numbers, extras2"></Keywords>
+            <Keywords name="Numbers, suffix1">L</Keywords>
+            <Keywords name="Numbers, suffix2">u</Keywords>
+            <Keywords name="Numbers, range">..</Keywords>
+            <Keywords name="Operators1">+ - = ( )</Keywords>
+            <Keywords name="Operators2">and or</Keywords>
+            <Keywords name="Folders in code1, open">{</Keywords>
+            <Keywords name="Folders in code1, middle"></Keywords>
+            <Keywords name="Folders in code1, close">}</Keywords>
+            <Keywords name="Folders in code2, open">if</Keywords>
+            <Keywords name="Folders in code2, middle">else</Keywords>
+            <Keywords name="Folders in code2, close">endif</Keywords>
+            <Keywords name="Folders in comment, open">region</Keywords>
+            <Keywords name="Folders in comment, middle"></Keywords>
+            <Keywords name="Folders in comment, close">endregion</Keywords>
+            <Keywords name="Keywords1">let return</Keywords>
+            <Keywords name="Keywords2">print input</Keywords>
+            <Keywords name="Keywords3">get set</Keywords>
+            <Keywords name="Keywords4"></Keywords>
+            <Keywords name="Keywords5"></Keywords>
+            <Keywords name="Keywords6"></Keywords>
+            <Keywords name="Keywords7"></Keywords>
+            <Keywords name="Keywords8">TODO</Keywords>
+            <Keywords name="Delimiters">00&quot; 01\ 02&quot; 03&apos; 04 05&apos; 06[[ 07 08]]</Keywords>
+        </KeywordLists>
+        <Styles>
+            <WordsStyle name="DEFAULT" fgColor="000000" bgColor="FFFFFF" fontName="" fontStyle="0" nesting="0" />
+            <WordsStyle name="LINE COMMENTS" fgColor="008000" bgColor="FFFFFF" fontName="" fontStyle="2" nesting="0" />
+            <WordsStyle name="KEYWORDS2" fgColor="8000FF" bgColor="FFFFFF" fontName="" fontStyle="1" nesting="0" />
+            <WordsStyle name="DELIMITERS3" fgColor="FF8000" bgColor="FFFFFF" fontName="Consolas" fontStyle="4" nesting="1024" />
+        </Styles>
+    </UserLang>
+</NotepadPlus>
+"#;
+
+    #[test]
+    fn udl_2_1_files_round_trip() {
+        let definition = UdlDefinition::from_notepad_xml(TEAM_UDL).unwrap();
+        assert_eq!(definition.extensions, ["ts1", "tsx1"]);
+        assert!(!definition.case_sensitive);
+        assert!(definition.allow_fold_of_comments);
+        assert_eq!(
+            definition.line_comment_position,
+            LineCommentPosition::AfterWhitespace
+        );
+        assert_eq!(definition.decimal_separator, DecimalSeparator::Both);
+        assert_eq!(definition.line_comment.as_deref(), Some("; #"));
+        assert_eq!(definition.line_comment_continue.as_deref(), Some("\\"));
+        assert_eq!(
+            definition.block_comment,
+            Some(("(*".to_string(), "*)".to_string()))
+        );
+        assert_eq!(definition.keywords, ["let", "return"]);
+        assert_eq!(definition.keyword_group(2), ["print", "input"]);
+        assert_eq!(definition.keyword_group(8), ["TODO"]);
+        assert_eq!(definition.keyword_groups.len(), 7);
+        assert!(definition.keyword_prefix[2] && !definition.keyword_prefix[0]);
+        assert_eq!(definition.numbers.extras1.len(), 6);
+        assert_eq!(definition.numbers.range, [".."]);
+        assert_eq!(definition.operators2, ["and", "or"]);
+        assert_eq!(definition.folds_in_code2.middle, ["else"]);
+        assert_eq!(definition.folds_in_comment.close, ["endregion"]);
+        assert_eq!(
+            definition.delimiters,
+            [
+                Delimiter {
+                    start: "\"".into(),
+                    end: Some("\"".into()),
+                    escape: Some('\\'),
+                },
+                Delimiter {
+                    start: "'".into(),
+                    end: Some("'".into()),
+                    escape: None,
+                },
+                Delimiter {
+                    start: "[[".into(),
+                    end: Some("]]".into()),
+                    escape: None,
+                },
+            ]
+        );
+        assert_eq!(definition.styles.len(), 4);
+        assert_eq!(definition.styles[3].font_name.as_deref(), Some("Consolas"));
+        assert_eq!(definition.styles[3].nesting, 1024);
+
+        let palette = definition.palette();
+        let group2 = palette.style_for_scope("keyword.group2").unwrap();
+        assert!(group2.bold);
+        assert_eq!((group2.foreground.r, group2.foreground.b), (0x80, 0xFF));
+        assert!(
+            palette
+                .style_for_scope("string.delimiter3")
+                .unwrap()
+                .underline
+        );
+
+        let xml = definition.to_notepad_xml().unwrap();
+        assert!(xml.starts_with("<NotepadPlus><UserLang"));
+        assert!(xml.contains(r#"udlVersion="2.1""#));
+        assert_eq!(UdlDefinition::from_notepad_xml(&xml).unwrap(), definition);
+    }
 }
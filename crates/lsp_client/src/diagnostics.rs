@@ -0,0 +1,113 @@
+use std::ops::Range;
+
+/// Severity level for diagnostics emitted by an LSP server.
+/// （LSP 伺服器發出的診斷資訊嚴重層級。）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+/// Zero-based line and character column (Unicode scalar values, not UTF-16 units).
+/// （0 起算的行與字元欄（以 Unicode 字元計，而非 UTF-16 單位）。）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TextPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl TextPosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Span reported by a language server (diagnostic, location or edit).
+/// （語言伺服器回報的範圍，例如診斷、位置或編輯。）
+///
+/// Ranges parsed from server messages are resolved against the document text, so `bytes`
+/// (UTF-8 offsets into that text) is always set for them; it is `None` only for ranges built
+/// by hand.
+/// （由伺服器訊息解析的範圍皆依文件內容換算，因此必定帶有 `bytes`（該文字的 UTF-8 位元組
+/// 位置）；只有手動建立的範圍才會是 `None`。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspRange {
+    pub start: TextPosition,
+    pub end: TextPosition,
+    pub bytes: Option<Range<usize>>,
+}
+
+/// Secondary location attached to a diagnostic (e.g. "first defined here").
+/// （附加於診斷的次要位置，例如「首次定義於此」。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedInformation {
+    pub uri: String,
+    pub range: LspRange,
+    pub message: String,
+}
+
+/// Diagnostic message tracked for a document or, when pushed in by hand, a language.
+/// （針對文件追蹤的診斷訊息；手動推入時則對應整個語言。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub severity: DiagnosticSeverity,
+    /// URI of the document the server published the diagnostic for.
+    /// （伺服器發佈此診斷時所屬文件的 URI。）
+    pub uri: Option<String>,
+    pub range: Option<LspRange>,
+    pub code: Option<String>,
+    pub source: Option<String>,
+    pub related: Vec<RelatedInformation>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, severity: DiagnosticSeverity) -> Self {
+        Self {
+            message: message.into(),
+            severity,
+            uri: None,
+            range: None,
+            code: None,
+            source: None,
+            related: Vec::new(),
+        }
+    }
+
+    pub fn with_uri(mut self, uri: impl Into<String>) -> Self {
+        self.uri = Some(uri.into());
+        self
+    }
+
+    pub fn with_range(mut self, range: LspRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Byte span in the document text, if known.
+    /// （文件文字中的位元組範圍（若已知）。）
+    pub fn byte_range(&self) -> Option<Range<usize>> {
+        self.range.as_ref().and_then(|range| range.bytes.clone())
+    }
+
+    /// Start position used to order and navigate diagnostics.
+    /// （用於排序與跳轉的起始位置。）
+    pub fn start(&self) -> TextPosition {
+        self.range
+            .as_ref()
+            .map(|range| range.start)
+            .unwrap_or_default()
+    }
+}
@@ -1,46 +1,429 @@
-use std::collections::HashMap;
-use std::sync::RwLock;
+pub mod diagnostics;
+pub mod navigation;
+pub mod protocol;
+pub mod server;
+pub mod transport;
+pub mod worker;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
 
 use rustnotepad_autocomplete::{LspBridge, LspError, LspRequestParams, LspSuggestion};
+use rustnotepad_search::SearchReport;
+use serde_json::{json, Value};
 
-/// Severity level for diagnostics emitted by an LSP server.
-/// （LSP 伺服器發出的診斷資訊嚴重層級。）
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum DiagnosticSeverity {
-    Error,
-    Warning,
-    Information,
-    Hint,
-}
+use navigation::DocumentTexts;
 
-/// Diagnostic message tracked for the active language.
-/// （針對使用中語言追蹤的診斷訊息。）
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Diagnostic {
-    pub message: String,
-    pub severity: DiagnosticSeverity,
-}
+pub use diagnostics::{Diagnostic, DiagnosticSeverity, LspRange, RelatedInformation, TextPosition};
+pub use navigation::{
+    apply_text_edits, DocumentSymbol, Hover, Location, LspSymbolParser, SymbolKind, TextEdit,
+    WorkspaceEdit,
+};
+pub use protocol::{document_uri, uri_to_document};
+pub use server::{
+    LanguageServer, LanguageServerConfig, LspClientError, DEFAULT_REQUEST_TIMEOUT,
+    INITIALIZE_TIMEOUT,
+};
+pub use worker::{BackgroundCompletions, LspReply, LspWorker};
 
-impl Diagnostic {
-    pub fn new(message: impl Into<String>, severity: DiagnosticSeverity) -> Self {
-        Self {
-            message: message.into(),
-            severity,
-        }
-    }
+/// Lifecycle of the language server configured for a language.
+/// （某語言所設定之語言伺服器的生命週期狀態。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerStatus {
+    NotConfigured,
+    Stopped,
+    Running,
+    Failed(String),
 }
 
-/// Lightweight LSP client façade that fulfils the autocomplete bridge interface.
-/// （滿足自動完成橋接介面的輕量級 LSP 用戶端外觀層。）
+/// LSP client that fulfils the autocomplete bridge interface.
+/// （滿足自動完成橋接介面的 LSP 用戶端。）
+///
+/// Languages with a registered [`LanguageServerConfig`] are served by a spawned process over
+/// stdio; other languages fall back to the in-memory suggestions and diagnostics pushed in
+/// through [`update_suggestions`](Self::update_suggestions) and
+/// [`update_diagnostics`](Self::update_diagnostics).
+/// （已註冊 [`LanguageServerConfig`] 的語言由透過 stdio 啟動的程序提供服務；其他語言則退回
+/// 以 `update_suggestions` 與 `update_diagnostics` 推入的記憶體內建議與診斷。）
 pub struct LspClient {
-    state: RwLock<InnerState>,
+    state: Arc<RwLock<InnerState>>,
+    servers: Mutex<HashMap<String, Arc<LanguageServer>>>,
 }
 
 impl LspClient {
     pub fn new() -> Self {
         Self {
-            state: RwLock::new(InnerState::with_defaults()),
+            state: Arc::new(RwLock::new(InnerState::with_defaults())),
+            servers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers (or replaces) the server used for `config.language`; it starts lazily.
+    /// （註冊或取代 `config.language` 使用的伺服器；於需要時才啟動。）
+    pub fn register_server(&self, config: LanguageServerConfig) {
+        let language = config.language.clone();
+        self.stop_server(&language);
+        let mut guard = self.state.write().expect("LSP state poisoned");
+        guard.ensure_language(language.clone()).status = ServerStatus::Stopped;
+        guard.configs.insert(language, config);
+    }
+
+    pub fn server_status(&self, language: &str) -> ServerStatus {
+        let guard = self.state.read().expect("LSP state poisoned");
+        guard
+            .languages
+            .get(language)
+            .map(|session| session.status.clone())
+            .unwrap_or(ServerStatus::NotConfigured)
+    }
+
+    /// Spawns the configured server for `language` and replays its open documents.
+    /// （啟動 `language` 所設定的伺服器，並重新送出已開啟的文件。）
+    pub fn start_server(&self, language: &str) -> Result<(), LspClientError> {
+        if self.running_server(language).is_some() {
+            return Ok(());
+        }
+        let config = {
+            let guard = self.state.read().expect("LSP state poisoned");
+            guard.configs.get(language).cloned()
+        }
+        .ok_or_else(|| LspClientError::NotConfigured(language.to_string()))?;
+
+        let state = Arc::clone(&self.state);
+        let server_language = language.to_string();
+        let spawned = LanguageServer::spawn(&config, move |method, params| {
+            handle_notification(&state, &server_language, method, params)
+        });
+        let server = match spawned {
+            Ok(server) => Arc::new(server),
+            Err(err) => {
+                self.set_status(language, ServerStatus::Failed(err.to_string()));
+                return Err(err);
+            }
+        };
+        self.servers
+            .lock()
+            .expect("LSP servers poisoned")
+            .insert(language.to_string(), Arc::clone(&server));
+        self.set_status(language, ServerStatus::Running);
+
+        let documents: Vec<Value> = {
+            let guard = self.state.read().expect("LSP state poisoned");
+            guard
+                .documents
+                .values()
+                .filter(|document| document.language == language)
+                .map(TrackedDocument::did_open_params)
+                .collect()
+        };
+        for params in documents {
+            server.notify("textDocument/didOpen", params)?;
+        }
+        Ok(())
+    }
+
+    /// Shuts down the server for `language`, if one is running.
+    /// （關閉 `language` 執行中的伺服器。）
+    pub fn stop_server(&self, language: &str) {
+        let server = self
+            .servers
+            .lock()
+            .expect("LSP servers poisoned")
+            .remove(language);
+        if let Some(server) = server {
+            let _ = server.shutdown();
+            let mut guard = self.state.write().expect("LSP state poisoned");
+            guard.ensure_language(language.to_string()).status = ServerStatus::Stopped;
+            guard
+                .published
+                .retain(|_, published| published.language != language);
+        }
+    }
+
+    /// Tracks a newly opened document and sends `didOpen`, starting the server on first use.
+    /// （追蹤新開啟的文件並送出 `didOpen`；首次使用時會啟動伺服器。）
+    ///
+    /// A server that already failed to start is not retried until it is registered again.
+    /// （先前啟動失敗的伺服器在重新註冊之前不會重試。）
+    pub fn open_document(
+        &self,
+        language: &str,
+        document: &str,
+        text: &str,
+    ) -> Result<(), LspClientError> {
+        if self.is_tracked(document) {
+            self.close_document(document)?;
         }
+        let params = {
+            let mut guard = self.state.write().expect("LSP state poisoned");
+            let tracked = TrackedDocument {
+                uri: document_uri(document),
+                language: language.to_string(),
+                version: 1,
+                text: text.to_string(),
+                symbols: None,
+            };
+            let params = tracked.did_open_params();
+            guard.documents.insert(document.to_string(), tracked);
+            params
+        };
+        let server = match self.running_server(language) {
+            Some(server) => server,
+            None if self.server_status(language) == ServerStatus::Stopped => {
+                // 啟動時會重播已開啟的文件。 / Starting replays every open document.
+                return self.start_server(language);
+            }
+            None => return Ok(()),
+        };
+        server.notify("textDocument/didOpen", params)
+    }
+
+    /// Sends the full new text of a tracked document via `didChange`.
+    /// （以 `didChange` 送出已追蹤文件的完整新內容。）
+    pub fn change_document(&self, document: &str, text: &str) -> Result<(), LspClientError> {
+        let (language, params) = {
+            let mut guard = self.state.write().expect("LSP state poisoned");
+            let Some(tracked) = guard.documents.get_mut(document) else {
+                return Ok(());
+            };
+            if tracked.text == text {
+                return Ok(());
+            }
+            tracked.version += 1;
+            tracked.text = text.to_string();
+            let params = json!({
+                "textDocument": { "uri": tracked.uri, "version": tracked.version },
+                "contentChanges": [{ "text": text }],
+            });
+            (tracked.language.clone(), params)
+        };
+        match self.running_server(&language) {
+            Some(server) => server.notify("textDocument/didChange", params),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops tracking a document and sends `didClose`.
+    /// （停止追蹤文件並送出 `didClose`。）
+    pub fn close_document(&self, document: &str) -> Result<(), LspClientError> {
+        let tracked = {
+            let mut guard = self.state.write().expect("LSP state poisoned");
+            let Some(tracked) = guard.documents.remove(document) else {
+                return Ok(());
+            };
+            guard.published.remove(&tracked.uri);
+            tracked
+        };
+        match self.running_server(&tracked.language) {
+            Some(server) => server.notify(
+                "textDocument/didClose",
+                json!({ "textDocument": { "uri": tracked.uri } }),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Locations defining the symbol at `position` (`textDocument/definition`).
+    /// （`position` 處符號的定義位置（`textDocument/definition`）。）
+    pub fn goto_definition(
+        &self,
+        document: &str,
+        position: TextPosition,
+    ) -> Result<Vec<Location>, LspClientError> {
+        let (result, mut texts) = self.document_request(
+            document,
+            "textDocument/definition",
+            Some(position),
+            json!({}),
+        )?;
+        Ok(navigation::parse_locations(&result, &mut texts))
+    }
+
+    /// Hover text for the symbol at `position`, if the server has any.
+    /// （`position` 處符號的懸停說明（若伺服器有提供）。）
+    pub fn hover(
+        &self,
+        document: &str,
+        position: TextPosition,
+    ) -> Result<Option<Hover>, LspClientError> {
+        let (result, mut texts) =
+            self.document_request(document, "textDocument/hover", Some(position), json!({}))?;
+        let uri = document_uri(document);
+        Ok(navigation::parse_hover(&result, texts.get(&uri)))
+    }
+
+    /// References to the symbol at `position`, shaped as a search report for the results panel.
+    /// （`position` 處符號的所有參考，整理為供結果面板使用的搜尋報告。）
+    pub fn references(
+        &self,
+        document: &str,
+        position: TextPosition,
+        include_declaration: bool,
+    ) -> Result<SearchReport, LspClientError> {
+        let (result, mut texts) = self.document_request(
+            document,
+            "textDocument/references",
+            Some(position),
+            json!({ "context": { "includeDeclaration": include_declaration } }),
+        )?;
+        let locations = navigation::parse_locations(&result, &mut texts);
+        Ok(navigation::references_report(&locations, &mut texts))
+    }
+
+    /// Symbols declared in the document, flattened in source order.
+    /// （文件中宣告的符號，依原始碼順序攤平。）
+    ///
+    /// Results (including failures) are cached per document version, so repeated calls
+    /// between edits do not reach the server again.
+    /// （結果（包含失敗）依文件版本快取，編輯之間的重複呼叫不會再次詢問伺服器。）
+    pub fn document_symbols(&self, document: &str) -> Result<Vec<DocumentSymbol>, LspClientError> {
+        let version = {
+            let guard = self.state.read().expect("LSP state poisoned");
+            let tracked = guard
+                .documents
+                .get(document)
+                .ok_or_else(|| LspClientError::DocumentNotOpen(document.to_string()))?;
+            match &tracked.symbols {
+                Some((version, Ok(symbols))) if *version == tracked.version => {
+                    return Ok(symbols.clone())
+                }
+                Some((version, Err(message))) if *version == tracked.version => {
+                    return Err(LspClientError::CachedFailure(message.clone()))
+                }
+                _ => tracked.version,
+            }
+        };
+        let outcome = self
+            .document_request(document, "textDocument/documentSymbol", None, json!({}))
+            .map(|(result, mut texts)| {
+                navigation::parse_document_symbols(&result, texts.get(&document_uri(document)))
+            });
+        let mut guard = self.state.write().expect("LSP state poisoned");
+        if let Some(tracked) = guard.documents.get_mut(document) {
+            if tracked.version == version {
+                let cached = match &outcome {
+                    Ok(symbols) => Ok(symbols.clone()),
+                    Err(err) => Err(err.to_string()),
+                };
+                tracked.symbols = Some((version, cached));
+            }
+        }
+        outcome
+    }
+
+    /// Edits renaming the symbol at `position` across the workspace; nothing is applied.
+    /// （將 `position` 處符號於整個工作區更名所需的編輯；不會自動套用。）
+    pub fn rename(
+        &self,
+        document: &str,
+        position: TextPosition,
+        new_name: &str,
+    ) -> Result<WorkspaceEdit, LspClientError> {
+        let (result, mut texts) = self.document_request(
+            document,
+            "textDocument/rename",
+            Some(position),
+            json!({ "newName": new_name }),
+        )?;
+        navigation::parse_workspace_edit(&result, &mut texts)
+    }
+
+    /// Symbols of the open `language` document whose text equals `source`, if any.
+    /// （語言為 `language` 且內容等於 `source` 之已開啟文件的符號。）
+    ///
+    /// With `wait` unset only symbols already fetched for the current version are returned.
+    /// （未設定 `wait` 時只回傳目前版本已取得的符號。）
+    pub(crate) fn symbols_for_text(
+        &self,
+        language: &str,
+        source: &str,
+        wait: bool,
+    ) -> Option<Vec<DocumentSymbol>> {
+        let document = {
+            let guard = self.state.read().expect("LSP state poisoned");
+            guard
+                .documents
+                .iter()
+                .find(|(_, tracked)| tracked.language == language && tracked.text == source)
+                .map(|(document, _)| document.clone())?
+        };
+        if !wait {
+            let guard = self.state.read().expect("LSP state poisoned");
+            let tracked = guard.documents.get(&document)?;
+            return match &tracked.symbols {
+                Some((version, Ok(symbols))) if *version == tracked.version => {
+                    Some(symbols.clone())
+                }
+                _ => None,
+            };
+        }
+        self.document_symbols(&document).ok()
+    }
+
+    /// Sends a `textDocument/*` request for a tracked document, returning the raw result and
+    /// the texts of the open documents for resolving ranges.
+    /// （對已追蹤文件送出 `textDocument/*` 請求，回傳原始結果與用於換算範圍的已開啟文件內容。）
+    fn document_request(
+        &self,
+        document: &str,
+        method: &str,
+        position: Option<TextPosition>,
+        extra: Value,
+    ) -> Result<(Value, DocumentTexts), LspClientError> {
+        let (language, mut params, texts) = {
+            let guard = self.state.read().expect("LSP state poisoned");
+            let tracked = guard
+                .documents
+                .get(document)
+                .ok_or_else(|| LspClientError::DocumentNotOpen(document.to_string()))?;
+            let mut params = json!({ "textDocument": { "uri": tracked.uri } });
+            if let Some(position) = position {
+                params["position"] =
+                    protocol::lsp_position(&tracked.text, position.line, position.column);
+            }
+            let texts = DocumentTexts::new(
+                guard
+                    .documents
+                    .values()
+                    .map(|document| (document.uri.clone(), document.text.clone())),
+            );
+            (tracked.language.clone(), params, texts)
+        };
+        if let (Value::Object(params), Value::Object(extra)) = (&mut params, extra) {
+            params.extend(extra);
+        }
+        let server = self
+            .running_server(&language)
+            .ok_or(LspClientError::NotRunning(language))?;
+        let result = server.request(method, params)?;
+        Ok((result, texts))
+    }
+
+    fn is_tracked(&self, document: &str) -> bool {
+        let guard = self.state.read().expect("LSP state poisoned");
+        guard.documents.contains_key(document)
+    }
+
+    fn set_status(&self, language: &str, status: ServerStatus) {
+        let mut guard = self.state.write().expect("LSP state poisoned");
+        guard.ensure_language(language.to_string()).status = status;
+    }
+
+    /// Returns the live server for `language`, marking it failed if the process has died.
+    /// （回傳 `language` 仍在執行的伺服器；若程序已結束則標記為失敗。）
+    fn running_server(&self, language: &str) -> Option<Arc<LanguageServer>> {
+        let mut servers = self.servers.lock().expect("LSP servers poisoned");
+        let server = servers.get(language)?;
+        if server.is_running() {
+            return Some(Arc::clone(server));
+        }
+        servers.remove(language);
+        drop(servers);
+        self.set_status(
+            language,
+            ServerStatus::Failed(LspClientError::Exited.to_string()),
+        );
+        None
     }
 
     pub fn set_online(&self, online: bool) {
@@ -80,14 +463,46 @@ impl LspClient {
         session.diagnostics = diagnostics;
     }
 
+    /// Diagnostics for the language: those published by its server when one is configured,
+    /// otherwise the in-memory list.
+    /// （語言的診斷：若已設定伺服器則為其發佈的診斷，否則為記憶體內清單。）
     pub fn diagnostics(&self, language: &str) -> Vec<Diagnostic> {
         let guard = self.state.read().expect("LSP state poisoned");
+        let Some(session) = guard.languages.get(language) else {
+            return Vec::new();
+        };
+        if session.status == ServerStatus::NotConfigured {
+            return session.diagnostics.clone();
+        }
         guard
-            .languages
-            .get(language)
-            .map(|session| session.diagnostics.clone())
+            .published
+            .values()
+            .filter(|published| published.language == language)
+            .flat_map(|published| published.diagnostics.iter().cloned())
+            .collect()
+    }
+
+    /// Diagnostics the server published for one document, ordered by position.
+    /// （伺服器針對單一文件發佈的診斷，依位置排序。）
+    pub fn document_diagnostics(&self, document: &str) -> Vec<Diagnostic> {
+        let guard = self.state.read().expect("LSP state poisoned");
+        guard
+            .published
+            .get(&document_uri(document))
+            .map(|published| published.diagnostics.clone())
             .unwrap_or_default()
     }
+
+    /// Every published diagnostic across documents, grouped by URI then position.
+    /// （所有文件已發佈的診斷，依 URI 與位置排序。）
+    pub fn problems(&self) -> Vec<Diagnostic> {
+        let guard = self.state.read().expect("LSP state poisoned");
+        guard
+            .published
+            .values()
+            .flat_map(|published| published.diagnostics.iter().cloned())
+            .collect()
+    }
 }
 
 impl Default for LspClient {
@@ -122,29 +537,128 @@ impl LspBridge for LspClient {
             Some(session) if session.enabled => session,
             _ => return Err(LspError::Disabled),
         };
+        if session.status == ServerStatus::NotConfigured {
+            return Ok(filter_suggestions(
+                session.suggestions.iter().cloned(),
+                params.prefix,
+                params.max_items,
+            ));
+        }
+
+        let request = params
+            .document
+            .and_then(|document| guard.documents.get(document))
+            .map(|document| {
+                let (line, column) = params
+                    .position
+                    .unwrap_or_else(|| protocol::end_of_text(&document.text));
+                json!({
+                    "textDocument": { "uri": document.uri },
+                    "position": protocol::lsp_position(&document.text, line, column),
+                })
+            });
+        drop(guard);
 
-        let prefix_lower = params.prefix.to_ascii_lowercase();
-        let mut suggestions = Vec::new();
-        for suggestion in &session.suggestions {
-            if params.prefix.is_empty()
+        let server = self.running_server(language).ok_or_else(|| {
+            LspError::Backend(format!("No language server running for {language}"))
+        })?;
+        let request = request.ok_or_else(|| {
+            LspError::Backend("Document not opened with the language server".into())
+        })?;
+        let result = server
+            .request("textDocument/completion", request)
+            .map_err(|err| LspError::Backend(err.to_string()))?;
+        Ok(filter_suggestions(
+            protocol::parse_completion(&result),
+            params.prefix,
+            params.max_items,
+        ))
+    }
+}
+
+fn filter_suggestions(
+    suggestions: impl IntoIterator<Item = LspSuggestion>,
+    prefix: &str,
+    max_items: usize,
+) -> Vec<LspSuggestion> {
+    let prefix_lower = prefix.to_ascii_lowercase();
+    suggestions
+        .into_iter()
+        .filter(|suggestion| {
+            prefix.is_empty()
                 || suggestion
                     .label
                     .to_ascii_lowercase()
                     .starts_with(&prefix_lower)
-            {
-                suggestions.push(suggestion.clone());
-            }
-            if suggestions.len() >= params.max_items {
-                break;
-            }
-        }
-        Ok(suggestions)
+        })
+        .take(max_items)
+        .collect()
+}
+
+fn handle_notification(state: &RwLock<InnerState>, language: &str, method: &str, params: Value) {
+    if method != "textDocument/publishDiagnostics" {
+        return;
+    }
+    let mut guard = state.write().expect("LSP state poisoned");
+    let parsed = protocol::parse_diagnostics(&params, |uri| {
+        guard
+            .documents
+            .values()
+            .find(|document| document.uri == uri)
+            .map(|document| document.text.as_str())
+    });
+    let Some((uri, diagnostics)) = parsed else {
+        return;
+    };
+    if diagnostics.is_empty() {
+        guard.published.remove(&uri);
+    } else {
+        guard.published.insert(
+            uri,
+            PublishedDiagnostics {
+                language: language.to_string(),
+                diagnostics,
+            },
+        );
     }
 }
 
 struct InnerState {
     online: bool,
     languages: HashMap<String, LanguageSession>,
+    configs: HashMap<String, LanguageServerConfig>,
+    documents: HashMap<String, TrackedDocument>,
+    /// Diagnostics published by servers, keyed by document URI.
+    /// （伺服器發佈的診斷，以文件 URI 為鍵。）
+    published: BTreeMap<String, PublishedDiagnostics>,
+}
+
+struct PublishedDiagnostics {
+    language: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+struct TrackedDocument {
+    uri: String,
+    language: String,
+    version: i64,
+    text: String,
+    /// Document symbols for `version`, or the error the server returned for it.
+    /// （`version` 版本的文件符號，或伺服器對該版本回傳的錯誤。）
+    symbols: Option<(i64, Result<Vec<DocumentSymbol>, String>)>,
+}
+
+impl TrackedDocument {
+    fn did_open_params(&self) -> Value {
+        json!({
+            "textDocument": {
+                "uri": self.uri,
+                "languageId": self.language,
+                "version": self.version,
+                "text": self.text,
+            }
+        })
+    }
 }
 
 impl InnerState {
@@ -152,6 +666,9 @@ impl InnerState {
         let mut state = Self {
             online: true,
             languages: HashMap::new(),
+            configs: HashMap::new(),
+            documents: HashMap::new(),
+            published: BTreeMap::new(),
         };
         state.languages.insert(
             "rust".into(),
@@ -179,6 +696,7 @@ impl InnerState {
                     "No diagnostics from rust-analyzer",
                     DiagnosticSeverity::Information,
                 )],
+                status: ServerStatus::NotConfigured,
             },
         );
 
@@ -197,6 +715,7 @@ impl InnerState {
                     "Schema validation not configured",
                     DiagnosticSeverity::Hint,
                 )],
+                status: ServerStatus::NotConfigured,
             },
         );
         state
@@ -209,6 +728,7 @@ impl InnerState {
                 enabled: true,
                 suggestions: Vec::new(),
                 diagnostics: Vec::new(),
+                status: ServerStatus::NotConfigured,
             })
     }
 }
@@ -217,6 +737,7 @@ struct LanguageSession {
     enabled: bool,
     suggestions: Vec<LspSuggestion>,
     diagnostics: Vec<Diagnostic>,
+    status: ServerStatus,
 }
 
 #[cfg(test)]
@@ -235,6 +756,7 @@ mod tests {
             trigger: rustnotepad_autocomplete::CompletionTrigger::Automatic,
             context: &rustnotepad_autocomplete::CompletionContext::default(),
             max_items: 8,
+            position: None,
         });
         assert!(matches!(result, Err(LspError::Backend(_))));
     }
@@ -250,6 +772,7 @@ mod tests {
                 trigger: rustnotepad_autocomplete::CompletionTrigger::Automatic,
                 context: &rustnotepad_autocomplete::CompletionContext::default(),
                 max_items: 8,
+                position: None,
             })
             .unwrap();
         assert_eq!(response.len(), 1);
@@ -0,0 +1,633 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rustnotepad_function_list::{FunctionEntry, FunctionKind, FunctionParser, TextRange};
+use rustnotepad_search::{FileSearchResult, SearchMatch, SearchReport};
+use serde_json::Value;
+
+use crate::diagnostics::LspRange;
+use crate::protocol::{parse_range, uri_to_document, LineIndex};
+use crate::server::LspClientError;
+use crate::LspClient;
+
+/// A position in some document, as returned by definition and reference requests.
+/// （定義與參考請求回傳的文件位置。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub uri: String,
+    pub range: LspRange,
+}
+
+/// Hover information rendered as plain text or markdown.
+/// （以純文字或 Markdown 呈現的懸停資訊。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hover {
+    pub contents: String,
+    pub range: Option<LspRange>,
+}
+
+/// Kind of a document symbol (LSP `SymbolKind`).
+/// （文件符號的種類，對應 LSP `SymbolKind`。）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    File,
+    Module,
+    Namespace,
+    Package,
+    Class,
+    Method,
+    Property,
+    Field,
+    Constructor,
+    Enum,
+    Interface,
+    Function,
+    Variable,
+    Constant,
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Key,
+    Null,
+    EnumMember,
+    Struct,
+    Event,
+    Operator,
+    TypeParameter,
+}
+
+impl SymbolKind {
+    const ALL: [SymbolKind; 26] = [
+        SymbolKind::File,
+        SymbolKind::Module,
+        SymbolKind::Namespace,
+        SymbolKind::Package,
+        SymbolKind::Class,
+        SymbolKind::Method,
+        SymbolKind::Property,
+        SymbolKind::Field,
+        SymbolKind::Constructor,
+        SymbolKind::Enum,
+        SymbolKind::Interface,
+        SymbolKind::Function,
+        SymbolKind::Variable,
+        SymbolKind::Constant,
+        SymbolKind::String,
+        SymbolKind::Number,
+        SymbolKind::Boolean,
+        SymbolKind::Array,
+        SymbolKind::Object,
+        SymbolKind::Key,
+        SymbolKind::Null,
+        SymbolKind::EnumMember,
+        SymbolKind::Struct,
+        SymbolKind::Event,
+        SymbolKind::Operator,
+        SymbolKind::TypeParameter,
+    ];
+
+    /// Maps the protocol's 1-based numeric kind.
+    /// （對應協定中從 1 起算的數值種類。）
+    pub fn from_lsp(kind: u64) -> Option<Self> {
+        let index = usize::try_from(kind).ok()?.checked_sub(1)?;
+        Self::ALL.get(index).copied()
+    }
+
+    /// Function-list category for outline-worthy symbols; `None` for variables, fields and the like.
+    /// （可列入大綱之符號的函式清單分類；變數、欄位等回傳 `None`。）
+    pub fn function_kind(self) -> Option<FunctionKind> {
+        match self {
+            SymbolKind::Function => Some(FunctionKind::Function),
+            SymbolKind::Method | SymbolKind::Constructor => Some(FunctionKind::Method),
+            SymbolKind::Class | SymbolKind::Interface => Some(FunctionKind::Class),
+            SymbolKind::Struct => Some(FunctionKind::Struct),
+            SymbolKind::Enum => Some(FunctionKind::Enum),
+            SymbolKind::Module | SymbolKind::Namespace | SymbolKind::Package => {
+                Some(FunctionKind::Region)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Symbol reported by `textDocument/documentSymbol`, flattened with its container name.
+/// （`textDocument/documentSymbol` 回報的符號，已攤平並保留容器名稱。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub detail: Option<String>,
+    pub container: Option<String>,
+    pub range: LspRange,
+    pub selection_range: LspRange,
+}
+
+/// Replacement of a span of text.
+/// （取代一段文字的編輯。）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: LspRange,
+    pub new_text: String,
+}
+
+/// Edits to apply across documents, keyed by URI (result of `textDocument/rename`).
+/// （跨文件套用的編輯，以 URI 為鍵，為 `textDocument/rename` 的結果。）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceEdit {
+    pub changes: BTreeMap<String, Vec<TextEdit>>,
+}
+
+impl WorkspaceEdit {
+    pub fn is_empty(&self) -> bool {
+        self.changes.values().all(Vec::is_empty)
+    }
+
+    pub fn edit_count(&self) -> usize {
+        self.changes.values().map(Vec::len).sum()
+    }
+}
+
+/// Applies edits whose byte ranges were resolved against `text`.
+/// （套用以 `text` 換算出位元組範圍的編輯。）
+///
+/// The whole set is checked first: an edit without a byte range, one that does not fit `text`,
+/// or two edits that overlap fail the call and leave nothing applied.
+/// （會先檢查整組編輯：缺少位元組範圍、超出 `text` 或彼此重疊的編輯都會使呼叫失敗，且不套用任何編輯。）
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> Result<String, LspClientError> {
+    let mut resolved = Vec::with_capacity(edits.len());
+    for edit in edits {
+        let bytes = edit
+            .range
+            .bytes
+            .clone()
+            .filter(|bytes| {
+                bytes.start <= bytes.end
+                    && bytes.end <= text.len()
+                    && text.is_char_boundary(bytes.start)
+                    && text.is_char_boundary(bytes.end)
+            })
+            .ok_or_else(|| {
+                LspClientError::InvalidEdit(format!(
+                    "edit at line {}, column {} does not fit the document",
+                    edit.range.start.line + 1,
+                    edit.range.start.column + 1
+                ))
+            })?;
+        resolved.push((bytes, edit.new_text.as_str()));
+    }
+    // 同位置的插入依原順序保留。 / Insertions at the same offset keep their original order.
+    resolved.sort_by_key(|(bytes, _)| (bytes.start, bytes.end));
+    for pair in resolved.windows(2) {
+        let (previous, next) = (&pair[0].0, &pair[1].0);
+        if next.start < previous.end {
+            return Err(LspClientError::InvalidEdit(format!(
+                "edits at bytes {}..{} and {}..{} overlap",
+                previous.start, previous.end, next.start, next.end
+            )));
+        }
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (bytes, new_text) in resolved {
+        result.push_str(&text[copied..bytes.start]);
+        result.push_str(new_text);
+        copied = bytes.end;
+    }
+    result.push_str(&text[copied..]);
+    Ok(result)
+}
+
+/// Converts reference locations into the search-results model shown by the Find Results panel.
+/// （將參考位置轉換為「尋找結果」面板使用的搜尋結果模型。）
+///
+/// Offsets are given for the text with every line break normalized to `\n`, matching what the
+/// editor loads, and come from the same line index as the line and column.
+/// （位元組位置以所有換行皆正規化為 `\n` 的文字（即編輯器載入的內容）計算，並與行、欄使用同一個行索引。）
+pub(crate) fn references_report(locations: &[Location], texts: &mut DocumentTexts) -> SearchReport {
+    let mut grouped: BTreeMap<&str, Vec<SearchMatch>> = BTreeMap::new();
+    for location in locations {
+        let Some(bytes) = location.range.bytes.clone() else {
+            continue;
+        };
+        let Some(text) = texts.get(&location.uri) else {
+            continue;
+        };
+        let index = LineIndex::new(text);
+        let (start_line, start_byte) = index.locate(bytes.start);
+        let (end_line, end_byte) = index.locate(bytes.end);
+        let start = index.normalized_offset(start_line, start_byte);
+        let end = index.normalized_offset(end_line, end_byte);
+        let line_text = index.line(start_line).unwrap_or_default();
+        grouped.entry(&location.uri).or_default().push(SearchMatch {
+            matched: text.get(bytes.clone()).unwrap_or_default().to_string(),
+            start,
+            end,
+            line: start_line + 1,
+            column: line_text[..start_byte].chars().count() + 1,
+            line_text: line_text.to_string(),
+            is_marked: false,
+        });
+    }
+
+    let results = grouped
+        .into_iter()
+        .map(|(uri, mut matches)| {
+            matches.sort_by_key(|hit| hit.start);
+            let path = uri
+                .starts_with("file://")
+                .then(|| PathBuf::from(uri_to_document(uri)));
+            FileSearchResult::new(path, matches)
+        })
+        .collect();
+    SearchReport::new(results)
+}
+
+/// Document contents keyed by URI: the client's copies of open documents, with other
+/// `file://` documents read from disk on demand.
+/// （以 URI 為鍵的文件內容：已開啟文件使用用戶端持有的內容，其他 `file://` 文件則按需自磁碟讀取。）
+pub(crate) struct DocumentTexts {
+    texts: HashMap<String, Option<String>>,
+}
+
+impl DocumentTexts {
+    pub(crate) fn new(open: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            texts: open
+                .into_iter()
+                .map(|(uri, text)| (uri, Some(text)))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, uri: &str) -> Option<&str> {
+        self.texts
+            .entry(uri.to_string())
+            .or_insert_with(|| {
+                uri.starts_with("file://")
+                    .then(|| fs::read_to_string(uri_to_document(uri)).ok())
+                    .flatten()
+            })
+            .as_deref()
+    }
+}
+
+/// Parses `Location`, `Location[]` or `LocationLink[]` results.
+/// （解析 `Location`、`Location[]` 或 `LocationLink[]` 結果。）
+pub(crate) fn parse_locations(result: &Value, texts: &mut DocumentTexts) -> Vec<Location> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        Value::Object(_) => std::slice::from_ref(result),
+        _ => &[],
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let (uri, range) = match item.get("targetUri") {
+                Some(uri) => (
+                    uri.as_str()?,
+                    item.get("targetSelectionRange")
+                        .or_else(|| item.get("targetRange"))?,
+                ),
+                None => (item.get("uri")?.as_str()?, item.get("range")?),
+            };
+            // 無法讀取的文件無從換算 UTF-16 欄位。 / Without the document text the UTF-16 columns cannot be resolved.
+            let range = parse_range(range, texts.get(uri)?)?;
+            Some(Location {
+                uri: uri.to_string(),
+                range,
+            })
+        })
+        .collect()
+}
+
+/// Parses a `textDocument/hover` result into plain text.
+/// （將 `textDocument/hover` 結果解析為純文字。）
+pub(crate) fn parse_hover(result: &Value, text: Option<&str>) -> Option<Hover> {
+    fn marked(value: &Value) -> Option<String> {
+        match value {
+            Value::String(text) => Some(text.clone()),
+            Value::Object(object) => object
+                .get("value")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            _ => None,
+        }
+    }
+
+    let contents = match result.get("contents")? {
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(marked)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        other => marked(other)?,
+    };
+    if contents.trim().is_empty() {
+        return None;
+    }
+    Some(Hover {
+        contents,
+        range: result
+            .get("range")
+            .zip(text)
+            .and_then(|(range, text)| parse_range(range, text)),
+    })
+}
+
+/// Parses hierarchical `DocumentSymbol[]` or flat `SymbolInformation[]` results.
+/// （解析階層式 `DocumentSymbol[]` 或扁平 `SymbolInformation[]` 結果。）
+pub(crate) fn parse_document_symbols(result: &Value, text: Option<&str>) -> Vec<DocumentSymbol> {
+    fn visit(item: &Value, container: Option<&str>, text: &str, symbols: &mut Vec<DocumentSymbol>) {
+        let Some(name) = item.get("name").and_then(Value::as_str) else {
+            return;
+        };
+        let Some(kind) = item
+            .get("kind")
+            .and_then(Value::as_u64)
+            .and_then(SymbolKind::from_lsp)
+        else {
+            return;
+        };
+        // SymbolInformation 以 location.range 表示範圍。 / SymbolInformation nests its range in `location`.
+        let range = item
+            .get("range")
+            .or_else(|| {
+                item.get("location")
+                    .and_then(|location| location.get("range"))
+            })
+            .and_then(|range| parse_range(range, text));
+        let Some(range) = range else {
+            return;
+        };
+        let selection_range = item
+            .get("selectionRange")
+            .and_then(|range| parse_range(range, text))
+            .unwrap_or_else(|| range.clone());
+        let container = item
+            .get("containerName")
+            .and_then(Value::as_str)
+            .or(container);
+        symbols.push(DocumentSymbol {
+            name: name.to_string(),
+            kind,
+            detail: item
+                .get("detail")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            container: container.map(str::to_string),
+            range,
+            selection_range,
+        });
+        if let Some(children) = item.get("children").and_then(Value::as_array) {
+            for child in children {
+                visit(child, Some(name), text, symbols);
+            }
+        }
+    }
+
+    let mut symbols = Vec::new();
+    let Some(text) = text else {
+        return symbols;
+    };
+    for item in result.as_array().map(Vec::as_slice).unwrap_or_default() {
+        visit(item, None, text, &mut symbols);
+    }
+    symbols.sort_by_key(|symbol| symbol.range.start);
+    symbols
+}
+
+/// Parses a `WorkspaceEdit` (`changes` map or `documentChanges` list).
+/// （解析 `WorkspaceEdit`，支援 `changes` 對應表或 `documentChanges` 清單。）
+///
+/// Fails when a document to edit cannot be read, since its edits could not be resolved.
+/// （若無法讀取需編輯的文件則失敗，因為其編輯無法換算。）
+pub(crate) fn parse_workspace_edit(
+    result: &Value,
+    texts: &mut DocumentTexts,
+) -> Result<WorkspaceEdit, LspClientError> {
+    let mut raw: Vec<(&str, &Value)> = Vec::new();
+    if let Some(changes) = result.get("changes").and_then(Value::as_object) {
+        raw.extend(changes.iter().map(|(uri, edits)| (uri.as_str(), edits)));
+    }
+    if let Some(document_changes) = result.get("documentChanges").and_then(Value::as_array) {
+        raw.extend(document_changes.iter().filter_map(|change| {
+            let uri = change.get("textDocument")?.get("uri")?.as_str()?;
+            Some((uri, change.get("edits")?))
+        }));
+    }
+
+    let mut edit = WorkspaceEdit::default();
+    for (uri, edits) in raw {
+        let text = texts
+            .get(uri)
+            .ok_or_else(|| LspClientError::DocumentUnavailable(uri.to_string()))?;
+        let parsed = edits
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|item| {
+                Some(TextEdit {
+                    range: parse_range(item.get("range")?, text)?,
+                    new_text: item.get("newText")?.as_str()?.to_string(),
+                })
+            });
+        edit.changes
+            .entry(uri.to_string())
+            .or_default()
+            .extend(parsed);
+    }
+    Ok(edit)
+}
+
+/// Function-list parser backed by `textDocument/documentSymbol`.
+/// （以 `textDocument/documentSymbol` 為後盾的函式清單解析器。）
+///
+/// The source is matched to the open document of the same language holding identical text;
+/// when none matches or the server cannot answer, the optional fallback parser is used.
+/// （以相同語言且內容相同的已開啟文件對應來源文字；若無對應或伺服器無法回應，則改用備援解析器。）
+pub struct LspSymbolParser {
+    client: Arc<LspClient>,
+    language: String,
+    fallback: Option<Box<dyn FunctionParser>>,
+    wait: bool,
+}
+
+impl LspSymbolParser {
+    pub fn new(client: Arc<LspClient>, language: impl Into<String>) -> Self {
+        Self {
+            client,
+            language: language.into(),
+            fallback: None,
+            wait: true,
+        }
+    }
+
+    /// Never asks the server; uses only symbols fetched earlier (e.g. by an
+    /// [`LspWorker`](crate::LspWorker)) and the fallback otherwise.
+    /// （不向伺服器查詢；僅使用先前（例如由 [`LspWorker`](crate::LspWorker)）取得的符號，
+    /// 否則改用備援剖析器。）
+    pub fn without_waiting(mut self) -> Self {
+        self.wait = false;
+        self
+    }
+
+    pub fn with_fallback(mut self, fallback: Option<Box<dyn FunctionParser>>) -> Self {
+        self.fallback = fallback;
+        self
+    }
+}
+
+impl FunctionParser for LspSymbolParser {
+    fn parse(&self, source: &str) -> Vec<FunctionEntry> {
+        match self
+            .client
+            .symbols_for_text(&self.language, source, self.wait)
+        {
+            Some(symbols) => symbols
+                .iter()
+                .filter_map(|symbol| {
+                    let kind = symbol.kind.function_kind()?;
+                    let bytes = symbol.range.bytes.clone()?;
+                    Some(FunctionEntry::new(
+                        symbol.name.clone(),
+                        kind,
+                        TextRange::new(bytes.start, bytes.end),
+                    ))
+                })
+                .collect(),
+            None => self
+                .fallback
+                .as_ref()
+                .map(|parser| parser.parse(source))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::TextPosition;
+    use serde_json::json;
+
+    #[test]
+    fn workspace_edits_apply_back_to_front() {
+        let text = "let value = 1;\nprintln!(\"{value}\", value);\n";
+        let uri = "file:///tmp/main.rs";
+        let mut texts = DocumentTexts::new([(uri.to_string(), text.to_string())]);
+        let edit = |line: u32, start: u32, end: u32| {
+            json!({
+                "range": {
+                    "start": { "line": line, "character": start },
+                    "end": { "line": line, "character": end }
+                },
+                "newText": "total"
+            })
+        };
+        let result = json!({
+            "documentChanges": [{
+                "textDocument": { "uri": uri, "version": 3 },
+                "edits": [edit(0, 4, 9), edit(1, 20, 25), edit(1, 11, 16)]
+            }]
+        });
+        let workspace_edit = parse_workspace_edit(&result, &mut texts).unwrap();
+        assert_eq!(workspace_edit.edit_count(), 3);
+        assert_eq!(
+            apply_text_edits(text, &workspace_edit.changes[uri]).unwrap(),
+            "let total = 1;\nprintln!(\"{total}\", total);\n"
+        );
+
+        let locations = parse_locations(
+            &json!([{ "targetUri": uri, "targetRange": {
+                "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 26 }
+            }, "targetSelectionRange": {
+                "start": { "line": 1, "character": 20 }, "end": { "line": 1, "character": 25 }
+            }}]),
+            &mut texts,
+        );
+        let report = references_report(&locations, &mut texts);
+        assert_eq!(report.total_matches, 1);
+        let hit = &report.results[0].matches[0];
+        assert_eq!((hit.line, hit.column), (2, 21));
+        assert_eq!(hit.matched, "value");
+        assert_eq!(hit.line_text, "println!(\"{value}\", value);");
+        assert_eq!(
+            report.results[0].path.as_deref(),
+            Some(std::path::Path::new("/tmp/main.rs"))
+        );
+    }
+
+    #[test]
+    fn conflicting_or_unresolved_edits_apply_nothing() {
+        let text = "let value = 1;\n";
+        let edit = |bytes: Option<std::ops::Range<usize>>| TextEdit {
+            range: LspRange {
+                start: TextPosition::default(),
+                end: TextPosition::default(),
+                bytes,
+            },
+            new_text: "x".into(),
+        };
+        assert!(matches!(
+            apply_text_edits(text, &[edit(Some(4..9)), edit(Some(6..12))]),
+            Err(LspClientError::InvalidEdit(_))
+        ));
+        assert!(matches!(
+            apply_text_edits(text, &[edit(Some(4..9)), edit(None)]),
+            Err(LspClientError::InvalidEdit(_))
+        ));
+        assert!(matches!(
+            apply_text_edits(text, &[edit(Some(12..40))]),
+            Err(LspClientError::InvalidEdit(_))
+        ));
+        // 同位置的插入依序套用。 / Insertions at one offset apply in order.
+        let mut second = edit(Some(4..4));
+        second.new_text = "y".into();
+        assert_eq!(
+            apply_text_edits(text, &[edit(Some(4..4)), second, edit(Some(4..9))]).unwrap(),
+            "let xyx = 1;\n"
+        );
+    }
+
+    #[test]
+    fn references_in_crlf_files_use_editor_offsets() {
+        let uri = "file:///tmp/crlf.rs";
+        let text = "fn a() {}\r\nfn b() {}\rfn c() { a(); }\r\n";
+        let mut texts = DocumentTexts::new([(uri.to_string(), text.to_string())]);
+        let locations = parse_locations(
+            &json!({ "uri": uri, "range": {
+                "start": { "line": 2, "character": 9 }, "end": { "line": 2, "character": 10 }
+            }}),
+            &mut texts,
+        );
+        assert_eq!(&text[locations[0].range.bytes.clone().unwrap()], "a");
+        let report = references_report(&locations, &mut texts);
+        let hit = &report.results[0].matches[0];
+        assert_eq!((hit.line, hit.column), (3, 10));
+        assert_eq!(hit.line_text, "fn c() { a(); }");
+        let editor = "fn a() {}\nfn b() {}\nfn c() { a(); }\n";
+        assert_eq!(&editor[hit.start..hit.end], "a");
+    }
+
+    #[test]
+    fn ranges_in_unreadable_documents_are_not_guessed() {
+        let missing = "file:///rustnotepad-missing/😀.rs";
+        let mut texts = DocumentTexts::new([]);
+        let range = json!({
+            "start": { "line": 0, "character": 2 }, "end": { "line": 0, "character": 4 }
+        });
+        // UTF-16 欄位無法在沒有文字時換算。 / UTF-16 columns cannot be resolved without the text.
+        assert!(parse_locations(&json!({ "uri": missing, "range": range }), &mut texts).is_empty());
+        let result = json!({ "changes": { missing: [{ "range": range, "newText": "x" }] } });
+        assert!(matches!(
+            parse_workspace_edit(&result, &mut texts),
+            Err(LspClientError::DocumentUnavailable(uri)) if uri == missing
+        ));
+    }
+}
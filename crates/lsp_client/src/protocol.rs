@@ -0,0 +1,419 @@
+use std::ops::Range;
+use std::path::Path;
+
+use rustnotepad_autocomplete::{CompletionKind, LspSuggestion};
+use serde_json::{json, Value};
+
+use crate::diagnostics::{
+    Diagnostic, DiagnosticSeverity, LspRange, RelatedInformation, TextPosition,
+};
+
+/// Converts an editor document identifier into the URI sent to language servers.
+/// （將編輯器文件識別碼轉換為傳給語言伺服器的 URI。）
+///
+/// Absolute paths become `file://` URIs; anything else (e.g. `untitled-1`) uses the `untitled:` scheme.
+/// （絕對路徑轉為 `file://` URI，其餘識別碼（例如 `untitled-1`）使用 `untitled:` 配置。）
+pub fn document_uri(document: &str) -> String {
+    let normalized = document.replace('\\', "/");
+    let is_absolute = Path::new(document).is_absolute()
+        || normalized.starts_with('/')
+        || normalized.as_bytes().get(1) == Some(&b':');
+    if !is_absolute {
+        return format!("untitled:{}", percent_encode(&normalized));
+    }
+    if normalized.starts_with('/') {
+        format!("file://{}", percent_encode(&normalized))
+    } else {
+        format!("file:///{}", percent_encode(&normalized))
+    }
+}
+
+/// Inverse of [`document_uri`]: recovers the editor document identifier from a URI.
+/// （[`document_uri`] 的反向轉換：由 URI 取回編輯器文件識別碼。）
+pub fn uri_to_document(uri: &str) -> String {
+    if let Some(id) = uri.strip_prefix("untitled:") {
+        return percent_decode(id);
+    }
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+    let path = percent_decode(path);
+    // `file:///C:/dir` 對應 Windows 磁碟路徑。 / `file:///C:/dir` maps to a Windows drive path.
+    if path.as_bytes().get(2) == Some(&b':') {
+        path[1..].to_string()
+    } else {
+        path
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            if let Some(byte) = text
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Line boundaries of a text, treating `\r\n`, `\n` and `\r` as line breaks like LSP does.
+/// （文字的行界線；與 LSP 相同，將 `\r\n`、`\n` 與 `\r` 皆視為換行。）
+pub(crate) struct LineIndex<'a> {
+    text: &'a str,
+    /// Byte range of each line's content, without its line break.
+    /// （各行內容的位元組範圍，不含換行字元。）
+    lines: Vec<Range<usize>>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        let bytes = text.as_bytes();
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut index = 0;
+        while index < bytes.len() {
+            match bytes[index] {
+                b'\n' => {
+                    lines.push(start..index);
+                    start = index + 1;
+                }
+                b'\r' => {
+                    lines.push(start..index);
+                    if bytes.get(index + 1) == Some(&b'\n') {
+                        index += 1;
+                    }
+                    start = index + 1;
+                }
+                _ => {}
+            }
+            index += 1;
+        }
+        lines.push(start..text.len());
+        Self { text, lines }
+    }
+
+    /// Content of the zero-based `line`, without its line break.
+    /// （0 起算第 `line` 行的內容，不含換行字元。）
+    pub(crate) fn line(&self, line: usize) -> Option<&'a str> {
+        self.lines.get(line).map(|range| &self.text[range.clone()])
+    }
+
+    /// Zero-based (line, byte within that line) of a byte offset into the text.
+    /// （文字中位元組位置所在的 0 起算（行, 行內位元組）。）
+    pub(crate) fn locate(&self, offset: usize) -> (usize, usize) {
+        let line = self
+            .lines
+            .partition_point(|range| range.start <= offset)
+            .saturating_sub(1);
+        let start = self.lines[line].start;
+        (line, offset.min(self.lines[line].end) - start)
+    }
+
+    /// Byte offset of (line, byte within line) once every line break is a single `\n`,
+    /// which is how the editor holds text.
+    /// （將每個換行視為單一 `\n`（編輯器保存文字的方式）時，（行, 行內位元組）的位元組位置。）
+    pub(crate) fn normalized_offset(&self, line: usize, byte: usize) -> usize {
+        self.lines[..line.min(self.lines.len())]
+            .iter()
+            .map(|range| range.len() + 1)
+            .sum::<usize>()
+            + byte
+    }
+}
+
+/// Translates a zero-based (line, character column) into an LSP position measured in UTF-16 units.
+/// （將 0 起算的（行, 字元欄）轉換為以 UTF-16 單位計算的 LSP 位置。）
+pub(crate) fn lsp_position(text: &str, line: usize, column: usize) -> Value {
+    let character: usize = LineIndex::new(text)
+        .line(line)
+        .map(|line_text| line_text.chars().take(column).map(char::len_utf16).sum())
+        .unwrap_or(0);
+    json!({ "line": line, "character": character })
+}
+
+/// Resolves an LSP position (UTF-16 units) against the text, returning the character
+/// position and its byte offset. Positions past the end clamp to the end of the text.
+/// （依文字解析 LSP 位置（UTF-16 單位），回傳字元位置與其位元組位置；超出結尾時停在文字結尾。）
+pub(crate) fn resolve_position(text: &str, line: usize, character: usize) -> (TextPosition, usize) {
+    let index = LineIndex::new(text);
+    let Some(line_range) = index.lines.get(line) else {
+        let (line, column) = end_of_text(text);
+        return (TextPosition::new(line, column), text.len());
+    };
+    let mut units = 0;
+    let mut column = 0;
+    let mut bytes = 0;
+    for ch in text[line_range.clone()].chars() {
+        if units >= character {
+            break;
+        }
+        units += ch.len_utf16();
+        column += 1;
+        bytes += ch.len_utf8();
+    }
+    (TextPosition::new(line, column), line_range.start + bytes)
+}
+
+/// Zero-based (line, character column) of the end of the text.
+/// （文字結尾的 0 起算（行, 字元欄）。）
+pub(crate) fn end_of_text(text: &str) -> (usize, usize) {
+    let index = LineIndex::new(text);
+    let line = index.lines.len() - 1;
+    (line, index.line(line).unwrap_or("").chars().count())
+}
+
+/// Builds the `initialize` parameters advertising what the editor understands.
+/// （建立 `initialize` 參數，宣告編輯器支援的能力。）
+pub(crate) fn initialize_params(root_uri: Option<String>, options: Option<Value>) -> Value {
+    json!({
+        "processId": std::process::id(),
+        "clientInfo": { "name": "RustNotePad", "version": env!("CARGO_PKG_VERSION") },
+        "rootUri": root_uri,
+        "initializationOptions": options,
+        "capabilities": {
+            "textDocument": {
+                "synchronization": { "didSave": false, "dynamicRegistration": false },
+                "completion": {
+                    "completionItem": { "snippetSupport": true },
+                    "contextSupport": false
+                },
+                "publishDiagnostics": { "relatedInformation": false }
+            },
+            "workspace": { "configuration": true }
+        }
+    })
+}
+
+/// Parses a `textDocument/completion` result (item array or `CompletionList`).
+/// （解析 `textDocument/completion` 結果（項目陣列或 `CompletionList`）。）
+pub(crate) fn parse_completion(result: &Value) -> Vec<LspSuggestion> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        Value::Object(list) => list
+            .get("items")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+        _ => &[],
+    };
+
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let label = item.get("label")?.as_str()?.to_string();
+            let insert_text = item
+                .get("textEdit")
+                .and_then(|edit| edit.get("newText"))
+                .or_else(|| item.get("insertText"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let detail = item
+                .get("detail")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let kind = item
+                .get("kind")
+                .and_then(Value::as_u64)
+                .map(completion_kind);
+            // 保留伺服器排序。 / Preserve the server's ordering.
+            let relevance = (0.99 - index as f32 * 0.001).max(0.5);
+            Some(LspSuggestion {
+                label,
+                insert_text,
+                detail,
+                kind,
+                relevance: Some(relevance),
+            })
+        })
+        .collect()
+}
+
+fn completion_kind(kind: u64) -> CompletionKind {
+    match kind {
+        1 => CompletionKind::Text,
+        9 => CompletionKind::Module,
+        14 => CompletionKind::Keyword,
+        15 => CompletionKind::Snippet,
+        17 | 19 => CompletionKind::FilePath,
+        _ => CompletionKind::Symbol,
+    }
+}
+
+/// Parses `textDocument/publishDiagnostics` parameters into the target URI and its diagnostics.
+/// （將 `textDocument/publishDiagnostics` 參數解析為目標 URI 與其診斷。）
+///
+/// `text_of` returns the client's copy of a document so ranges can be converted to character
+/// columns and byte offsets; without it columns stay in UTF-16 units and bytes are unknown.
+/// （`text_of` 回傳用戶端持有的文件內容，以便將範圍換算為字元欄與位元組位置；若無內容，
+/// 欄位維持 UTF-16 單位且無位元組範圍。）
+pub(crate) fn parse_diagnostics<'a>(
+    params: &Value,
+    text_of: impl Fn(&str) -> Option<&'a str>,
+) -> Option<(String, Vec<Diagnostic>)> {
+    let uri = params.get("uri")?.as_str()?.to_string();
+    let text = text_of(&uri);
+    let mut diagnostics: Vec<Diagnostic> = params
+        .get("diagnostics")?
+        .as_array()?
+        .iter()
+        .filter_map(|diagnostic| {
+            let message = diagnostic.get("message")?.as_str()?;
+            let severity = match diagnostic.get("severity").and_then(Value::as_u64) {
+                Some(2) => DiagnosticSeverity::Warning,
+                Some(3) => DiagnosticSeverity::Information,
+                Some(4) => DiagnosticSeverity::Hint,
+                _ => DiagnosticSeverity::Error,
+            };
+            let mut parsed = Diagnostic::new(message, severity).with_uri(uri.clone());
+            parsed.range = diagnostic
+                .get("range")
+                .zip(text)
+                .and_then(|(range, text)| parse_range(range, text));
+            parsed.code = diagnostic.get("code").and_then(|code| match code {
+                Value::String(code) => Some(code.clone()),
+                Value::Number(code) => Some(code.to_string()),
+                _ => None,
+            });
+            parsed.source = diagnostic
+                .get("source")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            parsed.related = diagnostic
+                .get("relatedInformation")
+                .and_then(Value::as_array)
+                .map(|related| {
+                    related
+                        .iter()
+                        .filter_map(|info| {
+                            let location = info.get("location")?;
+                            let uri = location.get("uri")?.as_str()?;
+                            let range = parse_range(location.get("range")?, text_of(uri)?)?;
+                            Some(RelatedInformation {
+                                uri: uri.to_string(),
+                                range,
+                                message: info.get("message")?.as_str()?.to_string(),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(parsed)
+        })
+        .collect();
+    diagnostics.sort_by_key(Diagnostic::start);
+    Some((uri, diagnostics))
+}
+
+/// Parses an LSP `Range` and resolves it against the document `text` it refers to.
+/// （解析 LSP `Range`，並依其所屬文件的 `text` 換算。）
+///
+/// LSP columns count UTF-16 code units, so they cannot be turned into character columns or
+/// byte offsets without the text; callers drop ranges whose document they cannot read.
+/// （LSP 欄位以 UTF-16 單位計算，沒有文字便無法換算為字元欄或位元組位置；呼叫端應捨棄
+/// 無法讀取其文件的範圍。）
+pub(crate) fn parse_range(range: &Value, text: &str) -> Option<LspRange> {
+    let position = |key: &str| -> Option<(usize, usize)> {
+        let position = range.get(key)?;
+        Some((
+            position.get("line")?.as_u64()? as usize,
+            position.get("character")?.as_u64()? as usize,
+        ))
+    };
+    let (start_line, start_character) = position("start")?;
+    let (end_line, end_character) = position("end")?;
+    let (start, start_byte) = resolve_position(text, start_line, start_character);
+    let (end, end_byte) = resolve_position(text, end_line, end_character);
+    Some(LspRange {
+        start,
+        end,
+        bytes: Some(start_byte..end_byte.max(start_byte)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_resolve_utf16_ranges_to_bytes() {
+        let text = "let 😀 = 1;\r\nlet x = y;\n";
+        let params = json!({
+            "uri": "file:///tmp/a.rs",
+            "diagnostics": [
+                {
+                    "range": {
+                        "start": { "line": 1, "character": 8 },
+                        "end": { "line": 1, "character": 9 }
+                    },
+                    "severity": 1,
+                    "code": 425,
+                    "source": "rustc",
+                    "message": "cannot find value `y`",
+                    "relatedInformation": [{
+                        "location": {
+                            "uri": "file:///tmp/a.rs",
+                            "range": {
+                                "start": { "line": 0, "character": 6 },
+                                "end": { "line": 0, "character": 7 }
+                            }
+                        },
+                        "message": "similar name"
+                    }]
+                },
+                {
+                    "range": {
+                        "start": { "line": 0, "character": 4 },
+                        "end": { "line": 0, "character": 6 }
+                    },
+                    "severity": 2,
+                    "message": "unused"
+                }
+            ]
+        });
+        let (uri, diagnostics) =
+            parse_diagnostics(&params, |uri| (uri == "file:///tmp/a.rs").then_some(text)).unwrap();
+        assert_eq!(uri, "file:///tmp/a.rs");
+        assert_eq!(diagnostics.len(), 2);
+
+        let unused = &diagnostics[0];
+        assert_eq!(unused.severity, DiagnosticSeverity::Warning);
+        assert_eq!(unused.start(), TextPosition::new(0, 4));
+        assert_eq!(&text[unused.byte_range().unwrap()], "😀");
+
+        let missing = &diagnostics[1];
+        assert_eq!(missing.code.as_deref(), Some("425"));
+        assert_eq!(missing.source.as_deref(), Some("rustc"));
+        assert_eq!(&text[missing.byte_range().unwrap()], "y");
+        assert_eq!(missing.related[0].range.start, TextPosition::new(0, 5));
+
+        assert_eq!(
+            uri_to_document(&document_uri("/tmp/a b/ü.rs")),
+            "/tmp/a b/ü.rs"
+        );
+        assert_eq!(uri_to_document(&document_uri("untitled-3")), "untitled-3");
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::protocol;
+use crate::transport::{read_message, write_message};
+
+/// Default time to wait for a response to an ordinary request.
+/// （一般請求等待回應的預設時間。）
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time allowed for the `initialize` handshake.
+/// （`initialize` 交握允許的時間。）
+pub const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Errors raised while talking to a language server process.
+/// （與語言伺服器程序溝通時發生的錯誤。）
+#[derive(Debug, Error)]
+pub enum LspClientError {
+    #[error("no language server configured for `{0}`")]
+    NotConfigured(String),
+    #[error("failed to start language server `{command}`: {source}")]
+    Spawn {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("language server I/O failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("language server did not answer `{method}` in time")]
+    Timeout { method: String },
+    #[error("language server rejected request ({code}): {message}")]
+    Server { code: i64, message: String },
+    #[error("language server exited")]
+    Exited,
+    #[error("no language server running for `{0}`")]
+    NotRunning(String),
+    #[error("document `{0}` is not open with a language server")]
+    DocumentNotOpen(String),
+    #[error("cannot resolve edits for `{0}`: document text unavailable")]
+    DocumentUnavailable(String),
+    #[error("cannot apply edits: {0}")]
+    InvalidEdit(String),
+    #[error("{0} (cached for this document version)")]
+    CachedFailure(String),
+}
+
+/// How to launch the language server for one language.
+/// （某語言之語言伺服器的啟動方式。）
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageServerConfig {
+    pub language: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub root: Option<PathBuf>,
+    pub initialization_options: Option<Value>,
+    pub request_timeout: Duration,
+}
+
+impl LanguageServerConfig {
+    pub fn new(language: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            language: language.into(),
+            command: command.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            root: None,
+            initialization_options: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    pub fn with_initialization_options(mut self, options: Value) -> Self {
+        self.initialization_options = Some(options);
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+}
+
+type PendingRequests = Arc<Mutex<HashMap<u64, Sender<Result<Value, LspClientError>>>>>;
+
+/// A running language server speaking JSON-RPC over its stdin/stdout.
+/// （透過 stdin/stdout 以 JSON-RPC 溝通的執行中語言伺服器。）
+///
+/// A reader thread dispatches responses to waiting requests, answers server-initiated
+/// requests, and forwards notifications to the callback given to [`spawn`](Self::spawn).
+/// （讀取執行緒會將回應交給等待中的請求、回覆伺服器發起的請求，並將通知轉交給
+/// [`spawn`](Self::spawn) 提供的回呼。）
+pub struct LanguageServer {
+    language: String,
+    child: Mutex<Child>,
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: PendingRequests,
+    next_id: AtomicU64,
+    capabilities: Value,
+    request_timeout: Duration,
+}
+
+impl LanguageServer {
+    /// Spawns the server and completes the `initialize`/`initialized` handshake.
+    /// （啟動伺服器並完成 `initialize`/`initialized` 交握。）
+    pub fn spawn<F>(
+        config: &LanguageServerConfig,
+        on_notification: F,
+    ) -> Result<Self, LspClientError>
+    where
+        F: Fn(&str, Value) + Send + 'static,
+    {
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .envs(config.env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        if let Some(root) = &config.root {
+            command.current_dir(root);
+        }
+        let mut child = command.spawn().map_err(|source| LspClientError::Spawn {
+            command: config.command.clone(),
+            source,
+        })?;
+
+        let stdin = Arc::new(Mutex::new(child.stdin.take().expect("stdin is piped")));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let pending: PendingRequests = Arc::default();
+        {
+            let stdin = Arc::clone(&stdin);
+            let pending = Arc::clone(&pending);
+            thread::Builder::new()
+                .name(format!("lsp-{}", config.language))
+                .spawn(move || read_loop(stdout, stdin, pending, on_notification))?;
+        }
+
+        let mut server = Self {
+            language: config.language.clone(),
+            child: Mutex::new(child),
+            stdin,
+            pending,
+            next_id: AtomicU64::new(1),
+            capabilities: Value::Null,
+            request_timeout: config.request_timeout,
+        };
+
+        let root_uri = config
+            .root
+            .as_ref()
+            .map(|root| protocol::document_uri(&root.to_string_lossy()));
+        let params = protocol::initialize_params(root_uri, config.initialization_options.clone());
+        let result = match server.request_with_timeout("initialize", params, INITIALIZE_TIMEOUT) {
+            Ok(result) => result,
+            Err(err) => {
+                server.kill();
+                return Err(err);
+            }
+        };
+        server.capabilities = result.get("capabilities").cloned().unwrap_or(Value::Null);
+        server.notify("initialized", json!({}))?;
+        Ok(server)
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Capabilities reported by the server during `initialize`.
+    /// （伺服器於 `initialize` 時回報的能力。）
+    pub fn capabilities(&self) -> &Value {
+        &self.capabilities
+    }
+
+    pub fn is_running(&self) -> bool {
+        let mut child = self.child.lock().expect("LSP child poisoned");
+        matches!(child.try_wait(), Ok(None))
+    }
+
+    /// Sends a request and blocks until its response arrives or the configured timeout elapses.
+    /// （送出請求並等待回應，直到逾時為止。）
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, LspClientError> {
+        self.request_with_timeout(method, params, self.request_timeout)
+    }
+
+    /// Sends a notification; no response is expected.
+    /// （送出通知，不等待回應。）
+    pub fn notify(&self, method: &str, params: Value) -> Result<(), LspClientError> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// Performs the `shutdown`/`exit` sequence, killing the process if it lingers.
+    /// （執行 `shutdown`/`exit` 流程，若程序未結束則強制終止。）
+    pub fn shutdown(&self) -> Result<(), LspClientError> {
+        if !self.is_running() {
+            return Ok(());
+        }
+        let outcome = self
+            .request("shutdown", Value::Null)
+            .and_then(|_| self.notify("exit", Value::Null));
+        let deadline = Instant::now() + EXIT_GRACE_PERIOD;
+        while self.is_running() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.kill();
+        outcome
+    }
+
+    fn request_with_timeout(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, LspClientError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        self.pending
+            .lock()
+            .expect("LSP pending requests poisoned")
+            .insert(id, sender);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(err) = self.send(&message) {
+            self.forget(id);
+            return Err(err);
+        }
+        match receiver.recv_timeout(timeout) {
+            Ok(outcome) => outcome,
+            Err(RecvTimeoutError::Timeout) => {
+                self.forget(id);
+                let _ = self.notify("$/cancelRequest", json!({ "id": id }));
+                Err(LspClientError::Timeout {
+                    method: method.to_string(),
+                })
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(LspClientError::Exited),
+        }
+    }
+
+    fn send(&self, message: &Value) -> Result<(), LspClientError> {
+        let mut stdin = self.stdin.lock().expect("LSP stdin poisoned");
+        write_message(&mut *stdin, message).map_err(|err| match err.kind() {
+            io::ErrorKind::BrokenPipe => LspClientError::Exited,
+            _ => LspClientError::Io(err),
+        })
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .expect("LSP pending requests poisoned")
+            .remove(&id);
+    }
+
+    fn kill(&self) {
+        let mut child = self.child.lock().expect("LSP child poisoned");
+        if matches!(child.try_wait(), Ok(None)) {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+fn read_loop<F>(
+    stdout: ChildStdout,
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: PendingRequests,
+    on_notification: F,
+) where
+    F: Fn(&str, Value),
+{
+    let mut reader = BufReader::new(stdout);
+    while let Ok(Some(message)) = read_message(&mut reader) {
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        match (method, message.get("id")) {
+            (Some(method), Some(id)) => {
+                let reply = json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": server_request_result(method, &params),
+                });
+                let mut stdin = stdin.lock().expect("LSP stdin poisoned");
+                if write_message(&mut *stdin, &reply).is_err() {
+                    break;
+                }
+            }
+            (Some(method), None) => on_notification(method, params),
+            (None, Some(id)) => {
+                let Some(id) = id.as_u64() else {
+                    continue;
+                };
+                let sender = pending
+                    .lock()
+                    .expect("LSP pending requests poisoned")
+                    .remove(&id);
+                if let Some(sender) = sender {
+                    let outcome = match message.get("error") {
+                        Some(error) => Err(LspClientError::Server {
+                            code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                            message: error
+                                .get("message")
+                                .and_then(Value::as_str)
+                                .unwrap_or_default()
+                                .to_string(),
+                        }),
+                        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+                    let _ = sender.send(outcome);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    // 丟棄等待者，使其收到斷線。 / Drop waiters so they observe the disconnect.
+    pending
+        .lock()
+        .expect("LSP pending requests poisoned")
+        .clear();
+}
+
+/// Minimal answers for requests the server may send to the client.
+/// （伺服器可能向用戶端發出之請求的最小回覆。）
+fn server_request_result(method: &str, params: &Value) -> Value {
+    match method {
+        "workspace/configuration" => {
+            let count = params
+                .get("items")
+                .and_then(Value::as_array)
+                .map(Vec::len)
+                .unwrap_or(0);
+            Value::Array(vec![Value::Null; count])
+        }
+        _ => Value::Null,
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// Writes a JSON-RPC message framed with a `Content-Length` header.
+/// （以 `Content-Length` 標頭封裝並寫出 JSON-RPC 訊息。）
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = serde_json::to_vec(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n", body.len())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+/// Reads the next framed JSON-RPC message, returning `None` once the stream ends cleanly.
+/// （讀取下一則封裝的 JSON-RPC 訊息；串流正常結束時回傳 `None`。）
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return match content_length {
+                None => Ok(None),
+                Some(_) => Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "stream ended inside message header",
+                )),
+            };
+        }
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed header `{header}`"),
+            ));
+        };
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            let length = value.trim().parse::<usize>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid Content-Length `{}`", value.trim()),
+                )
+            })?;
+            content_length = Some(length);
+        }
+    }
+
+    let mut body = vec![0; content_length.unwrap_or_default()];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(io::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buffer = Vec::new();
+        let first = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+        let second = json!({"jsonrpc": "2.0", "method": "exit", "params": {"note": "多位元組"}});
+        write_message(&mut buffer, &first).unwrap();
+        write_message(&mut buffer, &second).unwrap();
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(first));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(second));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+
+        let mut truncated = Cursor::new(b"Content-Length: 10\r\n".to_vec());
+        assert!(read_message(&mut truncated).is_err());
+    }
+}
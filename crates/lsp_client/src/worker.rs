@@ -0,0 +1,302 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rustnotepad_autocomplete::{
+    CompletionContext, CompletionTrigger, LspBridge, LspError, LspRequestParams, LspSuggestion,
+};
+use rustnotepad_search::SearchReport;
+
+use crate::diagnostics::TextPosition;
+use crate::navigation::{Hover, Location, WorkspaceEdit};
+use crate::server::LspClientError;
+use crate::{LspClient, ServerStatus};
+
+type Job = Box<dyn FnOnce(&LspClient) -> Option<LspReply> + Send>;
+type CachedCompletions = Arc<Mutex<Option<(CompletionKey, Vec<LspSuggestion>)>>>;
+
+/// Result of a request run by an [`LspWorker`], tagged with the document it was made for.
+/// （由 [`LspWorker`] 執行之請求的結果，並標示其所屬文件。）
+#[derive(Debug)]
+pub enum LspReply {
+    Definition {
+        document: String,
+        result: Result<Vec<Location>, LspClientError>,
+    },
+    References {
+        document: String,
+        result: Result<SearchReport, LspClientError>,
+    },
+    Hover {
+        document: String,
+        result: Result<Option<Hover>, LspClientError>,
+    },
+    Rename {
+        document: String,
+        new_name: String,
+        result: Result<WorkspaceEdit, LspClientError>,
+    },
+    /// A document notification (`didOpen`, `didChange`, `didClose`) could not be delivered.
+    /// （文件通知（`didOpen`、`didChange`、`didClose`）無法送達。）
+    Notification {
+        document: String,
+        error: LspClientError,
+    },
+    /// Server completions for the latest request are ready; ask the engine again.
+    /// （最新請求的伺服器補全已就緒；請再次向補全引擎查詢。）
+    Completions,
+}
+
+/// Runs [`LspClient`] calls on a background thread so the caller never waits on a server.
+/// （於背景執行緒執行 [`LspClient`] 呼叫，呼叫端不必等待伺服器。）
+///
+/// Jobs run one at a time in submission order, so document notifications and the requests
+/// that follow them reach the server in the order they were made. Poll [`drain`](Self::drain)
+/// for the replies.
+/// （工作依提交順序逐一執行，文件通知與其後的請求會依原順序送達伺服器；以
+/// [`drain`](Self::drain) 取回結果。）
+pub struct LspWorker {
+    client: Arc<LspClient>,
+    jobs: Sender<Job>,
+    replies: Receiver<LspReply>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl LspWorker {
+    pub fn spawn(client: Arc<LspClient>) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (reply_sender, replies) = mpsc::channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let worker_client = Arc::clone(&client);
+        let worker_pending = Arc::clone(&pending);
+        thread::spawn(move || {
+            for job in job_receiver {
+                let reply = job(&worker_client);
+                worker_pending.fetch_sub(1, Ordering::AcqRel);
+                if let Some(reply) = reply {
+                    if reply_sender.send(reply).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Self {
+            client,
+            jobs,
+            replies,
+            pending,
+        }
+    }
+
+    pub fn client(&self) -> &Arc<LspClient> {
+        &self.client
+    }
+
+    /// Queues `job`; its reply, if any, is returned by a later [`drain`](Self::drain).
+    /// （排入 `job`；其結果（若有）會由之後的 [`drain`](Self::drain) 回傳。）
+    pub fn submit<F>(&self, job: F)
+    where
+        F: FnOnce(&LspClient) -> Option<LspReply> + Send + 'static,
+    {
+        submit(&self.jobs, &self.pending, Box::new(job));
+    }
+
+    /// Tracks the document and sends `didOpen`, starting its server if needed.
+    /// （追蹤文件並送出 `didOpen`，必要時啟動伺服器。）
+    pub fn open_document(&self, language: &str, document: &str, text: &str) {
+        let (language, document, text) =
+            (language.to_string(), document.to_string(), text.to_string());
+        self.submit(
+            move |client| match client.open_document(&language, &document, &text) {
+                Ok(()) => {
+                    prefetch_symbols(client, &document);
+                    None
+                }
+                Err(error) => Some(LspReply::Notification { document, error }),
+            },
+        );
+    }
+
+    pub fn change_document(&self, document: &str, text: &str) {
+        let (document, text) = (document.to_string(), text.to_string());
+        self.submit(
+            move |client| match client.change_document(&document, &text) {
+                Ok(()) => {
+                    prefetch_symbols(client, &document);
+                    None
+                }
+                Err(error) => Some(LspReply::Notification { document, error }),
+            },
+        );
+    }
+
+    pub fn close_document(&self, document: &str) {
+        let document = document.to_string();
+        self.submit(move |client| {
+            client
+                .close_document(&document)
+                .err()
+                .map(|error| LspReply::Notification { document, error })
+        });
+    }
+
+    pub fn goto_definition(&self, document: &str, position: TextPosition) {
+        let document = document.to_string();
+        self.submit(move |client| {
+            let result = client.goto_definition(&document, position);
+            Some(LspReply::Definition { document, result })
+        });
+    }
+
+    pub fn references(&self, document: &str, position: TextPosition, include_declaration: bool) {
+        let document = document.to_string();
+        self.submit(move |client| {
+            let result = client.references(&document, position, include_declaration);
+            Some(LspReply::References { document, result })
+        });
+    }
+
+    pub fn hover(&self, document: &str, position: TextPosition) {
+        let document = document.to_string();
+        self.submit(move |client| {
+            let result = client.hover(&document, position);
+            Some(LspReply::Hover { document, result })
+        });
+    }
+
+    pub fn rename(&self, document: &str, position: TextPosition, new_name: &str) {
+        let (document, new_name) = (document.to_string(), new_name.to_string());
+        self.submit(move |client| {
+            let result = client.rename(&document, position, &new_name);
+            Some(LspReply::Rename {
+                document,
+                new_name,
+                result,
+            })
+        });
+    }
+
+    /// Completion bridge answering from the last server reply and refreshing it here.
+    /// （以最近一次伺服器回覆作答、並於此背景更新的補全橋接。）
+    pub fn completions(&self) -> BackgroundCompletions {
+        BackgroundCompletions {
+            client: Arc::clone(&self.client),
+            jobs: self.jobs.clone(),
+            pending: Arc::clone(&self.pending),
+            latest: Arc::new(Mutex::new(None)),
+            requested: Mutex::new(None),
+        }
+    }
+
+    /// Replies produced so far.
+    /// （目前已產生的結果。）
+    pub fn drain(&self) -> Vec<LspReply> {
+        self.replies.try_iter().collect()
+    }
+
+    /// Whether jobs are still queued or running.
+    /// （是否仍有排隊或執行中的工作。）
+    pub fn is_busy(&self) -> bool {
+        self.pending.load(Ordering::Acquire) > 0
+    }
+}
+
+/// [`LspBridge`] that never waits on a running server.
+/// （永不等待執行中伺服器的 [`LspBridge`]。）
+///
+/// A request whose answer is not cached yet returns no items and queues the server call on
+/// the worker, which sends [`LspReply::Completions`] once the answer is in. Languages without
+/// a configured server are answered directly from the client's in-memory suggestions.
+/// （尚未快取答案的請求不回傳項目，並將伺服器呼叫排入背景工作；答案就緒後會送出
+/// [`LspReply::Completions`]。未設定伺服器的語言直接以用戶端的記憶體內建議作答。）
+pub struct BackgroundCompletions {
+    client: Arc<LspClient>,
+    jobs: Sender<Job>,
+    pending: Arc<AtomicUsize>,
+    latest: CachedCompletions,
+    requested: Mutex<Option<CompletionKey>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CompletionKey {
+    document: Option<String>,
+    language: Option<String>,
+    prefix: String,
+    trigger: CompletionTrigger,
+    context: CompletionContext,
+    max_items: usize,
+    position: Option<(usize, usize)>,
+}
+
+impl CompletionKey {
+    fn params(&self) -> LspRequestParams<'_> {
+        LspRequestParams {
+            document: self.document.as_deref(),
+            language: self.language.as_deref(),
+            prefix: &self.prefix,
+            trigger: self.trigger,
+            context: &self.context,
+            max_items: self.max_items,
+            position: self.position,
+        }
+    }
+}
+
+impl LspBridge for BackgroundCompletions {
+    fn is_enabled(&self, language: Option<&str>) -> bool {
+        LspBridge::is_enabled(&*self.client, language)
+    }
+
+    fn complete(&self, params: LspRequestParams<'_>) -> Result<Vec<LspSuggestion>, LspError> {
+        let language = params.language.ok_or(LspError::Disabled)?;
+        if matches!(
+            self.client.server_status(language),
+            ServerStatus::NotConfigured
+        ) {
+            return self.client.complete(params);
+        }
+        let key = CompletionKey {
+            document: params.document.map(str::to_string),
+            language: Some(language.to_string()),
+            prefix: params.prefix.to_string(),
+            trigger: params.trigger,
+            context: params.context.clone(),
+            max_items: params.max_items,
+            position: params.position,
+        };
+        if let Some((cached, suggestions)) = &*self.latest.lock().expect("completions poisoned") {
+            if *cached == key {
+                return Ok(suggestions.clone());
+            }
+        }
+        let mut requested = self.requested.lock().expect("completions poisoned");
+        if requested.as_ref() != Some(&key) {
+            *requested = Some(key.clone());
+            let latest = Arc::clone(&self.latest);
+            submit(
+                &self.jobs,
+                &self.pending,
+                Box::new(move |client| {
+                    let suggestions = client.complete(key.params()).unwrap_or_default();
+                    *latest.lock().expect("completions poisoned") = Some((key, suggestions));
+                    Some(LspReply::Completions)
+                }),
+            );
+        }
+        Ok(Vec::new())
+    }
+}
+
+fn submit(jobs: &Sender<Job>, pending: &AtomicUsize, job: Job) {
+    pending.fetch_add(1, Ordering::AcqRel);
+    if jobs.send(job).is_err() {
+        pending.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Fetches the document symbols so function-list lookups find them cached.
+/// （預先取得文件符號，讓函式清單查詢時可直接使用快取。）
+fn prefetch_symbols(client: &LspClient, document: &str) {
+    let _ = client.document_symbols(document);
+}
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustnotepad_autocomplete::{
+    CompletionKind, CompletionProvider, CompletionRequest, LspProvider,
+};
+use rustnotepad_function_list::{FunctionKind, FunctionParser};
+use rustnotepad_lsp_client::{
+    apply_text_edits, DiagnosticSeverity, LanguageServerConfig, LspClient, LspClientError,
+    LspReply, LspSymbolParser, LspWorker, ServerStatus, SymbolKind, TextPosition,
+};
+
+fn fake_server() -> LanguageServerConfig {
+    LanguageServerConfig::new("rust", env!("CARGO_BIN_EXE_fake-lsp-server"))
+}
+
+fn document_path() -> String {
+    temp_document("main.rs")
+}
+
+fn temp_document(name: &str) -> String {
+    std::env::temp_dir()
+        .join("rustnotepad lsp")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn wait_for<T>(mut probe: impl FnMut() -> Option<T>) -> T {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        if let Some(value) = probe() {
+            return value;
+        }
+        assert!(Instant::now() < deadline, "timed out waiting for server");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn fake_server_drives_completion_and_diagnostics() {
+    let client = Arc::new(LspClient::new());
+    client.register_server(fake_server());
+    assert_eq!(client.server_status("rust"), ServerStatus::Stopped);
+
+    let document = document_path();
+    let text = "fn main() {\n    // TODO: 😀 ok\n    pri\n}\n";
+    client.open_document("rust", &document, text).unwrap();
+    assert_eq!(client.server_status("rust"), ServerStatus::Running);
+
+    let diagnostics = wait_for(|| {
+        let diagnostics = client.diagnostics("rust");
+        (!diagnostics.is_empty()).then_some(diagnostics)
+    });
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "TODO left in source");
+    assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+    assert_eq!(client.document_diagnostics(&document), diagnostics);
+    assert!(client.document_diagnostics("untitled-9").is_empty());
+    let todo = &diagnostics[0];
+    assert_eq!(todo.source.as_deref(), Some("fake"));
+    assert_eq!(todo.start(), TextPosition::new(1, 7));
+    assert_eq!(&text[todo.byte_range().unwrap()], "TODO");
+    assert_eq!(
+        todo.uri
+            .as_deref()
+            .map(rustnotepad_lsp_client::uri_to_document),
+        Some(document.clone())
+    );
+
+    let provider = LspProvider::new(client.clone());
+    let request = CompletionRequest::new(Some(document.clone()), "pri")
+        .with_language(Some("rust".into()))
+        .with_position(1, 14);
+    let result = provider.complete(&request);
+    let labels: Vec<_> = result
+        .items
+        .iter()
+        .map(|item| item.item.label.as_str())
+        .collect();
+    assert_eq!(labels, ["println", "print_all"]);
+    // 😀 佔兩個 UTF-16 單位。 / The emoji counts as two UTF-16 code units.
+    assert_eq!(
+        result.items[0].item.detail.as_deref(),
+        Some("line 1, character 15")
+    );
+    assert_eq!(result.items[0].item.kind, CompletionKind::Symbol);
+
+    client
+        .change_document(&document, "fn main() {\n    println!();\n}\n")
+        .unwrap();
+    wait_for(|| client.diagnostics("rust").is_empty().then_some(()));
+    assert!(client.problems().is_empty());
+
+    client.stop_server("rust");
+    assert_eq!(client.server_status("rust"), ServerStatus::Stopped);
+}
+
+#[test]
+fn fake_server_answers_navigation_requests() {
+    let client = Arc::new(LspClient::new());
+    client.register_server(fake_server());
+
+    let main = temp_document("navigation.rs");
+    let util = temp_document("navigation_util.rs");
+    let main_text = "struct Counter;\nimpl Counter {\n    fn bump() {}\n}\nfn helper() {}\nfn main() {\n    let value = helper();\n    helper();\n}\n";
+    let util_text = "fn other() {\n    helper();\n}\n";
+    client.open_document("rust", &main, main_text).unwrap();
+    client.open_document("rust", &util, util_text).unwrap();
+
+    let definitions = client
+        .goto_definition(&main, TextPosition::new(6, 18))
+        .unwrap();
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0].range.start, TextPosition::new(4, 3));
+    assert_eq!(
+        &main_text[definitions[0].range.bytes.clone().unwrap()],
+        "helper"
+    );
+
+    let hover = client
+        .hover(&main, TextPosition::new(0, 9))
+        .unwrap()
+        .unwrap();
+    assert_eq!(hover.contents, "```rust\nCounter\n```");
+    assert!(client
+        .hover(&main, TextPosition::new(3, 1))
+        .unwrap()
+        .is_none());
+
+    let report = client
+        .references(&util, TextPosition::new(1, 6), true)
+        .unwrap();
+    assert_eq!(report.total_matches, 4);
+    let files: Vec<_> = report
+        .results
+        .iter()
+        .map(|result| {
+            (
+                result.path.as_ref().unwrap().to_string_lossy().into_owned(),
+                result.matches.len(),
+            )
+        })
+        .collect();
+    assert_eq!(files, [(main.clone(), 3), (util.clone(), 1)]);
+    let first = &report.results[0].matches[1];
+    assert_eq!((first.line, first.column), (7, 17));
+    assert_eq!(first.line_text, "    let value = helper();");
+
+    let symbols = client.document_symbols(&main).unwrap();
+    let outline: Vec<_> = symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.name.as_str(),
+                symbol.kind,
+                symbol.container.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        outline,
+        [
+            ("Counter", SymbolKind::Struct, None),
+            ("Counter", SymbolKind::Module, None),
+            ("bump", SymbolKind::Method, Some("Counter")),
+            ("helper", SymbolKind::Function, None),
+            ("main", SymbolKind::Function, None),
+            ("value", SymbolKind::Variable, Some("main")),
+        ]
+    );
+
+    let parser = LspSymbolParser::new(client.clone(), "rust");
+    let entries: Vec<_> = parser
+        .parse(main_text)
+        .into_iter()
+        .map(|entry| (entry.name, entry.kind))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("Counter".to_string(), FunctionKind::Struct),
+            ("Counter".to_string(), FunctionKind::Region),
+            ("bump".to_string(), FunctionKind::Method),
+            ("helper".to_string(), FunctionKind::Function),
+            ("main".to_string(), FunctionKind::Function),
+        ]
+    );
+    // 非已開啟文件的內容不會詢問伺服器。 / Unknown text never reaches the server.
+    assert!(parser.parse("fn detached() {}\n").is_empty());
+
+    let edit = client
+        .rename(&main, TextPosition::new(4, 5), "assist")
+        .unwrap();
+    assert_eq!(edit.edit_count(), 4);
+    let main_uri = rustnotepad_lsp_client::document_uri(&main);
+    let renamed = apply_text_edits(main_text, &edit.changes[&main_uri]).unwrap();
+    assert_eq!(renamed.matches("assist").count(), 3);
+    assert!(!renamed.contains("helper"));
+
+    // 編輯後符號快取失效。 / Editing invalidates the symbol cache.
+    client.change_document(&main, &renamed).unwrap();
+    let symbols = client.document_symbols(&main).unwrap();
+    assert!(symbols.iter().any(|symbol| symbol.name == "assist"));
+
+    assert!(matches!(
+        client.hover("untitled-3", TextPosition::default()),
+        Err(LspClientError::DocumentNotOpen(_))
+    ));
+    client.stop_server("rust");
+}
+
+#[test]
+fn worker_runs_requests_off_the_calling_thread() {
+    let client = Arc::new(LspClient::new());
+    client.register_server(fake_server());
+    let worker = LspWorker::spawn(client.clone());
+
+    let document = temp_document("worker.rs");
+    let text = "fn helper() {}\nfn main() {\n    helper();\n    pri\n}\n";
+    worker.open_document("rust", &document, text);
+    worker.goto_definition(&document, TextPosition::new(2, 6));
+    let replies = wait_for(|| {
+        let replies = worker.drain();
+        (!replies.is_empty()).then_some(replies)
+    });
+    match &replies[..] {
+        [LspReply::Definition {
+            document: target,
+            result: Ok(locations),
+        }] => {
+            assert_eq!(target, &document);
+            assert_eq!(locations[0].range.start, TextPosition::new(0, 3));
+        }
+        other => panic!("unexpected replies: {other:?}"),
+    }
+    assert_eq!(client.server_status("rust"), ServerStatus::Running);
+
+    // 符號已預先取得，不等待的剖析器可直接使用。 / Symbols were prefetched for the non-waiting parser.
+    let parser = LspSymbolParser::new(client.clone(), "rust").without_waiting();
+    wait_for(|| (!worker.is_busy()).then_some(()));
+    let names: Vec<_> = parser
+        .parse(text)
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    assert_eq!(names, ["helper", "main"]);
+
+    let provider = LspProvider::new(Arc::new(worker.completions()));
+    let request = CompletionRequest::new(Some(document.clone()), "pri")
+        .with_language(Some("rust".into()))
+        .with_position(3, 7);
+    assert!(provider.complete(&request).items.is_empty());
+    wait_for(|| {
+        worker
+            .drain()
+            .iter()
+            .any(|reply| matches!(reply, LspReply::Completions))
+            .then_some(())
+    });
+    let labels: Vec<_> = provider
+        .complete(&request)
+        .items
+        .into_iter()
+        .map(|item| item.item.label)
+        .collect();
+    assert_eq!(labels, ["println", "print_all"]);
+    client.stop_server("rust");
+}
+
+#[test]
+fn missing_server_binary_is_reported_once() {
+    let client = LspClient::new();
+    client.register_server(LanguageServerConfig::new(
+        "rust",
+        "rustnotepad-no-such-language-server",
+    ));
+
+    let err = client
+        .open_document("rust", &document_path(), "fn main() {}\n")
+        .unwrap_err();
+    assert!(matches!(err, LspClientError::Spawn { .. }));
+    assert!(matches!(
+        client.server_status("rust"),
+        ServerStatus::Failed(_)
+    ));
+
+    // 失敗後不再重試，文件仍被追蹤。 / Later documents do not retry the failed server.
+    client
+        .open_document("rust", "untitled-2", "let x = 1;\n")
+        .unwrap();
+    assert!(client.diagnostics("rust").is_empty());
+}
@@ -0,0 +1,295 @@
+//! Minimal language server used by the integration tests.
+//! （整合測試使用的最小語言伺服器。）
+//!
+//! It reports a warning for every line containing `TODO`, answers completion with a fixed list
+//! whose detail echoes the requested position, resolves navigation requests by matching whole
+//! words across open documents (positions assume ASCII text), and exits on `exit`.
+//! （每行含 `TODO` 即回報警告，補全回傳固定清單並在 detail 中回顯請求位置，導覽請求以比對已開啟
+//! 文件中的完整單字處理（位置假設為 ASCII 文字），收到 `exit` 後結束。）
+
+use std::collections::HashMap;
+use std::io::{self, BufReader};
+
+use rustnotepad_lsp_client::transport::{read_message, write_message};
+use serde_json::{json, Value};
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut stdout = io::stdout();
+    let mut documents: HashMap<String, String> = HashMap::new();
+
+    while let Some(message) = read_message(&mut reader)? {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // 用戶端對伺服器請求的回覆。 / Replies to our own requests.
+            continue;
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "referencesProvider": true,
+                    "documentSymbolProvider": true,
+                    "renameProvider": true
+                },
+                "serverInfo": { "name": "fake-lsp-server" }
+            }),
+            "initialized" => {
+                let request = json!({
+                    "jsonrpc": "2.0",
+                    "id": "config-1",
+                    "method": "workspace/configuration",
+                    "params": { "items": [{ "section": "fake" }] }
+                });
+                write_message(&mut stdout, &request)?;
+                continue;
+            }
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                let text = if method == "textDocument/didOpen" {
+                    params["textDocument"]["text"].as_str()
+                } else {
+                    params["contentChanges"][0]["text"].as_str()
+                }
+                .unwrap_or_default()
+                .to_string();
+                publish(&mut stdout, &uri, &text)?;
+                documents.insert(uri, text);
+                continue;
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                documents.remove(&uri);
+                publish(&mut stdout, &uri, "")?;
+                continue;
+            }
+            "textDocument/completion" => {
+                let position = &params["position"];
+                let detail = format!(
+                    "line {}, character {}",
+                    position["line"], position["character"]
+                );
+                json!({
+                    "isIncomplete": false,
+                    "items": [
+                        {
+                            "label": "println",
+                            "kind": 3,
+                            "detail": detail,
+                            "insertText": "println!(${1})",
+                            "insertTextFormat": 2
+                        },
+                        { "label": "print_all", "kind": 3 },
+                        { "label": "fake_keyword", "kind": 14 }
+                    ]
+                })
+            }
+            "textDocument/definition" => {
+                let word = word_at(&documents, &params);
+                // 定義為 `fn`/`struct` 之後的第一次出現。 / The definition follows `fn` or `struct`.
+                occurrences(&documents, &word)
+                    .into_iter()
+                    .find(|(uri, line, column)| {
+                        let content = documents[uri].lines().nth(*line).unwrap_or_default();
+                        let before = content[..*column].trim_end();
+                        before.ends_with("fn") || before.ends_with("struct")
+                    })
+                    .map(|(uri, line, column)| location(&uri, line, column, word.len()))
+                    .unwrap_or(Value::Null)
+            }
+            "textDocument/hover" => {
+                let word = word_at(&documents, &params);
+                if word.is_empty() {
+                    Value::Null
+                } else {
+                    json!({
+                        "contents": { "kind": "markdown", "value": format!("```rust\n{word}\n```") }
+                    })
+                }
+            }
+            "textDocument/references" => {
+                let word = word_at(&documents, &params);
+                let hits: Vec<Value> = occurrences(&documents, &word)
+                    .into_iter()
+                    .map(|(uri, line, column)| location(&uri, line, column, word.len()))
+                    .collect();
+                Value::Array(hits)
+            }
+            "textDocument/rename" => {
+                let word = word_at(&documents, &params);
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                let mut changes = serde_json::Map::new();
+                for (uri, line, column) in occurrences(&documents, &word) {
+                    let edit = json!({
+                        "range": location(&uri, line, column, word.len())["range"],
+                        "newText": new_name
+                    });
+                    changes
+                        .entry(uri)
+                        .or_insert_with(|| Value::Array(Vec::new()))
+                        .as_array_mut()
+                        .expect("edits are arrays")
+                        .push(edit);
+                }
+                json!({ "changes": changes })
+            }
+            "textDocument/documentSymbol" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                document_symbols(documents.get(uri).map(String::as_str).unwrap_or_default())
+            }
+            "shutdown" => Value::Null,
+            "exit" => return Ok(()),
+            _ => {
+                if let Some(id) = id {
+                    let reply = json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("unknown method {method}") }
+                    });
+                    write_message(&mut stdout, &reply)?;
+                }
+                continue;
+            }
+        };
+
+        if let Some(id) = id {
+            write_message(
+                &mut stdout,
+                &json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn publish(stdout: &mut io::Stdout, uri: &str, text: &str) -> io::Result<()> {
+    let diagnostics: Vec<Value> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(line, content)| {
+            let column = content.find("TODO")?;
+            Some(json!({
+                "range": {
+                    "start": { "line": line, "character": column },
+                    "end": { "line": line, "character": column + 4 }
+                },
+                "severity": 2,
+                "source": "fake",
+                "message": "TODO left in source"
+            }))
+        })
+        .collect();
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics }
+    });
+    write_message(stdout, &notification)
+}
+
+fn word_at(documents: &HashMap<String, String>, params: &Value) -> String {
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+    let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+    let column = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+    let content = documents
+        .get(uri)
+        .and_then(|text| text.lines().nth(line))
+        .unwrap_or_default();
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let column = column.min(content.len());
+    let start = content[..column]
+        .rfind(|c| !is_word(c))
+        .map_or(0, |index| index + 1);
+    let end = content[column..]
+        .find(|c| !is_word(c))
+        .map_or(content.len(), |index| column + index);
+    content[start..end].to_string()
+}
+
+fn occurrences(documents: &HashMap<String, String>, word: &str) -> Vec<(String, usize, usize)> {
+    let mut hits = Vec::new();
+    if word.is_empty() {
+        return hits;
+    }
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut uris: Vec<_> = documents.keys().collect();
+    uris.sort();
+    for uri in uris {
+        for (line, content) in documents[uri].lines().enumerate() {
+            for (column, _) in content.match_indices(word) {
+                let before = content[..column].chars().next_back();
+                let after = content[column + word.len()..].chars().next();
+                if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                    hits.push((uri.clone(), line, column));
+                }
+            }
+        }
+    }
+    hits
+}
+
+fn location(uri: &str, line: usize, column: usize, length: usize) -> Value {
+    json!({
+        "uri": uri,
+        "range": {
+            "start": { "line": line, "character": column },
+            "end": { "line": line, "character": column + length }
+        }
+    })
+}
+
+/// Hierarchical symbols: `struct`/`impl` blocks contain the indented `fn` items after them.
+/// （階層式符號：`struct`/`impl` 區塊包含其後縮排的 `fn` 項目。）
+fn document_symbols(text: &str) -> Value {
+    let mut symbols: Vec<Value> = Vec::new();
+    for (line, content) in text.lines().enumerate() {
+        let trimmed = content.trim_start();
+        let indent = content.len() - trimmed.len();
+        let (keyword, kind) = if trimmed.starts_with("fn ") {
+            ("fn ", if indent > 0 { 6 } else { 12 })
+        } else if trimmed.starts_with("struct ") {
+            ("struct ", 23)
+        } else if trimmed.starts_with("impl ") {
+            ("impl ", 2)
+        } else if trimmed.starts_with("let ") {
+            ("let ", 13)
+        } else {
+            continue;
+        };
+        let name: String = trimmed[keyword.len()..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let column = indent + keyword.len();
+        let symbol = json!({
+            "name": name,
+            "kind": kind,
+            "range": {
+                "start": { "line": line, "character": indent },
+                "end": { "line": line, "character": content.len() }
+            },
+            "selectionRange": location("", line, column, name.len())["range"],
+            "children": []
+        });
+        match symbols.last_mut() {
+            Some(parent) if indent > 0 => parent["children"]
+                .as_array_mut()
+                .expect("children are arrays")
+                .push(symbol),
+            _ => symbols.push(symbol),
+        }
+    }
+    Value::Array(symbols)
+}
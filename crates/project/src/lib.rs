@@ -4,14 +4,20 @@
 mod serde_path;
 mod util;
 
+pub mod search_history;
 pub mod session;
 pub mod tree;
 pub mod tree_store;
 pub mod workspace;
 
+pub use search_history::{
+    SavedSearch, SavedSearchMode, SearchHistory, SearchHistoryError, SearchHistoryStore,
+    SearchPreset, SEARCH_HISTORY_LIMIT,
+};
 pub use session::{
-    AutosaveManifest, AutosaveStore, SessionCaret, SessionError, SessionMetadata, SessionScroll,
-    SessionSelection, SessionSnapshot, SessionStore, SessionTab, SessionWindow, UnsavedHash,
+    AutosaveManifest, AutosaveStore, SessionCaret, SessionError, SessionMark, SessionMetadata,
+    SessionScroll, SessionSelection, SessionSnapshot, SessionStore, SessionTab, SessionWindow,
+    UnsavedHash,
 };
 pub use tree::{
     NodeMetadata, ProjectFilter, ProjectNode, ProjectNodeDraft, ProjectNodeId, ProjectNodeKind,
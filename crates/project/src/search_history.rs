@@ -0,0 +1,273 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use rustnotepad_search::{SearchMode, SearchOptions};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::util::write_atomic;
+
+/// Number of recent searches kept per workspace.
+/// 每個工作區保留的最近搜尋筆數。
+pub const SEARCH_HISTORY_LIMIT: usize = 50;
+
+/// Serializable counterpart of `SearchMode`.
+/// 可序列化的搜尋模式。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SavedSearchMode {
+    #[default]
+    Plain,
+    Extended,
+    Regex,
+    Fuzzy,
+}
+
+impl From<SearchMode> for SavedSearchMode {
+    fn from(mode: SearchMode) -> Self {
+        match mode {
+            SearchMode::Plain => Self::Plain,
+            SearchMode::Extended => Self::Extended,
+            SearchMode::Regex => Self::Regex,
+            SearchMode::Fuzzy => Self::Fuzzy,
+        }
+    }
+}
+
+impl From<SavedSearchMode> for SearchMode {
+    fn from(mode: SavedSearchMode) -> Self {
+        match mode {
+            SavedSearchMode::Plain => Self::Plain,
+            SavedSearchMode::Extended => Self::Extended,
+            SavedSearchMode::Regex => Self::Regex,
+            SavedSearchMode::Fuzzy => Self::Fuzzy,
+        }
+    }
+}
+
+/// A search as typed by the user: pattern, replacement, flags and file masks.
+/// 使用者輸入的搜尋：樣式、取代字串、選項與檔案遮罩。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct SavedSearch {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: Option<String>,
+    #[serde(default)]
+    pub mode: SavedSearchMode,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default)]
+    pub whole_word: bool,
+    #[serde(default)]
+    pub dot_matches_newline: bool,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl SavedSearch {
+    /// Captures the pattern and flags of `options`; direction, scope and wrap are per-run state.
+    /// 擷取搜尋選項中的樣式與旗標；方向、範圍與循環屬於單次執行狀態，不會保存。
+    pub fn from_options(options: &SearchOptions) -> Self {
+        Self {
+            pattern: options.pattern.clone(),
+            mode: options.mode.clone().into(),
+            case_sensitive: options.case_sensitive,
+            whole_word: options.whole_word,
+            dot_matches_newline: options.dot_matches_newline,
+            ..Self::default()
+        }
+    }
+
+    /// Builds search options with defaults for the per-run fields.
+    /// 以預設的單次執行欄位建立搜尋選項。
+    pub fn to_options(&self) -> SearchOptions {
+        let mut options = SearchOptions::new(self.pattern.clone());
+        options.mode = self.mode.into();
+        options.case_sensitive = self.case_sensitive;
+        options.whole_word = self.whole_word;
+        options.dot_matches_newline = self.dot_matches_newline;
+        options
+    }
+}
+
+/// A saved search recalled by name.
+/// 以名稱呼叫的已儲存搜尋。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub search: SavedSearch,
+}
+
+/// Recent searches (newest first) and named presets for one workspace.
+/// 單一工作區的最近搜尋（新到舊）與具名預設。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct SearchHistory {
+    #[serde(default)]
+    pub recent: Vec<SavedSearch>,
+    #[serde(default)]
+    pub presets: Vec<SearchPreset>,
+}
+
+impl SearchHistory {
+    /// Moves the search to the front of the history, dropping duplicates and the oldest entries.
+    /// 將搜尋移到歷史最前面，移除重複項目與超出上限的舊項目。
+    pub fn record(&mut self, search: SavedSearch) {
+        if search.pattern.is_empty() {
+            return;
+        }
+        self.recent.retain(|existing| *existing != search);
+        self.recent.insert(0, search);
+        self.recent.truncate(SEARCH_HISTORY_LIMIT);
+    }
+
+    /// Distinct recent patterns, newest first.
+    /// 不重複的最近搜尋樣式（新到舊）。
+    pub fn recent_patterns(&self) -> Vec<&str> {
+        let mut patterns: Vec<&str> = Vec::new();
+        for search in &self.recent {
+            if !patterns.contains(&search.pattern.as_str()) {
+                patterns.push(&search.pattern);
+            }
+        }
+        patterns
+    }
+
+    /// Looks up a preset by name.
+    /// 依名稱尋找預設。
+    pub fn preset(&self, name: &str) -> Option<&SavedSearch> {
+        self.presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .map(|preset| &preset.search)
+    }
+
+    /// Saves or overwrites a preset; returns `true` when an existing one was replaced.
+    /// 儲存或覆寫預設；覆寫既有預設時回傳 `true`。
+    pub fn save_preset(&mut self, name: impl Into<String>, search: SavedSearch) -> bool {
+        let name = name.into();
+        if let Some(existing) = self.presets.iter_mut().find(|preset| preset.name == name) {
+            existing.search = search;
+            return true;
+        }
+        self.presets.push(SearchPreset { name, search });
+        self.presets.sort_by(|a, b| a.name.cmp(&b.name));
+        false
+    }
+
+    /// Removes a preset; returns `false` if none had that name.
+    /// 刪除預設；找不到該名稱時回傳 `false`。
+    pub fn remove_preset(&mut self, name: &str) -> bool {
+        let before = self.presets.len();
+        self.presets.retain(|preset| preset.name != name);
+        self.presets.len() != before
+    }
+}
+
+/// Errors raised while loading or saving search history.
+/// 載入或儲存搜尋歷史時的錯誤。
+#[derive(Debug, Error)]
+pub enum SearchHistoryError {
+    #[error("search history IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid search history: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// Persists a workspace's search history as JSON.
+/// 以 JSON 保存工作區的搜尋歷史。
+#[derive(Debug)]
+pub struct SearchHistoryStore {
+    path: PathBuf,
+}
+
+impl SearchHistoryStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the history; a missing file yields an empty history.
+    /// 載入搜尋歷史；檔案不存在時回傳空白歷史。
+    pub fn load(&self) -> Result<SearchHistory, SearchHistoryError> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(SearchHistory::default()),
+            Err(err) => Err(SearchHistoryError::Io(err)),
+        }
+    }
+
+    /// Saves the history using an atomic write.
+    /// 以原子寫入儲存搜尋歷史。
+    pub fn save(&self, history: &SearchHistory) -> Result<(), SearchHistoryError> {
+        let json = serde_json::to_vec_pretty(history)?;
+        write_atomic(&self.path, &json)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn search(pattern: &str) -> SavedSearch {
+        SavedSearch {
+            pattern: pattern.into(),
+            ..SavedSearch::default()
+        }
+    }
+
+    #[test]
+    fn history_deduplicates_and_caps_recent_searches() {
+        let mut history = SearchHistory::default();
+        for idx in 0..SEARCH_HISTORY_LIMIT + 5 {
+            history.record(search(&format!("term{idx}")));
+        }
+        history.record(search("term10"));
+        let mut regex = search("term10");
+        regex.mode = SavedSearchMode::Regex;
+        history.record(regex.clone());
+
+        assert_eq!(history.recent.len(), SEARCH_HISTORY_LIMIT);
+        assert_eq!(history.recent[0], regex);
+        assert_eq!(history.recent_patterns()[..2], ["term10", "term54"]);
+        assert_eq!(regex.to_options().mode, SearchMode::Regex);
+    }
+
+    #[test]
+    fn presets_round_trip_through_the_store() {
+        let tmp = tempdir().unwrap();
+        let store = SearchHistoryStore::new(tmp.path().join("search_history.json"));
+        assert_eq!(store.load().unwrap(), SearchHistory::default());
+
+        let mut history = SearchHistory::default();
+        let mut todo = search("TODO|FIXME");
+        todo.mode = SavedSearchMode::Regex;
+        todo.case_sensitive = true;
+        todo.include = vec!["*.rs".into()];
+        assert!(!history.save_preset("todo", todo.clone()));
+        assert!(!history.save_preset("alpha", search("alpha")));
+        assert!(history.save_preset("alpha", search("beta")));
+        history.record(search("needle"));
+        store.save(&history).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded, history);
+        assert_eq!(loaded.presets[0].name, "alpha");
+        assert_eq!(loaded.preset("todo"), Some(&todo));
+
+        let mut loaded = loaded;
+        assert!(loaded.remove_preset("alpha"));
+        assert!(!loaded.remove_preset("alpha"));
+        assert!(loaded.preset("alpha").is_none());
+    }
+}
@@ -112,6 +112,7 @@ pub struct SessionTab {
     pub path: Option<PathBuf>,
     #[serde(default)]
     pub display_name: Option<String>,
+    /// 編碼標籤（如 `koi8-r`），還原時接受任何 WHATWG 標籤。 / Encoding label such as `koi8-r`; any WHATWG label is accepted on restore.
     #[serde(default)]
     pub encoding: Option<String>,
     #[serde(default)]
@@ -122,6 +123,9 @@ pub struct SessionTab {
     pub scroll: SessionScroll,
     #[serde(default)]
     pub folds: Vec<u32>,
+    /// 以五種標記樣式標示的範圍。 / Ranges highlighted with the five mark styles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub marks: Vec<SessionMark>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub unsaved_hash: Option<UnsavedHash>,
     #[serde(default)]
@@ -138,6 +142,7 @@ impl Default for SessionTab {
             selection: None,
             scroll: SessionScroll::default(),
             folds: Vec::new(),
+            marks: Vec::new(),
             unsaved_hash: None,
             dirty_external: false,
         }
@@ -160,6 +165,14 @@ pub struct SessionSelection {
     pub head: SessionCaret,
 }
 
+/// 標記範圍；`style` 為 0 起算的標記樣式，`start`/`end` 為位元組位移。 / A marked range; `style` is the zero-based mark style and `start`/`end` are byte offsets.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionMark {
+    pub style: u8,
+    pub start: usize,
+    pub end: usize,
+}
+
 /// Scroll offset (top line and horizontal column).  
 /// 捲動偏移量（頂端行與水平欄位）。
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -402,6 +415,11 @@ mod tests {
                 horizontal_offset: 16,
             },
             folds: vec![5, 20],
+            marks: vec![SessionMark {
+                style: 2,
+                start: 3,
+                end: 9,
+            }],
             unsaved_hash: Some(UnsavedHash::new("deadbeef")),
             dirty_external: true,
         });
@@ -415,9 +433,43 @@ mod tests {
         let tab = &loaded.windows[0].tabs[0];
         assert_eq!(tab.caret.line, 12);
         assert_eq!(tab.scroll.horizontal_offset, 16);
+        assert_eq!(
+            tab.marks,
+            [SessionMark {
+                style: 2,
+                start: 3,
+                end: 9
+            }]
+        );
         assert_eq!(tab.dirty_external, true);
     }
 
+    #[test]
+    fn legacy_encoding_labels_round_trip() {
+        let tmp = tempdir().unwrap();
+        let store = SessionStore::new(tmp.path().join("session.json"), tmp.path().join("autosave"));
+
+        let mut window = SessionWindow::new();
+        for label in ["koi8-r", "windows-1252", "shift_jis"] {
+            window.tabs.push(SessionTab {
+                encoding: Some(label.into()),
+                ..SessionTab::default()
+            });
+        }
+        store.save(&SessionSnapshot::new(vec![window])).unwrap();
+
+        let loaded = store.load().unwrap().unwrap();
+        let labels: Vec<_> = loaded.windows[0]
+            .tabs
+            .iter()
+            .map(|tab| tab.encoding.as_deref())
+            .collect();
+        assert_eq!(
+            labels,
+            [Some("koi8-r"), Some("windows-1252"), Some("shift_jis")]
+        );
+    }
+
     #[test]
     fn autosave_manifest_tracks_entries() {
         let mut manifest = AutosaveManifest::default();
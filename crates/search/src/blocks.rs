@@ -0,0 +1,144 @@
+//! Searching text that is held in line-aligned blocks (a rope, a file read in chunks).
+//! （搜尋以行對齊區塊保存的文字，例如 rope 或分段讀取的檔案。）
+//!
+//! The pattern is compiled once and every block is scanned on its own, so the
+//! whole text never has to be copied into one string. That is only sound when
+//! no match can cross a line break and the pattern does not anchor to the start
+//! or end of the whole text; [`BlockSearch::needs_whole_text`] reports the
+//! patterns that must be run over the complete text instead.
+//! 樣式只編譯一次，並逐一掃描每個區塊，因此不需將整份文字複製成單一字串。
+//! 這只在結果不會跨越換行、且樣式未錨定整份文字開頭或結尾時成立；
+//! [`BlockSearch::needs_whole_text`] 會指出必須對完整文字執行的樣式。
+
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind, Look};
+use regex_syntax::ParserBuilder;
+
+use crate::{
+    build_regex, translate_pattern, SearchEngine, SearchError, SearchMatch, SearchOptions,
+};
+
+/// A pattern compiled once and run over line-aligned blocks of a larger text.
+/// （編譯一次、用於較大文字中各個行對齊區塊的樣式。）
+///
+/// The scope in the options is ignored; every block is searched in full.
+/// （選項中的範圍會被忽略；每個區塊都完整搜尋。）
+#[derive(Clone, Debug)]
+pub struct BlockSearch {
+    options: SearchOptions,
+    regex: Regex,
+    needs_whole_text: bool,
+}
+
+impl BlockSearch {
+    pub fn new(options: &SearchOptions) -> Result<Self, SearchError> {
+        options.validate()?;
+        let regex = build_regex(options)?;
+        let hir = ParserBuilder::new()
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .dot_matches_new_line(options.dot_matches_newline)
+            .build()
+            .parse(&translate_pattern(options)?)
+            .map_err(|err| SearchError::InvalidPattern(err.to_string()))?;
+        Ok(Self {
+            options: options.clone(),
+            regex,
+            needs_whole_text: needs_whole_text(&hir),
+        })
+    }
+
+    /// Whether a match can cross a line break or depends on the start or end of the whole text.
+    /// （結果是否可能跨越換行，或依賴整份文字的開頭或結尾。）
+    pub fn needs_whole_text(&self) -> bool {
+        self.needs_whole_text
+    }
+
+    /// Matches in `block`, which starts at byte `offset` after `lines_before` complete lines.
+    /// （搜尋 `block`；該區塊起於位元組 `offset`，之前有 `lines_before` 個完整的行。）
+    ///
+    /// `block` must start at a line start and end right after a `\n` unless it is the last block.
+    /// Empty matches at the very end of a block that is not the last are skipped, since the next
+    /// block reports them at its start.
+    /// （`block` 必須從行首開始，並在 `\n` 之後結束（最後一個區塊除外）。非最後區塊結尾處的
+    /// 空結果會略過，由下一個區塊在其開頭回報。）
+    pub fn find_in(
+        &self,
+        block: &str,
+        offset: usize,
+        lines_before: usize,
+        last: bool,
+    ) -> Vec<SearchMatch> {
+        let engine = SearchEngine::new(block);
+        engine
+            .collect_prepared_matches(&self.regex, block, 0, &self.options)
+            .into_iter()
+            .filter(|entry| last || entry.abs_start < block.len())
+            .map(|entry| {
+                let mut found = engine.build_match(entry.abs_start, entry.abs_end, &entry.matched);
+                found.start += offset;
+                found.end += offset;
+                found.line += lines_before;
+                found
+            })
+            .collect()
+    }
+}
+
+fn needs_whole_text(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty => false,
+        HirKind::Literal(literal) => literal.0.contains(&b'\n'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= '\n' && '\n' <= range.end()),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|range| range.start() <= b'\n' && b'\n' <= range.end()),
+        HirKind::Look(look) => matches!(look, Look::Start | Look::End),
+        HirKind::Repetition(repetition) => needs_whole_text(&repetition.sub),
+        HirKind::Capture(capture) => needs_whole_text(&capture.sub),
+        HirKind::Concat(parts) | HirKind::Alternation(parts) => parts.iter().any(needs_whole_text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchMode;
+
+    #[test]
+    fn blocks_match_a_whole_text_search() {
+        let text = "alpha beta\ngamma alpha\n\nalpha";
+        let split = "alpha beta\n".len();
+        let mut options = SearchOptions::new("alpha|^");
+        options.mode = SearchMode::Regex;
+        let search = BlockSearch::new(&options).unwrap();
+        assert!(!search.needs_whole_text());
+
+        let mut found = search.find_in(&text[..split], 0, 0, false);
+        found.extend(search.find_in(&text[split..], split, 1, true));
+        let expected = SearchEngine::new(text).find_all(&options).unwrap();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn patterns_crossing_lines_or_anchoring_the_text_need_it_whole() {
+        let needs_whole = |pattern: &str, mode: SearchMode, dot_all: bool| {
+            let mut options = SearchOptions::new(pattern);
+            options.mode = mode;
+            options.dot_matches_newline = dot_all;
+            BlockSearch::new(&options).unwrap().needs_whole_text()
+        };
+        assert!(!needs_whole("a.c", SearchMode::Regex, false));
+        assert!(!needs_whole(r"\w+$", SearchMode::Regex, false));
+        assert!(needs_whole("a.c", SearchMode::Regex, true));
+        assert!(needs_whole(r"a\sb", SearchMode::Regex, false));
+        assert!(needs_whole(r"[^x]", SearchMode::Regex, false));
+        assert!(needs_whole(r"\Aa", SearchMode::Regex, false));
+        assert!(needs_whole(r"a\nb", SearchMode::Extended, false));
+        assert!(!needs_whole("a.b", SearchMode::Plain, false));
+    }
+}
@@ -0,0 +1,201 @@
+//! Fuzzy (subsequence) matching and scoring.
+//! （模糊比對：以子序列方式比對並評分。）
+//!
+//! `SearchMode::Fuzzy` locates candidates with a regex that requires every
+//! pattern character in order within one line; [`fuzzy_score`] then ranks a
+//! candidate the way quick-jump bars do, rewarding word starts and runs of
+//! consecutive characters and penalising gaps.
+//! `SearchMode::Fuzzy` 先以正則找出同一行內依序包含所有字元的候選，
+//! 再由 [`fuzzy_score`] 評分：字首與連續字元加分，間隔扣分。
+
+use crate::SearchMatch;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+const PENALTY_GAP_START: i64 = -3;
+const PENALTY_GAP_EXTENSION: i64 = -1;
+
+/// Score of a fuzzy match and the byte offsets of the matched characters.
+/// （模糊比對的分數與各符合字元的位元組位移。）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyScore {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// Scores `candidate` against `pattern`, or returns `None` when the pattern is not a subsequence.
+/// （計算候選字串對樣式的分數；樣式不是子序列時回傳 `None`。）
+pub fn fuzzy_score(pattern: &str, candidate: &str, case_sensitive: bool) -> Option<FuzzyScore> {
+    let needle: Vec<char> = pattern.chars().collect();
+    if needle.is_empty() {
+        return None;
+    }
+    let hay: Vec<(usize, char)> = candidate.char_indices().collect();
+    let (m, n) = (needle.len(), hay.len());
+    if m > n {
+        return None;
+    }
+    let same =
+        |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+    let bonus: Vec<i64> = (0..n)
+        .map(|j| position_bonus(j.checked_sub(1).map(|prev| hay[prev].1), hay[j].1))
+        .collect();
+
+    // scores[i * n + j]：樣式第 i 個字元對上候選第 j 個字元時的最佳分數。 / Best score with pattern char `i` matched at candidate char `j`.
+    let mut scores: Vec<Option<i64>> = vec![None; m * n];
+    let mut from: Vec<usize> = vec![0; m * n];
+    for i in 0..m {
+        let mut gap: Option<(i64, usize)> = None;
+        for j in 0..n {
+            if i > 0 && j >= 2 {
+                let opened =
+                    scores[(i - 1) * n + j - 2].map(|score| (score + PENALTY_GAP_START, j - 2));
+                let extended = gap.map(|(score, k)| (score + PENALTY_GAP_EXTENSION, k));
+                gap = match (opened, extended) {
+                    (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                };
+            }
+            if !same(needle[i], hay[j].1) {
+                continue;
+            }
+            let own = SCORE_MATCH
+                + if i == 0 {
+                    bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER
+                } else {
+                    bonus[j]
+                };
+            let best = if i == 0 {
+                Some((0, 0))
+            } else {
+                let consecutive = j.checked_sub(1).and_then(|k| {
+                    scores[(i - 1) * n + k].map(|score| (score + BONUS_CONSECUTIVE, k))
+                });
+                match (consecutive, gap) {
+                    (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                }
+            };
+            if let Some((score, k)) = best {
+                scores[i * n + j] = Some(score + own);
+                from[i * n + j] = k;
+            }
+        }
+    }
+
+    let (mut j, score) = (0..n)
+        .filter_map(|j| scores[(m - 1) * n + j].map(|score| (j, score)))
+        .fold(None, |best: Option<(usize, i64)>, (j, score)| match best {
+            Some((_, top)) if top >= score => best,
+            _ => Some((j, score)),
+        })?;
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = hay[j].0;
+        j = from[i * n + j];
+    }
+    Some(FuzzyScore { score, positions })
+}
+
+/// Scores every match's text against the pattern and returns `(index, score)` pairs, best first.
+/// （以樣式為每筆結果的文字評分，依分數由高到低回傳 `(索引, 分數)`。）
+///
+/// Matches whose text does not contain the pattern as a subsequence (e.g. regex hits) are omitted.
+/// （文字不含樣式子序列的結果（例如正則結果）不會列出。）
+pub fn rank_matches(
+    pattern: &str,
+    case_sensitive: bool,
+    matches: &[SearchMatch],
+) -> Vec<(usize, FuzzyScore)> {
+    let mut ranked: Vec<(usize, FuzzyScore)> = matches
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, hit)| Some((idx, fuzzy_score(pattern, &hit.matched, case_sensitive)?)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.score.cmp(&a.1.score).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+/// Regex matching every pattern character in order within a single line.
+/// （依序包含所有樣式字元且不跨行的正則。）
+pub(crate) fn fuzzy_regex(pattern: &str) -> String {
+    let mut translated = String::new();
+    for (idx, ch) in pattern.chars().enumerate() {
+        if idx > 0 {
+            translated.push_str("[^\\n]*?");
+        }
+        translated.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4])));
+    }
+    translated
+}
+
+fn position_bonus(prev: Option<char>, current: char) -> i64 {
+    let Some(prev) = prev else {
+        return BONUS_BOUNDARY;
+    };
+    if !current.is_alphanumeric() {
+        return 0;
+    }
+    if !prev.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if (prev.is_lowercase() && current.is_uppercase())
+        || (!prev.is_numeric() && current.is_numeric())
+    {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SearchEngine, SearchMode, SearchOptions};
+
+    #[test]
+    fn fuzzy_score_prefers_word_starts_and_runs() {
+        let score = |pattern: &str, candidate: &str| {
+            fuzzy_score(pattern, candidate, false).map(|found| found.score)
+        };
+        assert_eq!(score("xyz", "xzy"), None);
+        assert_eq!(score("", "abc"), None);
+        assert!(score("fb", "foo_bar") > score("fb", "fabric"));
+        assert!(score("ne", "new") > score("ne", "one"));
+        assert!(score("sc", "SearchSession") < score("ss", "SearchSession"));
+
+        let found = fuzzy_score("gSe", "get_search_engine", false).unwrap();
+        assert_eq!(found.positions, [0, 4, 5]);
+        let found = fuzzy_score("ab", "a-ab", true).unwrap();
+        assert_eq!(found.positions, [2, 3]);
+        assert!(fuzzy_score("AB", "ab", true).is_none());
+    }
+
+    #[test]
+    fn fuzzy_mode_matches_subsequences_within_lines() {
+        let text = "fn find_all()\nfn replace_all()\nfind\nall";
+        let mut options = SearchOptions::new("fdal");
+        options.mode = SearchMode::Fuzzy;
+        let matches = SearchEngine::new(text).find_all(&options).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].matched, "fn find_al");
+
+        options.pattern = "fal".into();
+        let matches = SearchEngine::new(text).find_all(&options).unwrap();
+        assert_eq!(matches.len(), 2);
+        let ranked = rank_matches(&options.pattern, false, &matches);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(matches[ranked[0].0].line, 1);
+
+        let outcome = SearchEngine::new("a.b axb")
+            .replace_all("$0", &{
+                let mut options = SearchOptions::new("a.b");
+                options.mode = SearchMode::Fuzzy;
+                options
+            })
+            .unwrap();
+        assert_eq!(outcome.replaced_text, "$0 axb");
+    }
+}
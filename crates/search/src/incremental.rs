@@ -0,0 +1,287 @@
+//! Incremental "find as you type" search.
+//! （輸入即搜尋的增量搜尋。）
+//!
+//! Each keystroke usually extends the previous pattern. For plain and fuzzy
+//! patterns any line matching the longer pattern also matches the shorter one,
+//! so only the lines that matched the previous step are rescanned. Each step
+//! is kept on a stack, which makes backspacing back to an earlier pattern free.
+//! 每次按鍵通常只是延長前一個樣式；對一般與模糊樣式而言，符合較長樣式的行必定
+//! 符合較短樣式，因此只需重新掃描上一步有結果的行。每一步都保存在堆疊中，
+//! 按退格鍵回到先前的樣式時可直接重用結果。
+
+use crate::{
+    build_regex, line_starts, SearchEngine, SearchError, SearchMatch, SearchMode, SearchOptions,
+};
+
+/// How the last [`IncrementalSearch::update`] produced its matches.
+/// （上一次 [`IncrementalSearch::update`] 取得結果的方式。）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IncrementalScan {
+    /// The pattern was cleared or nothing has been searched yet.
+    /// （樣式已清空或尚未搜尋。）
+    Idle,
+    /// Reused the results of an earlier step (e.g. after backspace).
+    /// （重用先前步驟的結果，例如按下退格鍵後。）
+    Cached,
+    /// Rescanned only the given number of lines that matched the previous step.
+    /// （只重新掃描上一步有結果的指定行數。）
+    Narrowed { lines: usize },
+    /// Scanned the whole search scope.
+    /// （掃描整個搜尋範圍。）
+    Full,
+}
+
+#[derive(Clone, Debug)]
+struct Step {
+    pattern: String,
+    matches: Vec<SearchMatch>,
+}
+
+/// Find-as-you-type state that refines the previous results on every keystroke.
+/// （輸入即搜尋的狀態，每次按鍵都在前一次結果上修正。）
+///
+/// Cached steps are keyed on the caller's edit revision of the text, so any edit starts over
+/// with a full scan without hashing the document on every keystroke.
+/// （快取的步驟以呼叫端提供的文字編輯版本號為鍵，任何編輯都會重新完整掃描，
+/// 且不必在每次按鍵時對整份文件計算雜湊。）
+#[derive(Clone, Debug)]
+pub struct IncrementalSearch {
+    options: Option<SearchOptions>,
+    revision: Option<u64>,
+    line_starts: Vec<usize>,
+    steps: Vec<Step>,
+    last_scan: IncrementalScan,
+}
+
+impl Default for IncrementalSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IncrementalSearch {
+    pub fn new() -> Self {
+        Self {
+            options: None,
+            revision: None,
+            line_starts: Vec::new(),
+            steps: Vec::new(),
+            last_scan: IncrementalScan::Idle,
+        }
+    }
+
+    /// Searches `text` for `options.pattern`, reusing earlier steps where possible.
+    /// （以 `options.pattern` 搜尋 `text`，盡可能重用先前的步驟。）
+    ///
+    /// `revision` must change whenever `text` does, e.g. `TextBuffer::revision` in the core crate.
+    /// （`revision` 必須隨 `text` 變動而改變，例如核心套件的 `TextBuffer::revision`。）
+    ///
+    /// An empty pattern clears the results instead of failing.
+    /// （空白樣式會清空結果，而不是回傳錯誤。）
+    pub fn update(
+        &mut self,
+        text: &str,
+        revision: u64,
+        options: &SearchOptions,
+    ) -> Result<&[SearchMatch], SearchError> {
+        if !self.is_compatible(revision, options) {
+            self.reset();
+        }
+        if self.options.is_none() {
+            let mut base = options.clone();
+            base.pattern.clear();
+            self.options = Some(base);
+        }
+        if self.line_starts.is_empty() {
+            self.revision = Some(revision);
+            self.line_starts = line_starts(text);
+        }
+
+        let pattern = options.pattern.as_str();
+        while self
+            .steps
+            .last()
+            .is_some_and(|step| !pattern.starts_with(step.pattern.as_str()))
+        {
+            self.steps.pop();
+        }
+        if pattern.is_empty() {
+            self.last_scan = IncrementalScan::Idle;
+            return Ok(&[]);
+        }
+        if self
+            .steps
+            .last()
+            .is_some_and(|step| step.pattern == pattern)
+        {
+            self.last_scan = IncrementalScan::Cached;
+            return Ok(self.matches());
+        }
+
+        let engine = SearchEngine::with_line_starts(text, &self.line_starts);
+        let (matches, scan) = match self.steps.last() {
+            Some(previous) if can_narrow(options) => {
+                let regex = build_regex(options)?;
+                let scope = options.scope.resolve(text.len());
+                let mut lines: Vec<usize> = previous.matches.iter().map(|hit| hit.line).collect();
+                lines.dedup();
+                let mut matches = Vec::new();
+                for line in &lines {
+                    let start = self.line_starts[line - 1].max(scope.start);
+                    let end = self
+                        .line_starts
+                        .get(*line)
+                        .copied()
+                        .unwrap_or(text.len())
+                        .min(scope.end);
+                    if start >= end {
+                        continue;
+                    }
+                    for hit in regex.find_iter(&text[start..end]) {
+                        matches.push(engine.build_match(
+                            start + hit.start(),
+                            start + hit.end(),
+                            hit.as_str(),
+                        ));
+                    }
+                }
+                let lines = lines.len();
+                (matches, IncrementalScan::Narrowed { lines })
+            }
+            _ => (engine.find_all(options)?, IncrementalScan::Full),
+        };
+        self.steps.push(Step {
+            pattern: pattern.to_string(),
+            matches,
+        });
+        self.last_scan = scan;
+        Ok(self.matches())
+    }
+
+    /// Matches of the latest step.
+    /// （最新步驟的結果。）
+    pub fn matches(&self) -> &[SearchMatch] {
+        self.steps
+            .last()
+            .map_or(&[], |step| step.matches.as_slice())
+    }
+
+    /// How the latest update was computed.
+    /// （最新一次更新的計算方式。）
+    pub fn last_scan(&self) -> IncrementalScan {
+        self.last_scan
+    }
+
+    /// Drops every cached step, e.g. after the text was edited.
+    /// （清除所有快取步驟，例如文字被編輯之後。）
+    pub fn reset(&mut self) {
+        self.options = None;
+        self.revision = None;
+        self.line_starts.clear();
+        self.steps.clear();
+        self.last_scan = IncrementalScan::Idle;
+    }
+
+    fn is_compatible(&self, revision: u64, options: &SearchOptions) -> bool {
+        let Some(base) = &self.options else {
+            return true;
+        };
+        self.revision.is_none_or(|cached| cached == revision)
+            && base.mode == options.mode
+            && base.case_sensitive == options.case_sensitive
+            && base.whole_word == options.whole_word
+            && base.scope == options.scope
+            && base.dot_matches_newline == options.dot_matches_newline
+    }
+}
+
+/// Whether lines without a match for a pattern can hold none for its extensions.
+/// （樣式延長後，原本沒有結果的行是否必定仍無結果。）
+fn can_narrow(options: &SearchOptions) -> bool {
+    matches!(options.mode, SearchMode::Plain | SearchMode::Fuzzy)
+        && !options.whole_word
+        && !options.pattern.contains('\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchScope;
+
+    #[test]
+    fn typing_narrows_to_previous_lines_and_backspace_reuses_steps() {
+        let text = "alpha\nbeta\nalphabet\nALP\ngamma\n";
+        let mut search = IncrementalSearch::new();
+        let mut options = SearchOptions::new("a");
+        assert_eq!(search.update(text, 1, &options).unwrap().len(), 8);
+        assert_eq!(search.last_scan(), IncrementalScan::Full);
+
+        options.pattern = "alp".into();
+        let starts: Vec<usize> = search
+            .update(text, 1, &options)
+            .unwrap()
+            .iter()
+            .map(|hit| hit.start)
+            .collect();
+        assert_eq!(starts, [0, 11, 20]);
+        assert_eq!(search.last_scan(), IncrementalScan::Narrowed { lines: 5 });
+
+        options.pattern = "alpha".into();
+        let hits = search.update(text, 1, &options).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[1].line, hits[1].column), (3, 1));
+        assert_eq!(search.last_scan(), IncrementalScan::Narrowed { lines: 3 });
+
+        options.pattern = "al".into();
+        assert_eq!(search.update(text, 1, &options).unwrap().len(), 3);
+        assert_eq!(search.last_scan(), IncrementalScan::Narrowed { lines: 5 });
+        options.pattern = "a".into();
+        assert_eq!(search.update(text, 1, &options).unwrap().len(), 8);
+        assert_eq!(search.last_scan(), IncrementalScan::Cached);
+
+        options.pattern.clear();
+        assert!(search.update(text, 1, &options).unwrap().is_empty());
+        assert_eq!(search.last_scan(), IncrementalScan::Idle);
+    }
+
+    #[test]
+    fn option_and_text_changes_start_over() {
+        let mut search = IncrementalSearch::new();
+        let mut options = SearchOptions::new("b");
+        options.mode = SearchMode::Fuzzy;
+        options.scope = SearchScope::Selection { start: 4, end: 20 };
+        let text = "abc abd\nxbyd\nbd";
+        assert_eq!(search.update(text, 1, &options).unwrap().len(), 3);
+        options.pattern = "bd".into();
+        let hits = search.update(text, 1, &options).unwrap();
+        assert_eq!(
+            hits.iter()
+                .map(|hit| hit.matched.as_str())
+                .collect::<Vec<_>>(),
+            ["bd", "byd", "bd"]
+        );
+        assert_eq!(search.last_scan(), IncrementalScan::Narrowed { lines: 3 });
+
+        options.case_sensitive = true;
+        search.update(text, 1, &options).unwrap();
+        assert_eq!(search.last_scan(), IncrementalScan::Full);
+        search.update("abc abd\nxbyd\nbd\nbd", 2, &options).unwrap();
+        assert_eq!(search.last_scan(), IncrementalScan::Full);
+
+        // 新的編輯版本號會重新搜尋。 / A new edit revision starts over.
+        options.pattern = "b".into();
+        search.update("abc abd\nxbyd\nbd\nbd", 2, &options).unwrap();
+        let hits = search.update("abc xxx\nxbyd\nbd\nbd", 3, &options).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].start, 9);
+        assert_eq!(search.last_scan(), IncrementalScan::Full);
+        options.pattern = "bd".into();
+
+        options.mode = SearchMode::Regex;
+        options.pattern = "b(".into();
+        assert!(matches!(
+            search.update(text, 1, &options),
+            Err(SearchError::InvalidPattern(_))
+        ));
+    }
+}
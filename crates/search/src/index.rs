@@ -0,0 +1,432 @@
+//! Trigram index used to prune candidate files before running a multi-file search.
+//! （多檔搜尋前用來篩除不可能符合檔案的三字元組索引。）
+//!
+//! Trigrams are taken over the UTF-8 bytes of the decoded text with ASCII letters folded
+//! to lower case. A query lists the trigrams every match must contain; files missing any
+//! of them cannot match and are skipped. The index never hides a file it does not know:
+//! unindexed paths are always searched.
+//! （三字元組取自解碼後文字的 UTF-8 位元組，ASCII 字母一律轉為小寫；未索引的檔案一律會被搜尋。）
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use regex_syntax::ParserBuilder;
+
+use crate::{translate_pattern, FileSearchInput, SearchError, SearchOptions};
+
+const MAGIC: &[u8; 8] = b"RNPTRI1\n";
+
+/// Trigram sets for a collection of files, keyed by path.
+/// （以路徑為鍵的檔案三字元組集合。）
+#[derive(Clone, Debug, Default)]
+pub struct TrigramIndex {
+    ids: HashMap<PathBuf, u32>,
+    slots: Vec<Option<IndexedFile>>,
+    free: Vec<u32>,
+    postings: HashMap<u32, BTreeSet<u32>>,
+}
+
+#[derive(Clone, Debug)]
+struct IndexedFile {
+    path: PathBuf,
+    stamp: u64,
+    trigrams: Box<[u32]>,
+}
+
+/// Trigrams a file must contain for a search to have any chance of matching it.
+/// （檔案必須包含這些三字元組才可能符合搜尋。）
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexQuery {
+    /// Every clause must hold; a clause holds when all trigrams of one alternative are present.
+    clauses: Vec<Vec<Vec<u32>>>,
+}
+
+impl IndexQuery {
+    /// Derives the required trigrams from the prefix and suffix literals of the pattern.
+    /// （由樣式的前綴與後綴字面值推導必要的三字元組。）
+    pub fn new(options: &SearchOptions) -> Result<Self, SearchError> {
+        options.validate()?;
+        let hir = ParserBuilder::new()
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .dot_matches_new_line(options.dot_matches_newline)
+            .build()
+            .parse(&translate_pattern(options)?)
+            .map_err(|err| SearchError::InvalidPattern(err.to_string()))?;
+
+        let mut clauses = Vec::new();
+        for kind in [ExtractKind::Prefix, ExtractKind::Suffix] {
+            let seq = Extractor::new().kind(kind).extract(&hir);
+            let Some(literals) = seq.literals() else {
+                continue;
+            };
+            let mut alternatives: Vec<Vec<u32>> = literals
+                .iter()
+                .map(|literal| trigrams_of(literal.as_bytes()))
+                .collect();
+            // 任一候選沒有三字元組時，這個條件無法篩除任何檔案。 / An alternative without trigrams cannot rule anything out.
+            if alternatives.is_empty() || alternatives.iter().any(Vec::is_empty) {
+                continue;
+            }
+            alternatives.sort();
+            alternatives.dedup();
+            if !clauses.contains(&alternatives) {
+                clauses.push(alternatives);
+            }
+        }
+        Ok(Self { clauses })
+    }
+
+    /// Returns `true` when the query cannot rule out any file.
+    /// （查詢無法排除任何檔案時回傳 `true`。）
+    pub fn is_unconstrained(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    fn accepts(&self, trigrams: &[u32]) -> bool {
+        self.clauses.iter().all(|alternatives| {
+            alternatives.iter().any(|required| {
+                required
+                    .iter()
+                    .all(|trigram| trigrams.binary_search(trigram).is_ok())
+            })
+        })
+    }
+}
+
+impl TrigramIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.ids.contains_key(path)
+    }
+
+    /// Returns the caller-defined stamp recorded when the file was indexed.
+    /// （回傳索引時記錄的版本戳記，由呼叫端定義，例如大小與修改時間。）
+    pub fn stamp(&self, path: &Path) -> Option<u64> {
+        self.entry(path).map(|file| file.stamp)
+    }
+
+    /// Iterates over the indexed paths in no particular order.
+    /// （列舉已索引的路徑，順序不定。）
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.ids.keys().map(PathBuf::as_path)
+    }
+
+    /// Indexes (or re-indexes) a file's decoded text.
+    /// （索引或重新索引檔案的解碼後內容。）
+    pub fn insert(&mut self, path: impl Into<PathBuf>, stamp: u64, text: &str) {
+        let path = path.into();
+        self.remove(&path);
+        let trigrams = trigrams_of(text.as_bytes()).into_boxed_slice();
+        self.attach(IndexedFile {
+            path,
+            stamp,
+            trigrams,
+        });
+    }
+
+    /// Forgets a file; returns `false` if it was not indexed.
+    /// （移除檔案的索引；原本未索引時回傳 `false`。）
+    pub fn remove(&mut self, path: &Path) -> bool {
+        let Some(id) = self.ids.remove(path) else {
+            return false;
+        };
+        if let Some(file) = self.slots[id as usize].take() {
+            for trigram in file.trigrams.iter() {
+                if let Some(ids) = self.postings.get_mut(trigram) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        self.postings.remove(trigram);
+                    }
+                }
+            }
+        }
+        self.free.push(id);
+        true
+    }
+
+    /// Moves an entry to a new path without re-reading the file.
+    /// （將索引項目移到新路徑，不重新讀取檔案。）
+    pub fn rename(&mut self, from: &Path, to: impl Into<PathBuf>) -> bool {
+        let to = to.into();
+        let Some(id) = self.ids.remove(from) else {
+            return false;
+        };
+        self.remove(&to);
+        if let Some(file) = self.slots[id as usize].as_mut() {
+            file.path = to.clone();
+        }
+        self.ids.insert(to, id);
+        true
+    }
+
+    /// Drops every entry whose path fails the predicate; returns how many were removed.
+    /// （移除不符合條件的項目並回傳移除數量。）
+    pub fn retain(&mut self, mut keep: impl FnMut(&Path) -> bool) -> usize {
+        let doomed: Vec<PathBuf> = self
+            .ids
+            .keys()
+            .filter(|path| !keep(path))
+            .cloned()
+            .collect();
+        for path in &doomed {
+            self.remove(path);
+        }
+        doomed.len()
+    }
+
+    /// Returns `Some(false)` when the indexed text of `path` cannot match, `None` for unindexed paths.
+    /// （已索引檔案不可能符合時回傳 `Some(false)`；未索引的路徑回傳 `None`。）
+    pub fn may_match(&self, path: &Path, query: &IndexQuery) -> Option<bool> {
+        self.entry(path).map(|file| query.accepts(&file.trigrams))
+    }
+
+    /// Lists the indexed files that may match, sorted; `None` when the query cannot prune.
+    /// （列出可能符合的已索引檔案（已排序）；查詢無法篩選時回傳 `None`。）
+    pub fn candidates(&self, query: &IndexQuery) -> Option<Vec<&Path>> {
+        if query.is_unconstrained() {
+            return None;
+        }
+        let mut matched: Option<BTreeSet<u32>> = None;
+        for alternatives in &query.clauses {
+            let mut clause = BTreeSet::new();
+            for required in alternatives {
+                clause.extend(self.files_with_all(required));
+            }
+            matched = Some(match matched {
+                Some(previous) => previous.intersection(&clause).copied().collect(),
+                None => clause,
+            });
+        }
+        let mut paths: Vec<&Path> = matched
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| self.slots[id as usize].as_ref())
+            .map(|file| file.path.as_path())
+            .collect();
+        paths.sort();
+        Some(paths)
+    }
+
+    /// Serialises the index in a compact binary form.
+    /// （以精簡的二進位格式輸出索引。）
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let files: Vec<(&str, &IndexedFile)> = self
+            .slots
+            .iter()
+            .flatten()
+            .filter_map(|file| file.path.to_str().map(|path| (path, file)))
+            .collect();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(files.len() as u32).to_le_bytes())?;
+        for (path, file) in files {
+            writer.write_all(&(path.len() as u32).to_le_bytes())?;
+            writer.write_all(path.as_bytes())?;
+            writer.write_all(&file.stamp.to_le_bytes())?;
+            writer.write_all(&(file.trigrams.len() as u32).to_le_bytes())?;
+            for trigram in file.trigrams.iter() {
+                writer.write_all(&trigram.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Reads an index written by [`TrigramIndex::write_to`].
+    /// （讀取由 [`TrigramIndex::write_to`] 寫出的索引。）
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a RustNotePad trigram index",
+            ));
+        }
+        let mut index = Self::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let mut path = vec![0u8; read_u32(&mut reader)? as usize];
+            reader.read_exact(&mut path)?;
+            let path = String::from_utf8(path)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let mut stamp = [0u8; 8];
+            reader.read_exact(&mut stamp)?;
+            let count = read_u32(&mut reader)? as usize;
+            let mut trigrams = Vec::with_capacity(count.min(1 << 20));
+            for _ in 0..count {
+                trigrams.push(read_u32(&mut reader)?);
+            }
+            trigrams.sort_unstable();
+            trigrams.dedup();
+            let path = PathBuf::from(path);
+            index.remove(&path);
+            index.attach(IndexedFile {
+                path,
+                stamp: u64::from_le_bytes(stamp),
+                trigrams: trigrams.into_boxed_slice(),
+            });
+        }
+        Ok(index)
+    }
+
+    fn entry(&self, path: &Path) -> Option<&IndexedFile> {
+        let id = *self.ids.get(path)?;
+        self.slots[id as usize].as_ref()
+    }
+
+    fn attach(&mut self, file: IndexedFile) {
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                self.slots.push(None);
+                (self.slots.len() - 1) as u32
+            }
+        };
+        for trigram in file.trigrams.iter() {
+            self.postings.entry(*trigram).or_default().insert(id);
+        }
+        self.ids.insert(file.path.clone(), id);
+        self.slots[id as usize] = Some(file);
+    }
+
+    fn files_with_all(&self, required: &[u32]) -> BTreeSet<u32> {
+        let mut sets = Vec::with_capacity(required.len());
+        for trigram in required {
+            match self.postings.get(trigram) {
+                Some(ids) => sets.push(ids),
+                None => return BTreeSet::new(),
+            }
+        }
+        sets.sort_by_key(|ids| ids.len());
+        let Some((smallest, rest)) = sets.split_first() else {
+            return BTreeSet::new();
+        };
+        smallest
+            .iter()
+            .filter(|id| rest.iter().all(|ids| ids.contains(id)))
+            .copied()
+            .collect()
+    }
+}
+
+/// Like [`crate::search_in_files`], but skips indexed inputs whose trigrams rule out a match.
+/// （與 [`crate::search_in_files`] 相同，但會略過索引判定不可能符合的輸入。）
+pub fn search_in_files_with_index<'a, I>(
+    inputs: I,
+    options: &SearchOptions,
+    index: &TrigramIndex,
+) -> Result<crate::SearchReport, SearchError>
+where
+    I: IntoIterator<Item = FileSearchInput<'a>>,
+{
+    let query = IndexQuery::new(options)?;
+    crate::search_in_files(
+        inputs
+            .into_iter()
+            .filter(|input| index.may_match(&input.path, &query) != Some(false)),
+        options,
+    )
+}
+
+/// Sorted, de-duplicated trigrams of `bytes` with ASCII letters folded to lower case.
+fn trigrams_of(bytes: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = bytes
+        .windows(3)
+        .map(|window| {
+            u32::from_be_bytes([
+                0,
+                window[0].to_ascii_lowercase(),
+                window[1].to_ascii_lowercase(),
+                window[2].to_ascii_lowercase(),
+            ])
+        })
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchMode;
+
+    fn sample() -> TrigramIndex {
+        let mut index = TrigramIndex::new();
+        index.insert("a.rs", 1, "fn parse_config() {}\n");
+        index.insert("b.rs", 2, "let Needle = 1;\n");
+        index.insert("c.txt", 3, "nothing to see\n");
+        index
+    }
+
+    fn regex(pattern: &str) -> SearchOptions {
+        let mut options = SearchOptions::new(pattern);
+        options.mode = SearchMode::Regex;
+        options
+    }
+
+    #[test]
+    fn queries_prune_files_without_required_trigrams() {
+        let index = sample();
+        let query = IndexQuery::new(&SearchOptions::new("needle")).unwrap();
+        assert_eq!(index.candidates(&query), Some(vec![Path::new("b.rs")]));
+        assert_eq!(index.may_match(Path::new("a.rs"), &query), Some(false));
+        assert_eq!(index.may_match(Path::new("unknown.rs"), &query), None);
+
+        let mut sensitive = SearchOptions::new("needle");
+        sensitive.case_sensitive = true;
+        // ASCII 大小寫折疊後仍為候選，由搜尋本身判定。 / Case is folded in the index, so the search makes the final call.
+        let query = IndexQuery::new(&sensitive).unwrap();
+        assert_eq!(index.candidates(&query), Some(vec![Path::new("b.rs")]));
+
+        let query = IndexQuery::new(&regex(r"parse_\w+|nothing")).unwrap();
+        assert_eq!(
+            index.candidates(&query),
+            Some(vec![Path::new("a.rs"), Path::new("c.txt")])
+        );
+
+        assert!(IndexQuery::new(&regex(r"\w+")).unwrap().is_unconstrained());
+        assert!(IndexQuery::new(&SearchOptions::new("ab"))
+            .unwrap()
+            .is_unconstrained());
+    }
+
+    #[test]
+    fn updates_and_round_trip_keep_postings_consistent() {
+        let mut index = sample();
+        let query = IndexQuery::new(&SearchOptions::new("needle")).unwrap();
+        index.insert("b.rs", 4, "no match now\n");
+        index.insert("c.txt", 5, "needle moved here\n");
+        assert!(index.rename(Path::new("c.txt"), "d.txt"));
+        assert_eq!(index.candidates(&query), Some(vec![Path::new("d.txt")]));
+        assert_eq!(index.stamp(Path::new("d.txt")), Some(5));
+
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        let mut restored = TrigramIndex::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.candidates(&query), Some(vec![Path::new("d.txt")]));
+
+        assert_eq!(restored.retain(|path| path != Path::new("d.txt")), 1);
+        assert_eq!(restored.candidates(&query), Some(Vec::new()));
+        assert!(TrigramIndex::read_from(&b"garbage!"[..]).is_err());
+    }
+}
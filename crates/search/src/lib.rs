@@ -16,6 +16,20 @@ use std::path::PathBuf;
 use regex::{Regex, RegexBuilder};
 use thiserror::Error;
 
+mod blocks;
+mod fuzzy;
+mod incremental;
+mod index;
+mod plan;
+mod replace;
+
+pub use blocks::BlockSearch;
+pub use fuzzy::{fuzzy_score, rank_matches, FuzzyScore};
+pub use incremental::{IncrementalScan, IncrementalSearch};
+pub use index::{search_in_files_with_index, IndexQuery, TrigramIndex};
+pub use plan::{FileRewrite, ReplacePlan};
+pub use replace::{unescape_extended, ReplaceTemplate};
+
 /// Error conditions raised by the search engine.
 /// （搜尋引擎可能遇到的錯誤情況。）
 #[derive(Debug, Error, PartialEq, Eq)]
@@ -24,6 +38,8 @@ pub enum SearchError {
     EmptyPattern,
     #[error("invalid pattern: {0}")]
     InvalidPattern(String),
+    #[error("invalid replacement: {0}")]
+    InvalidReplacement(String),
 }
 
 /// Determines how the search pattern is interpreted.
@@ -31,35 +47,36 @@ pub enum SearchError {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SearchMode {
     Plain,
+    /// Plain text with `\n`, `\r`, `\t`, `\0` and `\xNN` escapes, in the pattern and the replacement.
+    /// （含 `\n`、`\r`、`\t`、`\0` 與 `\xNN` 跳脫字元的純文字，適用於樣式與取代字串。）
+    Extended,
+    /// Regular expression; replacements use the [`ReplaceTemplate`] syntax.
+    /// （正規表示式；取代字串採用 [`ReplaceTemplate`] 語法。）
     Regex,
+    /// Pattern characters in order, with anything but a newline between them; rank hits with [`rank_matches`].
+    /// （依序出現的樣式字元，中間可夾任意非換行字元；可用 [`rank_matches`] 排序結果。）
+    Fuzzy,
 }
 
 /// Direction for iterative searches (`Find Next` / `Find Previous`).
 /// （設定逐筆搜尋時的方向，例如下一筆或上一筆。）
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum SearchDirection {
+    #[default]
     Forward,
     Backward,
 }
 
-impl Default for SearchDirection {
-    fn default() -> Self {
-        Self::Forward
-    }
-}
-
 /// Search target scope within a document.
 /// （定義搜尋作用的文件範圍。）
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub enum SearchScope {
+    #[default]
     EntireDocument,
-    Selection { start: usize, end: usize },
-}
-
-impl Default for SearchScope {
-    fn default() -> Self {
-        Self::EntireDocument
-    }
+    Selection {
+        start: usize,
+        end: usize,
+    },
 }
 
 impl SearchScope {
@@ -289,6 +306,18 @@ impl<'a> SearchEngine<'a> {
         }
     }
 
+    /// Binds the engine to `text` reusing line starts computed by [`line_starts`].
+    /// （沿用 [`line_starts`] 算好的行首位移建立引擎。）
+    pub(crate) fn with_line_starts(text: &'a str, starts: &'a [usize]) -> Self {
+        Self {
+            text,
+            line_index: LineIndex {
+                text,
+                starts: Cow::Borrowed(starts),
+            },
+        }
+    }
+
     /// Finds the next match according to the search options, starting from the given byte index.
     /// （依搜尋選項自指定位元組索引往後尋找下一筆結果。）
     pub fn find(
@@ -404,68 +433,62 @@ impl<'a> SearchEngine<'a> {
         replacement: &str,
         options: &SearchOptions,
     ) -> Result<ReplaceAllOutcome, SearchError> {
+        let candidates = self.expand_replacements(replacement, options)?;
+        let mut replaced_text = String::with_capacity(self.text.len());
+        let mut last = 0usize;
+        let mut matches = Vec::with_capacity(candidates.len());
+        for (hit, expanded) in candidates {
+            replaced_text.push_str(&self.text[last..hit.start]);
+            replaced_text.push_str(&expanded);
+            last = hit.end;
+            matches.push(hit);
+        }
+        replaced_text.push_str(&self.text[last..]);
+
+        Ok(ReplaceAllOutcome {
+            replaced_text,
+            replacements: matches.len(),
+            matches,
+        })
+    }
+
+    /// Pairs every match in scope with its expanded replacement text.
+    /// （將範圍內的每個結果與展開後的取代文字配對。）
+    pub(crate) fn expand_replacements(
+        &self,
+        replacement: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<(SearchMatch, String)>, SearchError> {
         options.validate()?;
         let regex = build_regex(options)?;
-        let text_len = self.text.len();
-        let scope = options.scope.resolve(text_len);
+        let replacement = Replacement::new(replacement, &options.mode)?;
+        let scope = options.scope.resolve(self.text.len());
         if scope.is_empty() {
-            return Ok(ReplaceAllOutcome {
-                replaced_text: self.text.to_string(),
-                replacements: 0,
-                matches: Vec::new(),
-            });
+            return Ok(Vec::new());
         }
 
         let subset = &self.text[scope.clone()];
         let offset = scope.start;
-        let mut matches: Vec<SearchMatch> = Vec::new();
-        let mut replaced_segment = String::with_capacity(subset.len());
-        let mut last = 0usize;
-
+        let mut candidates = Vec::new();
         for caps in regex.captures_iter(subset) {
             let m = caps
                 .get(0)
                 .expect("regex::captures_iter should always yield group 0");
-            let rel_start = m.start();
-            let rel_end = m.end();
-            let abs_start = offset + rel_start;
-            let abs_end = offset + rel_end;
+            let abs_start = offset + m.start();
+            let abs_end = offset + m.end();
 
             if options.whole_word && !self.is_whole_word(abs_start, abs_end) {
                 continue;
             }
 
-            matches.push(self.build_match(abs_start, abs_end, m.as_str()));
-            replaced_segment.push_str(&subset[last..rel_start]);
-            if matches!(options.mode, SearchMode::Regex) {
-                caps.expand(replacement, &mut replaced_segment);
-            } else {
-                replaced_segment.push_str(replacement);
+            let mut expanded = String::new();
+            match &replacement {
+                Replacement::Template(template) => template.expand(&caps, &mut expanded),
+                Replacement::Literal(text) => expanded.push_str(text),
             }
-            last = rel_end;
-        }
-
-        if matches.is_empty() {
-            return Ok(ReplaceAllOutcome {
-                replaced_text: self.text.to_string(),
-                replacements: 0,
-                matches,
-            });
+            candidates.push((self.build_match(abs_start, abs_end, m.as_str()), expanded));
         }
-
-        replaced_segment.push_str(&subset[last..]);
-
-        let mut new_text =
-            String::with_capacity(self.text.len() - subset.len() + replaced_segment.len());
-        new_text.push_str(&self.text[..scope.start]);
-        new_text.push_str(&replaced_segment);
-        new_text.push_str(&self.text[scope.end..]);
-
-        Ok(ReplaceAllOutcome {
-            replaced_text: new_text,
-            replacements: matches.len(),
-            matches,
-        })
+        Ok(candidates)
     }
 
     fn build_match(&self, start: usize, end: usize, matched: &str) -> SearchMatch {
@@ -519,12 +542,12 @@ impl<'a> SearchEngine<'a> {
         } else {
             bytes
                 .get(start.saturating_sub(1))
-                .map_or(false, |b| is_word(*b))
+                .is_some_and(|b| is_word(*b))
         };
         let right = if end >= bytes.len() {
             false
         } else {
-            bytes.get(end).map_or(false, |b| is_word(*b))
+            bytes.get(end).is_some_and(|b| is_word(*b))
         };
         !(left || right)
     }
@@ -554,6 +577,145 @@ where
     Ok(SearchReport::new(results))
 }
 
+/// Byte-level pattern used by the hex view, e.g. `DE AD ?? EF` or `"ELF" 02`.
+/// Tokens are hex byte pairs (`DEAD` is two bytes), `??` wildcards, or quoted UTF-8 literals.
+/// （十六進位檢視使用的位元組樣式，支援十六進位位元組、`??` 萬用字元與引號括住的字串。）
+#[derive(Clone, Debug)]
+pub struct BytePattern {
+    source: String,
+    regex: regex::bytes::Regex,
+}
+
+impl BytePattern {
+    /// Parses a byte pattern, rejecting malformed hex digits and unterminated literals.
+    /// （解析位元組樣式；無效的十六進位或未結束的字串會回傳錯誤。）
+    pub fn parse(pattern: &str) -> Result<Self, SearchError> {
+        let mut translated = String::from("(?s-u)");
+        let mut byte_count = 0usize;
+        let mut rest = pattern.trim_start();
+        while !rest.is_empty() {
+            if let Some(literal) = rest.strip_prefix('"') {
+                let end = literal.find('"').ok_or_else(|| {
+                    SearchError::InvalidPattern("unterminated string literal".into())
+                })?;
+                for byte in literal[..end].bytes() {
+                    push_byte(&mut translated, byte);
+                    byte_count += 1;
+                }
+                rest = literal[end + 1..].trim_start();
+                continue;
+            }
+            let token_end = rest
+                .find(|ch: char| ch.is_whitespace() || ch == '"')
+                .unwrap_or(rest.len());
+            let token = &rest[..token_end];
+            if !token.len().is_multiple_of(2) {
+                return Err(SearchError::InvalidPattern(format!(
+                    "odd number of hex digits in `{token}`"
+                )));
+            }
+            for pair in token.as_bytes().chunks(2) {
+                if pair == b"??" {
+                    translated.push('.');
+                } else {
+                    let digits = std::str::from_utf8(pair).unwrap_or_default();
+                    let byte = u8::from_str_radix(digits, 16).map_err(|_| {
+                        SearchError::InvalidPattern(format!("invalid hex byte `{digits}`"))
+                    })?;
+                    push_byte(&mut translated, byte);
+                }
+                byte_count += 1;
+            }
+            rest = rest[token_end..].trim_start();
+        }
+        if byte_count == 0 {
+            return Err(SearchError::EmptyPattern);
+        }
+        let regex = regex::bytes::Regex::new(&translated)
+            .map_err(|err| SearchError::InvalidPattern(err.to_string()))?;
+        Ok(Self {
+            source: pattern.to_string(),
+            regex,
+        })
+    }
+
+    /// Returns the pattern as typed by the user.
+    /// （回傳使用者輸入的原始樣式。）
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Finds every non-overlapping match as byte ranges.
+    /// （以位元組範圍列出所有不重疊的結果。）
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<Range<usize>> {
+        self.regex.find_iter(haystack).map(|m| m.range()).collect()
+    }
+
+    /// Finds the next match starting at or after `from`, wrapping to the start if requested.
+    /// （從 `from` 往後尋找下一筆，可選擇是否循環。）
+    pub fn find_next(
+        &self,
+        haystack: &[u8],
+        from: usize,
+        wrap_around: bool,
+    ) -> Option<Range<usize>> {
+        let from = from.min(haystack.len());
+        self.regex
+            .find_at(haystack, from)
+            .map(|m| m.range())
+            .or_else(|| {
+                wrap_around
+                    .then(|| self.regex.find(haystack).map(|m| m.range()))
+                    .flatten()
+            })
+    }
+
+    /// Finds the last match that ends at or before `before`, wrapping to the end if requested.
+    /// （尋找在 `before` 之前結束的最後一筆，可選擇是否循環。）
+    pub fn find_previous(
+        &self,
+        haystack: &[u8],
+        before: usize,
+        wrap_around: bool,
+    ) -> Option<Range<usize>> {
+        let matches = self.find_all(haystack);
+        matches
+            .iter()
+            .rev()
+            .find(|range| range.end <= before)
+            .or_else(|| wrap_around.then(|| matches.last()).flatten())
+            .cloned()
+    }
+}
+
+fn push_byte(pattern: &mut String, byte: u8) {
+    use std::fmt::Write as _;
+    let _ = write!(pattern, "\\x{byte:02X}");
+}
+
+/// Replacement text prepared for the active search mode.
+/// （依目前搜尋模式準備好的取代內容。）
+enum Replacement<'a> {
+    Literal(Cow<'a, str>),
+    Template(ReplaceTemplate),
+}
+
+impl<'a> Replacement<'a> {
+    fn new(replacement: &'a str, mode: &SearchMode) -> Result<Self, SearchError> {
+        Ok(match mode {
+            SearchMode::Plain => Replacement::Literal(Cow::Borrowed(replacement)),
+            SearchMode::Extended => Replacement::Literal(Cow::Owned(
+                unescape_extended(replacement).map_err(|err| match err {
+                    SearchError::InvalidPattern(reason) => SearchError::InvalidReplacement(reason),
+                    other => other,
+                })?,
+            )),
+            SearchMode::Regex => Replacement::Template(ReplaceTemplate::parse(replacement)?),
+            SearchMode::Fuzzy => Replacement::Literal(Cow::Borrowed(replacement)),
+        })
+    }
+}
+
 #[derive(Clone)]
 struct PreparedMatch {
     rel_start: usize,
@@ -564,7 +726,7 @@ struct PreparedMatch {
 }
 
 fn build_regex(options: &SearchOptions) -> Result<Regex, SearchError> {
-    let mut builder = RegexBuilder::new(&translate_pattern(options));
+    let mut builder = RegexBuilder::new(&translate_pattern(options)?);
     builder.case_insensitive(!options.case_sensitive);
     builder.multi_line(true);
     builder.dot_matches_new_line(options.dot_matches_newline);
@@ -573,31 +735,35 @@ fn build_regex(options: &SearchOptions) -> Result<Regex, SearchError> {
         .map_err(|err| SearchError::InvalidPattern(err.to_string()))
 }
 
-fn translate_pattern(options: &SearchOptions) -> String {
-    match options.mode {
+fn translate_pattern(options: &SearchOptions) -> Result<String, SearchError> {
+    Ok(match options.mode {
         SearchMode::Plain => regex::escape(&options.pattern),
+        SearchMode::Extended => regex::escape(&unescape_extended(&options.pattern)?),
         SearchMode::Regex => options.pattern.clone(),
-    }
+        SearchMode::Fuzzy => fuzzy::fuzzy_regex(&options.pattern),
+    })
+}
+
+/// Byte offsets at which each line of `text` starts.
+/// （`text` 每一行起始的位元組位移。）
+pub(crate) fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(idx, _)| idx + 1));
+    starts
 }
 
 #[derive(Clone)]
 struct LineIndex<'a> {
     text: &'a str,
-    starts: Vec<usize>,
+    starts: Cow<'a, [usize]>,
 }
 
 impl<'a> LineIndex<'a> {
     fn new(text: &'a str) -> Self {
-        let mut starts = Vec::new();
-        starts.push(0);
-        for (idx, ch) in text.char_indices() {
-            if ch == '\n' {
-                if idx + ch.len_utf8() <= text.len() {
-                    starts.push(idx + ch.len_utf8());
-                }
-            }
+        Self {
+            text,
+            starts: Cow::Owned(line_starts(text)),
         }
-        Self { text, starts }
     }
 
     fn line_and_column(&self, index: usize) -> (usize, usize) {
@@ -620,9 +786,9 @@ impl<'a> LineIndex<'a> {
             .starts
             .get(zero_based + 1)
             .copied()
-            .unwrap_or_else(|| self.text.len());
+            .unwrap_or(self.text.len());
         self.text[start..end]
-            .trim_end_matches(|c| c == '\n' || c == '\r')
+            .trim_end_matches(['\n', '\r'])
             .to_string()
     }
 }
@@ -721,6 +887,34 @@ mod tests {
             .contains("const x: i32 = 10;\nconst y: i32 = 20;"));
     }
 
+    #[test]
+    fn replace_all_regex_converts_case_and_extended_mode_unescapes() {
+        let engine = SearchEngine::new("user_name = 1;\nuser_id = 2;");
+        let mut options = opts(r"user_(\w+) = (\d+);");
+        options.mode = SearchMode::Regex;
+        options.case_sensitive = true;
+        let outcome = engine.replace_all(r"\U$1\E: $2(?2,)", &options).unwrap();
+        assert_eq!(outcome.replaced_text, "NAME: 1,\nID: 2,");
+
+        let mut options = opts(r";\n");
+        options.mode = SearchMode::Extended;
+        let outcome = engine.replace_all(r"\t|\x41\t", &options).unwrap();
+        assert_eq!(outcome.replacements, 1);
+        assert_eq!(outcome.replaced_text, "user_name = 1\t|A\tuser_id = 2;");
+
+        options.pattern = r"\xG0".into();
+        assert!(matches!(
+            engine.find_all(&options),
+            Err(SearchError::InvalidPattern(_))
+        ));
+        let mut options = opts("user");
+        options.mode = SearchMode::Regex;
+        assert!(matches!(
+            engine.replace_all("(?1never", &options),
+            Err(SearchError::InvalidReplacement(_))
+        ));
+    }
+
     #[test]
     fn multi_file_search_collects_summary() {
         let options = opts("needle");
@@ -802,4 +996,30 @@ mod tests {
         assert!(report.results[0].matches[0].is_marked);
         assert!(!report.results[0].matches[1].is_marked);
     }
+
+    #[test]
+    fn byte_pattern_supports_wildcards_and_literals() {
+        let haystack = b"\x7FELF\x02\x01\x00\xDE\xAD\xBE\xEF\xDE\x00\xBE\xEF";
+        let pattern = BytePattern::parse("DE ?? BEEF").unwrap();
+        assert_eq!(pattern.find_all(haystack), vec![7..11, 11..15]);
+        assert_eq!(pattern.find_next(haystack, 8, false), Some(11..15));
+        assert_eq!(pattern.find_next(haystack, 12, true), Some(7..11));
+        assert_eq!(pattern.find_previous(haystack, 11, false), Some(7..11));
+
+        let header = BytePattern::parse("7F \"ELF\" 02").unwrap();
+        assert_eq!(header.find_all(haystack), vec![0..5]);
+
+        assert!(matches!(
+            BytePattern::parse("DE A"),
+            Err(SearchError::InvalidPattern(_))
+        ));
+        assert!(matches!(
+            BytePattern::parse("zz"),
+            Err(SearchError::InvalidPattern(_))
+        ));
+        assert_eq!(
+            BytePattern::parse("  ").unwrap_err(),
+            SearchError::EmptyPattern
+        );
+    }
 }
@@ -0,0 +1,448 @@
+//! Reviewable multi-file replace plans with per-match selection and unified diff previews.
+//! （可審閱的多檔取代計畫，支援逐筆選取與 unified diff 預覽。）
+
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    FileSearchInput, FileSearchResult, SearchEngine, SearchError, SearchMatch, SearchOptions,
+    SearchReport, SearchScope,
+};
+
+/// Candidate replacements across files; marked matches are the ones that will be applied.
+/// （跨檔案的取代候選清單；被標記的結果才會套用。）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplacePlan {
+    report: SearchReport,
+    files: Vec<PlannedFile>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PlannedFile {
+    original: String,
+    replacements: Vec<String>,
+}
+
+/// New contents for one file, produced from the selected replacements.
+/// （依選取的取代項目產生的單一檔案新內容。）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileRewrite<'a> {
+    pub path: &'a Path,
+    pub original: &'a str,
+    pub replaced: String,
+    pub replacements: usize,
+}
+
+impl ReplacePlan {
+    /// Finds every replacement candidate in the inputs; all of them start out selected.
+    /// （找出輸入中的所有取代候選，預設全部選取。）
+    pub fn build<'a, I>(
+        inputs: I,
+        options: &SearchOptions,
+        replacement: &str,
+    ) -> Result<Self, SearchError>
+    where
+        I: IntoIterator<Item = FileSearchInput<'a>>,
+    {
+        let mut scoped_options = options.clone();
+        scoped_options.scope = SearchScope::EntireDocument;
+        let mut results = Vec::new();
+        let mut files = Vec::new();
+        for input in inputs {
+            let candidates = SearchEngine::new(&input.contents)
+                .expand_replacements(replacement, &scoped_options)?;
+            if candidates.is_empty() {
+                continue;
+            }
+            let (matches, replacements): (Vec<_>, Vec<_>) = candidates
+                .into_iter()
+                .map(|(mut hit, expanded)| {
+                    hit.mark();
+                    (hit, expanded)
+                })
+                .unzip();
+            results.push(FileSearchResult::new(Some(input.path), matches));
+            files.push(PlannedFile {
+                original: input.contents.into_owned(),
+                replacements,
+            });
+        }
+        Ok(Self {
+            report: SearchReport::new(results),
+            files,
+        })
+    }
+
+    /// Returns the candidates as a report; `is_marked` flags the selected ones.
+    /// （以報告形式回傳候選項目，`is_marked` 表示已選取。）
+    pub fn report(&self) -> &SearchReport {
+        &self.report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.report.is_empty()
+    }
+
+    /// Number of candidates currently selected.
+    /// （目前選取的候選數量。）
+    pub fn selected_count(&self) -> usize {
+        self.matches().filter(|(_, hit, _)| hit.is_marked).count()
+    }
+
+    /// Iterates over `(path, match, replacement)` for every candidate.
+    /// （逐一列出每個候選的路徑、結果與取代文字。）
+    pub fn matches(&self) -> impl Iterator<Item = (&Path, &SearchMatch, &str)> {
+        self.report
+            .results
+            .iter()
+            .zip(&self.files)
+            .flat_map(|(entry, file)| {
+                let path = entry.path.as_deref().unwrap_or_else(|| Path::new(""));
+                entry
+                    .matches
+                    .iter()
+                    .zip(&file.replacements)
+                    .map(move |(hit, replacement)| (path, hit, replacement.as_str()))
+            })
+    }
+
+    /// Selects every candidate satisfying the predicate, reusing [`SearchReport::mark_where`].
+    /// （選取符合條件的候選，沿用 [`SearchReport::mark_where`]。）
+    pub fn mark_where<F>(&mut self, predicate: F) -> usize
+    where
+        F: FnMut(&SearchMatch) -> bool,
+    {
+        self.report.mark_where(predicate)
+    }
+
+    /// Deselects every candidate satisfying the predicate.
+    /// （取消選取符合條件的候選。）
+    pub fn unmark_where<F>(&mut self, mut predicate: F) -> usize
+    where
+        F: FnMut(&SearchMatch) -> bool,
+    {
+        let mut count = 0;
+        for hit in self
+            .report
+            .results
+            .iter_mut()
+            .flat_map(|entry| entry.matches.iter_mut())
+        {
+            if predicate(hit) {
+                hit.clear_mark();
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Selects or deselects one candidate by position; returns `false` when out of range.
+    /// （依位置選取或取消單一候選；超出範圍時回傳 `false`。）
+    pub fn set_marked(&mut self, file: usize, index: usize, marked: bool) -> bool {
+        let Some(hit) = self
+            .report
+            .results
+            .get_mut(file)
+            .and_then(|entry| entry.matches.get_mut(index))
+        else {
+            return false;
+        };
+        if marked {
+            hit.mark();
+        } else {
+            hit.clear_mark();
+        }
+        true
+    }
+
+    /// New contents for every file with at least one selected replacement.
+    /// （列出至少有一個選取項目的檔案及其新內容。）
+    pub fn rewrites(&self) -> Vec<FileRewrite<'_>> {
+        self.report
+            .results
+            .iter()
+            .zip(&self.files)
+            .filter_map(|(entry, file)| {
+                let edits = selected_edits(entry, file);
+                if edits.is_empty() {
+                    return None;
+                }
+                Some(FileRewrite {
+                    path: entry.path.as_deref().unwrap_or_else(|| Path::new("")),
+                    original: &file.original,
+                    replaced: apply_edits(&file.original, 0..file.original.len(), &edits),
+                    replacements: edits.len(),
+                })
+            })
+            .collect()
+    }
+
+    /// Unified diff of the selected replacements with `context` lines around each hunk.
+    /// （以 unified diff 呈現選取的取代，每個區塊前後保留 `context` 行。）
+    pub fn unified_diff(&self, context: usize) -> String {
+        let mut output = String::new();
+        for (entry, file) in self.report.results.iter().zip(&self.files) {
+            let edits = selected_edits(entry, file);
+            if edits.is_empty() {
+                continue;
+            }
+            let label = entry
+                .path
+                .as_deref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            let _ = writeln!(output, "--- {label}");
+            let _ = writeln!(output, "+++ {label}");
+            write_hunks(&mut output, &file.original, &edits, context);
+        }
+        output
+    }
+
+    /// Paths of every file holding candidates, selected or not.
+    /// （列出含有候選項目的所有檔案路徑。）
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.report
+            .results
+            .iter()
+            .filter_map(|entry| entry.path.as_ref())
+    }
+}
+
+/// Byte range in the original text and the text replacing it.
+/// （原始文字中的位元組範圍與其取代文字。）
+type Edit<'a> = (Range<usize>, &'a str);
+
+fn selected_edits<'a>(entry: &FileSearchResult, file: &'a PlannedFile) -> Vec<Edit<'a>> {
+    entry
+        .matches
+        .iter()
+        .zip(&file.replacements)
+        .filter(|(hit, _)| hit.is_marked)
+        .map(|(hit, replacement)| (hit.start..hit.end, replacement.as_str()))
+        .collect()
+}
+
+/// Applies the edits that fall inside `range` and returns the rewritten slice.
+/// （套用落在 `range` 內的編輯並回傳改寫後的片段。）
+fn apply_edits(text: &str, range: Range<usize>, edits: &[Edit<'_>]) -> String {
+    let mut output = String::with_capacity(range.len());
+    let mut last = range.start;
+    for (span, replacement) in edits {
+        if span.start < range.start || span.end > range.end {
+            continue;
+        }
+        output.push_str(&text[last..span.start]);
+        output.push_str(replacement);
+        last = span.end;
+    }
+    output.push_str(&text[last..range.end]);
+    output
+}
+
+/// A run of changed original lines `[first, last)` plus the lines replacing them.
+/// （一段變更的原始行 `[first, last)` 與取而代之的新行。）
+struct ChangeBlock {
+    first: usize,
+    last: usize,
+    new_lines: Vec<String>,
+}
+
+fn write_hunks(output: &mut String, original: &str, edits: &[Edit<'_>], context: usize) {
+    let lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let mut starts = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+    for line in &lines {
+        starts.push(offset);
+        offset += line.len();
+    }
+    starts.push(offset);
+    let line_of = |position: usize| starts.partition_point(|&start| start <= position) - 1;
+
+    // 以受影響的完整行為單位合併編輯。 / Group edits by the whole lines they touch.
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    for (span, _) in edits {
+        let first = line_of(span.start).min(lines.len().saturating_sub(1));
+        let last = if span.end > span.start && original[..span.end].ends_with('\n') {
+            line_of(span.end)
+        } else {
+            line_of(span.end) + 1
+        }
+        .min(lines.len())
+        // 空白原文沒有任何行，插入僅佔零行。 / An empty original has no lines, so an insertion spans none.
+        .max((first + 1).min(lines.len()));
+        match spans.last_mut() {
+            Some(previous) if first <= previous.end => previous.end = previous.end.max(last),
+            _ => spans.push(first..last),
+        }
+    }
+    let blocks: Vec<ChangeBlock> = spans
+        .into_iter()
+        .map(|span| {
+            let bytes = starts[span.start]..starts[span.end];
+            let rewritten = apply_edits(original, bytes, edits);
+            ChangeBlock {
+                first: span.start,
+                last: span.end,
+                new_lines: rewritten
+                    .split_inclusive('\n')
+                    .map(str::to_string)
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let mut delta: isize = 0;
+    let mut index = 0;
+    while index < blocks.len() {
+        // 區塊間距不超過兩倍上下文時併入同一個 hunk。 / Blocks closer than twice the context share a hunk.
+        let mut end = index + 1;
+        while end < blocks.len() && blocks[end].first - blocks[end - 1].last <= 2 * context {
+            end += 1;
+        }
+        let group = &blocks[index..end];
+        let old_start = group[0].first.saturating_sub(context);
+        let old_end = (group[group.len() - 1].last + context).min(lines.len());
+        let growth: isize = group
+            .iter()
+            .map(|block| block.new_lines.len() as isize - (block.last - block.first) as isize)
+            .sum();
+        let old_count = old_end - old_start;
+        let new_count = (old_count as isize + growth) as usize;
+        let new_start = (old_start as isize + delta) as usize;
+        let _ = writeln!(
+            output,
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        );
+
+        let mut cursor = old_start;
+        for block in group {
+            for line in &lines[cursor..block.first] {
+                push_line(output, ' ', line);
+            }
+            for line in &lines[block.first..block.last] {
+                push_line(output, '-', line);
+            }
+            for line in &block.new_lines {
+                push_line(output, '+', line);
+            }
+            cursor = block.last;
+        }
+        for line in &lines[cursor..old_end] {
+            push_line(output, ' ', line);
+        }
+
+        delta += growth;
+        index = end;
+    }
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+fn push_line(output: &mut String, prefix: char, line: &str) {
+    output.push(prefix);
+    match line.strip_suffix('\n') {
+        Some(body) => {
+            output.push_str(body);
+            output.push('\n');
+        }
+        None => {
+            output.push_str(line);
+            output.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchMode;
+
+    fn plan(
+        inputs: Vec<FileSearchInput<'static>>,
+        pattern: &str,
+        replacement: &str,
+    ) -> ReplacePlan {
+        let mut options = SearchOptions::new(pattern);
+        options.mode = SearchMode::Regex;
+        ReplacePlan::build(inputs, &options, replacement).unwrap()
+    }
+
+    #[test]
+    fn selection_controls_rewrites_and_diff() {
+        let text = "one foo\ntwo\nthree\nfour\nfive\nsix\nseven\neight foo\nnine foo";
+        let mut plan = plan(
+            vec![
+                FileSearchInput::new("a.txt", text),
+                FileSearchInput::new("b.txt", "nothing here\n"),
+            ],
+            r"f(oo)",
+            "b$1r",
+        );
+        assert_eq!(plan.report().total_matches, 3);
+        assert_eq!(plan.selected_count(), 3);
+        assert_eq!(
+            plan.matches().map(|(_, _, r)| r).collect::<Vec<_>>(),
+            ["boor"; 3]
+        );
+
+        assert!(plan.set_marked(0, 1, false));
+        assert!(!plan.set_marked(3, 0, false));
+        let rewrites = plan.rewrites();
+        assert_eq!(rewrites.len(), 1);
+        assert_eq!(rewrites[0].replacements, 2);
+        assert_eq!(
+            rewrites[0].replaced,
+            "one boor\ntwo\nthree\nfour\nfive\nsix\nseven\neight foo\nnine boor"
+        );
+
+        assert_eq!(
+            plan.unified_diff(1),
+            "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n-one foo\n+one boor\n two\n\
+             @@ -8,2 +8,2 @@\n eight foo\n-nine foo\n\\ No newline at end of file\n\
+             +nine boor\n\\ No newline at end of file\n"
+        );
+
+        assert_eq!(plan.unmark_where(|hit| hit.line == 1), 1);
+        plan.mark_where(|hit| hit.line == 8);
+        let diff = plan.unified_diff(3);
+        assert!(diff.starts_with("--- a.txt\n+++ a.txt\n@@ -5,5 +5,5 @@\n five\n"));
+        assert!(diff.ends_with("-eight foo\n-nine foo\n\\ No newline at end of file\n+eight boor\n+nine boor\n\\ No newline at end of file\n"));
+    }
+
+    #[test]
+    fn multi_line_replacements_shift_new_line_numbers() {
+        let plan = plan(
+            vec![FileSearchInput::new("c.txt", "a;b\nc\nd;e\n")],
+            ";",
+            r"\n",
+        );
+        assert_eq!(
+            plan.unified_diff(0),
+            "--- c.txt\n+++ c.txt\n@@ -1 +1,2 @@\n-a;b\n+a\n+b\n@@ -3 +4,2 @@\n-d;e\n+d\n+e\n"
+        );
+        assert_eq!(plan.rewrites()[0].replaced, "a\nb\nc\nd\ne\n");
+    }
+
+    #[test]
+    fn empty_width_edit_in_an_empty_original_inserts_lines() {
+        let mut output = String::new();
+        write_hunks(&mut output, "", &[(0..0, "header\n")], 3);
+        assert_eq!(output, "@@ -0,0 +1 @@\n+header\n");
+
+        let mut output = String::new();
+        write_hunks(&mut output, "abc\n", &[(4..4, "tail")], 1);
+        assert_eq!(
+            output,
+            "@@ -1 +1,2 @@\n-abc\n+abc\n+tail\n\\ No newline at end of file\n"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Replacement templates and extended-mode escapes.
+//!
+//! Regex replacements follow the Notepad++ (Boost) format: `$1`, `${name}`, `$&`, `\1`
+//! back-references, `\U`/`\L`/`\E` case conversion, `\u`/`\l` for the next character,
+//! conditionals such as `(?1yes:no)` and the escapes `\n`, `\r`, `\t` and `\xNN`.
+//! Extended mode interprets only the escapes, in both the pattern and the replacement.
+//! 正規表示式取代採用 Notepad++（Boost）格式：支援 `$1`、`${name}`、`$&`、`\1` 反向參照、
+//! `\U`/`\L`/`\E` 大小寫轉換、`\u`/`\l` 轉換下一個字元、`(?1yes:no)` 條件式，以及
+//! `\n`、`\r`、`\t`、`\xNN` 跳脫字元。延伸模式則只在樣式與取代字串中解析跳脫字元。
+
+use regex::Captures;
+
+use crate::SearchError;
+
+/// Resolves extended-mode escapes: `\n`, `\r`, `\t`, `\0`, `\\` and `\xNN`.
+/// Unknown escapes are kept verbatim, matching Notepad++.
+/// （解析延伸模式跳脫字元：`\n`、`\r`、`\t`、`\0`、`\\` 與 `\xNN`；未知的跳脫序列原樣保留，與 Notepad++ 相同。）
+pub fn unescape_extended(text: &str) -> Result<String, SearchError> {
+    let mut chars = text.chars().peekable();
+    let mut result = String::with_capacity(text.len());
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            None => result.push('\\'),
+            Some('x') => result.push(hex_escape(&mut chars).map_err(SearchError::InvalidPattern)?),
+            Some(next) => match simple_escape(next) {
+                Some(resolved) => result.push(resolved),
+                None => {
+                    result.push('\\');
+                    result.push(next);
+                }
+            },
+        }
+    }
+    Ok(result)
+}
+
+fn simple_escape(ch: char) -> Option<char> {
+    match ch {
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        '0' => Some('\0'),
+        '\\' => Some('\\'),
+        _ => None,
+    }
+}
+
+fn hex_escape(chars: &mut impl Iterator<Item = char>) -> Result<char, String> {
+    let digits: String = chars.take(2).collect();
+    if digits.chars().count() != 2 {
+        return Err(format!("incomplete hex escape `\\x{digits}`"));
+    }
+    u8::from_str_radix(&digits, 16)
+        .map(char::from)
+        .map_err(|_| format!("invalid hex escape `\\x{digits}`"))
+}
+
+/// Reference to a capture group by index or by name.
+/// （以索引或名稱參照的擷取群組。）
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum GroupRef {
+    Index(usize),
+    Name(String),
+}
+
+impl GroupRef {
+    fn parse(name: &str) -> Self {
+        match name.parse() {
+            Ok(index) => GroupRef::Index(index),
+            Err(_) => GroupRef::Name(name.to_string()),
+        }
+    }
+
+    fn resolve<'h>(&self, caps: &Captures<'h>) -> Option<&'h str> {
+        match self {
+            GroupRef::Index(index) => caps.get(*index),
+            GroupRef::Name(name) => caps.name(name),
+        }
+        .map(|group| group.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CaseOp {
+    UpperAll,
+    LowerAll,
+    UpperNext,
+    LowerNext,
+    End,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Group(GroupRef),
+    Case(CaseOp),
+    Conditional {
+        group: GroupRef,
+        matched: Vec<Part>,
+        unmatched: Vec<Part>,
+    },
+}
+
+/// Parsed regex replacement string.
+/// （已解析的正規表示式取代字串。）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplaceTemplate {
+    parts: Vec<Part>,
+}
+
+impl ReplaceTemplate {
+    /// Parses a replacement, rejecting unterminated conditionals and malformed escapes.
+    /// （解析取代字串；未結束的條件式或無效的跳脫序列會回傳錯誤。）
+    pub fn parse(template: &str) -> Result<Self, SearchError> {
+        let mut parser = TemplateParser {
+            chars: template.chars().collect(),
+            pos: 0,
+        };
+        let (parts, terminator) = parser.parse_sequence(false)?;
+        debug_assert!(terminator.is_none());
+        Ok(Self { parts })
+    }
+
+    /// Appends the expansion for one match to `out`.
+    /// （將單筆結果的展開內容附加至 `out`。）
+    pub fn expand(&self, caps: &Captures<'_>, out: &mut String) {
+        let mut writer = CaseWriter {
+            out,
+            mode: None,
+            next: None,
+        };
+        writer.write_parts(&self.parts, caps);
+    }
+}
+
+struct TemplateParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl TemplateParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += 1;
+        Some(ch)
+    }
+
+    /// Parses until the end of input or, inside a conditional branch, an unbalanced `:` or `)`.
+    /// （解析至輸入結尾；於條件式分支內則解析至未配對的 `:` 或 `)`。）
+    fn parse_sequence(
+        &mut self,
+        in_branch: bool,
+    ) -> Result<(Vec<Part>, Option<char>), SearchError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        // 分支內一般括號的深度。 / Depth of plain parentheses inside a branch.
+        let mut depth = 0usize;
+        while let Some(ch) = self.bump() {
+            let part = match ch {
+                ':' | ')' if in_branch && depth == 0 => {
+                    flush(&mut literal, &mut parts);
+                    return Ok((parts, Some(ch)));
+                }
+                '(' if self.peek() == Some('?') => {
+                    self.pos += 1;
+                    self.parse_conditional()?
+                }
+                '(' if in_branch => {
+                    depth += 1;
+                    literal.push(ch);
+                    continue;
+                }
+                ')' if in_branch => {
+                    depth -= 1;
+                    literal.push(ch);
+                    continue;
+                }
+                '$' => match self.parse_dollar() {
+                    Some(group) => Part::Group(group),
+                    None => {
+                        literal.push('$');
+                        continue;
+                    }
+                },
+                '\\' => match self.parse_backslash()? {
+                    Ok(part) => part,
+                    Err(resolved) => {
+                        literal.push(resolved);
+                        continue;
+                    }
+                },
+                other => {
+                    literal.push(other);
+                    continue;
+                }
+            };
+            flush(&mut literal, &mut parts);
+            parts.push(part);
+        }
+        if in_branch {
+            return Err(SearchError::InvalidReplacement(
+                "unterminated conditional `(?...)`".into(),
+            ));
+        }
+        flush(&mut literal, &mut parts);
+        Ok((parts, None))
+    }
+
+    fn parse_conditional(&mut self) -> Result<Part, SearchError> {
+        let name: String = if self.peek() == Some('{') {
+            self.pos += 1;
+            let name = self.take_while(|ch| ch != '}');
+            if self.bump() != Some('}') {
+                return Err(SearchError::InvalidReplacement(
+                    "unterminated group name in conditional".into(),
+                ));
+            }
+            name
+        } else {
+            self.take_while(|ch| ch.is_ascii_digit())
+        };
+        if name.is_empty() {
+            return Err(SearchError::InvalidReplacement(
+                "conditional `(?...)` needs a group number or `{name}`".into(),
+            ));
+        }
+        let (matched, terminator) = self.parse_sequence(true)?;
+        let unmatched = match terminator {
+            Some(':') => {
+                let (unmatched, terminator) = self.parse_sequence(true)?;
+                if terminator != Some(')') {
+                    return Err(SearchError::InvalidReplacement(
+                        "conditional has more than one `:`; escape it as `\\:`".into(),
+                    ));
+                }
+                unmatched
+            }
+            _ => Vec::new(),
+        };
+        Ok(Part::Conditional {
+            group: GroupRef::parse(&name),
+            matched,
+            unmatched,
+        })
+    }
+
+    /// `$N` (digits only), `$name`, `${name}`, `$&`; `$$` and a lone `$` yield `None` (a literal dollar).
+    /// （`$N`（僅數字）、`$name`、`${name}`、`$&`；`$$` 與單獨的 `$` 回傳 `None`，代表字面上的錢號。）
+    fn parse_dollar(&mut self) -> Option<GroupRef> {
+        match self.peek()? {
+            '$' => {
+                self.pos += 1;
+                None
+            }
+            '&' => {
+                self.pos += 1;
+                Some(GroupRef::Index(0))
+            }
+            '{' => {
+                let start = self.pos;
+                self.pos += 1;
+                let name = self.take_while(|ch| ch != '}');
+                if name.is_empty() || self.bump() != Some('}') {
+                    self.pos = start;
+                    return None;
+                }
+                Some(GroupRef::parse(&name))
+            }
+            // 與 Notepad++ 相同，`$1Name` 是群組 1 加上 `Name`。 / As in Notepad++, `$1Name` is group 1 then `Name`.
+            digit if digit.is_ascii_digit() => {
+                Some(GroupRef::parse(&self.take_while(|ch| ch.is_ascii_digit())))
+            }
+            _ => {
+                let name = self.take_while(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+                (!name.is_empty()).then_some(GroupRef::Name(name))
+            }
+        }
+    }
+
+    /// Returns either a template part or a resolved literal character.
+    /// （回傳範本片段或已解析的字元。）
+    fn parse_backslash(&mut self) -> Result<Result<Part, char>, SearchError> {
+        let Some(next) = self.bump() else {
+            return Ok(Err('\\'));
+        };
+        Ok(match next {
+            'U' => Ok(Part::Case(CaseOp::UpperAll)),
+            'L' => Ok(Part::Case(CaseOp::LowerAll)),
+            'u' => Ok(Part::Case(CaseOp::UpperNext)),
+            'l' => Ok(Part::Case(CaseOp::LowerNext)),
+            'E' => Ok(Part::Case(CaseOp::End)),
+            digit @ '0'..='9' => Ok(Part::Group(GroupRef::Index(
+                digit.to_digit(10).unwrap_or_default() as usize,
+            ))),
+            'x' => {
+                let mut iter = self.chars[self.pos..].iter().copied();
+                let resolved = hex_escape(&mut iter).map_err(SearchError::InvalidReplacement)?;
+                self.pos += 2;
+                Err(resolved)
+            }
+            other => Err(simple_escape(other).unwrap_or(other)),
+        })
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+}
+
+fn flush(literal: &mut String, parts: &mut Vec<Part>) {
+    if !literal.is_empty() {
+        parts.push(Part::Literal(std::mem::take(literal)));
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+/// Output sink applying the active case conversion.
+/// （套用目前大小寫轉換的輸出端。）
+struct CaseWriter<'a> {
+    out: &'a mut String,
+    mode: Option<Case>,
+    next: Option<Case>,
+}
+
+impl CaseWriter<'_> {
+    fn write_parts(&mut self, parts: &[Part], caps: &Captures<'_>) {
+        for part in parts {
+            match part {
+                Part::Literal(text) => self.write(text),
+                Part::Group(group) => self.write(group.resolve(caps).unwrap_or_default()),
+                Part::Case(op) => match op {
+                    CaseOp::UpperAll => self.mode = Some(Case::Upper),
+                    CaseOp::LowerAll => self.mode = Some(Case::Lower),
+                    CaseOp::UpperNext => self.next = Some(Case::Upper),
+                    CaseOp::LowerNext => self.next = Some(Case::Lower),
+                    CaseOp::End => {
+                        self.mode = None;
+                        self.next = None;
+                    }
+                },
+                Part::Conditional {
+                    group,
+                    matched,
+                    unmatched,
+                } => {
+                    let branch = if group.resolve(caps).is_some() {
+                        matched
+                    } else {
+                        unmatched
+                    };
+                    self.write_parts(branch, caps);
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        for ch in text.chars() {
+            match self.next.take().or(self.mode) {
+                Some(Case::Upper) => self.out.extend(ch.to_uppercase()),
+                Some(Case::Lower) => self.out.extend(ch.to_lowercase()),
+                None => self.out.push(ch),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn expand(pattern: &str, template: &str, haystack: &str) -> String {
+        let regex = Regex::new(pattern).unwrap();
+        let caps = regex.captures(haystack).unwrap();
+        let mut out = String::new();
+        ReplaceTemplate::parse(template)
+            .unwrap()
+            .expand(&caps, &mut out);
+        out
+    }
+
+    #[test]
+    fn templates_convert_case_and_branch_on_groups() {
+        let pattern = r"(?P<first>\w+) (\w+)(!)?";
+        assert_eq!(
+            expand(pattern, r"\U$1\E-\L$2", "hello World"),
+            "HELLO-world"
+        );
+        assert_eq!(
+            expand(pattern, r"\u$2 \l${first}", "alpha Beta"),
+            "Beta alpha"
+        );
+        assert_eq!(expand(pattern, r"\2\t\1$$", "a b"), "b\ta$");
+        assert_eq!(expand(pattern, r"$2(?3 (loud):.)", "a b!"), "b (loud)");
+        assert_eq!(expand(pattern, r"$2(?3 (loud):.)", "a b"), "b.");
+        assert_eq!(expand(pattern, r"(?{first}\U${first}\x21)", "ok go"), "OK!");
+        assert_eq!(expand(pattern, r"[$&] $ (x)", "p q"), "[p q] $ (x)");
+        assert_eq!(expand(pattern, r"$2x $first", "k v"), "vx k");
+
+        assert!(matches!(
+            ReplaceTemplate::parse("(?1yes"),
+            Err(SearchError::InvalidReplacement(_))
+        ));
+        assert!(matches!(
+            ReplaceTemplate::parse(r"\xZ1"),
+            Err(SearchError::InvalidReplacement(_))
+        ));
+    }
+
+    #[test]
+    fn extended_escapes_resolve_control_characters() {
+        assert_eq!(
+            unescape_extended(r"a\tb\r\n\x41\0\\\q").unwrap(),
+            "a\tb\r\nA\0\\\\q"
+        );
+        assert!(matches!(
+            unescape_extended(r"\x4"),
+            Err(SearchError::InvalidPattern(_))
+        ));
+    }
+}
@@ -16,7 +16,8 @@ pub use localization::{
     LocaleCatalogStats, LocaleSummary, LocalizationError, LocalizationManager, LocalizationParams,
 };
 pub use preferences::{
-    EditorPreferences, Preferences, PreferencesError, PreferencesStore, UiPreferences,
+    EditorPreferences, LanguageServerPreference, LspPreferences, Preferences, PreferencesError,
+    PreferencesStore, UiPreferences,
 };
 pub use recent::RecentFiles;
 pub use snippets::{SnippetDefinition, SnippetStore};
@@ -39,6 +39,7 @@ const DEFAULT_STRINGS: &[(&str, &str)] = &[
     ("menu.edit.delete", "Delete"),
     ("menu.edit.select_all", "Select All"),
     ("menu.edit.column_editor", "Column Editor..."),
+    ("menu.edit.rename_symbol", "Rename Symbol..."),
     ("menu.search", "Search"),
     ("menu.search.find", "Find..."),
     ("menu.search.find_next", "Find Next"),
@@ -46,6 +47,19 @@ const DEFAULT_STRINGS: &[(&str, &str)] = &[
     ("menu.search.replace", "Replace..."),
     ("menu.search.find_in_files", "Find in Files..."),
     ("menu.search.bookmark", "Bookmark ▸"),
+    ("menu.search.mark_style_1", "Mark All: Using 1st Style"),
+    ("menu.search.mark_style_2", "Mark All: Using 2nd Style"),
+    ("menu.search.mark_style_3", "Mark All: Using 3rd Style"),
+    ("menu.search.mark_style_4", "Mark All: Using 4th Style"),
+    ("menu.search.mark_style_5", "Mark All: Using 5th Style"),
+    ("menu.search.clear_marks", "Clear All Marks"),
+    ("menu.search.next_mark", "Jump to Next Mark"),
+    ("menu.search.previous_mark", "Jump to Previous Mark"),
+    ("menu.search.copy_marked_text", "Copy Marked Text"),
+    ("menu.search.delete_marked_lines", "Delete Marked Lines"),
+    ("menu.search.goto_definition", "Go to Definition"),
+    ("menu.search.find_references", "Find References"),
+    ("menu.search.show_hover", "Show Symbol Info"),
     ("menu.view", "View"),
     ("menu.view.toggle_fullscreen", "Toggle Full Screen"),
     ("menu.view.restore_zoom", "Restore Default Zoom"),
@@ -113,6 +127,7 @@ const DEFAULT_STRINGS: &[(&str, &str)] = &[
     ("dialog.find.mode.normal", "Normal"),
     ("dialog.find.mode.extended", "Extended (\\n, \\r, ...)"),
     ("dialog.find.mode.regex", "Regular expression"),
+    ("dialog.find.mode.fuzzy", "Fuzzy (characters in order)"),
     ("dialog.find.regex_dot", "`.` matches newline"),
     ("dialog.find.button.find_next", "Find Next"),
     ("dialog.find.button.count", "Count"),
@@ -120,6 +135,18 @@ const DEFAULT_STRINGS: &[(&str, &str)] = &[
     ("dialog.find.button.find_all_current", "Find All in Current Document"),
     ("dialog.find.button.close", "Close"),
     ("dialog.find.placeholder", "{0} is not available in the preview build."),
+    ("dialog.find.filters", "Filters:"),
+    ("dialog.find.filters_hint", "*.rs *.toml !target"),
+    ("dialog.find.directory", "Directory:"),
+    ("dialog.find.option.hidden", "In hidden folders"),
+    ("dialog.find.option.respect_ignore", "Respect .gitignore"),
+    ("dialog.find.button.find_all_files", "Find All"),
+    ("dialog.find.button.stop", "Stop"),
+    ("dialog.find.history", "History:"),
+    ("dialog.find.presets", "Presets:"),
+    ("dialog.find.preset_hint", "Preset name"),
+    ("dialog.find.button.save_preset", "Save Preset"),
+    ("dialog.find.button.delete_preset", "Delete Preset"),
     ("panel.project.title", "Project Panel"),
     ("panel.secondary.title", "Secondary View (preview)"),
     ("panel.secondary.active", "Active: {0}"),
@@ -147,11 +174,11 @@ const DEFAULT_STRINGS: &[(&str, &str)] = &[
         "panel.notifications.font_warning",
         "⚠ Theme 'Nordic Daylight' missing custom font, using fallback.",
     ),
-    ("panel.lsp.title", "LSP Diagnostics"),
+    ("panel.lsp.title", "Problems"),
     ("panel.lsp.connected", "Language server connected"),
     ("panel.lsp.offline", "Language server offline"),
     ("panel.lsp.no_diagnostics", "No diagnostics."),
-    ("panel.lsp.more_diagnostics", "... {0} more diagnostics"),
+    ("panel.lsp.entry", "Ln {0}, Col {1}: {2}"),
     (
         "panel.generic.no_content",
         "Panel '{panel}' has no content in preview mode.",
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -48,6 +49,8 @@ pub struct Preferences {
     pub editor: EditorPreferences,
     #[serde(default)]
     pub ui: UiPreferences,
+    #[serde(default)]
+    pub lsp: LspPreferences,
 }
 
 fn default_version() -> u32 {
@@ -60,6 +63,7 @@ impl Default for Preferences {
             version: PREFERENCES_VERSION,
             editor: EditorPreferences::default(),
             ui: UiPreferences::default(),
+            lsp: LspPreferences::default(),
         }
     }
 }
@@ -71,6 +75,7 @@ impl Preferences {
         }
         self.editor.sanitize();
         self.ui.sanitize();
+        self.lsp.sanitize();
     }
 }
 
@@ -88,6 +93,8 @@ pub struct EditorPreferences {
     pub editor_font_family: String,
     #[serde(default = "default_editor_font_size")]
     pub editor_font_size: u32,
+    #[serde(default = "default_large_file_threshold_mb")]
+    pub large_file_threshold_mb: u32,
 }
 
 fn default_editor_font_family() -> String {
@@ -98,6 +105,10 @@ fn default_editor_font_size() -> u32 {
     12
 }
 
+fn default_large_file_threshold_mb() -> u32 {
+    64
+}
+
 fn default_true() -> bool {
     true
 }
@@ -115,6 +126,7 @@ impl Default for EditorPreferences {
             highlight_active_line: true,
             editor_font_family: default_editor_font_family(),
             editor_font_size: default_editor_font_size(),
+            large_file_threshold_mb: default_large_file_threshold_mb(),
         }
     }
 }
@@ -132,6 +144,10 @@ impl EditorPreferences {
             self.editor_font_size = default_editor_font_size();
         }
         self.editor_font_size = self.editor_font_size.clamp(6, 72);
+        if self.large_file_threshold_mb == 0 {
+            self.large_file_threshold_mb = default_large_file_threshold_mb();
+        }
+        self.large_file_threshold_mb = self.large_file_threshold_mb.clamp(1, 4096);
     }
 }
 
@@ -171,6 +187,35 @@ impl UiPreferences {
     }
 }
 
+/// Language servers launched by the editor, one entry per language.
+/// （編輯器啟動的語言伺服器，每種語言一筆。）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LspPreferences {
+    #[serde(default)]
+    pub servers: Vec<LanguageServerPreference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageServerPreference {
+    pub language: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl LspPreferences {
+    fn sanitize(&mut self) {
+        for server in &mut self.servers {
+            server.language = server.language.trim().to_ascii_lowercase();
+            server.command = server.command.trim().to_string();
+        }
+        self.servers
+            .retain(|server| !server.language.is_empty() && !server.command.is_empty());
+    }
+}
+
 #[derive(Debug)]
 pub struct PreferencesStore {
     path: PathBuf,
@@ -1,4 +1,4 @@
-use rustnotepad_settings::{Preferences, PreferencesStore};
+use rustnotepad_settings::{LanguageServerPreference, Preferences, PreferencesStore};
 use std::fs;
 use tempfile::tempdir;
 
@@ -10,8 +10,10 @@ fn load_missing_file_returns_defaults() {
     let store = PreferencesStore::load(&path).expect("load defaults");
     assert!(store.preferences().editor.autosave_enabled);
     assert_eq!(store.preferences().editor.autosave_interval_minutes, 5);
+    assert_eq!(store.preferences().editor.large_file_threshold_mb, 64);
     assert_eq!(store.preferences().ui.locale, "en-US");
     assert_eq!(store.preferences().ui.theme, "Notepad++ Classic");
+    assert!(store.preferences().lsp.servers.is_empty());
 }
 
 #[test]
@@ -43,6 +45,7 @@ fn overwrite_replaces_existing_values() {
     let mut prefs = store.preferences().clone();
     prefs.editor.autosave_interval_minutes = 0;
     prefs.editor.show_line_numbers = false;
+    prefs.editor.large_file_threshold_mb = 0;
     prefs.ui.theme = String::new();
 
     store.overwrite(prefs).expect("overwrite");
@@ -50,6 +53,7 @@ fn overwrite_replaces_existing_values() {
     let current = store.preferences();
     assert_eq!(current.editor.autosave_interval_minutes, 5);
     assert!(!current.editor.show_line_numbers);
+    assert_eq!(current.editor.large_file_threshold_mb, 64);
     assert_eq!(current.ui.theme, "Notepad++ Classic");
 }
 
@@ -94,3 +98,38 @@ fn legacy_version_is_upgraded_on_load() {
         "specified locale should be preserved during migration"
     );
 }
+
+#[test]
+fn language_servers_roundtrip_and_drop_incomplete_entries() {
+    let temp = tempdir().expect("tempdir");
+    let path = temp.path().join("preferences.json");
+
+    let mut store = PreferencesStore::new(path.clone(), Preferences::default());
+    store
+        .update(|prefs| {
+            prefs.lsp.servers = vec![
+                LanguageServerPreference {
+                    language: " Rust ".into(),
+                    command: "rust-analyzer".into(),
+                    args: Vec::new(),
+                    env: [("RA_LOG".to_string(), "error".to_string())].into(),
+                },
+                LanguageServerPreference {
+                    language: "python".into(),
+                    command: "  ".into(),
+                    args: vec!["--stdio".into()],
+                    env: Default::default(),
+                },
+            ];
+        })
+        .expect("save");
+
+    let reloaded = PreferencesStore::load(&path).expect("reload");
+    let servers = &reloaded.preferences().lsp.servers;
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0].language, "rust");
+    assert_eq!(
+        servers[0].env.get("RA_LOG").map(String::as_str),
+        Some("error")
+    );
+}
@@ -17,9 +17,24 @@ use rustnotepad_autocomplete::{
     LanguageDictionaryProvider, LspProvider, Snippet, SnippetProvider,
 };
 use rustnotepad_cmdline::{FileTarget, LaunchConfig, ThemeSpec};
+use rustnotepad_core::hex::{parse_hex_bytes, parse_offset};
+use rustnotepad_core::{
+    line_ops, motion, multi_cursor, BytePattern, Caret, Document, DocumentError, EditorBuffer,
+    EditorError, Encoding, FileFilter, Motion, OccurrenceOptions, Selection, TextBuffer,
+    LargeFileDocument, LargeFileOptions, OpenedDocument, FindEvent, FindInFiles, FindInFilesTask,
+    HexDocument, HexEditMode, LineEnding, LineEndingStats, MarkStyle, MarkStyles, TrigramIndex,
+    WatchedIndex, WorkspaceIndex, DEFAULT_BYTES_PER_ROW,
+};
 use rustnotepad_function_list::{FunctionKind, ParserRegistry, RegexParser, RegexRule, TextRange};
-use rustnotepad_highlight::LanguageRegistry;
-use rustnotepad_lsp_client::{DiagnosticSeverity, LspClient};
+use rustnotepad_highlight::{
+    Grammar, GrammarError, HighlightError, HighlightPalette, HighlightToken,
+    IncrementalHighlighter, LanguageRegistry,
+};
+use rustnotepad_lsp_client::{
+    apply_text_edits, uri_to_document, Diagnostic, DiagnosticSeverity, Hover, LanguageServerConfig,
+    Location, LspClient, LspClientError, LspRange, LspReply, LspSymbolParser, LspWorker,
+    ServerStatus, TextPosition, WorkspaceEdit,
+};
 use rustnotepad_macros::{MacroError, MacroExecutor, MacroPlayer, MacroRecorder, MacroStore};
 use rustnotepad_plugin_admin as plugin_admin;
 use rustnotepad_plugin_host::{CommandOutcome, WasmPluginRuntime};
@@ -39,7 +54,8 @@ use rustnotepad_plugin_winabi::{
 };
 use rustnotepad_project::{
     AutosaveManifest, ProjectNode, ProjectNodeDraft, ProjectNodeId, ProjectNodeKind, ProjectTree,
-    ProjectTreeStore, SessionCaret, SessionScroll, SessionSnapshot, SessionStore, SessionTab,
+    ProjectTreeStore, SavedSearch, SavedSearchMode, SearchHistory, SearchHistoryStore,
+    SessionCaret, SessionMark, SessionScroll, SessionSnapshot, SessionStore, SessionTab,
     SessionWindow, UnsavedHash,
 };
 use rustnotepad_runexec::{RunExecutor, RunResult, RunSpec, StdinPayload};
@@ -75,8 +91,8 @@ use rustnotepad_printing::{
     PrintJobOptions, PrintPreviewKey, SimplePaginator,
 };
 use rustnotepad_search::{
-    FileSearchResult, SearchDirection, SearchEngine, SearchError, SearchMatch, SearchMode,
-    SearchOptions, SearchReport,
+    FileSearchResult, IncrementalSearch, SearchDirection, SearchEngine, SearchError, SearchMatch,
+    SearchMode, SearchOptions, SearchReport,
 };
 use serde_json;
 #[cfg(all(unix, not(target_os = "macos")))]
@@ -118,6 +134,8 @@ const MAX_MACRO_MESSAGES: usize = 10;
 const MAX_RUN_HISTORY: usize = 6;
 const MAX_EDITOR_HISTORY: usize = 128;
 const MAX_NOTIFICATION_MESSAGES: usize = 8;
+/// 巨檔模式下載入編輯區的行數上限。 / Number of lines shown in the editor for a file opened in large-file mode.
+const LARGE_FILE_PREVIEW_LINES: usize = 10_000;
 
 static MACRO_COMMAND_OPTIONS: &[(&str, &str, &str)] = &[
     (
@@ -162,6 +180,87 @@ struct PrintPreviewState {
     generation: u64,
 }
 
+/// 載入時偵測到的磁碟格式，儲存時沿用。 / On-disk format detected at load time and reused when saving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DocumentFormat {
+    encoding: Encoding,
+    line_ending: LineEnding,
+    has_bom: bool,
+}
+
+impl Default for DocumentFormat {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Lf,
+            has_bom: false,
+        }
+    }
+}
+
+impl DocumentFormat {
+    fn of(document: &Document) -> Self {
+        Self {
+            encoding: document.encoding(),
+            line_ending: document.line_ending(),
+            has_bom: document.has_bom(),
+        }
+    }
+
+    fn of_large(document: &LargeFileDocument) -> Self {
+        Self {
+            encoding: document.encoding(),
+            line_ending: document.line_ending(),
+            has_bom: document.has_bom(),
+        }
+    }
+
+    /// 狀態列顯示的編碼名稱，與編碼選單的標籤一致。 / Encoding label for the status bar, matching the Encoding menu labels.
+    fn status_label(&self) -> &'static str {
+        match (self.encoding, self.has_bom) {
+            (Encoding::Utf8, false) => "UTF-8",
+            (Encoding::Utf8, true) => "UTF-8 BOM",
+            (Encoding::Utf16Le, _) => "UCS-2 LE",
+            (Encoding::Utf16Be, _) => "UCS-2 BE",
+            (Encoding::Legacy(legacy), _) => legacy.name(),
+        }
+    }
+}
+
+/// 無法解碼為文字時改用的十六進位檢視狀態。 / State of the hex view used when a file cannot be decoded as text.
+struct HexViewState {
+    document: HexDocument,
+    cursor: usize,
+    edit_mode: HexEditMode,
+    goto_input: String,
+    pattern_input: String,
+    bytes_input: String,
+    matches: Vec<std::ops::Range<usize>>,
+    scroll_to_cursor: bool,
+    message: Option<String>,
+}
+
+impl HexViewState {
+    fn new(document: HexDocument) -> Self {
+        Self {
+            document,
+            cursor: 0,
+            edit_mode: HexEditMode::Overwrite,
+            goto_input: String::new(),
+            pattern_input: String::new(),
+            bytes_input: String::new(),
+            matches: Vec::new(),
+            scroll_to_cursor: false,
+            message: None,
+        }
+    }
+
+    fn move_cursor(&mut self, offset: usize) {
+        self.cursor = offset.min(self.document.len());
+        self.scroll_to_cursor = true;
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FindDialogTab {
     Find,
@@ -195,6 +294,7 @@ enum FindMode {
     Normal,
     Extended,
     Regex,
+    Fuzzy,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -1343,7 +1443,13 @@ static MENU_STRUCTURE: Lazy<Vec<MenuSection>> = Lazy::new(|| {
                 "menu.edit.paste",
                 "menu.edit.delete",
                 "menu.edit.select_all",
+                "menu.edit.add_next_occurrence",
+                "menu.edit.select_all_occurrences",
+                "menu.edit.add_caret_above",
+                "menu.edit.add_caret_below",
+                "menu.edit.split_selection_into_lines",
                 "menu.edit.column_editor",
+                "menu.edit.rename_symbol",
             ],
         ),
         MenuSection::new(
@@ -1355,6 +1461,19 @@ static MENU_STRUCTURE: Lazy<Vec<MenuSection>> = Lazy::new(|| {
                 "menu.search.replace",
                 "menu.search.find_in_files",
                 "menu.search.bookmark",
+                "menu.search.mark_style_1",
+                "menu.search.mark_style_2",
+                "menu.search.mark_style_3",
+                "menu.search.mark_style_4",
+                "menu.search.mark_style_5",
+                "menu.search.clear_marks",
+                "menu.search.next_mark",
+                "menu.search.previous_mark",
+                "menu.search.copy_marked_text",
+                "menu.search.delete_marked_lines",
+                "menu.search.goto_definition",
+                "menu.search.find_references",
+                "menu.search.show_hover",
             ],
         ),
         MenuSection::new(
@@ -1667,6 +1786,8 @@ struct StatusBarState {
     selection: usize,
     encoding: &'static str,
     eol: &'static str,
+    /// 目前文件混用行尾時的提示。 / Warning shown while the current document mixes line endings.
+    eol_warning: Option<String>,
     mode: &'static str,
     document_language: String,
     ui_language: String,
@@ -1684,6 +1805,7 @@ impl StatusBarState {
             selection: 0,
             encoding: "UTF-8",
             eol: "Windows (CR LF)",
+            eol_warning: None,
             mode: "INS",
             document_language: "Plain Text".into(),
             ui_language: short_locale_display_name(locale),
@@ -1703,45 +1825,23 @@ impl StatusBarState {
         self.document_language = language;
     }
 
-    fn refresh_cursor(&mut self, contents: &str) {
-        self.length = contents.chars().count();
-        // Count lines: newlines + 1, but don't count trailing empty line from final newline
-        let newline_count = contents.chars().filter(|&c| c == '\n').count();
-        self.lines = if contents.is_empty() {
-            1
-        } else if contents.ends_with('\n') {
-            newline_count.max(1)  // Don't add 1 for trailing newline
+    fn refresh_cursor(&mut self, text: &TextBuffer) {
+        self.length = text.len_chars();
+        // The empty line after a final newline is not counted
+        self.lines = if text.ends_with_newline() {
+            (text.line_count() - 1).max(1)
         } else {
-            newline_count + 1  // Add 1 for the last line without newline
+            text.line_count()
         };
         let caret_index = self.caret_char_index.min(self.length);
-        let (line, column) = Self::line_column_from_index(contents, caret_index);
-        self.line = line;
-        self.column = column;
+        let (line, column) = text.line_column(text.offset_of_char(caret_index));
+        self.line = line + 1;
+        self.column = column + 1;
     }
 
-    fn set_caret_index(&mut self, contents: &str, char_index: usize) {
+    fn set_caret_index(&mut self, text: &TextBuffer, char_index: usize) {
         self.caret_char_index = char_index;
-        self.refresh_cursor(contents);
-    }
-
-    fn line_column_from_index(contents: &str, char_index: usize) -> (usize, usize) {
-        let mut line = 1;
-        let mut column = 1;
-        let mut current_index = 0;
-        for ch in contents.chars() {
-            if current_index == char_index {
-                return (line, column);
-            }
-            if ch == '\n' {
-                line += 1;
-                column = 1;
-            } else {
-                column += 1;
-            }
-            current_index += 1;
-        }
-        (line, column)
+        self.refresh_cursor(text);
     }
 
     fn set_theme(&mut self, theme_name: &str) {
@@ -1790,6 +1890,20 @@ struct RustNotePadApp {
     available_monospace_fonts: Vec<String>,
     show_font_restart_dialog: bool,
     highlight_registry: LanguageRegistry,
+    /// 編輯區的增量高亮器；文件或語言切換時重建。 / Incremental highlighter for the editor, rebuilt on document or language switches.
+    editor_highlighter: Option<IncrementalHighlighter>,
+    /// 上一個畫面編輯區最上方的可見行。 / Topmost visible editor line in the previous frame.
+    editor_first_visible_line: usize,
+    /// 編輯區文字的編輯版本號，每次修改都會遞增。 / Edit revision of the editor text, bumped by every change.
+    editor_revision: u64,
+    /// 編輯區文字的 rope 鏡像，供游標移動與行列查詢。 / Rope mirror of the editor text for caret motions and line/column lookups.
+    editor_text: TextBuffer,
+    /// 主游標垂直移動時保留的（位置, 欄位）。 / (position, column) the primary caret keeps across vertical moves.
+    editor_goal_column: Option<(usize, usize)>,
+    /// 主游標以外的游標（位元組位置）；主游標仍由文字框管理。 / Carets besides the primary one, as byte offsets; the text box still owns the primary caret.
+    editor_extra_carets: Vec<Caret>,
+    /// 上一個畫面編輯區是否有焦點，決定多重游標快捷鍵是否生效。 / Whether the editor had focus last frame, which gates the multi-caret shortcuts.
+    editor_has_focus: bool,
     function_registry: ParserRegistry,
     project_tree: ProjectTree,
     project_tree_store: ProjectTreeStore,
@@ -1799,6 +1913,7 @@ struct RustNotePadApp {
     workspace_root: PathBuf,
     document_index: Arc<DocumentIndex>,
     lsp_client: Arc<LspClient>,
+    lsp_worker: LspWorker,
     autocomplete_engine: CompletionEngine,
     completion_prefix: String,
     completion_results: Vec<CompletionItem>,
@@ -1832,8 +1947,16 @@ struct RustNotePadApp {
     show_save_as_dialog: bool,
     save_dialog_path: String,
     save_dialog_error: Option<String>,
+    show_rename_dialog: bool,
+    rename_dialog_name: String,
+    rename_dialog_error: Option<String>,
+    rename_pending: bool,
     current_document_path: Option<PathBuf>,
     document_dirty: bool,
+    hex_view: Option<HexViewState>,
+    document_format: DocumentFormat,
+    /// 以巨檔模式開啟的唯讀文件。 / Read-only document opened in large-file mode.
+    large_file: Option<LargeFileDocument>,
     untitled_counter: usize,
     pending_exit: bool,
     print_preview: PrintPreviewState,
@@ -1867,7 +1990,22 @@ struct RustNotePadApp {
     find_mode: FindMode,
     find_regex_dot_matches_newline: bool,
     find_status: Option<UiMessage>,
+    /// 尋找對話框輸入即搜尋的狀態。 / Find-as-you-type state for the Find dialog.
+    find_incremental: IncrementalSearch,
+    find_in_files_directory: String,
+    find_in_files_filters: String,
+    find_in_files_hidden: bool,
+    find_in_files_respect_ignore: bool,
+    find_in_files_use_index: bool,
+    find_in_files_task: Option<FindInFilesTask>,
+    /// 「在檔案中尋找」使用的工作區索引，由檔案監看維持最新。 / Workspace index for Find in Files, kept current by a file watch.
+    workspace_search_index: Option<WatchedIndex>,
+    find_preset_name: String,
+    search_history: SearchHistory,
+    search_history_store: SearchHistoryStore,
     search_report: Option<SearchReport>,
+    mark_styles: MarkStyles,
+    active_mark_style: MarkStyle,
     #[cfg(target_os = "windows")]
     windows_handles: WindowsSessionHandles,
 }
@@ -1903,6 +2041,14 @@ fn build_function_registry() -> ParserRegistry {
         .expect("rust impl rule"),
     ];
     registry.register_parser(PREVIEW_LANGUAGE_ID, Box::new(RegexParser::new(rust_rules)));
+    // 啟用 tree-sitter 時以語法樹取代正則規則。 / With tree-sitter enabled, syntax trees replace the regex rules.
+    #[cfg(feature = "tree-sitter")]
+    for language in rustnotepad_highlight::TreeSitterLanguage::ALL {
+        match rustnotepad_highlight::TreeSitterBackend::new(language) {
+            Ok(backend) => registry.register_parser(language.id(), Box::new(backend)),
+            Err(err) => log_warn(format!("tree-sitter {} unavailable: {err}", language.id())),
+        }
+    }
     registry
 }
 
@@ -2066,8 +2212,11 @@ impl RustNotePadApp {
         let mut status =
             StatusBarState::new(&layout, &theme_manager.active_theme().name, &locale_display);
 
-        let highlight_registry = LanguageRegistry::with_defaults();
-        let function_registry = build_function_registry();
+        #[cfg(not(feature = "tree-sitter"))]
+        let mut highlight_registry = LanguageRegistry::with_defaults();
+        #[cfg(feature = "tree-sitter")]
+        let mut highlight_registry = LanguageRegistry::with_tree_sitter_defaults();
+        let mut function_registry = build_function_registry();
         let state_dir = workspace_root.join(".rustnotepad");
         if let Err(err) = fs::create_dir_all(&state_dir) {
             log_warn(format!(
@@ -2075,6 +2224,7 @@ impl RustNotePadApp {
                 state_dir.display()
             ));
         }
+        let grammar_skips = load_user_grammars(&mut highlight_registry, &state_dir.join("grammars"));
         let project_tree_store = ProjectTreeStore::new(state_dir.join("project_tree.json"));
         let project_tree = build_filesystem_project_tree(&workspace_root, 4, 200);
         if let Err(err) = project_tree_store.save(&project_tree) {
@@ -2092,15 +2242,40 @@ impl RustNotePadApp {
             session_dir.join("session.json"),
             session_dir.join("autosave"),
         );
+        let search_history_store = SearchHistoryStore::new(state_dir.join("search_history.json"));
+        let search_history = search_history_store.load().unwrap_or_else(|err| {
+            log_warn(format!("Failed to load search history: {err}"));
+            SearchHistory::default()
+        });
 
         let document_index = Arc::new(DocumentIndex::new());
         document_index.update_document(PREVIEW_DOCUMENT_ID, "");
         let lsp_client = Arc::new(LspClient::new());
+        // 伺服器啟動與請求皆在背景執行緒進行。 / Server start-up and requests run on a background thread.
+        let lsp_worker = LspWorker::spawn(lsp_client.clone());
+        for server in &current_preferences.lsp.servers {
+            let config = server.env.iter().fold(
+                LanguageServerConfig::new(server.language.clone(), server.command.clone())
+                    .with_args(server.args.clone()),
+                |config, (key, value)| config.with_env(key.clone(), value.clone()),
+            );
+            lsp_client.register_server(config);
+            // 函式清單優先採用伺服器的文件符號。 / Prefer the server's document symbols in the function list.
+            let fallback = function_registry.remove_parser(&server.language);
+            function_registry.register_parser(
+                server.language.clone(),
+                Box::new(
+                    LspSymbolParser::new(lsp_client.clone(), server.language.clone())
+                        .without_waiting()
+                        .with_fallback(fallback),
+                ),
+            );
+        }
         let mut autocomplete_engine = CompletionEngine::new();
         autocomplete_engine.register_provider(
             "lsp",
             0,
-            LspProvider::new(lsp_client.clone()).with_max_items(24),
+            LspProvider::new(Arc::new(lsp_worker.completions())).with_max_items(24),
         );
 
         let snippet_store = SnippetStore::builtin();
@@ -2218,6 +2393,13 @@ impl RustNotePadApp {
             available_monospace_fonts: get_available_monospace_fonts(),
             show_font_restart_dialog: false,
             highlight_registry,
+            editor_highlighter: None,
+            editor_first_visible_line: 0,
+            editor_revision: 0,
+            editor_text: TextBuffer::new(),
+            editor_goal_column: None,
+            editor_extra_carets: Vec::new(),
+            editor_has_focus: false,
             function_registry,
             project_tree,
             project_tree_store,
@@ -2227,6 +2409,7 @@ impl RustNotePadApp {
             workspace_root,
             document_index,
             lsp_client,
+            lsp_worker,
             autocomplete_engine,
             completion_prefix,
             completion_results: Vec::new(),
@@ -2260,8 +2443,15 @@ impl RustNotePadApp {
             show_save_as_dialog: false,
             save_dialog_path: String::new(),
             save_dialog_error: None,
+            show_rename_dialog: false,
+            rename_dialog_name: String::new(),
+            rename_dialog_error: None,
+            rename_pending: false,
             current_document_path: None,
             document_dirty: false,
+            hex_view: None,
+            document_format: DocumentFormat::default(),
+            large_file: None,
             untitled_counter: 1,
             pending_exit: false,
             print_preview: PrintPreviewState::new(),
@@ -2295,11 +2485,31 @@ impl RustNotePadApp {
             find_mode: FindMode::Normal,
             find_regex_dot_matches_newline: false,
             find_status: None,
+            find_incremental: IncrementalSearch::new(),
+            find_in_files_directory: String::new(),
+            find_in_files_filters: String::new(),
+            find_in_files_hidden: false,
+            find_in_files_respect_ignore: true,
+            find_in_files_use_index: false,
+            find_in_files_task: None,
+            workspace_search_index: None,
+            find_preset_name: String::new(),
+            search_history,
+            search_history_store,
             search_report: None,
+            mark_styles: MarkStyles::default(),
+            active_mark_style: MarkStyle::First,
             #[cfg(target_os = "windows")]
             windows_handles,
         };
-        app.status.refresh_cursor(&app.editor_preview);
+        app.editor_text = TextBuffer::from(app.editor_preview.as_str());
+        for (grammar, skipped) in grammar_skips {
+            app.push_localized_notification(
+                format!("Grammar {grammar}: {skipped} unsupported rules skipped"),
+                format!("語法 {grammar}：略過 {skipped} 條不支援的規則"),
+            );
+        }
+        app.status.refresh_cursor(&app.editor_text);
         app.refresh_completions();
         app.seed_profile_defaults();
 
@@ -2714,6 +2924,7 @@ impl RustNotePadApp {
             Some(tab.caret.column)
         };
         self.apply_caret_position(line, column);
+        self.restore_session_marks(&tab.marks);
     }
 
     fn prepare_primary_pane(&mut self) {
@@ -2725,16 +2936,21 @@ impl RustNotePadApp {
         self.document_index.remove_document(&previous_id);
         self.current_document_id = PREVIEW_DOCUMENT_ID.to_string();
         self.current_document_path = None;
+        self.document_format = DocumentFormat::default();
+        self.large_file = None;
+        self.status.eol_warning = None;
         self.current_language_id = PREVIEW_LANGUAGE_ID.to_string();
         self.editor_preview.clear();
+        self.editor_text_replaced();
         self.clear_search_results();
+        self.mark_styles.clear_all();
         self.autocomplete_panel_used = false;
         self.editor_undo_stack.clear();
         self.editor_redo_stack.clear();
         self.pending_editor_selection = None;
         self.update_editor_selection(None);
         self.document_dirty = false;
-        self.status.refresh_cursor(&self.editor_preview);
+        self.status.refresh_cursor(&self.editor_text);
         self.document_index
             .update_document(&self.current_document_id, &self.editor_preview);
     }
@@ -2885,7 +3101,11 @@ impl RustNotePadApp {
             self.completion_prefix.clone(),
         )
         .with_max_items(16)
-        .with_language(Some(self.current_language_id.clone()));
+        .with_language(Some(self.current_language_id.clone()))
+        .with_position(
+            self.status.line.saturating_sub(1),
+            self.status.column.saturating_sub(1),
+        );
         if let Some(language) = self
             .highlight_registry
             .get(self.current_language_id.as_str())
@@ -3102,10 +3322,12 @@ impl RustNotePadApp {
     }
 
     fn after_macro_edit(&mut self) {
+        self.editor_text_replaced();
         self.clear_search_results();
         self.document_index
             .update_document(&self.current_document_id, &self.editor_preview);
-        self.status.refresh_cursor(&self.editor_preview);
+        self.sync_lsp_document();
+        self.status.refresh_cursor(&self.editor_text);
         self.refresh_completions();
         self.mark_document_dirty();
     }
@@ -3332,6 +3554,18 @@ impl RustNotePadApp {
             "menu.edit.paste" => self.perform_paste(),
             "menu.edit.delete" => self.perform_delete(),
             "menu.edit.select_all" => self.select_all_in_editor(),
+            "menu.edit.add_next_occurrence" => self.run_multi_caret(|buffer| {
+                multi_cursor::add_next_occurrence(buffer, OccurrenceOptions::default())
+            }),
+            "menu.edit.select_all_occurrences" => self.run_multi_caret(|buffer| {
+                multi_cursor::select_all_occurrences(buffer, OccurrenceOptions::default())
+            }),
+            "menu.edit.add_caret_above" => self.run_multi_caret(multi_cursor::add_caret_above),
+            "menu.edit.add_caret_below" => self.run_multi_caret(multi_cursor::add_caret_below),
+            "menu.edit.split_selection_into_lines" => {
+                self.run_multi_caret(multi_cursor::split_selection_into_lines)
+            }
+            "menu.edit.rename_symbol" => self.open_rename_dialog(),
             _ => log_warn(self.localized_owned(
                 format!("Unsupported edit command {item_key}"),
                 format!("未支援的編輯指令 {item_key}"),
@@ -3362,6 +3596,19 @@ impl RustNotePadApp {
                     "切換書籤（預覽動作）。",
                 );
             }
+            "menu.search.mark_style_1" => self.mark_with_style(MarkStyle::First),
+            "menu.search.mark_style_2" => self.mark_with_style(MarkStyle::Second),
+            "menu.search.mark_style_3" => self.mark_with_style(MarkStyle::Third),
+            "menu.search.mark_style_4" => self.mark_with_style(MarkStyle::Fourth),
+            "menu.search.mark_style_5" => self.mark_with_style(MarkStyle::Fifth),
+            "menu.search.clear_marks" => self.mark_styles.clear_all(),
+            "menu.search.next_mark" => self.jump_to_mark(SearchDirection::Forward),
+            "menu.search.previous_mark" => self.jump_to_mark(SearchDirection::Backward),
+            "menu.search.copy_marked_text" => self.copy_marked_text(),
+            "menu.search.delete_marked_lines" => self.delete_marked_lines(),
+            "menu.search.goto_definition" => self.goto_definition(),
+            "menu.search.find_references" => self.find_references(),
+            "menu.search.show_hover" => self.show_hover(),
             _ => log_warn(self.localized_owned(
                 format!("Unsupported search command {item_key}"),
                 format!("未支援的搜尋指令 {item_key}"),
@@ -3382,6 +3629,7 @@ impl RustNotePadApp {
                 return Err(());
             }
         };
+        self.record_search_history();
         let start_byte = self.search_start_byte(direction);
         let engine = SearchEngine::new(&self.editor_preview);
         match engine.find(start_byte, &options) {
@@ -3410,6 +3658,50 @@ impl RustNotePadApp {
         }
     }
 
+    /// 尋找對話框的輸入即搜尋：結果依編輯版本號快取，並選取游標處或之後的第一筆。 / Find-as-you-type for the Find dialog: results are cached per edit revision and the first match at or after the caret is selected.
+    fn find_as_you_type(&mut self) {
+        let options = match self.build_search_options(SearchDirection::Forward) {
+            Ok(options) => options,
+            Err(_) => {
+                self.find_incremental.reset();
+                self.find_status = None;
+                return;
+            }
+        };
+        let anchor_char = self
+            .editor_selection_char_range()
+            .map_or_else(|| self.current_caret_char_index(), |(start, _)| start);
+        let anchor = Self::char_index_to_byte(&self.editor_preview, anchor_char);
+        let result = self
+            .find_incremental
+            .update(&self.editor_preview, self.editor_revision, &options)
+            .map(|hits| {
+                let target = hits.iter().find(|hit| hit.start >= anchor).or(hits.first());
+                (hits.len(), target.cloned())
+            });
+        match result {
+            Ok((count, Some(hit))) => {
+                self.jump_to_search_match(&hit);
+                self.show_find_status_message(
+                    format!("{count} matches"),
+                    format!("共 {count} 筆結果"),
+                    false,
+                );
+            }
+            Ok((_, None)) => {
+                self.show_find_status_message(
+                    "Search string not found",
+                    "找不到符合的字串",
+                    true,
+                );
+            }
+            Err(err) => {
+                let (en, zh) = self.describe_search_error(err);
+                self.show_find_status_message(en, zh, true);
+            }
+        }
+    }
+
     fn build_search_options(
         &self,
         direction: SearchDirection,
@@ -3420,26 +3712,12 @@ impl RustNotePadApp {
                 "請輸入要搜尋的內容".to_string(),
             ));
         }
-        let mut pattern = self.find_query.clone();
-        match self.find_mode {
-            FindMode::Normal => {}
-            FindMode::Extended => {
-                pattern = self
-                    .translate_extended_pattern(&pattern)
-                    .map_err(|err| {
-                        (
-                            format!("Extended mode error: {err}"),
-                            format!("延伸模式錯誤：{err}"),
-                        )
-                    })?;
-            }
-            FindMode::Regex => {}
-        }
-        let mut options = SearchOptions::new(pattern);
-        options.mode = if self.find_mode == FindMode::Regex {
-            SearchMode::Regex
-        } else {
-            SearchMode::Plain
+        let mut options = SearchOptions::new(self.find_query.clone());
+        options.mode = match self.find_mode {
+            FindMode::Normal => SearchMode::Plain,
+            FindMode::Extended => SearchMode::Extended,
+            FindMode::Regex => SearchMode::Regex,
+            FindMode::Fuzzy => SearchMode::Fuzzy,
         };
         options.case_sensitive = self.find_match_case;
         options.whole_word = self.find_whole_word;
@@ -3449,42 +3727,178 @@ impl RustNotePadApp {
         Ok(options)
     }
 
-    fn translate_extended_pattern(&self, pattern: &str) -> Result<String, String> {
-        let mut chars = pattern.chars().peekable();
-        let mut result = String::with_capacity(pattern.len());
-        while let Some(ch) = chars.next() {
-            if ch != '\\' {
-                result.push(ch);
-                continue;
+    /// 目前尋找對話框的內容，供搜尋紀錄與預設使用。 / Captures the Find dialog fields for history and presets.
+    fn current_saved_search(&self) -> SavedSearch {
+        let masks = FileFilter::default().with_masks(&self.find_in_files_filters);
+        SavedSearch {
+            pattern: self.find_query.clone(),
+            replacement: (!self.find_replace_text.is_empty())
+                .then(|| self.find_replace_text.clone()),
+            mode: match self.find_mode {
+                FindMode::Normal => SavedSearchMode::Plain,
+                FindMode::Extended => SavedSearchMode::Extended,
+                FindMode::Regex => SavedSearchMode::Regex,
+                FindMode::Fuzzy => SavedSearchMode::Fuzzy,
+            },
+            case_sensitive: self.find_match_case,
+            whole_word: self.find_whole_word,
+            dot_matches_newline: self.find_regex_dot_matches_newline,
+            include: masks.include,
+            exclude: masks.exclude,
+        }
+    }
+
+    /// 將已儲存的搜尋填回尋找對話框。 / Fills the Find dialog from a saved search.
+    fn apply_saved_search(&mut self, search: &SavedSearch) {
+        self.find_query = search.pattern.clone();
+        self.find_replace_text = search.replacement.clone().unwrap_or_default();
+        self.find_mode = match search.mode {
+            SavedSearchMode::Plain => FindMode::Normal,
+            SavedSearchMode::Extended => FindMode::Extended,
+            SavedSearchMode::Regex => FindMode::Regex,
+            SavedSearchMode::Fuzzy => FindMode::Fuzzy,
+        };
+        self.find_match_case = search.case_sensitive;
+        self.find_whole_word = search.whole_word;
+        self.find_regex_dot_matches_newline = search.dot_matches_newline;
+        self.find_in_files_filters = search
+            .include
+            .iter()
+            .cloned()
+            .chain(search.exclude.iter().map(|mask| format!("!{mask}")))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    fn record_search_history(&mut self) {
+        let search = self.current_saved_search();
+        // 重複執行同一搜尋（如 F3）時不必重寫檔案。 / Repeating the latest search (e.g. F3) leaves the file untouched.
+        if self.search_history.recent.first() == Some(&search) {
+            return;
+        }
+        self.search_history.record(search);
+        self.persist_search_history();
+    }
+
+    fn persist_search_history(&self) {
+        if let Err(err) = self.search_history_store.save(&self.search_history) {
+            log_warn(format!("Failed to persist search history: {err}"));
+        }
+    }
+
+    fn save_search_preset(&mut self) {
+        let name = self.find_preset_name.trim().to_string();
+        if name.is_empty() {
+            self.show_find_status_message(
+                "Enter a name for the preset",
+                "請輸入預設名稱",
+                true,
+            );
+            return;
+        }
+        if self.find_query.trim().is_empty() {
+            self.show_find_status_message(
+                "Enter a search query before saving a preset",
+                "請先輸入搜尋內容再儲存預設",
+                true,
+            );
+            return;
+        }
+        let search = self.current_saved_search();
+        self.search_history.save_preset(name.clone(), search);
+        self.persist_search_history();
+        self.show_find_status_message(
+            format!("Saved preset \"{name}\""),
+            format!("已儲存預設「{name}」"),
+            false,
+        );
+    }
+
+    fn load_search_preset(&mut self, name: &str) {
+        if let Some(search) = self.search_history.preset(name).cloned() {
+            self.apply_saved_search(&search);
+            self.find_preset_name = name.to_string();
+        }
+    }
+
+    fn delete_search_preset(&mut self) {
+        let name = self.find_preset_name.trim().to_string();
+        if self.search_history.remove_preset(&name) {
+            self.persist_search_history();
+            self.find_preset_name.clear();
+            self.show_find_status_message(
+                format!("Deleted preset \"{name}\""),
+                format!("已刪除預設「{name}」"),
+                false,
+            );
+        } else {
+            self.show_find_status_message(
+                format!("No preset named \"{name}\""),
+                format!("找不到預設「{name}」"),
+                true,
+            );
+        }
+    }
+
+    fn render_search_history_row(&mut self, ui: &mut egui::Ui) {
+        let label_history =
+            self.localized_text("dialog.find.history", "History:", "搜尋紀錄：");
+        let label_presets =
+            self.localized_text("dialog.find.presets", "Presets:", "搜尋預設：");
+        let hint_preset =
+            self.localized_text("dialog.find.preset_hint", "Preset name", "預設名稱");
+        let label_save =
+            self.localized_text("dialog.find.button.save_preset", "Save Preset", "儲存預設");
+        let label_delete = self.localized_text(
+            "dialog.find.button.delete_preset",
+            "Delete Preset",
+            "刪除預設",
+        );
+        let mut chosen_recent = None;
+        let mut chosen_preset = None;
+        ui.horizontal(|ui| {
+            ui.label(label_history);
+            egui::ComboBox::from_id_source("find_history_selector")
+                .width(160.0)
+                .selected_text("")
+                .show_ui(ui, |ui| {
+                    for (idx, search) in self.search_history.recent.iter().enumerate() {
+                        if ui.selectable_label(false, &search.pattern).clicked() {
+                            chosen_recent = Some(idx);
+                        }
+                    }
+                });
+            ui.label(label_presets);
+            egui::ComboBox::from_id_source("find_preset_selector")
+                .width(120.0)
+                .selected_text(self.find_preset_name.clone())
+                .show_ui(ui, |ui| {
+                    for preset in &self.search_history.presets {
+                        let selected = preset.name == self.find_preset_name;
+                        if ui.selectable_label(selected, &preset.name).clicked() {
+                            chosen_preset = Some(preset.name.clone());
+                        }
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.find_preset_name)
+                    .desired_width(100.0)
+                    .hint_text(hint_preset),
+            );
+            if ui.button(label_save).clicked() {
+                self.save_search_preset();
             }
-            let Some(next) = chars.next() else {
-                result.push('\\');
-                break;
-            };
-            match next {
-                'n' => result.push('\n'),
-                'r' => result.push('\r'),
-                't' => result.push('\t'),
-                '0' => result.push('\0'),
-                '\\' => result.push('\\'),
-                'x' => {
-                    let hi = chars.next();
-                    let lo = chars.next();
-                    let Some(hi) = hi else {
-                        return Err("Incomplete hex escape".to_string());
-                    };
-                    let Some(lo) = lo else {
-                        return Err("Incomplete hex escape".to_string());
-                    };
-                    let value = format!("{hi}{lo}");
-                    let byte = u8::from_str_radix(&value, 16)
-                        .map_err(|_| "Invalid hex escape".to_string())?;
-                    result.push(byte as char);
-                }
-                other => result.push(other),
+            if ui.button(label_delete).clicked() {
+                self.delete_search_preset();
             }
+        });
+        if let Some(search) = chosen_recent.and_then(|idx| self.search_history.recent.get(idx)) {
+            let search = search.clone();
+            self.apply_saved_search(&search);
+        }
+        if let Some(name) = chosen_preset {
+            self.load_search_preset(&name);
         }
-        Ok(result)
     }
 
     fn describe_search_error(&self, err: SearchError) -> (String, String) {
@@ -3497,6 +3911,10 @@ impl RustNotePadApp {
                 format!("Invalid pattern: {reason}"),
                 format!("無效的樣式：{reason}"),
             ),
+            SearchError::InvalidReplacement(reason) => (
+                format!("Invalid replacement: {reason}"),
+                format!("無效的取代字串：{reason}"),
+            ),
         }
     }
 
@@ -3535,6 +3953,25 @@ impl RustNotePadApp {
         self.update_editor_selection(Some(range));
     }
 
+    /// 跳至結果，必要時先開啟其檔案。 / Jumps to a result, opening its file first when needed.
+    fn jump_to_search_result(&mut self, path: Option<&Path>, hit: &SearchMatch) {
+        if let Some(path) = path {
+            if self.current_document_path.as_deref() != Some(path) && path.is_file() {
+                // 載入文件會清除結果，這裡保留參考清單。 / Loading clears results; keep the list.
+                let report = self.search_report.take();
+                let document = path.to_string_lossy().into_owned();
+                let display = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(document.as_str())
+                    .to_string();
+                self.open_document(&document, &display);
+                self.search_report = report;
+            }
+        }
+        self.jump_to_search_match(hit);
+    }
+
     fn perform_count_matches(&mut self) {
         let mut options = match self.build_search_options(SearchDirection::Forward) {
             Ok(opts) => opts,
@@ -3543,6 +3980,7 @@ impl RustNotePadApp {
                 return;
             }
         };
+        self.record_search_history();
         options.direction = SearchDirection::Forward;
         let engine = SearchEngine::new(&self.editor_preview);
         match engine.find_all(&options) {
@@ -3569,6 +4007,7 @@ impl RustNotePadApp {
                 return;
             }
         };
+        self.record_search_history();
         options.direction = SearchDirection::Forward;
         let engine = SearchEngine::new(&self.editor_preview);
         match engine.find_all(&options) {
@@ -3609,6 +4048,167 @@ impl RustNotePadApp {
         }
     }
 
+    /// 在背景開始「在檔案中尋找」，結果逐步加入搜尋結果面板。 / Starts a background Find in Files run whose hits stream into the results panel.
+    fn start_find_in_files(&mut self) {
+        let mut options = match self.build_search_options(SearchDirection::Forward) {
+            Ok(opts) => opts,
+            Err((en, zh)) => {
+                self.show_find_status_message(en, zh, true);
+                return;
+            }
+        };
+        options.wrap_around = false;
+        if let Err(err) = options.validate() {
+            let (en, zh) = self.describe_search_error(err);
+            self.show_find_status_message(en, zh, true);
+            return;
+        }
+        self.record_search_history();
+        if self.find_in_files_directory.trim().is_empty() {
+            let fallback = self
+                .current_document_path
+                .as_deref()
+                .and_then(Path::parent)
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok());
+            if let Some(dir) = fallback {
+                self.find_in_files_directory = dir.to_string_lossy().into_owned();
+            }
+        }
+        let directory = PathBuf::from(self.find_in_files_directory.trim());
+        if !directory.is_dir() {
+            let shown = directory.display().to_string();
+            self.show_find_status_message(
+                format!("Directory not found: {shown}"),
+                format!("找不到資料夾：{shown}"),
+                true,
+            );
+            return;
+        }
+        if let Some(task) = self.find_in_files_task.take() {
+            task.cancel();
+        }
+        let filter = FileFilter {
+            include_hidden: self.find_in_files_hidden,
+            respect_ignore_files: self.find_in_files_respect_ignore,
+            ..FileFilter::default()
+        }
+        .with_masks(&self.find_in_files_filters);
+        // 索引以絕對路徑為鍵。 / The index is keyed by absolute paths.
+        let directory = std::path::absolute(&directory).unwrap_or(directory);
+        let mut finder = FindInFiles::new([directory.clone()])
+            .with_filter(filter)
+            .with_large_files(self.large_file_options());
+        if let Some(index) = self.find_in_files_index(&directory) {
+            finder = finder.with_index(index);
+        }
+        let task = finder.spawn(options);
+        self.find_in_files_task = Some(task);
+        self.search_report = Some(SearchReport::default());
+        self.show_find_results_panel();
+        self.show_find_status_message("Searching files...", "正在搜尋檔案…", false);
+    }
+
+    /// 取得工作區索引快照；索引仍在建立或資料夾不在工作區內時回傳 `None`。 / Returns the workspace index snapshot, or `None` while it is still being built or when the directory lies outside the workspace.
+    fn find_in_files_index(&mut self, directory: &Path) -> Option<Arc<TrigramIndex>> {
+        if !self.find_in_files_use_index {
+            return None;
+        }
+        let root = std::path::absolute(&self.workspace_root).ok()?;
+        if !directory.starts_with(&root) {
+            return None;
+        }
+        if self
+            .workspace_search_index
+            .as_ref()
+            .is_some_and(|watched| watched.roots() != [root.clone()])
+        {
+            self.workspace_search_index = None;
+        }
+        if self.workspace_search_index.is_none() {
+            let watched = WorkspaceIndex::open(
+                root.join(".rustnotepad").join("search.index"),
+                vec![root],
+                FileFilter::default(),
+            )
+            .and_then(WorkspaceIndex::watch);
+            match watched {
+                Ok(watched) => self.workspace_search_index = Some(watched),
+                Err(err) => {
+                    log_warn(format!("Search index: {err}"));
+                    return None;
+                }
+            }
+        }
+        let watched = self.workspace_search_index.as_ref()?;
+        if let Some(err) = watched.take_error() {
+            log_warn(format!("Search index: {err}"));
+        }
+        watched.index()
+    }
+
+    /// 收取背景搜尋的事件；仍在執行時回傳 `true`。 / Collects streamed events; returns `true` while the run is still going.
+    fn poll_find_in_files(&mut self) -> bool {
+        let Some(task) = self.find_in_files_task.as_mut() else {
+            return false;
+        };
+        let mut events = task.drain();
+        let finished = task.try_finish();
+        if finished.is_some() {
+            events.extend(task.drain());
+        }
+        let cancelled = task.is_cancelled();
+        let report = self.search_report.get_or_insert_with(SearchReport::default);
+        for event in events {
+            match event {
+                FindEvent::Matched(result) => {
+                    report.total_matches += result.matches.len();
+                    report.results.push(result);
+                }
+                FindEvent::Failed { path, message } => match path {
+                    Some(path) => log_warn(format!("Find in Files: {}: {message}", path.display())),
+                    None => log_warn(format!("Find in Files: {message}")),
+                },
+                FindEvent::Skipped { .. } => {}
+            }
+        }
+        let Some(outcome) = finished else {
+            return true;
+        };
+        self.find_in_files_task = None;
+        if let Some(report) = self.search_report.as_mut() {
+            report.results.sort_by(|left, right| left.path.cmp(&right.path));
+        }
+        match outcome {
+            Ok(stats) => {
+                let (en, zh) = if cancelled {
+                    ("Find in Files stopped".to_string(), "已停止在檔案中尋找".to_string())
+                } else {
+                    (
+                        format!(
+                            "Find in Files: {} matches in {} of {} files",
+                            stats.total_matches, stats.files_matched, stats.files_searched
+                        ),
+                        format!(
+                            "在檔案中尋找：{} 筆（{}/{} 個檔案）",
+                            stats.total_matches, stats.files_matched, stats.files_searched
+                        ),
+                    )
+                };
+                self.show_find_status_message(en, zh, stats.total_matches == 0);
+            }
+            Err(err) => {
+                let message = err.to_string();
+                self.show_find_status_message(
+                    format!("Find in Files failed: {message}"),
+                    format!("在檔案中尋找失敗：{message}"),
+                    true,
+                );
+            }
+        }
+        false
+    }
+
     fn show_find_results_panel(&mut self) {
         self.bottom_panels_visible = true;
         if let Some(idx) = self
@@ -3623,6 +4223,143 @@ impl RustNotePadApp {
         self.layout.bottom_dock.active_panel = Some("find_results".into());
     }
 
+    /// 以樣式標記目前搜尋的所有結果；搜尋字串為空時改為標記選取範圍。 / Marks every match of the current query with a style, or the selection when the query is empty.
+    fn mark_with_style(&mut self, style: MarkStyle) {
+        self.active_mark_style = style;
+        let added = if self.find_query.is_empty() {
+            match self.editor_selection_byte_range() {
+                Some((start, end)) => usize::from(self.mark_styles.mark(style, start..end)),
+                None => 0,
+            }
+        } else {
+            let mut options = match self.build_search_options(SearchDirection::Forward) {
+                Ok(opts) => opts,
+                Err((en, zh)) => {
+                    self.show_find_status_message(en, zh, true);
+                    return;
+                }
+            };
+            options.direction = SearchDirection::Forward;
+            match SearchEngine::new(&self.editor_preview).find_all(&options) {
+                Ok(matches) => self.mark_styles.mark_matches(style, &matches),
+                Err(err) => {
+                    let (en, zh) = self.describe_search_error(err);
+                    self.show_find_status_message(en, zh, true);
+                    return;
+                }
+            }
+        };
+        let number = style.index() + 1;
+        self.push_localized_notification(
+            format!("Marked {added} ranges with style {number}."),
+            format!("已以第 {number} 種樣式標記 {added} 處。"),
+        );
+    }
+
+    /// 跳至作用中樣式的下一個或上一個標記，到底時從另一端繼續。 / Jumps to the next or previous mark of the active style, wrapping at either end.
+    fn jump_to_mark(&mut self, direction: SearchDirection) {
+        let style = self.active_mark_style;
+        let (from, _) = self.editor_selection_byte_range().unwrap_or_else(|| {
+            let caret =
+                Self::char_index_to_byte(&self.editor_preview, self.current_caret_char_index());
+            (caret, caret)
+        });
+        let ranges = self.mark_styles.ranges(style);
+        let target = match direction {
+            SearchDirection::Forward => self
+                .mark_styles
+                .next_after(style, from)
+                .or_else(|| ranges.first().cloned()),
+            SearchDirection::Backward => self
+                .mark_styles
+                .previous_before(style, from)
+                .or_else(|| ranges.last().cloned()),
+        };
+        let Some(target) = target else {
+            let number = style.index() + 1;
+            self.push_localized_notification(
+                format!("No marks with style {number}."),
+                format!("沒有第 {number} 種樣式的標記。"),
+            );
+            return;
+        };
+        let text = &self.editor_preview;
+        let start_char = Self::char_index_from_byte(text, target.start);
+        let end_char = Self::char_index_from_byte(text, target.end);
+        let range = CCursorRange::two(CCursor::new(start_char), CCursor::new(end_char));
+        self.pending_editor_selection = Some(range);
+        self.update_editor_selection(Some(range));
+    }
+
+    fn copy_marked_text(&mut self) {
+        let buffer = EditorBuffer::new(self.editor_preview.clone());
+        let text =
+            line_ops::copy_marked_text(&buffer, self.mark_styles.ranges(self.active_mark_style));
+        if !text.is_empty() {
+            self.editor_clipboard = text;
+        }
+    }
+
+    fn delete_marked_lines(&mut self) {
+        let style = self.active_mark_style;
+        let marked = self.mark_styles.ranges(style).to_vec();
+        let mut buffer = EditorBuffer::new(self.editor_preview.clone());
+        let spans = line_ops::marked_line_spans(buffer.text(), &marked);
+        let removed = match line_ops::delete_marked_lines(&mut buffer, &marked) {
+            Ok(removed) => removed,
+            Err(err) => {
+                log_warn(format!("Failed to delete marked lines: {err}"));
+                return;
+            }
+        };
+        if removed == 0 {
+            return;
+        }
+        // 逐段調整其他樣式的標記，比整段文字比對更精確。 / Shift the other styles span by span, which is more precise than diffing the whole text.
+        let mut marks = self.mark_styles.clone();
+        for span in spans.iter().rev() {
+            marks.apply_edit(span.start, span.len(), 0);
+        }
+        marks.clear(style);
+        let previous_text = self.editor_preview.clone();
+        self.record_undo_snapshot(previous_text);
+        self.editor_redo_stack.clear();
+        self.apply_editor_text(buffer.contents().to_string());
+        self.mark_styles = marks;
+        self.push_localized_notification(
+            format!("Deleted {removed} marked lines."),
+            format!("已刪除 {removed} 行標記行。"),
+        );
+    }
+
+    fn session_marks(&self) -> Vec<SessionMark> {
+        self.mark_styles
+            .iter()
+            .map(|(style, range)| SessionMark {
+                style: style.index() as u8,
+                start: range.start,
+                end: range.end,
+            })
+            .collect()
+    }
+
+    fn restore_session_marks(&mut self, marks: &[SessionMark]) {
+        self.mark_styles.clear_all();
+        let text = &self.editor_preview;
+        for mark in marks {
+            let Some(style) = MarkStyle::from_index(usize::from(mark.style)) else {
+                continue;
+            };
+            // 檔案在外部變更後，失效的範圍直接略過。 / Ranges made invalid by an outside change are dropped.
+            if mark.end <= text.len()
+                && text.is_char_boundary(mark.start)
+                && text.is_char_boundary(mark.end)
+            {
+                self.mark_styles.mark(style, mark.start..mark.end);
+            }
+        }
+    }
+
     fn clear_search_results(&mut self) {
         self.search_report = None;
         self.find_status = None;
@@ -3921,13 +4658,13 @@ impl RustNotePadApp {
 
     fn auto_detect_language(&mut self) {
         let candidate = if let Some(path) = &self.current_document_path {
-            language_id_from_path(path.to_string_lossy().as_ref())
+            self.language_id_for_path(path.to_string_lossy().as_ref())
         } else {
-            language_id_from_path(&self.current_document_id)
+            self.language_id_for_path(&self.current_document_id)
         };
         let current_id = self.current_document_id.clone();
-        self.set_tab_language_override(&current_id, candidate);
-        let language_name = self.language_display_name(candidate);
+        self.set_tab_language_override(&current_id, &candidate);
+        let language_name = self.language_display_name(&candidate);
         self.push_localized_notification(
             format!("Detected document language: {language_name}"),
             format!("已自動偵測文件語言：{language_name}"),
@@ -4188,21 +4925,195 @@ impl RustNotePadApp {
         self.pending_editor_selection = Some(range);
     }
 
-    fn handle_macro_command(&mut self, item_key: &str) {
-        match item_key {
-            "menu.macro.start_recording" => self.start_macro_recording(),
-            "menu.macro.stop_recording" => self.stop_macro_recording(),
-            "menu.macro.playback" => self.play_selected_macro(),
-            _ => {
-                self.push_macro_log_localized_owned(
-                    format!("Unsupported macro command {item_key}"),
-                    format!("未支援的巨集指令 {item_key}"),
-                );
-            }
-        }
-    }
-
-    fn handle_run_command(&mut self, item_key: &str) {
+    /// 目前所有游標，主游標在最後。 / All carets, the primary one last.
+    fn editor_carets(&self) -> Vec<Caret> {
+        let text = &self.editor_text;
+        let (anchor, position) = match self.editor_selection {
+            Some(range) => (range.secondary.index, range.primary.index),
+            None => (text.len_chars(), text.len_chars()),
+        };
+        let anchor = text.offset_of_char(anchor);
+        let position = text.offset_of_char(position);
+        let primary = if anchor == position {
+            Caret::new(position)
+        } else {
+            Caret::with_selection(position, Selection::new(anchor, position))
+        };
+        let primary = match self.editor_goal_column {
+            Some((at, goal)) if at == position => primary.with_goal_column(goal),
+            _ => primary,
+        };
+        let mut carets = self.editor_extra_carets.clone();
+        carets.push(primary);
+        carets
+    }
+
+    /// 採用指令後的游標：最後一個交給文字框，其餘畫在編輯區上。 / Adopts the carets left by a command: the last goes to the text box, the rest are painted over the editor.
+    fn set_editor_carets(&mut self, mut carets: Vec<Caret>) {
+        let Some(primary) = carets.pop() else {
+            self.editor_extra_carets.clear();
+            return;
+        };
+        let text = &self.editor_text;
+        let range = CCursorRange::two(
+            CCursor::new(text.char_of_offset(primary.anchor())),
+            CCursor::new(text.char_of_offset(primary.position())),
+        );
+        self.editor_goal_column = primary
+            .goal_column()
+            .map(|goal| (primary.position(), goal));
+        self.editor_extra_carets = carets;
+        self.pending_editor_selection = Some(range);
+        self.update_editor_selection(Some(range));
+    }
+
+    /// 對所有游標執行多重游標指令；文字改變時記錄復原並套用。 / Runs a multi-caret command over every caret, recording undo and applying the text when it changed.
+    fn run_multi_caret<T>(
+        &mut self,
+        command: impl FnOnce(&mut EditorBuffer) -> Result<T, EditorError>,
+    ) {
+        let carets = self.editor_carets();
+        let mut buffer = match EditorBuffer::with_carets(self.editor_preview.clone(), carets) {
+            Ok(buffer) => buffer,
+            Err(_) => {
+                // 額外游標已失效，只保留主游標。 / The extra carets went stale; keep only the primary one.
+                self.editor_extra_carets.clear();
+                match EditorBuffer::with_carets(self.editor_preview.clone(), self.editor_carets()) {
+                    Ok(buffer) => buffer,
+                    Err(_) => return,
+                }
+            }
+        };
+        if let Err(err) = command(&mut buffer) {
+            log_warn(self.localized_owned(
+                format!("Multi-caret command failed: {err}"),
+                format!("多重游標指令失敗：{err}"),
+            ));
+            return;
+        }
+        let carets = buffer.carets().to_vec();
+        if buffer.contents() != self.editor_preview {
+            self.record_undo_snapshot(self.editor_preview.clone());
+            self.editor_redo_stack.clear();
+            self.apply_editor_text(buffer.contents().to_string());
+        }
+        self.set_editor_carets(carets);
+    }
+
+    /// 編輯區的多重游標快捷鍵與方向鍵；有額外游標時，輸入與刪除會套用到每個游標。 / Multi-caret shortcuts and arrow keys for the editor; with extra carets, typing and deletion apply at every caret.
+    fn handle_caret_input(&mut self, ui: &egui::Ui) {
+        if !self.editor_has_focus || self.large_file.is_some() {
+            return;
+        }
+        let command_alt = egui::Modifiers::COMMAND | egui::Modifiers::ALT;
+        let command_shift = egui::Modifiers::COMMAND | egui::Modifiers::SHIFT;
+        let shortcuts = [
+            (command_alt, egui::Key::ArrowUp, "menu.edit.add_caret_above"),
+            (command_alt, egui::Key::ArrowDown, "menu.edit.add_caret_below"),
+            (command_shift, egui::Key::L, "menu.edit.split_selection_into_lines"),
+            (egui::Modifiers::ALT, egui::Key::F3, "menu.edit.select_all_occurrences"),
+            (egui::Modifiers::COMMAND, egui::Key::D, "menu.edit.add_next_occurrence"),
+        ];
+        let commands: Vec<&str> = ui.input_mut(|input| {
+            shortcuts
+                .iter()
+                .filter(|(modifiers, key, _)| input.consume_key(*modifiers, *key))
+                .map(|(_, _, command)| *command)
+                .collect()
+        });
+        for command in commands {
+            self.handle_edit_command(command);
+        }
+        // 方向鍵一律走核心的移動規則；有額外游標時，連同編輯事件一起取走，改由所有游標套用。 /
+        // Arrow keys always go through the core motions; with extra carets the editing events are
+        // taken from the text box as well and applied at every caret.
+        let multi_caret = !self.editor_extra_carets.is_empty();
+        let events: Vec<egui::Event> = ui.input_mut(|input| {
+            let mut taken = Vec::new();
+            input.events.retain(|event| {
+                let take = match event {
+                    egui::Event::Text(_) | egui::Event::Paste(_) => multi_caret,
+                    egui::Event::Key {
+                        key,
+                        pressed,
+                        modifiers,
+                        ..
+                    } => match multi_caret_key(*key, *modifiers) {
+                        Some(MultiCaretKey::Move(_)) => *pressed && !modifiers.alt,
+                        Some(_) => *pressed && multi_caret,
+                        None => false,
+                    },
+                    _ => false,
+                };
+                if take {
+                    taken.push(event.clone());
+                }
+                !take
+            });
+            taken
+        });
+        for event in events {
+            match event {
+                egui::Event::Text(text) | egui::Event::Paste(text) => {
+                    self.run_multi_caret(|buffer| buffer.insert_text(&text));
+                }
+                egui::Event::Key { key, modifiers, .. } => match multi_caret_key(key, modifiers) {
+                    Some(MultiCaretKey::Backspace) => self.run_multi_caret(EditorBuffer::delete_backward),
+                    Some(MultiCaretKey::Delete) => self.run_multi_caret(EditorBuffer::delete_forward),
+                    Some(MultiCaretKey::Newline) => self.run_multi_caret(EditorBuffer::insert_newline),
+                    Some(MultiCaretKey::Collapse) => self.editor_extra_carets.clear(),
+                    Some(MultiCaretKey::Move(motion)) => {
+                        self.move_editor_caret(motion, modifiers.shift)
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// 額外游標的（固定端, 位置）字元索引，供繪製使用。 / Character (anchor, position) indices of the extra carets, for painting.
+    fn extra_caret_char_ranges(&self) -> Vec<(usize, usize)> {
+        let text = &self.editor_text;
+        self.editor_extra_carets
+            .iter()
+            .map(|caret| {
+                (
+                    text.char_of_offset(caret.anchor()),
+                    text.char_of_offset(caret.position()),
+                )
+            })
+            .collect()
+    }
+
+    /// 以核心的移動規則移動游標；有額外游標時一併移動。 / Moves the caret with the core motion rules, together with any extra carets.
+    fn move_editor_caret(&mut self, motion: Motion, extend: bool) {
+        if !self.editor_extra_carets.is_empty() {
+            self.run_multi_caret(|buffer| motion::move_carets(buffer, motion, extend));
+            return;
+        }
+        let Some(caret) = self.editor_carets().pop() else {
+            return;
+        };
+        let moved = motion::move_caret(&self.editor_text, &caret, motion, extend);
+        self.set_editor_carets(vec![moved]);
+    }
+
+    fn handle_macro_command(&mut self, item_key: &str) {
+        match item_key {
+            "menu.macro.start_recording" => self.start_macro_recording(),
+            "menu.macro.stop_recording" => self.stop_macro_recording(),
+            "menu.macro.playback" => self.play_selected_macro(),
+            _ => {
+                self.push_macro_log_localized_owned(
+                    format!("Unsupported macro command {item_key}"),
+                    format!("未支援的巨集指令 {item_key}"),
+                );
+            }
+        }
+    }
+
+    fn handle_run_command(&mut self, item_key: &str) {
         if let Some((title, spec)) = self.build_run_spec(item_key) {
             self.execute_run_spec(title, spec);
         } else {
@@ -4234,9 +5145,14 @@ impl RustNotePadApp {
 
         self.current_document_id = tab_id.clone();
         self.current_document_path = None;
+        self.document_format = DocumentFormat::default();
+        self.large_file = None;
+        self.status.eol_warning = None;
         self.current_language_id = "plaintext".into();
         self.editor_preview.clear();
+        self.editor_text_replaced();
         self.clear_search_results();
+        self.mark_styles.clear_all();
         self.autocomplete_panel_used = false;
         self.editor_undo_stack.clear();
         self.editor_redo_stack.clear();
@@ -4246,7 +5162,7 @@ impl RustNotePadApp {
         self.set_tab_dirty_state(&tab_id, false);
         self.document_index
             .update_document(&self.current_document_id, &self.editor_preview);
-        self.status.refresh_cursor(&self.editor_preview);
+        self.status.refresh_cursor(&self.editor_text);
         self.status.set_document_language(language_name);
         self.refresh_completions();
         self.status.refresh_from_layout(&self.layout);
@@ -4536,27 +5452,45 @@ impl RustNotePadApp {
         if let Some(parent) = target.parent() {
             fs::create_dir_all(parent).map_err(|err| err.to_string())?;
         }
-        fs::write(target, &self.editor_preview).map_err(|err| err.to_string())?;
+        if let Some(hex) = self.hex_view.as_mut() {
+            hex.document
+                .save_as(target)
+                .map_err(|err| err.to_string())?;
+        } else if let Some(large) = self.large_file.as_ref() {
+            // 巨檔為唯讀，另存時直接複製原始檔。 / Large files are read-only, so "save as" copies the original bytes.
+            if large.path() != target {
+                fs::copy(large.path(), target).map_err(|err| err.to_string())?;
+            }
+        } else {
+            let mut document = Document::new();
+            document.set_contents(self.editor_preview.as_str());
+            document.set_encoding(self.document_format.encoding);
+            document.set_line_ending(self.document_format.line_ending);
+            document.set_bom(self.document_format.has_bom);
+            document.save_as(target).map_err(|err| err.to_string())?;
+        }
 
         let old_id = self.current_document_id.clone();
         let new_id = target.to_string_lossy().into_owned();
-        let language_id = language_id_from_path(&new_id);
+        let language_id = self.language_id_for_path(&new_id);
 
         if old_id != new_id {
             self.document_index.remove_document(&old_id);
+            self.close_lsp_document(&old_id);
         }
 
         self.current_document_id = new_id.clone();
         self.current_document_path = Some(target.to_path_buf());
         self.current_language_id = language_id.to_string();
+        self.open_lsp_document();
         self.document_dirty = false;
         self.document_index
             .update_document(&self.current_document_id, &self.editor_preview);
-        self.update_tab_identity(&old_id, &new_id, target, language_id);
+        self.update_tab_identity(&old_id, &new_id, target, &language_id);
         self.set_tab_dirty_state(&new_id, false);
         self.status
-            .set_document_language(self.language_display_name(language_id));
-        self.status.refresh_cursor(&self.editor_preview);
+            .set_document_language(self.language_display_name(&language_id));
+        self.status.refresh_cursor(&self.editor_text);
         self.refresh_completions();
         Ok(())
     }
@@ -4636,16 +5570,82 @@ impl RustNotePadApp {
         if !new_text.trim().is_empty() {
             self.autocomplete_panel_used = true;
         }
+        let (start, removed, inserted) = changed_span(&self.editor_preview, &new_text);
+        self.editor_text
+            .replace(start..start + removed, &new_text[start..start + inserted]);
+        self.mark_styles.apply_edit(start, removed, inserted);
+        // 只讓受影響的行失效，其餘快取留待重新驗證。 / Only the touched lines are invalidated; the rest of the cache is re-verified lazily.
+        if let Some(highlighter) = &mut self.editor_highlighter {
+            highlighter.apply_edit(&new_text, start, removed, inserted);
+        }
         self.editor_preview = new_text;
+        self.editor_revision = self.editor_revision.wrapping_add(1);
+        self.editor_extra_carets.clear();
         self.clear_search_results();
         self.update_editor_selection(None);
-        self.status.refresh_cursor(&self.editor_preview);
+        self.status.refresh_cursor(&self.editor_text);
         self.document_index
             .update_document(&self.current_document_id, &self.editor_preview);
+        self.sync_lsp_document();
         self.refresh_completions();
         self.mark_document_dirty();
     }
 
+    /// 編輯區文字被整份取代：重建 rope 鏡像、捨棄高亮器並換新編輯版本號。 / The editor text was replaced wholesale: rebuilds the rope mirror, drops the highlighter and takes a new edit revision.
+    fn editor_text_replaced(&mut self) {
+        self.editor_text = TextBuffer::from(self.editor_preview.as_str());
+        self.editor_highlighter = None;
+        self.editor_extra_carets.clear();
+        self.editor_revision = self.editor_revision.wrapping_add(1);
+    }
+
+    /// 以增量高亮器取得指定行的 token；語言改變或尚未建立時先重建。 / Tokens for the given lines from the incremental highlighter, rebuilt first when missing or the language changed.
+    fn editor_highlight_tokens(
+        &mut self,
+        lines: std::ops::Range<usize>,
+    ) -> Result<Vec<HighlightToken>, HighlightError> {
+        if self
+            .editor_highlighter
+            .as_ref()
+            .is_none_or(|highlighter| highlighter.language().id.as_ref() != self.current_language_id)
+        {
+            self.editor_highlighter = None;
+            self.editor_highlighter = Some(
+                self.highlight_registry
+                    .incremental(&self.current_language_id, &self.editor_preview)?,
+            );
+        }
+        let highlighter = self
+            .editor_highlighter
+            .as_mut()
+            .expect("highlighter was just built");
+        Ok(highlighter.highlight_lines(&self.editor_preview, lines))
+    }
+
+    /// 可見行的語法著色，語言自帶樣式優先於主題。 / Syntax colours for the visible lines, preferring the language's own styles over the theme.
+    fn editor_syntax_colors(&mut self, lines: std::ops::Range<usize>) -> Vec<(std::ops::Range<usize>, Color32)> {
+        let Ok(tokens) = self.editor_highlight_tokens(lines) else {
+            return Vec::new();
+        };
+        let Some(highlighter) = &self.editor_highlighter else {
+            return Vec::new();
+        };
+        let palettes: Vec<&HighlightPalette> = highlighter
+            .language()
+            .palette()
+            .into_iter()
+            .chain(self.theme_manager.active_theme().syntax_palette())
+            .collect();
+        tokens
+            .into_iter()
+            .filter_map(|token| {
+                let style = palettes.iter().find_map(|palette| palette.style_for(&token.kind))?;
+                let color = style.foreground;
+                Some((token.range, Color32::from_rgba_unmultiplied(color.r, color.g, color.b, color.a)))
+            })
+            .collect()
+    }
+
     fn update_editor_selection(&mut self, selection: Option<CCursorRange>) {
         self.editor_selection = selection;
         self.status.selection = self
@@ -4654,7 +5654,7 @@ impl RustNotePadApp {
             .unwrap_or(0);
         let caret_index = self.current_caret_char_index();
         self.status
-            .set_caret_index(&self.editor_preview, caret_index);
+            .set_caret_index(&self.editor_text, caret_index);
     }
 
     fn editor_selection_char_range(&self) -> Option<(usize, usize)> {
@@ -5134,6 +6134,7 @@ impl RustNotePadApp {
                         line: clamp_to_u32(self.status.line.saturating_sub(1)),
                         column: clamp_to_u32(self.status.column.saturating_sub(1)),
                     };
+                    session_tab.marks = self.session_marks();
                     session_tab.unsaved_hash = Some(hash.clone());
                     active_tab_index = Some(idx);
                 } else {
@@ -5167,6 +6168,7 @@ impl RustNotePadApp {
                 top_line: 0,
                 horizontal_offset: 0,
             };
+            tab.marks = self.session_marks();
             tab.unsaved_hash = Some(hash);
             tab.dirty_external = false;
             tabs.push(tab);
@@ -5200,9 +6202,30 @@ impl RustNotePadApp {
     }
 
     fn language_display_name(&self, language_id: &str) -> String {
-        self.localization
-            .text(language_display_key(language_id))
-            .into_owned()
+        match (
+            language_display_key(language_id),
+            self.highlight_registry.get(language_id),
+        ) {
+            // 由語法檔載入的語言沒有翻譯字串，直接使用語法名稱。 / Languages loaded from grammar files have no translation; use the grammar's name.
+            ("language.name.plaintext", Some(language)) if language_id != "plaintext" => {
+                language.display_name.clone()
+            }
+            (key, _) => self.localization.text(key).into_owned(),
+        }
+    }
+
+    /// 內建對應找不到時，改依已註冊語言的副檔名判斷。 / Falls back to the extensions of registered languages when the built-in mapping has none.
+    fn language_id_for_path(&self, path: &str) -> String {
+        let builtin = language_id_from_path(path);
+        if builtin != "plaintext" {
+            return builtin.to_string();
+        }
+        Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.highlight_registry.find_by_extension(ext))
+            .map(|language| language.id.as_ref().to_string())
+            .unwrap_or_else(|| builtin.to_string())
     }
 
     fn apply_locale_change(&mut self, index: usize, summaries: &[LocaleSummary]) {
@@ -5244,10 +6267,11 @@ impl RustNotePadApp {
         self.sample_editor_content = self.localization.text("sample.editor_preview").into_owned();
         if self.current_document_id == PREVIEW_DOCUMENT_ID {
             self.editor_preview = self.sample_editor_content.clone();
+            self.editor_text_replaced();
             self.clear_search_results();
             self.document_index
                 .update_document(&self.current_document_id, &self.editor_preview);
-            self.status.refresh_cursor(&self.editor_preview);
+            self.status.refresh_cursor(&self.editor_text);
         }
         if self.font_warning.is_some() {
             self.font_warning = Some(self.text("fonts.warning.cjk_missing").into_owned());
@@ -5320,14 +6344,39 @@ impl RustNotePadApp {
 
     fn load_document(&mut self, path: &str, language_hint: Option<&str>) {
         let fallback_template = self.text("document.load_error").into_owned();
-        let contents =
-            fs::read_to_string(path).unwrap_or_else(|_| fallback_template.replace("{path}", path));
+        self.hex_view = None;
+        self.document_format = DocumentFormat::default();
+        self.large_file = None;
+        self.status.eol_warning = None;
+        let contents = match Document::open_with_options(path, &self.large_file_options()) {
+            Ok(OpenedDocument::Full(document)) => {
+                self.document_format = DocumentFormat::of(&document);
+                self.report_line_endings(path, &document.line_ending_stats());
+                document.contents().to_owned()
+            }
+            Ok(OpenedDocument::Large(document)) => self
+                .open_large_file(path, document)
+                .unwrap_or_else(|| fallback_template.replace("{path}", path)),
+            // 偵測不到任何可用編碼（含 NUL 位元組的二進位資料）時改以十六進位檢視開啟。 / Fall back to the hex view only when no encoding decodes the data, e.g. binary files with NUL bytes.
+            Err(DocumentError::InvalidEncoding) => match HexDocument::open(path) {
+                Ok(document) => {
+                    self.hex_view = Some(HexViewState::new(document));
+                    String::new()
+                }
+                Err(_) => fallback_template.replace("{path}", path),
+            },
+            Err(_) => fallback_template.replace("{path}", path),
+        };
+        self.status.set_encoding(self.document_format.status_label());
         let old_id = self.current_document_id.clone();
         if old_id != path {
             self.document_index.remove_document(&old_id);
+            self.close_lsp_document(&old_id);
         }
         self.editor_preview = contents;
+        self.editor_text_replaced();
         self.clear_search_results();
+        self.mark_styles.clear_all();
         self.editor_undo_stack.clear();
         self.editor_redo_stack.clear();
         self.pending_editor_selection = None;
@@ -5341,21 +6390,288 @@ impl RustNotePadApp {
         let language_id = language_hint
             .and_then(language_id_from_hint)
             .map(|id| id.to_string())
-            .unwrap_or_else(|| language_id_from_path(path).to_string());
+            .unwrap_or_else(|| self.language_id_for_path(path));
         self.current_language_id = language_id.clone();
         self.lsp_client
             .set_enabled(self.current_language_id.clone(), true);
-        self.document_index
-            .update_document(&self.current_document_id, &self.editor_preview);
+        self.open_lsp_document();
+        // 巨檔模式停用字詞索引。 / Large-file mode skips the word index.
+        if self.large_file.is_none() {
+            self.document_index
+                .update_document(&self.current_document_id, &self.editor_preview);
+        }
         self.document_dirty = false;
         let current_id = self.current_document_id.clone();
         self.set_tab_dirty_state(&current_id, false);
-        self.status.refresh_cursor(&self.editor_preview);
+        self.status.refresh_cursor(&self.editor_text);
         self.status
             .set_document_language(self.language_display_name(&language_id));
         self.refresh_completions();
     }
 
+    fn large_file_options(&self) -> LargeFileOptions {
+        LargeFileOptions::with_threshold_mib(
+            self.preferences_store
+                .preferences()
+                .editor
+                .large_file_threshold_mb,
+        )
+    }
+
+    /// 以唯讀方式顯示巨檔的前段內容並停用昂貴功能。 / Shows the head of a large file read-only and turns off the expensive features.
+    fn open_large_file(&mut self, path: &str, document: LargeFileDocument) -> Option<String> {
+        let shown = document.line_count().min(LARGE_FILE_PREVIEW_LINES);
+        let lines = match document.lines(0..shown) {
+            Ok(lines) => lines,
+            Err(err) => {
+                log_warn(format!("Failed to read {path} in large-file mode: {err}"));
+                return None;
+            }
+        };
+        let total = document.line_count();
+        let size_mib = document.len() / (1024 * 1024);
+        self.push_localized_notification(
+            format!(
+                "{path} ({size_mib} MiB) opened read-only in large-file mode; showing {shown} of {total} lines."
+            ),
+            format!("{path}（{size_mib} MiB）以巨檔模式唯讀開啟；顯示 {total} 行中的 {shown} 行。"),
+        );
+        self.document_format = DocumentFormat::of_large(&document);
+        self.large_file = Some(document);
+        Some(lines.join("\n"))
+    }
+
+    fn open_lsp_document(&mut self) {
+        if self.hex_view.is_some() || self.large_file.is_some() {
+            return;
+        }
+        self.lsp_worker.open_document(
+            &self.current_language_id,
+            &self.current_document_id,
+            &self.editor_preview,
+        );
+    }
+
+    fn sync_lsp_document(&mut self) {
+        self.lsp_worker
+            .change_document(&self.current_document_id, &self.editor_preview);
+    }
+
+    fn close_lsp_document(&mut self, document_id: &str) {
+        self.lsp_worker.close_document(document_id);
+    }
+
+    /// 處理背景 LSP 工作的結果；仍有工作時回傳 `true`。 / Handles replies from the background LSP work; returns `true` while jobs remain.
+    fn poll_lsp_replies(&mut self) -> bool {
+        for reply in self.lsp_worker.drain() {
+            match reply {
+                LspReply::Definition { result, .. } => self.show_definition(result),
+                LspReply::References { result, .. } => self.show_references(result),
+                LspReply::Hover { result, .. } => self.show_hover_result(result),
+                LspReply::Rename {
+                    document,
+                    new_name,
+                    result,
+                } => self.finish_rename(&document, &new_name, result),
+                LspReply::Notification { document, error } => log_warn(format!(
+                    "Language server unavailable for {document}: {error}"
+                )),
+                LspReply::Completions => self.refresh_completions(),
+            }
+        }
+        self.lsp_worker.is_busy()
+    }
+
+    fn lsp_cursor_position(&self) -> TextPosition {
+        TextPosition::new(
+            self.status.line.saturating_sub(1),
+            self.status.column.saturating_sub(1),
+        )
+    }
+
+    fn goto_definition(&mut self) {
+        let position = self.lsp_cursor_position();
+        self.lsp_worker
+            .goto_definition(&self.current_document_id, position);
+    }
+
+    fn show_definition(&mut self, result: Result<Vec<Location>, LspClientError>) {
+        match result {
+            Ok(locations) => match locations.first() {
+                Some(location) => {
+                    let location = location.clone();
+                    self.jump_to_lsp_range(Some(&location.uri), Some(&location.range));
+                }
+                None => self.push_localized_notification(
+                    "No definition found.",
+                    "找不到定義。",
+                ),
+            },
+            Err(err) => self.push_localized_notification(
+                format!("Go to definition failed: {err}"),
+                format!("無法前往定義：{err}"),
+            ),
+        }
+    }
+
+    fn find_references(&mut self) {
+        let position = self.lsp_cursor_position();
+        self.lsp_worker
+            .references(&self.current_document_id, position, true);
+    }
+
+    fn show_references(&mut self, result: Result<SearchReport, LspClientError>) {
+        match result {
+            Ok(report) => {
+                let total = report.total_matches;
+                self.search_report = Some(report);
+                self.show_find_results_panel();
+                self.show_find_status_message(
+                    format!("Found {total} references"),
+                    format!("共找到 {total} 個參考"),
+                    total == 0,
+                );
+            }
+            Err(err) => self.push_localized_notification(
+                format!("Find references failed: {err}"),
+                format!("無法尋找參考：{err}"),
+            ),
+        }
+    }
+
+    fn show_hover(&mut self) {
+        let position = self.lsp_cursor_position();
+        self.lsp_worker.hover(&self.current_document_id, position);
+    }
+
+    fn show_hover_result(&mut self, result: Result<Option<Hover>, LspClientError>) {
+        match result {
+            Ok(Some(hover)) => self.push_notification(hover.contents),
+            Ok(None) => self.push_localized_notification(
+                "No information at the cursor.",
+                "游標處沒有可用資訊。",
+            ),
+            Err(err) => self.push_localized_notification(
+                format!("Hover failed: {err}"),
+                format!("無法取得說明：{err}"),
+            ),
+        }
+    }
+
+    fn open_rename_dialog(&mut self) {
+        let caret = self.current_caret_char_index();
+        self.rename_dialog_name = word_at_char_index(&self.editor_preview, caret);
+        self.rename_dialog_error = None;
+        self.show_rename_dialog = true;
+    }
+
+    /// 在背景送出更名請求，結果由 `finish_rename` 套用。 / Sends the rename request in the background; `finish_rename` applies the result.
+    fn attempt_rename_symbol(&mut self) {
+        let new_name = self.rename_dialog_name.trim().to_string();
+        if new_name.is_empty() {
+            self.rename_dialog_error =
+                Some(self.localized_owned("Enter a new name".into(), "請輸入新名稱".into()));
+            return;
+        }
+        let position = self.lsp_cursor_position();
+        self.lsp_worker
+            .rename(&self.current_document_id, position, &new_name);
+        self.rename_dialog_error = None;
+        self.rename_pending = true;
+    }
+
+    /// 套用伺服器回傳的更名編輯；其他檔案直接寫回磁碟。 / Applies the server's rename edits, writing other files to disk.
+    fn finish_rename(
+        &mut self,
+        document: &str,
+        new_name: &str,
+        result: Result<WorkspaceEdit, LspClientError>,
+    ) {
+        self.rename_pending = false;
+        let edit = match result {
+            Ok(edit) => edit,
+            Err(err) => {
+                self.rename_dialog_error = Some(err.to_string());
+                return;
+            }
+        };
+        if document != self.current_document_id {
+            // 更名期間已切換文件，不套用過時的編輯。 / The document changed while renaming; drop the stale edits.
+            self.rename_dialog_error = Some(self.localized_owned(
+                "The document changed before the rename finished".into(),
+                "更名完成前文件已切換".into(),
+            ));
+            return;
+        }
+        let current_uri = rustnotepad_lsp_client::document_uri(&self.current_document_id);
+        // 先算出所有結果，任何一份失敗就全部不套用。 / Work out every result first; one failure applies nothing.
+        let mut current = None;
+        let mut others = Vec::new();
+        for (uri, edits) in &edit.changes {
+            let renamed = if *uri == current_uri {
+                apply_text_edits(&self.editor_preview, edits)
+                    .map(|text| current = Some(text))
+                    .map_err(|err| err.to_string())
+            } else {
+                let path = uri_to_document(uri);
+                fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| apply_text_edits(&text, edits).map_err(|err| err.to_string()))
+                    .map(|text| others.push((path.clone(), text)))
+                    .map_err(|err| format!("{path}: {err}"))
+            };
+            if let Err(err) = renamed {
+                self.rename_dialog_error = Some(err);
+                return;
+            }
+        }
+        if let Some(text) = current {
+            self.apply_editor_text(text);
+        }
+        let mut failures = Vec::new();
+        for (path, text) in others {
+            if let Err(err) = fs::write(&path, text) {
+                failures.push(format!("{path}: {err}"));
+            }
+        }
+        let files = edit.changes.len();
+        let count = edit.edit_count();
+        self.push_localized_notification(
+            format!("Renamed to {new_name}: {count} edits in {files} files"),
+            format!("已更名為 {new_name}：{files} 個檔案共 {count} 處"),
+        );
+        for failure in failures {
+            log_warn(format!("Rename could not update {failure}"));
+        }
+        self.show_rename_dialog = false;
+    }
+
+    fn report_line_endings(&mut self, path: &str, stats: &LineEndingStats) {
+        if let Some(ending) = stats.dominant() {
+            self.status.eol = match ending {
+                LineEnding::CrLf => "Windows (CR LF)",
+                LineEnding::Cr => "Macintosh (CR)",
+                LineEnding::Lf => "Unix (LF)",
+            };
+        }
+        self.status.eol_warning = None;
+        if stats.is_mixed() {
+            let message = self.localized_owned(
+                format!(
+                    "{path} mixes line endings (LF {}, CRLF {}, CR {})",
+                    stats.lf, stats.crlf, stats.cr
+                ),
+                format!(
+                    "{path} 混用行尾（LF {}、CRLF {}、CR {}）",
+                    stats.lf, stats.crlf, stats.cr
+                ),
+            );
+            log_warn(message.clone());
+            self.push_notification(message.clone());
+            self.status.eol_warning = Some(message);
+        }
+    }
+
     fn close_tab(&mut self, role: PaneRole, tab_id: &str) {
         if let Some(pane) = self.layout.panes.iter_mut().find(|pane| pane.role == role) {
             if let Some(pos) = pane
@@ -5384,11 +6700,17 @@ impl RustNotePadApp {
             {
                 self.load_document(&active_tab.id, active_tab.language.as_deref());
             } else {
+                self.close_lsp_document(tab_id);
                 self.editor_preview = self.sample_editor_content.clone();
+                self.editor_text_replaced();
+                self.hex_view = None;
                 self.clear_search_results();
                 self.current_document_id = PREVIEW_DOCUMENT_ID.to_string();
                 self.current_language_id = PREVIEW_LANGUAGE_ID.to_string();
                 self.current_document_path = None;
+                self.document_format = DocumentFormat::default();
+                self.large_file = None;
+                self.status.eol_warning = None;
                 self.document_dirty = false;
                 self.editor_undo_stack.clear();
                 self.editor_redo_stack.clear();
@@ -5396,7 +6718,7 @@ impl RustNotePadApp {
                 self.update_editor_selection(None);
                 self.document_index
                     .update_document(&self.current_document_id, &self.editor_preview);
-                self.status.refresh_cursor(&self.editor_preview);
+                self.status.refresh_cursor(&self.editor_text);
                 self.status
                     .set_document_language(self.language_display_name(PREVIEW_LANGUAGE_ID));
                 self.refresh_completions();
@@ -6007,6 +7329,13 @@ impl RustNotePadApp {
                 self.persist_panel_visibility("view.document_map_visible", false);
             }
         });
+        if self.large_file.is_some() {
+            ui.label(self.localized(
+                "Document map is disabled in large-file mode.",
+                "巨檔模式下停用文件地圖。",
+            ));
+            return;
+        }
         let scroll_height = ui.available_height().max(0.0);
         egui::ScrollArea::vertical()
             .max_height(scroll_height)
@@ -6202,42 +7531,140 @@ impl RustNotePadApp {
                     ui.label(self.text("panel.notifications.font_warning"));
                 }
             }
-            "lsp" => {
-                if self.lsp_client.is_online() {
-                    ui.label(self.text("panel.lsp.connected"));
-                } else {
-                    ui.colored_label(
-                        Color32::from_rgb(239, 68, 68),
-                        self.text("panel.lsp.offline").to_string(),
+            "lsp" => self.render_problems_panel(ui),
+            other => {
+                let template = self.text("panel.generic.no_content").into_owned();
+                ui.label(template.replace("{panel}", other));
+            }
+        }
+    }
+
+    fn render_problems_panel(&mut self, ui: &mut egui::Ui) {
+        if self.lsp_client.is_online() {
+            ui.label(self.text("panel.lsp.connected"));
+        } else {
+            ui.colored_label(
+                Color32::from_rgb(239, 68, 68),
+                self.text("panel.lsp.offline").to_string(),
+            );
+        }
+        let status = self.lsp_client.server_status(&self.current_language_id);
+        if let ServerStatus::Failed(reason) = &status {
+            ui.colored_label(Color32::from_rgb(239, 68, 68), reason.as_str());
+        }
+        // 未設定伺服器時顯示手動推入、無位置的診斷。 / Without a server, show the hand-fed diagnostics that carry no location.
+        let problems = if status == ServerStatus::NotConfigured {
+            self.lsp_client
+                .diagnostics(self.current_language_id.as_str())
+        } else {
+            self.lsp_client.problems()
+        };
+        if problems.is_empty() {
+            ui.label(self.text("panel.lsp.no_diagnostics"));
+            return;
+        }
+
+        let mut jump = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                let mut current_uri = None;
+                for diagnostic in &problems {
+                    if diagnostic.uri.is_some() && diagnostic.uri != current_uri {
+                        current_uri = diagnostic.uri.clone();
+                        let document = current_uri
+                            .as_deref()
+                            .map(uri_to_document)
+                            .unwrap_or_default();
+                        ui.strong(document);
+                    }
+                    let mut message = diagnostic.message.clone();
+                    let origin = [diagnostic.source.as_deref(), diagnostic.code.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    if !origin.is_empty() {
+                        message = format!("{message} [{origin}]");
+                    }
+                    let color = diagnostic_color(diagnostic.severity);
+                    let Some(range) = &diagnostic.range else {
+                        ui.colored_label(color, format!("[{:?}] {message}", diagnostic.severity));
+                        continue;
+                    };
+                    let label = self.format_indexed(
+                        "panel.lsp.entry",
+                        &[
+                            (range.start.line + 1).to_string(),
+                            (range.start.column + 1).to_string(),
+                            message,
+                        ],
                     );
-                }
-                let diagnostics = self
-                    .lsp_client
-                    .diagnostics(self.current_language_id.as_str());
-                if diagnostics.is_empty() {
-                    ui.label(self.text("panel.lsp.no_diagnostics"));
-                } else {
-                    for diagnostic in diagnostics.iter().take(5) {
-                        let color = diagnostic_color(diagnostic.severity);
-                        ui.colored_label(
-                            color,
-                            format!("[{:?}] {}", diagnostic.severity, diagnostic.message),
-                        );
+                    let mut response =
+                        ui.selectable_label(false, RichText::new(label).color(color));
+                    if !diagnostic.related.is_empty() {
+                        let related = diagnostic
+                            .related
+                            .iter()
+                            .map(|info| {
+                                format!(
+                                    "{}:{}:{}: {}",
+                                    uri_to_document(&info.uri),
+                                    info.range.start.line + 1,
+                                    info.range.start.column + 1,
+                                    info.message
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        response = response.on_hover_text(related);
                     }
-                    if diagnostics.len() > 5 {
-                        let remaining = diagnostics.len() - 5;
-                        ui.label(self.format_indexed(
-                            "panel.lsp.more_diagnostics",
-                            &[remaining.to_string()],
-                        ));
+                    if response.clicked() {
+                        jump = Some(diagnostic.clone());
                     }
                 }
-            }
-            other => {
-                let template = self.text("panel.generic.no_content").into_owned();
-                ui.label(template.replace("{panel}", other));
+            });
+        if let Some(diagnostic) = jump {
+            self.jump_to_diagnostic(&diagnostic);
+        }
+    }
+
+    fn jump_to_diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.jump_to_lsp_range(diagnostic.uri.as_deref(), diagnostic.range.as_ref());
+    }
+
+    /// 開啟 `uri` 指向的文件並選取範圍。 / Opens the document behind `uri` and selects the range.
+    fn jump_to_lsp_range(&mut self, uri: Option<&str>, range: Option<&LspRange>) {
+        if let Some(document) = uri.map(uri_to_document) {
+            if document != self.current_document_id && Path::new(&document).is_file() {
+                let display = Path::new(&document)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(document.as_str())
+                    .to_string();
+                self.open_document(&document, &display);
             }
         }
+        let text = &self.editor_preview;
+        let (start, end) = match range.and_then(|range| range.bytes.clone()) {
+            Some(bytes)
+                if bytes.end <= text.len()
+                    && text.is_char_boundary(bytes.start)
+                    && text.is_char_boundary(bytes.end) =>
+            {
+                (bytes.start, bytes.end)
+            }
+            _ => {
+                let position = range.map(|range| range.start).unwrap_or_default();
+                let offset = byte_offset_of_position(text, position.line, position.column);
+                (offset, offset)
+            }
+        };
+        let start_char = Self::char_index_from_byte(text, start);
+        let end_char = Self::char_index_from_byte(text, end);
+        let range = CCursorRange::two(CCursor::new(start_char), CCursor::new(end_char));
+        self.pending_editor_selection = Some(range);
+        self.update_editor_selection(Some(range));
     }
 
     fn render_find_results_panel(&mut self, ui: &mut egui::Ui) {
@@ -6297,7 +7724,7 @@ impl RustNotePadApp {
                                     .on_hover_text(hit.line_text.clone())
                                     .clicked()
                                 {
-                                    self.jump_to_search_match(hit);
+                                    self.jump_to_search_result(entry.path.as_deref(), hit);
                                 }
                             }
                         });
@@ -6321,6 +7748,7 @@ impl RustNotePadApp {
             }
         };
         let ui_language_value = self.active_locale_display_name();
+        let mixed_eol_label = self.localized("Mixed EOL", "混用行尾");
         let status_response =
             ui.allocate_ui(vec2(ui.available_width(), STATUS_BAR_HEIGHT), |ui| {
                 ui.set_min_height(STATUS_BAR_HEIGHT);
@@ -6359,6 +7787,10 @@ impl RustNotePadApp {
                             ui.label(ui_language_value);
                             ui.separator();
                             ui.label(eol_label);
+                            if let Some(warning) = &self.status.eol_warning {
+                                ui.colored_label(Color32::from_rgb(249, 115, 22), mixed_eol_label)
+                                    .on_hover_text(warning.as_str());
+                            }
                             ui.separator();
                             ui.label(encoding_label);
                             ui.separator();
@@ -6408,21 +7840,150 @@ impl RustNotePadApp {
                 });
         }
 
-        if self.document_map_visible {
-            egui::SidePanel::right("document_map_panel")
-                .resizable(true)
-                .default_width(DOCUMENT_MAP_WIDTH)
-                .show(ctx, |ui| {
-                    let height = ui.available_height();
-                    self.render_document_map_panel(ui, height);
-                });
+        if self.document_map_visible {
+            egui::SidePanel::right("document_map_panel")
+                .resizable(true)
+                .default_width(DOCUMENT_MAP_WIDTH)
+                .show(ctx, |ui| {
+                    let height = ui.available_height();
+                    self.render_document_map_panel(ui, height);
+                });
+        }
+
+        egui::CentralPanel::default()
+            .frame(self.main_panel_frame(ctx))
+            .show(ctx, |ui| {
+                self.render_editor_panes(ui);
+            });
+    }
+
+    fn render_hex_view(&mut self, ui: &mut egui::Ui, font_size: f32) {
+        let goto_label = self.localized("Go to offset", "跳至位移");
+        let find_label = self.localized("Find bytes", "尋找位元組");
+        let find_next_label = self.localized("Find Next", "找下一個");
+        let overwrite_label = self.localized("Overwrite", "覆寫");
+        let insert_label = self.localized("Insert", "插入");
+        let apply_label = self.localized("Write at cursor", "寫入游標處");
+        let invalid_offset = self.localized("Invalid offset", "無效的位移");
+        let invalid_bytes = self.localized(
+            "Enter bytes as hex pairs, e.g. DE AD BE EF",
+            "請以十六進位輸入位元組，例如 DE AD BE EF",
+        );
+        let no_match = self.localized("Byte pattern not found", "找不到位元組樣式");
+        let Some(hex) = self.hex_view.as_mut() else {
+            return;
+        };
+        let was_dirty = hex.document.is_dirty();
+
+        ui.horizontal(|ui| {
+            ui.label(goto_label);
+            let goto = ui.add(
+                egui::TextEdit::singleline(&mut hex.goto_input)
+                    .desired_width(96.0)
+                    .hint_text("0x0000"),
+            );
+            if goto.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                match parse_offset(&hex.goto_input) {
+                    Some(offset) => {
+                        hex.move_cursor(offset);
+                        hex.message = None;
+                    }
+                    None => hex.message = Some(invalid_offset.clone()),
+                }
+            }
+            ui.separator();
+            ui.label(find_label);
+            ui.add(
+                egui::TextEdit::singleline(&mut hex.pattern_input)
+                    .desired_width(160.0)
+                    .hint_text("7F \"ELF\" ??"),
+            );
+            if ui.button(find_next_label).clicked() {
+                match BytePattern::parse(&hex.pattern_input) {
+                    Ok(pattern) => {
+                        hex.matches = hex.document.find_all(&pattern);
+                        match hex.document.find_next(&pattern, hex.cursor + 1) {
+                            Some(found) => {
+                                hex.move_cursor(found.start);
+                                hex.message = None;
+                            }
+                            None => hex.message = Some(no_match.clone()),
+                        }
+                    }
+                    Err(err) => hex.message = Some(err.to_string()),
+                }
+            }
+            ui.separator();
+            ui.selectable_value(&mut hex.edit_mode, HexEditMode::Overwrite, overwrite_label);
+            ui.selectable_value(&mut hex.edit_mode, HexEditMode::Insert, insert_label);
+            ui.add(
+                egui::TextEdit::singleline(&mut hex.bytes_input)
+                    .desired_width(120.0)
+                    .hint_text("DE AD BE EF"),
+            );
+            if ui.button(apply_label).clicked() {
+                match parse_hex_bytes(&hex.bytes_input) {
+                    Some(bytes) if !bytes.is_empty() => {
+                        match hex.document.write(hex.cursor, &bytes, hex.edit_mode) {
+                            Ok(()) => {
+                                hex.move_cursor(hex.cursor + bytes.len());
+                                hex.matches.clear();
+                                hex.message = None;
+                            }
+                            Err(err) => hex.message = Some(err.to_string()),
+                        }
+                    }
+                    _ => hex.message = Some(invalid_bytes.clone()),
+                }
+            }
+        });
+        ui.label(
+            RichText::new(format!(
+                "0x{:08X} / 0x{:08X}",
+                hex.cursor,
+                hex.document.len()
+            ))
+            .monospace(),
+        );
+        if let Some(message) = &hex.message {
+            ui.colored_label(Color32::from_rgb(220, 120, 80), message);
+        }
+        ui.separator();
+
+        let font = FontId::monospace(font_size);
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font)) + ui.spacing().item_spacing.y;
+        let total_rows = hex.document.row_count(DEFAULT_BYTES_PER_ROW);
+        let cursor_row = hex
+            .document
+            .row_of_offset(hex.cursor, DEFAULT_BYTES_PER_ROW);
+        let mut scroll = egui::ScrollArea::both().auto_shrink([false, false]);
+        if std::mem::take(&mut hex.scroll_to_cursor) {
+            scroll = scroll.vertical_scroll_offset(cursor_row as f32 * row_height);
+        }
+        let mut clicked_offset = None;
+        scroll.show_rows(ui, row_height, total_rows, |ui, rows| {
+            for row in hex.document.rows(rows, DEFAULT_BYTES_PER_ROW) {
+                let row_end = row.offset + row.width;
+                let selected = (row.offset..row_end).contains(&hex.cursor)
+                    || hex
+                        .matches
+                        .iter()
+                        .any(|found| found.start < row_end && found.end > row.offset);
+                let text = RichText::new(row.to_string()).font(font.clone());
+                if ui.selectable_label(selected, text).clicked() {
+                    clicked_offset = Some(row.offset);
+                }
+            }
+        });
+        if let Some(offset) = clicked_offset {
+            hex.cursor = offset;
         }
 
-        egui::CentralPanel::default()
-            .frame(self.main_panel_frame(ctx))
-            .show(ctx, |ui| {
-                self.render_editor_panes(ui);
-            });
+        if !was_dirty && hex.document.is_dirty() {
+            self.document_dirty = true;
+            let current_id = self.current_document_id.clone();
+            self.set_tab_dirty_state(&current_id, true);
+        }
     }
 
     fn render_editor_panes(&mut self, ui: &mut egui::Ui) {
@@ -6476,6 +8037,12 @@ impl RustNotePadApp {
                                         .stroke(egui::Stroke::NONE) // Modern look: no border
                                         .inner_margin(Margin::same(0.0)) // Maximize space
                                         .show(ui, |ui| {
+                                            if self.hex_view.is_some() {
+                                                self.render_hex_view(ui, editor_font_size);
+                                                return;
+                                            }
+                                            self.handle_caret_input(ui);
+                                            let extra_carets = self.extra_caret_char_ranges();
                                             let previous_text = self.editor_preview.clone();
                                             let mut buffer = previous_text.clone();
 
@@ -6565,23 +8132,47 @@ impl RustNotePadApp {
                                                         |ui| {
                                                             ui.set_min_height(full_height);
                                                             
+                                                            let underlines = diagnostic_underlines(
+                                                                &self.editor_preview,
+                                                                &self.lsp_client.document_diagnostics(&self.current_document_id),
+                                                            );
+                                                            let mark_styles = self.mark_styles.clone();
+                                                            // Only the lines in view are highlighted; the highlighter lexes lazily up to them
+                                                            let first_line = self.editor_first_visible_line;
+                                                            let syntax_colors = self.editor_syntax_colors(
+                                                                first_line..first_line + visible_lines as usize + 1,
+                                                            );
                                                             let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
                                                                 let mut layout_job = egui::text::LayoutJob::default();
                                                                 // Use system monospace font
                                                                 let font_id = egui::FontId::monospace(editor_font_size);
                                                                 let color = ui.visuals().text_color();
-                                                                layout_job.append(
-                                                                    string,
-                                                                    0.0,
-                                                                    egui::text::TextFormat {
-                                                                        font_id,
-                                                                        color,
-                                                                        line_height: Some(editor_line_height),
-                                                                        valign: egui::Align::Center,  // Center text vertically within line height
-                                                                        extra_letter_spacing: 1.5,    // Add spacing between characters
-                                                                        ..Default::default()
-                                                                    },
-                                                                );
+                                                                let format = egui::text::TextFormat {
+                                                                    font_id,
+                                                                    color,
+                                                                    line_height: Some(editor_line_height),
+                                                                    valign: egui::Align::Center,  // Center text vertically within line height
+                                                                    extra_letter_spacing: 1.5,    // Add spacing between characters
+                                                                    ..Default::default()
+                                                                };
+                                                                // Underline diagnostics and shade marks; spans made stale by an edit this frame are skipped
+                                                                let segments = editor_text_segments(string, &underlines, &mark_styles, &syntax_colors);
+                                                                if segments.is_empty() {
+                                                                    layout_job.append(string, 0.0, format.clone());
+                                                                }
+                                                                for (range, underline, background, foreground) in segments {
+                                                                    let mut styled = format.clone();
+                                                                    if let Some(color) = foreground {
+                                                                        styled.color = color;
+                                                                    }
+                                                                    if let Some(color) = underline {
+                                                                        styled.underline = egui::Stroke::new(1.5, color);
+                                                                    }
+                                                                    if let Some(color) = background {
+                                                                        styled.background = color;
+                                                                    }
+                                                                    layout_job.append(&string[range], 0.0, styled);
+                                                                }
                                                                 // Disable word wrap - each line stays on one line
                                                                 // Use horizontal scrollbar instead of wrapping
                                                                 layout_job.wrap.max_width = f32::INFINITY;
@@ -6666,8 +8257,10 @@ impl RustNotePadApp {
                                                                         .desired_width(f32::INFINITY)
                                                                         .desired_rows(1) // Let it grow
                                                                         .lock_focus(true)
+                                                                        .interactive(self.large_file.is_none())
                                                                         .frame(false); // Modern look: no internal frame
                                                                     let edit_output = text_edit.show(ui);
+                                                                    paint_extra_carets(ui, edit_output.text_draw_pos, &edit_output.galley, &extra_carets);
                                                                     
                                                                     edit_output
                                                                 });
@@ -6684,6 +8277,7 @@ impl RustNotePadApp {
                                                             // Now draw line numbers based on editor scroll offset
                                                             let scroll_offset = aligned_offset_y;
                                                             let first_visible_line = (scroll_offset / gutter_line_height).floor() as usize;
+                                                            self.editor_first_visible_line = first_visible_line;
                                                             let visible_lines_count = (full_height / gutter_line_height).ceil() as usize + 1;
                                                             
                                                             // Get hover position for gutter highlighting
@@ -6742,6 +8336,11 @@ impl RustNotePadApp {
                                                             }
                                                             
                                                             let output = scroll_output.inner;
+                                                            self.editor_has_focus = output.response.has_focus();
+                                                            // 以滑鼠放置游標時回到單一游標。 / Placing the caret with the mouse goes back to a single caret.
+                                                            if output.response.clicked() || output.response.drag_started() {
+                                                                self.editor_extra_carets.clear();
+                                                            }
 
                                                             if output.response.changed()
                                                                 && buffer != previous_text
@@ -6807,12 +8406,9 @@ impl RustNotePadApp {
         );
     }
 
-    fn render_highlight_summary(&self, ui: &mut egui::Ui) {
+    fn render_highlight_summary(&mut self, ui: &mut egui::Ui) {
         ui.heading(self.text("highlight.heading").to_string());
-        match self
-            .highlight_registry
-            .highlight(self.current_language_id.as_str(), &self.editor_preview)
-        {
+        match self.editor_highlight_tokens(0..usize::MAX) {
             Ok(tokens) => {
                 ui.label(self.format_indexed("highlight.tokens", &[tokens.len().to_string()]));
                 let mut counts: BTreeMap<String, usize> = BTreeMap::new();
@@ -7414,6 +9010,13 @@ impl App for RustNotePadApp {
             }
         }
 
+        if self.poll_find_in_files() {
+            ctx.request_repaint();
+        }
+        if self.poll_lsp_replies() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
         let is_modal_open = self.show_settings_window || self.show_help_about_window;
 
         self.show_menu_bar(ctx);
@@ -7442,6 +9045,7 @@ impl App for RustNotePadApp {
         self.render_settings_window(ctx);
         self.render_font_restart_dialog(ctx);
         self.render_file_dialogs(ctx);
+        self.render_rename_dialog(ctx);
         self.show_print_preview_window(ctx);
         self.render_help_windows(ctx);
 
@@ -7499,9 +9103,7 @@ impl RustNotePadApp {
                     FindDialogTab::Replace => {
                         self.render_placeholder_find_tab(ui, FindDialogTab::Replace)
                     }
-                    FindDialogTab::FindInFiles => {
-                        self.render_placeholder_find_tab(ui, FindDialogTab::FindInFiles)
-                    }
+                    FindDialogTab::FindInFiles => self.render_find_in_files_tab(ui),
                     FindDialogTab::Mark => {
                         self.render_placeholder_find_tab(ui, FindDialogTab::Mark)
                     }
@@ -7549,6 +9151,11 @@ impl RustNotePadApp {
             "Regular expression",
             "規則運算式",
         );
+        let label_mode_fuzzy = self.localized_text(
+            "dialog.find.mode.fuzzy",
+            "Fuzzy (characters in order)",
+            "模糊 (依序比對字元)",
+        );
         let label_regex_dot = self.localized_text(
             "dialog.find.regex_dot",
             "`.` matches newline",
@@ -7577,6 +9184,9 @@ impl RustNotePadApp {
                     .desired_width(320.0)
                     .hint_text(hint_find.clone());
                 let response = ui.add(text_edit);
+                if response.changed() {
+                    self.find_as_you_type();
+                }
                 if response.lost_focus() && ui.ctx().input(|i| i.key_pressed(egui::Key::Enter)) {
                     let direction = if self.find_direction_up {
                         SearchDirection::Backward
@@ -7586,6 +9196,7 @@ impl RustNotePadApp {
                     let _ = self.perform_find(direction);
                 }
             });
+            self.render_search_history_row(ui);
             ui.add_space(8.0);
             ui.horizontal(|ui| {
                 ui.vertical(|ui| {
@@ -7605,6 +9216,7 @@ impl RustNotePadApp {
                     );
                     let regex_selected = self.find_mode == FindMode::Regex;
                     ui.radio_value(&mut self.find_mode, FindMode::Regex, label_mode_regex.clone());
+                    ui.radio_value(&mut self.find_mode, FindMode::Fuzzy, label_mode_fuzzy.clone());
                     if !regex_selected {
                         self.find_regex_dot_matches_newline = false;
                     }
@@ -7687,6 +9299,116 @@ impl RustNotePadApp {
         });
     }
 
+    fn render_find_in_files_tab(&mut self, ui: &mut egui::Ui) {
+        let label_find_what =
+            self.localized_text("dialog.find.find_what", "Find what:", "尋找內容：");
+        let label_filters = self.localized_text("dialog.find.filters", "Filters:", "篩選條件：");
+        let hint_filters = self.localized_text(
+            "dialog.find.filters_hint",
+            "*.rs *.toml !target",
+            "*.rs *.toml !target",
+        );
+        let label_directory =
+            self.localized_text("dialog.find.directory", "Directory:", "資料夾：");
+        let label_whole_word = self.localized_text(
+            "dialog.find.option.whole_word",
+            "Match whole word only",
+            "僅符合整個單字",
+        );
+        let label_match_case = self.localized_text(
+            "dialog.find.option.match_case",
+            "Match case",
+            "區分大小寫",
+        );
+        let label_hidden = self.localized_text(
+            "dialog.find.option.hidden",
+            "In hidden folders",
+            "包含隱藏資料夾",
+        );
+        let label_ignore = self.localized_text(
+            "dialog.find.option.respect_ignore",
+            "Respect .gitignore",
+            "遵循 .gitignore",
+        );
+        let label_use_index = self.localized_text(
+            "dialog.find.option.use_index",
+            "Use workspace index",
+            "使用工作區索引",
+        );
+        let label_find_all = self.localized_text(
+            "dialog.find.button.find_all_files",
+            "Find All",
+            "全部尋找",
+        );
+        let label_stop = self.localized_text("dialog.find.button.stop", "Stop", "停止");
+        let running = self.find_in_files_task.is_some();
+        ui.vertical(|ui| {
+            egui::Grid::new("find_in_files_fields")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label(label_find_what);
+                    ui.add(egui::TextEdit::singleline(&mut self.find_query).desired_width(320.0));
+                    ui.end_row();
+                    ui.label(label_filters);
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.find_in_files_filters)
+                            .desired_width(320.0)
+                            .hint_text(hint_filters),
+                    );
+                    ui.end_row();
+                    ui.label(label_directory);
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.find_in_files_directory)
+                            .desired_width(320.0),
+                    );
+                    ui.end_row();
+                });
+            self.render_search_history_row(ui);
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.checkbox(&mut self.find_whole_word, label_whole_word);
+                    ui.checkbox(&mut self.find_match_case, label_match_case);
+                    ui.checkbox(&mut self.find_in_files_hidden, label_hidden);
+                    ui.checkbox(&mut self.find_in_files_respect_ignore, label_ignore);
+                    if ui
+                        .checkbox(&mut self.find_in_files_use_index, label_use_index)
+                        .changed()
+                        && !self.find_in_files_use_index
+                    {
+                        self.workspace_search_index = None;
+                    }
+                });
+                ui.add_space(16.0);
+                ui.vertical(|ui| {
+                    if ui
+                        .add_enabled(!running, egui::Button::new(label_find_all))
+                        .clicked()
+                    {
+                        self.start_find_in_files();
+                    }
+                    if ui
+                        .add_enabled(running, egui::Button::new(label_stop))
+                        .clicked()
+                    {
+                        if let Some(task) = &self.find_in_files_task {
+                            task.cancel();
+                        }
+                    }
+                });
+            });
+            if let Some(status) = &self.find_status {
+                ui.add_space(8.0);
+                let color = if status.is_error {
+                    Color32::from_rgb(239, 68, 68)
+                } else {
+                    Color32::from_rgb(34, 197, 94)
+                };
+                ui.colored_label(color, &status.text);
+            }
+        });
+    }
+
     fn render_placeholder_find_tab(&mut self, ui: &mut egui::Ui, tab: FindDialogTab) {
         if matches!(tab, FindDialogTab::Replace) {
             let label_find_what =
@@ -8131,6 +9853,45 @@ impl RustNotePadApp {
         }
     }
 
+    fn render_rename_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_rename_dialog {
+            return;
+        }
+        let mut open = self.show_rename_dialog;
+        let mut should_close = false;
+        egui::Window::new(self.localized("Rename Symbol", "重新命名符號"))
+            .collapsible(false)
+            .resizable(false)
+            .frame(self.window_frame(ctx))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.set_min_width(320.0);
+                ui.label(self.localized("New name", "新名稱"));
+                ui.add(egui::TextEdit::singleline(&mut self.rename_dialog_name));
+                if let Some(err) = &self.rename_dialog_error {
+                    ui.colored_label(Color32::from_rgb(239, 68, 68), err);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let rename = ui.add_enabled(
+                        !self.rename_pending,
+                        egui::Button::new(self.localized("Rename", "重新命名")),
+                    );
+                    if rename.clicked() {
+                        self.attempt_rename_symbol();
+                    }
+                    if self.rename_pending {
+                        ui.spinner();
+                    }
+                    if ui.button(self.localized("Cancel", "取消")).clicked() {
+                        self.rename_dialog_error = None;
+                        should_close = true;
+                    }
+                });
+            });
+        self.show_rename_dialog = if should_close { false } else { open };
+    }
+
     fn render_plugins_page(&mut self, ui: &mut egui::Ui) {
         ui.heading(self.localized("Plugin Management", "外掛管理"));
         ui.separator();
@@ -8532,6 +10293,255 @@ impl RustNotePadApp {
     }
 }
 
+/// 編輯器中需加底線的診斷範圍：依起點排序、互不重疊，空範圍延伸至一個字元。 /
+/// Diagnostic spans to underline in the editor: sorted, non-overlapping, with empty spans
+/// widened to one character so they stay visible.
+fn diagnostic_underlines(
+    text: &str,
+    diagnostics: &[Diagnostic],
+) -> Vec<(std::ops::Range<usize>, Color32)> {
+    let mut spans: Vec<(std::ops::Range<usize>, DiagnosticSeverity)> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let bytes = diagnostic.byte_range()?;
+            if bytes.end > text.len()
+                || !text.is_char_boundary(bytes.start)
+                || !text.is_char_boundary(bytes.end)
+            {
+                return None;
+            }
+            if !bytes.is_empty() {
+                return Some((bytes, diagnostic.severity));
+            }
+            let next = text[bytes.start..]
+                .chars()
+                .next()
+                .filter(|ch| *ch != '\n' && *ch != '\r')
+                .map(|ch| bytes.start + ch.len_utf8());
+            let previous = text[..bytes.start]
+                .chars()
+                .next_back()
+                .filter(|ch| *ch != '\n' && *ch != '\r')
+                .map(|ch| bytes.start - ch.len_utf8());
+            match (next, previous) {
+                (Some(end), _) => Some((bytes.start..end, diagnostic.severity)),
+                (None, Some(start)) => Some((start..bytes.start, diagnostic.severity)),
+                (None, None) => None,
+            }
+        })
+        .collect();
+    // 同起點時較嚴重者優先。 / At equal starts the more severe diagnostic wins.
+    spans.sort_by_key(|(range, severity)| (range.start, *severity));
+    let mut underlines = Vec::with_capacity(spans.len());
+    let mut covered = 0;
+    for (range, severity) in spans {
+        if range.start < covered {
+            continue;
+        }
+        covered = range.end;
+        underlines.push((range, diagnostic_color(severity)));
+    }
+    underlines
+}
+
+/// 標記樣式的背景色，沿用 Notepad++ 的預設配色。 / Background of each mark style, after Notepad++'s default colours.
+fn mark_style_color(style: MarkStyle) -> Color32 {
+    match style {
+        MarkStyle::First => Color32::from_rgba_unmultiplied(0, 255, 255, 90),
+        MarkStyle::Second => Color32::from_rgba_unmultiplied(255, 128, 0, 90),
+        MarkStyle::Third => Color32::from_rgba_unmultiplied(255, 255, 0, 90),
+        MarkStyle::Fourth => Color32::from_rgba_unmultiplied(128, 0, 255, 90),
+        MarkStyle::Fifth => Color32::from_rgba_unmultiplied(0, 128, 0, 90),
+    }
+}
+
+/// 編輯器文字片段：範圍、底線色、背景色與語法前景色。 / An editor text segment: range, underline, background and syntax foreground colours.
+type EditorSegment = (std::ops::Range<usize>, Option<Color32>, Option<Color32>, Option<Color32>);
+
+/// 將編輯器文字切成片段，各自帶有底線色、背景色與語法前景色；重疊的標記以編號較大的樣式為準。 /
+/// Splits the editor text into segments, each with its underline, background and syntax foreground
+/// colour; where marks overlap the higher-numbered style wins.
+fn editor_text_segments(
+    text: &str,
+    underlines: &[(std::ops::Range<usize>, Color32)],
+    marks: &MarkStyles,
+    syntax: &[(std::ops::Range<usize>, Color32)],
+) -> Vec<EditorSegment> {
+    let usable = |range: &std::ops::Range<usize>| {
+        range.end <= text.len()
+            && text.is_char_boundary(range.start)
+            && text.is_char_boundary(range.end)
+    };
+    let underlines: Vec<_> = underlines
+        .iter()
+        .filter(|(range, _)| usable(range))
+        .collect();
+    let mut bounds = vec![0, text.len()];
+    for (range, _) in &underlines {
+        bounds.extend([range.start, range.end]);
+    }
+    for (_, range) in marks.iter() {
+        if usable(&range) {
+            bounds.extend([range.start, range.end]);
+        }
+    }
+    let syntax: Vec<_> = syntax.iter().filter(|(range, _)| usable(range)).collect();
+    for (range, _) in &syntax {
+        bounds.extend([range.start, range.end]);
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds
+        .windows(2)
+        .map(|pair| {
+            let segment = pair[0]..pair[1];
+            let underline = underlines
+                .iter()
+                .find(|(range, _)| range.start <= segment.start && segment.end <= range.end)
+                .map(|(_, color)| *color);
+            let background = marks.styles_at(segment.start).last().map(mark_style_color);
+            let foreground = syntax
+                .iter()
+                .find(|(range, _)| range.start <= segment.start && segment.end <= range.end)
+                .map(|(_, color)| *color);
+            (segment, underline, background, foreground)
+        })
+        .collect()
+}
+
+/// 有額外游標時由所有游標處理的按鍵。 / Keys every caret handles while extra carets exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MultiCaretKey {
+    Backspace,
+    Delete,
+    Newline,
+    /// 收合為單一游標。 / Collapses back to a single caret.
+    Collapse,
+    Move(Motion),
+}
+
+fn multi_caret_key(key: egui::Key, modifiers: egui::Modifiers) -> Option<MultiCaretKey> {
+    let word = modifiers.command;
+    Some(match key {
+        egui::Key::Backspace => MultiCaretKey::Backspace,
+        egui::Key::Delete => MultiCaretKey::Delete,
+        egui::Key::Enter => MultiCaretKey::Newline,
+        egui::Key::Escape => MultiCaretKey::Collapse,
+        egui::Key::ArrowLeft if word => MultiCaretKey::Move(Motion::WordLeft),
+        egui::Key::ArrowLeft => MultiCaretKey::Move(Motion::CharLeft),
+        egui::Key::ArrowRight if word => MultiCaretKey::Move(Motion::WordRight),
+        egui::Key::ArrowRight => MultiCaretKey::Move(Motion::CharRight),
+        egui::Key::ArrowUp => MultiCaretKey::Move(Motion::LineUp),
+        egui::Key::ArrowDown => MultiCaretKey::Move(Motion::LineDown),
+        egui::Key::Home if word => MultiCaretKey::Move(Motion::DocumentStart),
+        egui::Key::Home => MultiCaretKey::Move(Motion::LineStart),
+        egui::Key::End if word => MultiCaretKey::Move(Motion::DocumentEnd),
+        egui::Key::End => MultiCaretKey::Move(Motion::LineEnd),
+        _ => return None,
+    })
+}
+
+/// 在文字框上畫出額外游標與其選取，`carets` 為（固定端, 位置）字元索引。 / Paints the extra carets and their selections over the text box; `carets` holds (anchor, position) character indices.
+fn paint_extra_carets(
+    ui: &egui::Ui,
+    origin: egui::Pos2,
+    galley: &egui::Galley,
+    carets: &[(usize, usize)],
+) {
+    let painter = ui.painter();
+    let stroke = ui.visuals().text_cursor;
+    let fill = ui.visuals().selection.bg_fill.linear_multiply(0.5);
+    for &(anchor, position) in carets {
+        let (start, end) = (anchor.min(position), anchor.max(position));
+        if start < end {
+            let first = galley.from_ccursor(CCursor::new(start)).rcursor;
+            let last = galley.from_ccursor(CCursor::new(end)).rcursor;
+            for row_index in first.row..=last.row.min(galley.rows.len().saturating_sub(1)) {
+                let row = &galley.rows[row_index];
+                let left = if row_index == first.row {
+                    row.x_offset(first.column)
+                } else {
+                    row.rect.left()
+                };
+                let right = if row_index == last.row {
+                    row.x_offset(last.column)
+                } else {
+                    row.rect.right()
+                };
+                let rect = Rect::from_min_max(
+                    origin + vec2(left, row.min_y()),
+                    origin + vec2(right, row.max_y()),
+                );
+                painter.rect_filled(rect, 0.0, fill);
+            }
+        }
+        let caret = galley
+            .pos_from_cursor(&galley.from_ccursor(CCursor::new(position)))
+            .translate(origin.to_vec2());
+        painter.line_segment([caret.center_top(), caret.center_bottom()], stroke);
+    }
+}
+
+/// 比較前後文字，回傳變更的起點、刪除與插入的位元組數。 / Compares two texts and returns the changed span as (start, removed, inserted) bytes.
+fn changed_span(old: &str, new: &str) -> (usize, usize, usize) {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    (prefix, old.len() - prefix - suffix, new.len() - prefix - suffix)
+}
+
+fn byte_offset_of_position(text: &str, line: usize, column: usize) -> usize {
+    let line_start = text
+        .split('\n')
+        .take(line)
+        .map(|line_text| line_text.len() + 1)
+        .sum::<usize>()
+        .min(text.len());
+    let line_text = text[line_start..]
+        .split('\n')
+        .next()
+        .unwrap_or("")
+        .trim_end_matches('\r');
+    line_start
+        + line_text
+            .char_indices()
+            .nth(column)
+            .map(|(index, _)| index)
+            .unwrap_or(line_text.len())
+}
+
+/// 游標所在的識別字，作為更名的預設值。 / Identifier under the caret, used to prefill renames.
+fn word_at_char_index(text: &str, index: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let index = index.min(chars.len());
+    let start = chars[..index]
+        .iter()
+        .rposition(|c| !is_word(c))
+        .map_or(0, |position| position + 1);
+    let end = chars[index..]
+        .iter()
+        .position(|c| !is_word(c))
+        .map_or(chars.len(), |position| index + position);
+    chars[start..end].iter().collect()
+}
+
 fn diagnostic_color(severity: DiagnosticSeverity) -> Color32 {
     match severity {
         DiagnosticSeverity::Error => Color32::from_rgb(239, 68, 68),
@@ -8568,6 +10578,38 @@ fn language_id_from_path(path: &str) -> &'static str {
     }
 }
 
+/// 載入工作區 `grammars` 資料夾中的 TextMate 與 Sublime 語法；回傳有規則被略過的語法名稱與略過數。 / Loads the TextMate and Sublime grammars in the workspace `grammars` folder; returns the name and skipped-rule count of each grammar that dropped rules.
+fn load_user_grammars(registry: &mut LanguageRegistry, dir: &Path) -> Vec<(String, usize)> {
+    let mut skips = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return skips;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        match Grammar::from_path(&path) {
+            Ok(grammar) => {
+                let skipped = grammar.skipped_patterns();
+                if !skipped.is_empty() {
+                    log_warn(format!(
+                        "Grammar {}: skipped {} unsupported rules: {}",
+                        path.display(),
+                        skipped.len(),
+                        skipped.join(", ")
+                    ));
+                    skips.push((grammar.name.clone(), skipped.len()));
+                }
+                registry.register_grammar(grammar);
+            }
+            Err(GrammarError::UnsupportedFormat(_)) => {}
+            Err(err) => log_warn(format!("Failed to load grammar {}: {err}", path.display())),
+        }
+    }
+    skips
+}
+
 fn language_id_from_hint(hint: &str) -> Option<&'static str> {
     match hint.to_lowercase().replace(' ', "").as_str() {
         "rust" => Some("rust"),
@@ -9131,13 +11173,17 @@ mod tests {
     use egui::text::CCursor;
     use egui::text_edit::CCursorRange;
     use serde_json::json;
-    use tempfile::{tempdir, tempdir_in};
+    use rustnotepad_search::IncrementalScan;
+    use tempfile::{tempdir, tempdir_in, TempDir};
 
-    fn make_test_app() -> RustNotePadApp {
+    /// 以暫存工作區建立應用程式，狀態檔不會寫入原始碼樹。 / Builds an app over a temporary workspace so its state files stay out of the checkout.
+    fn make_test_app() -> (TempDir, RustNotePadApp) {
         let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
         let workspace_root = manifest_dir.parent().expect("workspace root");
         std::env::set_current_dir(workspace_root).expect("set cwd to workspace root");
-        RustNotePadApp::default()
+        let workspace = tempdir().expect("temp workspace");
+        let app = RustNotePadApp::new_with_workspace_root(workspace.path().to_path_buf());
+        (workspace, app)
     }
 
     #[test]
@@ -9223,6 +11269,35 @@ mod tests {
         assert_eq!(app.text("menu.file"), "ملف");
     }
 
+    #[test]
+    fn workspace_grammars_are_loaded_and_picked_by_extension() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let workspace_root = manifest_dir.parent().expect("workspace root");
+        std::env::set_current_dir(workspace_root).expect("set cwd to workspace root");
+
+        let temp_workspace = tempdir().expect("temp workspace");
+        let grammar_dir = temp_workspace.path().join(".rustnotepad/grammars");
+        fs::create_dir_all(&grammar_dir).expect("create grammars dir");
+        fs::write(
+            grammar_dir.join("mini.tmLanguage.json"),
+            r#"{ "name": "Mini", "scopeName": "source.mini", "fileTypes": ["mini"],
+                 "patterns": [ { "match": "\\bif\\b", "name": "keyword.control.mini" } ] }"#,
+        )
+        .expect("write grammar");
+        fs::write(grammar_dir.join("README.txt"), "not a grammar").expect("write readme");
+
+        let app = RustNotePadApp::new_with_workspace_root(temp_workspace.path().to_path_buf());
+        assert_eq!(app.language_id_for_path("demo.MINI"), "mini");
+        assert_eq!(app.language_display_name("mini"), "Mini");
+        assert_eq!(app.language_id_for_path("demo.rs"), "rust");
+        assert_eq!(app.language_id_for_path("notes.txt"), "plaintext");
+        let tokens = app
+            .highlight_registry
+            .highlight("mini", "if x")
+            .expect("grammar highlights");
+        assert_eq!(tokens.len(), 1);
+    }
+
     #[test]
     fn print_preview_generates_visible_pages_in_gui_mode() {
         let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -9236,6 +11311,7 @@ mod tests {
             .map(|idx| format!("fn example_{idx}() {{ println!(\"preview\"); }}\n"))
             .collect::<String>();
         app.editor_preview = sample;
+        app.editor_text_replaced();
 
         app.open_print_preview();
 
@@ -9297,9 +11373,10 @@ mod tests {
         // Reset the editor buffer to isolate this scenario.
         // 重設編輯器內容以確保測試過程獨立。
         app.editor_preview.clear();
+        app.editor_text_replaced();
         app.document_dirty = false;
         app.document_index.remove_document(&app.current_document_id);
-        app.status.refresh_cursor(&app.editor_preview);
+        app.status.refresh_cursor(&app.editor_text);
         app.document_index
             .update_document(&app.current_document_id, "");
 
@@ -9666,6 +11743,7 @@ mod tests {
 
         let mut app = RustNotePadApp::default();
         app.editor_preview = "hello world".to_string();
+        app.editor_text_replaced();
         app.editor_undo_stack.clear();
         app.editor_redo_stack.clear();
         app.editor_clipboard.clear();
@@ -9714,7 +11792,7 @@ mod tests {
 
     #[test]
     fn view_menu_toggle_affects_document_map() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         assert!(app.document_map_visible);
         app.handle_view_command("menu.view.document_map", None);
         assert!(!app.document_map_visible);
@@ -9724,22 +11802,432 @@ mod tests {
 
     #[test]
     fn encoding_selection_updates_status() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         app.handle_encoding_command("menu.encoding.encode_ucs2_be");
         assert_eq!(app.status.encoding, "UCS-2 BE");
     }
 
+    #[test]
+    fn binary_files_fall_back_to_hex_view() {
+        let (_workspace, mut app) = make_test_app();
+        let dir = tempdir().expect("tempdir");
+        let blob = dir.path().join("firmware.bin");
+        fs::write(&blob, [0x7F, 0x45, 0x4C, 0x46, 0xFF, 0xFE, 0x00]).expect("write blob");
+        app.load_document(blob.to_str().unwrap(), None);
+        let hex = app.hex_view.as_ref().expect("hex view active");
+        assert_eq!(hex.document.len(), 7);
+        assert!(app.editor_preview.is_empty());
+
+        let text = dir.path().join("notes.txt");
+        fs::write(&text, "plain").expect("write text");
+        app.load_document(text.to_str().unwrap(), None);
+        assert!(app.hex_view.is_none());
+        assert_eq!(app.editor_preview, "plain");
+    }
+
+    #[test]
+    fn files_above_the_threshold_open_read_only_in_large_file_mode() {
+        let (_workspace, mut app) = make_test_app();
+        app.preferences_store
+            .preferences_mut()
+            .editor
+            .large_file_threshold_mb = 1;
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("huge.log");
+        fs::write(&path, "0123456789abcdef\n".repeat(80_000)).expect("write large file");
+
+        app.load_document(path.to_str().unwrap(), None);
+        let large = app.large_file.as_ref().expect("large-file mode");
+        assert_eq!(large.line_count(), 80_001);
+        assert_eq!(app.editor_preview.lines().count(), LARGE_FILE_PREVIEW_LINES);
+        assert!(app.hex_view.is_none());
+
+        let small = dir.path().join("small.txt");
+        fs::write(&small, "tiny\n").expect("write small file");
+        app.load_document(small.to_str().unwrap(), None);
+        assert!(app.large_file.is_none());
+        assert_eq!(app.editor_preview, "tiny\n");
+    }
+
+    #[test]
+    fn mixed_line_endings_are_flagged_in_the_status_bar() {
+        let (_workspace, mut app) = make_test_app();
+        let dir = tempdir().expect("tempdir");
+        let mixed = dir.path().join("mixed.txt");
+        fs::write(&mixed, "a\r\nb\nc\r\n").expect("write mixed file");
+        app.load_document(mixed.to_str().unwrap(), None);
+        assert_eq!(app.status.eol, "Windows (CR LF)");
+        let warning = app.status.eol_warning.clone().expect("mixed EOL warning");
+        assert!(warning.contains("CRLF 2"));
+        assert_eq!(app.notification_log.front(), Some(&warning));
+
+        let clean = dir.path().join("clean.txt");
+        fs::write(&clean, "a\nb\n").expect("write clean file");
+        app.load_document(clean.to_str().unwrap(), None);
+        assert!(app.status.eol_warning.is_none());
+    }
+
+    #[test]
+    fn legacy_encoded_text_opens_decoded_and_saves_back() {
+        let (_workspace, mut app) = make_test_app();
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("menu.txt");
+        let original = b"caf\xe9 cr\xe8me br\xfbl\xe9e\r\n".to_vec();
+        fs::write(&path, &original).expect("write cp1252 text");
+
+        app.load_document(path.to_str().unwrap(), None);
+        assert!(app.hex_view.is_none());
+        assert_eq!(app.editor_preview, "café crème brûlée\n");
+        assert_eq!(app.status.encoding, "windows-1252");
+
+        app.write_document_to(&path).expect("save");
+        assert_eq!(fs::read(&path).expect("read back"), original);
+    }
+
+    #[test]
+    fn diagnostics_underline_and_jump_to_their_range() {
+        use rustnotepad_lsp_client::{LspRange, TextPosition};
+
+        let text = "let 值 = y;\nfoo()\n";
+        let at = |start: (usize, usize), end: (usize, usize), bytes: std::ops::Range<usize>| {
+            LspRange {
+                start: TextPosition::new(start.0, start.1),
+                end: TextPosition::new(end.0, end.1),
+                bytes: Some(bytes),
+            }
+        };
+        let diagnostics = vec![
+            Diagnostic::new("unused", DiagnosticSeverity::Warning).with_range(at((0, 4), (0, 5), 4..7)),
+            Diagnostic::new("overlap", DiagnosticSeverity::Hint).with_range(at((0, 4), (0, 6), 4..8)),
+            Diagnostic::new("missing ;", DiagnosticSeverity::Error).with_range(at((1, 5), (1, 5), 18..18)),
+        ];
+        let underlines = diagnostic_underlines(text, &diagnostics);
+        assert_eq!(
+            underlines,
+            vec![
+                (4..7, diagnostic_color(DiagnosticSeverity::Warning)),
+                (17..18, diagnostic_color(DiagnosticSeverity::Error)),
+            ]
+        );
+
+        let (_workspace, mut app) = make_test_app();
+        app.editor_preview = text.to_string();
+        app.editor_text_replaced();
+        app.jump_to_diagnostic(&diagnostics[0]);
+        assert_eq!(app.editor_selection_char_range(), Some((4, 5)));
+        let mut stale = diagnostics[2].clone();
+        stale.range.as_mut().unwrap().bytes = Some(40..41);
+        app.jump_to_diagnostic(&stale);
+        assert_eq!(app.editor_selection_char_range(), Some((16, 16)));
+    }
+
+    #[test]
+    fn reference_results_open_their_file_and_keep_the_list() {
+        let dir = tempdir().expect("temp dir");
+        let other = dir.path().join("other.rs");
+        fs::write(&other, "fn helper() {}\nhelper();\n").expect("write other file");
+        let hit = SearchMatch {
+            start: 15,
+            end: 21,
+            line: 2,
+            column: 1,
+            matched: "helper".into(),
+            line_text: "helper();".into(),
+            is_marked: false,
+        };
+
+        let (_workspace, mut app) = make_test_app();
+        app.editor_preview = "let helper_name = 1;\n".into();
+        app.editor_text_replaced();
+        assert_eq!(word_at_char_index(&app.editor_preview, 7), "helper_name");
+        assert_eq!(word_at_char_index(&app.editor_preview, 3), "let");
+
+        app.search_report = Some(SearchReport::new(vec![FileSearchResult::new(
+            Some(other.clone()),
+            vec![hit.clone()],
+        )]));
+        app.jump_to_search_result(Some(&other), &hit);
+        assert_eq!(app.current_document_path.as_deref(), Some(other.as_path()));
+        assert_eq!(app.editor_selection_char_range(), Some((15, 21)));
+        assert_eq!(app.search_report.as_ref().map(|report| report.total_matches), Some(1));
+    }
+
+    #[test]
+    fn find_in_files_streams_hits_into_the_results_panel() {
+        let dir = tempdir().expect("temp dir");
+        fs::create_dir_all(dir.path().join("src")).expect("create src");
+        fs::write(dir.path().join("src/lib.rs"), "// needle\nneedle();\n").expect("write lib");
+        fs::write(dir.path().join("notes.txt"), "needle\n").expect("write notes");
+
+        let (_workspace, mut app) = make_test_app();
+        app.find_query = "needle".into();
+        app.find_in_files_filters = "*.rs".into();
+        app.find_in_files_directory = dir.path().to_string_lossy().into_owned();
+        app.start_find_in_files();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while app.poll_find_in_files() {
+            assert!(std::time::Instant::now() < deadline, "find in files timed out");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let report = app.search_report.as_ref().expect("report");
+        assert_eq!(report.total_matches, 2);
+        assert_eq!(report.results.len(), 1);
+        assert!(report.results[0].path.as_ref().unwrap().ends_with("lib.rs"));
+        assert!(app.find_in_files_task.is_none());
+        assert!(!app.find_status.as_ref().unwrap().is_error);
+    }
+
+    #[test]
+    fn multi_caret_commands_edit_at_every_caret() {
+        let (_workspace, mut app) = make_test_app();
+        app.apply_editor_text("foo bar\nfoo baz\nfoo\n".into());
+        app.update_editor_selection(Some(CCursorRange::one(CCursor::new(1))));
+
+        app.handle_edit_command("menu.edit.add_next_occurrence");
+        assert_eq!(app.editor_selection_char_range(), Some((0, 3)));
+        assert!(app.editor_extra_carets.is_empty());
+        app.handle_edit_command("menu.edit.add_next_occurrence");
+        assert_eq!(app.editor_selection_char_range(), Some((8, 11)));
+        assert_eq!(app.extra_caret_char_ranges(), [(0, 3)]);
+        app.handle_edit_command("menu.edit.select_all_occurrences");
+        assert_eq!(app.extra_caret_char_ranges().len(), 2);
+
+        app.run_multi_caret(|buffer| buffer.insert_text("qux"));
+        assert_eq!(app.editor_preview, "qux bar\nqux baz\nqux\n");
+        assert_eq!(app.extra_caret_char_ranges(), [(3, 3), (11, 11)]);
+        assert_eq!(app.editor_selection_char_range(), Some((19, 19)));
+        app.run_multi_caret(|buffer| motion::move_carets(buffer, Motion::LineStart, true));
+        app.run_multi_caret(EditorBuffer::delete_backward);
+        assert_eq!(app.editor_preview, " bar\n baz\n\n");
+
+        app.perform_undo();
+        assert_eq!(app.editor_preview, "qux bar\nqux baz\nqux\n");
+        assert!(app.editor_extra_carets.is_empty());
+    }
+
+    #[test]
+    fn vertical_caret_commands_and_split_selection_add_carets() {
+        let (_workspace, mut app) = make_test_app();
+        app.apply_editor_text("ab\ncd\nef".into());
+        app.update_editor_selection(Some(CCursorRange::one(CCursor::new(4))));
+        app.handle_edit_command("menu.edit.add_caret_above");
+        app.handle_edit_command("menu.edit.add_caret_below");
+        assert_eq!(app.extra_caret_char_ranges(), [(4, 4), (1, 1)]);
+        assert_eq!(app.editor_selection_char_range(), Some((7, 7)));
+
+        app.select_all_in_editor();
+        app.editor_extra_carets.clear();
+        app.handle_edit_command("menu.edit.split_selection_into_lines");
+        assert_eq!(app.extra_caret_char_ranges(), [(0, 2), (3, 5)]);
+        assert_eq!(app.editor_selection_char_range(), Some((6, 8)));
+
+        assert_eq!(
+            multi_caret_key(egui::Key::ArrowLeft, egui::Modifiers::COMMAND),
+            Some(MultiCaretKey::Move(Motion::WordLeft))
+        );
+        assert_eq!(multi_caret_key(egui::Key::A, egui::Modifiers::NONE), None);
+    }
+
+    #[test]
+    fn skipped_grammar_rules_are_reported_in_notifications() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        std::env::set_current_dir(manifest_dir.parent().expect("workspace root"))
+            .expect("set cwd to workspace root");
+        let workspace = tempdir().expect("temp workspace");
+        let grammars = workspace.path().join(".rustnotepad").join("grammars");
+        fs::create_dir_all(&grammars).expect("create grammars dir");
+        fs::write(
+            grammars.join("mini.tmLanguage.json"),
+            r#"{ "name": "Mini", "scopeName": "source.mini", "patterns": [
+                { "match": "(?<=\\.)\\w+", "name": "variable.other.member.mini" },
+                { "begin": "<<(\\w+)", "end": "^\\1$", "name": "string.unquoted.heredoc.mini" }
+            ] }"#,
+        )
+        .expect("write grammar");
+        let app = RustNotePadApp::new_with_workspace_root(workspace.path().to_path_buf());
+        let expected = app.localized_owned(
+            "Grammar Mini: 2 unsupported rules skipped".to_string(),
+            "語法 Mini：略過 2 條不支援的規則".to_string(),
+        );
+        assert_eq!(app.notification_log.front(), Some(&expected));
+    }
+
+    #[test]
+    fn caret_keys_use_the_core_motions() {
+        let (_workspace, mut app) = make_test_app();
+        app.apply_editor_text("  héllo\nab\n  wörld!".into());
+        app.update_editor_selection(Some(CCursorRange::one(CCursor::new(6))));
+        app.move_editor_caret(Motion::LineDown, false);
+        assert_eq!(app.editor_selection_char_range(), Some((10, 10)));
+        app.move_editor_caret(Motion::LineDown, false);
+        assert_eq!(app.editor_selection_char_range(), Some((17, 17)));
+        assert_eq!((app.status.line, app.status.column), (3, 7));
+
+        app.move_editor_caret(Motion::LineStart, false);
+        assert_eq!(app.editor_selection_char_range(), Some((13, 13)));
+        app.move_editor_caret(Motion::LineEnd, true);
+        assert_eq!(app.editor_selection_char_range(), Some((13, 19)));
+        assert_eq!((app.status.line, app.status.column), (3, 9));
+    }
+
+    #[test]
+    fn find_as_you_type_narrows_and_starts_over_after_edits() {
+        let (_workspace, mut app) = make_test_app();
+        app.apply_editor_text("alpha\nbeta\nalphabet\n".into());
+        app.find_query = "a".into();
+        app.find_as_you_type();
+        assert_eq!(app.find_incremental.matches().len(), 5);
+        assert_eq!(app.find_incremental.last_scan(), IncrementalScan::Full);
+
+        app.find_query = "alp".into();
+        app.find_as_you_type();
+        assert_eq!(app.find_incremental.matches().len(), 2);
+        assert_eq!(app.find_incremental.last_scan(), IncrementalScan::Narrowed { lines: 3 });
+        assert_eq!(app.editor_selection_char_range(), Some((0, 3)));
+
+        app.apply_editor_text("beta\nalphabet\n".into());
+        app.find_as_you_type();
+        assert_eq!(app.find_incremental.matches().len(), 1);
+        assert_eq!(app.find_incremental.last_scan(), IncrementalScan::Full);
+        assert_eq!(app.editor_selection_char_range(), Some((5, 8)));
+        assert!(!app.find_status.as_ref().unwrap().is_error);
+
+        app.find_query.clear();
+        app.find_as_you_type();
+        assert!(app.find_incremental.matches().is_empty());
+        assert!(app.find_status.is_none());
+    }
+
+    #[test]
+    fn find_dialog_records_history_and_recalls_presets() {
+        let dir = tempdir().expect("temp dir");
+        let (_workspace, mut app) = make_test_app();
+        app.search_history_store = SearchHistoryStore::new(dir.path().join("history.json"));
+        app.search_history = SearchHistory::default();
+        app.find_query = "needle".into();
+        app.find_mode = FindMode::Regex;
+        app.find_match_case = true;
+        app.find_in_files_filters = "*.rs !target".into();
+        app.perform_count_matches();
+        app.perform_count_matches();
+
+        let stored = app.search_history_store.load().expect("load history");
+        assert_eq!(stored.recent.len(), 1);
+        assert_eq!(stored.recent[0].pattern, "needle");
+        assert_eq!(stored.recent[0].mode, SavedSearchMode::Regex);
+        assert_eq!(stored.recent[0].exclude, vec!["target".to_string()]);
+
+        app.find_replace_text = "pin".into();
+        app.find_preset_name = "needles".into();
+        app.save_search_preset();
+        app.find_query = "other".into();
+        app.find_mode = FindMode::Normal;
+        app.find_replace_text.clear();
+        app.find_in_files_filters.clear();
+        app.load_search_preset("needles");
+        assert_eq!(app.find_query, "needle");
+        assert_eq!(app.find_replace_text, "pin");
+        assert_eq!(app.find_mode, FindMode::Regex);
+        assert_eq!(app.find_in_files_filters, "*.rs !target");
+
+        let stored = app.search_history_store.load().expect("reload history");
+        assert_eq!(
+            stored.preset("needles").and_then(|search| search.replacement.as_deref()),
+            Some("pin")
+        );
+        app.delete_search_preset();
+        assert!(app.search_history.presets.is_empty());
+    }
+
+    #[test]
+    fn editor_highlighting_covers_the_viewport_and_follows_edits() {
+        let (_workspace, mut app) = make_test_app();
+        let line = "fn main() {}\n";
+        app.current_language_id = "rust".into();
+        app.editor_preview = line.repeat(200);
+        app.editor_text_replaced();
+        let colors = app.editor_syntax_colors(0..2);
+        assert!(!colors.is_empty());
+        assert!(colors.iter().all(|(range, _)| range.end <= 2 * line.len()));
+        let keyword = colors[0].1;
+
+        let edited = format!("/*{}", app.editor_preview);
+        app.apply_editor_text(edited);
+        let colors = app.editor_syntax_colors(0..2);
+        assert!(!colors.is_empty());
+        assert!(colors.iter().all(|(_, color)| *color != keyword));
+        let highlighter = app.editor_highlighter.as_ref().expect("highlighter kept across edits");
+        assert_eq!(highlighter.last_relexed(), 2);
+
+        app.current_language_id = "plaintext".into();
+        assert!(app.editor_syntax_colors(0..2).is_empty());
+    }
+
+    #[test]
+    fn mark_styles_highlight_navigate_and_persist() {
+        let (_workspace, mut app) = make_test_app();
+        app.editor_preview = "foo one\nbar\nfoo two\nbaz\n".into();
+        app.editor_text_replaced();
+        app.find_query = "foo".into();
+        app.handle_search_command("menu.search.mark_style_2");
+        assert_eq!(app.mark_styles.ranges(MarkStyle::Second), [0..3, 12..15]);
+        app.find_query.clear();
+        let selection = CCursorRange::two(CCursor::new(8), CCursor::new(11));
+        app.update_editor_selection(Some(selection));
+        app.mark_with_style(MarkStyle::Fourth);
+        assert_eq!(app.mark_styles.ranges(MarkStyle::Fourth).first(), Some(&(8..11)));
+
+        let segments = editor_text_segments(&app.editor_preview, &[], &app.mark_styles, &[]);
+        assert_eq!(segments[0], (0..3, None, Some(mark_style_color(MarkStyle::Second)), None));
+        assert_eq!(segments[1], (3..8, None, None, None));
+
+        app.active_mark_style = MarkStyle::Second;
+        app.update_editor_selection(None);
+        app.jump_to_mark(SearchDirection::Forward);
+        assert_eq!(app.editor_selection_char_range(), Some((0, 3)));
+        app.jump_to_mark(SearchDirection::Forward);
+        assert_eq!(app.editor_selection_char_range(), Some((12, 15)));
+        app.jump_to_mark(SearchDirection::Backward);
+        assert_eq!(app.editor_selection_char_range(), Some((0, 3)));
+
+        app.copy_marked_text();
+        assert_eq!(app.editor_clipboard, "foo\nfoo");
+        let saved = app.session_marks();
+        assert_eq!(saved.len(), 3);
+
+        app.delete_marked_lines();
+        assert_eq!(app.editor_preview, "bar\nbaz\n");
+        assert!(app.mark_styles.ranges(MarkStyle::Second).is_empty());
+        assert_eq!(app.mark_styles.ranges(MarkStyle::Fourth).first(), Some(&(0..3)));
+
+        app.apply_editor_text("> bar\nbaz\n".into());
+        assert_eq!(app.mark_styles.ranges(MarkStyle::Fourth).first(), Some(&(2..5)));
+
+        app.editor_preview = "foo one\nbar\nfoo two\nbaz\n".into();
+
+        app.editor_text_replaced();
+        let tab = SessionTab {
+            marks: saved,
+            ..SessionTab::default()
+        };
+        app.apply_session_tab_state(&tab);
+        assert_eq!(app.mark_styles.ranges(MarkStyle::Second), [0..3, 12..15]);
+        assert_eq!(app.mark_styles.ranges(MarkStyle::Fourth).first(), Some(&(8..11)));
+    }
+
     #[test]
     fn language_menu_switches_modes() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         app.handle_language_command("menu.language.json");
         assert_eq!(app.current_language_id, "json");
     }
 
     #[test]
     fn tools_menu_hashes_into_clipboard() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         app.editor_preview = "abc".into();
+        app.editor_text_replaced();
         app.handle_tools_command("menu.tools.md5");
         assert!(
             !app.editor_clipboard.is_empty(),
@@ -9749,7 +12237,7 @@ mod tests {
 
     #[test]
     fn plugins_menu_opens_admin_settings() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         app.handle_plugins_command("menu.plugins.admin");
         assert!(app.show_settings_window);
         assert!(matches!(app.active_settings_page, SettingsPage::Plugins));
@@ -9757,7 +12245,7 @@ mod tests {
 
     #[test]
     fn window_menu_duplicate_adds_tab() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         let before = app
             .layout
             .panes
@@ -9778,7 +12266,7 @@ mod tests {
 
     #[test]
     fn help_menu_flags_about_window() {
-        let mut app = make_test_app();
+        let (_workspace, mut app) = make_test_app();
         app.handle_help_command("menu.help.about");
         assert!(app.show_help_about_window);
     }
@@ -1,6 +1,9 @@
 use std::fs;
 
-use rustnotepad_search::{search_in_files, FileSearchInput, SearchOptions};
+use rustnotepad_search::{
+    search_in_files, search_in_files_with_index, FileSearchInput, IndexQuery, SearchOptions,
+    TrigramIndex,
+};
 use tempfile::tempdir;
 
 #[test]
@@ -26,3 +29,27 @@ fn large_workspace_search_scales() {
     assert_eq!(summary.files_with_matches, 10);
     assert_eq!(summary.total_matches, 10);
 }
+
+#[test]
+fn large_workspace_index_prunes_candidates() {
+    let mut index = TrigramIndex::new();
+    let mut inputs = Vec::new();
+
+    for idx in 0..500usize {
+        let path = format!("file_{idx:04}.txt");
+        let contents = if idx % 50 == 0 {
+            "needle here\nother text\n"
+        } else {
+            "other text\nstill other\n"
+        };
+        index.insert(&path, idx as u64, contents);
+        inputs.push(FileSearchInput::new(path, contents));
+    }
+
+    let options = SearchOptions::new("needle");
+    let query = IndexQuery::new(&options).expect("index query");
+    assert_eq!(index.candidates(&query).map(|paths| paths.len()), Some(10));
+
+    let report = search_in_files_with_index(inputs, &options, &index).expect("search report");
+    assert_eq!(report.summary().files_with_matches, 10);
+}